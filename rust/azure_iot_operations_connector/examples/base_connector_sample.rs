@@ -15,7 +15,8 @@ use std::{collections::HashMap, time::Duration};
 use azure_iot_operations_connector::{
     AdrConfigError, Data, DataOperationKind,
     base_connector::{
-        self, BaseConnector,
+        self, BaseConnector, SessionMonitor,
+        handler_supervisor::spawn_handler,
         managed_azure_device_registry::{
             AssetClient, AssetComponentClient, ClientNotification, DataOperationClient,
             DataOperationNotification, DeviceEndpointClient,
@@ -104,12 +105,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create a discovery client
     let adr_discovery_client = base_connector.discovery_client();
 
+    // Create a session monitor to pause sampling while disconnected from the MQTT broker
+    let session_monitor = base_connector.create_session_monitor();
+
     // Run the Session and the Azure Device Registry operations concurrently
     let res = tokio::select! {
 
         (r1, r2) = async {
             tokio::join!(
-                run_program(device_creation_observation),
+                run_program(device_creation_observation, session_monitor),
                 run_discovery(adr_discovery_client),
             )
         } => {
@@ -140,7 +144,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 // This function runs in a loop, waiting for device creation notifications.
-async fn run_program(mut device_creation_observation: DeviceEndpointClientCreationObservation) {
+async fn run_program(
+    mut device_creation_observation: DeviceEndpointClientCreationObservation,
+    session_monitor: SessionMonitor,
+) {
     // Wait for a device creation notification
     loop {
         let device_endpoint_client = device_creation_observation.recv_notification().await;
@@ -149,12 +156,25 @@ async fn run_program(mut device_creation_observation: DeviceEndpointClientCreati
 
         // Start handling the assets for this device endpoint
         // if we didn't accept the inbound endpoint, then we still want to run this to wait for updates
-        tokio::task::spawn(run_device(log_identifier, device_endpoint_client));
+        // If the handler panics, the device endpoint is reported as failed
+        spawn_handler(
+            log_identifier.clone(),
+            device_endpoint_client.get_status_reporter(),
+            run_device(
+                log_identifier,
+                device_endpoint_client,
+                session_monitor.clone(),
+            ),
+        );
     }
 }
 
 // This function runs in a loop, waiting for asset creation notifications.
-async fn run_device(log_identifier: String, mut device_endpoint_client: DeviceEndpointClient) {
+async fn run_device(
+    log_identifier: String,
+    mut device_endpoint_client: DeviceEndpointClient,
+    session_monitor: SessionMonitor,
+) {
     // Get the status reporter for this device endpoint - create once and reuse
     let mut device_endpoint_reporter = device_endpoint_client.get_status_reporter();
 
@@ -189,6 +209,13 @@ async fn run_device(log_identifier: String, mut device_endpoint_client: DeviceEn
                 log::warn!("{log_identifier} Device Endpoint deleted");
                 break;
             }
+            ClientNotification::OwnershipLost => {
+                // Only received if `exclusive_device_ownership` is enabled on the base connector
+                log::warn!("{log_identifier} Device Endpoint ownership lost");
+            }
+            ClientNotification::OwnershipRegained => {
+                log::info!("{log_identifier} Device Endpoint ownership regained");
+            }
             ClientNotification::Updated => {
                 // Pause reporting and refresh to the new version before processing the update
                 device_endpoint_reporter.pause_and_refresh_health_version();
@@ -225,14 +252,22 @@ async fn run_device(log_identifier: String, mut device_endpoint_client: DeviceEn
 
                 // Start handling the datasets for this asset
                 // if we didn't accept the asset, then we still want to run this to wait for updates
-                tokio::task::spawn(run_asset(asset_log_identifier, asset_client));
+                spawn_handler(
+                    asset_log_identifier.clone(),
+                    asset_client.get_status_reporter(),
+                    run_asset(asset_log_identifier, asset_client, session_monitor.clone()),
+                );
             }
         }
     }
 }
 
 // This function runs in a loop, waiting for dataset creation notifications.
-async fn run_asset(asset_log_identifier: String, mut asset_client: AssetClient) {
+async fn run_asset(
+    asset_log_identifier: String,
+    mut asset_client: AssetClient,
+    session_monitor: SessionMonitor,
+) {
     // Get the status reporter for this asset - create once and reuse
     let asset_reporter = asset_client.get_status_reporter();
 
@@ -279,11 +314,16 @@ async fn run_asset(asset_log_identifier: String, mut asset_client: AssetClient)
                     "{data_operation_log_identifier} Data Operation Created: {data_operation_client:?}"
                 );
                 if let DataOperationKind::Dataset = data_operation_client.kind() {
-                    tokio::task::spawn(run_dataset(
-                        data_operation_log_identifier,
-                        data_operation_client,
-                        initial_status,
-                    ));
+                    spawn_handler(
+                        data_operation_log_identifier.clone(),
+                        data_operation_client.get_status_reporter(),
+                        run_dataset(
+                            data_operation_log_identifier,
+                            data_operation_client,
+                            initial_status,
+                            session_monitor.clone(),
+                        ),
+                    );
                 } else {
                     tokio::task::spawn(handle_unsupported_component(
                         data_operation_log_identifier,
@@ -303,13 +343,20 @@ async fn run_asset(asset_log_identifier: String, mut asset_client: AssetClient)
                         .management_action_name
                 );
                 log::info!("{management_action_log_identifier} Management Action Created");
-                tokio::task::spawn(run_management_action(
-                    management_action_log_identifier,
-                    management_action_client,
-                    initial_executor,
-                ));
+                spawn_handler(
+                    management_action_log_identifier.clone(),
+                    management_action_client.get_status_reporter(),
+                    run_management_action(
+                        management_action_log_identifier,
+                        management_action_client,
+                        initial_executor,
+                    ),
+                );
             }
-            ClientNotification::Created(_) => {}
+            ClientNotification::Created(_)
+            // Ownership notifications are only received on device endpoint clients
+            | ClientNotification::OwnershipLost
+            | ClientNotification::OwnershipRegained => {}
         }
     }
 }
@@ -320,6 +367,7 @@ async fn run_dataset(
     log_identifier: String,
     mut data_operation_client: DataOperationClient,
     initial_status: Result<(), AdrConfigError>,
+    session_monitor: SessionMonitor,
 ) {
     // Get the status reporter for this data operation - create once and reuse
     let mut data_operation_reporter = data_operation_client.get_status_reporter();
@@ -389,6 +437,11 @@ async fn run_dataset(
                 }
             },
             _ = timer.tick(), if dataset_valid => {
+                // No point sampling if the data can't be forwarded to the broker
+                if !session_monitor.is_connected() {
+                    log::info!("{log_identifier} Not connected to MQTT broker, skipping sample");
+                    continue;
+                }
                 let sample_data = mock_received_data(count);
 
                 let current_message_schema =
@@ -433,7 +486,7 @@ async fn run_dataset(
                 }
 
                 match data_operation_client.forward_data(sample_data).await {
-                    Ok(()) => {
+                    Ok(_) => {
                         log::info!(
                             "{log_identifier} data {count} forwarded"
                         );
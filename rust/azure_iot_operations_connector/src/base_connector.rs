@@ -5,6 +5,8 @@
 
 use std::{sync::Arc, time::Duration};
 
+/// Monitor for the connectivity of the [`BaseConnector`]'s MQTT Session
+pub use azure_iot_operations_mqtt::session::SessionMonitor;
 use azure_iot_operations_mqtt::session::{
     Session, SessionError, SessionManagedClient, SessionOptionsBuilder,
     reconnect_policy::ExponentialBackoffWithJitter, reconnect_policy::ReconnectPolicy,
@@ -22,8 +24,17 @@ use tokio_util::sync::{CancellationToken, DropGuard};
 
 use crate::{deployment_artifacts::connector::ConnectorArtifacts, readiness_probe::ReadinessProbe};
 
+pub mod activation;
 pub mod adr_discovery;
+mod device_ownership;
+pub mod handler_supervisor;
+pub mod lifecycle;
 pub mod managed_azure_device_registry;
+pub mod reconciliation;
+pub mod secret_reference;
+pub mod status;
+#[cfg(feature = "test-utils")]
+pub mod test_harness;
 
 /// Error describing why a [`BaseConnector`] run ended
 #[derive(Debug, Error)]
@@ -36,6 +47,8 @@ pub(crate) enum ConnectorErrorRepr {
     Session(#[from] SessionError),
     #[error("Unrecoverable error: {0}")]
     Unrecoverable(String),
+    #[error("Start hook failed: {0}")]
+    StartHook(String),
 }
 
 /// Context required to run the base connector operations
@@ -56,12 +69,28 @@ pub(crate) struct ConnectorContext {
     pub(crate) state_store_timeout: Duration,
     /// Health status reporting interval
     pub(crate) health_report_interval: ReportInterval,
+    /// Duration that a deleted Data Operation can still forward data
+    pub(crate) deletion_grace_period: Duration,
+    /// Topic namespace for forwarded data of Assets and Datasets that don't specify their own
+    pub(crate) default_topic_namespace: Option<String>,
+    /// Duration of Device Endpoint ownership leases, if exclusive device ownership is enabled
+    device_ownership_lease_duration: Option<Duration>,
+    /// Used to renew Device Endpoint ownership leases only while connected
+    session_monitor: SessionMonitor,
     /// Clients used to perform connector operations
     azure_device_registry_client: azure_device_registry::Client,
     pub(crate) state_store_client: Arc<state_store::Client>,
     schema_registry_client: schema_registry::Client,
     /// Channel for signaling that the connector requires a restart
     pub(crate) connector_restart_tx: mpsc::Sender<String>,
+    /// Cancelled once [`BaseConnector::run`] has been called. Creation notifications are held
+    /// until then so that none are delivered before all observations have been registered.
+    pub(crate) run_started: CancellationToken,
+    /// Records and reconciles the artifacts of Datasets, if reconciliation is enabled
+    pub(crate) reconciler:
+        Option<Arc<reconciliation::Reconciler<reconciliation::ConnectorBackend>>>,
+    /// Whether the connector is active, or passive as a warm standby
+    pub(crate) activation_gate: activation::ActivationGate,
 }
 
 #[allow(clippy::missing_fields_in_debug)]
@@ -75,13 +104,21 @@ impl std::fmt::Debug for ConnectorContext {
             )
             .field("schema_registry_timeout", &self.schema_registry_timeout)
             .field("state_store_timeout", &self.state_store_timeout)
+            .field("deletion_grace_period", &self.deletion_grace_period)
+            .field("default_topic_namespace", &self.default_topic_namespace)
+            .field(
+                "device_ownership_lease_duration",
+                &self.device_ownership_lease_duration,
+            )
+            .field("reconciliation_enabled", &self.reconciler.is_some())
+            .field("active", &self.activation_gate.is_active())
             .finish()
     }
 }
 
 /// Options for configuring a new [`BaseConnector`]
 #[derive(Builder)]
-#[builder(pattern = "owned")] // Keep for when we have more options like reconnect policy
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))] // Keep for when we have more options like reconnect policy
 pub struct Options {
     // Timeouts for underlying service operations
     /// Timeout for Azure Device Registry operations
@@ -104,6 +141,36 @@ pub struct Options {
     #[builder(default = "Duration::from_secs(5)")]
     filemount_debounce_duration: Duration,
 
+    /// Duration that a [`DataOperationClient`](managed_azure_device_registry::DataOperationClient)
+    /// can still forward data after it has returned a `Deleted` notification. This allows buffered
+    /// samples and tombstones to be flushed before the destination is torn down.
+    #[builder(default = "Duration::from_secs(5)")]
+    deletion_grace_period: Duration,
+
+    /// Topic namespace prepended to the topics of MQTT destinations of forwarded data. Can be
+    /// overridden per Asset and per Dataset with the
+    /// [`TOPIC_NAMESPACE_KEY`](crate::destination_endpoint::TOPIC_NAMESPACE_KEY) attribute or configuration.
+    #[builder(default = "None", setter(into, strip_option))]
+    default_topic_namespace: Option<String>,
+
+    /// If `true`, each Device Endpoint is owned by at most one instance of the connector at a time.
+    ///
+    /// The connector campaigns for a lease named after each Device Endpoint before delivering its
+    /// [`DeviceEndpointClient`](managed_azure_device_registry::DeviceEndpointClient), and notifies
+    /// [`ClientNotification::OwnershipLost`](managed_azure_device_registry::ClientNotification::OwnershipLost)
+    /// and [`ClientNotification::OwnershipRegained`](managed_azure_device_registry::ClientNotification::OwnershipRegained)
+    /// if the lease is lost and regained afterwards. Use this when running multiple replicas of a
+    /// connector that must not sample the same Device Endpoint concurrently.
+    #[builder(default = "false")]
+    exclusive_device_ownership: bool,
+
+    /// Duration of the Device Endpoint ownership leases when `exclusive_device_ownership` is
+    /// enabled. Leases are renewed every third of this duration, and a Device Endpoint owned by
+    /// an instance that stops renewing its lease is picked up by another instance after at most
+    /// this duration.
+    #[builder(default = "Duration::from_secs(15)")]
+    device_ownership_lease_duration: Duration,
+
     /// Reconnect policy used by the MQTT Session.
     #[builder(default = "Box::new(ExponentialBackoffWithJitter::default())")]
     reconnect_policy: Box<dyn ReconnectPolicy>,
@@ -111,6 +178,40 @@ pub struct Options {
     /// Optional readiness probe implementation to use for the connector.
     #[builder(default = "None", setter(strip_option))]
     readiness_probe: Option<Box<dyn ReadinessProbe>>,
+
+    /// If specified, the artifacts left at the destinations of Datasets that have been deleted
+    /// are cleaned up at startup and periodically afterwards. See [`reconciliation`].
+    #[builder(default = "None", setter(strip_option))]
+    reconciliation: Option<reconciliation::ReconciliationOptions>,
+
+    /// If `true`, the connector starts passive as a warm standby: all clients are created as
+    /// usual, but data isn't forwarded and statuses aren't reported until the connector is
+    /// activated with its [`ActivationGate`](activation::ActivationGate). See [`activation`].
+    #[builder(default = "false")]
+    start_passive: bool,
+
+    /// Behavior of forwarding data while the connector is passive
+    #[builder(default)]
+    passive_forwarding: activation::PassiveForwarding,
+}
+
+impl OptionsBuilder {
+    /// Validate the [`Options`].
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if the default topic namespace is not a legal topic segment,
+    /// or if the device ownership lease duration is less than one second.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(default_topic_namespace)) = &self.default_topic_namespace {
+            crate::destination_endpoint::validate_topic_namespace(default_topic_namespace)?;
+        }
+        if let Some(device_ownership_lease_duration) = self.device_ownership_lease_duration
+            && device_ownership_lease_duration < Duration::from_secs(1)
+        {
+            return Err("device_ownership_lease_duration must be at least one second".to_string());
+        }
+        Ok(())
+    }
 }
 
 /// Base Connector for Azure IoT Operations
@@ -172,6 +273,22 @@ impl BaseConnector {
                 .map_err(|e| e.to_string())?,
         )
         .map_err(|e| e.to_string())?;
+        let state_store_client = Arc::new(state_store_client);
+
+        let reconciler = base_connector_options.reconciliation.map(|options| {
+            Arc::new(reconciliation::Reconciler::new(
+                reconciliation::ConnectorBackend {
+                    manifest_key: reconciliation::manifest_key(&connector_artifacts.connector_id),
+                    state_store_client: state_store_client.clone(),
+                    state_store_timeout: base_connector_options.state_store_timeout,
+                    azure_device_registry_client: azure_device_registry_client.clone(),
+                    azure_device_registry_timeout: base_connector_options
+                        .azure_device_registry_timeout,
+                    managed_client: session.create_managed_client(),
+                },
+                options,
+            ))
+        });
 
         Ok(Self {
             connector_context: Arc::new(ConnectorContext {
@@ -180,13 +297,25 @@ impl BaseConnector {
                 schema_registry_timeout: base_connector_options.schema_registry_timeout,
                 state_store_timeout: base_connector_options.state_store_timeout,
                 health_report_interval: base_connector_options.health_report_interval,
+                deletion_grace_period: base_connector_options.deletion_grace_period,
+                default_topic_namespace: base_connector_options.default_topic_namespace,
+                device_ownership_lease_duration: base_connector_options
+                    .exclusive_device_ownership
+                    .then_some(base_connector_options.device_ownership_lease_duration),
+                session_monitor: session.create_session_monitor(),
                 application_context,
                 managed_client: session.create_managed_client(),
                 connector_artifacts,
                 azure_device_registry_client,
                 schema_registry_client,
-                state_store_client: Arc::new(state_store_client),
+                state_store_client,
                 connector_restart_tx,
+                run_started: CancellationToken::new(),
+                reconciler,
+                activation_gate: activation::ActivationGate::new(
+                    !base_connector_options.start_passive,
+                    base_connector_options.passive_forwarding,
+                ),
             }),
             session,
             connector_restart_rx,
@@ -197,6 +326,12 @@ impl BaseConnector {
     /// Runs the MQTT Session that allows all Connector Operations to be performed.
     /// Returns if the session ends. If this happens, the base connector will need to be recreated
     ///
+    /// Notifications from observations created with [`BaseConnector::create_device_endpoint_client_create_observation`]
+    /// are not delivered until this method has been called. Since it consumes the [`BaseConnector`],
+    /// this guarantees that all observations are registered before any notification is delivered,
+    /// regardless of the order in which this method and the tasks receiving notifications start.
+    /// Notifications received before then are buffered.
+    ///
     /// # Errors
     /// Returns a [`ConnectorError`] if the session encounters a fatal error and ends, or if
     /// the connector encounters an error that requires a restart.
@@ -204,7 +339,38 @@ impl BaseConnector {
     /// # Panics
     /// Panics if the restart channel is closed, which should never happen since the [`BaseConnector`]
     /// itself holds the sender side of the channel.
-    pub async fn run(mut self) -> Result<(), ConnectorError> {
+    pub async fn run(self) -> Result<(), ConnectorError> {
+        self.run_with_hooks(lifecycle::NoLifecycleHooks).await
+    }
+
+    /// Runs the connector like [`BaseConnector::run`], invoking the [`LifecycleHooks`](lifecycle::LifecycleHooks)
+    /// with the [`ApplicationContext`] when the run starts and ends.
+    ///
+    /// [`LifecycleHooks::on_start`](lifecycle::LifecycleHooks::on_start) completes before any
+    /// notification is delivered, and [`LifecycleHooks::on_shutdown`](lifecycle::LifecycleHooks::on_shutdown)
+    /// is invoked with the result of the run before it is returned, including when the run ends
+    /// because of a fatal error.
+    ///
+    /// # Errors
+    /// Returns a [`ConnectorError`] if `on_start` fails, if the session encounters a fatal error
+    /// and ends, or if the connector encounters an error that requires a restart.
+    ///
+    /// # Panics
+    /// Panics if the restart channel is closed, which should never happen since the [`BaseConnector`]
+    /// itself holds the sender side of the channel.
+    pub async fn run_with_hooks(
+        self,
+        hooks: impl lifecycle::LifecycleHooks,
+    ) -> Result<(), ConnectorError> {
+        let application_context = self.connector_context.application_context.clone();
+        lifecycle::run_with_hooks(&hooks, &application_context, self.run_session()).await
+    }
+
+    /// Runs the MQTT Session and connector operations until the session ends or a restart is required
+    async fn run_session(mut self) -> Result<(), ConnectorError> {
+        // No more observations can be created, so notifications can start being delivered
+        self.connector_context.run_started.cancel();
+
         // When `run()` returns by any path, this guard fires and wakes the readiness monitor task
         // so it can mark the probe not-ready and exit cleanly.
         let _probe_shutdown_guard: Option<DropGuard> =
@@ -238,6 +404,18 @@ impl BaseConnector {
                 None
             };
 
+        // Stops the reconciliation sweeps when `run()` returns
+        let _reconciliation_guard: Option<DropGuard> =
+            self.connector_context.reconciler.clone().map(|reconciler| {
+                let cancellation_token = CancellationToken::new();
+                tokio::task::spawn(reconciliation::run_sweeps(
+                    reconciler,
+                    self.session.create_session_monitor(),
+                    cancellation_token.clone(),
+                ));
+                cancellation_token.drop_guard()
+            });
+
         tokio::select! {
             session_result = self.session.run() => {
                 session_result.map_err(|e| ConnectorError::from(ConnectorErrorRepr::from(e)))
@@ -250,6 +428,8 @@ impl BaseConnector {
 
     /// Creates a new [`DeviceEndpointClientCreationObservation`] to allow for Azure Device Registry operations
     ///
+    /// The observation doesn't deliver notifications until [`BaseConnector::run`] has been called.
+    ///
     /// # Errors
     /// Returns a `String` error if the underlying file mount observation cannot be created.
     pub fn create_device_endpoint_client_create_observation(
@@ -258,8 +438,64 @@ impl BaseConnector {
         DeviceEndpointClientCreationObservation::new(self.connector_context.clone())
     }
 
+    /// Creates a new [`SessionMonitor`] for the [`BaseConnector`]'s MQTT Session.
+    ///
+    /// Can be used to gate southbound operations (e.g. sampling a device) on the connectivity of
+    /// the connector to the MQTT broker, since data cannot be forwarded while disconnected.
+    pub fn create_session_monitor(&self) -> SessionMonitor {
+        self.session.create_session_monitor()
+    }
+
+    /// Creates a [`GracefulShutdown`](azure_iot_operations_mqtt::session::shutdown::GracefulShutdown)
+    /// for the [`BaseConnector`]'s MQTT Session that completes within `grace_period`.
+    ///
+    /// Spawn its `run` future before calling [`BaseConnector::run`] so that, when Kubernetes stops
+    /// the pod with SIGTERM, outgoing messages are drained and the Session is exited cleanly
+    /// instead of the connector being killed once the termination grace period ends.
+    /// [`BaseConnector::run`] then returns `Ok(())`.
+    #[cfg(feature = "signal")]
+    pub fn create_graceful_shutdown(
+        &self,
+        grace_period: Duration,
+    ) -> azure_iot_operations_mqtt::session::shutdown::GracefulShutdown {
+        azure_iot_operations_mqtt::session::shutdown::GracefulShutdown::new(
+            &self.session,
+            grace_period,
+        )
+    }
+
+    /// Activates the connector if it is passive. See [`ActivationGate::activate`](activation::ActivationGate::activate).
+    ///
+    /// Use [`BaseConnector::activation_gate`] to activate the connector once it is running.
+    pub fn activate(&self) {
+        self.connector_context.activation_gate.activate();
+    }
+
+    /// Deactivates the connector if it is active. See [`ActivationGate::deactivate`](activation::ActivationGate::deactivate).
+    ///
+    /// Use [`BaseConnector::activation_gate`] to deactivate the connector once it is running.
+    pub fn deactivate(&self) {
+        self.connector_context.activation_gate.deactivate();
+    }
+
+    /// Returns the [`ActivationGate`](activation::ActivationGate) controlling whether the
+    /// connector is active, which remains usable after [`BaseConnector::run`] has been called
+    /// (e.g. to activate the connector once it is elected leader).
+    pub fn activation_gate(&self) -> activation::ActivationGate {
+        self.connector_context.activation_gate.clone()
+    }
+
     /// Creates a handle to use the [`BaseConnector`]'s Azure Device Registry client for discovery operations.
     pub fn discovery_client(&self) -> adr_discovery::Client {
         adr_discovery::Client::new(self.connector_context.clone())
     }
+
+    /// Creates a handle to run, abort and observe reconciliation sweeps, or `None` if
+    /// reconciliation wasn't enabled with [`OptionsBuilder::reconciliation`].
+    pub fn reconciliation_handle(&self) -> Option<reconciliation::ReconciliationHandle> {
+        self.connector_context
+            .reconciler
+            .clone()
+            .map(reconciliation::ReconciliationHandle)
+    }
 }
@@ -0,0 +1,239 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Activation of a connector instance running as a warm standby.
+//!
+//! A connector started with [`OptionsBuilder::start_passive`](crate::base_connector::OptionsBuilder::start_passive)
+//! builds all of its Device Endpoint, Asset and Data Operation clients as usual, so that it can
+//! take over immediately on failover, but doesn't forward data or report statuses until it is
+//! activated with its [`ActivationGate`] (e.g. once it is elected leader).
+
+use std::{future::Future, sync::Arc};
+
+use tokio::sync::watch;
+
+use crate::{
+    base_connector::managed_azure_device_registry::RuntimeHealthEvent, destination_endpoint,
+};
+
+/// Reason code of the health event reported instead of any other while the connector is passive
+pub const STANDBY_REASON_CODE: &str = "Standby";
+
+/// Behavior of [`DataOperationClient::forward_data`](crate::base_connector::managed_azure_device_registry::DataOperationClient::forward_data)
+/// and [`DataOperationClient::final_flush`](crate::base_connector::managed_azure_device_registry::DataOperationClient::final_flush)
+/// while the connector is passive
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PassiveForwarding {
+    /// Data is not forwarded, and an error of kind [`Passive`](destination_endpoint::ErrorKind::Passive)
+    /// is returned
+    #[default]
+    Error,
+    /// Data is silently dropped, and `Ok(None)` is returned as if the data had been filtered out.
+    /// Tombstones are dropped as well.
+    Drop,
+}
+
+/// Controls whether a connector instance is active or passive.
+///
+/// While passive:
+/// - data is not forwarded to any destination, as configured with [`PassiveForwarding`]
+/// - Device Endpoint, Asset and Asset Component statuses are not reported, and reporting them
+///   returns [`ModifyResult::NotModified`](crate::base_connector::managed_azure_device_registry::ModifyResult::NotModified)
+/// - every health event is reported as [`RuntimeHealthEvent::Unavailable`] with the
+///   [`STANDBY_REASON_CODE`] reason code instead
+///
+/// Health events reported while active are re-reported periodically, so report the current
+/// health event again after changing the activation so that the standby health is reported, or
+/// replaced. Message schemas can still be reported while passive.
+///
+/// Clones share the same activation, so a clone can be handed to the task that decides which
+/// instance is active (e.g. a leader election) while the [`BaseConnector`](crate::base_connector::BaseConnector)
+/// runs.
+#[derive(Clone, Debug)]
+pub struct ActivationGate {
+    active_tx: Arc<watch::Sender<bool>>,
+    passive_forwarding: PassiveForwarding,
+}
+
+impl ActivationGate {
+    /// Creates a new [`ActivationGate`] that starts active or passive
+    pub(crate) fn new(active: bool, passive_forwarding: PassiveForwarding) -> Self {
+        Self {
+            active_tx: Arc::new(watch::Sender::new(active)),
+            passive_forwarding,
+        }
+    }
+
+    /// Activates the connector, resuming data forwarding and status reporting
+    pub fn activate(&self) {
+        self.set_active(true);
+    }
+
+    /// Deactivates the connector, putting it in standby
+    pub fn deactivate(&self) {
+        self.set_active(false);
+    }
+
+    /// Returns whether the connector is currently active
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        *self.active_tx.borrow()
+    }
+
+    /// Creates a [`watch::Receiver`] of whether the connector is active, which is notified every
+    /// time the connector is activated or deactivated.
+    ///
+    /// This can be used alongside other readiness watchers to only sample while active.
+    #[must_use]
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.active_tx.subscribe()
+    }
+
+    /// Waits until the connector is active, returning immediately if it already is
+    pub async fn wait_until_active(&self) {
+        // The sender is held by `self`, so this can't fail
+        let _ = self.subscribe().wait_for(|active| *active).await;
+    }
+
+    fn set_active(&self, active: bool) {
+        let changed = self.active_tx.send_if_modified(|current| {
+            if *current == active {
+                false
+            } else {
+                *current = active;
+                true
+            }
+        });
+        if changed {
+            log::info!(
+                "Connector {}",
+                if active { "activated" } else { "deactivated" }
+            );
+        }
+    }
+
+    /// Polls `write` only if the connector is active, so that nothing is written to the
+    /// destination while passive. When dropped, the write returns the default value.
+    pub(crate) async fn write_if_active<T: Default>(
+        &self,
+        write: impl Future<Output = Result<T, destination_endpoint::Error>>,
+    ) -> Result<T, destination_endpoint::Error> {
+        if self.is_active() {
+            return write.await;
+        }
+        match self.passive_forwarding {
+            PassiveForwarding::Error => Err(destination_endpoint::ErrorKind::Passive.into()),
+            PassiveForwarding::Drop => {
+                log::debug!("Connector is passive, dropping destination write");
+                Ok(T::default())
+            }
+        }
+    }
+
+    /// Returns `health_event`, or the standby health event if the connector is passive
+    pub(crate) fn health_event(&self, health_event: RuntimeHealthEvent) -> RuntimeHealthEvent {
+        if self.is_active() {
+            health_event
+        } else {
+            RuntimeHealthEvent::Unavailable {
+                message: Some("Connector instance is on standby".to_string()),
+                reason_code: Some(STANDBY_REASON_CODE.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::destination_endpoint::DeliveryReceipt;
+
+    /// Forwards data to a destination that counts the writes made to it
+    async fn forward(
+        gate: &ActivationGate,
+        writes: &AtomicUsize,
+    ) -> Result<Option<DeliveryReceipt>, destination_endpoint::Error> {
+        gate.write_if_active(async {
+            writes.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn no_destination_writes_while_passive() {
+        let gate = ActivationGate::new(false, PassiveForwarding::Error);
+        let writes = AtomicUsize::new(0);
+
+        assert!(matches!(
+            forward(&gate, &writes).await.unwrap_err().kind(),
+            destination_endpoint::ErrorKind::Passive
+        ));
+        assert_eq!(writes.load(Ordering::SeqCst), 0);
+
+        gate.activate();
+        forward(&gate, &writes).await.unwrap();
+        assert_eq!(writes.load(Ordering::SeqCst), 1);
+
+        gate.clone().deactivate();
+        assert!(forward(&gate, &writes).await.is_err());
+        assert_eq!(writes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn passive_forwarding_drop() {
+        let gate = ActivationGate::new(false, PassiveForwarding::Drop);
+        let writes = AtomicUsize::new(0);
+
+        assert!(forward(&gate, &writes).await.unwrap().is_none());
+        assert_eq!(writes.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn activation_toggled_at_runtime() {
+        let gate = ActivationGate::new(false, PassiveForwarding::Error);
+        let mut active_rx = gate.subscribe();
+        assert!(!*active_rx.borrow_and_update());
+
+        let waiter = tokio::task::spawn({
+            let gate = gate.clone();
+            async move { gate.wait_until_active().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        gate.activate();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        active_rx.changed().await.unwrap();
+        assert!(*active_rx.borrow_and_update());
+
+        gate.deactivate();
+        active_rx.changed().await.unwrap();
+        assert!(!*active_rx.borrow_and_update());
+        assert!(!gate.is_active());
+    }
+
+    #[test]
+    fn standby_health_event_while_passive() {
+        let gate = ActivationGate::new(false, PassiveForwarding::Error);
+        assert!(matches!(
+            gate.health_event(RuntimeHealthEvent::Available),
+            RuntimeHealthEvent::Unavailable { reason_code: Some(reason_code), .. }
+                if reason_code == STANDBY_REASON_CODE
+        ));
+
+        gate.activate();
+        assert!(matches!(
+            gate.health_event(RuntimeHealthEvent::Available),
+            RuntimeHealthEvent::Available
+        ));
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Exclusive ownership of Device Endpoints across multiple instances of a connector.
+//!
+//! Each Device Endpoint is owned by holding a lease in the State Store named after it. The lease
+//! is renewed while the MQTT Session is connected, and ownership is considered lost as soon as
+//! the Session disconnects, since the lease can't be renewed and may expire at any time.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use azure_iot_operations_mqtt::session::SessionMonitor;
+use azure_iot_operations_services::{
+    leased_lock::{self, lease},
+    state_store,
+};
+use tokio::sync::mpsc;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+use crate::deployment_artifacts::azure_device_registry::DeviceEndpointRef;
+
+/// Prefix of the names of the leases used to own Device Endpoints
+const LEASE_NAME_PREFIX: &str = "connector-ownership";
+
+/// Changes in the ownership of a Device Endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OwnershipEvent {
+    /// This instance now owns the Device Endpoint
+    Acquired,
+    /// This instance no longer owns the Device Endpoint
+    Lost,
+}
+
+/// A lease that can be held by at most one instance at a time
+pub(crate) trait OwnershipLease: Send + Sync + 'static {
+    /// Attempts to acquire or renew the lease. Returns whether the lease is held by this instance.
+    fn try_acquire(&self) -> impl Future<Output = Result<bool, String>> + Send;
+    /// Releases the lease if it is held by this instance.
+    fn release(&self) -> impl Future<Output = ()> + Send;
+}
+
+/// Connectivity of the instance to the service holding the leases
+pub(crate) trait Connectivity: Send + Sync + 'static {
+    /// Returns whether the instance is currently connected
+    fn is_connected(&self) -> bool;
+    /// Waits until the instance is connected. Returns immediately if already connected.
+    fn connected(&self) -> impl Future<Output = ()> + Send;
+    /// Waits until the instance is disconnected. Returns immediately if already disconnected.
+    fn disconnected(&self) -> impl Future<Output = ()> + Send;
+}
+
+impl Connectivity for SessionMonitor {
+    fn is_connected(&self) -> bool {
+        SessionMonitor::is_connected(self)
+    }
+
+    fn connected(&self) -> impl Future<Output = ()> + Send {
+        SessionMonitor::connected(self)
+    }
+
+    fn disconnected(&self) -> impl Future<Output = ()> + Send {
+        SessionMonitor::disconnected(self)
+    }
+}
+
+/// [`OwnershipLease`] backed by a [`lease::Client`]
+pub(crate) struct StateStoreLease {
+    client: lease::Client,
+    lease_duration: Duration,
+    request_timeout: Duration,
+}
+
+impl StateStoreLease {
+    /// Creates a new [`StateStoreLease`] for the given Device Endpoint.
+    ///
+    /// # Errors
+    /// Returns a `String` error if `holder_name` is empty.
+    pub(crate) fn new(
+        state_store_client: Arc<state_store::Client>,
+        device_endpoint_ref: &DeviceEndpointRef,
+        holder_name: &str,
+        lease_duration: Duration,
+        request_timeout: Duration,
+    ) -> Result<Self, String> {
+        let client = lease::Client::new(
+            state_store_client,
+            lease_name(device_endpoint_ref).into_bytes(),
+            holder_name.as_bytes().to_vec(),
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            lease_duration,
+            request_timeout,
+        })
+    }
+}
+
+impl OwnershipLease for StateStoreLease {
+    async fn try_acquire(&self) -> Result<bool, String> {
+        match self
+            .client
+            .acquire(self.lease_duration, self.request_timeout, None)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.kind(), leased_lock::ErrorKind::LeaseAlreadyHeld) => Ok(false),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn release(&self) {
+        if let Err(e) = self.client.release(self.request_timeout).await {
+            log::warn!("Failed to release Device Endpoint ownership lease: {e}");
+        }
+    }
+}
+
+/// Returns the name of the lease used to own a Device Endpoint
+pub(crate) fn lease_name(device_endpoint_ref: &DeviceEndpointRef) -> String {
+    format!(
+        "{LEASE_NAME_PREFIX}/{}/{}",
+        device_endpoint_ref.device_name, device_endpoint_ref.inbound_endpoint_name
+    )
+}
+
+/// Campaign for the ownership of a Device Endpoint. The campaign runs in the background until
+/// this struct is dropped, at which point the lease is released if it is held.
+#[derive(Debug)]
+pub(crate) struct DeviceOwnership {
+    events_rx: mpsc::UnboundedReceiver<OwnershipEvent>,
+    _campaign_drop_guard: DropGuard,
+}
+
+impl DeviceOwnership {
+    /// Starts campaigning for the lease, renewing it every `renewal_period` while it is held.
+    pub(crate) fn start(
+        lease: impl OwnershipLease,
+        connectivity: impl Connectivity,
+        renewal_period: Duration,
+    ) -> Self {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let cancellation_token = CancellationToken::new();
+        tokio::task::spawn(run_campaign(
+            lease,
+            connectivity,
+            renewal_period,
+            events_tx,
+            cancellation_token.clone(),
+        ));
+        Self {
+            events_rx,
+            _campaign_drop_guard: cancellation_token.drop_guard(),
+        }
+    }
+
+    /// Receives the next change in ownership. The first event is always
+    /// [`OwnershipEvent::Acquired`], after which [`OwnershipEvent::Lost`] and
+    /// [`OwnershipEvent::Acquired`] alternate.
+    ///
+    /// Returns `None` if the campaign has ended.
+    pub(crate) async fn recv_event(&mut self) -> Option<OwnershipEvent> {
+        self.events_rx.recv().await
+    }
+}
+
+async fn run_campaign(
+    lease: impl OwnershipLease,
+    connectivity: impl Connectivity,
+    renewal_period: Duration,
+    events_tx: mpsc::UnboundedSender<OwnershipEvent>,
+    cancellation_token: CancellationToken,
+) {
+    let mut owned = false;
+    loop {
+        // The lease can't be acquired or renewed while disconnected
+        if !connectivity.is_connected() {
+            if owned {
+                owned = false;
+                if events_tx.send(OwnershipEvent::Lost).is_err() {
+                    break;
+                }
+            }
+            tokio::select! {
+                () = cancellation_token.cancelled() => break,
+                () = connectivity.connected() => {},
+            }
+        }
+
+        let acquired = tokio::select! {
+            () = cancellation_token.cancelled() => break,
+            acquired = lease.try_acquire() => acquired.unwrap_or_else(|e| {
+                // We can't be sure that the lease is still held, so treat it as lost
+                log::warn!("Failed to acquire Device Endpoint ownership lease: {e}");
+                false
+            }),
+        };
+        if acquired != owned {
+            owned = acquired;
+            let event = if owned {
+                OwnershipEvent::Acquired
+            } else {
+                OwnershipEvent::Lost
+            };
+            if events_tx.send(event).is_err() {
+                break;
+            }
+        }
+
+        tokio::select! {
+            () = cancellation_token.cancelled() => break,
+            // Wake up on disconnection only if the lease is held, to report its loss right away
+            () = connectivity.disconnected(), if owned => {},
+            () = tokio::time::sleep(renewal_period) => {},
+        }
+    }
+
+    if owned {
+        lease.release().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::sync::watch;
+
+    use super::*;
+
+    const RENEWAL_PERIOD: Duration = Duration::from_secs(5);
+
+    /// In-memory lease shared by all [`FakeLease`]s created from the same holder slot
+    struct FakeLease {
+        holder: Arc<Mutex<Option<String>>>,
+        name: String,
+    }
+
+    impl OwnershipLease for FakeLease {
+        async fn try_acquire(&self) -> Result<bool, String> {
+            let mut holder = self.holder.lock().unwrap();
+            match holder.as_ref() {
+                Some(current) if *current != self.name => Ok(false),
+                _ => {
+                    *holder = Some(self.name.clone());
+                    Ok(true)
+                }
+            }
+        }
+
+        async fn release(&self) {
+            let mut holder = self.holder.lock().unwrap();
+            if holder.as_ref() == Some(&self.name) {
+                *holder = None;
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct FakeConnectivity(watch::Sender<bool>);
+
+    impl FakeConnectivity {
+        fn new(connected: bool) -> Self {
+            Self(watch::Sender::new(connected))
+        }
+
+        fn set_connected(&self, connected: bool) {
+            self.0.send_replace(connected);
+        }
+
+        async fn wait_for(&self, connected: bool) {
+            let mut rx = self.0.subscribe();
+            let _ = rx.wait_for(|c| *c == connected).await;
+        }
+    }
+
+    impl Connectivity for FakeConnectivity {
+        fn is_connected(&self) -> bool {
+            *self.0.borrow()
+        }
+
+        fn connected(&self) -> impl Future<Output = ()> + Send {
+            self.wait_for(true)
+        }
+
+        fn disconnected(&self) -> impl Future<Output = ()> + Send {
+            self.wait_for(false)
+        }
+    }
+
+    fn start_ownership(
+        holder: &Arc<Mutex<Option<String>>>,
+        name: &str,
+        connectivity: &FakeConnectivity,
+    ) -> DeviceOwnership {
+        DeviceOwnership::start(
+            FakeLease {
+                holder: holder.clone(),
+                name: name.to_string(),
+            },
+            connectivity.clone(),
+            RENEWAL_PERIOD,
+        )
+    }
+
+    #[test]
+    fn lease_name_layout() {
+        let device_endpoint_ref = DeviceEndpointRef {
+            device_name: "device".to_string(),
+            inbound_endpoint_name: "endpoint".to_string(),
+        };
+        assert_eq!(
+            lease_name(&device_endpoint_ref),
+            "connector-ownership/device/endpoint"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_one_owner_at_a_time() {
+        let holder = Arc::new(Mutex::new(None));
+        let connectivity = FakeConnectivity::new(true);
+
+        let mut first = start_ownership(&holder, "first", &connectivity);
+        assert_eq!(first.recv_event().await, Some(OwnershipEvent::Acquired));
+
+        let mut second = start_ownership(&holder, "second", &connectivity);
+        tokio::time::sleep(RENEWAL_PERIOD * 3).await;
+        assert!(second.events_rx.try_recv().is_err());
+        assert!(first.events_rx.try_recv().is_err());
+        assert_eq!(holder.lock().unwrap().as_deref(), Some("first"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ownership_fails_over_when_owner_stops() {
+        let holder = Arc::new(Mutex::new(None));
+        let connectivity = FakeConnectivity::new(true);
+
+        let mut first = start_ownership(&holder, "first", &connectivity);
+        assert_eq!(first.recv_event().await, Some(OwnershipEvent::Acquired));
+        let mut second = start_ownership(&holder, "second", &connectivity);
+
+        // Dropping the owner releases the lease, which the other instance picks up on its next attempt
+        drop(first);
+        assert_eq!(second.recv_event().await, Some(OwnershipEvent::Acquired));
+        assert_eq!(holder.lock().unwrap().as_deref(), Some("second"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ownership_lost_on_disconnect_and_regained_on_reconnect() {
+        let holder = Arc::new(Mutex::new(None));
+        let connectivity = FakeConnectivity::new(false);
+
+        let mut ownership = start_ownership(&holder, "owner", &connectivity);
+        // Not acquired until connected
+        tokio::time::sleep(RENEWAL_PERIOD * 2).await;
+        assert!(ownership.events_rx.try_recv().is_err());
+
+        connectivity.set_connected(true);
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Acquired));
+
+        connectivity.set_connected(false);
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Lost));
+
+        connectivity.set_connected(true);
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Acquired));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ownership_lost_when_taken_by_another_holder() {
+        let holder = Arc::new(Mutex::new(None));
+        let connectivity = FakeConnectivity::new(true);
+
+        let mut ownership = start_ownership(&holder, "owner", &connectivity);
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Acquired));
+
+        // Simulate the lease expiring and being acquired by another instance
+        *holder.lock().unwrap() = Some("other".to_string());
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Lost));
+
+        *holder.lock().unwrap() = None;
+        assert_eq!(ownership.recv_event().await, Some(OwnershipEvent::Acquired));
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Spawning of Device Endpoint and Asset Component handler tasks that report panics to Azure Device Registry.
+//!
+//! A handler task that panics otherwise ends silently, and Azure Device Registry keeps showing the
+//! last status the handler reported. Spawning the handler with [`spawn_handler`] or
+//! [`spawn_restartable_handler`] instead reports the entity as failed with an internal error
+//! status when the handler panics, so that a connector that looks alive but isn't is visible.
+
+use std::{any::Any, future::Future, time::Duration};
+
+use azure_iot_operations_services::azure_device_registry;
+use tokio::task::JoinHandle;
+
+use crate::{
+    AdrConfigError,
+    base_connector::{
+        managed_azure_device_registry::{
+            AssetStatusReporter, DataOperationStatusReporter, DeviceEndpointStatusReporter,
+            ManagementActionStatusReporter, ModifyResult, RuntimeHealthEvent,
+        },
+        status::{Status, StatusBuilder, StatusErrorBuilder},
+    },
+};
+
+/// Status code reported when a handler panics
+const HANDLER_PANICKED_CODE: &str = "500";
+/// Runtime health reason code reported when a handler panics
+const HANDLER_PANICKED_REASON_CODE: &str = "HandlerPanicked";
+
+/// Reports the [`Status`] of the entity a handler is responsible for, when the handler panics.
+///
+/// Implemented for all of the status reporters, so the status reporter of the entity the handler
+/// is responsible for can be provided to [`spawn_handler`] or [`spawn_restartable_handler`].
+pub trait PanicStatusReporter: Send + Sync + 'static {
+    /// Report the status of the entity after its handler panicked
+    fn report_panic_status(
+        &self,
+        status: Status,
+    ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send;
+}
+
+impl PanicStatusReporter for DeviceEndpointStatusReporter {
+    fn report_panic_status(
+        &self,
+        status: Status,
+    ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send {
+        self.report_status(status)
+    }
+}
+
+impl PanicStatusReporter for DataOperationStatusReporter {
+    fn report_panic_status(
+        &self,
+        status: Status,
+    ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send {
+        self.report_status(status)
+    }
+}
+
+impl PanicStatusReporter for ManagementActionStatusReporter {
+    fn report_panic_status(
+        &self,
+        status: Status,
+    ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send {
+        self.report_status(status)
+    }
+}
+
+impl PanicStatusReporter for AssetStatusReporter {
+    /// Assets have no runtime health, so only the `config` section of the [`Status`] is reported
+    fn report_panic_status(
+        &self,
+        status: Status,
+    ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send {
+        let config = status.config().map_err(AdrConfigError::clone);
+        async move {
+            self.report_status_if_modified(|current| {
+                (current != Some(config.as_ref().copied())).then(|| config.clone())
+            })
+            .await
+        }
+    }
+}
+
+/// Whether a handler is restarted after it panics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// The handler is not restarted
+    #[default]
+    Never,
+    /// The handler is restarted after `delay`, at most `max_restarts` times
+    Limited {
+        /// Maximum number of times the handler is restarted
+        max_restarts: u32,
+        /// Delay before each restart
+        delay: Duration,
+    },
+}
+
+/// Spawns a handler task, reporting the entity as failed with `reporter` if the handler panics.
+///
+/// The returned [`JoinHandle`] completes once the handler completes or, if it panics, once the
+/// failed status has been reported. It never returns a panic as an error.
+///
+/// `log_identifier` identifies the handler in logs and in the reported status message.
+pub fn spawn_handler<R, Fut>(log_identifier: String, reporter: R, handler: Fut) -> JoinHandle<()>
+where
+    R: PanicStatusReporter,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut handler = Some(handler);
+    // The handler is only created once, as it is never restarted
+    spawn_restartable_handler(log_identifier, reporter, RestartPolicy::Never, move || {
+        let handler = handler.take();
+        async move {
+            if let Some(handler) = handler {
+                handler.await;
+            }
+        }
+    })
+}
+
+/// Spawns a handler task created by `make_handler`, reporting the entity as failed with
+/// `reporter` if the handler panics, and creating and running a new handler as allowed by the
+/// [`RestartPolicy`].
+///
+/// The restarted handler is responsible for reporting the status of the entity again once it
+/// recovers.
+///
+/// The returned [`JoinHandle`] completes once a handler completes or, if it panics and is not
+/// restarted, once the failed status has been reported. It never returns a panic as an error.
+///
+/// `log_identifier` identifies the handler in logs and in the reported status message.
+pub fn spawn_restartable_handler<R, F, Fut>(
+    log_identifier: String,
+    reporter: R,
+    restart_policy: RestartPolicy,
+    mut make_handler: F,
+) -> JoinHandle<()>
+where
+    R: PanicStatusReporter,
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::task::spawn(async move {
+        let mut restarts = 0;
+        loop {
+            // The handler runs in its own task so that a panic ends only that task
+            let Err(e) = tokio::task::spawn(make_handler()).await else {
+                return;
+            };
+            if !e.is_panic() {
+                // The runtime is shutting down
+                log::debug!("{log_identifier} Handler was cancelled");
+                return;
+            }
+
+            let panic_message = panic_message(e.into_panic().as_ref());
+            log::error!("{log_identifier} Handler panicked: {panic_message}");
+            report_panic(&log_identifier, &reporter, &panic_message).await;
+
+            match restart_policy {
+                RestartPolicy::Limited {
+                    max_restarts,
+                    delay,
+                } if restarts < max_restarts => {
+                    restarts += 1;
+                    log::info!(
+                        "{log_identifier} Restarting handler in {delay:?} ({restarts}/{max_restarts})"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => return,
+            }
+        }
+    })
+}
+
+async fn report_panic<R: PanicStatusReporter>(
+    log_identifier: &str,
+    reporter: &R,
+    panic_message: &str,
+) {
+    let message = format!("Handler for {log_identifier} panicked: {panic_message}");
+    let status = StatusBuilder::default()
+        .config_error(
+            StatusErrorBuilder::default()
+                .code(HANDLER_PANICKED_CODE)
+                .message(message.clone())
+                .build()
+                .expect("code is numeric and message is not empty"),
+        )
+        .runtime(RuntimeHealthEvent::Unavailable {
+            message: Some(message),
+            reason_code: Some(HANDLER_PANICKED_REASON_CODE.to_string()),
+        })
+        .build()
+        .expect("all status fields have defaults");
+    if let Err(e) = reporter.report_panic_status(status).await {
+        log::error!("{log_identifier} Failed to report handler panic status: {e}");
+    }
+}
+
+/// Gets the message of a panic payload, which is a `&str` or `String` for panics raised with `panic!`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct MockReporter {
+        reported: Arc<Mutex<Vec<Status>>>,
+    }
+
+    impl PanicStatusReporter for MockReporter {
+        fn report_panic_status(
+            &self,
+            status: Status,
+        ) -> impl Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send
+        {
+            self.reported.lock().unwrap().push(status);
+            async { Ok(ModifyResult::Reported) }
+        }
+    }
+
+    #[tokio::test]
+    async fn handler_completes_without_report() {
+        let reporter = MockReporter::default();
+        spawn_handler("[test]".to_string(), reporter.clone(), async {})
+            .await
+            .unwrap();
+        assert!(reporter.reported.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn handler_panic_reported() {
+        let reporter = MockReporter::default();
+        spawn_handler("[test]".to_string(), reporter.clone(), async {
+            panic!("dataset handler failed");
+        })
+        .await
+        .unwrap();
+
+        let statuses = reporter.reported.lock().unwrap();
+        assert_eq!(statuses.len(), 1);
+        let config_error = statuses[0].config().unwrap_err();
+        assert_eq!(config_error.code.as_deref(), Some(HANDLER_PANICKED_CODE));
+        assert!(
+            config_error
+                .message
+                .as_ref()
+                .unwrap()
+                .contains("dataset handler failed")
+        );
+        match statuses[0].runtime() {
+            Some(RuntimeHealthEvent::Unavailable { reason_code, .. }) => {
+                assert_eq!(reason_code.as_deref(), Some(HANDLER_PANICKED_REASON_CODE));
+            }
+            _ => panic!("expected unavailable runtime health"),
+        }
+    }
+
+    #[test_case(RestartPolicy::Never, 1; "never")]
+    #[test_case(RestartPolicy::Limited { max_restarts: 2, delay: Duration::ZERO }, 3; "limited")]
+    #[tokio::test]
+    async fn handler_restarts(restart_policy: RestartPolicy, expected_runs: u32) {
+        let reporter = MockReporter::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let handler_runs = runs.clone();
+        spawn_restartable_handler(
+            "[test]".to_string(),
+            reporter.clone(),
+            restart_policy,
+            move || {
+                handler_runs.fetch_add(1, Ordering::SeqCst);
+                async {
+                    panic!("handler failed");
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), expected_runs);
+        assert_eq!(
+            reporter.reported.lock().unwrap().len(),
+            expected_runs as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn restarted_handler_completes() {
+        let reporter = MockReporter::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let handler_runs = runs.clone();
+        spawn_restartable_handler(
+            "[test]".to_string(),
+            reporter.clone(),
+            RestartPolicy::Limited {
+                max_restarts: 5,
+                delay: Duration::ZERO,
+            },
+            move || {
+                let run = handler_runs.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    assert!(run > 0, "first run fails");
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(reporter.reported.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Hooks for managing the resources of a connector over the lifetime of a [`BaseConnector`] run.
+//!
+//! Implement [`LifecycleHooks`] and run the connector with [`BaseConnector::run_with_hooks`] to
+//! set up resources (e.g. open a database or initialize a driver) before any Device Endpoint is
+//! delivered for sampling, and to tear them down once the run ends.
+//!
+//! [`BaseConnector`]: crate::base_connector::BaseConnector
+//! [`BaseConnector::run_with_hooks`]: crate::base_connector::BaseConnector::run_with_hooks
+
+use std::future::Future;
+
+use azure_iot_operations_protocol::application::ApplicationContext;
+
+use crate::base_connector::{ConnectorError, ConnectorErrorRepr};
+
+/// Hooks invoked by [`BaseConnector::run_with_hooks`](crate::base_connector::BaseConnector::run_with_hooks)
+/// when the connector starts and shuts down.
+///
+/// Both hooks have a default no-op implementation, so only the hooks needed have to be implemented.
+pub trait LifecycleHooks: Send + Sync {
+    /// Invoked when the run starts, before any
+    /// [`DeviceEndpointClient`](crate::base_connector::managed_azure_device_registry::DeviceEndpointClient)
+    /// is delivered and before the MQTT Session is started, so it must not wait on operations that
+    /// require connectivity to the MQTT broker.
+    ///
+    /// Returning an error ends the run with that error without starting the MQTT Session, and
+    /// [`LifecycleHooks::on_shutdown`] is not invoked. Any resources set up before the error must
+    /// be released by this hook.
+    fn on_start(
+        &self,
+        _application_context: &ApplicationContext,
+    ) -> impl Future<Output = Result<(), String>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Invoked once the run ends after [`LifecycleHooks::on_start`] succeeded, with the result
+    /// the run returns. This includes the run ending because of a fatal MQTT Session error or
+    /// because the connector requires a restart.
+    ///
+    /// It is not invoked if the run panics or is cancelled (i.e. its future is dropped).
+    fn on_shutdown(
+        &self,
+        _application_context: &ApplicationContext,
+        _result: &Result<(), ConnectorError>,
+    ) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// [`LifecycleHooks`] that do nothing, used when the connector is run without hooks
+pub(crate) struct NoLifecycleHooks;
+
+impl LifecycleHooks for NoLifecycleHooks {}
+
+/// Runs `run` between the [`LifecycleHooks::on_start`] and [`LifecycleHooks::on_shutdown`] hooks.
+///
+/// `run` is not polled until `on_start` has succeeded.
+pub(crate) async fn run_with_hooks(
+    hooks: &impl LifecycleHooks,
+    application_context: &ApplicationContext,
+    run: impl Future<Output = Result<(), ConnectorError>>,
+) -> Result<(), ConnectorError> {
+    hooks
+        .on_start(application_context)
+        .await
+        .map_err(ConnectorErrorRepr::StartHook)?;
+
+    let result = run.await;
+    if let Err(e) = &result {
+        log::warn!("Connector run ended with error, running shutdown hook: {e}");
+    }
+    hooks.on_shutdown(application_context, &result).await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockHooks {
+        fail_start: bool,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl LifecycleHooks for MockHooks {
+        fn on_start(
+            &self,
+            _application_context: &ApplicationContext,
+        ) -> impl Future<Output = Result<(), String>> + Send {
+            self.calls.lock().unwrap().push("start".to_string());
+            let fail_start = self.fail_start;
+            async move {
+                if fail_start {
+                    Err("driver unavailable".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        fn on_shutdown(
+            &self,
+            _application_context: &ApplicationContext,
+            result: &Result<(), ConnectorError>,
+        ) -> impl Future<Output = ()> + Send {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("shutdown: {}", result.is_ok()));
+            async {}
+        }
+    }
+
+    fn application_context() -> ApplicationContext {
+        ApplicationContextBuilder::default().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn hooks_run_around_run() {
+        let hooks = MockHooks::default();
+        let calls = hooks.calls.clone();
+        let result = run_with_hooks(&hooks, &application_context(), async {
+            calls.lock().unwrap().push("run".to_string());
+            Ok(())
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            *hooks.calls.lock().unwrap(),
+            vec!["start", "run", "shutdown: true"]
+        );
+    }
+
+    #[tokio::test]
+    async fn shutdown_hook_runs_on_fatal_error() {
+        let hooks = MockHooks::default();
+        let result = run_with_hooks(&hooks, &application_context(), async {
+            Err(ConnectorErrorRepr::Unrecoverable("restart required".to_string()).into())
+        })
+        .await;
+
+        assert!(result.unwrap_err().to_string().contains("restart required"));
+        assert_eq!(
+            *hooks.calls.lock().unwrap(),
+            vec!["start", "shutdown: false"]
+        );
+    }
+
+    #[tokio::test]
+    async fn start_hook_failure_skips_run() {
+        let hooks = MockHooks {
+            fail_start: true,
+            ..Default::default()
+        };
+        let calls = hooks.calls.clone();
+        let result = run_with_hooks(&hooks, &application_context(), async {
+            calls.lock().unwrap().push("run".to_string());
+            Ok(())
+        })
+        .await;
+
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("driver unavailable")
+        );
+        assert_eq!(*hooks.calls.lock().unwrap(), vec!["start"]);
+    }
+
+    #[tokio::test]
+    async fn default_hooks() {
+        let result =
+            run_with_hooks(&NoLifecycleHooks, &application_context(), async { Ok(()) }).await;
+        assert!(result.is_ok());
+    }
+}
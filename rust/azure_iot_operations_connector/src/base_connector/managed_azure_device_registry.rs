@@ -3,7 +3,9 @@
 
 //! Types for Azure IoT Operations Connectors.
 
-use std::{borrow::Cow, collections::HashMap, hash::Hash, path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow, collections::HashMap, hash::Hash, path::PathBuf, sync::Arc, time::Duration,
+};
 
 use azure_iot_operations_services::{
     azure_device_registry::{
@@ -20,19 +22,26 @@ use tokio::sync::{
     Notify,
     mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
+use tokio::time::Instant;
 use tokio_retry2::{Retry, RetryError};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     AdrConfigError, Data, DataOperationKind, DataOperationName, DataOperationRef,
     ManagementActionRef, MessageSchema, MessageSchemaReference,
-    base_connector::ConnectorContext,
+    base_connector::{
+        ConnectorContext,
+        device_ownership::{DeviceOwnership, OwnershipEvent, StateStoreLease},
+        secret_reference::{ConfigurationResolver, ResolvedConfiguration, SecretReferenceError},
+        status::Status,
+    },
     deployment_artifacts::{
         self,
         azure_device_registry::{AssetRef, DeviceEndpointRef},
     },
-    destination_endpoint::{self, DataOperationForwarder},
+    destination_endpoint::{self, DataOperationForwarder, DeliveryReceipt},
     management_action_executor::{self, ManagementActionExecutor},
+    message_schema,
 };
 
 /// Used as the strategy when using [`tokio_retry2::Retry`]
@@ -61,6 +70,18 @@ pub enum ClientNotification<T> {
     Deleted,
     /// Indicates that there is a new `T` for this Client, which is included in the notification
     Created(T),
+    /// Indicates that this connector instance no longer owns the Client's Device Endpoint, and
+    /// should stop operating on it (e.g. sampling) until [`ClientNotification::OwnershipRegained`].
+    ///
+    /// Only returned by [`DeviceEndpointClient`] when `exclusive_device_ownership` is enabled
+    /// on the [`BaseConnector`](crate::base_connector::BaseConnector).
+    OwnershipLost,
+    /// Indicates that this connector instance owns the Client's Device Endpoint again after
+    /// [`ClientNotification::OwnershipLost`].
+    ///
+    /// Only returned by [`DeviceEndpointClient`] when `exclusive_device_ownership` is enabled
+    /// on the [`BaseConnector`](crate::base_connector::BaseConnector).
+    OwnershipRegained,
 }
 
 /// Represents the result of a network modification
@@ -97,7 +118,14 @@ impl DeviceEndpointStatusReporter {
     ///
     /// The version used for reporting is the snapshotted version from the last call to
     /// `refresh_health_version()` or `pause_and_refresh_health_version()`, not necessarily the current specification version.
+    ///
+    /// While the connector is passive, a standby health event is reported instead. See
+    /// [`ActivationGate`](crate::base_connector::activation::ActivationGate).
     pub fn report_health_event(&self, health_event: RuntimeHealthEvent) {
+        let health_event = self
+            .connector_context
+            .activation_gate
+            .health_event(health_event);
         let (status, message, reason_code) = match health_event {
             RuntimeHealthEvent::Available => (HealthStatus::Available, None, None),
             RuntimeHealthEvent::Unavailable {
@@ -153,6 +181,35 @@ impl DeviceEndpointStatusReporter {
         self.refresh_health_version();
     }
 
+    /// Reports a typed [`Status`] for the Endpoint.
+    ///
+    /// The `config` section is reported as the endpoint status only if it differs from the current endpoint status,
+    /// and the `runtime` section, if present, is reported as a runtime health event.
+    ///
+    /// # Returns
+    /// - [`ModifyResult::Reported`] if the `config` section was reported
+    /// - [`ModifyResult::NotModified`] if the `config` section was already current, the version changed during processing, or the connector is passive
+    ///
+    /// # Errors
+    /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
+    /// there are any underlying errors from the AIO RPC protocol. This error will be retried
+    /// 10 times with exponential backoff and jitter and only returned if it still is failing.
+    ///
+    /// [`azure_device_registry::Error`] of kind [`ServiceError`](azure_device_registry::ErrorKind::ServiceError) if an error is returned
+    /// by the Azure Device Registry service.
+    pub async fn report_status(
+        &self,
+        status: Status,
+    ) -> Result<ModifyResult, azure_device_registry::Error> {
+        let result = self
+            .report_endpoint_status_if_modified(|current| status.config_if_modified(current))
+            .await?;
+        if let Some(runtime) = status.runtime() {
+            self.report_health_event(runtime.clone());
+        }
+        Ok(result)
+    }
+
     /// Used to conditionally report the device status and then updates the device with the new status returned.
     ///
     /// The `modify` function is called with the current device status (if any) and should return:
@@ -161,7 +218,7 @@ impl DeviceEndpointStatusReporter {
     ///
     /// # Returns
     /// - [`ModifyResult::Reported`] if the status was updated and successfully reported
-    /// - [`ModifyResult::NotModified`] if no modification was needed or the version changed during processing
+    /// - [`ModifyResult::NotModified`] if no modification was needed, the version changed during processing, or the connector is passive
     ///
     /// # Errors
     /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
@@ -180,6 +237,10 @@ impl DeviceEndpointStatusReporter {
     where
         F: Fn(Option<Result<(), &AdrConfigError>>) -> Option<Result<(), AdrConfigError>>,
     {
+        if !self.connector_context.activation_gate.is_active() {
+            log::debug!("Connector is passive, not reporting status");
+            return Ok(ModifyResult::NotModified);
+        }
         // Get the current version of the device endpoint specification
         let cached_version = self.device_endpoint_specification.read().unwrap().version;
 
@@ -292,7 +353,7 @@ impl DeviceEndpointStatusReporter {
     ///
     /// # Returns
     /// - [`ModifyResult::Reported`] if the status was updated and successfully reported
-    /// - [`ModifyResult::NotModified`] if no modification was needed or the version changed during processing
+    /// - [`ModifyResult::NotModified`] if no modification was needed, the version changed during processing, or the connector is passive
     ///
     /// # Errors
     /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
@@ -311,6 +372,10 @@ impl DeviceEndpointStatusReporter {
     where
         F: Fn(Option<Result<(), &AdrConfigError>>) -> Option<Result<(), AdrConfigError>>,
     {
+        if !self.connector_context.activation_gate.is_active() {
+            log::debug!("Connector is passive, not reporting status");
+            return Ok(ModifyResult::NotModified);
+        }
         // Get the current version of the device endpoint specification
         let cached_version = self.device_endpoint_specification.read().unwrap().version;
 
@@ -440,6 +505,30 @@ impl DeviceEndpointStatusReporter {
     }
 }
 
+/// Last discovered asset reported for each asset name
+#[derive(Debug, Default)]
+struct ReportedDiscoveredAssets(std::sync::Mutex<HashMap<String, adr_models::DiscoveredAsset>>);
+
+impl ReportedDiscoveredAssets {
+    /// Returns whether `discovered_asset` is the last discovered asset reported for `asset_name`
+    fn is_reported(
+        &self,
+        asset_name: &str,
+        discovered_asset: &adr_models::DiscoveredAsset,
+    ) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(asset_name)
+            .is_some_and(|reported| reported == discovered_asset)
+    }
+
+    /// Records `discovered_asset` as the last discovered asset reported for `asset_name`
+    fn record(&self, asset_name: String, discovered_asset: adr_models::DiscoveredAsset) {
+        self.0.lock().unwrap().insert(asset_name, discovered_asset);
+    }
+}
+
 /// An Observation for device endpoint creation events that uses
 /// multiple underlying clients to get full device endpoint information.
 pub struct DeviceEndpointClientCreationObservation {
@@ -452,7 +541,19 @@ pub struct DeviceEndpointClientCreationObservation {
     /// This is used to ensure that we only process one device creation at a time
     device_completion_rx: mpsc::Receiver<Option<DeviceEndpointClient>>,
     device_completion_tx: mpsc::Sender<Option<DeviceEndpointClient>>,
+    /// Channels for sending and receiving device endpoints whose ownership has been acquired,
+    /// if exclusive device ownership is enabled
+    owned_device_rx: UnboundedReceiver<OwnedDeviceEndpoint>,
+    owned_device_tx: UnboundedSender<OwnedDeviceEndpoint>,
 }
+
+/// A device endpoint create notification whose ownership has been acquired
+type OwnedDeviceEndpoint = (
+    DeviceEndpointRef,
+    deployment_artifacts::azure_device_registry::AssetCreateObservation,
+    Option<DeviceOwnership>,
+);
+
 impl DeviceEndpointClientCreationObservation {
     /// Creates a new [`DeviceEndpointClientCreationObservation`] that uses the given [`ConnectorContext`]
     ///
@@ -475,6 +576,7 @@ impl DeviceEndpointClientCreationObservation {
             })?;
 
         let (device_completion_tx, device_completion_rx) = mpsc::channel(1);
+        let (owned_device_tx, owned_device_rx) = mpsc::unbounded_channel();
 
         Ok(Self {
             connector_context,
@@ -482,6 +584,8 @@ impl DeviceEndpointClientCreationObservation {
             pending_device_creation: false,
             device_completion_rx,
             device_completion_tx,
+            owned_device_rx,
+            owned_device_tx,
         })
     }
 
@@ -489,9 +593,17 @@ impl DeviceEndpointClientCreationObservation {
     /// notification includes the [`DeviceEndpointClient`], which can be used
     /// to receive Assets related to this Device Endpoint
     ///
+    /// If exclusive device ownership is enabled, the notification is only received
+    /// once this connector instance owns the Device Endpoint.
+    ///
+    /// Notifications are not received until [`BaseConnector::run`](crate::base_connector::BaseConnector::run)
+    /// has been called. Device Endpoints created before then are buffered and received afterwards.
+    ///
     /// # Panics
     /// If the `device_endpoint_create_observation` channel is closed, which should not be possible
     pub async fn recv_notification(&mut self) -> DeviceEndpointClient {
+        // Hold notifications until all observations have been registered and the connector is running
+        self.connector_context.run_started.cancelled().await;
         loop {
             tokio::select! {
                 // Check for completed device creation
@@ -502,36 +614,111 @@ impl DeviceEndpointClientCreationObservation {
                     }
                     // If device_client_option is None, creation failed, continue loop
                 },
+                // Get owned device endpoints only if not already processing one
+                Some((device_endpoint_ref, asset_create_observation, ownership)) = self.owned_device_rx.recv(), if !self.pending_device_creation => {
+                    self.start_device_endpoint_client_creation(device_endpoint_ref, asset_create_observation, ownership);
+                },
                 // Get new device creation notifications only if not already processing one
                 create_notification = self.device_endpoint_create_observation.recv_notification(), if !self.pending_device_creation => {
                     let (device_endpoint_ref, asset_create_observation) =
                         create_notification.expect("Device Endpoint Create Observation should never return None because the device_endpoint_create_observation struct holds the sending side of the channel");
 
-                    // Start device creation task
-                    self.pending_device_creation = true;
-                    let connector_context = self.connector_context.clone();
-                    let device_completion_tx = self.device_completion_tx.clone();
-
-                    tokio::task::spawn(async move {
-                        let device_client = Self::create_device_endpoint_client(
-                            connector_context,
+                    if let Some(lease_duration) = self.connector_context.device_ownership_lease_duration {
+                        // Campaign for ownership in the background so that other device endpoints
+                        // can still be processed while another instance owns this one
+                        Self::campaign_for_device_endpoint(
+                            &self.connector_context,
+                            lease_duration,
                             device_endpoint_ref,
                             asset_create_observation,
-                        ).await;
-
-                        // Always send the result (Some or None) to unblock the receiver
-                        let _ = device_completion_tx.send(device_client).await;
-                    });
+                            self.owned_device_tx.clone(),
+                        );
+                    } else {
+                        self.start_device_endpoint_client_creation(device_endpoint_ref, asset_create_observation, None);
+                    }
                 }
             }
         }
     }
 
+    /// Internal helper to start a device creation task
+    fn start_device_endpoint_client_creation(
+        &mut self,
+        device_endpoint_ref: DeviceEndpointRef,
+        asset_create_observation: deployment_artifacts::azure_device_registry::AssetCreateObservation,
+        ownership: Option<DeviceOwnership>,
+    ) {
+        self.pending_device_creation = true;
+        let connector_context = self.connector_context.clone();
+        let device_completion_tx = self.device_completion_tx.clone();
+
+        tokio::task::spawn(async move {
+            let device_client = Self::create_device_endpoint_client(
+                connector_context,
+                device_endpoint_ref,
+                asset_create_observation,
+                ownership,
+            )
+            .await;
+
+            // Always send the result (Some or None) to unblock the receiver
+            let _ = device_completion_tx.send(device_client).await;
+        });
+    }
+
+    /// Internal helper that campaigns for the ownership of a device endpoint and sends it to
+    /// `owned_device_tx` once acquired. Gives up if the device endpoint is deleted first.
+    fn campaign_for_device_endpoint(
+        connector_context: &ConnectorContext,
+        lease_duration: Duration,
+        device_endpoint_ref: DeviceEndpointRef,
+        asset_create_observation: deployment_artifacts::azure_device_registry::AssetCreateObservation,
+        owned_device_tx: UnboundedSender<OwnedDeviceEndpoint>,
+    ) {
+        let lease = match StateStoreLease::new(
+            connector_context.state_store_client.clone(),
+            &device_endpoint_ref,
+            connector_context.managed_client.client_id(),
+            lease_duration,
+            connector_context.state_store_timeout,
+        ) {
+            Ok(lease) => lease,
+            Err(e) => {
+                log::error!(
+                    "Dropping device endpoint create notification: {device_endpoint_ref:?}. Failed to create ownership lease: {e}"
+                );
+                return;
+            }
+        };
+        let mut ownership = DeviceOwnership::start(
+            lease,
+            connector_context.session_monitor.clone(),
+            lease_duration / 3,
+        );
+        let device_deletion_token = asset_create_observation.device_deletion_token();
+
+        tokio::task::spawn(async move {
+            log::info!("Campaigning for ownership of device endpoint {device_endpoint_ref:?}");
+            tokio::select! {
+                () = device_deletion_token.cancelled() => {
+                    log::info!("Device endpoint {device_endpoint_ref:?} deleted before its ownership was acquired");
+                },
+                event = ownership.recv_event() => {
+                    if event == Some(OwnershipEvent::Acquired) {
+                        log::info!("Acquired ownership of device endpoint {device_endpoint_ref:?}");
+                        let _ = owned_device_tx.send((device_endpoint_ref, asset_create_observation, Some(ownership)));
+                    }
+                },
+            }
+        });
+    }
+
     /// Internal helper to create a [`DeviceEndpointClient`]
     async fn create_device_endpoint_client(
         connector_context: Arc<ConnectorContext>,
         device_endpoint_ref: DeviceEndpointRef,
         asset_create_observation: deployment_artifacts::azure_device_registry::AssetCreateObservation,
+        ownership: Option<DeviceOwnership>,
     ) -> Option<DeviceEndpointClient> {
         // Obtain the device update observation
         let device_endpoint_update_observation =  match Retry::spawn(
@@ -625,6 +812,7 @@ impl DeviceEndpointClientCreationObservation {
             device_endpoint_ref.clone(),
             device_endpoint_update_observation,
             asset_create_observation,
+            ownership,
             connector_context.clone(),
         ) {
             Ok(managed_device) => Some(managed_device),
@@ -682,6 +870,12 @@ pub struct DeviceEndpointClient {
     asset_completion_rx: mpsc::UnboundedReceiver<Option<AssetClient>>,
     #[getter(skip)]
     asset_completion_tx: mpsc::UnboundedSender<Option<AssetClient>>,
+    /// Ownership of the device endpoint, if exclusive device ownership is enabled
+    #[getter(skip)]
+    ownership: Option<DeviceOwnership>,
+    /// Discovered assets that have been reported, used to avoid reporting the same discovery twice
+    #[getter(skip)]
+    reported_discovered_assets: ReportedDiscoveredAssets,
     #[getter(skip)]
     connector_context: Arc<ConnectorContext>,
 }
@@ -692,6 +886,7 @@ impl DeviceEndpointClient {
         device_endpoint_ref: DeviceEndpointRef,
         device_update_observation: azure_device_registry::DeviceUpdateObservation,
         asset_create_observation: deployment_artifacts::azure_device_registry::AssetCreateObservation,
+        ownership: Option<DeviceOwnership>,
         connector_context: Arc<ConnectorContext>,
         // TODO: This won't need to return an error once the service properly sends errors if the endpoint doesn't exist
     ) -> Result<Self, DeviceSpecificationError> {
@@ -731,6 +926,8 @@ impl DeviceEndpointClient {
             health_cancellation_token,
             asset_completion_rx,
             asset_completion_tx,
+            ownership,
+            reported_discovered_assets: ReportedDiscoveredAssets::default(),
             connector_context,
         })
     }
@@ -748,6 +945,10 @@ impl DeviceEndpointClient {
     /// Returns [`ClientNotification::Created`] with a new [`AssetClient`] if a new
     /// Asset has been created.
     ///
+    /// Returns [`ClientNotification::OwnershipLost`] if exclusive device ownership is
+    /// enabled and this connector instance no longer owns the Device Endpoint, and
+    /// [`ClientNotification::OwnershipRegained`] once it owns it again.
+    ///
     /// # Panics
     /// If the Azure Device Registry Service provides a notification that isn't for this Device Endpoint. This should not be possible.
     ///
@@ -765,6 +966,8 @@ impl DeviceEndpointClient {
                         // if the update notification is None, then the device endpoint has been deleted
                         // Cancel health reporting task
                         self.health_cancellation_token.cancel();
+                        // Release ownership of the device endpoint
+                        self.ownership = None;
                         // unobserve as cleanup
                         // Spawn a new task to prevent a possible cancellation and ensure the deleted
                         // notification reaches the application.
@@ -804,6 +1007,19 @@ impl DeviceEndpointClient {
                         }
                     }
                 },
+                // Check for changes in ownership
+                Some(ownership_event) = Self::recv_ownership_event(&mut self.ownership) => {
+                    match ownership_event {
+                        OwnershipEvent::Lost => {
+                            log::warn!("Lost ownership of device endpoint {:?}", self.device_endpoint_ref);
+                            return ClientNotification::OwnershipLost;
+                        }
+                        OwnershipEvent::Acquired => {
+                            log::info!("Regained ownership of device endpoint {:?}", self.device_endpoint_ref);
+                            return ClientNotification::OwnershipRegained;
+                        }
+                    }
+                },
                 // Check for completed asset creation
                 Some(asset_client_option) = self.asset_completion_rx.recv() => {
                     self.pending_asset_creation = false;
@@ -818,6 +1034,8 @@ impl DeviceEndpointClient {
                         log::info!("Device Endpoint Deletion detected, stopping device update observation for {:?}", self.device_endpoint_ref);
                         // Cancel health reporting task
                         self.health_cancellation_token.cancel();
+                        // Release ownership of the device endpoint
+                        self.ownership = None;
                         // unobserve as cleanup
                         // Spawn a new task to prevent a possible cancellation and ensure the deleted
                         // notification reaches the application.
@@ -857,6 +1075,99 @@ impl DeviceEndpointClient {
         }
     }
 
+    /// Reports an Asset discovered on this Device Endpoint to the Azure Device Registry service,
+    /// where it can be onboarded as an Asset. Datasets, events, streams and management groups
+    /// discovered on the Asset are reported as part of `discovered_asset`.
+    ///
+    /// The `device_ref` of `discovered_asset` is set to this Device Endpoint.
+    ///
+    /// A discovered asset that is identical to the last one reported with the same `asset_name`
+    /// through this [`DeviceEndpointClient`] is not reported again, so this can be called each time
+    /// the device is browsed. Timestamps such as `last_updated_on` are part of the comparison, so
+    /// they should only change when the discovered item itself changes.
+    ///
+    /// # Returns
+    /// - [`ModifyResult::Reported`] if the discovered asset was reported
+    /// - [`ModifyResult::NotModified`] if the same discovered asset has already been reported
+    ///
+    /// # Errors
+    /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
+    /// there are any underlying errors from the AIO RPC protocol. This error will be retried
+    /// 10 times with exponential backoff and jitter and only returned if it still is failing.
+    ///
+    /// [`azure_device_registry::Error`] of kind [`ValidationError`](azure_device_registry::ErrorKind::ValidationError)
+    /// if the asset name is empty.
+    ///
+    /// [`azure_device_registry::Error`] of kind [`ServiceError`](azure_device_registry::ErrorKind::ServiceError) if an error is returned
+    /// by the Azure Device Registry service.
+    ///
+    /// # Panics
+    /// if the reported discovered assets mutex has been poisoned, which should not be possible
+    pub async fn report_discovered_asset(
+        &self,
+        asset_name: String,
+        mut discovered_asset: adr_models::DiscoveredAsset,
+    ) -> Result<ModifyResult, azure_device_registry::Error> {
+        discovered_asset.device_ref = DeviceRef {
+            device_name: self.device_endpoint_ref.device_name.clone(),
+            endpoint_name: self.device_endpoint_ref.inbound_endpoint_name.clone(),
+        };
+
+        if self
+            .reported_discovered_assets
+            .is_reported(&asset_name, &discovered_asset)
+        {
+            log::debug!(
+                "Discovered asset {asset_name} on {:?} has already been reported; skipping",
+                self.device_endpoint_ref
+            );
+            return Ok(ModifyResult::NotModified);
+        }
+
+        let (discovery_id, version) = Retry::spawn(
+            RETRY_STRATEGY.map(tokio_retry2::strategy::jitter).take(10),
+            async || -> Result<(String, u64), RetryError<azure_device_registry::Error>> {
+                self.connector_context
+                    .azure_device_registry_client
+                    .create_or_update_discovered_asset(
+                        self.device_endpoint_ref.device_name.clone(),
+                        self.device_endpoint_ref.inbound_endpoint_name.clone(),
+                        asset_name.clone(),
+                        discovered_asset.clone(),
+                        self.connector_context.azure_device_registry_timeout,
+                    )
+                    .await
+                    .map_err(|e| match e.kind() {
+                        // an empty asset name is provided by the application, so don't treat it as unreachable
+                        azure_device_registry::ErrorKind::ValidationError(_) => {
+                            RetryError::permanent(e)
+                        }
+                        _ => adr_error_into_retry_error(e, "Create or Update Discovered Asset"),
+                    })
+            },
+        )
+        .await?;
+        log::info!(
+            "Reported discovered asset {asset_name} on {:?} with discovery ID {discovery_id} and version {version}",
+            self.device_endpoint_ref
+        );
+
+        self.reported_discovered_assets
+            .record(asset_name, discovered_asset);
+        Ok(ModifyResult::Reported)
+    }
+
+    /// Internal helper to receive the next change in ownership. Never completes if exclusive
+    /// device ownership isn't enabled.
+    async fn recv_ownership_event(
+        ownership: &mut Option<DeviceOwnership>,
+    ) -> Option<OwnershipEvent> {
+        match ownership {
+            Some(ownership) => ownership.recv_event().await,
+            None => std::future::pending().await,
+        }
+    }
+
     /// Creates a new status reporter for this [`DeviceEndpointClient`].
     /// The reporter's version snapshot is initialized to the current specification version.
     ///
@@ -1061,7 +1372,7 @@ impl AssetStatusReporter {
     ///
     /// # Returns
     /// - [`ModifyResult::Reported`] if the status was updated and successfully reported
-    /// - [`ModifyResult::NotModified`] if no modification was needed or the version changed during processing
+    /// - [`ModifyResult::NotModified`] if no modification was needed, the version changed during processing, or the connector is passive
     ///
     /// # Errors
     /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
@@ -1080,6 +1391,10 @@ impl AssetStatusReporter {
     where
         F: Fn(Option<Result<(), &AdrConfigError>>) -> Option<Result<(), AdrConfigError>>,
     {
+        if !self.connector_context.activation_gate.is_active() {
+            log::debug!("Connector is passive, not reporting status");
+            return Ok(ModifyResult::NotModified);
+        }
         // Get the current version of the asset specification
         let cached_version = self.asset_specification.read().unwrap().version;
 
@@ -1272,6 +1587,9 @@ pub struct AssetClient {
         (String, String), // (ManagementGroup name, ManagementAction name)
         watch::Sender<ManagementActionUpdateNotification>,
     >,
+    /// Resolves the secret references in the asset and dataset configurations
+    #[getter(skip)]
+    configuration_resolver: ConfigurationResolver,
     #[getter(skip)]
     connector_context: Arc<ConnectorContext>,
 }
@@ -1309,6 +1627,13 @@ impl AssetClient {
             event_hashmap: HashMap::new(),
             stream_hashmap: HashMap::new(),
             management_action_hashmap: HashMap::new(),
+            configuration_resolver: ConfigurationResolver::new(
+                &asset,
+                connector_context
+                    .connector_artifacts
+                    .device_endpoint_credentials_mount
+                    .clone(),
+            ),
             connector_context,
             release_asset_component_notifications_tx: watch::Sender::new(()),
             asset_deletion_token,
@@ -1563,41 +1888,57 @@ impl AssetClient {
                 .iter()
                 .any(|data_operation| data_operation.hash_name() == data_operation_name)
         });
-        // Get the new default data operation destinations and track whether they're different or not from the current one
-        let default_data_operation_destination_updated = match T::kind() {
-            DataOperationKind::Dataset => {
-                updated_asset.default_datasets_destinations
-                    != self
-                        .specification
-                        .read()
-                        .unwrap()
-                        .default_datasets_destinations
-            }
-            DataOperationKind::Event => {
-                updated_asset.default_events_destinations
-                    != self
-                        .specification
-                        .read()
-                        .unwrap()
-                        .default_events_destinations
-            }
-            DataOperationKind::Stream => {
-                updated_asset.default_streams_destinations
-                    != self
-                        .specification
-                        .read()
-                        .unwrap()
-                        .default_streams_destinations
-            }
-        };
+        // Get the new default data operation destinations and track whether they're different or not from the current one.
+        // A change to the asset's topic namespace also changes the destinations of all of its data operations
+        let topic_namespace_updated = updated_asset
+            .attributes
+            .get(destination_endpoint::TOPIC_NAMESPACE_KEY)
+            != self
+                .specification
+                .read()
+                .unwrap()
+                .attributes
+                .get(destination_endpoint::TOPIC_NAMESPACE_KEY);
+        let default_data_operation_destination_updated = topic_namespace_updated
+            || match T::kind() {
+                DataOperationKind::Dataset => {
+                    updated_asset.default_datasets_destinations
+                        != self
+                            .specification
+                            .read()
+                            .unwrap()
+                            .default_datasets_destinations
+                }
+                DataOperationKind::Event => {
+                    updated_asset.default_events_destinations
+                        != self
+                            .specification
+                            .read()
+                            .unwrap()
+                            .default_events_destinations
+                }
+                DataOperationKind::Stream => {
+                    updated_asset.default_streams_destinations
+                        != self
+                            .specification
+                            .read()
+                            .unwrap()
+                            .default_streams_destinations
+                }
+            };
 
-        let default_destinations_result = match T::kind() {
+        let default_destinations_result = destination_endpoint::asset_topic_namespace(
+            &updated_asset.attributes,
+            self.connector_context.default_topic_namespace.as_deref(),
+        )
+        .and_then(|asset_topic_namespace| match T::kind() {
             DataOperationKind::Dataset => {
                 destination_endpoint::Destination::new_dataset_destinations(
                     &updated_asset.default_datasets_destinations,
                     &self.asset_ref,
                     updated_asset.uuid.as_ref(),
                     updated_asset.external_asset_id.as_ref(),
+                    asset_topic_namespace.as_deref(),
                     &self.connector_context,
                 )
             }
@@ -1611,6 +1952,7 @@ impl AssetClient {
                     &self.asset_ref,
                     updated_asset.uuid.as_ref(),
                     updated_asset.external_asset_id.as_ref(),
+                    asset_topic_namespace.as_deref(),
                     &self.connector_context,
                 )
             }
@@ -1620,10 +1962,11 @@ impl AssetClient {
                     &self.asset_ref,
                     updated_asset.uuid.as_ref(),
                     updated_asset.external_asset_id.as_ref(),
+                    asset_topic_namespace.as_deref(),
                     &self.connector_context,
                 )
             }
-        };
+        });
         let default_data_operation_destinations = match default_destinations_result {
             Ok(res) => res.into_iter().map(Arc::new).collect(),
             Err(e) => {
@@ -1844,6 +2187,7 @@ impl AssetClient {
         }
 
         // update specification
+        self.configuration_resolver.update(&updated_asset);
         let mut unlocked_specification = self.specification.write().unwrap(); // unwrap can't fail unless lock is poisoned
         *unlocked_specification = AssetSpecification::from(updated_asset);
 
@@ -1895,7 +2239,8 @@ impl AssetClient {
     /// Registry Service.
     ///
     /// Returns [`ClientNotification::Updated`] if the Asset Specification has
-    /// been updated in place.
+    /// been updated in place, or if the [`AssetClient::resolved_configuration`] changed because
+    /// a referenced secret changed.
     ///
     /// Returns [`ClientNotification::Deleted`] if the Asset has been deleted.
     /// The [`AssetClient`] should not be used after this point, and no more
@@ -1977,6 +2322,10 @@ impl AssetClient {
 
                 self.handle_update(updated_asset).await
             }
+            () = self.configuration_resolver.secrets_changed() => {
+                log::info!("Secrets referenced by the configuration of asset {:?} changed", self.asset_ref);
+                ClientNotification::Updated
+            },
             create_notification = self.asset_component_creation_rx.recv() => {
                 let Some(asset_component_client) = create_notification else {
                     // unobserve as cleanup
@@ -2006,6 +2355,33 @@ impl AssetClient {
         (*self.specification.read().unwrap()).clone()
     }
 
+    /// Returns the asset's default datasets configuration and dataset configurations with all
+    /// secret references (`{"$secretRef": "<name>"}`) resolved against the device endpoint
+    /// credentials mount. See [`secret_reference`](crate::base_connector::secret_reference).
+    ///
+    /// The resolved configuration is kept up to date with the asset specification, and
+    /// [`AssetClient::recv_notification`] returns [`ClientNotification::Updated`] if it changes
+    /// because a referenced secret changed.
+    ///
+    /// # Errors
+    /// [`SecretReferenceError`] if a configuration is not valid JSON, contains an invalid secret
+    /// reference, or references a secret that can't be read. It can be reported to ADR with
+    /// [`AssetStatusReporter::report_status_if_modified`] after conversion into an [`AdrConfigError`].
+    pub fn resolved_configuration(&self) -> Result<ResolvedConfiguration, SecretReferenceError> {
+        self.configuration_resolver.resolved()
+    }
+
+    /// Returns whether the current asset specification defines any datasets, including ones
+    /// that were invalid.
+    ///
+    /// When there are none, no dataset will validate the asset's
+    /// [`default_datasets_configuration`](AssetSpecification::default_datasets_configuration),
+    /// so it should be validated at the asset level if it is set.
+    #[must_use]
+    pub fn has_datasets(&self) -> bool {
+        !self.dataset_hashmap.is_empty()
+    }
+
     /// Returns a clone of the current asset status
     /// Note that this is the value of the last reported status (or the status
     /// from when the asset was first received if one hasn't been reported yet),
@@ -2201,7 +2577,14 @@ impl<T: AssetComponentRef> AssetComponentStatusReporter<T> {
     ///
     /// The version used for reporting is the snapshotted version from the last call to
     /// `refresh_health_version()` or `pause_and_refresh_health_version()`, not necessarily the current specification version.
+    ///
+    /// While the connector is passive, a standby health event is reported instead. See
+    /// [`ActivationGate`](crate::base_connector::activation::ActivationGate).
     pub fn report_health_event(&self, health_event: RuntimeHealthEvent) {
+        let health_event = self
+            .connector_context
+            .activation_gate
+            .health_event(health_event);
         let (status, message, reason_code) = match health_event {
             RuntimeHealthEvent::Available => (HealthStatus::Available, None, None),
             RuntimeHealthEvent::Unavailable {
@@ -2257,6 +2640,35 @@ impl<T: AssetComponentRef> AssetComponentStatusReporter<T> {
         self.refresh_health_version();
     }
 
+    /// Reports a typed [`Status`] for the asset component.
+    ///
+    /// The `config` section is reported as the asset component status only if it differs from the current status,
+    /// and the `runtime` section, if present, is reported as a runtime health event.
+    ///
+    /// # Returns
+    /// - [`ModifyResult::Reported`] if the `config` section was reported
+    /// - [`ModifyResult::NotModified`] if the `config` section was already current, the version changed during processing, or the connector is passive
+    ///
+    /// # Errors
+    /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
+    /// there are any underlying errors from the AIO RPC protocol. This error will be retried
+    /// 10 times with exponential backoff and jitter and only returned if it still is failing.
+    ///
+    /// [`azure_device_registry::Error`] of kind [`ServiceError`](azure_device_registry::ErrorKind::ServiceError) if an error is returned
+    /// by the Azure Device Registry service.
+    pub async fn report_status(
+        &self,
+        status: Status,
+    ) -> Result<ModifyResult, azure_device_registry::Error> {
+        let result = self
+            .report_status_if_modified(|current| status.config_if_modified(current))
+            .await?;
+        if let Some(runtime) = status.runtime() {
+            self.report_health_event(runtime.clone());
+        }
+        Ok(result)
+    }
+
     /// Used to conditionally report the asset component status and then updates the asset with the new status returned.
     ///
     /// The `modify` function is called with the current asset component status (if any) and should return:
@@ -2265,7 +2677,7 @@ impl<T: AssetComponentRef> AssetComponentStatusReporter<T> {
     ///
     /// # Returns
     /// - [`ModifyResult::Reported`] if the status was updated and successfully reported
-    /// - [`ModifyResult::NotModified`] if no modification was needed or the version changed during processing
+    /// - [`ModifyResult::NotModified`] if no modification was needed, the version changed during processing, or the connector is passive
     ///
     /// # Errors
     /// [`azure_device_registry::Error`] of kind [`AIOProtocolError`](azure_device_registry::ErrorKind::AIOProtocolError) if
@@ -2284,6 +2696,10 @@ impl<T: AssetComponentRef> AssetComponentStatusReporter<T> {
     where
         F: Fn(Option<Result<(), &AdrConfigError>>) -> Option<Result<(), AdrConfigError>>,
     {
+        if !self.connector_context.activation_gate.is_active() {
+            log::debug!("Connector is passive, not reporting status");
+            return Ok(ModifyResult::NotModified);
+        }
         // Get the current version of the asset specification
         let cached_version = self.asset_specification.read().unwrap().version;
 
@@ -2392,6 +2808,109 @@ pub trait UnsupportedComponentClient {
     ) -> impl std::future::Future<Output = UnsupportedComponentNotification> + Send;
 }
 
+/// A trait to receive the [`ClientNotification`]s of a Client generically, so that the handler of a
+/// [`DeviceEndpointClient`] or [`AssetClient`] can be tested without the Azure Device Registry
+/// Service, by injecting notifications with the
+/// `base_connector::test_harness` (requires the `test-utils` feature).
+pub trait ClientNotificationReceiver: Send {
+    /// The type of the Client included in a [`ClientNotification::Created`]
+    type Created;
+    /// Receives the next [`ClientNotification`] for this Client
+    fn recv_notification(
+        &mut self,
+    ) -> impl std::future::Future<Output = ClientNotification<Self::Created>> + Send;
+}
+
+impl ClientNotificationReceiver for DeviceEndpointClient {
+    type Created = AssetClient;
+    /// See [`DeviceEndpointClient::recv_notification`]
+    fn recv_notification(
+        &mut self,
+    ) -> impl std::future::Future<Output = ClientNotification<AssetClient>> + Send {
+        DeviceEndpointClient::recv_notification(self)
+    }
+}
+
+impl ClientNotificationReceiver for AssetClient {
+    type Created = AssetComponentClient;
+    /// See [`AssetClient::recv_notification`]
+    fn recv_notification(
+        &mut self,
+    ) -> impl std::future::Future<Output = ClientNotification<AssetComponentClient>> + Send {
+        AssetClient::recv_notification(self)
+    }
+}
+
+/// A trait for the operations of a [`DataOperationClient`] used by a Data Operation handler, so
+/// that the handler can be tested without the Azure Device Registry Service or a destination, by
+/// injecting notifications and asserting on the reported statuses and message schemas and the
+/// forwarded data with the `base_connector::test_harness` (requires the
+/// `test-utils` feature).
+pub trait DataOperationHandle: Send {
+    /// Returns the definition of the Data Operation
+    fn definition(&self) -> &DataOperationDefinition;
+    /// Receives the next [`DataOperationNotification`]. See [`DataOperationClient::recv_notification`]
+    fn recv_notification(
+        &mut self,
+    ) -> impl std::future::Future<Output = DataOperationNotification> + Send;
+    /// Forwards data to the destination. See [`DataOperationClient::forward_data`]
+    fn forward_data(
+        &self,
+        data: Data,
+    ) -> impl std::future::Future<
+        Output = Result<Option<DeliveryReceipt>, destination_endpoint::Error>,
+    > + Send;
+    /// Reports the [`Status`] of the Data Operation. See [`AssetComponentStatusReporter::report_status`]
+    fn report_status(
+        &self,
+        status: Status,
+    ) -> impl std::future::Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send;
+    /// Reports the [`MessageSchema`] of the Data Operation, unless it is equivalent to the one
+    /// already reported. See [`DataOperationClient::report_message_schema_if_modified`]
+    fn report_message_schema(
+        &mut self,
+        message_schema: MessageSchema,
+    ) -> impl std::future::Future<Output = Result<SchemaModifyResult, MessageSchemaError>> + Send;
+}
+
+impl DataOperationHandle for DataOperationClient {
+    fn definition(&self) -> &DataOperationDefinition {
+        &self.definition
+    }
+
+    fn recv_notification(
+        &mut self,
+    ) -> impl std::future::Future<Output = DataOperationNotification> + Send {
+        DataOperationClient::recv_notification(self)
+    }
+
+    fn forward_data(
+        &self,
+        data: Data,
+    ) -> impl std::future::Future<
+        Output = Result<Option<DeliveryReceipt>, destination_endpoint::Error>,
+    > + Send {
+        DataOperationClient::forward_data(self, data)
+    }
+
+    fn report_status(
+        &self,
+        status: Status,
+    ) -> impl std::future::Future<Output = Result<ModifyResult, azure_device_registry::Error>> + Send
+    {
+        let status_reporter = self.get_status_reporter();
+        async move { status_reporter.report_status(status).await }
+    }
+
+    async fn report_message_schema(
+        &mut self,
+        message_schema: MessageSchema,
+    ) -> Result<SchemaModifyResult, MessageSchemaError> {
+        self.report_message_schema_if_modified(|_| Some(message_schema.clone()))
+            .await
+    }
+}
+
 /// A cloneable status reporter for Data Operation status reporting.
 ///
 /// This provides a way to report Data Operation status changes from outside the [`DataOperationClient`].
@@ -2578,6 +3097,47 @@ pub struct DataOperationClient {
     /// Cancellation token for health reporting task - cancelled on deletion
     #[getter(skip)]
     health_cancellation_token: CancellationToken,
+    /// Tracks whether the data operation has been deleted and how long it can still forward data
+    #[getter(skip)]
+    deletion: DeletionGracePeriod,
+    /// The last message schema (in canonical form) reported by this client and its reference
+    #[getter(skip)]
+    last_reported_message_schema: Option<(MessageSchema, MessageSchemaReference)>,
+}
+
+/// Tracks the deletion of an asset component and the grace period during which it may still forward data
+#[derive(Debug, Clone, Copy)]
+struct DeletionGracePeriod {
+    /// How long forwarding remains possible after deletion
+    grace_period: Duration,
+    /// When the deletion was first observed, if it has been
+    deleted_at: Option<Instant>,
+}
+
+impl DeletionGracePeriod {
+    fn new(grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            deleted_at: None,
+        }
+    }
+
+    /// Records the deletion. Only the first deletion starts the grace period.
+    fn mark_deleted(&mut self) {
+        if self.deleted_at.is_none() {
+            self.deleted_at = Some(Instant::now());
+        }
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Returns whether the data operation has been deleted and the grace period has elapsed
+    fn grace_period_elapsed(&self) -> bool {
+        self.deleted_at
+            .is_some_and(|deleted_at| deleted_at.elapsed() >= self.grace_period)
+    }
 }
 
 /// Creates a health reporter sender for a data operation.
@@ -2652,55 +3212,63 @@ impl DataOperationClient {
                 device_spec.external_device_id.clone(),
             )
         };
-        let (asset_uuid, asset_external_asset_id) = {
+        let (asset_uuid, asset_external_asset_id, asset_topic_namespace) = {
             let asset_spec = asset_specification.read().unwrap();
             (
                 asset_spec.uuid.clone(),
                 asset_spec.external_asset_id.clone(),
+                destination_endpoint::asset_topic_namespace(
+                    &asset_spec.attributes,
+                    connector_context.default_topic_namespace.as_deref(),
+                ),
             )
         };
-        let forwarder_res = match definition {
-            DataOperationDefinition::Dataset(ref dataset) => {
-                destination_endpoint::Forwarder::new_dataset_forwarder(
-                    dataset,
-                    default_destinations,
-                    &asset_ref,
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    connector_context.clone(),
-                )
-            }
-            DataOperationDefinition::Event(ref event) => {
-                destination_endpoint::Forwarder::new_event_stream_forwarder(
-                    &event.destinations,
-                    default_destinations,
-                    &data_operation_ref,
-                    event.data_source.clone(),
-                    event.type_ref.clone(),
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    connector_context.clone(),
-                )
-            }
-            DataOperationDefinition::Stream(ref stream) => {
-                destination_endpoint::Forwarder::new_event_stream_forwarder(
-                    &stream.destinations,
-                    default_destinations,
-                    &data_operation_ref,
-                    None,
-                    stream.type_ref.clone(),
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    connector_context.clone(),
-                )
-            }
-        };
+        let forwarder_res =
+            asset_topic_namespace.and_then(|asset_topic_namespace| match definition {
+                DataOperationDefinition::Dataset(ref dataset) => {
+                    destination_endpoint::Forwarder::new_dataset_forwarder(
+                        dataset,
+                        default_destinations,
+                        &asset_ref,
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace,
+                        connector_context.clone(),
+                    )
+                }
+                DataOperationDefinition::Event(ref event) => {
+                    destination_endpoint::Forwarder::new_event_stream_forwarder(
+                        &event.destinations,
+                        default_destinations,
+                        &data_operation_ref,
+                        event.data_source.clone(),
+                        event.type_ref.clone(),
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace.as_deref(),
+                        connector_context.clone(),
+                    )
+                }
+                DataOperationDefinition::Stream(ref stream) => {
+                    destination_endpoint::Forwarder::new_event_stream_forwarder(
+                        &stream.destinations,
+                        default_destinations,
+                        &data_operation_ref,
+                        None,
+                        stream.type_ref.clone(),
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace.as_deref(),
+                        connector_context.clone(),
+                    )
+                }
+            });
         let health_cancellation_token = CancellationToken::new();
         let health_sender = new_data_operation_health_sender(
             &connector_context,
@@ -2726,11 +3294,13 @@ impl DataOperationClient {
                 device_specification,
                 device_status,
                 forwarder,
+                deletion: DeletionGracePeriod::new(connector_context.deletion_grace_period),
                 connector_context,
                 asset_ref,
                 data_operation_update_watcher_rx,
                 health_sender,
                 health_cancellation_token,
+                last_reported_message_schema: None,
             },
             res,
         )
@@ -2854,9 +3424,13 @@ impl DataOperationClient {
     /// - `Some(new_message_schema)` if the schema should be updated and reported
     /// - `None` if no update is needed
     ///
+    /// If the returned schema is semantically equal (see [`message_schema::is_equivalent`]) to the
+    /// last schema reported by this client, and the current message schema reference is still the
+    /// one that was reported for it, the schema is not put in the Schema Registry again.
+    ///
     /// # Returns
     /// - [`SchemaModifyResult::Reported`] if the schema was updated and successfully reported, containing the reported [`MessageSchemaReference`]
-    /// - [`SchemaModifyResult::NotModified`] if no modification was needed or the version changed during processing
+    /// - [`SchemaModifyResult::NotModified`] if no modification was needed, the schema is equivalent to the one already reported, or the version changed during processing
     ///
     /// # Errors
     /// [`MessageSchemaError`] of kind [`SchemaRegistryError::InvalidRequestArgument`](schema_registry::ErrorKind::InvalidRequestArgument)
@@ -2927,6 +3501,19 @@ impl DataOperationClient {
             return Ok(SchemaModifyResult::NotModified);
         };
 
+        if let Some((last_reported_schema, last_reported_reference)) =
+            &self.last_reported_message_schema
+            && modify_input == Some(last_reported_reference)
+            && message_schema::is_equivalent(last_reported_schema, &new_message_schema)
+        {
+            // The schema only differs from the one already reported in formatting, so there's no need to put it again
+            log::debug!(
+                "Message schema for {:?} is equivalent to the one already reported, will not modify",
+                self.data_operation_ref
+            );
+            return Ok(SchemaModifyResult::NotModified);
+        }
+
         let mut asset_status_to_report = current_asset_status.into_owned();
 
         asset_status_to_report.config = match asset_status_to_report.config {
@@ -3005,6 +3592,11 @@ impl DataOperationClient {
         )
         .await?;
 
+        self.last_reported_message_schema = Some((
+            message_schema::canonicalize(&new_message_schema),
+            message_schema_reference.clone(),
+        ));
+
         Ok(SchemaModifyResult::Reported(message_schema_reference))
     }
 
@@ -3184,7 +3776,9 @@ impl DataOperationClient {
     }
 
     /// Used to send transformed data to the destination
-    /// Returns once the message has been sent successfully
+    /// Returns once the message has been sent successfully, with a [`DeliveryReceipt`] that can be
+    /// used to track the latency of forwarding data, or `None` if the data was filtered out by the
+    /// transforms declared in the dataset configuration.
     /// Use `forward_data_provide_protocol_specific_identifier` if it is desired to
     /// provide a Protocol Specific Identifier to be used on the Cloud Event `source`
     /// header used if the destination is `MQTT`. If this fn is used, the Cloud Event Header
@@ -3202,12 +3796,29 @@ impl DataOperationClient {
     ///
     /// [`destination_endpoint::Error`] of kind [`MqttTelemetryError`](destination_endpoint::ErrorKind::MqttTelemetryError)
     /// if the destination is `Mqtt` and there are any errors sending the message to the broker
-    pub async fn forward_data(&self, data: Data) -> Result<(), destination_endpoint::Error> {
-        self.forwarder.send_data(data, None).await
+    ///
+    /// [`destination_endpoint::Error`] of kind [`Deleted`](destination_endpoint::ErrorKind::Deleted)
+    /// if the Data Operation has been deleted and the deletion grace period has elapsed
+    ///
+    /// [`destination_endpoint::Error`] of kind [`Passive`](destination_endpoint::ErrorKind::Passive)
+    /// if the connector is passive and [`PassiveForwarding::Error`](crate::base_connector::activation::PassiveForwarding::Error)
+    /// is configured. See [`ActivationGate`](crate::base_connector::activation::ActivationGate)
+    pub async fn forward_data(
+        &self,
+        data: Data,
+    ) -> Result<Option<DeliveryReceipt>, destination_endpoint::Error> {
+        if self.deletion.grace_period_elapsed() {
+            return Err(destination_endpoint::ErrorKind::Deleted.into());
+        }
+        self.connector_context
+            .activation_gate
+            .write_if_active(self.forwarder.send_data(data, None))
+            .await
     }
 
     /// Used to send transformed data to the destination
-    /// Returns once the message has been sent successfully.
+    /// Returns once the message has been sent successfully, with a [`DeliveryReceipt`], or `None`
+    /// if the data was filtered out by the transforms declared in the dataset configuration.
     /// `protocol_specific_identifier` will be used on the Cloud Event
     /// `source` header used if the destination is `MQTT`. If `forward_data` is used instead of this fn,
     /// the Cloud Event Header will default to using either the device external device id or the device name.
@@ -3225,13 +3836,27 @@ impl DataOperationClient {
     ///
     /// [`destination_endpoint::Error`] of kind [`MqttTelemetryError`](destination_endpoint::ErrorKind::MqttTelemetryError)
     /// if the destination is `Mqtt` and there are any errors sending the message to the broker
+    ///
+    /// [`destination_endpoint::Error`] of kind [`Deleted`](destination_endpoint::ErrorKind::Deleted)
+    /// if the Data Operation has been deleted and the deletion grace period has elapsed
+    ///
+    /// [`destination_endpoint::Error`] of kind [`Passive`](destination_endpoint::ErrorKind::Passive)
+    /// if the connector is passive and [`PassiveForwarding::Error`](crate::base_connector::activation::PassiveForwarding::Error)
+    /// is configured. See [`ActivationGate`](crate::base_connector::activation::ActivationGate)
     pub async fn forward_data_provide_protocol_specific_identifier(
         &self,
         data: Data,
         protocol_specific_identifier: &str,
-    ) -> Result<(), destination_endpoint::Error> {
-        self.forwarder
-            .send_data(data, Some(protocol_specific_identifier))
+    ) -> Result<Option<DeliveryReceipt>, destination_endpoint::Error> {
+        if self.deletion.grace_period_elapsed() {
+            return Err(destination_endpoint::ErrorKind::Deleted.into());
+        }
+        self.connector_context
+            .activation_gate
+            .write_if_active(
+                self.forwarder
+                    .send_data(data, Some(protocol_specific_identifier)),
+            )
             .await
     }
 
@@ -3246,8 +3871,10 @@ impl DataOperationClient {
     /// for the Data Operation was detected. The definition is still updated in place, but the [`DataOperationClient`] should not be
     /// used until there is a new update because forwarding the data will not be possible.
     ///
-    /// Returns [`DataOperationNotification::Deleted`] if the Data Operation has been deleted. The [`DataOperationClient`]
-    /// should not be used after this point, and no more notifications will be received.
+    /// Returns [`DataOperationNotification::Deleted`] if the Data Operation has been deleted. No other notifications
+    /// will be received after this point and any further calls will return [`DataOperationNotification::Deleted`].
+    /// Data can still be forwarded for the configured deletion grace period so that buffered samples can be flushed,
+    /// after which [`DataOperationClient::final_flush`] should be called to send a tombstone and release the client.
     ///
     /// # Panics
     /// If the asset specification mutex has been poisoned, which should not be possible
@@ -3257,6 +3884,10 @@ impl DataOperationClient {
     /// completes first, then it is guaranteed that no data operation notifications will be lost, and the data operation will not
     /// be updated without a notification being returned.
     pub async fn recv_notification(&mut self) -> DataOperationNotification {
+        // Once deleted, never surface an update, even if one was pending
+        if self.deletion.is_deleted() {
+            return DataOperationNotification::Deleted;
+        }
         if self
             .data_operation_update_watcher_rx
             .changed()
@@ -3265,6 +3896,7 @@ impl DataOperationClient {
         {
             // Cancel health reporting task on deletion
             self.health_cancellation_token.cancel();
+            self.deletion.mark_deleted();
             return DataOperationNotification::Deleted;
         }
         // In case this function gets cancelled the next time it is called we will process the update again.
@@ -3281,6 +3913,7 @@ impl DataOperationClient {
             self.data_operation_update_watcher_rx.mark_unchanged();
             // Cancel health reporting task on deletion
             self.health_cancellation_token.cancel();
+            self.deletion.mark_deleted();
             return DataOperationNotification::Deleted;
         }
         let data_operation_changed = update_notification.definition != self.definition;
@@ -3309,55 +3942,63 @@ impl DataOperationClient {
                 device_spec.external_device_id.clone(),
             )
         };
-        let (asset_uuid, asset_external_asset_id) = {
+        let (asset_uuid, asset_external_asset_id, asset_topic_namespace) = {
             let asset_spec = self.asset_specification.read().unwrap();
             (
                 asset_spec.uuid.clone(),
                 asset_spec.external_asset_id.clone(),
+                destination_endpoint::asset_topic_namespace(
+                    &asset_spec.attributes,
+                    self.connector_context.default_topic_namespace.as_deref(),
+                ),
             )
         };
-        let forwarder_result = match self.definition {
-            DataOperationDefinition::Dataset(ref updated_dataset) => {
-                destination_endpoint::Forwarder::new_dataset_forwarder(
-                    updated_dataset,
-                    &update_notification.default_destinations,
-                    &self.asset_ref,
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    self.connector_context.clone(),
-                )
-            }
-            DataOperationDefinition::Event(ref updated_event) => {
-                destination_endpoint::Forwarder::new_event_stream_forwarder(
-                    &updated_event.destinations,
-                    &update_notification.default_destinations,
-                    &self.data_operation_ref,
-                    updated_event.data_source.clone(),
-                    updated_event.type_ref.clone(),
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    self.connector_context.clone(),
-                )
-            }
-            DataOperationDefinition::Stream(ref updated_stream) => {
-                destination_endpoint::Forwarder::new_event_stream_forwarder(
-                    &updated_stream.destinations,
-                    &update_notification.default_destinations,
-                    &self.data_operation_ref,
-                    None,
-                    updated_stream.type_ref.clone(),
-                    device_uuid,
-                    device_external_device_id,
-                    asset_uuid.as_ref(),
-                    asset_external_asset_id.as_ref(),
-                    self.connector_context.clone(),
-                )
-            }
-        };
+        let forwarder_result =
+            asset_topic_namespace.and_then(|asset_topic_namespace| match self.definition {
+                DataOperationDefinition::Dataset(ref updated_dataset) => {
+                    destination_endpoint::Forwarder::new_dataset_forwarder(
+                        updated_dataset,
+                        &update_notification.default_destinations,
+                        &self.asset_ref,
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace,
+                        self.connector_context.clone(),
+                    )
+                }
+                DataOperationDefinition::Event(ref updated_event) => {
+                    destination_endpoint::Forwarder::new_event_stream_forwarder(
+                        &updated_event.destinations,
+                        &update_notification.default_destinations,
+                        &self.data_operation_ref,
+                        updated_event.data_source.clone(),
+                        updated_event.type_ref.clone(),
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace.as_deref(),
+                        self.connector_context.clone(),
+                    )
+                }
+                DataOperationDefinition::Stream(ref updated_stream) => {
+                    destination_endpoint::Forwarder::new_event_stream_forwarder(
+                        &updated_stream.destinations,
+                        &update_notification.default_destinations,
+                        &self.data_operation_ref,
+                        None,
+                        updated_stream.type_ref.clone(),
+                        device_uuid,
+                        device_external_device_id,
+                        asset_uuid.as_ref(),
+                        asset_external_asset_id.as_ref(),
+                        asset_topic_namespace.as_deref(),
+                        self.connector_context.clone(),
+                    )
+                }
+            });
         self.forwarder = match forwarder_result {
             Ok(forwarder) => DataOperationForwarder::Forwarder(Box::new(forwarder)),
             Err(e) => {
@@ -3381,6 +4022,45 @@ impl DataOperationClient {
         }
     }
 
+    /// Returns whether this Data Operation has been deleted
+    #[must_use]
+    pub fn is_deleted(&self) -> bool {
+        self.deletion.is_deleted()
+    }
+
+    /// Sends a tombstone to the destination and releases the [`DataOperationClient`].
+    ///
+    /// This is intended to be called once the Data Operation has been deleted and any buffered data has
+    /// been forwarded. For `BrokerStateStore` destinations, the key is deleted. For `Mqtt` destinations,
+    /// an empty retained message is published to clear any retained message on the topic.
+    ///
+    /// # Errors
+    /// [`destination_endpoint::Error`] of kind [`Deleted`](destination_endpoint::ErrorKind::Deleted)
+    /// if the Data Operation has been deleted and the deletion grace period has elapsed
+    ///
+    /// [`destination_endpoint::Error`] of kind [`ValidationError`](destination_endpoint::ErrorKind::ValidationError)
+    /// if there isn't a valid destination configured for the data operation or the destination is `Storage`.
+    ///
+    /// [`destination_endpoint::Error`] of kind [`BrokerStateStoreError`](destination_endpoint::ErrorKind::BrokerStateStoreError)
+    /// if the destination is `BrokerStateStore` and there are any errors deleting the key with the service
+    ///
+    /// [`destination_endpoint::Error`] of kind [`MqttTelemetryError`](destination_endpoint::ErrorKind::MqttTelemetryError)
+    /// if the destination is `Mqtt` and there are any errors sending the message to the broker
+    ///
+    /// [`destination_endpoint::Error`] of kind [`Passive`](destination_endpoint::ErrorKind::Passive)
+    /// if the connector is passive and [`PassiveForwarding::Error`](crate::base_connector::activation::PassiveForwarding::Error)
+    /// is configured. See [`ActivationGate`](crate::base_connector::activation::ActivationGate)
+    pub async fn final_flush(self) -> Result<(), destination_endpoint::Error> {
+        if self.deletion.grace_period_elapsed() {
+            return Err(destination_endpoint::ErrorKind::Deleted.into());
+        }
+        self.connector_context
+            .activation_gate
+            .write_if_active(self.forwarder.send_tombstone())
+            .await
+        // internal resources are torn down when self is dropped here
+    }
+
     /// Creates a new status reporter for this [`DataOperationClient`]
     /// The reporter's version snapshot is initialized to the current asset specification version.
     ///
@@ -5664,4 +6344,107 @@ mod tests {
             DeviceSpecificationError::CredentialsMountPathMissing(_)
         ));
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn deletion_grace_period_forward_during_grace() {
+        let mut deletion = DeletionGracePeriod::new(Duration::from_secs(5));
+        assert!(!deletion.is_deleted());
+        assert!(!deletion.grace_period_elapsed());
+
+        deletion.mark_deleted();
+        assert!(deletion.is_deleted());
+        assert!(!deletion.grace_period_elapsed());
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert!(!deletion.grace_period_elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deletion_grace_period_forward_after_grace() {
+        let mut deletion = DeletionGracePeriod::new(Duration::from_secs(5));
+        deletion.mark_deleted();
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(deletion.grace_period_elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deletion_grace_period_not_restarted_by_repeated_deletion() {
+        let mut deletion = DeletionGracePeriod::new(Duration::from_secs(5));
+        deletion.mark_deleted();
+
+        tokio::time::advance(Duration::from_secs(3)).await;
+        // a repeated Deleted notification must not extend the grace period
+        deletion.mark_deleted();
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert!(deletion.is_deleted());
+        assert!(deletion.grace_period_elapsed());
+    }
+
+    fn discovered_asset(model: Option<&str>) -> adr_models::DiscoveredAsset {
+        adr_models::DiscoveredAsset {
+            asset_type_refs: vec![],
+            attributes: HashMap::default(),
+            datasets: vec![adr_models::DiscoveredDataset {
+                dataset_configuration: None,
+                data_points: vec![],
+                data_source: Some("ns=2;s=Temperature".to_string()),
+                destinations: vec![],
+                last_updated_on: None,
+                name: "temperature".to_string(),
+                type_ref: None,
+            }],
+            default_datasets_configuration: None,
+            default_datasets_destinations: vec![],
+            default_events_configuration: None,
+            default_events_destinations: vec![],
+            default_management_groups_configuration: None,
+            default_streams_configuration: None,
+            default_streams_destinations: vec![],
+            description: None,
+            device_ref: DeviceRef {
+                device_name: "device".to_string(),
+                endpoint_name: TEST_INBOUND_ENDPOINT_NAME.to_string(),
+            },
+            display_name: None,
+            documentation_uri: None,
+            event_groups: vec![],
+            external_asset_id: None,
+            hardware_revision: None,
+            management_groups: vec![],
+            manufacturer: None,
+            manufacturer_uri: None,
+            model: model.map(ToString::to_string),
+            product_code: None,
+            serial_number: None,
+            software_revision: None,
+            streams: vec![],
+        }
+    }
+
+    #[test]
+    fn reported_discovered_assets_dedup() {
+        let reported = ReportedDiscoveredAssets::default();
+        assert!(!reported.is_reported("asset", &discovered_asset(None)));
+
+        reported.record("asset".to_string(), discovered_asset(None));
+        // the same discovery isn't reported again
+        assert!(reported.is_reported("asset", &discovered_asset(None)));
+        // a changed discovery or a different asset name is reported
+        assert!(!reported.is_reported("asset", &discovered_asset(Some("model"))));
+        assert!(!reported.is_reported("other_asset", &discovered_asset(None)));
+
+        // only the last reported discovery is remembered
+        reported.record("asset".to_string(), discovered_asset(Some("model")));
+        assert!(!reported.is_reported("asset", &discovered_asset(None)));
+        assert!(reported.is_reported("asset", &discovered_asset(Some("model"))));
+    }
+
+    #[test]
+    fn zero_deletion_grace_period() {
+        let mut deletion = DeletionGracePeriod::new(Duration::ZERO);
+        assert!(!deletion.grace_period_elapsed());
+        deletion.mark_deleted();
+        assert!(deletion.grace_period_elapsed());
+    }
 }
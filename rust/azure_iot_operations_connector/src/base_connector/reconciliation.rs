@@ -0,0 +1,970 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Reconciliation of artifacts left behind at the destinations of deleted Datasets.
+//!
+//! When enabled with [`OptionsBuilder::reconciliation`](super::OptionsBuilder::reconciliation),
+//! the connector records the artifacts it creates at the destinations of Datasets in a manifest
+//! persisted in the State Store: the keys set by `BrokerStateStore` destinations and the retained
+//! messages published by `Mqtt` destinations. These artifacts are normally cleaned up when
+//! [`DataOperationClient::final_flush`](super::managed_azure_device_registry::DataOperationClient::final_flush)
+//! sends a tombstone, but a Dataset deleted while the connector isn't running never gets one.
+//!
+//! A sweep at startup, and periodically afterwards, cleans up the artifacts of every Dataset in
+//! the manifest that is no longer defined: State Store keys are deleted and an empty retained
+//! message is published on each topic. A Dataset is only considered deleted if its Device
+//! Endpoint or Asset is no longer in the file mount, or the Asset retrieved from the Azure Device
+//! Registry no longer contains it. If this can't be determined, its artifacts are left alone.
+//!
+//! Message Schemas registered for deleted Datasets are not cleaned up, since the Schema Registry
+//! doesn't support deleting schemas.
+//!
+//! The manifest is scoped to the connector ID, so reconciliation should only be enabled on a
+//! connector that runs a single instance.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+
+use azure_iot_operations_mqtt::{
+    control_packet::{PublishProperties, TopicName},
+    session::{SessionManagedClient, SessionMonitor},
+};
+use azure_iot_operations_services::{azure_device_registry, state_store};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    DataOperationName, DataOperationRef,
+    deployment_artifacts::azure_device_registry::{
+        DeviceEndpointRef, get_asset_names, get_device_endpoint_names, get_mount_path,
+    },
+};
+
+/// Prefix of the State Store key of the manifest, which is followed by the connector ID
+pub const MANIFEST_KEY_PREFIX: &str = "connector-reconciliation";
+
+/// Version of the manifest format
+const MANIFEST_VERSION: u32 = 1;
+
+/// Number of reports buffered for each [`ReportObservation`]
+const REPORT_CHANNEL_CAPACITY: usize = 16;
+
+/// Returns the State Store key of the manifest of the connector with the given connector ID
+#[must_use]
+pub fn manifest_key(connector_id: &str) -> Vec<u8> {
+    format!("{MANIFEST_KEY_PREFIX}/{connector_id}").into_bytes()
+}
+
+/// Options for reconciling the artifacts of deleted Datasets
+#[derive(Builder, Clone, Debug)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct ReconciliationOptions {
+    /// Interval between sweeps after the sweep at startup
+    #[builder(default = "Duration::from_secs(3600)")]
+    sweep_interval: Duration,
+    /// Minimum delay between the cleanup of two artifacts, to limit the load that a sweep puts
+    /// on the broker and the State Store
+    #[builder(default = "Duration::from_millis(100)")]
+    cleanup_interval: Duration,
+}
+
+impl ReconciliationOptionsBuilder {
+    /// Validate the [`ReconciliationOptions`].
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if the sweep interval is zero.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(sweep_interval) = self.sweep_interval
+            && sweep_interval.is_zero()
+        {
+            return Err("sweep_interval must not be zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An artifact created by the connector at the destination of a Dataset
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Artifact {
+    /// A key set in the State Store by a `BrokerStateStore` destination
+    StateStoreKey {
+        /// The State Store key
+        key: String,
+    },
+    /// A message retained by the broker on the topic of an `Mqtt` destination
+    RetainedMessage {
+        /// The topic of the retained message, including any topic namespace
+        topic: String,
+    },
+}
+
+impl std::fmt::Display for Artifact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Artifact::StateStoreKey { key } => write!(f, "State Store key '{key}'"),
+            Artifact::RetainedMessage { topic } => write!(f, "retained message on '{topic}'"),
+        }
+    }
+}
+
+/// Outcome of a reconciliation sweep
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReconciliationReport {
+    /// Artifacts that were cleaned up, with the deleted Dataset that created them
+    pub removed: Vec<(DataOperationRef, Artifact)>,
+    /// Artifacts that could not be cleaned up, with the deleted Dataset that created them and the
+    /// error. They are retried on the next sweep.
+    pub failed: Vec<(DataOperationRef, Artifact, String)>,
+    /// Whether the sweep was aborted before all artifacts of deleted Datasets were cleaned up.
+    /// The remaining artifacts are cleaned up on the next sweep.
+    pub aborted: bool,
+}
+
+/// Handle to the reconciliation of a [`BaseConnector`](super::BaseConnector), used to run or
+/// abort sweeps and to observe their reports
+#[derive(Clone)]
+pub struct ReconciliationHandle(pub(crate) Arc<Reconciler<ConnectorBackend>>);
+
+impl ReconciliationHandle {
+    /// Runs a sweep now, waiting for any sweep in progress to finish first
+    pub async fn sweep(&self) -> ReconciliationReport {
+        self.0.sweep().await
+    }
+
+    /// Aborts the sweep in progress, if any. Artifacts cleaned up so far are removed from the
+    /// manifest and the remaining ones are cleaned up on the next sweep.
+    pub fn abort_sweep(&self) {
+        self.0.abort_sweep();
+    }
+
+    /// Creates a new [`ReportObservation`] to receive the reports of the sweeps that end after
+    /// this call
+    #[must_use]
+    pub fn create_report_observation(&self) -> ReportObservation {
+        ReportObservation(self.0.reports_tx.subscribe())
+    }
+}
+
+/// Receives the [`ReconciliationReport`]s of sweeps
+pub struct ReportObservation(broadcast::Receiver<ReconciliationReport>);
+
+impl ReportObservation {
+    /// Receives the report of the next sweep, or `None` if there will be no more sweeps. Reports
+    /// that were not received before 16 newer ones are dropped.
+    pub async fn recv_report(&mut self) -> Option<ReconciliationReport> {
+        loop {
+            match self.0.recv().await {
+                Ok(report) => return Some(report),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("{skipped} reconciliation reports were dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Dataset identifier used in the manifest
+#[allow(clippy::struct_field_names)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DatasetId {
+    device_name: String,
+    inbound_endpoint_name: String,
+    asset_name: String,
+    dataset_name: String,
+}
+
+impl DatasetId {
+    /// Returns the [`DatasetId`] of a Data Operation, or `None` if it isn't a Dataset
+    fn from_data_operation_ref(data_operation_ref: &DataOperationRef) -> Option<Self> {
+        let DataOperationName::Dataset { name } = &data_operation_ref.data_operation_name else {
+            return None;
+        };
+        Some(Self {
+            device_name: data_operation_ref.device_name.clone(),
+            inbound_endpoint_name: data_operation_ref.inbound_endpoint_name.clone(),
+            asset_name: data_operation_ref.asset_name.clone(),
+            dataset_name: name.clone(),
+        })
+    }
+
+    fn to_data_operation_ref(&self) -> DataOperationRef {
+        DataOperationRef {
+            data_operation_name: DataOperationName::Dataset {
+                name: self.dataset_name.clone(),
+            },
+            asset_name: self.asset_name.clone(),
+            device_name: self.device_name.clone(),
+            inbound_endpoint_name: self.inbound_endpoint_name.clone(),
+        }
+    }
+}
+
+type Manifest = BTreeMap<DatasetId, BTreeSet<Artifact>>;
+
+/// Format of the manifest persisted in the State Store
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedManifest {
+    version: u32,
+    datasets: Vec<PersistedManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedManifestEntry {
+    #[serde(flatten)]
+    dataset: DatasetId,
+    artifacts: BTreeSet<Artifact>,
+}
+
+fn serialize_manifest(manifest: &Manifest) -> Vec<u8> {
+    serde_json::to_vec(&PersistedManifest {
+        version: MANIFEST_VERSION,
+        datasets: manifest
+            .iter()
+            .map(|(dataset, artifacts)| PersistedManifestEntry {
+                dataset: dataset.clone(),
+                artifacts: artifacts.clone(),
+            })
+            .collect(),
+    })
+    .expect("Manifest is always serializable")
+}
+
+fn deserialize_manifest(value: &[u8]) -> Result<Manifest, String> {
+    let persisted: PersistedManifest = serde_json::from_slice(value).map_err(|e| e.to_string())?;
+    if persisted.version > MANIFEST_VERSION {
+        return Err(format!(
+            "Manifest version {} is newer than the supported version {MANIFEST_VERSION}",
+            persisted.version
+        ));
+    }
+    Ok(persisted
+        .datasets
+        .into_iter()
+        .map(|entry| (entry.dataset, entry.artifacts))
+        .collect())
+}
+
+/// Services used to persist the manifest, look up Dataset definitions and clean up artifacts
+pub(crate) trait ReconciliationBackend: Send + Sync + 'static {
+    /// Loads the persisted manifest, if any
+    fn load_manifest(&self) -> impl Future<Output = Result<Option<Vec<u8>>, String>> + Send;
+    /// Persists the manifest
+    fn save_manifest(&self, manifest: Vec<u8>) -> impl Future<Output = Result<(), String>> + Send;
+    /// Returns whether the Dataset is currently defined
+    fn is_dataset_defined(
+        &self,
+        dataset_ref: &DataOperationRef,
+    ) -> impl Future<Output = Result<bool, String>> + Send;
+    /// Cleans up an artifact
+    fn clean_up(&self, artifact: &Artifact) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// [`ReconciliationBackend`] backed by the services used by the connector
+pub(crate) struct ConnectorBackend {
+    pub(crate) manifest_key: Vec<u8>,
+    pub(crate) state_store_client: Arc<state_store::Client>,
+    pub(crate) state_store_timeout: Duration,
+    pub(crate) azure_device_registry_client: azure_device_registry::Client,
+    pub(crate) azure_device_registry_timeout: Duration,
+    pub(crate) managed_client: SessionManagedClient,
+}
+
+impl ReconciliationBackend for ConnectorBackend {
+    async fn load_manifest(&self) -> Result<Option<Vec<u8>>, String> {
+        Ok(self
+            .state_store_client
+            .get(self.manifest_key.clone(), self.state_store_timeout)
+            .await
+            .map_err(|e| e.to_string())?
+            .response)
+    }
+
+    async fn save_manifest(&self, manifest: Vec<u8>) -> Result<(), String> {
+        self.state_store_client
+            .set(
+                self.manifest_key.clone(),
+                manifest,
+                self.state_store_timeout,
+                None,
+                state_store::SetOptions::default(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn is_dataset_defined(&self, dataset_ref: &DataOperationRef) -> Result<bool, String> {
+        let DataOperationName::Dataset { name } = &dataset_ref.data_operation_name else {
+            return Err(format!(
+                "{} is not a Dataset",
+                dataset_ref.data_operation_name
+            ));
+        };
+        let mount_path = get_mount_path().map_err(|e| e.to_string())?;
+        let device_endpoint_ref = DeviceEndpointRef {
+            device_name: dataset_ref.device_name.clone(),
+            inbound_endpoint_name: dataset_ref.inbound_endpoint_name.clone(),
+        };
+        if !get_device_endpoint_names(&mount_path)
+            .map_err(|e| e.to_string())?
+            .contains(&device_endpoint_ref)
+        {
+            return Ok(false);
+        }
+        if !get_asset_names(&mount_path, &device_endpoint_ref)
+            .map_err(|e| e.to_string())?
+            .iter()
+            .any(|asset_ref| asset_ref.name == dataset_ref.asset_name)
+        {
+            return Ok(false);
+        }
+        let asset = self
+            .azure_device_registry_client
+            .get_asset(
+                dataset_ref.device_name.clone(),
+                dataset_ref.inbound_endpoint_name.clone(),
+                dataset_ref.asset_name.clone(),
+                self.azure_device_registry_timeout,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(asset.datasets.iter().any(|dataset| dataset.name == *name))
+    }
+
+    async fn clean_up(&self, artifact: &Artifact) -> Result<(), String> {
+        match artifact {
+            Artifact::StateStoreKey { key } => {
+                // The number of keys deleted isn't relevant, since the key may already be gone
+                self.state_store_client
+                    .del(key.clone().into_bytes(), None, self.state_store_timeout)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Artifact::RetainedMessage { topic } => {
+                // An empty retained message clears the retained message on the topic
+                let topic = TopicName::new(topic).map_err(|e| e.to_string())?;
+                self.managed_client
+                    .publish_qos1(topic, true, Vec::new(), PublishProperties::default())
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .as_result()
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the artifacts of Datasets and cleans up those of deleted Datasets
+pub(crate) struct Reconciler<B: ReconciliationBackend> {
+    backend: B,
+    options: ReconciliationOptions,
+    /// Manifest loaded from the backend, `None` until it has been loaded successfully
+    manifest: tokio::sync::Mutex<Option<Manifest>>,
+    /// Ensures that only one sweep runs at a time
+    sweep_lock: tokio::sync::Mutex<()>,
+    /// Cancelled to abort the sweep in progress
+    sweep_cancellation_token: std::sync::Mutex<CancellationToken>,
+    reports_tx: broadcast::Sender<ReconciliationReport>,
+}
+
+impl<B: ReconciliationBackend> Reconciler<B> {
+    pub(crate) fn new(backend: B, options: ReconciliationOptions) -> Self {
+        let (reports_tx, _) = broadcast::channel(REPORT_CHANNEL_CAPACITY);
+        Self {
+            backend,
+            options,
+            manifest: tokio::sync::Mutex::new(None),
+            sweep_lock: tokio::sync::Mutex::new(()),
+            sweep_cancellation_token: std::sync::Mutex::new(CancellationToken::new()),
+            reports_tx,
+        }
+    }
+
+    /// Returns the manifest, loading it from the backend if it hasn't been loaded yet
+    async fn loaded_manifest<'a>(
+        &self,
+        manifest: &'a mut Option<Manifest>,
+    ) -> Result<&'a mut Manifest, String> {
+        if manifest.is_none() {
+            let loaded = match self.backend.load_manifest().await? {
+                Some(value) => deserialize_manifest(&value)?,
+                None => Manifest::new(),
+            };
+            *manifest = Some(loaded);
+        }
+        Ok(manifest.as_mut().expect("Manifest was just loaded"))
+    }
+
+    async fn save(&self, manifest: &Manifest) {
+        if let Err(e) = self
+            .backend
+            .save_manifest(serialize_manifest(manifest))
+            .await
+        {
+            // The manifest is kept in memory and persisted again on the next change
+            log::warn!("Failed to save the reconciliation manifest: {e}");
+        }
+    }
+
+    /// Records an artifact created at the destination of a Dataset. Returns whether the artifact
+    /// is recorded in the manifest, which is always `true` for Data Operations other than Datasets
+    /// since their artifacts aren't reconciled.
+    pub(crate) async fn record(
+        &self,
+        data_operation_ref: &DataOperationRef,
+        artifact: Artifact,
+    ) -> bool {
+        let Some(dataset) = DatasetId::from_data_operation_ref(data_operation_ref) else {
+            return true;
+        };
+        let mut manifest = self.manifest.lock().await;
+        let manifest = match self.loaded_manifest(&mut manifest).await {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                // Recording against an empty manifest would overwrite the persisted one
+                log::warn!(
+                    "Failed to load the reconciliation manifest, {artifact} of {} is not recorded: {e}",
+                    data_operation_ref.data_operation_name
+                );
+                return false;
+            }
+        };
+        if manifest.entry(dataset).or_default().insert(artifact) {
+            self.save(manifest).await;
+        }
+        true
+    }
+
+    /// Removes a Dataset whose artifacts have been cleaned up from the manifest
+    pub(crate) async fn forget(&self, data_operation_ref: &DataOperationRef) {
+        let Some(dataset) = DatasetId::from_data_operation_ref(data_operation_ref) else {
+            return;
+        };
+        let mut manifest = self.manifest.lock().await;
+        match self.loaded_manifest(&mut manifest).await {
+            Ok(manifest) => {
+                if manifest.remove(&dataset).is_some() {
+                    self.save(manifest).await;
+                }
+            }
+            Err(e) => {
+                // The artifacts are cleaned up again by a later sweep, which is harmless
+                log::warn!("Failed to load the reconciliation manifest: {e}");
+            }
+        }
+    }
+
+    /// Aborts the sweep in progress, if any
+    pub(crate) fn abort_sweep(&self) {
+        self.sweep_cancellation_token
+            .lock()
+            .expect("Sweep cancellation token mutex should not be poisoned")
+            .cancel();
+    }
+
+    /// Cleans up the artifacts of Datasets in the manifest that are no longer defined, and
+    /// publishes the report of the sweep
+    pub(crate) async fn sweep(&self) -> ReconciliationReport {
+        let _sweep_guard = self.sweep_lock.lock().await;
+        let cancellation_token = CancellationToken::new();
+        *self
+            .sweep_cancellation_token
+            .lock()
+            .expect("Sweep cancellation token mutex should not be poisoned") =
+            cancellation_token.clone();
+
+        let report = self.sweep_orphaned_artifacts(&cancellation_token).await;
+        if report.removed.is_empty() && report.failed.is_empty() {
+            log::debug!("Reconciliation sweep found no artifacts of deleted Datasets");
+        } else {
+            log::info!(
+                "Reconciliation sweep removed {} and failed to remove {} artifacts of deleted Datasets{}",
+                report.removed.len(),
+                report.failed.len(),
+                if report.aborted {
+                    ", and was aborted"
+                } else {
+                    ""
+                }
+            );
+        }
+        // There may not be any observations, which is fine
+        let _ = self.reports_tx.send(report.clone());
+        report
+    }
+
+    async fn sweep_orphaned_artifacts(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> ReconciliationReport {
+        let mut report = ReconciliationReport::default();
+
+        // Don't hold the manifest during the sweep, so that artifacts can still be recorded
+        let snapshot = {
+            let mut manifest = self.manifest.lock().await;
+            match self.loaded_manifest(&mut manifest).await {
+                Ok(manifest) => manifest.clone(),
+                Err(e) => {
+                    log::warn!("Failed to load the reconciliation manifest, skipping sweep: {e}");
+                    return report;
+                }
+            }
+        };
+
+        let mut cleaned_up = Vec::new();
+        let mut is_first_cleanup = true;
+        'sweep: for (dataset, artifacts) in snapshot {
+            let dataset_ref = dataset.to_data_operation_ref();
+            let is_defined = tokio::select! {
+                () = cancellation_token.cancelled() => {
+                    report.aborted = true;
+                    break 'sweep;
+                }
+                is_defined = self.backend.is_dataset_defined(&dataset_ref) => is_defined,
+            };
+            match is_defined {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => {
+                    log::warn!(
+                        "Could not determine whether {} of Asset '{}' is still defined, skipping its artifacts: {e}",
+                        dataset_ref.data_operation_name,
+                        dataset_ref.asset_name
+                    );
+                    continue;
+                }
+            }
+
+            for artifact in artifacts {
+                if !is_first_cleanup {
+                    tokio::select! {
+                        () = cancellation_token.cancelled() => {
+                            report.aborted = true;
+                            break 'sweep;
+                        }
+                        () = tokio::time::sleep(self.options.cleanup_interval) => {}
+                    }
+                }
+                is_first_cleanup = false;
+                if cancellation_token.is_cancelled() {
+                    report.aborted = true;
+                    break 'sweep;
+                }
+                match self.backend.clean_up(&artifact).await {
+                    Ok(()) => {
+                        log::info!(
+                            "Removed {artifact} of deleted {} of Asset '{}'",
+                            dataset_ref.data_operation_name,
+                            dataset_ref.asset_name
+                        );
+                        cleaned_up.push((dataset.clone(), artifact.clone()));
+                        report.removed.push((dataset_ref.clone(), artifact));
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to remove {artifact} of deleted {} of Asset '{}': {e}",
+                            dataset_ref.data_operation_name,
+                            dataset_ref.asset_name
+                        );
+                        report.failed.push((dataset_ref.clone(), artifact, e));
+                    }
+                }
+            }
+        }
+
+        if !cleaned_up.is_empty() {
+            let mut manifest = self.manifest.lock().await;
+            if let Some(manifest) = manifest.as_mut() {
+                for (dataset, artifact) in cleaned_up {
+                    if let Some(artifacts) = manifest.get_mut(&dataset) {
+                        artifacts.remove(&artifact);
+                        if artifacts.is_empty() {
+                            manifest.remove(&dataset);
+                        }
+                    }
+                }
+                self.save(manifest).await;
+            }
+        }
+        report
+    }
+}
+
+/// Runs a sweep once the session is connected, and then every
+/// [`sweep_interval`](ReconciliationOptionsBuilder::sweep_interval), until cancelled
+pub(crate) async fn run_sweeps<B: ReconciliationBackend>(
+    reconciler: Arc<Reconciler<B>>,
+    session_monitor: SessionMonitor,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            () = cancellation_token.cancelled() => break,
+            () = session_monitor.connected() => {}
+        }
+        tokio::select! {
+            () = cancellation_token.cancelled() => {
+                reconciler.abort_sweep();
+                break;
+            }
+            _ = reconciler.sweep() => {}
+        }
+        tokio::select! {
+            () = cancellation_token.cancelled() => break,
+            () = tokio::time::sleep(reconciler.options.sweep_interval) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Services shared by all [`FakeBackend`]s, which outlive a [`Reconciler`] across restarts
+    #[derive(Default)]
+    struct FakeServices {
+        manifest: Option<Vec<u8>>,
+        load_fails: bool,
+        defined_datasets: HashSet<String>,
+        undetermined_datasets: HashSet<String>,
+        failing_artifacts: HashSet<Artifact>,
+        cleaned_up: Vec<Artifact>,
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeBackend(Arc<Mutex<FakeServices>>);
+
+    impl FakeBackend {
+        fn services(&self) -> std::sync::MutexGuard<'_, FakeServices> {
+            self.0.lock().unwrap()
+        }
+
+        fn persisted_manifest(&self) -> Manifest {
+            deserialize_manifest(self.services().manifest.as_ref().unwrap()).unwrap()
+        }
+    }
+
+    impl ReconciliationBackend for FakeBackend {
+        async fn load_manifest(&self) -> Result<Option<Vec<u8>>, String> {
+            let services = self.services();
+            if services.load_fails {
+                return Err("load failed".to_string());
+            }
+            Ok(services.manifest.clone())
+        }
+
+        async fn save_manifest(&self, manifest: Vec<u8>) -> Result<(), String> {
+            self.services().manifest = Some(manifest);
+            Ok(())
+        }
+
+        async fn is_dataset_defined(&self, dataset_ref: &DataOperationRef) -> Result<bool, String> {
+            let services = self.services();
+            if services
+                .undetermined_datasets
+                .contains(&dataset_ref.asset_name)
+            {
+                return Err("lookup failed".to_string());
+            }
+            Ok(services.defined_datasets.contains(&dataset_ref.asset_name))
+        }
+
+        async fn clean_up(&self, artifact: &Artifact) -> Result<(), String> {
+            let mut services = self.services();
+            if services.failing_artifacts.contains(artifact) {
+                return Err("cleanup failed".to_string());
+            }
+            services.cleaned_up.push(artifact.clone());
+            Ok(())
+        }
+    }
+
+    /// Creates a reconciler as a newly started connector would
+    fn start_reconciler(backend: &FakeBackend) -> Reconciler<FakeBackend> {
+        Reconciler::new(
+            backend.clone(),
+            ReconciliationOptionsBuilder::default()
+                .cleanup_interval(CLEANUP_INTERVAL)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// Each dataset is under its own asset so that the fake can look it up by asset name
+    fn dataset_ref(asset_name: &str) -> DataOperationRef {
+        DataOperationRef {
+            data_operation_name: DataOperationName::Dataset {
+                name: "dataset".to_string(),
+            },
+            asset_name: asset_name.to_string(),
+            device_name: "device".to_string(),
+            inbound_endpoint_name: "endpoint".to_string(),
+        }
+    }
+
+    fn state_store_key(key: &str) -> Artifact {
+        Artifact::StateStoreKey {
+            key: key.to_string(),
+        }
+    }
+
+    fn retained_message(topic: &str) -> Artifact {
+        Artifact::RetainedMessage {
+            topic: topic.to_string(),
+        }
+    }
+
+    /// Creates a backend with a dataset under each of the given assets, each with a state store
+    /// key and a retained message recorded by a previous run of the connector
+    async fn backend_with_artifacts(asset_names: &[&str]) -> FakeBackend {
+        let backend = FakeBackend::default();
+        let reconciler = start_reconciler(&backend);
+        for asset_name in asset_names {
+            backend
+                .services()
+                .defined_datasets
+                .insert((*asset_name).to_string());
+            assert!(
+                reconciler
+                    .record(&dataset_ref(asset_name), state_store_key(asset_name))
+                    .await
+            );
+            assert!(
+                reconciler
+                    .record(&dataset_ref(asset_name), retained_message(asset_name))
+                    .await
+            );
+        }
+        backend
+    }
+
+    #[test]
+    fn zero_sweep_interval_invalid() {
+        assert!(
+            ReconciliationOptionsBuilder::default()
+                .sweep_interval(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+        assert!(ReconciliationOptionsBuilder::default().build().is_ok());
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let mut manifest = Manifest::new();
+        manifest.insert(
+            DatasetId::from_data_operation_ref(&dataset_ref("asset")).unwrap(),
+            BTreeSet::from([state_store_key("key"), retained_message("ns/topic")]),
+        );
+        assert_eq!(
+            deserialize_manifest(&serialize_manifest(&manifest)).unwrap(),
+            manifest
+        );
+        assert!(deserialize_manifest(br#"{"version":2,"datasets":[]}"#).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deleted_dataset_cleaned_up_after_restart() {
+        let backend = backend_with_artifacts(&["kept", "deleted"]).await;
+
+        // The dataset is deleted while the connector isn't running
+        backend.services().defined_datasets.remove("deleted");
+
+        let reconciler = start_reconciler(&backend);
+        let mut observation = ReportObservation(reconciler.reports_tx.subscribe());
+        let report = reconciler.sweep().await;
+
+        assert_eq!(
+            report,
+            ReconciliationReport {
+                removed: vec![
+                    (dataset_ref("deleted"), state_store_key("deleted")),
+                    (dataset_ref("deleted"), retained_message("deleted")),
+                ],
+                failed: vec![],
+                aborted: false,
+            }
+        );
+        assert_eq!(observation.recv_report().await.unwrap(), report);
+        assert_eq!(
+            backend.services().cleaned_up,
+            vec![state_store_key("deleted"), retained_message("deleted")]
+        );
+        let manifest = backend.persisted_manifest();
+        assert_eq!(manifest.len(), 1);
+        assert!(
+            manifest
+                .contains_key(&DatasetId::from_data_operation_ref(&dataset_ref("kept")).unwrap())
+        );
+
+        // Nothing is left to clean up on the next sweep
+        assert_eq!(reconciler.sweep().await, ReconciliationReport::default());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn undetermined_dataset_not_cleaned_up() {
+        let backend = backend_with_artifacts(&["asset"]).await;
+        {
+            let mut services = backend.services();
+            services.defined_datasets.clear();
+            services.undetermined_datasets.insert("asset".to_string());
+        }
+
+        let reconciler = start_reconciler(&backend);
+        assert_eq!(reconciler.sweep().await, ReconciliationReport::default());
+        assert!(backend.services().cleaned_up.is_empty());
+        assert_eq!(backend.persisted_manifest().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_cleanup_retried_on_next_sweep() {
+        let backend = backend_with_artifacts(&["deleted"]).await;
+        {
+            let mut services = backend.services();
+            services.defined_datasets.clear();
+            services
+                .failing_artifacts
+                .insert(retained_message("deleted"));
+        }
+
+        let reconciler = start_reconciler(&backend);
+        let report = reconciler.sweep().await;
+        assert_eq!(
+            report.removed,
+            vec![(dataset_ref("deleted"), state_store_key("deleted"))]
+        );
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].1, retained_message("deleted"));
+        assert_eq!(
+            backend
+                .persisted_manifest()
+                .into_values()
+                .collect::<Vec<_>>(),
+            vec![BTreeSet::from([retained_message("deleted")])]
+        );
+
+        backend.services().failing_artifacts.clear();
+        let report = reconciler.sweep().await;
+        assert_eq!(
+            report.removed,
+            vec![(dataset_ref("deleted"), retained_message("deleted"))]
+        );
+        assert!(report.failed.is_empty());
+        assert!(backend.persisted_manifest().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cleanup_rate_limited() {
+        let backend = backend_with_artifacts(&["a", "b"]).await;
+        backend.services().defined_datasets.clear();
+
+        let reconciler = start_reconciler(&backend);
+        let start = tokio::time::Instant::now();
+        let report = reconciler.sweep().await;
+        assert_eq!(report.removed.len(), 4);
+        // No delay before the first cleanup
+        assert_eq!(start.elapsed(), CLEANUP_INTERVAL * 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sweep_aborted() {
+        let backend = backend_with_artifacts(&["a", "b"]).await;
+        backend.services().defined_datasets.clear();
+
+        let reconciler = Arc::new(start_reconciler(&backend));
+        let sweep = tokio::task::spawn({
+            let reconciler = reconciler.clone();
+            async move { reconciler.sweep().await }
+        });
+
+        // Abort after the second cleanup
+        tokio::time::sleep(CLEANUP_INTERVAL + CLEANUP_INTERVAL / 2).await;
+        reconciler.abort_sweep();
+        let report = sweep.await.unwrap();
+
+        assert!(report.aborted);
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(backend.services().cleaned_up.len(), 2);
+        // The remaining artifacts are still in the manifest
+        assert_eq!(
+            backend
+                .persisted_manifest()
+                .values()
+                .map(BTreeSet::len)
+                .sum::<usize>(),
+            2
+        );
+
+        // Aborting only affects the sweep in progress
+        let report = reconciler.sweep().await;
+        assert!(!report.aborted);
+        assert_eq!(report.removed.len(), 2);
+        assert!(backend.persisted_manifest().is_empty());
+    }
+
+    #[tokio::test]
+    async fn forget_removes_dataset() {
+        let backend = backend_with_artifacts(&["a", "b"]).await;
+
+        let reconciler = start_reconciler(&backend);
+        reconciler.forget(&dataset_ref("a")).await;
+        let manifest = backend.persisted_manifest();
+        assert_eq!(manifest.len(), 1);
+        assert!(
+            manifest.contains_key(&DatasetId::from_data_operation_ref(&dataset_ref("b")).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn record_skipped_when_manifest_load_fails() {
+        let backend = backend_with_artifacts(&["a"]).await;
+        backend.services().load_fails = true;
+
+        let reconciler = start_reconciler(&backend);
+        assert!(
+            !reconciler
+                .record(&dataset_ref("b"), state_store_key("b"))
+                .await
+        );
+        assert_eq!(reconciler.sweep().await, ReconciliationReport::default());
+
+        // The persisted manifest isn't overwritten
+        backend.services().load_fails = false;
+        assert_eq!(backend.persisted_manifest().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn non_dataset_artifacts_not_recorded() {
+        let backend = FakeBackend::default();
+        let reconciler = start_reconciler(&backend);
+        let event_ref = DataOperationRef {
+            data_operation_name: DataOperationName::Stream {
+                name: "stream".to_string(),
+            },
+            ..dataset_ref("a")
+        };
+        assert!(reconciler.record(&event_ref, retained_message("a")).await);
+        assert!(backend.services().manifest.is_none());
+    }
+}
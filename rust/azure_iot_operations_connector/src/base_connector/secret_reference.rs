@@ -0,0 +1,687 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Resolution of secret references in Asset and Dataset configurations.
+//!
+//! Any value of the form `{"$secretRef": "<name>"}` in an Asset's default datasets configuration or
+//! in a Dataset configuration refers to the secret `<name>` in the Device Endpoint credentials
+//! mount, allowing assets behind the same Device Endpoint to use different credentials. The
+//! resolved configurations are available from
+//! [`AssetClient::resolved_configuration`](crate::base_connector::managed_azure_device_registry::AssetClient::resolved_configuration),
+//! with each reference replaced by the content of the secret as a [`Secret`].
+//!
+//! References are resolved again whenever the Asset is updated or a secret file in the mount
+//! changes. If the resolved configuration changes because of a secret change, the
+//! [`AssetClient`](crate::base_connector::managed_azure_device_registry::AssetClient) returns a
+//! [`ClientNotification::Updated`](crate::base_connector::managed_azure_device_registry::ClientNotification::Updated).
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use azure_iot_operations_services::azure_device_registry::models::Asset;
+use notify::RecommendedWatcher;
+use notify_debouncer_full::{RecommendedCache, new_debouncer};
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::AdrConfigError;
+
+/// Key of the object that references a secret in a configuration
+pub const SECRET_REF_KEY: &str = "$secretRef";
+
+/// Duration to debounce file events in the credentials mount, which are generated in bursts when
+/// Kubernetes updates a mounted secret
+const SECRET_MOUNT_DEBOUNCE_DURATION: Duration = Duration::from_secs(1);
+
+/// The content of a secret referenced by a configuration.
+///
+/// Intentionally does not implement [`Debug`](std::fmt::Debug) or [`Display`](std::fmt::Display)
+/// so that the secret isn't accidentally logged.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    /// Returns the raw content of the secret
+    #[must_use]
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the content of the secret as a `str`, or `None` if it isn't valid UTF-8
+    #[must_use]
+    pub fn expose_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+}
+
+/// A JSON configuration value with all secret references resolved
+#[derive(Clone, PartialEq)]
+pub enum ResolvedValue {
+    /// JSON `null`
+    Null,
+    /// JSON boolean
+    Bool(bool),
+    /// JSON number
+    Number(serde_json::Number),
+    /// JSON string
+    String(String),
+    /// JSON array
+    Array(Vec<ResolvedValue>),
+    /// JSON object
+    Object(HashMap<String, ResolvedValue>),
+    /// A resolved secret reference
+    Secret(Secret),
+}
+
+impl ResolvedValue {
+    /// Returns the value of `key` if this is an object containing it
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&ResolvedValue> {
+        match self {
+            ResolvedValue::Object(object) => object.get(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if this is a string value
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ResolvedValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the secret if this is a resolved secret reference
+    #[must_use]
+    pub fn as_secret(&self) -> Option<&Secret> {
+        match self {
+            ResolvedValue::Secret(secret) => Some(secret),
+            _ => None,
+        }
+    }
+}
+
+/// The configurations of an Asset with all secret references resolved
+#[derive(Clone, Default, PartialEq)]
+pub struct ResolvedConfiguration {
+    /// The resolved default datasets configuration of the Asset, if it has one
+    pub default_datasets_configuration: Option<ResolvedValue>,
+    /// The resolved configuration of each Dataset that has one, by Dataset name
+    pub datasets: HashMap<String, ResolvedValue>,
+}
+
+impl ResolvedConfiguration {
+    /// Returns the resolved configuration of the Dataset `name`, if it has one
+    #[must_use]
+    pub fn dataset(&self, name: &str) -> Option<&ResolvedValue> {
+        self.datasets.get(name)
+    }
+}
+
+/// Represents an error resolving the secret references in a configuration
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum SecretReferenceError {
+    /// The configuration is not valid JSON
+    #[error("the {location} is not valid JSON: {message}")]
+    InvalidJson {
+        /// The configuration containing the error
+        location: String,
+        /// The parsing error
+        message: String,
+    },
+    /// A secret reference is not an object with a single secret name
+    #[error("the {location} contains an invalid '{SECRET_REF_KEY}': {message}")]
+    InvalidReference {
+        /// The configuration containing the error
+        location: String,
+        /// Why the reference is invalid
+        message: String,
+    },
+    /// A referenced secret could not be read from the credentials mount
+    #[error("secret '{name}' referenced in the {location} could not be read: {message}")]
+    MissingSecret {
+        /// The configuration containing the reference
+        location: String,
+        /// The name of the secret
+        name: String,
+        /// Why the secret could not be read
+        message: String,
+    },
+}
+
+impl From<SecretReferenceError> for AdrConfigError {
+    fn from(value: SecretReferenceError) -> Self {
+        AdrConfigError {
+            code: None,
+            details: None,
+            message: Some(value.to_string()),
+        }
+    }
+}
+
+/// The configurations of an Asset that may contain secret references
+#[derive(Clone, Debug, Default)]
+struct ConfigurationSources {
+    default_datasets_configuration: Option<String>,
+    /// Dataset names and their configurations
+    datasets: Vec<(String, Option<String>)>,
+}
+
+impl From<&Asset> for ConfigurationSources {
+    fn from(asset: &Asset) -> Self {
+        Self {
+            default_datasets_configuration: asset.default_datasets_configuration.clone(),
+            datasets: asset
+                .datasets
+                .iter()
+                .map(|dataset| (dataset.name.clone(), dataset.dataset_configuration.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl ConfigurationSources {
+    /// Resolves all secret references in the configurations against the `secrets_mount`
+    fn resolve(
+        &self,
+        secrets_mount: Option<&Path>,
+    ) -> Result<ResolvedConfiguration, SecretReferenceError> {
+        let default_datasets_configuration = self
+            .default_datasets_configuration
+            .as_deref()
+            .map(|configuration| {
+                resolve_configuration(
+                    configuration,
+                    "asset default datasets configuration",
+                    secrets_mount,
+                )
+            })
+            .transpose()?;
+        let mut datasets = HashMap::new();
+        for (name, configuration) in &self.datasets {
+            if let Some(configuration) = configuration {
+                datasets.insert(
+                    name.clone(),
+                    resolve_configuration(
+                        configuration,
+                        &format!("configuration of dataset '{name}'"),
+                        secrets_mount,
+                    )?,
+                );
+            }
+        }
+        Ok(ResolvedConfiguration {
+            default_datasets_configuration,
+            datasets,
+        })
+    }
+
+    /// Returns whether any configuration contains a secret reference
+    fn references_secrets(&self) -> bool {
+        self.default_datasets_configuration
+            .iter()
+            .chain(
+                self.datasets
+                    .iter()
+                    .filter_map(|(_, configuration)| configuration.as_ref()),
+            )
+            .any(|configuration| configuration.contains(SECRET_REF_KEY))
+    }
+}
+
+fn resolve_configuration(
+    configuration: &str,
+    location: &str,
+    secrets_mount: Option<&Path>,
+) -> Result<ResolvedValue, SecretReferenceError> {
+    let value =
+        serde_json::from_str(configuration).map_err(|e| SecretReferenceError::InvalidJson {
+            location: location.to_string(),
+            message: e.to_string(),
+        })?;
+    resolve_value(value, location, secrets_mount)
+}
+
+fn resolve_value(
+    value: serde_json::Value,
+    location: &str,
+    secrets_mount: Option<&Path>,
+) -> Result<ResolvedValue, SecretReferenceError> {
+    Ok(match value {
+        serde_json::Value::Null => ResolvedValue::Null,
+        serde_json::Value::Bool(b) => ResolvedValue::Bool(b),
+        serde_json::Value::Number(n) => ResolvedValue::Number(n),
+        serde_json::Value::String(s) => ResolvedValue::String(s),
+        serde_json::Value::Array(array) => ResolvedValue::Array(
+            array
+                .into_iter()
+                .map(|value| resolve_value(value, location, secrets_mount))
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_json::Value::Object(object) => match object.get(SECRET_REF_KEY) {
+            Some(reference) => {
+                let invalid_reference = |message: &str| SecretReferenceError::InvalidReference {
+                    location: location.to_string(),
+                    message: message.to_string(),
+                };
+                if object.len() != 1 {
+                    return Err(invalid_reference(
+                        "a secret reference can't contain other keys",
+                    ));
+                }
+                let Some(name) = reference.as_str() else {
+                    return Err(invalid_reference("the secret name must be a string"));
+                };
+                ResolvedValue::Secret(read_secret(name, location, secrets_mount)?)
+            }
+            None => ResolvedValue::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, resolve_value(value, location, secrets_mount)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        },
+    })
+}
+
+fn read_secret(
+    name: &str,
+    location: &str,
+    secrets_mount: Option<&Path>,
+) -> Result<Secret, SecretReferenceError> {
+    let missing_secret = |message: String| SecretReferenceError::MissingSecret {
+        location: location.to_string(),
+        name: name.to_string(),
+        message,
+    };
+    // Secret names are file names in the mount, so they can't traverse out of it
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(SecretReferenceError::InvalidReference {
+            location: location.to_string(),
+            message: format!("'{name}' is not a valid secret name"),
+        });
+    }
+    let secrets_mount = secrets_mount.ok_or_else(|| {
+        missing_secret("the device endpoint credentials mount is not present".to_string())
+    })?;
+    std::fs::read(secrets_mount.join(name))
+        .map(Secret)
+        .map_err(|e| missing_secret(e.to_string()))
+}
+
+/// Watches the credentials mount for changes to secret files
+struct SecretMountWatcher {
+    /// A file watcher used to monitor changes in the credentials mount.
+    #[allow(dead_code)]
+    debouncer: notify_debouncer_full::Debouncer<RecommendedWatcher, RecommendedCache>,
+    /// Notified when files in the credentials mount change
+    changed_rx: watch::Receiver<()>,
+}
+
+impl SecretMountWatcher {
+    fn new(secrets_mount: &Path, debounce_duration: Duration) -> Result<Self, notify::Error> {
+        let (changed_tx, changed_rx) = watch::channel(());
+        let mut debouncer = new_debouncer(
+            debounce_duration,
+            None,
+            move |res: Result<Vec<notify_debouncer_full::DebouncedEvent>, Vec<notify::Error>>| {
+                match res {
+                    // Kubernetes updates secrets by swapping a symlink to a new directory, so any
+                    // event in the mount may change a secret
+                    Ok(_) => changed_tx.send_replace(()),
+                    Err(err) => {
+                        for e in &err {
+                            log::error!(
+                                "Error processing events from credentials mount watcher: {e:?}"
+                            );
+                        }
+                    }
+                }
+            },
+        )?;
+        debouncer.watch(secrets_mount, notify::RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            debouncer,
+            changed_rx,
+        })
+    }
+}
+
+/// Tracks the resolved configurations of an Asset, resolving them again when the Asset or the
+/// referenced secrets change
+pub(crate) struct ConfigurationResolver {
+    secrets_mount: Option<PathBuf>,
+    debounce_duration: Duration,
+    sources: ConfigurationSources,
+    resolved: Result<ResolvedConfiguration, SecretReferenceError>,
+    /// Only present while the configurations reference secrets
+    watcher: Option<SecretMountWatcher>,
+}
+
+impl fmt::Debug for ConfigurationResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The resolved configuration contains secrets, so only the outcome is included
+        f.debug_struct("ConfigurationResolver")
+            .field("secrets_mount", &self.secrets_mount)
+            .field("resolved", &self.resolved.as_ref().map(|_| ()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConfigurationResolver {
+    pub(crate) fn new(asset: &Asset, secrets_mount: Option<PathBuf>) -> Self {
+        Self::with_debounce_duration(
+            ConfigurationSources::from(asset),
+            secrets_mount,
+            SECRET_MOUNT_DEBOUNCE_DURATION,
+        )
+    }
+
+    fn with_debounce_duration(
+        sources: ConfigurationSources,
+        secrets_mount: Option<PathBuf>,
+        debounce_duration: Duration,
+    ) -> Self {
+        let mut resolver = Self {
+            resolved: sources.resolve(secrets_mount.as_deref()),
+            secrets_mount,
+            debounce_duration,
+            sources,
+            watcher: None,
+        };
+        resolver.update_watcher();
+        resolver
+    }
+
+    /// Returns the current resolved configuration
+    pub(crate) fn resolved(&self) -> Result<ResolvedConfiguration, SecretReferenceError> {
+        self.resolved.clone()
+    }
+
+    /// Resolves the configurations of an updated `asset`
+    pub(crate) fn update(&mut self, asset: &Asset) {
+        self.update_sources(ConfigurationSources::from(asset));
+    }
+
+    fn update_sources(&mut self, sources: ConfigurationSources) {
+        self.resolved = sources.resolve(self.secrets_mount.as_deref());
+        self.sources = sources;
+        self.update_watcher();
+    }
+
+    /// Waits until a change to the referenced secrets changes the resolved configuration.
+    /// Never completes if the configurations don't reference secrets.
+    ///
+    /// # Cancel safety
+    /// This method is cancel safe. The resolved configuration is only replaced right before returning.
+    pub(crate) async fn secrets_changed(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return std::future::pending().await;
+        };
+        loop {
+            if watcher.changed_rx.changed().await.is_err() {
+                // The watcher has stopped, so no more changes will be observed
+                return std::future::pending().await;
+            }
+            let resolved = self.sources.resolve(self.secrets_mount.as_deref());
+            if resolved != self.resolved {
+                log::info!("Resolved configuration changed due to a secret change");
+                self.resolved = resolved;
+                return;
+            }
+        }
+    }
+
+    /// Watches the credentials mount only while the configurations reference secrets
+    fn update_watcher(&mut self) {
+        match (&self.secrets_mount, self.sources.references_secrets()) {
+            (Some(secrets_mount), true) => {
+                if self.watcher.is_none() {
+                    self.watcher =
+                        SecretMountWatcher::new(secrets_mount, self.debounce_duration)
+                            .inspect_err(|e| {
+                                log::warn!(
+                                    "Failed to watch the credentials mount, secret changes won't be observed: {e}"
+                                );
+                            })
+                            .ok();
+                }
+            }
+            _ => self.watcher = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    const DEBOUNCE_DURATION: Duration = Duration::from_millis(100);
+
+    fn sources(
+        default_datasets_configuration: Option<&str>,
+        datasets: &[(&str, Option<&str>)],
+    ) -> ConfigurationSources {
+        ConfigurationSources {
+            default_datasets_configuration: default_datasets_configuration.map(str::to_string),
+            datasets: datasets
+                .iter()
+                .map(|(name, configuration)| {
+                    ((*name).to_string(), configuration.map(str::to_string))
+                })
+                .collect(),
+        }
+    }
+
+    fn secrets_mount(secrets: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (name, value) in secrets {
+            std::fs::write(dir.path().join(name), value).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn resolve_nested_references() {
+        let mount = secrets_mount(&[("plc1-password", "hunter2"), ("api-key", "abc")]);
+        let sources = sources(
+            Some(r#"{"auth": {"password": {"$secretRef": "plc1-password"}}, "port": 502}"#),
+            &[
+                (
+                    "dataset1",
+                    Some(r#"{"headers": [{"name": "key", "value": {"$secretRef": "api-key"}}]}"#),
+                ),
+                ("dataset2", None),
+            ],
+        );
+
+        let resolved = sources.resolve(Some(mount.path())).unwrap();
+
+        let default_configuration = resolved.default_datasets_configuration.as_ref().unwrap();
+        let password = default_configuration
+            .get("auth")
+            .and_then(|auth| auth.get("password"))
+            .and_then(ResolvedValue::as_secret)
+            .unwrap();
+        assert_eq!(password.expose_str(), Some("hunter2"));
+        assert!(matches!(
+            default_configuration.get("port"),
+            Some(ResolvedValue::Number(_))
+        ));
+
+        let ResolvedValue::Array(headers) = resolved
+            .dataset("dataset1")
+            .unwrap()
+            .get("headers")
+            .unwrap()
+        else {
+            panic!("Expected array");
+        };
+        assert_eq!(
+            headers[0].get("name").and_then(ResolvedValue::as_str),
+            Some("key")
+        );
+        assert_eq!(
+            headers[0]
+                .get("value")
+                .and_then(ResolvedValue::as_secret)
+                .unwrap()
+                .expose(),
+            b"abc"
+        );
+        assert!(resolved.dataset("dataset2").is_none());
+    }
+
+    #[test]
+    fn resolve_missing_secret() {
+        let mount = secrets_mount(&[]);
+        let sources = sources(
+            None,
+            &[(
+                "dataset1",
+                Some(r#"{"password": {"$secretRef": "plc1-password"}}"#),
+            )],
+        );
+
+        let Err(error) = sources.resolve(Some(mount.path())) else {
+            panic!("Expected missing secret error");
+        };
+        assert!(matches!(
+            &error,
+            SecretReferenceError::MissingSecret { name, .. } if name == "plc1-password"
+        ));
+        let config_error = AdrConfigError::from(error);
+        assert!(config_error.message.unwrap().contains("plc1-password"));
+    }
+
+    #[test]
+    fn resolve_without_mount() {
+        let sources = sources(
+            Some(r#"{"password": {"$secretRef": "plc1-password"}}"#),
+            &[],
+        );
+        assert!(matches!(
+            sources.resolve(None),
+            Err(SecretReferenceError::MissingSecret { .. })
+        ));
+    }
+
+    #[test_case::test_case(r#"{"$secretRef": 5}"#; "not a string")]
+    #[test_case::test_case(r#"{"$secretRef": "a", "other": 1}"#; "other keys")]
+    #[test_case::test_case(r#"{"$secretRef": "../a"}"#; "path traversal")]
+    #[test_case::test_case(r#"{"$secretRef": ""}"#; "empty")]
+    fn resolve_invalid_reference(configuration: &str) {
+        let mount = secrets_mount(&[("a", "secret")]);
+        let sources = sources(Some(configuration), &[]);
+        assert!(matches!(
+            sources.resolve(Some(mount.path())),
+            Err(SecretReferenceError::InvalidReference { .. })
+        ));
+    }
+
+    #[test]
+    fn resolve_without_references() {
+        let sources = sources(Some(r#"{"port": 502}"#), &[("dataset1", Some("not json"))]);
+        assert!(matches!(
+            sources.resolve(None),
+            Err(SecretReferenceError::InvalidJson { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolver_rotation() {
+        let mount = secrets_mount(&[("plc1-password", "old")]);
+        let mut resolver = ConfigurationResolver::with_debounce_duration(
+            sources(
+                None,
+                &[(
+                    "dataset1",
+                    Some(r#"{"password": {"$secretRef": "plc1-password"}}"#),
+                )],
+            ),
+            Some(mount.path().to_path_buf()),
+            DEBOUNCE_DURATION,
+        );
+        let password = |resolver: &ConfigurationResolver| {
+            resolver
+                .resolved()
+                .unwrap()
+                .dataset("dataset1")
+                .and_then(|configuration| configuration.get("password"))
+                .and_then(ResolvedValue::as_secret)
+                .and_then(|secret| secret.expose_str().map(str::to_string))
+        };
+        assert_eq!(password(&resolver).as_deref(), Some("old"));
+
+        std::fs::write(mount.path().join("plc1-password"), "new").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), resolver.secrets_changed())
+            .await
+            .expect("secret change should be observed");
+        assert_eq!(password(&resolver).as_deref(), Some("new"));
+
+        // Removing the secret is reported as an error
+        std::fs::remove_file(mount.path().join("plc1-password")).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), resolver.secrets_changed())
+            .await
+            .expect("secret removal should be observed");
+        assert!(matches!(
+            resolver.resolved(),
+            Err(SecretReferenceError::MissingSecret { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolver_unrelated_change() {
+        let mount = secrets_mount(&[("plc1-password", "old")]);
+        let mut resolver = ConfigurationResolver::with_debounce_duration(
+            sources(
+                Some(r#"{"password": {"$secretRef": "plc1-password"}}"#),
+                &[],
+            ),
+            Some(mount.path().to_path_buf()),
+            DEBOUNCE_DURATION,
+        );
+
+        // Changes to other secrets don't change the resolved configuration
+        std::fs::write(mount.path().join("plc2-password"), "other").unwrap();
+        assert!(
+            tokio::time::timeout(DEBOUNCE_DURATION * 5, resolver.secrets_changed())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn resolver_update_stops_watching() {
+        let mount = secrets_mount(&[("plc1-password", "old")]);
+        let mut resolver = ConfigurationResolver::with_debounce_duration(
+            sources(
+                Some(r#"{"password": {"$secretRef": "plc1-password"}}"#),
+                &[],
+            ),
+            Some(mount.path().to_path_buf()),
+            DEBOUNCE_DURATION,
+        );
+        assert!(resolver.watcher.is_some());
+
+        resolver.update_sources(sources(Some(r#"{"password": "inline"}"#), &[]));
+        assert!(resolver.watcher.is_none());
+        assert_eq!(
+            resolver
+                .resolved()
+                .unwrap()
+                .default_datasets_configuration
+                .unwrap()
+                .get("password")
+                .and_then(ResolvedValue::as_str),
+            Some("inline")
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Typed representation of the Azure Device Registry status schema.
+//!
+//! A [`Status`] contains the `config` and `runtime` sections that Azure Device Registry expects
+//! for a Device Endpoint or Asset Component (Dataset, Event, Stream, or Management Action).
+//! Reporting a [`Status`] with one of the status reporters ensures the status document is well formed,
+//! rather than relying on an arbitrary [`AdrConfigError`] being translated correctly.
+
+use azure_iot_operations_services::azure_device_registry;
+use derive_builder::Builder;
+
+use crate::{AdrConfigError, base_connector::managed_azure_device_registry::RuntimeHealthEvent};
+
+/// A configuration error in the format expected by the Azure Device Registry status schema.
+#[derive(Builder, Clone, Debug, PartialEq)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct StatusError {
+    /// Error code for classification of errors (ex: '400', '404', '500', etc.).
+    code: String,
+    /// Human readable helpful error message to provide additional context for the error.
+    message: String,
+    /// Details that describe the status of each error.
+    #[builder(default, setter(custom))]
+    details: Vec<StatusErrorDetail>,
+}
+
+impl StatusErrorBuilder {
+    /// Adds a [`StatusErrorDetail`] to the error
+    pub fn detail(&mut self, detail: StatusErrorDetail) -> &mut Self {
+        self.details.get_or_insert_with(Vec::new).push(detail);
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(code) = &self.code {
+            validate_code(code)?;
+        }
+        if let Some(message) = &self.message
+            && message.trim().is_empty()
+        {
+            return Err("message cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A detail of a [`StatusError`] in the format expected by the Azure Device Registry status schema.
+#[derive(Builder, Clone, Debug, PartialEq)]
+#[builder(setter(into, strip_option), build_fn(validate = "Self::validate"))]
+pub struct StatusErrorDetail {
+    /// Multi-part error code for classification and root causing of errors (ex: 400.200.100.432).
+    #[builder(default)]
+    code: Option<String>,
+    /// Unique identifier for the transaction to aid in debugging.
+    #[builder(default)]
+    correlation_id: Option<String>,
+    /// Human readable helpful detailed text context for debugging.
+    #[builder(default)]
+    info: Option<String>,
+    /// Human readable helpful error message to provide additional context for the error.
+    #[builder(default)]
+    message: Option<String>,
+}
+
+impl StatusErrorDetailBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(code)) = &self.code {
+            // multi-part codes are dot separated codes
+            for part in code.split('.') {
+                validate_code(part)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error codes are numeric, as they mirror HTTP status codes
+fn validate_code(code: &str) -> Result<(), String> {
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("code must be numeric, but was '{code}'"));
+    }
+    Ok(())
+}
+
+impl From<StatusErrorDetail> for azure_device_registry::Details {
+    fn from(value: StatusErrorDetail) -> Self {
+        azure_device_registry::Details {
+            code: value.code,
+            correlation_id: value.correlation_id,
+            info: value.info,
+            message: value.message,
+        }
+    }
+}
+
+impl From<StatusError> for AdrConfigError {
+    fn from(value: StatusError) -> Self {
+        AdrConfigError {
+            code: Some(value.code),
+            details: if value.details.is_empty() {
+                None
+            } else {
+                Some(value.details.into_iter().map(Into::into).collect())
+            },
+            message: Some(value.message),
+        }
+    }
+}
+
+/// The status of a Device Endpoint or Asset Component in the format expected by the Azure Device Registry status schema.
+#[derive(Builder, Clone, Debug)]
+pub struct Status {
+    /// The `config` section, indicating whether the current definition is valid
+    #[builder(default = "Ok(())", setter(custom))]
+    config: Result<(), AdrConfigError>,
+    /// The `runtime` section, indicating the current runtime health. If not set, the runtime health is not reported
+    #[builder(default, setter(strip_option))]
+    runtime: Option<RuntimeHealthEvent>,
+}
+
+impl StatusBuilder {
+    /// Sets the `config` section to the provided [`StatusError`], indicating that the definition is not valid.
+    pub fn config_error(&mut self, error: StatusError) -> &mut Self {
+        self.config = Some(Err(error.into()));
+        self
+    }
+}
+
+impl Status {
+    /// Returns the `config` section of the status
+    ///
+    /// # Errors
+    /// The [`AdrConfigError`] if the `config` section indicates the definition is not valid
+    pub fn config(&self) -> Result<(), &AdrConfigError> {
+        self.config.as_ref().copied()
+    }
+
+    /// Returns the `runtime` section of the status
+    #[must_use]
+    pub fn runtime(&self) -> Option<&RuntimeHealthEvent> {
+        self.runtime.as_ref()
+    }
+
+    /// Modify function for the `report_*_if_modified` functions that reports the `config`
+    /// section only if it differs from the current status.
+    pub(crate) fn config_if_modified(
+        &self,
+        current: Option<Result<(), &AdrConfigError>>,
+    ) -> Option<Result<(), AdrConfigError>> {
+        if current == Some(self.config()) {
+            None
+        } else {
+            Some(self.config.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn status_error_to_adr_config_error() {
+        let error = StatusErrorBuilder::default()
+            .code("400")
+            .message("Invalid dataset configuration")
+            .detail(
+                StatusErrorDetailBuilder::default()
+                    .code("400.200")
+                    .info("samplingInterval must be positive")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let adr_error = AdrConfigError::from(error);
+        assert_eq!(adr_error.code.as_deref(), Some("400"));
+        assert_eq!(
+            adr_error.message.as_deref(),
+            Some("Invalid dataset configuration")
+        );
+        let details = adr_error.details.unwrap();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].code.as_deref(), Some("400.200"));
+        assert_eq!(
+            details[0].info.as_deref(),
+            Some("samplingInterval must be positive")
+        );
+        assert_eq!(details[0].correlation_id, None);
+    }
+
+    #[test]
+    fn status_error_without_details() {
+        let error = StatusErrorBuilder::default()
+            .code("500")
+            .message("Internal error")
+            .build()
+            .unwrap();
+        assert_eq!(AdrConfigError::from(error).details, None);
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("bad"; "not_numeric")]
+    #[test_case("4O4"; "letter")]
+    fn status_error_invalid_code(code: &str) {
+        assert!(
+            StatusErrorBuilder::default()
+                .code(code)
+                .message("message")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn status_error_missing_fields() {
+        assert!(StatusErrorBuilder::default().code("400").build().is_err());
+        assert!(
+            StatusErrorBuilder::default()
+                .message("message")
+                .build()
+                .is_err()
+        );
+        assert!(
+            StatusErrorBuilder::default()
+                .code("400")
+                .message(" ")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test_case("400.200.100.432", true; "multi_part")]
+    #[test_case("400..432", false; "empty_part")]
+    #[test_case("400.abc", false; "not_numeric_part")]
+    fn status_error_detail_code(code: &str, valid: bool) {
+        assert_eq!(
+            StatusErrorDetailBuilder::default()
+                .code(code)
+                .build()
+                .is_ok(),
+            valid
+        );
+    }
+
+    #[test]
+    fn status_defaults() {
+        let status = StatusBuilder::default().build().unwrap();
+        assert!(status.config().is_ok());
+        assert!(status.runtime().is_none());
+    }
+
+    #[test]
+    fn status_config_if_modified() {
+        let ok_status = StatusBuilder::default()
+            .runtime(RuntimeHealthEvent::Available)
+            .build()
+            .unwrap();
+        assert!(ok_status.config_if_modified(Some(Ok(()))).is_none());
+        assert_eq!(ok_status.config_if_modified(None), Some(Ok(())));
+
+        let error = StatusErrorBuilder::default()
+            .code("400")
+            .message("Invalid")
+            .build()
+            .unwrap();
+        let adr_error = AdrConfigError::from(error.clone());
+        let err_status = StatusBuilder::default()
+            .config_error(error)
+            .build()
+            .unwrap();
+        assert!(
+            err_status
+                .config_if_modified(Some(Err(&adr_error)))
+                .is_none()
+        );
+        assert_eq!(
+            err_status.config_if_modified(Some(Ok(()))),
+            Some(Err(adr_error.clone()))
+        );
+        assert_eq!(
+            ok_status.config_if_modified(Some(Err(&adr_error))),
+            Some(Ok(()))
+        );
+    }
+}
@@ -0,0 +1,532 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![allow(clippy::missing_panics_doc)]
+
+//! Utilities for testing connector logic without the Azure Device Registry Service or a destination.
+//! Note that these test utilities are provided AS IS without any guarantee of stability
+//!
+//! Handlers written against the [`ClientNotificationReceiver`] and [`DataOperationHandle`] traits
+//! instead of the concrete clients can be driven in tests with the mocks in this module:
+//! - [`MockClientNotificationReceiver`] returns the [`ClientNotification`]s pushed with its
+//!   [`ClientNotificationInjector`], e.g. a [`ClientNotification::Created`] with a
+//!   [`MockDataOperationClient`].
+//! - [`MockDataOperationClient`] returns the [`DataOperationNotification`]s pushed with its
+//!   [`DataOperationController`], and records the statuses and message schemas reported and the
+//!   data forwarded to its mock destination so that the controller can assert on them.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use azure_iot_operations_services::azure_device_registry;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    Data, MessageSchema, MessageSchemaReference,
+    base_connector::{
+        managed_azure_device_registry::{
+            ClientNotification, ClientNotificationReceiver, DataOperationDefinition,
+            DataOperationHandle, DataOperationNotification, MessageSchemaError, ModifyResult,
+            SchemaModifyResult,
+        },
+        status::Status,
+    },
+    destination_endpoint::{self, DeliveryReceipt, DestinationKind},
+    message_schema,
+};
+
+/// Behavior of the mock destination of a [`MockDataOperationClient`] when data is forwarded
+type ForwardDataBehavior =
+    Box<dyn Fn(&Data) -> Result<(), destination_endpoint::Error> + Send + Sync>;
+
+/// Creates a [`MockClientNotificationReceiver`] and the [`ClientNotificationInjector`] used to
+/// push notifications to it
+#[must_use]
+pub fn client_notification_channel<T>() -> (
+    ClientNotificationInjector<T>,
+    MockClientNotificationReceiver<T>,
+) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (
+        ClientNotificationInjector { tx },
+        MockClientNotificationReceiver { rx, deleted: false },
+    )
+}
+
+/// Pushes [`ClientNotification`]s to a [`MockClientNotificationReceiver`]
+pub struct ClientNotificationInjector<T> {
+    tx: UnboundedSender<ClientNotification<T>>,
+}
+
+impl<T> ClientNotificationInjector<T> {
+    /// Push a [`ClientNotification`] to be returned by the [`MockClientNotificationReceiver`]
+    pub fn push(&self, notification: ClientNotification<T>) {
+        // The notification is dropped if the receiver has been dropped, like it would be for a real client
+        let _ = self.tx.send(notification);
+    }
+
+    /// Push a [`ClientNotification::Created`] with `client`
+    pub fn push_created(&self, client: T) {
+        self.push(ClientNotification::Created(client));
+    }
+}
+
+/// A [`ClientNotificationReceiver`] that returns the [`ClientNotification`]s pushed with its
+/// [`ClientNotificationInjector`], in the order they were pushed.
+///
+/// Like a real client, once a [`ClientNotification::Deleted`] has been returned it is returned
+/// for every following call. [`ClientNotification::Deleted`] is also returned once the
+/// [`ClientNotificationInjector`] has been dropped and all pushed notifications have been returned.
+pub struct MockClientNotificationReceiver<T> {
+    rx: UnboundedReceiver<ClientNotification<T>>,
+    deleted: bool,
+}
+
+impl<T: Send> ClientNotificationReceiver for MockClientNotificationReceiver<T> {
+    type Created = T;
+
+    async fn recv_notification(&mut self) -> ClientNotification<T> {
+        if self.deleted {
+            return ClientNotification::Deleted;
+        }
+        match self.rx.recv().await {
+            Some(ClientNotification::Deleted) | None => {
+                self.deleted = true;
+                ClientNotification::Deleted
+            }
+            Some(notification) => notification,
+        }
+    }
+}
+
+/// Calls made to a [`MockDataOperationClient`], recorded for its [`DataOperationController`]
+#[derive(Default)]
+struct RecordedCalls {
+    forwarded_data: VecDeque<Data>,
+    reported_statuses: VecDeque<Status>,
+    reported_message_schemas: VecDeque<MessageSchema>,
+}
+
+/// State of a [`MockDataOperationClient`] shared with its [`DataOperationController`]
+struct MockDataOperationState {
+    recorded_calls: Mutex<RecordedCalls>,
+    calls_notify: tokio::sync::Notify,
+    forward_data_behavior: Mutex<ForwardDataBehavior>,
+    forward_data_delay: Mutex<Duration>,
+    message_schema_reference: Mutex<MessageSchemaReference>,
+}
+
+/// A [`DataOperationHandle`] with a mock destination, that returns the
+/// [`DataOperationNotification`]s pushed with its [`DataOperationController`] and records the
+/// calls made to it for the [`DataOperationController`] to assert on.
+///
+/// Like a real client, once a [`DataOperationNotification::Deleted`] has been returned it is
+/// returned for every following call. [`DataOperationNotification::Deleted`] is also returned
+/// once the [`DataOperationController`] has been dropped and all pushed notifications have been
+/// returned.
+pub struct MockDataOperationClient {
+    definition: DataOperationDefinition,
+    notification_rx: UnboundedReceiver<DataOperationNotification>,
+    deleted: bool,
+    last_reported_message_schema: Option<MessageSchema>,
+    state: Arc<MockDataOperationState>,
+}
+
+impl MockDataOperationClient {
+    /// Create a new [`MockDataOperationClient`] with `definition` and its [`DataOperationController`].
+    ///
+    /// By default, forwarding data succeeds immediately, and reporting a message schema returns a
+    /// [`MessageSchemaReference`] to `test-schema` version `1.0.0` in `test-namespace`.
+    #[must_use]
+    pub fn new(definition: DataOperationDefinition) -> (Self, DataOperationController) {
+        let (notification_tx, notification_rx) = mpsc::unbounded_channel();
+        let state = Arc::new(MockDataOperationState {
+            recorded_calls: Mutex::new(RecordedCalls::default()),
+            calls_notify: tokio::sync::Notify::new(),
+            forward_data_behavior: Mutex::new(Box::new(|_| Ok(()))),
+            forward_data_delay: Mutex::new(Duration::ZERO),
+            message_schema_reference: Mutex::new(MessageSchemaReference {
+                name: "test-schema".to_string(),
+                version: "1.0.0".to_string(),
+                registry_namespace: "test-namespace".to_string(),
+            }),
+        });
+        (
+            Self {
+                definition,
+                notification_rx,
+                deleted: false,
+                last_reported_message_schema: None,
+                state: state.clone(),
+            },
+            DataOperationController {
+                notification_tx,
+                state,
+            },
+        )
+    }
+
+    /// Records a call and wakes up the [`DataOperationController`] if it is waiting for one
+    fn record(&self, f: impl FnOnce(&mut RecordedCalls)) {
+        f(&mut self.state.recorded_calls.lock().unwrap());
+        self.state.calls_notify.notify_waiters();
+    }
+}
+
+impl DataOperationHandle for MockDataOperationClient {
+    fn definition(&self) -> &DataOperationDefinition {
+        &self.definition
+    }
+
+    async fn recv_notification(&mut self) -> DataOperationNotification {
+        if self.deleted {
+            return DataOperationNotification::Deleted;
+        }
+        match self.notification_rx.recv().await {
+            Some(DataOperationNotification::Deleted) | None => {
+                self.deleted = true;
+                DataOperationNotification::Deleted
+            }
+            Some(notification) => notification,
+        }
+    }
+
+    /// The mock destination confirms delivery after the configured delay, and is reported as an
+    /// MQTT destination in the [`DeliveryReceipt`]
+    async fn forward_data(
+        &self,
+        data: Data,
+    ) -> Result<Option<DeliveryReceipt>, destination_endpoint::Error> {
+        let accepted_at = Instant::now();
+        let delay = *self.state.forward_data_delay.lock().unwrap();
+        tokio::time::sleep(delay).await;
+        (self.state.forward_data_behavior.lock().unwrap())(&data)?;
+        let receipt = DeliveryReceipt::confirmed_now(
+            accepted_at,
+            DestinationKind::Mqtt,
+            data.timestamp.as_ref(),
+        );
+        self.record(|calls| calls.forwarded_data.push_back(data));
+        Ok(Some(receipt))
+    }
+
+    async fn report_status(
+        &self,
+        status: Status,
+    ) -> Result<ModifyResult, azure_device_registry::Error> {
+        self.record(|calls| calls.reported_statuses.push_back(status));
+        Ok(ModifyResult::Reported)
+    }
+
+    /// Like [`DataOperationClient::report_message_schema_if_modified`](crate::base_connector::managed_azure_device_registry::DataOperationClient::report_message_schema_if_modified),
+    /// a message schema equivalent to the last one reported isn't reported again
+    async fn report_message_schema(
+        &mut self,
+        message_schema: MessageSchema,
+    ) -> Result<SchemaModifyResult, MessageSchemaError> {
+        if let Some(last_reported_message_schema) = &self.last_reported_message_schema
+            && message_schema::is_equivalent(last_reported_message_schema, &message_schema)
+        {
+            return Ok(SchemaModifyResult::NotModified);
+        }
+        self.last_reported_message_schema = Some(message_schema.clone());
+        self.record(|calls| calls.reported_message_schemas.push_back(message_schema));
+        Ok(SchemaModifyResult::Reported(
+            self.state.message_schema_reference.lock().unwrap().clone(),
+        ))
+    }
+}
+
+/// Controls a [`MockDataOperationClient`]: pushes [`DataOperationNotification`]s to it, configures
+/// its mock destination, and returns the calls made to it in the order they were made.
+pub struct DataOperationController {
+    notification_tx: UnboundedSender<DataOperationNotification>,
+    state: Arc<MockDataOperationState>,
+}
+
+impl DataOperationController {
+    /// Push a [`DataOperationNotification`] to be returned by the [`MockDataOperationClient`]
+    pub fn push_notification(&self, notification: DataOperationNotification) {
+        // The notification is dropped if the client has been dropped, like it would be for a real client
+        let _ = self.notification_tx.send(notification);
+    }
+
+    /// Set the result of forwarding data to the mock destination. Data is only recorded as
+    /// forwarded if `behavior` returns `Ok`.
+    pub fn set_forward_data_behavior(
+        &self,
+        behavior: impl Fn(&Data) -> Result<(), destination_endpoint::Error> + Send + Sync + 'static,
+    ) {
+        *self.state.forward_data_behavior.lock().unwrap() = Box::new(behavior);
+    }
+
+    /// Set the delay before the mock destination confirms delivery of forwarded data
+    pub fn set_forward_data_delay(&self, delay: Duration) {
+        *self.state.forward_data_delay.lock().unwrap() = delay;
+    }
+
+    /// Set the [`MessageSchemaReference`] returned when a message schema is reported
+    pub fn set_message_schema_reference(&self, message_schema_reference: MessageSchemaReference) {
+        *self.state.message_schema_reference.lock().unwrap() = message_schema_reference;
+    }
+
+    /// Waits for the next [`Data`] forwarded to the mock destination
+    pub async fn recv_forwarded_data(&self) -> Data {
+        self.recv_call(|calls| calls.forwarded_data.pop_front())
+            .await
+    }
+
+    /// Waits for the next [`Status`] reported
+    pub async fn recv_reported_status(&self) -> Status {
+        self.recv_call(|calls| calls.reported_statuses.pop_front())
+            .await
+    }
+
+    /// Waits for the next [`MessageSchema`] reported
+    pub async fn recv_reported_message_schema(&self) -> MessageSchema {
+        self.recv_call(|calls| calls.reported_message_schemas.pop_front())
+            .await
+    }
+
+    /// Returns all [`Data`] forwarded to the mock destination that hasn't been returned yet
+    #[must_use]
+    pub fn take_forwarded_data(&self) -> Vec<Data> {
+        self.state
+            .recorded_calls
+            .lock()
+            .unwrap()
+            .forwarded_data
+            .drain(..)
+            .collect()
+    }
+
+    /// Returns all [`Status`]es reported that haven't been returned yet
+    #[must_use]
+    pub fn take_reported_statuses(&self) -> Vec<Status> {
+        self.state
+            .recorded_calls
+            .lock()
+            .unwrap()
+            .reported_statuses
+            .drain(..)
+            .collect()
+    }
+
+    /// Returns all [`MessageSchema`]s reported that haven't been returned yet
+    #[must_use]
+    pub fn take_reported_message_schemas(&self) -> Vec<MessageSchema> {
+        self.state
+            .recorded_calls
+            .lock()
+            .unwrap()
+            .reported_message_schemas
+            .drain(..)
+            .collect()
+    }
+
+    /// Waits until `take` returns a recorded call
+    async fn recv_call<T>(&self, take: impl Fn(&mut RecordedCalls) -> Option<T>) -> T {
+        loop {
+            // Register for the notification before checking, so a call recorded in between isn't missed
+            let notified = self.state.calls_notify.notified();
+            if let Some(call) = take(&mut self.state.recorded_calls.lock().unwrap()) {
+                return call;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+    use azure_iot_operations_services::azure_device_registry::models as adr_models;
+
+    use super::*;
+    use crate::{
+        MessageSchemaBuilder,
+        base_connector::status::{StatusBuilder, StatusErrorBuilder},
+    };
+
+    fn dataset_definition() -> DataOperationDefinition {
+        DataOperationDefinition::Dataset(adr_models::Dataset {
+            dataset_configuration: None,
+            data_points: Vec::new(),
+            data_source: None,
+            destinations: Vec::new(),
+            name: "test-dataset".to_string(),
+            type_ref: None,
+        })
+    }
+
+    fn message_schema() -> MessageSchema {
+        MessageSchemaBuilder::default()
+            .schema_content(r#"{"type":"object"}"#)
+            .format(azure_iot_operations_services::schema_registry::Format::JsonSchemaDraft07)
+            .build()
+            .unwrap()
+    }
+
+    fn data(payload: &[u8]) -> Data {
+        Data {
+            payload: payload.to_vec(),
+            content_type: "application/json".to_string(),
+            custom_user_data: Vec::new(),
+            timestamp: None,
+        }
+    }
+
+    /// Example Data Operation handler, written against [`DataOperationHandle`]
+    async fn handle_data_operation(mut client: impl DataOperationHandle) {
+        client
+            .report_message_schema(message_schema())
+            .await
+            .unwrap();
+        // Runs until the Data Operation is deleted
+        while let DataOperationNotification::Updated(result)
+        | DataOperationNotification::AssetUpdated(result) = client.recv_notification().await
+        {
+            let status = match result {
+                Ok(()) => StatusBuilder::default().build().unwrap(),
+                Err(e) => StatusBuilder::default()
+                    .config_error(
+                        StatusErrorBuilder::default()
+                            .code(e.code.unwrap_or_default())
+                            .message(e.message.unwrap_or_default())
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            };
+            client.report_status(status).await.unwrap();
+            client
+                .report_message_schema(message_schema())
+                .await
+                .unwrap();
+            client.forward_data(data(b"{}")).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn data_operation_handler() {
+        let (client, controller) = MockDataOperationClient::new(dataset_definition());
+        let handler = tokio::task::spawn(handle_data_operation(client));
+
+        assert_eq!(
+            controller.recv_reported_message_schema().await,
+            message_schema()
+        );
+
+        controller.push_notification(DataOperationNotification::Updated(Ok(())));
+        assert_eq!(controller.recv_forwarded_data().await, data(b"{}"));
+        let reported_statuses = controller.take_reported_statuses();
+        assert_eq!(reported_statuses.len(), 1);
+        assert!(reported_statuses[0].config().is_ok());
+        // The unchanged message schema isn't reported again
+        assert!(controller.take_reported_message_schemas().is_empty());
+
+        let config_error: crate::AdrConfigError = StatusErrorBuilder::default()
+            .code("400")
+            .message("invalid configuration")
+            .build()
+            .unwrap()
+            .into();
+        controller.push_notification(DataOperationNotification::AssetUpdated(Err(
+            config_error.clone()
+        )));
+        assert_eq!(
+            controller.recv_reported_status().await.config(),
+            Err(&config_error)
+        );
+
+        controller.push_notification(DataOperationNotification::Deleted);
+        handler.await.unwrap();
+        assert_eq!(controller.take_forwarded_data().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn forward_data_behavior() {
+        let (client, controller) = MockDataOperationClient::new(dataset_definition());
+        controller.set_forward_data_behavior(|_| {
+            Err(destination_endpoint::ErrorKind::MissingMessageSchema.into())
+        });
+
+        assert!(client.forward_data(data(b"{}")).await.is_err());
+        assert!(controller.take_forwarded_data().is_empty());
+    }
+
+    #[tokio::test]
+    async fn forward_data_delivery_receipt() {
+        let (client, controller) = MockDataOperationClient::new(dataset_definition());
+        let delay = Duration::from_millis(50);
+        controller.set_forward_data_delay(delay);
+        let mut data = data(b"{}");
+        data.timestamp = Some(HybridLogicalClock::new());
+
+        let before = Instant::now();
+        let receipt = client.forward_data(data).await.unwrap().unwrap();
+        let after = Instant::now();
+
+        assert!(receipt.accepted_at >= before);
+        assert!(receipt.confirmed_at <= after);
+        assert!(receipt.confirmed_at - receipt.accepted_at >= delay);
+        assert_eq!(receipt.destination, DestinationKind::Mqtt);
+        let end_to_end_latency = receipt.end_to_end_latency.unwrap();
+        assert!(end_to_end_latency >= delay);
+        assert!(end_to_end_latency <= after - before + Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn data_operation_deleted_when_controller_dropped() {
+        let (mut client, controller) = MockDataOperationClient::new(dataset_definition());
+        controller.push_notification(DataOperationNotification::Updated(Ok(())));
+        drop(controller);
+
+        assert!(matches!(
+            client.recv_notification().await,
+            DataOperationNotification::Updated(Ok(()))
+        ));
+        assert!(matches!(
+            client.recv_notification().await,
+            DataOperationNotification::Deleted
+        ));
+        assert!(matches!(
+            client.recv_notification().await,
+            DataOperationNotification::Deleted
+        ));
+    }
+
+    #[tokio::test]
+    async fn client_notifications() {
+        let (injector, mut receiver) = client_notification_channel();
+        let (data_operation_client, _controller) =
+            MockDataOperationClient::new(dataset_definition());
+        injector.push_created(data_operation_client);
+        injector.push(ClientNotification::Updated);
+        injector.push(ClientNotification::Deleted);
+        injector.push(ClientNotification::Updated);
+
+        let ClientNotification::Created(data_operation_client) = receiver.recv_notification().await
+        else {
+            panic!("Expected Created notification");
+        };
+        assert_eq!(data_operation_client.definition(), &dataset_definition());
+        assert!(matches!(
+            receiver.recv_notification().await,
+            ClientNotification::Updated
+        ));
+        // No notifications are returned after the client is deleted
+        assert!(matches!(
+            receiver.recv_notification().await,
+            ClientNotification::Deleted
+        ));
+        assert!(matches!(
+            receiver.recv_notification().await,
+            ClientNotification::Deleted
+        ));
+    }
+}
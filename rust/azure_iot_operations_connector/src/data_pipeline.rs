@@ -0,0 +1,652 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Backpressure-aware bounded channels for connecting the stages of a data pipeline.
+//!
+//! A connector typically moves data through several stages (e.g. sampling a device, transforming
+//! the sample, and forwarding it to a destination). Each stage runs at its own pace, so the
+//! channel between two stages has to absorb differences in throughput. An unbounded channel does
+//! this by growing without limit when a downstream stage stalls (e.g. a destination that is
+//! unreachable), eventually exhausting the connector's memory.
+//!
+//! The channels in this module are always bounded. What happens when a channel is full is
+//! decided per stage by its [`WhenFull`] policy, and every channel reports its current depth and
+//! how many items it has dropped or coalesced so that a [`PipelineReport`] can be surfaced by the
+//! connector.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use derive_builder::Builder;
+use thiserror::Error;
+use tokio::sync::Notify;
+
+/// Default capacity of a pipeline stage.
+pub const DEFAULT_STAGE_CAPACITY: usize = 1024;
+
+/// Function merging an incoming item into the newest item already queued on a full stage.
+///
+/// Called with the newest queued item and the incoming item, in that order.
+pub type CoalesceFn<T> = Arc<dyn Fn(T, T) -> T + Send + Sync>;
+
+/// Policy applied when an item is sent on a stage that is at capacity.
+#[derive(Default)]
+pub enum WhenFull<T> {
+    /// Wait until the downstream stage has made room. This propagates backpressure to the
+    /// upstream stage and never loses data.
+    #[default]
+    Block,
+    /// Discard the oldest queued item to make room for the incoming one.
+    DropOldest,
+    /// Discard the incoming item.
+    DropNewest,
+    /// Merge the incoming item into the newest queued item (e.g. to keep only the latest
+    /// sample, or to aggregate samples).
+    Coalesce(CoalesceFn<T>),
+}
+
+impl<T> WhenFull<T> {
+    /// Creates a [`WhenFull::Coalesce`] policy from a merge function.
+    pub fn coalesce(f: impl Fn(T, T) -> T + Send + Sync + 'static) -> Self {
+        WhenFull::Coalesce(Arc::new(f))
+    }
+
+    fn kind(&self) -> WhenFullKind {
+        match self {
+            WhenFull::Block => WhenFullKind::Block,
+            WhenFull::DropOldest => WhenFullKind::DropOldest,
+            WhenFull::DropNewest => WhenFullKind::DropNewest,
+            WhenFull::Coalesce(_) => WhenFullKind::Coalesce,
+        }
+    }
+}
+
+impl<T> Clone for WhenFull<T> {
+    fn clone(&self) -> Self {
+        match self {
+            WhenFull::Block => WhenFull::Block,
+            WhenFull::DropOldest => WhenFull::DropOldest,
+            WhenFull::DropNewest => WhenFull::DropNewest,
+            WhenFull::Coalesce(f) => WhenFull::Coalesce(f.clone()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for WhenFull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+/// The kind of [`WhenFull`] policy of a stage, as reported in a [`StageReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenFullKind {
+    /// [`WhenFull::Block`]
+    Block,
+    /// [`WhenFull::DropOldest`]
+    DropOldest,
+    /// [`WhenFull::DropNewest`]
+    DropNewest,
+    /// [`WhenFull::Coalesce`]
+    Coalesce,
+}
+
+/// Options for a pipeline stage.
+#[derive(Builder, Clone)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct StageOptions<T> {
+    /// Name of the stage, used to identify it in a [`PipelineReport`]
+    #[builder(setter(into))]
+    name: String,
+    /// Maximum number of items queued on the stage. Must be greater than zero.
+    #[builder(default = "DEFAULT_STAGE_CAPACITY")]
+    capacity: usize,
+    /// Policy applied when an item is sent while the stage is at capacity
+    #[builder(default)]
+    when_full: WhenFull<T>,
+}
+
+impl<T> StageOptionsBuilder<T> {
+    fn validate(&self) -> Result<(), String> {
+        if self.capacity == Some(0) {
+            return Err("capacity must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`StageSender::send`] when the [`StageReceiver`] has been dropped. Contains
+/// the item that could not be sent.
+#[derive(Error)]
+#[error("pipeline stage is closed")]
+pub struct StageClosedError<T>(pub T);
+
+impl<T> fmt::Debug for StageClosedError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StageClosedError").finish_non_exhaustive()
+    }
+}
+
+/// Snapshot of the state of a pipeline stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageReport {
+    /// Name of the stage
+    pub name: String,
+    /// Maximum number of items queued on the stage
+    pub capacity: usize,
+    /// Number of items currently queued on the stage
+    pub depth: usize,
+    /// Highest number of items queued on the stage at any time
+    pub high_watermark: usize,
+    /// Policy applied when the stage is at capacity
+    pub when_full: WhenFullKind,
+    /// Number of items accepted onto the stage
+    pub accepted: u64,
+    /// Number of items discarded because the stage was at capacity
+    pub dropped: u64,
+    /// Number of items merged into a queued item because the stage was at capacity
+    pub coalesced: u64,
+}
+
+/// Snapshot of the state of all stages of a [`Pipeline`], in the order they were added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineReport {
+    /// Reports of the individual stages
+    pub stages: Vec<StageReport>,
+}
+
+impl PipelineReport {
+    /// Returns the report of the stage with the given name, if any.
+    #[must_use]
+    pub fn stage(&self, name: &str) -> Option<&StageReport> {
+        self.stages.iter().find(|stage| stage.name == name)
+    }
+
+    /// Total number of items currently queued across all stages.
+    #[must_use]
+    pub fn total_depth(&self) -> usize {
+        self.stages.iter().map(|stage| stage.depth).sum()
+    }
+}
+
+/// Collection of the stages of a data pipeline, used to produce a [`PipelineReport`].
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    stages: Arc<Mutex<Vec<Arc<dyn ReportStage>>>>,
+}
+
+impl Pipeline {
+    /// Creates a new [`Pipeline`] without any stages.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stage to the pipeline, returning the sending and receiving halves of its channel.
+    ///
+    /// The upstream stage sends items on the [`StageSender`], and the downstream stage receives
+    /// them from the [`StageReceiver`].
+    ///
+    /// # Panics
+    /// if the pipeline mutex has been poisoned, which should not be possible
+    #[must_use]
+    pub fn add_stage<T: Send + 'static>(
+        &self,
+        options: StageOptions<T>,
+    ) -> (StageSender<T>, StageReceiver<T>) {
+        let (sender, receiver) = stage(options);
+        self.stages
+            .lock()
+            .unwrap()
+            .push(sender.shared.clone() as Arc<dyn ReportStage>);
+        (sender, receiver)
+    }
+
+    /// Returns a snapshot of the state of all stages of the pipeline.
+    ///
+    /// # Panics
+    /// if the pipeline mutex has been poisoned, which should not be possible
+    #[must_use]
+    pub fn report(&self) -> PipelineReport {
+        PipelineReport {
+            stages: self
+                .stages
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|stage| stage.report())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("report", &self.report())
+            .finish()
+    }
+}
+
+/// Creates a standalone stage that is not part of a [`Pipeline`], returning the sending and
+/// receiving halves of its channel.
+#[must_use]
+pub fn stage<T>(options: StageOptions<T>) -> (StageSender<T>, StageReceiver<T>) {
+    let shared = Arc::new(Shared {
+        name: options.name,
+        capacity: options.capacity,
+        when_full: options.when_full,
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(options.capacity.min(DEFAULT_STAGE_CAPACITY)),
+            senders: 1,
+            receiver_alive: true,
+            high_watermark: 0,
+            accepted: 0,
+            dropped: 0,
+            coalesced: 0,
+        }),
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+    });
+    (
+        StageSender {
+            shared: shared.clone(),
+        },
+        StageReceiver { shared },
+    )
+}
+
+/// Sending half of a pipeline stage. Can be cloned to send from multiple upstream tasks.
+pub struct StageSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> StageSender<T> {
+    /// Sends an item on the stage.
+    ///
+    /// If the stage is at capacity, the stage's [`WhenFull`] policy is applied. Only
+    /// [`WhenFull::Block`] waits; the other policies always return immediately.
+    ///
+    /// Returns `Ok(())` if the item was handled according to the policy, including when it
+    /// was dropped or coalesced.
+    ///
+    /// # Errors
+    /// Returns [`StageClosedError`] containing the item if the [`StageReceiver`] has been dropped
+    pub async fn send(&self, item: T) -> Result<(), StageClosedError<T>> {
+        let mut item = item;
+        loop {
+            let not_full = self.shared.not_full.notified();
+            tokio::pin!(not_full);
+            // Register for a wakeup before checking for room so that none can be missed
+            not_full.as_mut().enable();
+            match self.shared.try_push(item) {
+                Ok(()) => return Ok(()),
+                Err(TryPushError::Closed(returned)) => return Err(StageClosedError(returned)),
+                Err(TryPushError::Full(returned)) => item = returned,
+            }
+            not_full.await;
+        }
+    }
+
+    /// Returns a snapshot of the state of the stage.
+    #[must_use]
+    pub fn report(&self) -> StageReport {
+        self.shared.report()
+    }
+}
+
+impl<T> Clone for StageSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for StageSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            // Wake the receiver so that it can observe that the stage is closed
+            self.shared.not_empty.notify_one();
+        }
+    }
+}
+
+impl<T> fmt::Debug for StageSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StageSender")
+            .field("report", &self.report())
+            .finish()
+    }
+}
+
+/// Receiving half of a pipeline stage.
+pub struct StageReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> StageReceiver<T> {
+    /// Receives the next item from the stage, waiting until one is available.
+    ///
+    /// Returns `None` once all [`StageSender`]s have been dropped and no items remain queued.
+    ///
+    /// # Panics
+    /// if the stage mutex has been poisoned, which should not be possible
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let not_empty = self.shared.not_empty.notified();
+            tokio::pin!(not_empty);
+            // Register for a wakeup before checking for items so that none can be missed
+            not_empty.as_mut().enable();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(item) = state.queue.pop_front() {
+                    drop(state);
+                    self.shared.not_full.notify_one();
+                    return Some(item);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            not_empty.await;
+        }
+    }
+
+    /// Returns a snapshot of the state of the stage.
+    #[must_use]
+    pub fn report(&self) -> StageReport {
+        self.shared.report()
+    }
+}
+
+impl<T> Drop for StageReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver_alive = false;
+        state.queue.clear();
+        drop(state);
+        // Wake all blocked senders so that they can observe that the stage is closed
+        self.shared.not_full.notify_waiters();
+    }
+}
+
+impl<T> fmt::Debug for StageReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StageReceiver")
+            .field("report", &self.report())
+            .finish()
+    }
+}
+
+enum TryPushError<T> {
+    Full(T),
+    Closed(T),
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    high_watermark: usize,
+    accepted: u64,
+    dropped: u64,
+    coalesced: u64,
+}
+
+struct Shared<T> {
+    name: String,
+    capacity: usize,
+    when_full: WhenFull<T>,
+    state: Mutex<State<T>>,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+impl<T> Shared<T> {
+    fn try_push(&self, item: T) -> Result<(), TryPushError<T>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.receiver_alive {
+            return Err(TryPushError::Closed(item));
+        }
+        if state.queue.len() >= self.capacity {
+            match &self.when_full {
+                WhenFull::Block => return Err(TryPushError::Full(item)),
+                WhenFull::DropOldest => {
+                    state.queue.pop_front();
+                    state.queue.push_back(item);
+                    state.accepted += 1;
+                    state.dropped += 1;
+                }
+                WhenFull::DropNewest => {
+                    state.dropped += 1;
+                }
+                WhenFull::Coalesce(f) => {
+                    // The queue is full, so it can't be empty
+                    let newest = state.queue.pop_back().expect("full queue is not empty");
+                    state.queue.push_back(f(newest, item));
+                    state.coalesced += 1;
+                }
+            }
+            return Ok(());
+        }
+        state.queue.push_back(item);
+        state.accepted += 1;
+        state.high_watermark = state.high_watermark.max(state.queue.len());
+        drop(state);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    fn report(&self) -> StageReport {
+        let state = self.state.lock().unwrap();
+        StageReport {
+            name: self.name.clone(),
+            capacity: self.capacity,
+            depth: state.queue.len(),
+            high_watermark: state.high_watermark,
+            when_full: self.when_full.kind(),
+            accepted: state.accepted,
+            dropped: state.dropped,
+            coalesced: state.coalesced,
+        }
+    }
+}
+
+/// Type-erased access to the report of a stage, so that stages carrying different item types
+/// can be held by the same [`Pipeline`]
+trait ReportStage: Send + Sync {
+    fn report(&self) -> StageReport;
+}
+
+impl<T: Send> ReportStage for Shared<T> {
+    fn report(&self) -> StageReport {
+        Shared::report(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    const LOAD_TEST_ITEMS: u64 = 100_000;
+    const LOAD_TEST_CAPACITY: usize = 64;
+    const CAPACITY_U64: u64 = LOAD_TEST_CAPACITY as u64;
+
+    fn stage_options<T: Clone>(capacity: usize, when_full: WhenFull<T>) -> StageOptions<T> {
+        StageOptionsBuilder::default()
+            .name("test_stage")
+            .capacity(capacity)
+            .when_full(when_full)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_default_options() {
+        let options = StageOptionsBuilder::<u64>::default()
+            .name("test_stage")
+            .build()
+            .unwrap();
+        assert_eq!(options.capacity, DEFAULT_STAGE_CAPACITY);
+        assert!(matches!(options.when_full, WhenFull::Block));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        assert!(
+            StageOptionsBuilder::<u64>::default()
+                .name("test_stage")
+                .capacity(0)
+                .build()
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_items_received_in_order() {
+        let (sender, mut receiver) = stage(stage_options(4, WhenFull::Block));
+        for i in 0..4u64 {
+            sender.send(i).await.unwrap();
+        }
+        drop(sender);
+        for i in 0..4u64 {
+            assert_eq!(receiver.recv().await, Some(i));
+        }
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_send_after_receiver_dropped() {
+        let (sender, receiver) = stage(stage_options(4, WhenFull::Block));
+        drop(receiver);
+        let error = sender.send(7u64).await.unwrap_err();
+        assert_eq!(error.0, 7);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_send_resumes_when_room_is_made() {
+        let (sender, mut receiver) = stage(stage_options(1, WhenFull::Block));
+        sender.send(0u64).await.unwrap();
+        let blocked_sender = sender.clone();
+        let blocked_send = tokio::task::spawn(async move { blocked_sender.send(1).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked_send.is_finished());
+
+        assert_eq!(receiver.recv().await, Some(0));
+        blocked_send.await.unwrap().unwrap();
+        assert_eq!(receiver.recv().await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_blocked_send_fails_when_receiver_dropped() {
+        let (sender, receiver) = stage(stage_options(1, WhenFull::Block));
+        sender.send(0u64).await.unwrap();
+        let blocked_send = tokio::task::spawn(async move { sender.send(1).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(receiver);
+        assert_eq!(blocked_send.await.unwrap().unwrap_err().0, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_report() {
+        let pipeline = Pipeline::new();
+        let (sample_sender, _sample_receiver) =
+            pipeline.add_stage(stage_options(2, WhenFull::<u64>::DropNewest));
+        let (forward_sender, _forward_receiver) = pipeline.add_stage(
+            StageOptionsBuilder::default()
+                .name("forward")
+                .when_full(WhenFull::<String>::DropOldest)
+                .build()
+                .unwrap(),
+        );
+        for i in 0..3 {
+            sample_sender.send(i).await.unwrap();
+        }
+        forward_sender.send("data".to_string()).await.unwrap();
+
+        let report = pipeline.report();
+        assert_eq!(report.stages.len(), 2);
+        assert_eq!(report.total_depth(), 3);
+        assert_eq!(
+            report.stage("test_stage"),
+            Some(&StageReport {
+                name: "test_stage".to_string(),
+                capacity: 2,
+                depth: 2,
+                high_watermark: 2,
+                when_full: WhenFullKind::DropNewest,
+                accepted: 2,
+                dropped: 1,
+                coalesced: 0,
+            })
+        );
+        let forward = report.stage("forward").unwrap();
+        assert_eq!(forward.capacity, DEFAULT_STAGE_CAPACITY);
+        assert_eq!(forward.depth, 1);
+        assert_eq!(forward.when_full, WhenFullKind::DropOldest);
+    }
+
+    /// Sends many items to a stage whose sink has stalled, and checks that the stage never
+    /// grows past its capacity and that the policy decides which items remain queued
+    #[test_case(WhenFull::DropOldest, LOAD_TEST_ITEMS, LOAD_TEST_ITEMS - CAPACITY_U64, 0, (LOAD_TEST_ITEMS - CAPACITY_U64..LOAD_TEST_ITEMS).collect(); "drop oldest")]
+    #[test_case(WhenFull::DropNewest, CAPACITY_U64, LOAD_TEST_ITEMS - CAPACITY_U64, 0, (0..CAPACITY_U64).collect(); "drop newest")]
+    #[test_case(WhenFull::coalesce(|_, incoming| incoming), CAPACITY_U64, 0, LOAD_TEST_ITEMS - CAPACITY_U64, (0..CAPACITY_U64 - 1).chain([LOAD_TEST_ITEMS - 1]).collect(); "coalesce keep latest")]
+    #[tokio::test]
+    async fn test_load_with_stalled_sink(
+        when_full: WhenFull<u64>,
+        expected_accepted: u64,
+        expected_dropped: u64,
+        expected_coalesced: u64,
+        expected_queued: Vec<u64>,
+    ) {
+        let (sender, mut receiver) = stage(stage_options(LOAD_TEST_CAPACITY, when_full));
+        for i in 0..LOAD_TEST_ITEMS {
+            sender.send(i).await.unwrap();
+            assert!(sender.report().depth <= LOAD_TEST_CAPACITY);
+        }
+
+        let report = sender.report();
+        assert_eq!(report.depth, LOAD_TEST_CAPACITY);
+        assert_eq!(report.high_watermark, LOAD_TEST_CAPACITY);
+        assert_eq!(report.accepted, expected_accepted);
+        assert_eq!(report.dropped, expected_dropped);
+        assert_eq!(report.coalesced, expected_coalesced);
+
+        drop(sender);
+        for expected in expected_queued {
+            assert_eq!(receiver.recv().await, Some(expected));
+        }
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_load_with_stalled_sink_block() {
+        let (sender, _receiver) = stage(stage_options(LOAD_TEST_CAPACITY, WhenFull::Block));
+        let producer_sender = sender.clone();
+        let producer = tokio::task::spawn(async move {
+            for i in 0..LOAD_TEST_ITEMS {
+                producer_sender.send(i).await.unwrap();
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The producer is held back by the stalled sink rather than growing the stage
+        assert!(!producer.is_finished());
+        let report = sender.report();
+        assert_eq!(report.depth, LOAD_TEST_CAPACITY);
+        assert_eq!(report.high_watermark, LOAD_TEST_CAPACITY);
+        assert_eq!(report.accepted, CAPACITY_U64);
+        assert_eq!(report.dropped, 0);
+        producer.abort();
+    }
+}
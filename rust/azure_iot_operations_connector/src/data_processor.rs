@@ -4,3 +4,4 @@
 //! Pre-built data processors for common use cases.
 
 pub mod derived_json;
+pub mod transform;
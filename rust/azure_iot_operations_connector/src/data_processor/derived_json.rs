@@ -1,13 +1,21 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-//! Processor for generating [`MessageSchema`] for the JSON payload defined in a [`Data`].
+//! Processor for generating [`MessageSchema`] for the JSON or CBOR payload defined in a [`Data`].
 
+use std::collections::HashMap;
+
+use azure_iot_operations_protocol::common::payload_serialize::{
+    Cbor, CborError, DeserializationError, FormatIndicator, PayloadSerialize, is_cbor_content_type,
+};
 use azure_iot_operations_services::schema_registry::{Format, SchemaType};
 use serde_json::{self, Value};
 
 use crate::{Data, MessageSchema, MessageSchemaBuilder, MessageSchemaBuilderError};
 
+/// Tag added to the [`MessageSchema`] of data that is not JSON, recording its content type.
+pub const CONTENT_TYPE_TAG: &str = "contentType";
+
 /// An error that occurred during the schema generation of data.
 #[derive(Debug, thiserror::Error)]
 #[error("{repr}")]
@@ -22,11 +30,17 @@ enum SchemaGenerationErrorRepr {
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
     #[error(transparent)]
+    Cbor(#[from] DeserializationError<CborError>),
+    #[error(transparent)]
     Schema(#[from] MessageSchemaBuilderError),
 }
 
 /// Returns a new [`MessageSchema`] that describes it.
 ///
+/// The payload is parsed as CBOR if the content type of the data is `application/cbor`, and as
+/// JSON otherwise. The schema of CBOR data is a JSON schema of the decoded values, tagged with the
+/// content type under [`CONTENT_TYPE_TAG`] so that it can be told apart from the schema of JSON data.
+///
 /// # Limitations
 /// - Cannot correctly interpret enums as it derives the schema only from JSON payload provided.
 /// - Similarly, optionality of fields cannot be inferred correctly in the schema.
@@ -50,8 +64,19 @@ pub fn create_schema(data: &Data) -> Result<MessageSchema, SchemaGenerationError
 /// Returns an error if the transformation or schema generation cannot be made.
 /// Input data will not be modified.
 fn create_output_schema(data: &Data) -> Result<MessageSchema, SchemaGenerationErrorRepr> {
-    // Parse the input JSON from bytes
-    let output_json: Value = serde_json::from_slice(&data.payload)?;
+    // Parse the input JSON from bytes, decoding it from CBOR if necessary
+    let mut tags = HashMap::new();
+    let output_json: Value = if is_cbor_content_type(&data.content_type) {
+        tags.insert(CONTENT_TYPE_TAG.to_string(), data.content_type.clone());
+        Cbor::<Value>::deserialize(
+            &data.payload,
+            Some(&data.content_type),
+            &FormatIndicator::UnspecifiedBytes,
+        )?
+        .into_inner()
+    } else {
+        serde_json::from_slice(&data.payload)?
+    };
 
     // Derive the schema from the output JSON, removing the unnecessary examples metadata
     let mut output_root_schema = schemars::schema_for_value!(&output_json);
@@ -64,6 +89,7 @@ fn create_output_schema(data: &Data) -> Result<MessageSchema, SchemaGenerationEr
         .schema_content(serde_json::to_string(&output_root_schema)?)
         .format(Format::JsonSchemaDraft07)
         .schema_type(SchemaType::MessageSchema)
+        .tags(tags)
         .build()?;
 
     Ok(output_message_schema)
@@ -72,6 +98,7 @@ fn create_output_schema(data: &Data) -> Result<MessageSchema, SchemaGenerationEr
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::message_schema;
     use test_case::test_case;
 
     struct SchemaGenerationTestCase {
@@ -79,29 +106,6 @@ mod test {
         expected_output_json_schema: Value,
     }
 
-    /// Helper function to compare two `MessageSchema` structs for equality.
-    /// This is necessary over the PartialEq/Eq trait because when using JSON, we can
-    /// end up with different ordering of the keys in the JSON object, which prevents
-    /// us from being able to make accurate comparisons of the `content` field.
-    fn message_schema_eq(schema1: &MessageSchema, schema2: &MessageSchema) -> bool {
-        // Make new structs with the content set to empty strings to normalize our MessageSchema
-        // under comparison since we can't directly compare the content accurately.
-        let schema1_no_content = MessageSchema {
-            schema_content: String::new(),
-            ..schema1.clone()
-        };
-        let schema2_no_content = MessageSchema {
-            schema_content: String::new(),
-            ..schema2.clone()
-        };
-
-        // Compare the content of the schemas
-        let schema1_json_content: Value = serde_json::from_str(&schema1.schema_content).unwrap();
-        let schema2_json_content: Value = serde_json::from_str(&schema2.schema_content).unwrap();
-
-        schema1_no_content == schema2_no_content && schema1_json_content == schema2_json_content
-    }
-
     /// Test case for 1:1 transformation of JSON values
     fn valid_testcase_1() -> SchemaGenerationTestCase {
         let input_json_str = r#"{
@@ -248,7 +252,37 @@ mod test {
 
         let output_message_schema = create_schema(&input_data).unwrap();
 
-        assert!(message_schema_eq(
+        assert!(message_schema::is_equivalent(
+            &output_message_schema,
+            &expected_output_message_schema
+        ));
+    }
+
+    #[test]
+    fn valid_create_schema_cbor() {
+        let test_case = valid_testcase_1();
+        let input_data = Data {
+            payload: Cbor(test_case.input_json).serialize().unwrap().payload,
+            content_type: "application/cbor".to_string(),
+            custom_user_data: vec![],
+            timestamp: None,
+        };
+
+        // We expect the same JSON schema as for the equivalent JSON payload, tagged as CBOR
+        let expected_output_message_schema = MessageSchemaBuilder::default()
+            .schema_content(serde_json::to_string(&test_case.expected_output_json_schema).unwrap())
+            .format(Format::JsonSchemaDraft07)
+            .schema_type(SchemaType::MessageSchema)
+            .tags(HashMap::from([(
+                CONTENT_TYPE_TAG.to_string(),
+                "application/cbor".to_string(),
+            )]))
+            .build()
+            .unwrap();
+
+        let output_message_schema = create_schema(&input_data).unwrap();
+
+        assert!(message_schema::is_equivalent(
             &output_message_schema,
             &expected_output_message_schema
         ));
@@ -267,4 +301,18 @@ mod test {
         let r = create_schema(&input_data);
         assert!(r.is_err());
     }
+
+    #[test_case(&[0xa1, 0x61]; "Truncated CBOR")]
+    #[test_case(&[0xff]; "Invalid CBOR")]
+    fn invalid_cbor_data_payload(invalid_payload: &[u8]) {
+        let input_data = Data {
+            payload: invalid_payload.into(),
+            content_type: "application/cbor".to_string(),
+            custom_user_data: vec![],
+            timestamp: None,
+        };
+
+        let r = create_schema(&input_data);
+        assert!(r.is_err());
+    }
 }
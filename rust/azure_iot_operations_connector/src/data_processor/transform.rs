@@ -0,0 +1,1027 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Transformation of [`Data`] by a chain of [`DataTransformer`]s, which can be declared in
+//! configuration rather than coded.
+//!
+//! A declarative transform chain is a JSON array of transform specifications, each identified by
+//! its `type`. The built-in transforms operate on JSON payloads and refer to values within the
+//! payload with a `JSONPath` subset: `$` for the root, `.name` or `['name']` for object members
+//! and `[index]` for array elements (e.g. `$.sensors[0].temperature`).
+//!
+//! ```json
+//! [
+//!     { "type": "filter", "field": "$.status", "operator": "eq", "value": "active" },
+//!     { "type": "scale", "field": "$.temperature", "factor": 1.8, "offset": 32 },
+//!     { "type": "rename", "from": "$.temperature", "to": "$.temperatureF" },
+//!     { "type": "map", "field": "$.status", "values": { "active": 1 }, "default": 0 },
+//!     { "type": "jsonpathExtract", "path": "$.readings" },
+//!     { "type": "split", "path": "$.channels", "shared": ["$.quality"] }
+//! ]
+//! ```
+//!
+//! A `split` transform fans one payload out into one [`Data`] per element of an array or member of
+//! an object. Each child carries a naming token (the array index or member name) in its
+//! [`custom_user_data`](Data::custom_user_data) under [`SPLIT_TOKEN_KEY`], which MQTT destinations
+//! also substitute for `{splitToken}` in their topic. Transforms after a split apply to each child.
+//!
+//! A transform chain can be declared under the [`TRANSFORMS_KEY`] key of the JSON configuration of
+//! a Dataset, in which case it is applied to all data forwarded for that Dataset.
+
+use std::fmt::Debug;
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use crate::Data;
+
+/// Key of the transform chain in the JSON configuration of a Dataset.
+pub const TRANSFORMS_KEY: &str = "transforms";
+
+/// Key of the naming token of [`Data`] produced by a [`TransformSpec::Split`], both in its
+/// [`custom_user_data`](Data::custom_user_data) and as a topic token of MQTT destinations.
+pub const SPLIT_TOKEN_KEY: &str = "splitToken";
+
+/// An error that occurred while transforming data.
+#[derive(Debug, thiserror::Error)]
+#[error("{repr}")]
+pub struct TransformError {
+    #[source]
+    repr: TransformErrorRepr,
+}
+
+impl TransformError {
+    /// Creates a new [`TransformError`] with the given message, for use by custom
+    /// [`DataTransformer`]s.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            repr: TransformErrorRepr::Transform(message.into()),
+        }
+    }
+}
+
+/// Inner representation of a [`TransformError`].
+#[derive(Debug, thiserror::Error)]
+enum TransformErrorRepr {
+    #[error("payload is not valid JSON: {0}")]
+    Payload(#[from] serde_json::Error),
+    #[error("{0}")]
+    Transform(String),
+}
+
+/// An error in the specification of a transform chain.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {}: {message}", index.map_or_else(|| "transforms".to_string(), |i| format!("transform at index {i}")))]
+pub struct TransformConfigError {
+    /// Index of the invalid transform in the chain, if the error is specific to one transform
+    index: Option<usize>,
+    message: String,
+}
+
+/// Transforms [`Data`] before it is forwarded to its destination.
+pub trait DataTransformer: Debug + Send + Sync {
+    /// Transforms the data.
+    ///
+    /// Returns the data to forward, which may be more than one item if the data has been split, or
+    /// none if the data has been filtered out and should not be forwarded.
+    ///
+    /// # Errors
+    /// [`TransformError`] if the data cannot be transformed
+    fn transform(&self, data: Data) -> Result<Vec<Data>, TransformError>;
+}
+
+/// Comparison applied by a [`TransformSpec::Filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterOperator {
+    /// The field is equal to the value
+    Eq,
+    /// The field is missing or not equal to the value
+    Ne,
+    /// The field is a number greater than the value
+    Gt,
+    /// The field is a number greater than or equal to the value
+    Gte,
+    /// The field is a number less than the value
+    Lt,
+    /// The field is a number less than or equal to the value
+    Lte,
+    /// The field is present. The value is ignored.
+    Exists,
+}
+
+/// Behavior of a [`TransformSpec::Split`] when its path does not select elements to split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SplitFallback {
+    /// Produce no data, as if the data had been filtered out
+    Skip,
+    /// Fail to transform the data
+    Error,
+}
+
+/// Declarative specification of a built-in transform.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+pub enum TransformSpec {
+    /// Replaces the value of a field with the value it maps to. Strings are looked up as is,
+    /// other values by their JSON representation. Values without a mapping are replaced by
+    /// `default` if it is set, otherwise left unchanged.
+    Map {
+        /// Path of the field to map
+        field: String,
+        /// Mapping of field values to their replacements
+        values: Map<String, Value>,
+        /// Replacement for values without a mapping
+        #[serde(default)]
+        default: Option<Value>,
+    },
+    /// Keeps only the data whose field satisfies the condition; other data is not forwarded.
+    Filter {
+        /// Path of the field to compare
+        field: String,
+        /// Comparison to apply
+        operator: FilterOperator,
+        /// Value to compare the field to
+        #[serde(default)]
+        value: Value,
+    },
+    /// Replaces a numeric field `x` with `x * factor + offset`.
+    Scale {
+        /// Path of the field to scale
+        field: String,
+        /// Factor to multiply the field by
+        #[serde(default = "default_factor")]
+        factor: f64,
+        /// Offset to add to the field after multiplying it
+        #[serde(default)]
+        offset: f64,
+    },
+    /// Moves a field to a new path.
+    Rename {
+        /// Current path of the field
+        from: String,
+        /// New path of the field
+        to: String,
+    },
+    /// Extracts the value at a path, either replacing the payload with it or, if `target` is
+    /// set, copying it to the `target` path.
+    JsonpathExtract {
+        /// Path of the value to extract
+        path: String,
+        /// Path to copy the extracted value to
+        #[serde(default)]
+        target: Option<String>,
+    },
+    /// Splits the array or object at a path into one payload per element, whose naming token is
+    /// the array index or member name. The [`timestamp`](Data::timestamp) and other metadata of
+    /// the data are copied to each payload.
+    Split {
+        /// Path of the array or object to split
+        path: String,
+        /// Paths of fields copied from the payload into each split payload at the same path, e.g.
+        /// a quality shared by all readings. Split payloads must be objects to receive them.
+        #[serde(default)]
+        shared: Vec<String>,
+        /// Behavior if the path is missing or selects an empty array or object
+        #[serde(default = "default_on_empty")]
+        on_empty: SplitFallback,
+        /// Behavior if the path selects a value that is neither an array nor an object
+        #[serde(default = "default_on_non_collection")]
+        on_non_collection: SplitFallback,
+    },
+}
+
+fn default_factor() -> f64 {
+    1.0
+}
+
+fn default_on_empty() -> SplitFallback {
+    SplitFallback::Skip
+}
+
+fn default_on_non_collection() -> SplitFallback {
+    SplitFallback::Error
+}
+
+/// An ordered chain of [`DataTransformer`]s. Data is passed through each transformer in turn,
+/// stopping as soon as one filters it out. Each item produced by a transformer that splits data
+/// is passed through the rest of the chain on its own.
+#[derive(Debug, Default)]
+pub struct TransformChain {
+    transformers: Vec<Box<dyn DataTransformer>>,
+}
+
+impl TransformChain {
+    /// Creates a new, empty [`TransformChain`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a [`TransformChain`] of built-in transformers from their specifications.
+    ///
+    /// # Errors
+    /// [`TransformConfigError`] if a specification contains an invalid path
+    pub fn from_specs(specs: Vec<TransformSpec>) -> Result<Self, TransformConfigError> {
+        let steps = specs
+            .into_iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                JsonStep::compile(spec).map_err(|message| TransformConfigError {
+                    index: Some(index),
+                    message,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut chain = Self::new();
+        if !steps.is_empty() {
+            chain.push(JsonTransformer { steps });
+        }
+        Ok(chain)
+    }
+
+    /// Creates a [`TransformChain`] of built-in transformers from a JSON array of their
+    /// specifications.
+    ///
+    /// # Errors
+    /// [`TransformConfigError`] if the specification is not a JSON array, or if any transform in
+    /// it is of an unknown type, is missing a required property or contains an invalid path
+    pub fn from_json(specs: &str) -> Result<Self, TransformConfigError> {
+        let specs: Value = serde_json::from_str(specs).map_err(|e| TransformConfigError {
+            index: None,
+            message: e.to_string(),
+        })?;
+        Self::from_json_value(specs)
+    }
+
+    /// Creates a [`TransformChain`] of built-in transformers from an already parsed JSON array of
+    /// their specifications. See [`TransformChain::from_json`].
+    ///
+    /// # Errors
+    /// [`TransformConfigError`] if the specification is not a JSON array, or if any transform in
+    /// it is of an unknown type, is missing a required property or contains an invalid path
+    pub fn from_json_value(specs: Value) -> Result<Self, TransformConfigError> {
+        let Value::Array(specs) = specs else {
+            return Err(TransformConfigError {
+                index: None,
+                message: "must be a JSON array".to_string(),
+            });
+        };
+        let specs = specs
+            .into_iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                TransformSpec::deserialize(spec).map_err(|e| TransformConfigError {
+                    index: Some(index),
+                    message: e.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_specs(specs)
+    }
+
+    /// Appends a transformer to the end of the chain.
+    pub fn push(&mut self, transformer: impl DataTransformer + 'static) -> &mut Self {
+        self.transformers.push(Box::new(transformer));
+        self
+    }
+
+    /// Returns true if the chain contains no transformers.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.transformers.is_empty()
+    }
+
+    /// Passes the data through each transformer of the chain in turn.
+    ///
+    /// Returns the data to forward, which is empty if a transformer filtered the data out.
+    ///
+    /// # Errors
+    /// [`TransformError`] if any transformer fails to transform the data
+    pub fn apply(&self, data: Data) -> Result<Vec<Data>, TransformError> {
+        let mut items = vec![data];
+        for transformer in &self.transformers {
+            let mut transformed = Vec::with_capacity(items.len());
+            for data in items {
+                transformed.extend(transformer.transform(data)?);
+            }
+            items = transformed;
+        }
+        Ok(items)
+    }
+}
+
+/// Built-in transformer applying consecutive [`TransformSpec`]s to a JSON payload, which is only
+/// parsed and serialized once for all of them
+#[derive(Debug)]
+struct JsonTransformer {
+    steps: Vec<JsonStep>,
+}
+
+impl DataTransformer for JsonTransformer {
+    fn transform(&self, data: Data) -> Result<Vec<Data>, TransformError> {
+        let payload: Value =
+            serde_json::from_slice(&data.payload).map_err(|e| TransformError { repr: e.into() })?;
+        // Payloads to forward, with the naming token of the latest split that produced them
+        let mut payloads = vec![(payload, None)];
+        for (index, step) in self.steps.iter().enumerate() {
+            let mut transformed = Vec::with_capacity(payloads.len());
+            for (payload, token) in payloads {
+                let outputs = step.apply(payload).map_err(|message| {
+                    TransformError::new(format!("transform at index {index} failed: {message}"))
+                })?;
+                transformed.extend(
+                    outputs
+                        .into_iter()
+                        .map(|(payload, split_token)| (payload, split_token.or(token.clone()))),
+                );
+            }
+            payloads = transformed;
+        }
+        payloads
+            .into_iter()
+            .map(|(payload, token)| {
+                let mut transformed = data.clone();
+                transformed.payload =
+                    serde_json::to_vec(&payload).map_err(|e| TransformError { repr: e.into() })?;
+                if let Some(token) = token {
+                    transformed
+                        .custom_user_data
+                        .retain(|(key, _)| key != SPLIT_TOKEN_KEY);
+                    transformed
+                        .custom_user_data
+                        .push((SPLIT_TOKEN_KEY.to_string(), token));
+                }
+                Ok(transformed)
+            })
+            .collect()
+    }
+}
+
+/// A [`TransformSpec`] with its paths parsed
+#[derive(Debug)]
+enum JsonStep {
+    Map {
+        field: JsonPath,
+        values: Map<String, Value>,
+        default: Option<Value>,
+    },
+    Filter {
+        field: JsonPath,
+        operator: FilterOperator,
+        value: Value,
+    },
+    Scale {
+        field: JsonPath,
+        factor: f64,
+        offset: f64,
+    },
+    Rename {
+        from: JsonPath,
+        to: JsonPath,
+    },
+    JsonpathExtract {
+        path: JsonPath,
+        target: Option<JsonPath>,
+    },
+    Split {
+        path: JsonPath,
+        shared: Vec<JsonPath>,
+        on_empty: SplitFallback,
+        on_non_collection: SplitFallback,
+    },
+}
+
+impl JsonStep {
+    fn compile(spec: TransformSpec) -> Result<Self, String> {
+        Ok(match spec {
+            TransformSpec::Map {
+                field,
+                values,
+                default,
+            } => JsonStep::Map {
+                field: JsonPath::parse_field(&field)?,
+                values,
+                default,
+            },
+            TransformSpec::Filter {
+                field,
+                operator,
+                value,
+            } => JsonStep::Filter {
+                field: JsonPath::parse(&field)?,
+                operator,
+                value,
+            },
+            TransformSpec::Scale {
+                field,
+                factor,
+                offset,
+            } => {
+                if !factor.is_finite() || !offset.is_finite() {
+                    return Err("'factor' and 'offset' must be finite numbers".to_string());
+                }
+                JsonStep::Scale {
+                    field: JsonPath::parse_field(&field)?,
+                    factor,
+                    offset,
+                }
+            }
+            TransformSpec::Rename { from, to } => JsonStep::Rename {
+                from: JsonPath::parse_field(&from)?,
+                to: JsonPath::parse_field(&to)?,
+            },
+            TransformSpec::JsonpathExtract { path, target } => JsonStep::JsonpathExtract {
+                path: JsonPath::parse(&path)?,
+                target: target.as_deref().map(JsonPath::parse_field).transpose()?,
+            },
+            TransformSpec::Split {
+                path,
+                shared,
+                on_empty,
+                on_non_collection,
+            } => JsonStep::Split {
+                path: JsonPath::parse(&path)?,
+                shared: shared
+                    .iter()
+                    .map(|field| JsonPath::parse_field(field))
+                    .collect::<Result<_, _>>()?,
+                on_empty,
+                on_non_collection,
+            },
+        })
+    }
+
+    /// Applies the step to a payload, returning the resulting payloads along with their naming
+    /// token if they were produced by a split. Returns no payloads if the payload was filtered out.
+    fn apply(&self, mut payload: Value) -> Result<Vec<(Value, Option<String>)>, String> {
+        match self {
+            JsonStep::Map {
+                field,
+                values,
+                default,
+            } => {
+                if let Some(value) = field.get_mut(&mut payload) {
+                    let key = match &*value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    if let Some(replacement) = values.get(&key).or(default.as_ref()) {
+                        *value = replacement.clone();
+                    }
+                }
+            }
+            JsonStep::Filter {
+                field,
+                operator,
+                value,
+            } => {
+                if !filter_matches(field.get(&payload), *operator, value) {
+                    return Ok(vec![]);
+                }
+            }
+            JsonStep::Scale {
+                field,
+                factor,
+                offset,
+            } => {
+                if let Some(value) = field.get_mut(&mut payload) {
+                    let Some(number) = value.as_f64() else {
+                        return Err(format!("'{field}' is not a number"));
+                    };
+                    *value = Number::from_f64(number * factor + offset)
+                        .map(Value::Number)
+                        .ok_or_else(|| format!("scaled value of '{field}' is not finite"))?;
+                }
+            }
+            JsonStep::Rename { from, to } => {
+                if let Some(value) = from.remove(&mut payload) {
+                    to.set(&mut payload, value)?;
+                }
+            }
+            JsonStep::JsonpathExtract { path, target } => {
+                let extracted = path
+                    .get(&payload)
+                    .cloned()
+                    .ok_or_else(|| format!("'{path}' is not present in the payload"))?;
+                match target {
+                    Some(target) => target.set(&mut payload, extracted)?,
+                    None => payload = extracted,
+                }
+            }
+            JsonStep::Split {
+                path,
+                shared,
+                on_empty,
+                on_non_collection,
+            } => {
+                let elements: Vec<(String, Value)> = match path.get(&payload) {
+                    Some(Value::Array(array)) => array
+                        .iter()
+                        .enumerate()
+                        .map(|(index, element)| (index.to_string(), element.clone()))
+                        .collect(),
+                    Some(Value::Object(object)) => object
+                        .iter()
+                        .map(|(name, element)| (name.clone(), element.clone()))
+                        .collect(),
+                    Some(_) => {
+                        return match on_non_collection {
+                            SplitFallback::Skip => Ok(vec![]),
+                            SplitFallback::Error => {
+                                Err(format!("'{path}' is neither an array nor an object"))
+                            }
+                        };
+                    }
+                    None => vec![],
+                };
+                if elements.is_empty() {
+                    return match on_empty {
+                        SplitFallback::Skip => Ok(vec![]),
+                        SplitFallback::Error => Err(format!("'{path}' has no elements to split")),
+                    };
+                }
+                return elements
+                    .into_iter()
+                    .map(|(token, mut element)| {
+                        for field in shared {
+                            if let Some(value) = field.get(&payload) {
+                                field.set(&mut element, value.clone())?;
+                            }
+                        }
+                        Ok((element, Some(token)))
+                    })
+                    .collect();
+            }
+        }
+        Ok(vec![(payload, None)])
+    }
+}
+
+/// Evaluates the condition of a [`TransformSpec::Filter`]
+fn filter_matches(field: Option<&Value>, operator: FilterOperator, value: &Value) -> bool {
+    let numeric = |compare: fn(f64, f64) -> bool| {
+        field
+            .and_then(Value::as_f64)
+            .zip(value.as_f64())
+            .is_some_and(|(field, value)| compare(field, value))
+    };
+    match operator {
+        FilterOperator::Exists => field.is_some(),
+        FilterOperator::Eq => field.is_some_and(|field| json_eq(field, value)),
+        FilterOperator::Ne => !field.is_some_and(|field| json_eq(field, value)),
+        FilterOperator::Gt => numeric(|a, b| a > b),
+        FilterOperator::Gte => numeric(|a, b| a >= b),
+        FilterOperator::Lt => numeric(|a, b| a < b),
+        FilterOperator::Lte => numeric(|a, b| a <= b),
+    }
+}
+
+/// Compares JSON values, treating numbers as equal if they have the same value regardless of
+/// their representation (e.g. `1` and `1.0`)
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        #[allow(clippy::float_cmp)]
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Segment of a [`JsonPath`]
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Member(String),
+    Index(usize),
+}
+
+/// Path to a value within a JSON document, parsed from a `JSONPath` subset
+#[derive(Debug, Clone, PartialEq)]
+struct JsonPath {
+    source: String,
+    segments: Vec<PathSegment>,
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl JsonPath {
+    /// Parses a path of the form `$.member['member'][index]`
+    fn parse(path: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("invalid path '{path}': {reason}");
+        let mut rest = path
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("must start with '$'"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(member) = rest.strip_prefix('.') {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                if end == 0 {
+                    return Err(invalid("empty member name"));
+                }
+                segments.push(PathSegment::Member(member[..end].to_string()));
+                rest = &member[end..];
+            } else if let Some(quoted) = rest.strip_prefix("['") {
+                let end = quoted
+                    .find("']")
+                    .ok_or_else(|| invalid("unterminated member name"))?;
+                segments.push(PathSegment::Member(quoted[..end].to_string()));
+                rest = &quoted[end + 2..];
+            } else if let Some(index) = rest.strip_prefix('[') {
+                let end = index
+                    .find(']')
+                    .ok_or_else(|| invalid("unterminated index"))?;
+                let parsed = index[..end]
+                    .parse()
+                    .map_err(|_| invalid("index must be a non-negative integer"))?;
+                segments.push(PathSegment::Index(parsed));
+                rest = &index[end + 1..];
+            } else {
+                return Err(invalid("expected '.' or '['"));
+            }
+        }
+        Ok(Self {
+            source: path.to_string(),
+            segments,
+        })
+    }
+
+    /// Parses a path that must refer to a value below the root
+    fn parse_field(path: &str) -> Result<Self, String> {
+        let parsed = Self::parse(path)?;
+        if parsed.segments.is_empty() {
+            return Err(format!("invalid path '{path}': must not be the root"));
+        }
+        Ok(parsed)
+    }
+
+    fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Member(name) => value.get(name),
+                PathSegment::Index(index) => value.get(index),
+            })
+    }
+
+    fn get_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Member(name) => value.get_mut(name),
+                PathSegment::Index(index) => value.get_mut(index),
+            })
+    }
+
+    /// Removes the value at the path, returning it if it was present
+    fn remove(&self, value: &mut Value) -> Option<Value> {
+        let (last, parents) = self.segments.split_last()?;
+        let parent = parents
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Member(name) => value.get_mut(name),
+                PathSegment::Index(index) => value.get_mut(index),
+            })?;
+        match (last, parent) {
+            (PathSegment::Member(name), Value::Object(object)) => object.remove(name),
+            (PathSegment::Index(index), Value::Array(array)) if *index < array.len() => {
+                Some(array.remove(*index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sets the value at the path, creating missing parent objects
+    fn set(&self, value: &mut Value, new_value: Value) -> Result<(), String> {
+        let mut current = value;
+        for segment in &self.segments {
+            current = match segment {
+                PathSegment::Member(name) => {
+                    if current.is_null() {
+                        *current = Value::Object(Map::new());
+                    }
+                    let Value::Object(object) = current else {
+                        return Err(format!(
+                            "cannot set '{self}': '{name}' has a non-object parent"
+                        ));
+                    };
+                    object.entry(name.clone()).or_insert(Value::Null)
+                }
+                PathSegment::Index(index) => current
+                    .get_mut(index)
+                    .ok_or_else(|| format!("cannot set '{self}': index {index} is not present"))?,
+            };
+        }
+        *current = new_value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn data(payload: &Value) -> Data {
+        Data {
+            payload: serde_json::to_vec(payload).unwrap(),
+            content_type: "application/json".to_string(),
+            custom_user_data: vec![],
+            timestamp: None,
+        }
+    }
+
+    fn apply(transforms: &Value, payload: &Value) -> Option<Value> {
+        let mut transformed = TransformChain::from_json_value(transforms.clone())
+            .unwrap()
+            .apply(data(payload))
+            .unwrap();
+        assert!(transformed.len() <= 1);
+        transformed
+            .pop()
+            .map(|data| serde_json::from_slice(&data.payload).unwrap())
+    }
+
+    fn split_token(data: &Data) -> Option<&str> {
+        data.custom_user_data
+            .iter()
+            .find(|(key, _)| key == SPLIT_TOKEN_KEY)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn empty_chain() {
+        let chain = TransformChain::from_json("[]").unwrap();
+        assert!(chain.is_empty());
+        let input = Data {
+            payload: b"not json".to_vec(),
+            ..data(&json!(null))
+        };
+        assert_eq!(chain.apply(input.clone()).unwrap(), vec![input]);
+    }
+
+    #[test_case(json!({"status": "on"}), json!({"status": 1}); "mapped")]
+    #[test_case(json!({"status": "unknown"}), json!({"status": -1}); "default")]
+    #[test_case(json!({"status": true}), json!({"status": 2}); "non string")]
+    #[test_case(json!({"other": "on"}), json!({"other": "on"}); "missing field")]
+    #[allow(clippy::needless_pass_by_value)]
+    fn map(payload: Value, expected: Value) {
+        let transforms = json!([{
+            "type": "map",
+            "field": "$.status",
+            "values": {"on": 1, "true": 2},
+            "default": -1
+        }]);
+        assert_eq!(apply(&transforms, &payload), Some(expected));
+    }
+
+    #[test_case("eq", json!("active"), json!({"status": "active"}), true; "eq match")]
+    #[test_case("eq", json!("active"), json!({"status": "idle"}), false; "eq mismatch")]
+    #[test_case("eq", json!(1), json!({"status": 1.0}), true; "eq numeric representation")]
+    #[test_case("ne", json!("active"), json!({}), true; "ne missing")]
+    #[test_case("gt", json!(10), json!({"status": 11}), true; "gt match")]
+    #[test_case("gt", json!(10), json!({"status": 10}), false; "gt mismatch")]
+    #[test_case("gte", json!(10), json!({"status": 10}), true; "gte match")]
+    #[test_case("lt", json!(10), json!({"status": "5"}), false; "lt non numeric")]
+    #[test_case("lte", json!(10), json!({"status": 10}), true; "lte match")]
+    #[test_case("exists", Value::Null, json!({"status": null}), true; "exists match")]
+    #[test_case("exists", Value::Null, json!({}), false; "exists mismatch")]
+    #[allow(clippy::needless_pass_by_value)]
+    fn filter(operator: &str, value: Value, payload: Value, expect_kept: bool) {
+        let transforms = json!([{
+            "type": "filter",
+            "field": "$.status",
+            "operator": operator,
+            "value": value
+        }]);
+        assert_eq!(apply(&transforms, &payload).is_some(), expect_kept);
+    }
+
+    #[test]
+    fn scale() {
+        let transforms = json!([{"type": "scale", "field": "$.temp", "factor": 1.8, "offset": 32}]);
+        assert_eq!(
+            apply(&transforms, &json!({"temp": 100})),
+            Some(json!({"temp": 212.0}))
+        );
+        assert_eq!(apply(&transforms, &json!({})), Some(json!({})));
+    }
+
+    #[test]
+    fn scale_non_numeric() {
+        let chain =
+            TransformChain::from_json(r#"[{"type": "scale", "field": "$.temp", "offset": 1}]"#)
+                .unwrap();
+        let error = chain.apply(data(&json!({"temp": "hot"}))).unwrap_err();
+        assert!(error.to_string().contains("'$.temp' is not a number"));
+    }
+
+    #[test]
+    fn rename() {
+        let transforms = json!([{"type": "rename", "from": "$.a.b", "to": "$.c['d e']"}]);
+        assert_eq!(
+            apply(&transforms, &json!({"a": {"b": 1, "x": 2}})),
+            Some(json!({"a": {"x": 2}, "c": {"d e": 1}}))
+        );
+    }
+
+    #[test]
+    fn jsonpath_extract() {
+        let payload = json!({"readings": [{"value": 1}, {"value": 2}]});
+        assert_eq!(
+            apply(
+                &json!([{"type": "jsonpathExtract", "path": "$.readings[1]"}]),
+                &payload
+            ),
+            Some(json!({"value": 2}))
+        );
+        assert_eq!(
+            apply(
+                &json!([{"type": "jsonpathExtract", "path": "$.readings[0].value", "target": "$.first"}]),
+                &payload
+            ),
+            Some(json!({"readings": [{"value": 1}, {"value": 2}], "first": 1}))
+        );
+    }
+
+    #[test]
+    fn chain_stops_when_filtered() {
+        let transforms = json!([
+            {"type": "filter", "field": "$.temp", "operator": "gte", "value": 0},
+            {"type": "jsonpathExtract", "path": "$.missing"}
+        ]);
+        assert_eq!(apply(&transforms, &json!({"temp": -1})), None);
+    }
+
+    #[test]
+    fn custom_transformer() {
+        #[derive(Debug)]
+        struct Uppercase;
+        impl DataTransformer for Uppercase {
+            fn transform(&self, mut data: Data) -> Result<Vec<Data>, TransformError> {
+                data.payload.make_ascii_uppercase();
+                Ok(vec![data])
+            }
+        }
+
+        let mut chain =
+            TransformChain::from_json(r#"[{"type": "rename", "from": "$.a", "to": "$.b"}]"#)
+                .unwrap();
+        chain.push(Uppercase);
+        let transformed = chain.apply(data(&json!({"a": "x"}))).unwrap();
+        assert_eq!(transformed.len(), 1);
+        assert_eq!(transformed[0].payload, br#"{"B":"X"}"#);
+    }
+
+    #[test]
+    fn split_map_of_channels() {
+        let chain = TransformChain::from_json_value(json!([
+            {"type": "split", "path": "$.channels", "shared": ["$.quality"]},
+            {"type": "scale", "field": "$.value", "factor": 10}
+        ]))
+        .unwrap();
+        let timestamp = HybridLogicalClock::new();
+        let input = Data {
+            custom_user_data: vec![("source".to_string(), "plc1".to_string())],
+            timestamp: Some(timestamp.clone()),
+            ..data(&json!({
+                "quality": "good",
+                "channels": {
+                    "temperature": {"value": 2},
+                    "pressure": {"value": 3},
+                    "humidity": {"value": 4}
+                }
+            }))
+        };
+
+        let mut split = chain.apply(input).unwrap();
+        assert_eq!(split.len(), 3);
+        split.sort_by(|a, b| split_token(a).cmp(&split_token(b)));
+        for (data, (token, value)) in split.iter().zip([
+            ("humidity", 40.0),
+            ("pressure", 30.0),
+            ("temperature", 20.0),
+        ]) {
+            assert_eq!(split_token(data), Some(token));
+            assert_eq!(
+                serde_json::from_slice::<Value>(&data.payload).unwrap(),
+                json!({"value": value, "quality": "good"})
+            );
+            assert_eq!(data.timestamp, Some(timestamp.clone()));
+            assert_eq!(data.content_type, "application/json");
+            assert!(
+                data.custom_user_data
+                    .contains(&("source".to_string(), "plc1".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn split_array() {
+        let chain = TransformChain::from_json(r#"[{"type": "split", "path": "$"}]"#).unwrap();
+        let split = chain.apply(data(&json!([1, 2]))).unwrap();
+        let tokens: Vec<_> = split.iter().map(split_token).collect();
+        assert_eq!(tokens, [Some("0"), Some("1")]);
+        assert_eq!(split[1].payload, b"2");
+    }
+
+    #[test]
+    fn split_nested_replaces_token() {
+        let chain = TransformChain::from_json_value(json!([
+            {"type": "split", "path": "$.lines"},
+            {"type": "split", "path": "$.readings"}
+        ]))
+        .unwrap();
+        let split = chain
+            .apply(data(
+                &json!({"lines": {"a": {"readings": [1, 2]}, "b": {"readings": [3]}}}),
+            ))
+            .unwrap();
+        assert_eq!(split.len(), 3);
+        assert_eq!(split[2].payload, b"3");
+        assert_eq!(split[2].custom_user_data.len(), 1);
+        assert_eq!(split_token(&split[2]), Some("0"));
+    }
+
+    #[test_case(json!({"channels": {}}), "skip", "error", Ok(0); "empty skipped")]
+    #[test_case(json!({}), "error", "skip", Err("'$.channels' has no elements to split"); "missing error")]
+    #[test_case(json!({"channels": []}), "error", "skip", Err("'$.channels' has no elements to split"); "empty error")]
+    #[test_case(json!({"channels": 1}), "skip", "skip", Ok(0); "non collection skipped")]
+    #[test_case(json!({"channels": "x"}), "skip", "error", Err("'$.channels' is neither an array nor an object"); "non collection error")]
+    #[allow(clippy::needless_pass_by_value)]
+    fn split_fallback(
+        payload: Value,
+        on_empty: &str,
+        on_non_collection: &str,
+        expected: Result<usize, &str>,
+    ) {
+        let chain = TransformChain::from_json_value(json!([{
+            "type": "split",
+            "path": "$.channels",
+            "onEmpty": on_empty,
+            "onNonCollection": on_non_collection
+        }]))
+        .unwrap();
+        match (chain.apply(data(&payload)), expected) {
+            (Ok(split), Ok(expected)) => assert_eq!(split.len(), expected),
+            (Err(error), Err(expected)) => assert!(
+                error.to_string().contains(expected),
+                "unexpected error: {error}"
+            ),
+            (result, expected) => panic!("expected {expected:?}, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn split_shared_into_non_object() {
+        let chain = TransformChain::from_json(
+            r#"[{"type": "split", "path": "$.channels", "shared": ["$.quality"]}]"#,
+        )
+        .unwrap();
+        let error = chain
+            .apply(data(&json!({"quality": "good", "channels": [1]})))
+            .unwrap_err();
+        assert!(error.to_string().contains("non-object parent"));
+    }
+
+    #[test]
+    fn invalid_payload() {
+        let chain =
+            TransformChain::from_json(r#"[{"type": "rename", "from": "$.a", "to": "$.b"}]"#)
+                .unwrap();
+        let input = Data {
+            payload: b"not json".to_vec(),
+            ..data(&json!(null))
+        };
+        assert!(chain.apply(input).is_err());
+    }
+
+    #[test_case(r#"{"type": "rename"}"#, "invalid transforms: must be a JSON array"; "not an array")]
+    #[test_case(r#"[{"type": "uppercase"}]"#, "invalid transform at index 0: unknown variant `uppercase`"; "unknown type")]
+    #[test_case(r#"[{"type": "map", "field": "$.a", "values": {}}, {"type": "rename", "from": "$.a"}]"#, "invalid transform at index 1: missing field `to`"; "missing property")]
+    #[test_case(r#"[{"type": "rename", "from": "$.a", "to": "$.b", "extra": 1}]"#, "invalid transform at index 0: unknown field `extra`"; "unknown property")]
+    #[test_case(r#"[{"type": "scale", "field": "temp"}]"#, "invalid transform at index 0: invalid path 'temp': must start with '$'"; "path without root")]
+    #[test_case(r#"[{"type": "rename", "from": "$", "to": "$.b"}]"#, "invalid transform at index 0: invalid path '$': must not be the root"; "root field")]
+    #[test_case(r#"[{"type": "filter", "field": "$.a[x]", "operator": "exists"}]"#, "invalid transform at index 0: invalid path '$.a[x]': index must be a non-negative integer"; "invalid index")]
+    #[test_case(r#"[{"type": "filter", "field": "$.a", "operator": "like"}]"#, "invalid transform at index 0: unknown variant `like`"; "unknown operator")]
+    #[test_case(r#"[{"type": "split", "path": "$.a", "shared": ["$"]}]"#, "invalid transform at index 0: invalid path '$': must not be the root"; "split shared root")]
+    #[test_case(r#"[{"type": "split", "path": "$.a", "onEmpty": "ignore"}]"#, "invalid transform at index 0: unknown variant `ignore`"; "unknown split fallback")]
+    fn invalid_config(transforms: &str, expected_error: &str) {
+        let error = TransformChain::from_json(transforms).unwrap_err();
+        assert!(
+            error.to_string().starts_with(expected_error),
+            "unexpected error: {error}"
+        );
+    }
+}
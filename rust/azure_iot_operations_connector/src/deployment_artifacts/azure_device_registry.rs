@@ -162,6 +162,8 @@ impl DeviceEndpointCreateObservation {
 pub struct AssetCreateObservation {
     /// A channel for receiving notifications about asset creation events.
     asset_creation_rx: UnboundedReceiver<(AssetRef, CancellationToken)>,
+    /// Cancelled when the device endpoint is deleted.
+    device_deletion_token: CancellationToken,
 }
 
 impl AssetCreateObservation {
@@ -170,8 +172,20 @@ impl AssetCreateObservation {
     /// Returns a new [`AssetCreateObservation`] instance.
     pub(crate) fn new(
         asset_creation_rx: UnboundedReceiver<(AssetRef, CancellationToken)>,
+        device_deletion_token: CancellationToken,
     ) -> AssetCreateObservation {
-        Self { asset_creation_rx }
+        Self {
+            asset_creation_rx,
+            device_deletion_token,
+        }
+    }
+
+    /// Returns a [`CancellationToken`] that is cancelled when the device endpoint is deleted.
+    ///
+    /// Unlike [`AssetCreateObservation::recv_notification`], this can be used to wait for the
+    /// deletion of the device endpoint without receiving asset creation notifications.
+    pub(crate) fn device_deletion_token(&self) -> CancellationToken {
+        self.device_deletion_token.clone()
     }
 
     /// Receives a notification for a newly created asset.
@@ -251,7 +265,7 @@ pub fn get_device_endpoint_names(mount_path: &Path) -> Result<HashSet<DeviceEndp
 ///
 /// - [`struct@Error`] of kind [`ErrorKind::IoError`] if there are issues accessing the file mount.
 /// - [`struct@Error`] of kind [`ErrorKind::ParseError`] if the device endpoint's file content cannot be parsed.
-fn get_asset_names(
+pub(crate) fn get_asset_names(
     mount_path: &Path,
     device_endpoint: &DeviceEndpointRef,
 ) -> Result<HashSet<AssetRef>, Error> {
@@ -284,7 +298,8 @@ fn get_asset_names(
 /// sending notifications about device endpoint creation.
 ///
 /// Each device endpoint is associated with a tuple containing an unbounded sender for asset creation
-/// notifications and a hash map of asset references to their associated deletion token drop guards.
+/// notifications, a hash map of asset references to their associated deletion token drop guards and
+/// the device endpoint's deletion token drop guard.
 struct FileMountMap {
     // TODO: This is a complex type, need to simplify it later
     file_mount_path: PathBuf,
@@ -294,6 +309,7 @@ struct FileMountMap {
         (
             UnboundedSender<(AssetRef, CancellationToken)>,
             HashMap<AssetRef, DropGuard>,
+            DropGuard,
         ),
     >,
     create_device_tx: UnboundedSender<(DeviceEndpointRef, AssetCreateObservation)>,
@@ -331,18 +347,26 @@ impl FileMountMap {
             if !self.file_mount_hashmap.contains_key(device) {
                 // Create a new channel for asset creation notifications for this device
                 let (asset_creation_tx, asset_creation_rx) = mpsc::unbounded_channel();
+                // Create a cancellation token for device endpoint deletion
+                let device_deletion_token = CancellationToken::new();
 
                 // Create a new entry in the file mount map for the device
                 log::info!("New device: {device:?}");
-                self.file_mount_hashmap
-                    .insert(device.clone(), (asset_creation_tx, HashMap::new()));
+                self.file_mount_hashmap.insert(
+                    device.clone(),
+                    (
+                        asset_creation_tx,
+                        HashMap::new(),
+                        device_deletion_token.clone().drop_guard(),
+                    ),
+                );
 
                 // Notify on device creation
                 if self
                     .create_device_tx
                     .send((
                         device.clone(),
-                        AssetCreateObservation::new(asset_creation_rx),
+                        AssetCreateObservation::new(asset_creation_rx, device_deletion_token),
                     ))
                     .is_err()
                 {
@@ -377,7 +401,7 @@ impl FileMountMap {
     /// endpoint does not exist in the file mount map, it does nothing.
     pub fn update_assets(&mut self, device: &DeviceEndpointRef, mut assets: HashSet<AssetRef>) {
         // Get the asset creation channel and the tracked assets for this device
-        let Some((create_asset_tx, tracked_assets)) = self.file_mount_hashmap.get_mut(device)
+        let Some((create_asset_tx, tracked_assets, _)) = self.file_mount_hashmap.get_mut(device)
         else {
             // If the device is non-existent we can't update the assets. Most likely a create
             // notification has not been parsed yet but this function will be called again once
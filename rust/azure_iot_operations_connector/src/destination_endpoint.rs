@@ -3,7 +3,14 @@
 
 //! Traits, types, and implementations for Azure IoT Operations Connector Destination Endpoints.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 use azure_iot_operations_mqtt::{aio::cloud_event as aio_cloud_event, control_packet::QoS};
 use azure_iot_operations_protocol::{
@@ -20,8 +27,11 @@ use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 use crate::{
-    AdrConfigError, Data, DataOperationName, DataOperationRef, base_connector::ConnectorContext,
+    AdrConfigError, Data, DataOperationName, DataOperationRef,
+    base_connector::{ConnectorContext, reconciliation::Artifact},
+    data_processor::transform::{SPLIT_TOKEN_KEY, TRANSFORMS_KEY, TransformChain},
     deployment_artifacts::azure_device_registry::AssetRef,
+    state_store_layout::{DatasetValue, dataset_key},
 };
 
 /// Represents an error that occurred when forwarding data.
@@ -56,6 +66,170 @@ pub enum ErrorKind {
     /// Data provided to be forwarded is invalid or there is no valid destination
     #[error("Error with Destination or contents of Data: {0}")]
     ValidationError(String),
+    /// The Data Operation has been deleted and its deletion grace period has elapsed
+    #[error("Data Operation has been deleted")]
+    Deleted,
+    /// The connector is passive, so data is not forwarded until it is activated. See
+    /// [`ActivationGate`](crate::base_connector::activation::ActivationGate)
+    #[error("Connector is passive")]
+    Passive,
+}
+
+/// Kind of destination that [`Data`] is delivered to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DestinationKind {
+    /// Data is stored in the Broker State Store
+    BrokerStateStore,
+    /// Data is published as MQTT Telemetry
+    Mqtt,
+}
+
+/// Receipt for [`Data`] that has been delivered to its destination, which can be used to track
+/// the latency of forwarding data
+#[derive(Clone, Debug)]
+pub struct DeliveryReceipt {
+    /// When the [`Data`] was accepted for forwarding
+    pub accepted_at: Instant,
+    /// When the destination confirmed delivery of the [`Data`]. For `Mqtt` destinations, this is
+    /// when the publish completed (i.e. when the PUBACK was received for QoS 1), and for
+    /// `BrokerStateStore` destinations, when the State Store acknowledged the set.
+    pub confirmed_at: Instant,
+    /// Kind of destination the [`Data`] was delivered to
+    pub destination: DestinationKind,
+    /// Time from the [`timestamp`](Data::timestamp) of the [`Data`] until delivery was confirmed.
+    /// `None` if the [`Data`] has no timestamp, or if its timestamp is later than the time of
+    /// confirmation (e.g. due to clock skew with the source of the timestamp).
+    pub end_to_end_latency: Option<Duration>,
+}
+
+impl DeliveryReceipt {
+    /// Creates a [`DeliveryReceipt`] for data with `timestamp` that was accepted at `accepted_at`
+    /// and has just been confirmed as delivered
+    pub(crate) fn confirmed_now(
+        accepted_at: Instant,
+        destination: DestinationKind,
+        timestamp: Option<&HybridLogicalClock>,
+    ) -> Self {
+        Self {
+            accepted_at,
+            confirmed_at: Instant::now(),
+            destination,
+            end_to_end_latency: timestamp
+                .and_then(|hlc| SystemTime::now().duration_since(hlc.timestamp).ok()),
+        }
+    }
+}
+
+/// Key of the topic namespace in the attributes of an Asset, and in the JSON configuration of a
+/// Dataset.
+///
+/// The topic namespace is prepended to the topic of MQTT destinations. A namespace on a Dataset
+/// overrides the namespace on its Asset, which in turn overrides the connector default set with
+/// [`OptionsBuilder::default_topic_namespace`](crate::base_connector::OptionsBuilder::default_topic_namespace).
+pub const TOPIC_NAMESPACE_KEY: &str = "topicNamespace";
+
+/// Validates that a topic namespace is legal for use as a prefix of MQTT topics: it must be
+/// non-empty, consist only of printable ASCII characters other than '+', '#', '{' and '}', and
+/// must not start or end with '/' or contain "//".
+pub(crate) fn validate_topic_namespace(topic_namespace: &str) -> Result<(), String> {
+    if topic_namespace.is_empty()
+        || topic_namespace
+            .chars()
+            .any(|c| !('!'..='~').contains(&c) || matches!(c, '+' | '#' | '{' | '}'))
+        || topic_namespace.starts_with('/')
+        || topic_namespace.ends_with('/')
+        || topic_namespace.contains("//")
+    {
+        return Err(format!(
+            "Topic namespace '{topic_namespace}' is not a legal topic segment"
+        ));
+    }
+    Ok(())
+}
+
+/// Resolves the topic namespace for the data of an Asset from its attributes, falling back to
+/// the connector default
+///
+/// # Errors
+/// [`AdrConfigError`] if the topic namespace on the Asset is invalid
+pub(crate) fn asset_topic_namespace(
+    attributes: &HashMap<String, String>,
+    default_topic_namespace: Option<&str>,
+) -> Result<Option<String>, AdrConfigError> {
+    match attributes.get(TOPIC_NAMESPACE_KEY) {
+        Some(topic_namespace) => {
+            validate_topic_namespace(topic_namespace).map_err(topic_namespace_error)?;
+            Ok(Some(topic_namespace.clone()))
+        }
+        None => Ok(default_topic_namespace.map(ToString::to_string)),
+    }
+}
+
+/// Resolves the topic namespace for the data of a Dataset from its JSON configuration, falling
+/// back to the topic namespace of its Asset. Configurations that aren't JSON objects don't specify
+/// a topic namespace.
+///
+/// # Errors
+/// [`AdrConfigError`] if the topic namespace in the Dataset configuration is not a string or is invalid
+pub(crate) fn dataset_topic_namespace(
+    dataset: &adr_models::Dataset,
+    asset_topic_namespace: Option<String>,
+) -> Result<Option<String>, AdrConfigError> {
+    let configured_namespace = dataset
+        .dataset_configuration
+        .as_deref()
+        .and_then(|configuration| {
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(configuration).ok()
+        })
+        .and_then(|mut configuration| configuration.remove(TOPIC_NAMESPACE_KEY));
+    match configured_namespace {
+        Some(serde_json::Value::String(topic_namespace)) => {
+            validate_topic_namespace(&topic_namespace).map_err(topic_namespace_error)?;
+            Ok(Some(topic_namespace))
+        }
+        Some(_) => Err(topic_namespace_error(format!(
+            "'{TOPIC_NAMESPACE_KEY}' in the dataset configuration must be a string"
+        ))),
+        None => Ok(asset_topic_namespace),
+    }
+}
+
+/// Parses the transform chain declared in the JSON configuration of a Dataset, if any.
+/// Configurations that aren't JSON objects don't declare transforms.
+///
+/// # Errors
+/// [`AdrConfigError`] if the transforms in the Dataset configuration are invalid
+pub(crate) fn dataset_transforms(
+    dataset: &adr_models::Dataset,
+) -> Result<TransformChain, AdrConfigError> {
+    let configured_transforms = dataset
+        .dataset_configuration
+        .as_deref()
+        .and_then(|configuration| {
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(configuration).ok()
+        })
+        .and_then(|mut configuration| configuration.remove(TRANSFORMS_KEY));
+    match configured_transforms {
+        Some(transforms) => {
+            TransformChain::from_json_value(transforms).map_err(|e| AdrConfigError {
+                code: None,
+                details: None,
+                message: Some(format!(
+                    "'{TRANSFORMS_KEY}' in the dataset configuration is invalid: {e}"
+                )),
+            })
+        }
+        None => Ok(TransformChain::new()),
+    }
+}
+
+fn topic_namespace_error(message: String) -> AdrConfigError {
+    AdrConfigError {
+        code: None,
+        details: None,
+        message: Some(message),
+    }
 }
 
 /// Represents whether there is currently a valid Forwarder or not for a Data Operation
@@ -71,7 +245,7 @@ impl DataOperationForwarder {
         &self,
         data: Data,
         protocol_specific_identifier: Option<&str>,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<DeliveryReceipt>, Error> {
         match self {
             DataOperationForwarder::Forwarder(forwarder) => {
                 forwarder
@@ -84,6 +258,17 @@ impl DataOperationForwarder {
             .into()),
         }
     }
+
+    /// Wrapper to send a tombstone to the destination if a valid forwarder exists
+    pub(crate) async fn send_tombstone(&self) -> Result<(), Error> {
+        match self {
+            DataOperationForwarder::Forwarder(forwarder) => forwarder.send_tombstone().await,
+            DataOperationForwarder::Error(_) => Err(ErrorKind::ValidationError(
+                "No valid destination configured for data operation".to_string(),
+            )
+            .into()),
+        }
+    }
 }
 
 /// A [`Forwarder`] forwards [`Data`] to a destination defined in a data operation or asset
@@ -91,17 +276,25 @@ impl DataOperationForwarder {
 pub(crate) struct Forwarder {
     message_schema_reference: Option<adr_models::MessageSchemaReference>,
     destination: ForwarderDestination,
+    asset_ref: AssetRef,
     device_uuid: Option<String>,
     device_external_device_id: Option<String>,
     data_source: Option<String>,
     data_operation_name: DataOperationName,
     data_operation_type_ref: Option<String>,
     connector_context: Arc<ConnectorContext>,
+    /// Whether the artifact created at the destination has been recorded for reconciliation
+    artifact_recorded: AtomicBool,
+    /// Transforms applied to data before it is forwarded
+    transforms: TransformChain,
 }
 impl Forwarder {
     /// Creates a new [`Forwarder`] from a dataset definition's Destinations
     /// and default destinations, if present on the asset
     ///
+    /// MQTT destinations publish under the topic namespace of the dataset, if present in its
+    /// configuration, otherwise under `asset_topic_namespace`
+    ///
     /// # Errors
     /// [`AdrConfigError`] if there are any issues processing
     /// the destination from the definitions. This can be used to report the error
@@ -115,18 +308,24 @@ impl Forwarder {
         device_external_device_id: Option<String>,
         asset_uuid: Option<&String>,
         asset_external_asset_id: Option<&String>,
+        asset_topic_namespace: Option<String>,
         connector_context: Arc<ConnectorContext>,
     ) -> Result<Self, AdrConfigError> {
+        let topic_namespace = dataset_topic_namespace(dataset, asset_topic_namespace)?;
+        let transforms = dataset_transforms(dataset)?;
         // Use internal new fn with dataset destinations
-        Self::new_data_operation_forwarder(
+        let mut forwarder = Self::new_data_operation_forwarder(
             Destination::new_dataset_destinations(
                 &dataset.destinations,
                 asset_ref,
                 asset_uuid,
                 asset_external_asset_id,
+                topic_namespace.as_deref(),
                 &connector_context,
             )?,
             default_destinations,
+            topic_namespace.as_deref(),
+            asset_ref.clone(),
             device_uuid,
             device_external_device_id,
             dataset.data_source.clone(),
@@ -135,12 +334,16 @@ impl Forwarder {
             },
             dataset.type_ref.clone(),
             connector_context,
-        )
+        )?;
+        forwarder.transforms = transforms;
+        Ok(forwarder)
     }
 
     /// Creates a new [`Forwarder`] from an event/stream definition's Destinations
     /// and default destinations, if present on the asset
     ///
+    /// MQTT destinations publish under `topic_namespace`
+    ///
     /// # Errors
     /// [`AdrConfigError`] if there are any issues processing
     /// the destination from the definitions. This can be used to report the error
@@ -156,6 +359,7 @@ impl Forwarder {
         device_external_device_id: Option<String>,
         asset_uuid: Option<&String>,
         asset_external_asset_id: Option<&String>,
+        topic_namespace: Option<&str>,
         connector_context: Arc<ConnectorContext>,
     ) -> Result<Self, AdrConfigError> {
         // Use internal new fn with event/stream destinations
@@ -165,9 +369,12 @@ impl Forwarder {
                 &data_operation_ref.into(),
                 asset_uuid,
                 asset_external_asset_id,
+                topic_namespace,
                 &connector_context,
             )?,
             default_destinations,
+            topic_namespace,
+            data_operation_ref.into(),
             device_uuid,
             device_external_device_id,
             data_source,
@@ -181,6 +388,8 @@ impl Forwarder {
     fn new_data_operation_forwarder(
         mut data_operation_destinations: Vec<Destination>,
         default_destinations: &[Arc<Destination>],
+        topic_namespace: Option<&str>,
+        asset_ref: AssetRef,
         device_uuid: Option<String>,
         device_external_device_id: Option<String>,
         data_source: Option<String>,
@@ -202,7 +411,18 @@ impl Forwarder {
                             })?
                 } else {
                     // for now, this vec will only ever be length 1
-                    ForwarderDestination::DefaultDestination(default_destinations[0].clone())
+                    // The default destination is shared by all data operations of the asset, so
+                    // a copy is needed if this data operation overrides the topic namespace
+                    match default_destinations[0]
+                        .with_topic_namespace(topic_namespace, &connector_context)?
+                    {
+                        Some(destination) => {
+                            ForwarderDestination::DataOperationDestination(destination)
+                        }
+                        None => ForwarderDestination::DefaultDestination(
+                            default_destinations[0].clone(),
+                        ),
+                    }
                 }
             }
         };
@@ -210,20 +430,30 @@ impl Forwarder {
         Ok(Self {
             message_schema_reference: None,
             destination,
+            asset_ref,
             device_uuid,
             device_external_device_id,
             data_source,
             data_operation_name,
             data_operation_type_ref,
             connector_context,
+            artifact_recorded: AtomicBool::new(false),
+            transforms: TransformChain::new(),
         })
     }
 
     /// Forwards [`Data`] to the destination
-    /// Returns once the message has been sent successfully
+    /// Returns once the message has been sent successfully, with a [`DeliveryReceipt`] for the
+    /// [`Data`], or `None` if the [`Data`] was filtered out by the transforms
+    /// If the transforms split the [`Data`], each item is forwarded in turn, and the
+    /// [`DeliveryReceipt`] is confirmed once the last one has been delivered. If forwarding an
+    /// item fails, the items after it are not forwarded.
     /// `protocol_specific_identifier` can be provided to be used when forming Cloud Event Headers
     /// If not specified, fallback fields will be used instead
     ///
+    /// Data forwarded to a `BrokerStateStore` destination is stored as described in
+    /// [`state_store_layout`](crate::state_store_layout)
+    ///
     /// # Errors
     /// [`struct@Error`] of kind [`MissingMessageSchema`](ErrorKind::MissingMessageSchema)
     /// if the [`MessageSchema`] has not been reported yet. This is required before forwarding any data
@@ -240,11 +470,47 @@ impl Forwarder {
     /// [`struct@Error`] of kind [`ValidationError`](ErrorKind::ValidationError)
     /// if the destination is `Storage`. Storage destinations require a custom forwarder implementation
     /// separate from the SDK.
+    ///
+    /// [`struct@Error`] of kind [`ValidationError`](ErrorKind::ValidationError)
+    /// if the transforms declared in the dataset configuration fail to transform the [`Data`]
     pub(crate) async fn send_data(
         &self,
         data: Data,
         protocol_specific_identifier: Option<&str>,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<DeliveryReceipt>, Error> {
+        let accepted_at = Instant::now();
+        let transformed = self.transforms.apply(data).map_err(|e| {
+            ErrorKind::ValidationError(format!("Data could not be transformed: {e}"))
+        })?;
+        // Data filtered out by the transforms is not forwarded
+        if transformed.is_empty() {
+            log::debug!(
+                "Data for {:?} filtered out by transforms, not forwarding",
+                self.data_operation_name
+            );
+            return Ok(None);
+        }
+        let mut delivered = None;
+        for data in transformed {
+            let timestamp = data.timestamp.clone();
+            let destination = self
+                .forward_data(data, protocol_specific_identifier)
+                .await?;
+            delivered = Some((destination, timestamp));
+        }
+        let receipt = delivered.map(|(destination, timestamp)| {
+            DeliveryReceipt::confirmed_now(accepted_at, destination, timestamp.as_ref())
+        });
+        self.record_artifact().await;
+        Ok(receipt)
+    }
+
+    /// Forwards [`Data`] to the destination, returning the kind of destination it was delivered to
+    async fn forward_data(
+        &self,
+        data: Data,
+        protocol_specific_identifier: Option<&str>,
+    ) -> Result<DestinationKind, Error> {
         // Forward the data to the destination
         let destination = match &self.destination {
             ForwarderDestination::DefaultDestination(destination) => destination.as_ref(),
@@ -256,8 +522,9 @@ impl Forwarder {
                     .connector_context
                     .state_store_client
                     .set(
-                        key.clone().into(),
-                        data.payload,
+                        self.state_store_key(key.as_ref())
+                            .map_err(ErrorKind::ValidationError)?,
+                        DatasetValue::from(data).to_state_store_value(),
                         self.connector_context.state_store_timeout,
                         None,
                         state_store::SetOptions {
@@ -269,7 +536,7 @@ impl Forwarder {
                     .map_err(ErrorKind::from)?
                     .response
                 {
-                    Ok(())
+                    Ok(DestinationKind::BrokerStateStore)
                 } else {
                     // This shouldn't be possible since SetOptions are unconditional
                     unreachable!()
@@ -283,6 +550,7 @@ impl Forwarder {
                 asset_uuid,
                 asset_external_asset_id,
                 telemetry_sender,
+                ..
             } => {
                 // create MQTT message, setting schema id to response from SR (message_schema_uri)
                 let cloud_event = self
@@ -313,6 +581,17 @@ impl Forwarder {
                     })
                     .map_err(|e| ErrorKind::ValidationError(e.to_string()))?;
                 message_builder.cloud_event(cloud_event);
+                // Data split by the transforms can be published to a topic per split token
+                if let Some((_, split_token)) = data
+                    .custom_user_data
+                    .iter()
+                    .find(|(key, _)| key == SPLIT_TOKEN_KEY)
+                {
+                    message_builder.topic_tokens(HashMap::from([(
+                        SPLIT_TOKEN_KEY.to_string(),
+                        split_token.clone(),
+                    )]));
+                }
                 // passes through user headers and adds custom aio cloud event headers
                 message_builder.custom_user_data(Self::add_aio_ref_headers(
                     data.custom_user_data,
@@ -325,10 +604,11 @@ impl Forwarder {
                     .build()
                     .map_err(|e| ErrorKind::ValidationError(e.to_string()))?;
                 // send message with telemetry::Sender
-                Ok(telemetry_sender
+                telemetry_sender
                     .send(message)
                     .await
-                    .map_err(ErrorKind::from)?)
+                    .map_err(ErrorKind::from)?;
+                Ok(DestinationKind::Mqtt)
             }
             Destination::Storage { .. } => {
                 // TODO: Storage destinations are not handled by the default forwarder.
@@ -342,6 +622,137 @@ impl Forwarder {
         }
     }
 
+    /// Sends a tombstone to the destination to indicate that the data operation no longer produces data.
+    /// For `BrokerStateStore` destinations, this deletes the key. For `Mqtt` destinations, this
+    /// publishes an empty retained message so that any previously retained message is cleared.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`BrokerStateStoreError`](ErrorKind::BrokerStateStoreError)
+    /// if the destination is `BrokerStateStore` and there are any errors deleting the key with the service
+    ///
+    /// [`struct@Error`] of kind [`MqttTelemetryError`](ErrorKind::MqttTelemetryError)
+    /// if the destination is `Mqtt` and there are any errors sending the message to the broker
+    ///
+    /// [`struct@Error`] of kind [`ValidationError`](ErrorKind::ValidationError)
+    /// if the destination is `Storage`.
+    pub(crate) async fn send_tombstone(&self) -> Result<(), Error> {
+        self.clear_destination().await?;
+        if let Some(reconciler) = &self.connector_context.reconciler {
+            reconciler.forget(&self.data_operation_ref()).await;
+        }
+        Ok(())
+    }
+
+    async fn clear_destination(&self) -> Result<(), Error> {
+        let destination = match &self.destination {
+            ForwarderDestination::DefaultDestination(destination) => destination.as_ref(),
+            ForwarderDestination::DataOperationDestination(destination) => destination,
+        };
+        match destination {
+            Destination::BrokerStateStore { key } => {
+                // The number of keys deleted isn't relevant, since the key may never have been set
+                self.connector_context
+                    .state_store_client
+                    .del(
+                        self.state_store_key(key.as_ref())
+                            .map_err(ErrorKind::ValidationError)?,
+                        None,
+                        self.connector_context.state_store_timeout,
+                    )
+                    .await
+                    .map_err(ErrorKind::from)?;
+                Ok(())
+            }
+            Destination::Mqtt {
+                qos,
+                telemetry_sender,
+                ..
+            } => {
+                let mut message_builder = telemetry::sender::MessageBuilder::default();
+                if let Some(qos) = qos {
+                    message_builder.qos(*qos);
+                }
+                // An empty retained message clears the retained message on the topic
+                message_builder.retain(true);
+                message_builder
+                    .payload(BypassPayload {
+                        content_type: String::new(),
+                        payload: Vec::new(),
+                        format_indicator: FormatIndicator::default(),
+                    })
+                    .map_err(|e| ErrorKind::ValidationError(e.to_string()))?;
+                let message = message_builder
+                    .build()
+                    .map_err(|e| ErrorKind::ValidationError(e.to_string()))?;
+                Ok(telemetry_sender
+                    .send(message)
+                    .await
+                    .map_err(ErrorKind::from)?)
+            }
+            Destination::Storage { .. } => Err(ErrorKind::ValidationError(
+                "Storage destination is not handled by the default forwarder".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    /// Records the artifact created at the destination for reconciliation, if reconciliation is
+    /// enabled and it hasn't been recorded yet
+    async fn record_artifact(&self) {
+        let Some(reconciler) = &self.connector_context.reconciler else {
+            return;
+        };
+        if !matches!(self.data_operation_name, DataOperationName::Dataset { .. })
+            || self.artifact_recorded.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        let destination = match &self.destination {
+            ForwarderDestination::DefaultDestination(destination) => destination.as_ref(),
+            ForwarderDestination::DataOperationDestination(destination) => destination,
+        };
+        let artifact = match destination {
+            Destination::BrokerStateStore { key } => match self.state_store_key(key.as_ref()) {
+                Ok(key) => Artifact::StateStoreKey {
+                    key: String::from_utf8_lossy(&key).into_owned(),
+                },
+                // Data can't have been forwarded without a valid key
+                Err(_) => return,
+            },
+            Destination::Mqtt {
+                retain: Some(true),
+                topic,
+                topic_namespace,
+                ..
+            } => Artifact::RetainedMessage {
+                topic: match topic_namespace {
+                    Some(topic_namespace) => format!("{topic_namespace}/{topic}"),
+                    None => topic.clone(),
+                },
+            },
+            // Nothing is left behind at the destination
+            Destination::Mqtt { .. } | Destination::Storage { .. } => {
+                self.artifact_recorded.store(true, Ordering::Relaxed);
+                return;
+            }
+        };
+        if reconciler
+            .record(&self.data_operation_ref(), artifact)
+            .await
+        {
+            self.artifact_recorded.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn data_operation_ref(&self) -> DataOperationRef {
+        DataOperationRef {
+            data_operation_name: self.data_operation_name.clone(),
+            asset_name: self.asset_ref.name.clone(),
+            device_name: self.asset_ref.device_name.clone(),
+            inbound_endpoint_name: self.asset_ref.inbound_endpoint_name.clone(),
+        }
+    }
+
     /// Sets the message schema reference for this forwarder to use. Must be done before
     /// calling `send_data`
     pub(crate) fn update_message_schema_reference(
@@ -352,6 +763,22 @@ impl Forwarder {
         self.message_schema_reference = message_schema_reference;
     }
 
+    /// Returns the State Store key for a `BrokerStateStore` destination, following the
+    /// [`state_store_layout`](crate::state_store_layout)
+    fn state_store_key(&self, key: Option<&String>) -> Result<Vec<u8>, String> {
+        match (key, &self.data_operation_name) {
+            (Some(key), _) => Ok(key.clone().into()),
+            (None, DataOperationName::Dataset { name }) => Ok(dataset_key(
+                &self.asset_ref.device_name,
+                &self.asset_ref.name,
+                name,
+            )),
+            (None, data_operation_name) => Err(format!(
+                "BrokerStateStore destination must have a key for {data_operation_name}"
+            )),
+        }
+    }
+
     fn build_cloud_event_headers(
         &self,
         asset_ref: &AssetRef,
@@ -592,7 +1019,7 @@ impl DataOperationDestinationDefinition {
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Destination {
     BrokerStateStore {
-        key: String,
+        key: Option<String>, // if not specified, the key is determined by the state_store_layout
     },
     Mqtt {
         qos: Option<QoS>, // these are optional so that we use the defaults from the telemetry::sender if they aren't specified on the data_operation/asset definition
@@ -601,6 +1028,8 @@ pub(crate) enum Destination {
         asset_ref: AssetRef,
         asset_uuid: Option<String>,
         asset_external_asset_id: Option<String>,
+        topic: String,
+        topic_namespace: Option<String>,
         telemetry_sender: telemetry::Sender<BypassPayload>,
     },
     Storage {
@@ -614,14 +1043,17 @@ impl Destination {
     /// this function will return an empty Vec. This isn't an error, since a default destination may or
     /// may not exist in the definition.
     ///
+    /// `Mqtt` destinations publish under `topic_namespace`, if specified.
+    ///
     /// # Errors
-    /// [`AdrConfigError`] if the destination is `Mqtt` and the topic is invalid.
+    /// [`AdrConfigError`] if the destination is `Mqtt` and the topic or topic namespace is invalid.
     /// This can be used to report the error to the ADR service on the status
     pub(crate) fn new_dataset_destinations(
         dataset_destinations: &[adr_models::DatasetDestination],
         asset_ref: &AssetRef,
         asset_uuid: Option<&String>,
         asset_external_asset_id: Option<&String>,
+        topic_namespace: Option<&str>,
         connector_context: &Arc<ConnectorContext>,
     ) -> Result<Vec<Self>, AdrConfigError> {
         // Create a new forwarder
@@ -635,6 +1067,7 @@ impl Destination {
                 asset_ref,
                 asset_uuid,
                 asset_external_asset_id,
+                topic_namespace,
                 connector_context,
             )?;
             Ok(vec![destination])
@@ -646,14 +1079,17 @@ impl Destination {
     /// this function will return an empty Vec. This isn't an error, since a default destination may or
     /// may not exist in the definition.
     ///
+    /// `Mqtt` destinations publish under `topic_namespace`, if specified.
+    ///
     /// # Errors
-    /// [`AdrConfigError`] if the destination is `Mqtt` and the topic is invalid.
+    /// [`AdrConfigError`] if the destination is `Mqtt` and the topic or topic namespace is invalid.
     /// This can be used to report the error to the ADR service on the status
     pub(crate) fn new_event_stream_destinations(
         event_stream_destinations: &[adr_models::EventStreamDestination],
         asset_ref: &AssetRef,
         asset_uuid: Option<&String>,
         asset_external_asset_id: Option<&String>,
+        topic_namespace: Option<&str>,
         connector_context: &Arc<ConnectorContext>,
     ) -> Result<Vec<Self>, AdrConfigError> {
         // Create a new forwarder
@@ -667,6 +1103,7 @@ impl Destination {
                 asset_ref,
                 asset_uuid,
                 asset_external_asset_id,
+                topic_namespace,
                 connector_context,
             )?;
             Ok(vec![destination])
@@ -678,6 +1115,7 @@ impl Destination {
         asset_ref: &AssetRef,
         asset_uuid: Option<&String>,
         asset_external_asset_id: Option<&String>,
+        topic_namespace: Option<&str>,
         connector_context: &Arc<ConnectorContext>,
     ) -> Result<Self, AdrConfigError> {
         Ok(match data_operation_destination_definition.target() {
@@ -689,39 +1127,20 @@ impl Destination {
                     key: data_operation_destination_definition
                         .configuration()
                         .key
-                        .clone()
-                        .expect("Key must be present if Target is BrokerStateStore"),
+                        .clone(),
                 }
             }
             DataOperationDestinationDefinitionTarget::EventStream(
                 adr_models::EventStreamTarget::Mqtt,
             )
             | DataOperationDestinationDefinitionTarget::Dataset(adr_models::DatasetTarget::Mqtt) => {
-                let telemetry_sender_options = telemetry::sender::OptionsBuilder::default()
-                    .topic_pattern(
-                        data_operation_destination_definition
-                            .configuration()
-                            .topic
-                            .clone()
-                            .expect("Topic must be present if Target is Mqtt"),
-                    )
-                    .build()
-                    // TODO: check if this can fail, or just the next one
-                    .map_err(|e| AdrConfigError {
-                        code: None,
-                        details: None,
-                        message: Some(e.to_string()),
-                    })?; // can fail if topic isn't valid in config
-                let telemetry_sender = telemetry::Sender::new(
-                    connector_context.application_context.clone(),
-                    connector_context.managed_client.clone(),
-                    telemetry_sender_options,
-                )
-                .map_err(|e| AdrConfigError {
-                    code: None,
-                    details: None,
-                    message: Some(e.to_string()),
-                })?;
+                let topic = data_operation_destination_definition
+                    .configuration()
+                    .topic
+                    .clone()
+                    .expect("Topic must be present if Target is Mqtt");
+                let telemetry_sender =
+                    Self::new_telemetry_sender(&topic, topic_namespace, connector_context)?;
                 Destination::Mqtt {
                     qos: data_operation_destination_definition
                         .configuration()
@@ -736,6 +1155,8 @@ impl Destination {
                     asset_ref: asset_ref.clone(),
                     asset_uuid: asset_uuid.cloned(),
                     asset_external_asset_id: asset_external_asset_id.cloned(),
+                    topic,
+                    topic_namespace: topic_namespace.map(ToString::to_string),
                     telemetry_sender,
                 }
             }
@@ -753,6 +1174,83 @@ impl Destination {
             },
         })
     }
+
+    /// Creates a copy of an `Mqtt` [`Destination`] that publishes under a different topic namespace.
+    ///
+    /// Returns `None` if the destination isn't `Mqtt` or already publishes under `topic_namespace`
+    ///
+    /// # Errors
+    /// [`AdrConfigError`] if the topic namespace is invalid
+    fn with_topic_namespace(
+        &self,
+        topic_namespace: Option<&str>,
+        connector_context: &Arc<ConnectorContext>,
+    ) -> Result<Option<Self>, AdrConfigError> {
+        match self {
+            Destination::Mqtt {
+                qos,
+                retain,
+                ttl,
+                asset_ref,
+                asset_uuid,
+                asset_external_asset_id,
+                topic,
+                topic_namespace: current_topic_namespace,
+                telemetry_sender: _,
+            } if current_topic_namespace.as_deref() != topic_namespace => {
+                Ok(Some(Destination::Mqtt {
+                    qos: *qos,
+                    retain: *retain,
+                    ttl: *ttl,
+                    asset_ref: asset_ref.clone(),
+                    asset_uuid: asset_uuid.clone(),
+                    asset_external_asset_id: asset_external_asset_id.clone(),
+                    topic: topic.clone(),
+                    topic_namespace: topic_namespace.map(ToString::to_string),
+                    telemetry_sender: Self::new_telemetry_sender(
+                        topic,
+                        topic_namespace,
+                        connector_context,
+                    )?,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Creates a telemetry sender for an `Mqtt` [`Destination`]
+    ///
+    /// # Errors
+    /// [`AdrConfigError`] if the topic or topic namespace is invalid
+    fn new_telemetry_sender(
+        topic: &str,
+        topic_namespace: Option<&str>,
+        connector_context: &ConnectorContext,
+    ) -> Result<telemetry::Sender<BypassPayload>, AdrConfigError> {
+        let mut telemetry_sender_options_builder = telemetry::sender::OptionsBuilder::default();
+        if let Some(topic_namespace) = topic_namespace {
+            telemetry_sender_options_builder.topic_namespace(topic_namespace);
+        }
+        let telemetry_sender_options = telemetry_sender_options_builder
+            .topic_pattern(topic)
+            .build()
+            // TODO: check if this can fail, or just the next one
+            .map_err(|e| AdrConfigError {
+                code: None,
+                details: None,
+                message: Some(e.to_string()),
+            })?; // can fail if topic isn't valid in config
+        telemetry::Sender::new(
+            connector_context.application_context.clone(),
+            connector_context.managed_client.clone(),
+            telemetry_sender_options,
+        )
+        .map_err(|e| AdrConfigError {
+            code: None,
+            details: None,
+            message: Some(e.to_string()),
+        })
+    }
 }
 
 impl std::fmt::Debug for Destination {
@@ -769,6 +1267,8 @@ impl std::fmt::Debug for Destination {
                 asset_ref,
                 asset_uuid,
                 asset_external_asset_id,
+                topic,
+                topic_namespace,
                 telemetry_sender: _,
             } => f
                 .debug_struct("Mqtt")
@@ -778,6 +1278,8 @@ impl std::fmt::Debug for Destination {
                 .field("asset_ref", asset_ref)
                 .field("asset_uuid", asset_uuid)
                 .field("asset_external_asset_id", asset_external_asset_id)
+                .field("topic", topic)
+                .field("topic_namespace", topic_namespace)
                 // .field("telemetry_sender", telemetry_sender)
                 .finish(),
             Self::Storage { path } => f.debug_struct("Storage").field("path", path).finish(),
@@ -1007,4 +1509,120 @@ mod tests {
         );
         assert_eq!(subject, "asset_name/dataset_name");
     }
+
+    fn dataset(dataset_configuration: Option<&str>) -> adr_models::Dataset {
+        adr_models::Dataset {
+            dataset_configuration: dataset_configuration.map(ToString::to_string),
+            data_points: vec![],
+            data_source: None,
+            destinations: vec![],
+            name: "dataset_name".to_string(),
+            type_ref: None,
+        }
+    }
+
+    #[test_case("tenant-a"; "single level")]
+    #[test_case("tenants/tenant-a"; "multi level")]
+    fn valid_topic_namespace(topic_namespace: &str) {
+        assert!(validate_topic_namespace(topic_namespace).is_ok());
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("tenant a"; "space")]
+    #[test_case("tenant+"; "single level wildcard")]
+    #[test_case("tenant/#"; "multi level wildcard")]
+    #[test_case("{tenant}"; "token")]
+    #[test_case("/tenant"; "leading slash")]
+    #[test_case("tenant/"; "trailing slash")]
+    #[test_case("tenants//a"; "empty level")]
+    #[test_case("t\u{e9}nant"; "non ascii")]
+    fn invalid_topic_namespace(topic_namespace: &str) {
+        assert!(validate_topic_namespace(topic_namespace).is_err());
+    }
+
+    #[test_case(None, None, None; "no namespace")]
+    #[test_case(None, Some("default"), Some("default"); "connector default")]
+    #[test_case(Some("tenant-a"), None, Some("tenant-a"); "asset namespace")]
+    #[test_case(Some("tenant-a"), Some("default"), Some("tenant-a"); "asset overrides connector default")]
+    fn asset_topic_namespace_resolution(
+        asset_namespace: Option<&str>,
+        default_namespace: Option<&str>,
+        expected: Option<&str>,
+    ) {
+        let attributes = asset_namespace
+            .map(|ns| HashMap::from([(TOPIC_NAMESPACE_KEY.to_string(), ns.to_string())]))
+            .unwrap_or_default();
+        assert_eq!(
+            asset_topic_namespace(&attributes, default_namespace).unwrap(),
+            expected.map(ToString::to_string)
+        );
+    }
+
+    #[test]
+    fn asset_topic_namespace_invalid() {
+        let attributes = HashMap::from([(TOPIC_NAMESPACE_KEY.to_string(), "tenant/#".to_string())]);
+        assert!(asset_topic_namespace(&attributes, Some("default")).is_err());
+    }
+
+    #[test_case(None, Some("asset"); "no configuration")]
+    #[test_case(Some("not json"), Some("asset"); "non json configuration")]
+    #[test_case(Some(r#"{"samplingInterval": 100}"#), Some("asset"); "configuration without namespace")]
+    #[test_case(Some(r#"{"topicNamespace": "tenant-b"}"#), Some("tenant-b"); "dataset overrides asset")]
+    fn dataset_topic_namespace_resolution(
+        dataset_configuration: Option<&str>,
+        expected: Option<&str>,
+    ) {
+        assert_eq!(
+            dataset_topic_namespace(&dataset(dataset_configuration), Some("asset".to_string()))
+                .unwrap(),
+            expected.map(ToString::to_string)
+        );
+    }
+
+    #[test_case(r#"{"topicNamespace": 5}"#; "not a string")]
+    #[test_case(r#"{"topicNamespace": "tenant+"}"#; "invalid namespace")]
+    fn dataset_topic_namespace_invalid(dataset_configuration: &str) {
+        assert!(dataset_topic_namespace(&dataset(Some(dataset_configuration)), None).is_err());
+    }
+
+    #[test_case(None; "no configuration")]
+    #[test_case(Some("not json"); "non json configuration")]
+    #[test_case(Some(r#"{"samplingInterval": 100}"#); "configuration without transforms")]
+    fn dataset_transforms_absent(dataset_configuration: Option<&str>) {
+        assert!(
+            dataset_transforms(&dataset(dataset_configuration))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn dataset_transforms_declared() {
+        let transforms = dataset_transforms(&dataset(Some(
+            r#"{"transforms": [{"type": "rename", "from": "$.t", "to": "$.temperature"}]}"#,
+        )))
+        .unwrap();
+        let data = transforms
+            .apply(Data {
+                payload: br#"{"t":20}"#.to_vec(),
+                content_type: "application/json".to_string(),
+                custom_user_data: vec![],
+                timestamp: None,
+            })
+            .unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].payload, br#"{"temperature":20}"#);
+    }
+
+    #[test_case(r#"{"transforms": {"type": "rename"}}"#; "not an array")]
+    #[test_case(r#"{"transforms": [{"type": "uppercase"}]}"#; "unknown transform type")]
+    fn dataset_transforms_invalid(dataset_configuration: &str) {
+        let error = dataset_transforms(&dataset(Some(dataset_configuration))).unwrap_err();
+        assert!(
+            error
+                .message
+                .unwrap()
+                .starts_with("'transforms' in the dataset configuration is invalid")
+        );
+    }
 }
@@ -14,11 +14,14 @@ use azure_iot_operations_services::{
 };
 
 pub mod base_connector;
+pub mod data_pipeline;
 pub mod data_processor;
 pub mod deployment_artifacts;
 pub mod destination_endpoint;
 pub mod management_action_executor;
+pub mod message_schema;
 pub mod readiness_probe;
+pub mod state_store_layout;
 
 #[macro_use]
 extern crate derive_getters;
@@ -0,0 +1,118 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Utilities for comparing [`MessageSchema`]s.
+//!
+//! Two schemas that differ only in the ordering of the keys or the whitespace in their JSON
+//! content are semantically the same, and reporting both would cause unnecessary puts to the
+//! Schema Registry Service. The functions in this module canonicalize the content of a schema
+//! before comparing so that only meaningful changes are detected.
+
+use serde_json::Value;
+
+use crate::MessageSchema;
+
+/// Returns the canonical form of JSON schema content.
+///
+/// The content is parsed as JSON and re-serialized with object keys sorted and insignificant
+/// whitespace removed. Array ordering is preserved, since it is meaningful in JSON.
+///
+/// # Errors
+/// [`serde_json::Error`] if `schema_content` is not valid JSON
+pub fn canonicalize_schema_content(schema_content: &str) -> Result<String, serde_json::Error> {
+    // `serde_json::Map` is ordered by key, so serializing a parsed `Value` sorts the keys
+    let value: Value = serde_json::from_str(schema_content)?;
+    serde_json::to_string(&value)
+}
+
+/// Returns a copy of `message_schema` with its content in canonical form.
+///
+/// If the content is not valid JSON, it is left unchanged.
+#[must_use]
+pub fn canonicalize(message_schema: &MessageSchema) -> MessageSchema {
+    match canonicalize_schema_content(&message_schema.schema_content) {
+        Ok(schema_content) => MessageSchema {
+            schema_content,
+            ..message_schema.clone()
+        },
+        Err(_) => message_schema.clone(),
+    }
+}
+
+/// Returns whether two [`MessageSchema`]s are semantically equal.
+///
+/// All fields are compared directly except for the content, which is compared in canonical form
+/// (see [`canonicalize_schema_content`]). Content that is not valid JSON is compared as is.
+#[must_use]
+pub fn is_equivalent(schema1: &MessageSchema, schema2: &MessageSchema) -> bool {
+    canonicalize(schema1) == canonicalize(schema2)
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_services::schema_registry::{Format, SchemaType};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::MessageSchemaBuilder;
+
+    fn message_schema(schema_content: &str) -> MessageSchema {
+        MessageSchemaBuilder::default()
+            .schema_content(schema_content.to_string())
+            .format(Format::JsonSchemaDraft07)
+            .schema_type(SchemaType::MessageSchema)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn canonicalize_sorts_keys_and_removes_whitespace() {
+        assert_eq!(
+            canonicalize_schema_content(
+                r#"{
+                    "type": "object",
+                    "properties": { "temp": { "type": "number" }, "active": { "type": "boolean" } }
+                }"#
+            )
+            .unwrap(),
+            r#"{"properties":{"active":{"type":"boolean"},"temp":{"type":"number"}},"type":"object"}"#
+        );
+    }
+
+    #[test]
+    fn canonicalize_invalid_json() {
+        assert!(canonicalize_schema_content("not json").is_err());
+        let schema = message_schema("not json");
+        assert_eq!(canonicalize(&schema), schema);
+    }
+
+    #[test_case(r#"{"type":"object","required":["a"]}"#, r#"{ "required": [ "a" ], "type": "object" }"#; "reordered keys and whitespace")]
+    #[test_case(r#"{"type":"object"}"#, r#"{"type":"object"}"#; "identical")]
+    #[test_case("not json", "not json"; "identical invalid json")]
+    fn equivalent_schemas(content1: &str, content2: &str) {
+        assert!(is_equivalent(
+            &message_schema(content1),
+            &message_schema(content2)
+        ));
+    }
+
+    #[test_case(r#"{"type":"object"}"#, r#"{"type":"array"}"#; "different value")]
+    #[test_case(r#"{"enum":["a","b"]}"#, r#"{"enum":["b","a"]}"#; "different array order")]
+    #[test_case("not json", "not  json"; "different invalid json")]
+    fn non_equivalent_schemas(content1: &str, content2: &str) {
+        assert!(!is_equivalent(
+            &message_schema(content1),
+            &message_schema(content2)
+        ));
+    }
+
+    #[test]
+    fn non_equivalent_other_fields() {
+        let schema = message_schema(r#"{"type":"object"}"#);
+        let other_version = MessageSchema {
+            version: "2".to_string(),
+            ..schema.clone()
+        };
+        assert!(!is_equivalent(&schema, &other_version));
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Layout of Dataset data forwarded to a `BrokerStateStore` destination.
+//!
+//! Applications reading data forwarded by a connector should use these definitions rather than
+//! hard-coding key names or value formats, so that they remain compatible across SDK versions.
+//!
+//! # Key
+//! The key is the `key` configured on the Dataset's (or Asset's default) destination if present.
+//! Otherwise, the key is `connector/{device_name}/{asset_name}/{dataset_name}`, as returned by
+//! [`dataset_key`].
+//!
+//! # Value
+//! The value is a [`DatasetValue`] serialized as a JSON object:
+//! ```json
+//! {
+//!     "payload": "<base64 encoded payload>",
+//!     "contentType": "application/json",
+//!     "timestamp": "<hybrid logical clock of the data, if present>",
+//!     "customUserData": [["key", "value"]]
+//! }
+//! ```
+
+use std::{str::FromStr, time::Duration};
+
+use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+use azure_iot_operations_services::state_store;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::{Data, DataOperationName, DataOperationRef};
+
+/// Prefix of the keys of Datasets that don't have a key configured on their destination
+pub const DATASET_KEY_PREFIX: &str = "connector";
+
+/// Represents an error that occurred when reading a forwarded Dataset value.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct Error(#[from] ErrorKind);
+
+impl Error {
+    /// Returns the [`ErrorKind`] of the error.
+    #[must_use]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0
+    }
+}
+
+/// Represents the kinds of errors that occur when reading a forwarded Dataset value.
+#[derive(Error, Debug)]
+#[allow(clippy::large_enum_variant)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An error occurred while getting the value from the State Store
+    #[error(transparent)]
+    StateStoreError(#[from] state_store::Error),
+    /// The value in the State Store is not a valid [`DatasetValue`]
+    #[error("Invalid Dataset value: {0}")]
+    InvalidValue(#[from] serde_json::Error),
+    /// The provided [`DataOperationRef`] does not refer to a Dataset
+    #[error("Data Operation is not a Dataset: {0}")]
+    NotADataset(DataOperationName),
+}
+
+/// Returns the State Store key of a Dataset that doesn't have a key configured on its destination.
+#[must_use]
+pub fn dataset_key(device_name: &str, asset_name: &str, dataset_name: &str) -> Vec<u8> {
+    format!("{DATASET_KEY_PREFIX}/{device_name}/{asset_name}/{dataset_name}").into_bytes()
+}
+
+/// Value stored in the State Store for forwarded Dataset data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetValue {
+    /// The payload in raw bytes. Base64 encoded when serialized.
+    #[serde(with = "base64_payload")]
+    pub payload: Vec<u8>,
+    /// The content type of the payload
+    pub content_type: String,
+    /// Timestamp of the data, if present
+    #[serde(default, with = "hlc_timestamp")]
+    pub timestamp: Option<HybridLogicalClock>,
+    /// Custom user data related to the payload
+    #[serde(default)]
+    pub custom_user_data: Vec<(String, String)>,
+}
+
+impl DatasetValue {
+    /// Serializes the [`DatasetValue`] to the bytes stored in the State Store.
+    ///
+    /// # Panics
+    /// Never, all fields are serializable as JSON
+    #[must_use]
+    pub fn to_state_store_value(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("DatasetValue is always serializable")
+    }
+
+    /// Deserializes a [`DatasetValue`] from the bytes stored in the State Store.
+    ///
+    /// # Errors
+    /// [`serde_json::Error`] if the bytes are not a valid [`DatasetValue`]
+    pub fn from_state_store_value(value: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(value)
+    }
+}
+
+impl From<Data> for DatasetValue {
+    fn from(data: Data) -> Self {
+        Self {
+            payload: data.payload,
+            content_type: data.content_type,
+            timestamp: data.timestamp,
+            custom_user_data: data.custom_user_data,
+        }
+    }
+}
+
+impl From<DatasetValue> for Data {
+    fn from(value: DatasetValue) -> Self {
+        Self {
+            payload: value.payload,
+            content_type: value.content_type,
+            custom_user_data: value.custom_user_data,
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+/// Gets the latest value forwarded for a Dataset that doesn't have a key configured on its
+/// destination. Returns `None` if no value has been forwarded.
+///
+/// Use [`read_value`] for Datasets with a key configured on their destination.
+///
+/// # Errors
+/// [`struct@Error`] of kind [`NotADataset`](ErrorKind::NotADataset) if `dataset_ref` doesn't refer to a Dataset
+///
+/// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if there are any errors getting the value
+///
+/// [`struct@Error`] of kind [`InvalidValue`](ErrorKind::InvalidValue) if the value is not a valid [`DatasetValue`]
+pub async fn read_dataset_value(
+    state_store_client: &state_store::Client,
+    dataset_ref: &DataOperationRef,
+    timeout: Duration,
+) -> Result<Option<DatasetValue>, Error> {
+    let DataOperationName::Dataset { name } = &dataset_ref.data_operation_name else {
+        return Err(ErrorKind::NotADataset(dataset_ref.data_operation_name.clone()).into());
+    };
+    read_value(
+        state_store_client,
+        dataset_key(&dataset_ref.device_name, &dataset_ref.asset_name, name),
+        timeout,
+    )
+    .await
+}
+
+/// Gets the latest value forwarded for a Dataset to `key`. Returns `None` if no value has been forwarded.
+///
+/// # Errors
+/// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if there are any errors getting the value
+///
+/// [`struct@Error`] of kind [`InvalidValue`](ErrorKind::InvalidValue) if the value is not a valid [`DatasetValue`]
+pub async fn read_value(
+    state_store_client: &state_store::Client,
+    key: Vec<u8>,
+    timeout: Duration,
+) -> Result<Option<DatasetValue>, Error> {
+    state_store_client
+        .get(key, timeout)
+        .await
+        .map_err(ErrorKind::from)?
+        .response
+        .map(|value| DatasetValue::from_state_store_value(&value))
+        .transpose()
+        .map_err(|e| ErrorKind::from(e).into())
+}
+
+mod base64_payload {
+    use super::{Deserialize, Deserializer, Engine, STANDARD, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        payload: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(payload))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+mod hlc_timestamp {
+    use super::{Deserialize, Deserializer, FromStr, HybridLogicalClock, Serialize, Serializer};
+
+    #[allow(clippy::ref_option)] // signature required by serde
+    pub(super) fn serialize<S: Serializer>(
+        timestamp: &Option<HybridLogicalClock>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        timestamp
+            .as_ref()
+            .map(ToString::to_string)
+            .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<HybridLogicalClock>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| HybridLogicalClock::from_str(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn dataset_value() -> DatasetValue {
+        DatasetValue {
+            payload: br#"{"temperature":21}"#.to_vec(),
+            content_type: "application/json".to_string(),
+            timestamp: Some(HybridLogicalClock::from_str("000001700000000:00001:node").unwrap()),
+            custom_user_data: vec![("key".to_string(), "value".to_string())],
+        }
+    }
+
+    #[test]
+    fn dataset_key_layout() {
+        assert_eq!(
+            dataset_key("device", "asset", "dataset"),
+            b"connector/device/asset/dataset"
+        );
+    }
+
+    #[test]
+    fn dataset_value_layout() {
+        let value: serde_json::Value =
+            serde_json::from_slice(&dataset_value().to_state_store_value()).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "payload": "eyJ0ZW1wZXJhdHVyZSI6MjF9",
+                "contentType": "application/json",
+                "timestamp": "000001700000000:00001:node",
+                "customUserData": [["key", "value"]],
+            })
+        );
+    }
+
+    #[test]
+    fn dataset_value_round_trip() {
+        let value = dataset_value();
+        assert_eq!(
+            DatasetValue::from_state_store_value(&value.to_state_store_value()).unwrap(),
+            value
+        );
+
+        let value = DatasetValue {
+            timestamp: None,
+            custom_user_data: Vec::new(),
+            ..dataset_value()
+        };
+        assert_eq!(
+            DatasetValue::from_state_store_value(&value.to_state_store_value()).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn dataset_value_optional_fields() {
+        let value = DatasetValue::from_state_store_value(
+            br#"{"payload": "", "contentType": "application/octet-stream"}"#,
+        )
+        .unwrap();
+        assert!(value.payload.is_empty());
+        assert_eq!(value.timestamp, None);
+        assert!(value.custom_user_data.is_empty());
+    }
+
+    #[test]
+    fn dataset_value_invalid() {
+        for invalid in [
+            &b"raw payload"[..],
+            br#"{"payload": "not base64!", "contentType": ""}"#,
+            br#"{"payload": "", "contentType": "", "timestamp": "not an hlc"}"#,
+        ] {
+            assert!(DatasetValue::from_state_store_value(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn data_conversion() {
+        let value = dataset_value();
+        let data: Data = value.clone().into();
+        assert_eq!(data.payload, value.payload);
+        assert_eq!(DatasetValue::from(data), value);
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Round trip tests of the `state_store_layout` through the State Store.
+
+use std::{env, str::FromStr, time::Duration};
+
+use azure_iot_operations_connector::{
+    DataOperationName, DataOperationRef,
+    state_store_layout::{self, DatasetValue},
+};
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{Session, SessionExitHandle, SessionOptionsBuilder};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::hybrid_logical_clock::HybridLogicalClock,
+};
+use azure_iot_operations_services::state_store::{self, SetOptions};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn setup_test(client_id: &str) -> Result<(Session, state_store::Client, SessionExitHandle), ()> {
+    let _ = env_logger::Builder::new()
+        .filter_level(log::LevelFilter::max())
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .filter_module("azure_iot_operations", log::LevelFilter::Warn)
+        .try_init();
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        log::warn!("This test is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return Err(());
+    }
+
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("localhost")
+        .tcp_port(1883u16)
+        .keep_alive(Duration::from_secs(5))
+        .use_tls(false)
+        .build()
+        .unwrap();
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()
+        .unwrap();
+    let session = Session::new(session_options).unwrap();
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    let state_store_client = state_store::Client::new(
+        application_context,
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let exit_handle = session.create_exit_handle();
+    Ok((session, state_store_client, exit_handle))
+}
+
+fn dataset_ref(dataset_name: &str) -> DataOperationRef {
+    DataOperationRef {
+        data_operation_name: DataOperationName::Dataset {
+            name: dataset_name.to_string(),
+        },
+        asset_name: "state-store-layout-asset".to_string(),
+        device_name: "state-store-layout-device".to_string(),
+        inbound_endpoint_name: "endpoint".to_string(),
+    }
+}
+
+/// Tests that a value stored with the layout used by the `BrokerStateStore` destination is read back
+#[tokio::test]
+async fn state_store_layout_round_trip_network_tests() {
+    let Ok((session, state_store_client, exit_handle)) =
+        setup_test("state_store_layout_round_trip_network_tests-rust")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+
+    let test_task = tokio::task::spawn(async move {
+        let dataset_ref = dataset_ref("round-trip");
+        let DataOperationName::Dataset { name } = &dataset_ref.data_operation_name else {
+            unreachable!()
+        };
+        let key = state_store_layout::dataset_key(
+            &dataset_ref.device_name,
+            &dataset_ref.asset_name,
+            name,
+        );
+
+        // Delete the key in case it was left over from a previous run
+        state_store_client
+            .del(key.clone(), None, TIMEOUT)
+            .await
+            .unwrap();
+        assert!(
+            state_store_layout::read_dataset_value(&state_store_client, &dataset_ref, TIMEOUT)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Store the value as the destination does
+        let value = DatasetValue {
+            payload: br#"{"temperature":21}"#.to_vec(),
+            content_type: "application/json".to_string(),
+            timestamp: Some(HybridLogicalClock::from_str("000001700000000:00001:node").unwrap()),
+            custom_user_data: vec![("key".to_string(), "value".to_string())],
+        };
+        assert!(
+            state_store_client
+                .set(
+                    key.clone(),
+                    value.to_state_store_value(),
+                    TIMEOUT,
+                    None,
+                    SetOptions::default(),
+                )
+                .await
+                .unwrap()
+                .response
+        );
+
+        // Read it back by reference and by key
+        assert_eq!(
+            state_store_layout::read_dataset_value(&state_store_client, &dataset_ref, TIMEOUT)
+                .await
+                .unwrap(),
+            Some(value.clone())
+        );
+        assert_eq!(
+            state_store_layout::read_value(&state_store_client, key.clone(), TIMEOUT)
+                .await
+                .unwrap(),
+            Some(value)
+        );
+
+        // A value that doesn't follow the layout can't be read
+        state_store_client
+            .set(
+                key.clone(),
+                b"raw payload".to_vec(),
+                TIMEOUT,
+                None,
+                SetOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            state_store_layout::read_dataset_value(&state_store_client, &dataset_ref, TIMEOUT)
+                .await
+                .unwrap_err()
+                .kind(),
+            state_store_layout::ErrorKind::InvalidValue(_)
+        ));
+
+        // Only Datasets are stored in the State Store
+        let event_ref = DataOperationRef {
+            data_operation_name: DataOperationName::Event {
+                name: "event".to_string(),
+                event_group_name: "group".to_string(),
+            },
+            ..dataset_ref
+        };
+        assert!(matches!(
+            state_store_layout::read_dataset_value(&state_store_client, &event_ref, TIMEOUT)
+                .await
+                .unwrap_err()
+                .kind(),
+            state_store_layout::ErrorKind::NotADataset(_)
+        ));
+
+        state_store_client.del(key, None, TIMEOUT).await.unwrap();
+        state_store_client.shutdown().await.unwrap();
+        exit_handle.try_exit().unwrap();
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| e.to_string()) },
+            async move { session.run().await.map_err(|e| e.to_string()) }
+        )
+        .is_ok()
+    );
+}
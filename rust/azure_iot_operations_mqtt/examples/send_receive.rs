@@ -62,6 +62,9 @@ async fn run_program(client: SessionManagedClient, exit_handle: SessionExitHandl
         Err(e) => {
             println!("Program failed: {e}");
             exit(&exit_handle);
+            // Any further cleanup can safely happen once the Session has fully stopped
+            exit_handle.wait_until_stopped().await;
+            println!("Session stopped");
         }
     }
 }
@@ -6,6 +6,8 @@
 
 pub mod cloud_event;
 pub mod connection_settings;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
 
 /// Options for configuring features on a [`Session`](crate::session::Session) that are specific to the AIO broker
 #[derive(Builder)]
@@ -4,6 +4,7 @@
 //! Cloud Event tools
 
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     str::FromStr,
     time::SystemTime,
@@ -163,6 +164,73 @@ impl FromStr for CloudEventFields {
     }
 }
 
+// ~~~~~~~~~~ Fns/constants for validating Cloud Event extension attributes ~~~~~~~~~~
+
+/// Maximum length of an extension attribute name. The spec recommends that attribute names are no
+/// longer than 20 characters.
+pub const MAX_EXTENSION_NAME_LENGTH: usize = 20;
+
+/// Name of the `partitionkey` extension attribute, defined by the Partitioning extension.
+/// A partition key for the event, typically used to group related events.
+pub const PARTITION_KEY_EXTENSION: &str = "partitionkey";
+
+/// Name of the `dataref` extension attribute, defined by the Dataref (Claim Check) extension.
+/// A reference to a location where the event payload is stored.
+pub const DATA_REF_EXTENSION: &str = "dataref";
+
+/// Name of the `data` attribute, which is reserved by the spec but not sent as an MQTT header.
+const DATA_ATTRIBUTE: &str = "data";
+
+/// Validates the name of an extension attribute.
+///
+/// # Errors
+/// Returns a string containing the error message if
+///     - the name is empty, longer than [`MAX_EXTENSION_NAME_LENGTH`], or contains characters
+///       other than lowercase ASCII letters and digits
+///     - the name is the name of a core attribute
+pub fn validate_extension_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Extension attribute name cannot be empty".to_string());
+    }
+    if name.len() > MAX_EXTENSION_NAME_LENGTH {
+        return Err(format!(
+            "Invalid extension attribute name: {name}. Must be at most {MAX_EXTENSION_NAME_LENGTH} characters"
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(format!(
+            "Invalid extension attribute name: {name}. Must only contain lowercase letters and digits"
+        ));
+    }
+    if CloudEventFields::from_str(name).is_ok() || name == DATA_ATTRIBUTE {
+        return Err(format!(
+            "Invalid extension attribute name: {name}. Must not be a core attribute name"
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the name and value of an extension attribute.
+///
+/// # Errors
+/// Returns a string containing the error message if
+///     - the name is not valid, see [`validate_extension_name`]
+///     - the value is not valid for a well-known extension (e.g. `dataref` is not a URI reference)
+pub fn validate_extension(name: &str, value: &str) -> Result<(), String> {
+    validate_extension_name(name)?;
+    if name == DATA_REF_EXTENSION
+        && let Err(e) = UriRef::parse(value)
+    {
+        return Err(format!(
+            "Invalid {name} value: {value}. Must adhere to RFC 3986 Section 4.1. Error: {e}"
+        ));
+    }
+    Ok(())
+}
+
 // ~~~~~~~~~~ Public CloudEvent struct and fns to convert to/from publishes ~~~~~~~~~~
 // ~~~~~~~~~~ Builder only used for creating a Cloud Event to send ~~~~~~~~~~
 
@@ -215,9 +283,21 @@ pub struct CloudEvent {
     /// whereby format and encoding might differ from that of the chosen event format.
     #[builder(default = "None")]
     pub data_content_type: Option<String>,
+    /// Extension attributes, keyed by attribute name. Names must only contain lowercase letters
+    /// and digits and must not collide with the names of core attributes.
+    #[builder(default)]
+    pub extensions: BTreeMap<String, String>,
 }
 
 impl CloudEventBuilder {
+    /// Add a single extension attribute, replacing any existing value for `name`.
+    pub fn extension<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.extensions
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
     fn validate(&self) -> Result<(), String> {
         let mut spec_version = DEFAULT_CLOUD_EVENT_SPEC_VERSION.to_string();
 
@@ -250,6 +330,12 @@ impl CloudEventBuilder {
             CloudEventFields::DataContentType.validate(data_content_type, &spec_version)?;
         }
 
+        if let Some(extensions) = &self.extensions {
+            for (name, value) in extensions {
+                validate_extension(name, value)?;
+            }
+        }
+
         // time does not need to be validated because converting it to an rfc3339 compliant string will always succeed
 
         Ok(())
@@ -282,6 +368,9 @@ impl TryFrom<(&Vec<(String, String)>, Option<&str>)> for CloudEvent {
     /// Parse a [`CloudEvent`] from a Publish's user properties and content type.
     /// Note that this will return an error if the arguments do not contain the required fields for a [`CloudEvent`].
     ///
+    /// Any other user property whose name is a valid extension attribute name (see
+    /// [`validate_extension_name`]) is included in the [`CloudEvent`]'s extensions.
+    ///
     /// # Errors
     /// [`CloudEventParseError`] if
     ///     - the arguments do not contain the required fields for a [`CloudEvent`].
@@ -291,6 +380,7 @@ impl TryFrom<(&Vec<(String, String)>, Option<&str>)> for CloudEvent {
     ) -> Result<CloudEvent, CloudEventParseError> {
         // use builder so that all fields can be validated together
         let mut received_cloud_event_builder = ReceivedCloudEventBuilder::default();
+        let mut extensions = BTreeMap::new();
         if let Some(content_type) = content_type {
             received_cloud_event_builder.data_content_type(content_type.to_string());
         }
@@ -318,9 +408,16 @@ impl TryFrom<(&Vec<(String, String)>, Option<&str>)> for CloudEvent {
                 Ok(CloudEventFields::Time) => {
                     received_cloud_event_builder.builder_time(Some(value.into()));
                 }
-                _ => {}
+                Ok(CloudEventFields::DataContentType) => {}
+                // Any other property with a valid attribute name is an extension attribute
+                Err(()) => {
+                    if validate_extension_name(key).is_ok() {
+                        extensions.insert(key.clone(), value.clone());
+                    }
+                }
             }
         }
+        received_cloud_event_builder.extensions(extensions);
         let mut received_cloud_event = received_cloud_event_builder.build()?;
         // now that everything is validated, update the time field to its correct typing
         // NOTE: If the spec_version changes in the future, that may need to be taken into account here.
@@ -389,11 +486,30 @@ impl From<CloudEvent> for Vec<(String, String)> {
         if let Some(data_schema) = value.data_schema {
             headers.push((CloudEventFields::DataSchema.to_string(), data_schema));
         }
+        headers.extend(value.extensions);
         headers
     }
 }
 
 impl CloudEvent {
+    /// Get the value of the extension attribute `name`, if present
+    #[must_use]
+    pub fn extension(&self, name: &str) -> Option<&str> {
+        self.extensions.get(name).map(String::as_str)
+    }
+
+    /// Get the value of the [`PARTITION_KEY_EXTENSION`] extension attribute, if present
+    #[must_use]
+    pub fn partition_key(&self) -> Option<&str> {
+        self.extension(PARTITION_KEY_EXTENSION)
+    }
+
+    /// Get the value of the [`DATA_REF_EXTENSION`] extension attribute, if present
+    #[must_use]
+    pub fn data_ref(&self) -> Option<&str> {
+        self.extension(DATA_REF_EXTENSION)
+    }
+
     /// Set [`CloudEvent`] as user properties on a [`PublishProperties`] for an MQTT publish
     /// Note that if `data_content_type` is `Some` on the [`CloudEvent`], the value will override
     /// any `content_type` already set in the `PublishProperties`
@@ -415,7 +531,7 @@ impl Display for CloudEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "CloudEvent {{ id: {id}, source: {source}, spec_version: {spec_version}, event_type: {event_type}, subject: {subject}, data_schema: {data_schema}, data_content_type: {data_content_type}, time: {time:?} }}",
+            "CloudEvent {{ id: {id}, source: {source}, spec_version: {spec_version}, event_type: {event_type}, subject: {subject}, data_schema: {data_schema}, data_content_type: {data_content_type}, time: {time:?}, extensions: {extensions:?} }}",
             id = self.id,
             source = self.source,
             spec_version = self.spec_version,
@@ -424,6 +540,7 @@ impl Display for CloudEvent {
             data_schema = self.data_schema.as_deref().unwrap_or("None"),
             data_content_type = self.data_content_type.as_deref().unwrap_or("None"),
             time = self.time,
+            extensions = self.extensions,
         )
     }
 }
@@ -477,6 +594,10 @@ struct ReceivedCloudEvent {
     /// time as a string so that it can be validated during build
     #[builder(default = "None")]
     builder_time: Option<String>,
+    /// Extension attributes, keyed by attribute name. Only properties with valid extension
+    /// attribute names are included.
+    #[builder(default)]
+    pub extensions: BTreeMap<String, String>,
 }
 
 impl ReceivedCloudEventBuilder {
@@ -518,6 +639,12 @@ impl ReceivedCloudEventBuilder {
             CloudEventFields::Time.validate(builder_time, &spec_version)?;
         }
 
+        if let Some(extensions) = &self.extensions {
+            for (name, value) in extensions {
+                validate_extension(name, value)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -533,6 +660,7 @@ impl From<ReceivedCloudEvent> for CloudEvent {
             data_schema: received.data_schema,
             data_content_type: received.data_content_type,
             time: received.time,
+            extensions: received.extensions,
         }
     }
 }
@@ -905,6 +1033,130 @@ mod tests {
         // Should successfully parse, ignoring unknown fields
         assert_eq!(cloud_event.id, "ignore-unknown");
         assert_eq!(cloud_event.source, "aio://device");
+        // Names with underscores are not valid extension attribute names
+        assert!(cloud_event.extensions.is_empty());
+    }
+
+    #[test_case("partitionkey", true; "well_known")]
+    #[test_case("traceparent", true; "lowercase")]
+    #[test_case("ext2", true; "with_digits")]
+    #[test_case("abcdefghijklmnopqrst", true; "max_length")]
+    #[test_case("abcdefghijklmnopqrstu", false; "too_long")]
+    #[test_case("", false; "empty")]
+    #[test_case("traceParent", false; "uppercase")]
+    #[test_case("trace_parent", false; "underscore")]
+    #[test_case("__ts", false; "reserved_protocol_property")]
+    #[test_case("source", false; "core_attribute")]
+    #[test_case("datacontenttype", false; "core_data_content_type")]
+    #[test_case("data", false; "data")]
+    fn test_validate_extension_name(name: &str, expected: bool) {
+        assert_eq!(validate_extension_name(name).is_ok(), expected);
+    }
+
+    #[test_case("https://storage.example.com/blob/1", true; "absolute_uri")]
+    #[test_case("/blob/1", true; "relative_reference")]
+    #[test_case("not a uri", false; "invalid")]
+    fn test_validate_extension_data_ref(value: &str, expected: bool) {
+        assert_eq!(
+            validate_extension(DATA_REF_EXTENSION, value).is_ok(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_extension() {
+        let result = CloudEventBuilder::default()
+            .source("aio://device")
+            .event_type("test.event")
+            .extension("subject", "collides-with-core-attribute")
+            .build();
+        assert!(matches!(
+            result,
+            Err(CloudEventBuilderError::ValidationError(_))
+        ));
+
+        let result = CloudEventBuilder::default()
+            .source("aio://device")
+            .event_type("test.event")
+            .extension("Partition_Key", "value")
+            .build();
+        assert!(matches!(
+            result,
+            Err(CloudEventBuilderError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_from_extensions() {
+        let user_properties = vec![
+            ("id".to_string(), "extensions".to_string()),
+            ("source".to_string(), "aio://device".to_string()),
+            ("specversion".to_string(), "1.0".to_string()),
+            ("type".to_string(), "test.event".to_string()),
+            ("partitionkey".to_string(), "line-1".to_string()),
+            (
+                "dataref".to_string(),
+                "https://storage.example.com/blob/1".to_string(),
+            ),
+            (
+                "traceparent".to_string(),
+                "00-0af7651916cd43dd-01".to_string(),
+            ),
+            ("__ts".to_string(), "not-an-extension".to_string()),
+        ];
+
+        let cloud_event = CloudEvent::try_from((&user_properties, None)).unwrap();
+
+        assert_eq!(cloud_event.extensions.len(), 3);
+        assert_eq!(cloud_event.partition_key(), Some("line-1"));
+        assert_eq!(
+            cloud_event.data_ref(),
+            Some("https://storage.example.com/blob/1")
+        );
+        assert_eq!(
+            cloud_event.extension("traceparent"),
+            Some("00-0af7651916cd43dd-01")
+        );
+        assert_eq!(cloud_event.extension("__ts"), None);
+    }
+
+    #[test]
+    fn test_try_from_invalid_data_ref() {
+        let user_properties = vec![
+            ("id".to_string(), "invalid-dataref".to_string()),
+            ("source".to_string(), "aio://device".to_string()),
+            ("specversion".to_string(), "1.0".to_string()),
+            ("type".to_string(), "test.event".to_string()),
+            ("dataref".to_string(), "not a uri".to_string()),
+        ];
+
+        assert!(CloudEvent::try_from((&user_properties, None)).is_err());
+    }
+
+    #[test]
+    fn test_extensions_round_trip() {
+        let original_cloud_event = CloudEventBuilder::default()
+            .source("aio://device")
+            .event_type("test.event")
+            .extension(PARTITION_KEY_EXTENSION, "line-1")
+            .extension("traceparent", "00-0af7651916cd43dd-01")
+            .build()
+            .unwrap();
+
+        let publish_properties = original_cloud_event
+            .clone()
+            .set_on_publish_properties(PublishProperties::default());
+        assert!(
+            publish_properties
+                .user_properties
+                .contains(&("partitionkey".to_string(), "line-1".to_string()))
+        );
+
+        let reconstructed_cloud_event = CloudEvent::try_from(&publish_properties).unwrap();
+        assert_eq!(
+            reconstructed_cloud_event.extensions,
+            original_cloud_event.extensions
+        );
     }
 
     #[test]
@@ -4,8 +4,12 @@
 //! Generic MQTT connection settings implementations
 
 use std::env::{self, VarError};
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::session::auth_provider::AuthProvider;
+
 // TODO: Split up this struct to avoid weird combinations and separate concern.
 // Things like having both password and password_file don't make much sense,
 // nor frankly does combining MQTT and TLS settings.
@@ -38,6 +42,15 @@ pub struct MqttConnectionSettings {
     /// Connection timeout
     #[builder(default = "Duration::from_secs(30)")]
     pub(crate) connection_timeout: Duration,
+    /// Race connection attempts to the addresses the hostname resolves to ("Happy Eyeballs",
+    /// RFC 8305) instead of attempting them one at a time. This avoids slow connections on
+    /// dual-stack networks where some of the resolved addresses (e.g. IPv6) are unreachable.
+    #[builder(default = "true")]
+    pub(crate) happy_eyeballs: bool,
+    /// Time to wait for a connection attempt to one of the resolved addresses before also
+    /// attempting the next one. Only used if `happy_eyeballs` is enabled.
+    #[builder(default = "Duration::from_millis(250)")]
+    pub(crate) connection_attempt_delay: Duration,
     /// Clean start
     #[builder(default = "false")]
     //NOTE: Should be `true` outside of AIO context. Consider when refactoring settings.
@@ -57,6 +70,20 @@ pub struct MqttConnectionSettings {
     /// Path to a PEM file used to validate server identity
     #[builder(default = "None")]
     pub(crate) ca_file: Option<String>,
+    /// Name sent in SNI and used to validate the server cert, instead of `hostname`. Useful when
+    /// the host can only be reached by an address its cert isn't issued for (e.g. an IP address
+    /// behind a load balancer).
+    #[builder(default = "None")]
+    pub(crate) tls_server_name: Option<String>,
+    /// SHA-256 hashes of the server certs to accept. Each is the hash of either the DER-encoded
+    /// server cert or its DER-encoded `SubjectPublicKeyInfo`. If set, the cert presented by the
+    /// server must match one of them.
+    #[builder(default = "None")]
+    pub(crate) pinned_server_cert_sha256: Option<Vec<[u8; 32]>>,
+    /// Whether a server cert matching `pinned_server_cert_sha256` must also be validated against
+    /// the trusted CAs
+    #[builder(default)]
+    pub(crate) server_cert_pin_mode: ServerCertPinMode,
     /// Path to PEM file used to establish X509 client authentication
     #[builder(default = "None")]
     pub(crate) cert_file: Option<String>,
@@ -69,9 +96,35 @@ pub struct MqttConnectionSettings {
     /// Path to a SAT file to be used for SAT auth
     #[builder(default = "None")]
     pub(crate) sat_file: Option<String>,
+    /// Provider of username/password credentials, invoked before every connection attempt
+    #[builder(default = "None", setter(custom))]
+    pub(crate) auth_provider: Option<Arc<dyn AuthProvider>>,
+}
+
+/// How a server cert is validated when `pinned_server_cert_sha256` is set on the
+/// [`MqttConnectionSettings`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ServerCertPinMode {
+    /// The server cert must match a pin, in addition to passing the usual validation against the
+    /// trusted CAs and `hostname` (or `tls_server_name`)
+    #[default]
+    PinAndChain,
+    /// The server cert must match a pin, and is not otherwise validated. Allows self-signed server
+    /// certs without adding them to the trusted CAs.
+    PinOnly,
 }
 
 impl MqttConnectionSettingsBuilder {
+    /// Use the provided [`AuthProvider`] to obtain the username and password before every
+    /// connection attempt, instead of static values.
+    ///
+    /// Cannot be used together with `username`, `password`, `password_file` or `sat_file`.
+    #[must_use]
+    pub fn auth_provider(mut self, auth_provider: impl AuthProvider + 'static) -> Self {
+        self.auth_provider = Some(Some(Arc::new(auth_provider)));
+        self
+    }
+
     /// Initialize the [`MqttConnectionSettingsBuilder`] from environment variables.
     ///
     /// Values that are not present in the environment will be set to defaults (including those
@@ -192,6 +245,12 @@ impl MqttConnectionSettingsBuilder {
         if self.client_id.as_ref().is_some_and(String::is_empty) {
             return Err("client_id cannot be empty".to_string());
         }
+        if self
+            .connection_attempt_delay
+            .is_some_and(|delay| delay.is_zero())
+        {
+            return Err("connection_attempt_delay must be greater than zero".to_string());
+        }
         if [
             self.password.as_ref(),
             self.password_file.as_ref(),
@@ -204,6 +263,21 @@ impl MqttConnectionSettingsBuilder {
         {
             return Err("Only one of password, password_file or sat_file can be used.".to_string());
         }
+        if self.auth_provider.as_ref().is_some_and(Option::is_some)
+            && [
+                self.username.as_ref(),
+                self.password.as_ref(),
+                self.password_file.as_ref(),
+                self.sat_file.as_ref(),
+            ]
+            .into_iter()
+            .any(|v| v.is_some_and(|s| s.as_ref().is_some()))
+        {
+            return Err(
+                "auth_provider cannot be used with username, password, password_file or sat_file."
+                    .to_string(),
+            );
+        }
         match (self.key_file.as_ref(), self.cert_file.as_ref()) {
             (None | Some(None), None | Some(None)) => (),
             (Some(Some(key_file)), Some(Some(cert_file))) => {
@@ -218,10 +292,52 @@ impl MqttConnectionSettingsBuilder {
         {
             return Err("key_password_file is set, but key_file is not.".to_string());
         }
+        if let Some(Some(tls_server_name)) = &self.tls_server_name
+            && !is_valid_server_name(tls_server_name)
+        {
+            return Err(format!(
+                "tls_server_name is not a valid hostname or IP address: {tls_server_name:?}"
+            ));
+        }
+        if let Some(Some(pins)) = &self.pinned_server_cert_sha256
+            && pins.is_empty()
+        {
+            return Err("pinned_server_cert_sha256 cannot be empty".to_string());
+        }
+        if self.use_tls == Some(false)
+            && (self.tls_server_name.as_ref().is_some_and(Option::is_some)
+                || self
+                    .pinned_server_cert_sha256
+                    .as_ref()
+                    .is_some_and(Option::is_some))
+        {
+            return Err(
+                "tls_server_name and pinned_server_cert_sha256 require use_tls to be true."
+                    .to_string(),
+            );
+        }
         Ok(())
     }
 }
 
+/// Whether the server name is an IP address, or a hostname made of dot-separated labels of up to
+/// 63 letters, digits and hyphens that don't start or end with a hyphen
+fn is_valid_server_name(server_name: &str) -> bool {
+    if server_name.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+    let hostname = server_name.strip_suffix('.').unwrap_or(server_name);
+    !hostname.is_empty()
+        && hostname.len() <= 253
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
 /// Helper function to get an environment variable as a string.
 fn string_from_environment(key: &str) -> Result<Option<String>, String> {
     match env::var(key) {
@@ -265,6 +381,24 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn connection_attempt_delay() {
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .connection_attempt_delay(Duration::ZERO)
+            .build();
+        assert!(result.is_err());
+
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .happy_eyeballs(false)
+            .connection_attempt_delay(Duration::from_millis(100))
+            .build();
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn password_combos() {
         // The password and password_file cannot be used at the same time
@@ -329,6 +463,125 @@ mod tests {
         assert!(connection_settings_builder_result.is_ok());
     }
 
+    #[derive(Debug)]
+    struct StaticAuthProvider;
+
+    #[async_trait::async_trait]
+    impl AuthProvider for StaticAuthProvider {
+        async fn credentials(
+            &self,
+        ) -> Result<
+            crate::session::auth_provider::Credentials,
+            crate::session::auth_provider::AuthError,
+        > {
+            Ok(crate::session::auth_provider::Credentials::default())
+        }
+    }
+
+    #[test]
+    fn auth_provider_combos() {
+        // The auth_provider can be used alone
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .auth_provider(StaticAuthProvider)
+            .build();
+        assert!(result.is_ok());
+        assert!(result.unwrap().auth_provider().is_some());
+
+        // The auth_provider cannot be used with username
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .username("test_username".to_string())
+            .auth_provider(StaticAuthProvider)
+            .build();
+        assert!(result.is_err());
+
+        // The auth_provider cannot be used with password
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .password("test_password".to_string())
+            .auth_provider(StaticAuthProvider)
+            .build();
+        assert!(result.is_err());
+
+        // The auth_provider cannot be used with password_file
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .password_file("test_password_file".to_string())
+            .auth_provider(StaticAuthProvider)
+            .build();
+        assert!(result.is_err());
+
+        // The auth_provider cannot be used with sat_file
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .sat_file("test_sat_auth_file".to_string())
+            .auth_provider(StaticAuthProvider)
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test_case("broker.contoso.com", true; "hostname")]
+    #[test_case("broker", true; "single label")]
+    #[test_case("broker.contoso.com.", true; "trailing dot")]
+    #[test_case("10.0.0.4", true; "ipv4")]
+    #[test_case("fd00::4", true; "ipv6")]
+    #[test_case("", false; "empty")]
+    #[test_case("broker..contoso.com", false; "empty label")]
+    #[test_case("-broker.contoso.com", false; "leading hyphen")]
+    #[test_case("broker_1.contoso.com", false; "invalid character")]
+    #[test_case("broker.contoso.com:8883", false; "port")]
+    fn tls_server_name(tls_server_name: &str, valid: bool) {
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("10.0.0.4".to_string())
+            .tls_server_name(tls_server_name.to_string())
+            .build();
+        assert_eq!(result.is_ok(), valid);
+    }
+
+    #[test]
+    fn pinned_server_cert_sha256() {
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .pinned_server_cert_sha256(vec![[0; 32]])
+            .server_cert_pin_mode(ServerCertPinMode::PinOnly)
+            .build();
+        assert!(result.is_ok());
+
+        // At least one pin must be provided
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .pinned_server_cert_sha256(vec![])
+            .build();
+        assert!(result.is_err());
+
+        // Pins require TLS
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .use_tls(false)
+            .pinned_server_cert_sha256(vec![[0; 32]])
+            .build();
+        assert!(result.is_err());
+
+        // As does the server name
+        let result = MqttConnectionSettingsBuilder::default()
+            .client_id("test_client_id".to_string())
+            .hostname("test_host".to_string())
+            .use_tls(false)
+            .tls_server_name("broker".to_string())
+            .build();
+        assert!(result.is_err());
+    }
+
     #[test]
     fn cert_file_key_file_combos() {
         // The cert_file and key_file can be provided together
@@ -0,0 +1,772 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Diagnosis of the [`MqttConnectionSettings`] used to connect to an MQTT broker, to find the
+//! cause of connection failures such as a wrong port, a missing CA, an expired SAT or clock skew.
+
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use openssl::x509::X509VerifyResult;
+use tokio::net::TcpStream;
+use tokio_openssl::SslStream;
+
+use crate::aio::connection_settings::MqttConnectionSettings;
+use crate::azure_mqtt::transport::{ConnectionTransportType, TlsConfig};
+use crate::azure_mqtt_adapter::create_connection_transport_config;
+use crate::error::ConnectError;
+use crate::session::reconnect_policy::{ConnectionLossReason, ReconnectPolicy};
+use crate::session::{CertificateInfo, Session, SessionOptionsBuilder, TlsInfo};
+
+/// Time beyond the expiry of the connection timeout to wait for the MQTT connection probe, to
+/// allow for the TCP connection and TLS handshake
+const PROBE_TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+
+/// Difference between the local clock and the times in a SAT that is tolerated before it is
+/// reported as clock skew
+const CLOCK_SKEW_TOLERANCE: chrono::TimeDelta = chrono::TimeDelta::seconds(60);
+
+/// Stage of a connection diagnosis, in the order they are performed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosisStage {
+    /// Resolution of the hostname to IP addresses
+    DnsResolution,
+    /// TCP connection to the port on one of the resolved addresses
+    TcpConnect,
+    /// Loading of the TLS configuration and TLS handshake with the configured trust
+    TlsHandshake,
+    /// Readability and validity of the configured credential files, such as the SAT file
+    Credentials,
+    /// MQTT CONNECT with the configured authentication, followed by a DISCONNECT
+    MqttConnect,
+}
+
+impl fmt::Display for DiagnosisStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosisStage::DnsResolution => write!(f, "DNS resolution"),
+            DiagnosisStage::TcpConnect => write!(f, "TCP connection"),
+            DiagnosisStage::TlsHandshake => write!(f, "TLS handshake"),
+            DiagnosisStage::Credentials => write!(f, "Credentials"),
+            DiagnosisStage::MqttConnect => write!(f, "MQTT connection"),
+        }
+    }
+}
+
+/// Outcome of a [`DiagnosisStage`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StageOutcome {
+    /// The stage passed
+    Passed,
+    /// The stage failed for the given reason
+    Failed(String),
+    /// The stage was not performed for the given reason, e.g. because an earlier stage failed
+    Skipped(String),
+}
+
+/// Report of a single [`DiagnosisStage`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageReport {
+    /// The stage reported on
+    pub stage: DiagnosisStage,
+    /// Whether the stage passed
+    pub outcome: StageOutcome,
+    /// Details gathered during the stage, such as the resolved addresses or the certificates
+    /// presented by the server
+    pub details: Vec<String>,
+}
+
+impl StageReport {
+    fn new(stage: DiagnosisStage) -> Self {
+        Self {
+            stage,
+            outcome: StageOutcome::Passed,
+            details: Vec::new(),
+        }
+    }
+
+    fn fail(mut self, reason: impl Into<String>) -> Self {
+        self.outcome = StageOutcome::Failed(reason.into());
+        self
+    }
+
+    fn skip(mut self, reason: impl Into<String>) -> Self {
+        self.outcome = StageOutcome::Skipped(reason.into());
+        self
+    }
+
+    fn passed(&self) -> bool {
+        self.outcome == StageOutcome::Passed
+    }
+}
+
+/// Report of a diagnosis of [`MqttConnectionSettings`], produced by
+/// [`MqttConnectionSettings::diagnose`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiagnosisReport {
+    /// Reports of each stage, in the order they were performed
+    pub stages: Vec<StageReport>,
+    /// Addresses the hostname resolved to
+    pub resolved_addresses: Vec<SocketAddr>,
+    /// Certificate chain presented by the server during the TLS handshake, starting with the
+    /// server's own certificate. Also reported when the handshake fails to validate it.
+    pub server_certificate_chain: Vec<CertificateInfo>,
+    /// Expiry of the token in the SAT file, if one is configured and could be parsed
+    pub sat_expiry: Option<DateTime<Utc>>,
+}
+
+impl DiagnosisReport {
+    /// Returns `true` if none of the stages failed
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.first_failure().is_none()
+    }
+
+    /// Returns the report of the first stage that failed, if any
+    #[must_use]
+    pub fn first_failure(&self) -> Option<&StageReport> {
+        self.stages
+            .iter()
+            .find(|stage| matches!(stage.outcome, StageOutcome::Failed(_)))
+    }
+
+    /// Returns the report of the given stage
+    #[must_use]
+    pub fn stage(&self, stage: DiagnosisStage) -> Option<&StageReport> {
+        self.stages.iter().find(|report| report.stage == stage)
+    }
+}
+
+impl fmt::Display for DiagnosisReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for report in &self.stages {
+            match &report.outcome {
+                StageOutcome::Passed => writeln!(f, "[PASS] {}", report.stage)?,
+                StageOutcome::Failed(reason) => writeln!(f, "[FAIL] {}: {reason}", report.stage)?,
+                StageOutcome::Skipped(reason) => writeln!(f, "[SKIP] {}: {reason}", report.stage)?,
+            }
+            for detail in &report.details {
+                writeln!(f, "       {detail}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MqttConnectionSettings {
+    /// Diagnoses these settings by connecting to the MQTT broker in stages without starting a
+    /// [`Session`] for the application, reporting whether each stage passed along with the
+    /// details gathered. The stages are:
+    /// 1. [`DiagnosisStage::DnsResolution`] of the hostname
+    /// 2. [`DiagnosisStage::TcpConnect`] to the port
+    /// 3. [`DiagnosisStage::TlsHandshake`] with the configured trust, reporting the certificate
+    ///    chain presented by the server and its expiry
+    /// 4. [`DiagnosisStage::Credentials`], checking that the SAT file or password file can be read,
+    ///    and that the SAT has not expired and is not from the future according to the local clock
+    /// 5. [`DiagnosisStage::MqttConnect`], connecting with the configured authentication and then
+    ///    disconnecting
+    ///
+    /// Stages that depend on a failed stage are skipped. The MQTT connection is made with the
+    /// client id suffixed with `-diagnose`, a clean start and no session expiry, so that any
+    /// existing MQTT session of the client is not taken over or discarded.
+    pub async fn diagnose(&self) -> DiagnosisReport {
+        let mut report = DiagnosisReport::default();
+
+        let (dns_report, resolved_addresses) = self.diagnose_dns_resolution().await;
+        let dns_passed = dns_report.passed();
+        report.stages.push(dns_report);
+        report.resolved_addresses.clone_from(&resolved_addresses);
+
+        let (tcp_report, tcp_stream) = self.diagnose_tcp_connect(&resolved_addresses).await;
+        let tcp_passed = tcp_report.passed();
+        report.stages.push(tcp_report);
+
+        let (tls_report, server_certificate_chain) = self.diagnose_tls_handshake(tcp_stream).await;
+        let tls_passed = tls_report.passed();
+        report.stages.push(tls_report);
+        report.server_certificate_chain = server_certificate_chain;
+
+        let (credentials_report, sat_expiry, credentials_readable) = self.diagnose_credentials();
+        report.stages.push(credentials_report);
+        report.sat_expiry = sat_expiry;
+
+        let reachable = dns_passed && tcp_passed && (tls_passed || !self.use_tls);
+        let mqtt_report = if !reachable {
+            StageReport::new(DiagnosisStage::MqttConnect)
+                .skip("the broker could not be reached, see the previous stages")
+        } else if !credentials_readable {
+            StageReport::new(DiagnosisStage::MqttConnect)
+                .skip("the credentials could not be read, see the previous stage")
+        } else {
+            self.diagnose_mqtt_connect().await
+        };
+        report.stages.push(mqtt_report);
+
+        report
+    }
+
+    async fn diagnose_dns_resolution(&self) -> (StageReport, Vec<SocketAddr>) {
+        let report = StageReport::new(DiagnosisStage::DnsResolution);
+        let resolved = tokio::time::timeout(
+            self.connection_timeout,
+            tokio::net::lookup_host((self.hostname.as_str(), self.tcp_port)),
+        )
+        .await;
+        match resolved {
+            Ok(Ok(addresses)) => {
+                let addresses: Vec<_> = addresses.collect();
+                if addresses.is_empty() {
+                    return (
+                        report.fail(format!(
+                            "'{}' did not resolve to any address",
+                            self.hostname
+                        )),
+                        addresses,
+                    );
+                }
+                let mut report = report;
+                report.details = addresses
+                    .iter()
+                    .map(|address| format!("'{}' resolved to {}", self.hostname, address.ip()))
+                    .collect();
+                (report, addresses)
+            }
+            Ok(Err(e)) => (
+                report.fail(format!("could not resolve '{}': {e}", self.hostname)),
+                Vec::new(),
+            ),
+            Err(_) => (
+                report.fail(format!(
+                    "resolving '{}' did not complete within {:?}",
+                    self.hostname, self.connection_timeout
+                )),
+                Vec::new(),
+            ),
+        }
+    }
+
+    async fn diagnose_tcp_connect(
+        &self,
+        resolved_addresses: &[SocketAddr],
+    ) -> (StageReport, Option<TcpStream>) {
+        let mut report = StageReport::new(DiagnosisStage::TcpConnect);
+        if resolved_addresses.is_empty() {
+            return (report.skip("the hostname was not resolved"), None);
+        }
+        for address in resolved_addresses {
+            match tokio::time::timeout(self.connection_timeout, TcpStream::connect(address)).await {
+                Ok(Ok(stream)) => {
+                    report.details.push(format!("connected to {address}"));
+                    return (report, Some(stream));
+                }
+                Ok(Err(e)) => report.details.push(format!("{address}: {e}")),
+                Err(_) => report.details.push(format!(
+                    "{address}: not connected within {:?}",
+                    self.connection_timeout
+                )),
+            }
+        }
+        (
+            report.fail(format!(
+                "could not connect to port {} on any resolved address, check the hostname and port",
+                self.tcp_port
+            )),
+            None,
+        )
+    }
+
+    async fn diagnose_tls_handshake(
+        &self,
+        tcp_stream: Option<TcpStream>,
+    ) -> (StageReport, Vec<CertificateInfo>) {
+        let mut report = StageReport::new(DiagnosisStage::TlsHandshake);
+        if !self.use_tls {
+            return (report.skip("TLS is disabled"), Vec::new());
+        }
+
+        // The TLS configuration is loaded even if the broker can't be reached, to also report
+        // problems with the CA, certificate and key files
+        let tls_config = match create_connection_transport_config(
+            self.ca_file.clone(),
+            self.cert_file.clone(),
+            self.key_file.clone(),
+            self.key_password_file.clone(),
+            self.use_tls,
+            self.tls_server_name.clone(),
+            self.pinned_server_cert_sha256.clone(),
+            self.server_cert_pin_mode,
+            self.hostname.clone(),
+            self.tcp_port,
+            self.connection_timeout,
+            None,
+        ) {
+            Ok(transport_config) => match transport_config.transport_type {
+                ConnectionTransportType::Tls { tls_config, .. } => tls_config,
+                _ => unreachable!("TLS is enabled, so the transport is TLS"),
+            },
+            Err(e) => {
+                return (
+                    report.fail(format!(
+                        "could not load the TLS configuration: {}",
+                        error_chain(&e)
+                    )),
+                    Vec::new(),
+                );
+            }
+        };
+        if let Some(ca_file) = &self.ca_file {
+            report
+                .details
+                .push(format!("trusting the CA certificates in '{ca_file}'"));
+        } else {
+            report
+                .details
+                .push("no CA file configured, trusting the system CA certificates".to_string());
+        }
+
+        let Some(tcp_stream) = tcp_stream else {
+            return (
+                report.skip("the TCP connection failed, TLS configuration loaded"),
+                Vec::new(),
+            );
+        };
+
+        let server_name = self.tls_server_name.as_deref().unwrap_or(&self.hostname);
+        let (result, tls_info) = tls_handshake(tcp_stream, tls_config, server_name).await;
+        let now = Utc::now();
+        if let Some(tls_info) = &tls_info {
+            if result.is_ok() {
+                report.details.push(format!(
+                    "negotiated {} with {}",
+                    tls_info.version, tls_info.cipher_suite
+                ));
+            }
+            for (depth, certificate) in tls_info.peer_certificate_chain.iter().enumerate() {
+                report
+                    .details
+                    .push(describe_certificate(depth, certificate, now));
+            }
+        }
+        let chain = tls_info
+            .map(|tls_info| tls_info.peer_certificate_chain)
+            .unwrap_or_default();
+        match result {
+            Ok(()) => (report, chain),
+            Err(reason) => (report.fail(reason), chain),
+        }
+    }
+
+    fn diagnose_credentials(&self) -> (StageReport, Option<DateTime<Utc>>, bool) {
+        let mut report = StageReport::new(DiagnosisStage::Credentials);
+        let mut sat_expiry = None;
+        let mut failure = None;
+        let mut readable = true;
+
+        if let Some(sat_file) = &self.sat_file {
+            match fs::read_to_string(sat_file) {
+                Ok(token) => {
+                    report
+                        .details
+                        .push(format!("SAT file '{sat_file}' is readable"));
+                    match SatTimes::parse(token.trim()) {
+                        Ok(times) => {
+                            sat_expiry = times.expiry;
+                            failure = times.check(Utc::now(), &mut report.details);
+                        }
+                        Err(e) => failure = Some(format!("SAT file '{sat_file}' {e}")),
+                    }
+                }
+                Err(e) => {
+                    readable = false;
+                    failure = Some(format!("could not read SAT file '{sat_file}': {e}"));
+                }
+            }
+        }
+        if let Some(password_file) = &self.password_file {
+            match fs::read(password_file) {
+                Ok(_) => report
+                    .details
+                    .push(format!("password file '{password_file}' is readable")),
+                Err(e) => {
+                    readable = false;
+                    failure.get_or_insert(format!(
+                        "could not read password file '{password_file}': {e}"
+                    ));
+                }
+            }
+        }
+        if self.auth_provider.is_some() {
+            report.details.push(
+                "credentials are obtained from the auth provider when connecting".to_string(),
+            );
+        }
+        if let Some(username) = &self.username {
+            report.details.push(format!("username '{username}'"));
+        }
+        if self.cert_file.is_some() {
+            report
+                .details
+                .push("X.509 client certificate is checked by the TLS handshake".to_string());
+        }
+        if report.details.is_empty() && failure.is_none() {
+            return (report.skip("no credentials configured"), None, true);
+        }
+
+        match failure {
+            Some(reason) => (report.fail(reason), sat_expiry, readable),
+            None => (report, sat_expiry, readable),
+        }
+    }
+
+    async fn diagnose_mqtt_connect(&self) -> StageReport {
+        let mut report = StageReport::new(DiagnosisStage::MqttConnect);
+
+        let mut connection_settings = self.clone();
+        connection_settings.client_id = format!("{}-diagnose", self.client_id);
+        connection_settings.clean_start = true;
+        connection_settings.session_expiry = Duration::ZERO;
+        report.details.push(format!(
+            "connecting as client id '{}'",
+            connection_settings.client_id
+        ));
+        let session = match SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .reconnect_policy(Box::new(NoReconnect))
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|options| Session::new(options).map_err(|e| error_chain(&e)))
+        {
+            Ok(session) => session,
+            Err(e) => return report.fail(format!("could not create the MQTT session: {e}")),
+        };
+        let session_monitor = session.create_session_monitor();
+        let exit_handle = session.create_exit_handle();
+        // The session future is large, so it is boxed to keep the diagnosis future small
+        let mut run = Box::pin(session.run());
+
+        let connected =
+            tokio::time::timeout(self.connection_timeout + PROBE_TIMEOUT_MARGIN, async {
+                tokio::select! {
+                    () = session_monitor.connected() => true,
+                    _ = &mut run => false,
+                }
+            })
+            .await;
+        match connected {
+            Ok(true) => {
+                report
+                    .details
+                    .push("CONNACK accepted the connection".to_string());
+                if exit_handle.try_exit().is_ok() {
+                    report.details.push("disconnected".to_string());
+                }
+                let _ = run.await;
+                report
+            }
+            Ok(false) => match session_monitor.last_disconnect_cause() {
+                Some(cause) => report.fail(format!("connection failed: {cause:?}")),
+                None => report.fail("connection failed"),
+            },
+            Err(_) => {
+                exit_handle.force_exit();
+                report.fail(format!(
+                    "not connected within {:?}",
+                    self.connection_timeout + PROBE_TIMEOUT_MARGIN
+                ))
+            }
+        }
+    }
+}
+
+/// Reconnect policy that never reconnects, so that the MQTT connection probe is attempted once
+struct NoReconnect;
+
+impl ReconnectPolicy for NoReconnect {
+    fn connect_failure_reconnect_delay(
+        &self,
+        _prev_attempts: u32,
+        _error: &ConnectError,
+    ) -> Option<Duration> {
+        None
+    }
+
+    fn connection_loss_reconnect_delay(&self, _reason: &ConnectionLossReason) -> Option<Duration> {
+        None
+    }
+}
+
+/// Performs a TLS handshake on the stream, returning the details of the TLS session, which are
+/// also available if the server certificate failed to validate
+async fn tls_handshake(
+    stream: TcpStream,
+    tls_config: TlsConfig,
+    server_name: &str,
+) -> (Result<(), String>, Option<TlsInfo>) {
+    let ssl = match tls_config
+        .connector
+        .build()
+        .configure()
+        .and_then(|connector| connector.into_ssl(server_name))
+    {
+        Ok(ssl) => ssl,
+        Err(e) => return (Err(format!("could not configure TLS: {e}")), None),
+    };
+    let mut ssl_stream = match SslStream::new(ssl, stream) {
+        Ok(ssl_stream) => ssl_stream,
+        Err(e) => return (Err(format!("could not configure TLS: {e}")), None),
+    };
+    let result = Pin::new(&mut ssl_stream).connect().await;
+    let tls_info = TlsInfo::from_ssl(ssl_stream.ssl());
+    let result = result.map_err(|e| {
+        let verify_result = ssl_stream.ssl().verify_result();
+        if verify_result == X509VerifyResult::OK {
+            format!("handshake failed: {e}")
+        } else {
+            format!(
+                "server certificate for '{server_name}' failed to validate: {}",
+                verify_result.error_string()
+            )
+        }
+    });
+    (result, Some(tls_info))
+}
+
+/// Describes a certificate presented by the server, flagging it if it is not currently valid
+fn describe_certificate(depth: usize, certificate: &CertificateInfo, now: DateTime<Utc>) -> String {
+    let validity = if certificate.not_after < now {
+        " (EXPIRED)"
+    } else if certificate.not_before > now {
+        " (NOT YET VALID, check the clock)"
+    } else {
+        ""
+    };
+    format!(
+        "certificate {depth}: subject '{}', issuer '{}', valid from {} to {}{validity}",
+        certificate.subject, certificate.issuer, certificate.not_before, certificate.not_after
+    )
+}
+
+/// Formats an error followed by its sources
+fn error_chain(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        description.push_str(": ");
+        description.push_str(&e.to_string());
+        source = e.source();
+    }
+    description
+}
+
+/// Validity times of a SAT, which is a JWT
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SatTimes {
+    expiry: Option<DateTime<Utc>>,
+    not_before: Option<DateTime<Utc>>,
+    issued_at: Option<DateTime<Utc>>,
+}
+
+impl SatTimes {
+    /// Parses the validity times from the claims of a JWT, without validating its signature
+    fn parse(token: &str) -> Result<Self, String> {
+        let mut segments = token.split('.');
+        let (Some(_header), Some(claims), Some(_signature), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err("does not contain a JWT".to_string());
+        };
+        let claims = base64url_decode(claims)
+            .ok_or_else(|| "contains a JWT with invalid base64url claims".to_string())?;
+        let claims: serde_json::Value = serde_json::from_slice(&claims)
+            .map_err(|e| format!("contains a JWT with invalid JSON claims: {e}"))?;
+        let time = |claim: &str| {
+            claims
+                .get(claim)
+                .and_then(serde_json::Value::as_i64)
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        };
+        Ok(Self {
+            expiry: time("exp"),
+            not_before: time("nbf"),
+            issued_at: time("iat"),
+        })
+    }
+
+    /// Checks the validity times against the local clock, adding them to `details` and returning
+    /// the reason the SAT is not valid, if any
+    fn check(&self, now: DateTime<Utc>, details: &mut Vec<String>) -> Option<String> {
+        if let Some(issued_at) = self.issued_at {
+            details.push(format!("SAT issued at {issued_at}"));
+        }
+        if let Some(not_before) = self.not_before
+            && not_before > now + CLOCK_SKEW_TOLERANCE
+        {
+            return Some(format!(
+                "SAT is not valid until {not_before}, but the local time is {now}, check the clock"
+            ));
+        }
+        if let Some(issued_at) = self.issued_at
+            && issued_at > now + CLOCK_SKEW_TOLERANCE
+        {
+            details.push(format!(
+                "SAT was issued after the local time {now}, the clock may be behind"
+            ));
+        }
+        match self.expiry {
+            Some(expiry) if expiry <= now => {
+                Some(format!("SAT expired at {expiry}, the local time is {now}"))
+            }
+            Some(expiry) => {
+                details.push(format!("SAT expires at {expiry}"));
+                None
+            }
+            None => {
+                details.push("SAT has no expiry".to_string());
+                None
+            }
+        }
+    }
+}
+
+/// Decodes unpadded base64url, as used by JWTs
+fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut base64: String = encoded
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    while base64.len() % 4 != 0 {
+        base64.push('=');
+    }
+    openssl::base64::decode_block(&base64).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone};
+    use test_case::test_case;
+
+    use super::*;
+
+    fn jwt(claims: &str) -> String {
+        let encode = |s: &str| {
+            openssl::base64::encode_block(s.as_bytes())
+                .trim_end_matches('=')
+                .replace('+', "-")
+                .replace('/', "_")
+        };
+        format!(
+            "{}.{}.signature",
+            encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+            encode(claims)
+        )
+    }
+
+    #[test]
+    fn sat_times_parse() {
+        let times = SatTimes::parse(&jwt(
+            r#"{"aud":["aio-internal"],"exp":1800000000,"iat":1700000000,"nbf":1700000000}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            times,
+            SatTimes {
+                expiry: Some(Utc.timestamp_opt(1_800_000_000, 0).unwrap()),
+                not_before: Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+                issued_at: Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+            }
+        );
+    }
+
+    #[test_case("not a token"; "not a jwt")]
+    #[test_case("a.b"; "too few segments")]
+    #[test_case("a.!!!.c"; "invalid base64url")]
+    #[test_case("a.bm90IGpzb24.c"; "invalid json")]
+    fn sat_times_parse_invalid(token: &str) {
+        assert!(SatTimes::parse(token).is_err());
+    }
+
+    #[test]
+    fn sat_times_check() {
+        let now = Utc.timestamp_opt(1_750_000_000, 0).unwrap();
+        let valid = SatTimes {
+            expiry: Some(now + TimeDelta::hours(1)),
+            not_before: Some(now - TimeDelta::hours(1)),
+            issued_at: Some(now - TimeDelta::hours(1)),
+        };
+        let mut details = Vec::new();
+        assert_eq!(valid.check(now, &mut details), None);
+        assert!(
+            details
+                .iter()
+                .any(|detail| detail.starts_with("SAT expires at"))
+        );
+
+        let expired = SatTimes {
+            expiry: Some(now - TimeDelta::seconds(1)),
+            ..Default::default()
+        };
+        assert!(
+            expired
+                .check(now, &mut Vec::new())
+                .unwrap()
+                .starts_with("SAT expired at")
+        );
+
+        // Tolerated clock skew
+        let skewed = SatTimes {
+            expiry: Some(now + TimeDelta::hours(1)),
+            not_before: Some(now + TimeDelta::seconds(30)),
+            ..Default::default()
+        };
+        assert_eq!(skewed.check(now, &mut Vec::new()), None);
+
+        let future = SatTimes {
+            expiry: Some(now + TimeDelta::hours(2)),
+            not_before: Some(now + TimeDelta::hours(1)),
+            ..Default::default()
+        };
+        assert!(
+            future
+                .check(now, &mut Vec::new())
+                .unwrap()
+                .ends_with("check the clock")
+        );
+    }
+
+    #[test]
+    fn report_display_and_first_failure() {
+        let report = DiagnosisReport {
+            stages: vec![
+                StageReport {
+                    stage: DiagnosisStage::DnsResolution,
+                    outcome: StageOutcome::Passed,
+                    details: vec!["'localhost' resolved to 127.0.0.1".to_string()],
+                },
+                StageReport::new(DiagnosisStage::TcpConnect).fail("connection refused"),
+                StageReport::new(DiagnosisStage::TlsHandshake).skip("TLS is disabled"),
+            ],
+            ..Default::default()
+        };
+        assert!(!report.passed());
+        assert_eq!(
+            report.first_failure().unwrap().stage,
+            DiagnosisStage::TcpConnect
+        );
+        assert_eq!(
+            report.to_string(),
+            "[PASS] DNS resolution\n       'localhost' resolved to 127.0.0.1\n[FAIL] TCP connection: connection refused\n[SKIP] TLS handshake: TLS is disabled\n"
+        );
+    }
+}
@@ -9,6 +9,7 @@
 #![allow(clippy::unused_async)]
 
 use std::{future::Future, io, num::NonZeroU16, pin::pin, time::Duration};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 
 use bytes::{Bytes, BytesMut};
 use futures_util::future::{self, FutureExt as _};
@@ -54,7 +55,7 @@ use crate::azure_mqtt::packet::{
     RetainOptions, SubscribeProperties, UnsubscribeProperties, Will,
 };
 use crate::azure_mqtt::topic::{TopicFilter, TopicName};
-use crate::azure_mqtt::transport::{ConnectionTransportConfig, TlsConfig, ConnectionTransportType, Proxy, ProxyAuthorization, ProxyEndpoint};
+use crate::azure_mqtt::transport::{ConnectionTransportConfig, TlsConfig, ConnectionTransportType, Proxy, ProxyAuthorization, ProxyEndpoint, TlsInfo};
 
 // TODO: What should this module and factory function be called?
 // The three components are the client collectively - so what should the outbound struct (currently called the Client) be?
@@ -80,10 +81,12 @@ pub fn new_client(options: ClientOptions) -> (Client, ConnectHandle, Receiver) {
     // NOTE: We use an unbounded channel for incoming publishes, as messages read off the network must go
     // somewhere.
     let (i_pub_tx, i_pub_rx) = tokio::sync::mpsc::unbounded_channel();
+    let publish_counters = Arc::new(PublishCounters::default());
     let client = Client {
         pub_qos0_tx: o_pub_q0_tx,
         pub_qos12_tx: o_pub_q12_tx,
         sub_tx,
+        publish_counters: publish_counters.clone(),
     };
     let reader_pool = BytesPool;
     let writer_pool = BytesPool;
@@ -98,6 +101,8 @@ pub fn new_client(options: ClientOptions) -> (Client, ConnectHandle, Receiver) {
         ack_tx,
         auth_tx,
         options.max_packet_identifier,
+        publish_counters,
+        options.outgoing_topic_aliases,
         owned,
     );
     let connect_handle = ConnectHandle {
@@ -120,6 +125,9 @@ pub struct ClientOptions {
     pub publish_qos0_queue_size: usize,
     /// Maximum size of the outgoing queue for QoS 1 and 2 PUBLISH packets.
     pub publish_qos1_qos2_queue_size: usize,
+    /// Whether outgoing PUBLISH topics are replaced by topic aliases, up to the server's topic
+    /// alias maximum.
+    pub outgoing_topic_aliases: bool,
     // TODO: Consider using a Builder pattern?
 }
 
@@ -130,6 +138,7 @@ impl Default for ClientOptions {
             max_packet_identifier: PacketIdentifier::MAX,
             publish_qos0_queue_size: 100,
             publish_qos1_qos2_queue_size: 100,
+            outgoing_topic_aliases: false,
         }
     }
 }
@@ -155,6 +164,40 @@ impl From<KeepAliveConfig> for KeepAlive {
     }
 }
 
+/// Snapshot of the state of outgoing PUBLISH flow control.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PublishStats {
+    /// Number of QoS 1 and 2 PUBLISHes sent to the server and awaiting acknowledgement
+    pub inflight: usize,
+    /// Number of PUBLISHes queued to be sent, but not yet in-flight
+    pub queued: usize,
+    /// Highest number of in-flight PUBLISHes observed
+    pub inflight_high_water_mark: usize,
+    /// Highest number of queued PUBLISHes observed
+    pub queued_high_water_mark: usize,
+}
+
+/// Counters for outgoing PUBLISH flow control, shared between the `Client` and the `Session`.
+#[derive(Debug, Default)]
+pub(crate) struct PublishCounters {
+    inflight: AtomicUsize,
+    inflight_high_water_mark: AtomicUsize,
+    queued_high_water_mark: AtomicUsize,
+}
+
+impl PublishCounters {
+    /// Update the number of in-flight PUBLISHes
+    pub(crate) fn set_inflight(&self, inflight: usize) {
+        self.inflight.store(inflight, Ordering::Relaxed);
+        self.inflight_high_water_mark.fetch_max(inflight, Ordering::Relaxed);
+    }
+
+    /// Record the number of queued PUBLISHes for the high water mark
+    fn record_queued(&self, queued: usize) {
+        self.queued_high_water_mark.fetch_max(queued, Ordering::Relaxed);
+    }
+}
+
 // TODO: I don't like the naming of this as Client.
 // MQTTHandle? Sender? OperationsInterface? Outgoing?
 
@@ -170,9 +213,27 @@ pub struct Client {
     pub_qos12_tx: tokio::sync::mpsc::Sender<PublishRequestQoS1QoS2<Bytes>>,
     /// Channel that transmits outgoing SUBSCRIBE/UNSUBSCRIBE requests
     sub_tx: tokio::sync::mpsc::Sender<SubscriptionRequest<Bytes>>,
+    /// Counters for outgoing PUBLISH flow control
+    publish_counters: Arc<PublishCounters>,
 }
 
 impl Client {
+    /// Returns a snapshot of the state of outgoing PUBLISH flow control.
+    pub fn publish_stats(&self) -> PublishStats {
+        PublishStats {
+            inflight: self.publish_counters.inflight.load(Ordering::Relaxed),
+            queued: self.queued_publishes(),
+            inflight_high_water_mark: self.publish_counters.inflight_high_water_mark.load(Ordering::Relaxed),
+            queued_high_water_mark: self.publish_counters.queued_high_water_mark.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Number of PUBLISH requests in the outgoing channels that the session has not yet taken
+    fn queued_publishes(&self) -> usize {
+        (self.pub_qos0_tx.max_capacity() - self.pub_qos0_tx.capacity())
+            + (self.pub_qos12_tx.max_capacity() - self.pub_qos12_tx.capacity())
+    }
+
     /// Sends a PUBLISH packet to the broker at QoS 0.
     ///
     /// Returns a token that can be awaited for confirmation of the PUBLISH being sent.
@@ -194,6 +255,7 @@ impl Client {
             ))
             .await
             .map_err(|_| DetachedError {})?;
+        self.publish_counters.record_queued(self.queued_publishes());
         Ok(PublishQoS0CompletionToken(token))
     }
 
@@ -218,6 +280,7 @@ impl Client {
             ))
             .await
             .map_err(|_| DetachedError {})?;
+        self.publish_counters.record_queued(self.queued_publishes());
         Ok(PublishQoS1CompletionToken(token))
     }
 
@@ -243,6 +306,7 @@ impl Client {
             ))
             .await
             .map_err(|_| DetachedError {})?;
+        self.publish_counters.record_queued(self.queued_publishes());
         Ok(PublishQoS2CompletionToken(token))
     }
 
@@ -353,7 +417,7 @@ impl ConnectHandle {
         properties: ConnectProperties,
         response_timeout: Option<Duration>,
     ) -> ConnectResult {
-        let (mut reader, mut writer) = match self.transport_connect(connection_transport).await {
+        let (mut reader, mut writer, tls_info) = match self.transport_connect(connection_transport).await {
             Ok(streams) => streams,
             Err(err) => {
                 return ConnectResult::Failure(self, err.into());
@@ -414,6 +478,7 @@ impl ConnectHandle {
                 writer,
                 cfg_client_id: self.cfg_client_id,
                 cfg_pingresp_timeout,
+                tls_info,
             },
             connack.into(),
             DisconnectHandle(disconnect_tx),
@@ -450,7 +515,7 @@ impl ConnectHandle {
         response_timeout: Option<Duration>,
     ) -> ConnectEnhancedAuthResult {
         let auth_method = authentication_info.method.clone();
-        let (mut reader, mut writer) = match self.transport_connect(connection_transport).await {
+        let (mut reader, mut writer, tls_info) = match self.transport_connect(connection_transport).await {
             Ok(streams) => streams,
             Err(err) => return ConnectEnhancedAuthResult::Failure(self, err.into()),
         };
@@ -502,6 +567,7 @@ impl ConnectHandle {
                             writer,
                             cfg_client_id: self.cfg_client_id,
                             cfg_pingresp_timeout,
+                            tls_info,
                         },
                         connack.into(),
                         DisconnectHandle(disconnect_tx),
@@ -525,6 +591,7 @@ impl ConnectHandle {
                     auth_method,
                     cfg_client_id: self.cfg_client_id,
                     cfg_keep_alive: keep_alive,
+                    tls_info,
                 };
                 ConnectEnhancedAuthResult::Continue(auth.into(), auth_handle)
             }
@@ -539,27 +606,28 @@ impl ConnectHandle {
     async fn transport_connect(
         &self,
         transport_config: ConnectionTransportConfig,
-    ) -> io::Result<(Reader<BytesPool>, Writer<BytesPool>)> {
+    ) -> io::Result<(Reader<BytesPool>, Writer<BytesPool>, Option<TlsInfo>)> {
         let ConnectionTransportConfig {
             transport_type,
             timeout,
             proxy,
-            tcp_nodelay,
+            socket_options,
         } = transport_config;
         Ok(match transport_type {
             ConnectionTransportType::Tcp { hostname, port } => {
-                maybe_timeout(
+                let (reader, writer) = maybe_timeout(
                     timeout,
                     crate::azure_mqtt::io::tokio_tcp::connect(
                         &hostname,
                         port,
                         proxy,
-                        tcp_nodelay,
+                        socket_options,
                         &self.reader_pool,
                         &self.writer_pool,
                     ),
                 )
-                .await??
+                .await??;
+                (reader, writer, None)
             }
 
             ConnectionTransportType::Tls {
@@ -567,19 +635,20 @@ impl ConnectHandle {
                 port,
                 tls_config,
             } => {
-                maybe_timeout(
+                let (reader, writer, tls_info) = maybe_timeout(
                     timeout,
                     crate::azure_mqtt::io::tokio_tls::connect(
                         &hostname,
                         port,
                         tls_config,
                         proxy,
-                        tcp_nodelay,
+                        socket_options,
                         &self.reader_pool,
                         &self.writer_pool,
                     ),
                 )
-                .await??
+                .await??;
+                (reader, writer, Some(tls_info))
             }
 
             #[cfg(feature = "test-utils")]
@@ -587,23 +656,27 @@ impl ConnectHandle {
                 request,
                 tls_config,
             } => {
-                maybe_timeout(
+                let (reader, writer) = maybe_timeout(
                     timeout,
-                    crate::azure_mqtt::io::tokio_ws::connect(request, tls_config, proxy, tcp_nodelay, &self.reader_pool),
+                    crate::azure_mqtt::io::tokio_ws::connect(request, tls_config, proxy, socket_options, &self.reader_pool),
                 )
-                .await??
+                .await??;
+                (reader, writer, None)
             }
 
             #[cfg(feature = "test-utils")]
             ConnectionTransportType::Test {
                 incoming_packets,
                 outgoing_packets,
-            } => crate::azure_mqtt::io::test::connect(
-                incoming_packets,
-                outgoing_packets,
-                &self.reader_pool,
-                &self.writer_pool,
-            ),
+            } => {
+                let (reader, writer) = crate::azure_mqtt::io::test::connect(
+                    incoming_packets,
+                    outgoing_packets,
+                    &self.reader_pool,
+                    &self.writer_pool,
+                );
+                (reader, writer, None)
+            }
         })
     }
 
@@ -655,6 +728,7 @@ pub struct EnhancedAuthHandle {
     auth_method: String,
     cfg_client_id: Option<String>,
     cfg_keep_alive: KeepAliveConfig,
+    tls_info: Option<TlsInfo>,
 }
 
 impl EnhancedAuthHandle {
@@ -745,6 +819,7 @@ impl EnhancedAuthHandle {
                         writer: self.writer,
                         cfg_client_id: self.cfg_client_id,
                         cfg_pingresp_timeout,
+                        tls_info: self.tls_info,
                     };
                     ConnectEnhancedAuthResult::Success(
                         connection,
@@ -796,9 +871,15 @@ pub struct Connection {
     writer: Writer<BytesPool>,
     cfg_client_id: Option<String>,
     cfg_pingresp_timeout: Option<Duration>,
+    tls_info: Option<TlsInfo>,
 }
 
 impl Connection {
+    /// Details of the TLS session negotiated for this connection, if the transport uses TLS.
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_ref()
+    }
+
     /// Drives this connection until it is disconnected.
     /// Packets will only be sent and received while this future is running.
     pub async fn run_until_disconnect(mut self) -> (ConnectHandle, DisconnectedEvent) {
@@ -2,7 +2,9 @@
 // Licensed under the MIT License.
 
 use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU16;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::Poll;
 
 use derive_where::derive_where;
@@ -15,6 +17,7 @@ use tokio::time::Duration;
 use crate::azure_mqtt::buffer_pool::{Owned, Shared};
 use crate::azure_mqtt::client::{
     InnerConnectionError,
+    PublishCounters,
     buffered::ReauthResult,
     channel_data::{
         AcknowledgementRequest, DisconnectRequest, IncomingPublishAndToken, PublishRequestQoS0,
@@ -34,9 +37,10 @@ use crate::azure_mqtt::error::{ProtocolError, ProtocolErrorRepr};
 use crate::azure_mqtt::mqtt_proto::{
     Auth, AuthenticateReasonCode, ByteStr, ConnAck, ConnectReasonCode, Disconnect, KeepAlive,
     Packet, PacketIdentifier, PacketIdentifierDupQoS, PingReq, PubAck, PubComp, PubRec, PubRel,
-    Publish, PublishOtherProperties, SessionExpiryInterval, SubAck, Subscribe, SubscribeTo, Topic,
-    UnsubAck, Unsubscribe,
+    Publish, PublishOtherProperties, QoS, SessionExpiryInterval, SubAck, Subscribe, SubscribeTo,
+    Topic, UnsubAck, Unsubscribe,
 };
+use crate::azure_mqtt::packet::{self, SubAckReason};
 
 mod pkid;
 
@@ -64,6 +68,10 @@ where
     transient: bool,
     /// Timer for tracking when to send the next PINGREQ (based on keep-alive)
     pingreq_timer: Option<Timer>,
+    /// Counters for outgoing PUBLISH flow control
+    publish_counters: Arc<PublishCounters>,
+    /// Topic aliases assigned to outgoing PUBLISHes on the current connection, if enabled
+    outgoing_topic_aliases: Option<OutgoingTopicAliases>,
     pub(crate) owned: O, // NOTE: This really shouldn't be pub(crate)
 }
 
@@ -82,6 +90,8 @@ where
         ack_tx: Sender<AcknowledgementRequest<O::Shared>>,
         auth_tx: Sender<ReauthRequest<O::Shared>>,
         max_pkid: PacketIdentifier,
+        publish_counters: Arc<PublishCounters>,
+        outgoing_topic_aliases: bool,
         owned: O,
     ) -> Self {
         let ch = Channels {
@@ -105,10 +115,22 @@ where
             connection_epoch: 0, // move this to the connection state?
             transient: false,    // move this to the connection state?
             pingreq_timer: None,
+            publish_counters,
+            outgoing_topic_aliases: outgoing_topic_aliases.then(OutgoingTopicAliases::default),
             owned,
         }
     }
 
+    /// Update the in-flight PUBLISH counter from the in-flight tracker.
+    /// QoS 2 PUBLISHes remain in-flight until the PUBCOMP is received.
+    fn update_publish_counters(&self) {
+        self.publish_counters.set_inflight(
+            self.inflight.publish_qos1.len()
+                + self.inflight.publish_qos2.len()
+                + self.inflight.pubrel.len(),
+        );
+    }
+
     pub fn is_connected(&self) -> bool {
         matches!(self.connected, ConnectionState::Connected { .. })
     }
@@ -195,7 +217,9 @@ where
                             options,
                             other_properties,
                         ) => {
-                            self.inflight.subscribe.insert(packet_identifier, notifier);
+                            self.inflight
+                                .subscribe
+                                .insert(packet_identifier, (notifier, options.maximum_qos));
                             Packet::Subscribe(Subscribe {
                                 packet_identifier,
                                 subscribe_to: vec![SubscribeTo {
@@ -292,7 +316,9 @@ where
                             publish
                         }
                     };
-                    Packet::Publish(packet)
+                    // NOTE: The in-flight copy keeps the full topic, since topic aliases do not
+                    // carry over to the connection on which it might be replayed.
+                    Packet::Publish(self.apply_topic_alias(packet))
                 }
 
                 OutgoingPacketRequest::ReauthRequest(auth_req) => {
@@ -309,10 +335,40 @@ where
         if let Some(pingreq_timer) = self.pingreq_timer.as_mut() {
             pingreq_timer.reset();
         }
+        self.update_publish_counters();
 
         packet
     }
 
+    /// Refers to the topic of an outgoing PUBLISH by topic alias, if enabled.
+    ///
+    /// The first PUBLISH to a topic on a connection carries both the topic and a newly assigned
+    /// alias, and subsequent PUBLISHes to the topic carry only the alias. Once the server's topic
+    /// alias maximum is exhausted, PUBLISHes to topics without an alias carry only the topic.
+    /// PUBLISHes that already specify a topic alias are left unchanged.
+    fn apply_topic_alias(&mut self, mut publish: Publish<O::Shared>) -> Publish<O::Shared> {
+        let Some(outgoing_topic_aliases) = self.outgoing_topic_aliases.as_mut() else {
+            return publish;
+        };
+        if publish.other_properties.topic_alias.is_some() {
+            return publish;
+        }
+        let Some((topic_alias, newly_assigned)) =
+            outgoing_topic_aliases.alias(publish.topic_name.as_str())
+        else {
+            return publish;
+        };
+        if !newly_assigned {
+            match ByteStr::new(&mut self.owned, "") {
+                Ok(empty) => publish.topic_name = Topic::alias_only(empty),
+                // The topic alone is still a valid PUBLISH
+                Err(_) => return publish,
+            }
+        }
+        publish.other_properties.topic_alias = Some(topic_alias);
+        publish
+    }
+
     /// Returns the next outgoing MQTT packet request to be sent over the network
     async fn next_outgoing_request(&mut self) -> OutgoingPacketRequest<O::Shared> {
         // NOTE: A loop is used here because not all outgoing requests result in a packet being sent
@@ -386,10 +442,23 @@ where
         match operation {
             CompletedOperation::Subscribe(suback) => {
                 self.pkid_pool.release_pkid(suback.packet_identifier);
-                let Some(notifier) = self.inflight.subscribe.remove(&suback.packet_identifier)
+                let Some((notifier, requested_qos)) =
+                    self.inflight.subscribe.remove(&suback.packet_identifier)
                 else {
                     return Err(ProtocolErrorRepr::UnexpectedPacket)?;
                 };
+                // A downgraded QoS weakens the delivery guarantees of the subscription
+                let requested_qos = packet::QoS::from(requested_qos);
+                for reason_code in &suback.reason_codes {
+                    if let Some(granted_qos) = SubAckReason::from(*reason_code).granted_qos()
+                        && (granted_qos as u8) < (requested_qos as u8)
+                    {
+                        log::warn!(
+                            "SUBSCRIBE (pkid {}) requested {requested_qos:?} but server granted {granted_qos:?}",
+                            suback.packet_identifier
+                        );
+                    }
+                }
                 _ = notifier.complete(suback);
             }
             CompletedOperation::Unsubscribe(unsuback) => {
@@ -457,6 +526,7 @@ where
                 _ = notifier.complete(pubcomp);
             }
         }
+        self.update_publish_counters();
         Ok(())
     }
 
@@ -495,6 +565,11 @@ where
                 }
             }
 
+            // Topic aliases only last for the connection they were assigned on
+            if let Some(outgoing_topic_aliases) = self.outgoing_topic_aliases.as_mut() {
+                outgoing_topic_aliases.reset(connack.other_properties.topic_alias_maximum);
+            }
+
             self.connected = ConnectionState::Connected { connack };
         }
     }
@@ -621,7 +696,7 @@ where
         self.connected = ConnectionState::Disconnected;
         self.pingreq_timer = None;
         // Remove and cancel all in-flight SUBSCRIBEs
-        for (pkid, notifier) in self.inflight.subscribe.drain() {
+        for (pkid, (notifier, _)) in self.inflight.subscribe.drain() {
             let _ = notifier.cancel("Client disconnected");
             self.pkid_pool.release_pkid(pkid);
         }
@@ -693,6 +768,7 @@ where
             let _ = notifier.cancel("MQTT session expired");
         }
         self.inflight.packets_to_replay.clear();
+        self.update_publish_counters();
 
         // NOTE: No need to clear subscribe/unsubscribe/auth here because those are cleared on
         // any disconnect. So any session expiry that happens, either due to disconnect or on the
@@ -916,8 +992,8 @@ where
     publish_qos1: IndexMap<PacketIdentifier, (Publish<S>, PublishQoS1CompletionNotifier<S>)>,
     /// All inflight QoS 2 PUBLISH operations
     publish_qos2: IndexMap<PacketIdentifier, (Publish<S>, PublishQoS2CompletionNotifier<S>)>,
-    /// All inflight SUBSCRIBE operations
-    subscribe: HashMap<PacketIdentifier, SubscribeCompletionNotifier<S>>,
+    /// All inflight SUBSCRIBE operations, along with their requested QoS
+    subscribe: HashMap<PacketIdentifier, (SubscribeCompletionNotifier<S>, QoS)>,
     /// All inflight UNSUBSCRIBE operations
     unsubscribe: HashMap<PacketIdentifier, UnsubscribeCompletionNotifier<S>>,
 
@@ -936,6 +1012,37 @@ where
     auth: Option<ReauthCompletionNotifier<S>>,
 }
 
+/// Topic aliases assigned to the topics of outgoing PUBLISHes on the current connection
+#[derive(Default)]
+struct OutgoingTopicAliases {
+    /// Highest topic alias accepted by the server, from its CONNACK
+    maximum: u16,
+    /// Topic aliases by topic, assigned from 1 upwards
+    aliases: HashMap<String, NonZeroU16>,
+}
+
+impl OutgoingTopicAliases {
+    /// Forgets all assigned aliases at the start of a new connection
+    fn reset(&mut self, maximum: u16) {
+        self.maximum = maximum;
+        self.aliases.clear();
+    }
+
+    /// Returns the alias of `topic` and whether it was newly assigned, or `None` if it has no
+    /// alias and all aliases are in use
+    fn alias(&mut self, topic: &str) -> Option<(NonZeroU16, bool)> {
+        if let Some(alias) = self.aliases.get(topic) {
+            return Some((*alias, false));
+        }
+        let alias = u16::try_from(self.aliases.len() + 1)
+            .ok()
+            .filter(|alias| *alias <= self.maximum)
+            .and_then(NonZeroU16::new)?;
+        self.aliases.insert(topic.to_string(), alias);
+        Some((alias, true))
+    }
+}
+
 #[derive_where(Default)]
 struct InApplicationTracker<S>
 where
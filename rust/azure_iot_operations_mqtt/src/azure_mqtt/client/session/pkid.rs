@@ -70,7 +70,7 @@ impl PkidPool {
     /// Attempts to lease the next available Packet Identifier.
     /// Returns `Some(PacketIdentifier)` if successful, or `None` if all identifiers are in use.
     pub fn lease_next_pkid(&mut self) -> Option<PacketIdentifier> {
-        if self.leased.len() == self.max_pkid.get().into() {
+        if self.leased.len() == usize::from(self.max_pkid.get()) {
             return None; // All leased
         }
         // NOTE: Infinite loop is safe here as we are guaranteed to find a free pkid because of
@@ -42,3 +42,35 @@ impl Future for Timer {
         self.inner.as_mut().poll(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn fires_at_duration() {
+        let start = tokio::time::Instant::now();
+        let timer = Timer::new(Duration::from_secs(30));
+        assert_eq!(timer.deadline(), start + Duration::from_secs(30));
+        assert_eq!(timer.remaining_duration(), Duration::from_secs(30));
+
+        timer.await;
+        assert_eq!(tokio::time::Instant::now(), start + Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_postpones_deadline() {
+        let start = tokio::time::Instant::now();
+        let mut timer = Timer::new(Duration::from_secs(30));
+
+        tokio::time::advance(Duration::from_secs(20)).await;
+        assert_eq!(timer.remaining_duration(), Duration::from_secs(10));
+
+        // e.g. a packet was sent, so the next PINGREQ is due a full keep-alive interval later
+        timer.reset();
+        assert_eq!(timer.remaining_duration(), Duration::from_secs(30));
+
+        timer.await;
+        assert_eq!(tokio::time::Instant::now(), start + Duration::from_secs(50));
+    }
+}
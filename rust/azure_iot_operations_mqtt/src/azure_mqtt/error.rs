@@ -47,6 +47,8 @@ pub enum ConnectError {
     Rejected(crate::azure_mqtt::packet::ConnAck),
     #[error("timed out waiting for response packet")]
     ResponseTimeout,
+    #[error("could not obtain credentials: {0}")]
+    Credentials(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
 /// Indicates a protocol violation of the MQTT specification
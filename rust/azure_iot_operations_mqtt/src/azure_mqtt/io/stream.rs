@@ -11,17 +11,20 @@
 
 use std::{
     io::{self, IoSlice},
+    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
+use futures_util::stream::{FuturesUnordered, StreamExt as _};
 use tokio::{
     io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf},
     net::TcpStream,
 };
 use tokio_openssl::SslStream;
 
-use crate::azure_mqtt::transport::{Proxy, ProxyAuthorization, ProxyEndpoint, TlsConfig};
+use crate::azure_mqtt::transport::{Proxy, ProxyAuthorization, ProxyEndpoint, SocketOptions, TlsConfig};
 
 /// An established base transport byte stream.
 ///
@@ -44,19 +47,19 @@ enum TransportStreamInner {
 /// returning the stream. For an [`ProxyEndpoint::Https`] proxy, the connection to the proxy
 /// itself is wrapped in TLS; the connection to the target is not (see [`connect_tls`]).
 ///
-/// `tcp_nodelay` sets the `TCP_NODELAY` option (Nagle's algorithm) on the underlying TCP socket.
+/// `socket_options` are applied to the underlying TCP connection (to the target, or to the proxy).
 pub(crate) async fn connect(
     hostname: &str,
     port: u16,
     proxy: Option<Proxy>,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
 ) -> io::Result<TransportStream> {
     match proxy {
         None => {
-            let stream = tcp_connect(hostname, port, tcp_nodelay).await?;
+            let stream = tcp_connect(hostname, port, socket_options).await?;
             Ok(TransportStream(TransportStreamInner::Plain(stream)))
         }
-        Some(proxy) => http_connect_tunnel(proxy, hostname, port, tcp_nodelay).await,
+        Some(proxy) => http_connect_tunnel(proxy, hostname, port, socket_options).await,
     }
 }
 
@@ -67,26 +70,97 @@ pub(crate) async fn connect(
 /// The TLS session established here is with the target. For an [`ProxyEndpoint::Https`] proxy, the
 /// connection to the proxy itself is wrapped in a separate TLS session inside [`connect`].
 ///
-/// `tcp_nodelay` sets the `TCP_NODELAY` option (Nagle's algorithm) on the underlying TCP socket.
+/// `socket_options` are applied to the underlying TCP connection (to the target, or to the proxy).
 pub(crate) async fn connect_tls(
     hostname: &str,
     port: u16,
     config: TlsConfig,
     proxy: Option<Proxy>,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
 ) -> io::Result<SslStream<TransportStream>> {
-    let stream = connect(hostname, port, proxy, tcp_nodelay).await?;
+    let stream = connect(hostname, port, proxy, socket_options).await?;
     tls_handshake(stream, config, hostname).await
 }
 
 /// Connect a [`TcpStream`] to the given host and port, applying the `TCP_NODELAY` option
 /// (Nagle's algorithm) to the socket.
-async fn tcp_connect(host: &str, port: u16, tcp_nodelay: bool) -> io::Result<TcpStream> {
-    let stream = TcpStream::connect((host, port)).await?;
-    stream.set_nodelay(tcp_nodelay)?;
+///
+/// If [`SocketOptions::connection_attempt_delay`] is set, connection attempts to the resolved
+/// addresses are raced (see [`race_connect`]). Otherwise they are attempted one at a time.
+async fn tcp_connect(host: &str, port: u16, socket_options: SocketOptions) -> io::Result<TcpStream> {
+    let stream = match socket_options.connection_attempt_delay {
+        Some(attempt_delay) => {
+            let addrs = interleave_address_families(tokio::net::lookup_host((host, port)).await?.collect());
+            race_connect(addrs, attempt_delay, TcpStream::connect).await?
+        }
+        None => TcpStream::connect((host, port)).await?,
+    };
+    stream.set_nodelay(socket_options.tcp_nodelay)?;
     Ok(stream)
 }
 
+/// Order resolved addresses so that address families alternate, starting with the family of the
+/// first address (RFC 8305, section 4). The relative order of addresses within a family is kept.
+fn interleave_address_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_ipv6 = first.is_ipv6();
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    let mut other = other.into_iter();
+    for addr in preferred {
+        interleaved.push(addr);
+        interleaved.extend(other.next());
+    }
+    interleaved.extend(other);
+    interleaved
+}
+
+/// Race connection attempts to the given addresses, in order, returning the first connection to
+/// succeed (RFC 8305, section 5).
+///
+/// An attempt to the next address is started as soon as the previous attempt fails, or once
+/// `attempt_delay` has elapsed without any attempt completing. Attempts that are still in progress
+/// when one succeeds are dropped. If all attempts fail, the error of the last one to fail is returned.
+async fn race_connect<S, F, Fut>(addrs: Vec<SocketAddr>, attempt_delay: Duration, mut connect: F) -> io::Result<S>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+{
+    let mut addrs = addrs.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_err = None;
+
+    loop {
+        // Start an attempt to the next address, either initially, after the previous attempt
+        // failed, or after the attempt delay elapsed
+        if let Some(addr) = addrs.next() {
+            log::debug!("Attempting TCP connection to {addr}");
+            let attempt = connect(addr);
+            attempts.push(async move { (addr, attempt.await) });
+        }
+
+        if attempts.is_empty() {
+            return Err(last_err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "hostname did not resolve to any address")
+            }));
+        }
+
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    log::debug!("TCP connection to {addr} failed: {e}");
+                    last_err = Some(e);
+                }
+            },
+            () = tokio::time::sleep(attempt_delay) => {}
+        }
+    }
+}
+
 /// Establish an HTTP CONNECT tunnel through the given proxy to the target host and port.
 ///
 /// Connects to the proxy endpoint (wrapping the connection in TLS for an
@@ -96,12 +170,12 @@ async fn http_connect_tunnel(
     proxy: Proxy,
     target_host: &str,
     target_port: u16,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
 ) -> io::Result<TransportStream> {
     let Proxy { endpoint, auth } = proxy;
     match endpoint {
         ProxyEndpoint::Http { hostname, port } => {
-            let stream = tcp_connect(&hostname, port, tcp_nodelay).await?;
+            let stream = tcp_connect(&hostname, port, socket_options).await?;
             let stream = http_connect_exchange(stream, target_host, target_port, &auth).await?;
             Ok(TransportStream(TransportStreamInner::Plain(stream)))
         }
@@ -110,7 +184,7 @@ async fn http_connect_tunnel(
             port,
             tls_config,
         } => {
-            let stream = tcp_connect(&hostname, port, tcp_nodelay).await?;
+            let stream = tcp_connect(&hostname, port, socket_options).await?;
             // Wrap the connection to the proxy itself in TLS before tunneling.
             let stream = tls_handshake(stream, tls_config, &hostname).await?;
             let stream = http_connect_exchange(stream, target_host, target_port, &auth).await?;
@@ -196,7 +270,8 @@ where
 
 /// Wrap an established stream in a client-side TLS session, returning the encrypted stream.
 ///
-/// The hostname is used for SNI and to match against the server cert SAN.
+/// The hostname is used for SNI and to match against the server cert SAN, unless the
+/// [`TlsConfig`] has a server name set.
 pub(crate) async fn tls_handshake<S>(
     stream: S,
     config: TlsConfig,
@@ -205,10 +280,10 @@ pub(crate) async fn tls_handshake<S>(
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let TlsConfig(connector) = config;
+    let TlsConfig { connector, server_name } = config;
     let connector = connector.build().configure()?;
 
-    let ssl = connector.into_ssl(hostname)?;
+    let ssl = connector.into_ssl(server_name.as_deref().unwrap_or(hostname))?;
     let mut ssl_stream = SslStream::new(ssl, stream)?;
 
     Pin::new(&mut ssl_stream)
@@ -283,3 +358,230 @@ impl AsyncWrite for TransportStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use openssl::{
+        pkey::{PKey, Private},
+        sha::sha256,
+        ssl::{Ssl, SslAcceptor, SslMethod},
+        x509::X509,
+    };
+    use test_case::test_case;
+    use tokio_openssl::SslStream;
+
+    use super::{connect_tls, interleave_address_families, race_connect};
+    use crate::azure_mqtt::transport::{SocketOptions, TlsConfig, tests::self_signed_cert};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interleave_address_families_alternates() {
+        let addrs = vec![
+            addr("[2001:db8::1]:8883"),
+            addr("[2001:db8::2]:8883"),
+            addr("[2001:db8::3]:8883"),
+            addr("192.0.2.1:8883"),
+            addr("192.0.2.2:8883"),
+        ];
+        assert_eq!(
+            interleave_address_families(addrs),
+            vec![
+                addr("[2001:db8::1]:8883"),
+                addr("192.0.2.1:8883"),
+                addr("[2001:db8::2]:8883"),
+                addr("192.0.2.2:8883"),
+                addr("[2001:db8::3]:8883"),
+            ]
+        );
+
+        let addrs = vec![
+            addr("192.0.2.1:8883"),
+            addr("192.0.2.2:8883"),
+            addr("192.0.2.3:8883"),
+            addr("[2001:db8::1]:8883"),
+        ];
+        assert_eq!(
+            interleave_address_families(addrs),
+            vec![
+                addr("192.0.2.1:8883"),
+                addr("[2001:db8::1]:8883"),
+                addr("192.0.2.2:8883"),
+                addr("192.0.2.3:8883"),
+            ]
+        );
+
+        assert!(interleave_address_families(vec![]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn race_connect_unresponsive_address_falls_back_after_delay() {
+        let attempted = Arc::new(Mutex::new(vec![]));
+        let result = race_connect(
+            vec![addr("[2001:db8::1]:8883"), addr("192.0.2.1:8883")],
+            Duration::from_millis(50),
+            |a| {
+                attempted.lock().unwrap().push(a);
+                async move {
+                    if a.is_ipv6() {
+                        // Unreachable address that never completes
+                        std::future::pending::<()>().await;
+                    }
+                    Ok(a)
+                }
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), addr("192.0.2.1:8883"));
+        assert_eq!(attempted.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn race_connect_failed_address_falls_back_immediately() {
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            race_connect(
+                vec![addr("[2001:db8::1]:8883"), addr("192.0.2.1:8883")],
+                Duration::from_secs(60),
+                |a| async move {
+                    if a.is_ipv6() {
+                        Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+                    } else {
+                        Ok(a)
+                    }
+                },
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.unwrap(), addr("192.0.2.1:8883"));
+    }
+
+    #[tokio::test]
+    async fn race_connect_first_success_wins() {
+        let attempted = Arc::new(Mutex::new(vec![]));
+        let result = race_connect(
+            vec![addr("[2001:db8::1]:8883"), addr("192.0.2.1:8883")],
+            Duration::from_millis(50),
+            |a| {
+                attempted.lock().unwrap().push(a);
+                async move { Ok(a) }
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), addr("[2001:db8::1]:8883"));
+        // The second address is never attempted
+        assert_eq!(attempted.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn race_connect_all_fail() {
+        let result = race_connect(
+            vec![addr("[2001:db8::1]:8883"), addr("192.0.2.1:8883")],
+            Duration::from_millis(50),
+            |a| async move {
+                if a.is_ipv6() {
+                    Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionRefused))
+                } else {
+                    Err(io::Error::from(io::ErrorKind::TimedOut))
+                }
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        let result = race_connect(vec![], Duration::from_millis(50), |_| async { Ok(()) }).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn race_connect_real_listener() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+        let stream = race_connect(
+            vec![listener_addr],
+            Duration::from_millis(250),
+            tokio::net::TcpStream::connect,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener_addr);
+    }
+
+    /// Start a TLS server on a local port presenting `cert`, accepting a single connection
+    async fn local_tls_server(cert: X509, pkey: PKey<Private>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&pkey).unwrap();
+        let acceptor = acceptor.build();
+        tokio::task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let ssl = Ssl::new(acceptor.context()).unwrap();
+            let mut stream = SslStream::new(ssl, stream).unwrap();
+            // The handshake fails if the client rejects the cert
+            if std::pin::Pin::new(&mut stream).accept().await.is_ok() {
+                // Keep the connection open until the client closes it
+                let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut [0; 1]).await;
+            }
+        });
+        port
+    }
+
+    #[test_case(Some("broker"), true; "server name matches cert")]
+    #[test_case(None, false; "ip address does not match cert")]
+    #[tokio::test]
+    async fn tls_server_name(server_name: Option<&str>, expected_ok: bool) {
+        let (cert, pkey) = self_signed_cert();
+        let mut tls_config = TlsConfig::new(None, vec![cert.clone()]).unwrap();
+        if let Some(server_name) = server_name {
+            tls_config.set_server_name(server_name.to_string());
+        }
+        let port = local_tls_server(cert, pkey).await;
+
+        let result = connect_tls("127.0.0.1", port, tls_config, None, SocketOptions::default()).await;
+        assert_eq!(result.is_ok(), expected_ok);
+    }
+
+    #[derive(Clone, Copy)]
+    enum Pin {
+        Cert,
+        Spki,
+        Other,
+    }
+
+    #[test_case(Pin::Cert, true, true, true; "cert pin with chain")]
+    #[test_case(Pin::Spki, true, true, true; "spki pin with chain")]
+    #[test_case(Pin::Other, true, true, false; "pin mismatch with chain")]
+    #[test_case(Pin::Cert, false, true, false; "cert pin with untrusted chain")]
+    #[test_case(Pin::Spki, false, false, true; "spki pin only")]
+    #[test_case(Pin::Cert, false, false, true; "cert pin only")]
+    #[test_case(Pin::Other, false, false, false; "pin mismatch only")]
+    #[tokio::test]
+    async fn pinned_server_cert(pin: Pin, trusted: bool, validate_chain: bool, expected_ok: bool) {
+        let (cert, pkey) = self_signed_cert();
+        let pin = match pin {
+            Pin::Cert => sha256(&cert.to_der().unwrap()),
+            Pin::Spki => sha256(&cert.public_key().unwrap().public_key_to_der().unwrap()),
+            Pin::Other => sha256(b"other cert"),
+        };
+        let ca_trust_bundle = if trusted { vec![cert.clone()] } else { vec![] };
+        let mut tls_config = TlsConfig::new(None, ca_trust_bundle).unwrap();
+        tls_config.set_server_name("broker".to_string());
+        tls_config.set_pinned_server_certs(vec![[0; 32], pin], validate_chain);
+        let port = local_tls_server(cert, pkey).await;
+
+        let result = connect_tls("127.0.0.1", port, tls_config, None, SocketOptions::default()).await;
+        assert_eq!(result.is_ok(), expected_ok);
+    }
+}
@@ -10,7 +10,7 @@ use std::{
 use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 
 use crate::azure_mqtt::buffer_pool::{BufferPool, EitherAccumulator};
-use crate::azure_mqtt::transport::Proxy;
+use crate::azure_mqtt::transport::{Proxy, SocketOptions};
 
 use crate::azure_mqtt::io::stream::TransportStream;
 use crate::azure_mqtt::io::{ReadableStream, Reader, WritableStream, Writer};
@@ -22,14 +22,14 @@ pub async fn connect<BP>(
     hostname: &str,
     port: u16,
     proxy: Option<Proxy>,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
     reader_pool: &BP,
     writer_pool: &BP,
 ) -> io::Result<(Reader<BP>, Writer<BP>)>
 where
     BP: BufferPool,
 {
-    let stream = super::stream::connect(hostname, port, proxy, tcp_nodelay).await?;
+    let stream = super::stream::connect(hostname, port, proxy, socket_options).await?;
 
     let (read, write) = tokio::io::split(stream);
     let read_buf = reader_pool.take_empty_owned();
@@ -11,7 +11,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 use tokio_openssl::SslStream;
 
 use crate::azure_mqtt::buffer_pool::{BufferPool, EitherAccumulator};
-use crate::azure_mqtt::transport::{Proxy, TlsConfig};
+use crate::azure_mqtt::transport::{Proxy, SocketOptions, TlsConfig, TlsInfo};
 use crate::azure_mqtt::io::stream::TransportStream;
 use crate::azure_mqtt::io::{ReadableStream, Reader, WritableStream, Writer};
 
@@ -19,19 +19,22 @@ use crate::azure_mqtt::io::{ReadableStream, Reader, WritableStream, Writer};
 /// to initialize the buffers for the stream reader and writer.
 ///
 /// The hostname will be matched against the server cert SAN.
+///
+/// Also returns the details of the negotiated TLS session.
 pub async fn connect<BP>(
     hostname: &str,
     port: u16,
     config: TlsConfig,
     proxy: Option<Proxy>,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
     reader_pool: &BP,
     _writer_pool: &BP, // Historically was used with kTLS, currently unused, may be needed again in the future, so retained
-) -> io::Result<(Reader<BP>, Writer<BP>)>
+) -> io::Result<(Reader<BP>, Writer<BP>, TlsInfo)>
 where
     BP: BufferPool,
 {
-    let ssl_stream = super::stream::connect_tls(hostname, port, config, proxy, tcp_nodelay).await?;
+    let ssl_stream = super::stream::connect_tls(hostname, port, config, proxy, socket_options).await?;
+    let tls_info = TlsInfo::from_ssl(ssl_stream.ssl());
 
     let (read, write) = tokio::io::split(ssl_stream);
     let read_buf = reader_pool.take_empty_owned();
@@ -40,6 +43,7 @@ where
     Ok((
         Reader::new(Box::new(OpensslStreamRead { inner: read }), read_buf),
         Writer::new(Box::new(OpensslStreamWrite { inner: write }), write_buf),
+        tls_info,
     ))
 }
 
@@ -18,7 +18,7 @@ use futures_util::{Sink, Stream};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 
 use crate::azure_mqtt::buffer_pool::{BufferPool, EitherAccumulator};
-use crate::azure_mqtt::transport::{Proxy, TlsConfig};
+use crate::azure_mqtt::transport::{Proxy, SocketOptions, TlsConfig};
 use crate::azure_mqtt::io::{ReadableStream, Reader, WritableStream, Writer};
 
 /// Establish a WebSocket connection using the given request parameters,
@@ -27,7 +27,7 @@ pub async fn connect<BP>(
     request: impl IntoClientRequest,
     tls_config: Option<TlsConfig>,
     proxy: Option<Proxy>,
-    tcp_nodelay: bool,
+    socket_options: SocketOptions,
     reader_pool: &BP,
 ) -> io::Result<(Reader<BP>, Writer<BP>)>
 where
@@ -73,11 +73,11 @@ where
 
     let stream = if let Some(tls_config) = tls_config {
         Either::Right(
-            super::stream::connect_tls(addr, port.unwrap_or(443), tls_config, proxy, tcp_nodelay)
+            super::stream::connect_tls(addr, port.unwrap_or(443), tls_config, proxy, socket_options)
                 .await?,
         )
     } else {
-        let stream = super::stream::connect(addr, port.unwrap_or(80), proxy, tcp_nodelay).await?;
+        let stream = super::stream::connect(addr, port.unwrap_or(80), proxy, socket_options).await?;
         Either::Left(stream)
     };
 
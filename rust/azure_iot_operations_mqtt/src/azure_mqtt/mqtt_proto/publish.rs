@@ -90,7 +90,9 @@ where
         let dup = (flags & 0b0000_1000) != 0;
         let retain = (flags & 0b0000_0001) != 0;
 
-        let topic_name = Topic::decode(src)?.ok_or(DecodeError::IncompletePacket)?;
+        // An empty topic name is only valid when the topic is given by a topic alias, which is
+        // not known until the properties have been decoded.
+        let topic_name = ByteStr::decode(src)?.ok_or(DecodeError::IncompletePacket)?;
 
         let packet_identifier_dup_qos = match (flags & 0b0000_0110) >> 1 {
             0x00 if dup => return Err(DecodeError::PublishDupAtMostOnce),
@@ -112,6 +114,7 @@ where
 
         match version {
             ProtocolVersion::V3 => {
+                let topic_name = Topic::new(topic_name)?;
                 let payload = src.split_to(src.len());
 
                 Ok(Self {
@@ -136,6 +139,11 @@ where
                     content_type: ContentType,
                 );
 
+                let topic_name = if topic_name.is_empty() && topic_alias.is_some() {
+                    Topic::alias_only(topic_name)
+                } else {
+                    Topic::new(topic_name)?
+                };
                 let payload = src.split_to(src.len());
 
                 Ok(Self {
@@ -327,6 +335,17 @@ mod tests {
                 content_type: Some("stuff".into()),
             },
         }),
+
+        Packet::Publish(Publish {
+            packet_identifier_dup_qos: PacketIdentifierDupQoS::AtLeastOnce(PacketIdentifier::new(1).unwrap(), false),
+            retain: false,
+            topic_name: Topic::alias_only("".into()),
+            payload: Bytes::from_static(b"hello world"),
+            other_properties: PublishOtherProperties {
+                topic_alias: Some(NonZeroU16::new(16).unwrap()),
+                ..Default::default()
+            },
+        }),
     }
 
     #[test]
@@ -42,6 +42,13 @@ where
         Ok(Self(inner))
     }
 
+    /// Constructs the empty topic name of a PUBLISH that refers to its topic by topic alias alone.
+    /// This is not a valid topic in any other context.
+    pub(crate) fn alias_only(inner: S) -> Self {
+        debug_assert!(inner.as_ref().is_empty());
+        Self(inner)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &str> + Clone {
         self.into_iter()
     }
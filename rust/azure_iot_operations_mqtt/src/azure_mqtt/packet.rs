@@ -275,6 +275,18 @@ pub struct Publish {
     pub properties: PublishProperties,
 }
 
+impl Publish {
+    /// Returns whether this PUBLISH is a redelivery of one that was sent before (DUP flag), e.g.
+    /// by the broker after a reconnect. Always `false` for QoS 0.
+    #[must_use]
+    pub fn dup(&self) -> bool {
+        match &self.qos {
+            DeliveryQoS::AtMostOnce => false,
+            DeliveryQoS::AtLeastOnce(info) | DeliveryQoS::ExactlyOnce(info) => info.dup,
+        }
+    }
+}
+
 impl<S> From<mqtt_proto::Publish<S>> for Publish
 where
     S: buffer_pool::Shared,
@@ -460,6 +472,38 @@ impl SubAck {
             Err(s.into())
         }
     }
+
+    /// Returns the QoS granted by the server for each topic filter of the SUBSCRIBE, in order,
+    /// or `None` for topic filters that the server did not grant a subscription for.
+    pub fn granted_qos(&self) -> Vec<Option<QoS>> {
+        self.reasons.iter().map(SubAckReason::granted_qos).collect()
+    }
+
+    /// Returns whether the server granted a lower QoS than `requested_qos` for any topic filter
+    /// of the SUBSCRIBE.
+    pub fn is_downgraded(&self, requested_qos: QoS) -> bool {
+        self.granted_qos()
+            .into_iter()
+            .flatten()
+            .any(|granted_qos| (granted_qos as u8) < (requested_qos as u8))
+    }
+
+    /// Like [`SubAck::as_result`], but additionally fails if the server granted a lower QoS than
+    /// `requested_qos` for any topic filter, as the guarantees of the requested QoS do not hold.
+    pub fn as_result_with_qos(&self, requested_qos: QoS) -> Result<(), OperationFailure> {
+        self.as_result()?;
+        if self.is_downgraded(requested_qos) {
+            let mut s = format!("Requested {requested_qos:?} but server granted");
+            for (i, granted_qos) in self.granted_qos().into_iter().flatten().enumerate() {
+                if i > 0 {
+                    s.push(',');
+                }
+                let _ = write!(s, " {granted_qos:?}");
+            }
+            return Err(s.into());
+        }
+        Ok(())
+    }
 }
 
 impl<S> From<mqtt_proto::SubAck<S>> for SubAck
@@ -1563,6 +1607,18 @@ pub enum SubAckReason {
     WildcardSubscriptionsNotSupported = 0xA2,
 }
 
+impl SubAckReason {
+    /// Returns the QoS granted by the server, or `None` if the subscription was not granted.
+    pub fn granted_qos(&self) -> Option<QoS> {
+        match self {
+            SubAckReason::GrantedQoS0 => Some(QoS::AtMostOnce),
+            SubAckReason::GrantedQoS1 => Some(QoS::AtLeastOnce),
+            SubAckReason::GrantedQoS2 => Some(QoS::ExactlyOnce),
+            _ => None,
+        }
+    }
+}
+
 impl From<mqtt_proto::SubscribeReasonCode> for SubAckReason {
     fn from(value: mqtt_proto::SubscribeReasonCode) -> SubAckReason {
         match value {
@@ -2006,6 +2062,65 @@ mod test {
         };
     }
 
+    #[test]
+    fn publish_dup() {
+        let mut publish = packet::Publish {
+            payload: Bytes::new(),
+            qos: packet::DeliveryQoS::AtMostOnce,
+            retain: false,
+            topic_name: topic::TopicName::new("topic/name").unwrap(),
+            properties: packet::PublishProperties::default(),
+        };
+        assert!(!publish.dup());
+        for dup in [false, true] {
+            let delivery_info = packet::DeliveryInfo {
+                dup,
+                packet_identifier: PacketIdentifier::new(1).unwrap(),
+            };
+            publish.qos = packet::DeliveryQoS::AtLeastOnce(delivery_info);
+            assert_eq!(publish.dup(), dup);
+            publish.qos = packet::DeliveryQoS::ExactlyOnce(delivery_info);
+            assert_eq!(publish.dup(), dup);
+        }
+    }
+
+    #[test]
+    fn suback_granted_qos() {
+        let suback = packet::SubAck {
+            packet_identifier: PacketIdentifier::new(1).unwrap(),
+            reasons: vec![
+                packet::SubAckReason::GrantedQoS0,
+                packet::SubAckReason::GrantedQoS1,
+                packet::SubAckReason::NotAuthorized,
+            ],
+            properties: packet::SubAckProperties::default(),
+        };
+        assert_eq!(
+            suback.granted_qos(),
+            vec![Some(packet::QoS::AtMostOnce), Some(packet::QoS::AtLeastOnce), None]
+        );
+        assert!(suback.is_downgraded(packet::QoS::AtLeastOnce));
+        assert!(!suback.is_downgraded(packet::QoS::AtMostOnce));
+    }
+
+    #[test]
+    fn suback_as_result_with_qos() {
+        let suback = |reason| packet::SubAck {
+            packet_identifier: PacketIdentifier::new(1).unwrap(),
+            reasons: vec![reason],
+            properties: packet::SubAckProperties::default(),
+        };
+        // Granted as requested or higher
+        assert!(suback(packet::SubAckReason::GrantedQoS1).as_result_with_qos(packet::QoS::AtLeastOnce).is_ok());
+        assert!(suback(packet::SubAckReason::GrantedQoS2).as_result_with_qos(packet::QoS::AtLeastOnce).is_ok());
+        // Downgraded
+        let downgraded = suback(packet::SubAckReason::GrantedQoS0);
+        assert!(downgraded.as_result().is_ok());
+        assert!(downgraded.as_result_with_qos(packet::QoS::AtLeastOnce).is_err());
+        // Not granted
+        assert!(suback(packet::SubAckReason::NotAuthorized).as_result_with_qos(packet::QoS::AtMostOnce).is_err());
+    }
+
     #[test]
     /// Validate that default values for property structures are the same on the public and internal types
     fn property_defaults() {
@@ -9,10 +9,14 @@ use std::{
 };
 
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use openssl::{
+    asn1::{Asn1Time, Asn1TimeRef},
+    nid::Nid,
     pkey::{PKey, Private},
-    ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslVersion},
-    x509::X509,
+    sha::sha256,
+    ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslRef, SslVerifyMode, SslVersion},
+    x509::{X509, X509NameRef, X509Ref},
 };
 
 use crate::azure_mqtt::mqtt_proto::Packet;
@@ -34,10 +38,32 @@ pub struct ConnectionTransportConfig {
     pub transport_type: ConnectionTransportType,
     pub timeout: Option<Duration>,
     pub proxy: Option<Proxy>,
+    pub socket_options: SocketOptions,
+}
+
+/// Options for establishing the underlying TCP connection.
+#[derive(Clone, Copy, Debug)]
+pub struct SocketOptions {
     /// Whether to disable Nagle's algorithm (`TCP_NODELAY`) on the underlying TCP socket.
     /// Setting this to `true` reduces latency for small, frequent packets at the cost of slightly
     /// more packet overhead.
-    pub tcp_nodelay: bool, // TODO: Make this a defaultable SocketOptions
+    pub tcp_nodelay: bool,
+    /// If `Some`, connection attempts to the addresses the hostname resolves to are raced
+    /// ("Happy Eyeballs", RFC 8305), alternating between IPv6 and IPv4 addresses. An attempt to the
+    /// next address is started when the previous attempt fails, or when it has not completed
+    /// within this delay. The first attempt to succeed is used.
+    ///
+    /// If `None`, the resolved addresses are attempted one at a time.
+    pub connection_attempt_delay: Option<Duration>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            tcp_nodelay: true,
+            connection_attempt_delay: Some(Duration::from_millis(250)),
+        }
+    }
 }
 
 /// The type of transport to use for the new MQTT connection.
@@ -93,7 +119,11 @@ pub enum ProxyAuthorization {
 }
 
 /// Parameters for establishing a TLS connection.
-pub struct TlsConfig(pub(crate) SslConnectorBuilder);
+pub struct TlsConfig {
+    pub(crate) connector: SslConnectorBuilder,
+    /// Name sent in SNI and matched against the server cert SAN, instead of the hostname connected to
+    pub(crate) server_name: Option<String>,
+}
 
 impl TlsConfig {
     /// Constructs a [`TlsConfig`] with the given client certificate and CA trust bundle.
@@ -123,7 +153,36 @@ impl TlsConfig {
             }
         }
 
-        Ok(Self(connector))
+        Ok(Self { connector, server_name: None })
+    }
+
+    /// Use `server_name` for SNI and to match against the server cert SAN, instead of the hostname
+    /// connected to, e.g. when the server is only reachable by IP address.
+    pub fn set_server_name(&mut self, server_name: String) {
+        self.server_name = Some(server_name);
+    }
+
+    /// Require the server cert to match one of the given pins, each the SHA-256 hash of either the
+    /// DER-encoded cert or its DER-encoded `SubjectPublicKeyInfo`.
+    ///
+    /// If `validate_chain` is `true`, the server cert must also pass the usual chain and name
+    /// validation. Otherwise, a server cert matching a pin is accepted without it.
+    pub fn set_pinned_server_certs(&mut self, pins: Vec<[u8; 32]>, validate_chain: bool) {
+        self.connector
+            .set_verify_callback(SslVerifyMode::PEER, move |preverify_ok, ctx| {
+                // Only the server's own cert (depth 0) is pinned, the rest of the chain is
+                // validated as usual, or not at all
+                if ctx.error_depth() != 0 {
+                    return preverify_ok || !validate_chain;
+                }
+                let pin_matched = ctx
+                    .current_cert()
+                    .is_some_and(|cert| server_cert_matches_pins(cert, &pins));
+                if !pin_matched {
+                    log::warn!("Server cert does not match any of the pinned certs");
+                }
+                pin_matched && (preverify_ok || !validate_chain)
+            });
     }
 
     /// Constructs a [`TlsConfig`] with the client certificate and CA trust bundle
@@ -159,6 +218,199 @@ impl TlsConfig {
 
 impl From<SslConnectorBuilder> for TlsConfig {
     fn from(connector: SslConnectorBuilder) -> Self {
-        Self(connector)
+        Self { connector, server_name: None }
+    }
+}
+
+/// Whether the SHA-256 hash of the DER-encoded cert or of its DER-encoded `SubjectPublicKeyInfo`
+/// matches one of the pins
+fn server_cert_matches_pins(cert: &X509Ref, pins: &[[u8; 32]]) -> bool {
+    let cert_hash = cert.to_der().map(|der| sha256(&der));
+    let spki_hash = cert
+        .public_key()
+        .and_then(|public_key| public_key.public_key_to_der())
+        .map(|der| sha256(&der));
+    pins.iter().any(|pin| {
+        cert_hash.as_ref().is_ok_and(|hash| hash == pin)
+            || spki_hash.as_ref().is_ok_and(|hash| hash == pin)
+    })
+}
+
+/// Details of a negotiated TLS session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Negotiated protocol version (e.g. "TLSv1.3")
+    pub version: String,
+    /// Negotiated cipher suite, using the IANA name where known (e.g. "`TLS_AES_256_GCM_SHA384`")
+    pub cipher_suite: String,
+    /// Certificate chain presented by the server, starting with the server's own certificate
+    pub peer_certificate_chain: Vec<CertificateInfo>,
+}
+
+impl TlsInfo {
+    /// Collects the details of the TLS session negotiated on the given SSL connection.
+    pub(crate) fn from_ssl(ssl: &SslRef) -> Self {
+        let cipher_suite = ssl
+            .current_cipher()
+            .map(|cipher| cipher.standard_name().unwrap_or(cipher.name()).to_string())
+            .unwrap_or_default();
+        let peer_certificate_chain = ssl
+            .peer_cert_chain()
+            .map(|chain| chain.iter().filter_map(CertificateInfo::from_x509).collect())
+            .unwrap_or_default();
+        Self {
+            version: ssl.version_str().to_string(),
+            cipher_suite,
+            peer_certificate_chain,
+        }
+    }
+}
+
+/// Details of an X.509 certificate presented during a TLS handshake.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// Distinguished name of the certificate subject (e.g. "CN=broker, O=Contoso")
+    pub subject: String,
+    /// Distinguished name of the certificate issuer
+    pub issuer: String,
+    /// Start of the validity period of the certificate
+    pub not_before: DateTime<Utc>,
+    /// Expiry of the certificate
+    pub not_after: DateTime<Utc>,
+}
+
+impl CertificateInfo {
+    /// Collects the details of an X.509 certificate, returning `None` if its validity period
+    /// cannot be represented.
+    fn from_x509(cert: &X509Ref) -> Option<Self> {
+        let info = Self {
+            subject: distinguished_name(cert.subject_name()),
+            issuer: distinguished_name(cert.issuer_name()),
+            not_before: asn1_time_to_utc(cert.not_before())?,
+            not_after: asn1_time_to_utc(cert.not_after())?,
+        };
+        Some(info)
+    }
+}
+
+/// Formats an X.509 name as a comma-separated list of `key=value` attributes.
+fn distinguished_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let object = entry.object();
+            let key = match object.nid().short_name() {
+                Ok(short_name) if object.nid() != Nid::UNDEF => short_name.to_string(),
+                _ => object.to_string(),
+            };
+            let value = entry
+                .data()
+                .to_string()
+                .unwrap_or_else(|_| String::from_utf8_lossy(entry.data().as_slice()).into_owned());
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Converts an ASN.1 time to a UTC timestamp.
+fn asn1_time_to_utc(time: &Asn1TimeRef) -> Option<DateTime<Utc>> {
+    let epoch = Asn1Time::from_unix(0).ok()?;
+    let diff = epoch.diff(time).ok()?;
+    DateTime::from_timestamp(i64::from(diff.days) * 86_400 + i64::from(diff.secs), 0)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::pin::Pin;
+
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        hash::MessageDigest,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        ssl::{SslAcceptor, SslMethod},
+        x509::{X509, X509NameBuilder, extension::SubjectAlternativeName},
+    };
+    use tokio_openssl::SslStream;
+
+    use super::*;
+
+    const NOT_BEFORE: i64 = 1_700_000_000;
+    const NOT_AFTER: i64 = 1_800_000_000;
+
+    /// Create a self-signed certificate for "broker"
+    pub(crate) fn self_signed_cert() -> (X509, PKey<Private>) {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "broker").unwrap();
+        name.append_entry_by_text("O", "Contoso").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&pkey).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(NOT_BEFORE).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(NOT_AFTER).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("broker")
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(san).unwrap();
+        builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+        (builder.build(), pkey)
+    }
+
+    #[test]
+    fn certificate_info() {
+        let (cert, _) = self_signed_cert();
+        let info = CertificateInfo::from_x509(&cert).unwrap();
+        assert_eq!(info.subject, "CN=broker, O=Contoso");
+        assert_eq!(info.issuer, "CN=broker, O=Contoso");
+        assert_eq!(info.not_before, DateTime::from_timestamp(NOT_BEFORE, 0).unwrap());
+        assert_eq!(info.not_after, DateTime::from_timestamp(NOT_AFTER, 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn tls_info_from_handshake() {
+        let (cert, pkey) = self_signed_cert();
+        let (client_stream, server_stream) = tokio::io::duplex(16 * 1024);
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&pkey).unwrap();
+        let acceptor = acceptor.build();
+        let server = tokio::task::spawn(async move {
+            let ssl = openssl::ssl::Ssl::new(acceptor.context()).unwrap();
+            let mut stream = SslStream::new(ssl, server_stream).unwrap();
+            Pin::new(&mut stream).accept().await.unwrap();
+            stream
+        });
+
+        let TlsConfig { connector, .. } = TlsConfig::new(None, vec![cert]).unwrap();
+        let ssl = connector.build().configure().unwrap().into_ssl("broker").unwrap();
+        let mut client = SslStream::new(ssl, client_stream).unwrap();
+        Pin::new(&mut client).connect().await.unwrap();
+        let _server = server.await.unwrap();
+
+        let info = TlsInfo::from_ssl(client.ssl());
+        assert_eq!(info.version, "TLSv1.3");
+        assert!(info.cipher_suite.starts_with("TLS_"));
+        assert_eq!(info.peer_certificate_chain.len(), 1);
+        assert_eq!(info.peer_certificate_chain[0].subject, "CN=broker, O=Contoso");
+        assert_eq!(
+            info.peer_certificate_chain[0].not_after,
+            DateTime::from_timestamp(NOT_AFTER, 0).unwrap()
+        );
     }
 }
@@ -3,12 +3,17 @@
 
 //! Adapter layer for the `azure_mqtt` (TODO: rename this once settled) crate
 
+#[cfg(feature = "test-utils")]
+use std::collections::HashMap;
 use std::num::{NonZero, NonZeroU16, NonZeroU32};
+use std::sync::Arc;
 use std::{fmt, fs, time::Duration};
 
 use crate::azure_mqtt::client::ClientOptions;
 use crate::azure_mqtt::packet::{ConnectProperties, SessionExpiryInterval, Will};
-use crate::azure_mqtt::transport::{ConnectionTransportConfig, ConnectionTransportType, TlsConfig};
+use crate::azure_mqtt::transport::{
+    ConnectionTransportConfig, ConnectionTransportType, SocketOptions, TlsConfig,
+};
 use bytes::Bytes;
 use openssl::{
     pkey::{PKey, Private},
@@ -16,7 +21,9 @@ use openssl::{
 };
 use thiserror::Error;
 
-use crate::aio::connection_settings::MqttConnectionSettings;
+use crate::aio::connection_settings::{MqttConnectionSettings, ServerCertPinMode};
+use crate::session::auth_provider::{AuthError, AuthProvider};
+use crate::session::redirect_policy::ServerAddress;
 #[cfg(feature = "test-utils")]
 use crate::test_utils::InjectedPacketChannels;
 
@@ -125,15 +132,19 @@ fn create_connect_properties(
 
 /// Create [`ConnectionTransportConfig`]
 #[allow(clippy::too_many_arguments)]
-fn create_connection_transport_config(
+pub(crate) fn create_connection_transport_config(
     ca_file: Option<String>,
     cert_file: Option<String>,
     key_file: Option<String>,
     key_password_file: Option<String>,
     use_tls: bool,
+    tls_server_name: Option<String>,
+    pinned_server_cert_sha256: Option<Vec<[u8; 32]>>,
+    server_cert_pin_mode: ServerCertPinMode,
     hostname: String,
     tcp_port: u16,
     timeout: Duration,
+    connection_attempt_delay: Option<Duration>,
 ) -> Result<ConnectionTransportConfig, ConnectionSettingsAdapterError> {
     let transport_type = if use_tls {
         let (client_cert, ca_trust_bundle) =
@@ -148,7 +159,7 @@ fn create_connection_transport_config(
                 }
             })?;
 
-        let tls_config = TlsConfig::new(client_cert, ca_trust_bundle).map_err(|e| {
+        let mut tls_config = TlsConfig::new(client_cert, ca_trust_bundle).map_err(|e| {
             ConnectionSettingsAdapterError {
                 msg: "failed to create TLS config".to_string(),
                 field: ConnectionSettingsField::UseTls(true),
@@ -158,6 +169,15 @@ fn create_connection_transport_config(
                 })),
             }
         })?;
+        if let Some(tls_server_name) = tls_server_name {
+            tls_config.set_server_name(tls_server_name);
+        }
+        if let Some(pins) = pinned_server_cert_sha256 {
+            tls_config.set_pinned_server_certs(
+                pins,
+                server_cert_pin_mode == ServerCertPinMode::PinAndChain,
+            );
+        }
 
         ConnectionTransportType::Tls {
             tls_config,
@@ -175,8 +195,11 @@ fn create_connection_transport_config(
         transport_type,
         timeout: Some(timeout),
         proxy: None,
-        // Disable Nagle's algorithm (`TCP_NODELAY`) (hardcoded) to minimize latency
-        tcp_nodelay: true,
+        socket_options: SocketOptions {
+            // Disable Nagle's algorithm (`TCP_NODELAY`) (hardcoded) to minimize latency
+            tcp_nodelay: true,
+            connection_attempt_delay,
+        },
     })
 }
 
@@ -196,6 +219,8 @@ pub struct AzureMqttConnectParameters {
     pub connect_properties: ConnectProperties,
     /// Connection timeout duration
     pub connection_timeout: Duration,
+    /// Provider of username/password, used instead of `username` and `password` if present
+    pub auth_provider: Option<Arc<dyn AuthProvider>>,
 
     /// properties used to create the `ConnectionTransportConfig` on demand
     ca_file: Option<String>,
@@ -203,15 +228,60 @@ pub struct AzureMqttConnectParameters {
     key_file: Option<String>,
     key_password_file: Option<String>,
     use_tls: bool,
+    tls_server_name: Option<String>,
+    pinned_server_cert_sha256: Option<Vec<[u8; 32]>>,
+    server_cert_pin_mode: ServerCertPinMode,
     hostname: String,
     tcp_port: u16,
+    connection_attempt_delay: Option<Duration>,
 
     /// Injected packet channels for test purposes. Can be None to use normal transport config.
     #[cfg(feature = "test-utils")]
     pub injected_packet_channels: Option<InjectedPacketChannels>,
+    /// Injected packet channels for specific server addresses for test purposes. Used instead of
+    /// `injected_packet_channels` when connecting to one of these addresses.
+    #[cfg(feature = "test-utils")]
+    pub injected_server_packet_channels: HashMap<ServerAddress, InjectedPacketChannels>,
 }
 
 impl AzureMqttConnectParameters {
+    /// Get the username and password to use for the next connection attempt, invoking the
+    /// `AuthProvider` if there is one.
+    ///
+    /// # Errors
+    /// Returns [`AuthError`] if the `AuthProvider` fails to provide credentials
+    pub async fn credentials(&self) -> Result<(Option<String>, Option<Bytes>), AuthError> {
+        if let Some(auth_provider) = &self.auth_provider {
+            let credentials = auth_provider.credentials().await?;
+            if credentials.is_expired() {
+                log::warn!(
+                    "AuthProvider returned credentials that have already expired at {:?}",
+                    credentials.expiry
+                );
+            }
+            Ok((credentials.username, credentials.password.map(Bytes::from)))
+        } else {
+            Ok((self.username.clone(), self.password.clone()))
+        }
+    }
+
+    /// Get the address of the server to connect to
+    pub fn server_address(&self) -> ServerAddress {
+        ServerAddress {
+            hostname: self.hostname.clone(),
+            tcp_port: self.tcp_port,
+        }
+    }
+
+    /// Set the address of the server to connect to for subsequent connection attempts.
+    ///
+    /// All other settings, including credentials and TLS settings, remain the same. Unless a TLS
+    /// server name was explicitly set, the new hostname is also used as the TLS server name.
+    pub fn set_server_address(&mut self, server_address: ServerAddress) {
+        self.hostname = server_address.hostname;
+        self.tcp_port = server_address.tcp_port;
+    }
+
     /// Create a new `ConnectionTransportConfig` from stored parameters
     ///
     /// # Errors
@@ -220,7 +290,11 @@ impl AzureMqttConnectParameters {
         &self,
     ) -> Result<ConnectionTransportConfig, ConnectionSettingsAdapterError> {
         #[cfg(feature = "test-utils")]
-        if let Some(injected_packet_channels) = &self.injected_packet_channels {
+        if let Some(injected_packet_channels) = self
+            .injected_server_packet_channels
+            .get(&self.server_address())
+            .or(self.injected_packet_channels.as_ref())
+        {
             let (incoming_packets_tx, incoming_packets_rx) = tokio::sync::mpsc::unbounded_channel();
             let (outgoing_packets_tx, outgoing_packets_rx) = tokio::sync::mpsc::unbounded_channel();
             injected_packet_channels
@@ -237,7 +311,7 @@ impl AzureMqttConnectParameters {
                 },
                 timeout: Some(self.connection_timeout),
                 proxy: None,
-                tcp_nodelay: true,
+                socket_options: SocketOptions::default(),
             });
         }
 
@@ -247,9 +321,13 @@ impl AzureMqttConnectParameters {
             self.key_file.clone(),
             self.key_password_file.clone(),
             self.use_tls,
+            self.tls_server_name.clone(),
+            self.pinned_server_cert_sha256.clone(),
+            self.server_cert_pin_mode,
             self.hostname.clone(),
             self.tcp_port,
             self.connection_timeout,
+            self.connection_attempt_delay,
         )
     }
 }
@@ -270,12 +348,17 @@ impl MqttConnectionSettings {
         publish_qos0_queue_size: usize,
         publish_qos1_qos2_queue_size: usize,
         #[cfg(feature = "test-utils")] injected_packet_channels: Option<InjectedPacketChannels>,
+        #[cfg(feature = "test-utils")] injected_server_packet_channels: HashMap<
+            ServerAddress,
+            InjectedPacketChannels,
+        >,
     ) -> Result<(ClientOptions, AzureMqttConnectParameters), ConnectionSettingsAdapterError> {
         let client_options = ClientOptions {
             client_id: Some(self.client_id),
             max_packet_identifier,
             publish_qos0_queue_size,
             publish_qos1_qos2_queue_size,
+            ..ClientOptions::default()
         };
 
         let ping_after =
@@ -320,6 +403,8 @@ impl MqttConnectionSettings {
             user_properties,
         )?;
 
+        let connection_attempt_delay = self.happy_eyeballs.then_some(self.connection_attempt_delay);
+
         // not used, but we want to validate failures early.
         let _connection_transport_config = create_connection_transport_config(
             self.ca_file.clone(),
@@ -327,9 +412,13 @@ impl MqttConnectionSettings {
             self.key_file.clone(),
             self.key_password_file.clone(),
             self.use_tls,
+            self.tls_server_name.clone(),
+            self.pinned_server_cert_sha256.clone(),
+            self.server_cert_pin_mode,
             self.hostname.clone(),
             self.tcp_port,
             self.connection_timeout,
+            connection_attempt_delay,
         )?;
 
         Ok((
@@ -345,12 +434,19 @@ impl MqttConnectionSettings {
                 key_file: self.key_file,
                 key_password_file: self.key_password_file,
                 use_tls: self.use_tls,
+                tls_server_name: self.tls_server_name,
+                pinned_server_cert_sha256: self.pinned_server_cert_sha256,
+                server_cert_pin_mode: self.server_cert_pin_mode,
                 hostname: self.hostname,
                 tcp_port: self.tcp_port,
+                connection_attempt_delay,
                 connect_properties,
                 connection_timeout: self.connection_timeout,
+                auth_provider: self.auth_provider,
                 #[cfg(feature = "test-utils")]
                 injected_packet_channels,
+                #[cfg(feature = "test-utils")]
+                injected_server_packet_channels,
             },
         ))
     }
@@ -453,6 +549,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -474,6 +571,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -494,6 +592,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -518,6 +617,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -542,6 +642,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -567,6 +668,7 @@ mod tests {
             200,
             200,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -589,6 +691,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -616,6 +719,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -641,6 +745,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -670,6 +775,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
     }
@@ -690,6 +796,7 @@ mod tests {
             100,
             100,
             None,
+            std::collections::HashMap::new(),
         );
         assert!(result.is_ok());
         assert_eq!(
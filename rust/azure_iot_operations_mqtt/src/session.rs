@@ -21,7 +21,15 @@
 //! 1. The [`ReconnectPolicy`] configured on the [`Session`] halts reconnection attempts, causing
 //!    the [`Session`] to end the MQTT session.
 //! 2. The user uses the [`SessionExitHandle`] to end the MQTT session.
-//! 3. The server discards the MQTT session (e.g. due to expiry or other server-side policy).
+//! 3. The server discards the MQTT session (e.g. due to expiry or other server-side policy), and
+//!    the [`Session`] is configured not to resubscribe on session loss.
+//!
+//! By default, if the server discards the MQTT session, the [`Session`] instead continues with the
+//! new MQTT session created by the server on reconnect, and issues the `SUBSCRIBE`s of all
+//! subscriptions made through its [`SessionManagedClient`]s again so that their
+//! [`SessionPubReceiver`]s keep receiving messages. Any other state of the lost MQTT session (e.g.
+//! unacknowledged messages) is not recovered. Use [`SessionMonitor::session_lost`] to be notified
+//! when this happens.
 //!
 //! Additionally, the [`Session`] `run` can be ended forcefully via the
 //! [`SessionExitHandle::force_exit`] method, however this does not guarantee that the MQTT session
@@ -46,7 +54,69 @@
 //! should create the [`SessionPubReceiver`] *before* subscribing to the topic filter in order to
 //! prevent messages being received in between the time you subscribe and the time you create the
 //! [`SessionPubReceiver`].
+//!
+//! # Low-level MQTT operations
+//! The [`SessionManagedClient`] is a thin layer over the MQTT operations themselves, and can be
+//! used directly to build protocols not covered by the higher-level clients in other crates,
+//! while the [`Session`] continues to manage reconnection:
+//! * Every `PUBLISH`, `SUBSCRIBE` and `UNSUBSCRIBE` takes the full set of packet properties
+//!   (e.g. [`PublishProperties`](crate::control_packet::PublishProperties)), which are sent as
+//!   provided. The only exception is that the [`Session`] assigns a subscription identifier to a
+//!   `SUBSCRIBE` that does not specify one (see [`SessionManagedClient::subscribe`]).
+//! * Each operation returns a completion token that resolves to the acknowledgement packet from
+//!   the server (e.g. [`PubAck`](crate::control_packet::PubAck) or
+//!   [`SubAck`](crate::control_packet::SubAck)), including its reason code and properties, so
+//!   that the application decides how to handle failures and when to wait for them.
+//! * Incoming `PUBLISH`es can be acknowledged manually by receiving them with
+//!   [`SessionPubReceiver::recv_manual_ack`] and calling [`AckToken::ack`](crate::token::AckToken::ack)
+//!   when the application is ready. Acknowledgements are always sent in the order the `PUBLISH`es
+//!   were received, as required by the MQTT specification.
+//!
+//! Operations issued while disconnected are queued and delivered once the connection is
+//! re-established, and unacknowledged QoS 1 `PUBLISH`es are retransmitted on reconnect, as long
+//! as the MQTT session is maintained. QoS 2 is not currently supported.
+//!
+//! ```no_run
+//! # use azure_iot_operations_mqtt::control_packet::{
+//! #     PublishProperties, QoS, RetainOptions, SubscribeProperties, TopicFilter, TopicName,
+//! # };
+//! # use azure_iot_operations_mqtt::session::SessionManagedClient;
+//! # async fn example(client: SessionManagedClient) -> Result<(), Box<dyn std::error::Error>> {
+//! let topic_filter = TopicFilter::new("sample/request")?;
+//! let mut receiver = client.create_filtered_pub_receiver(topic_filter.clone());
+//! let suback = client
+//!     .subscribe(
+//!         topic_filter,
+//!         QoS::AtLeastOnce,
+//!         true,
+//!         RetainOptions::default(),
+//!         SubscribeProperties::default(),
+//!     )
+//!     .await?
+//!     .await?;
+//! println!("SUBACK: {suback:?}");
+//!
+//! while let Some((publish, ack_token)) = receiver.recv_manual_ack().await {
+//!     let properties = PublishProperties {
+//!         correlation_data: publish.properties.correlation_data.clone(),
+//!         ..Default::default()
+//!     };
+//!     let puback = client
+//!         .publish_qos1(TopicName::new("sample/response")?, false, publish.payload, properties)
+//!         .await?
+//!         .await?;
+//!     println!("PUBACK: {puback:?}");
+//!     // Only acknowledge the request once the response has been acknowledged
+//!     if let Some(ack_token) = ack_token {
+//!         ack_token.ack().await?.await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
 
+#[cfg(feature = "test-utils")]
+use std::collections::HashMap;
 use std::{
     fmt,
     sync::{Arc, Mutex, Weak},
@@ -58,15 +128,20 @@ use crate::azure_mqtt::{
     client::{
         ConnectEnhancedAuthResult, ConnectResult, Connection, DisconnectedEvent, ReauthResult,
     },
-    packet::{AuthProperties, ConnAck, DisconnectProperties, SessionExpiryInterval},
+    packet::{
+        AuthProperties, ConnAck, ConnAckProperties, ConnAckReason, DisconnectProperties,
+        DisconnectReason, SessionExpiryInterval,
+    },
     transport::ConnectionTransportConfig,
 };
 use thiserror::Error;
-use tokio::sync::Notify;
+use tokio::sync::{Notify, broadcast};
 
 use crate::aio::{
     AIOBrokerFeatures, AIOBrokerFeaturesBuilder, connection_settings::MqttConnectionSettings,
 };
+pub use crate::azure_mqtt::client::PublishStats;
+pub use crate::azure_mqtt::transport::{CertificateInfo, TlsInfo};
 use crate::azure_mqtt_adapter as adapter;
 use crate::azure_mqtt_adapter::AzureMqttConnectParameters;
 use crate::control_packet::PacketIdentifier;
@@ -76,17 +151,29 @@ use crate::session::state::SessionState;
 use crate::session::{
     dispatcher::IncomingPublishDispatcher,
     enhanced_auth_policy::{EnhancedAuthPolicy, K8sSatFileMonitor},
-    reconnect_policy::{ConnectionLossReason, ExponentialBackoffWithJitter, ReconnectPolicy},
+    reconnect_policy::{
+        ConnectionLossReason, ExponentialBackoffWithJitter, ReconnectAttempt, ReconnectPolicy,
+    },
+    redirect_policy::{RedirectPolicy, RedirectRejection, ServerAddress},
+    subscriptions::ActiveSubscriptions,
 };
 #[cfg(feature = "test-utils")]
 use crate::test_utils::InjectedPacketChannels;
 
+pub mod auth_provider;
 pub(crate) mod dispatcher;
 pub mod enhanced_auth_policy;
 mod managed_client;
 pub(crate) mod plenary_ack;
 pub mod reconnect_policy;
+pub mod redirect_policy;
+#[cfg(feature = "signal")]
+pub mod shutdown;
 mod state;
+mod subscriptions;
+
+/// Number of connection events buffered for each receiver of [`SessionMonitor::connection_events`]
+const CONNECTION_EVENT_CAPACITY: usize = 16;
 
 /// Error describing why a [`Session`] ended prematurely
 #[derive(Debug, Error)]
@@ -198,9 +285,13 @@ impl fmt::Display for SessionExitErrorKind {
 pub struct SessionOptions {
     /// MQTT Connection Settings for configuring the [`Session`]
     connection_settings: MqttConnectionSettings,
-    /// Reconnect Policy to by used by the `Session`
+    /// Reconnect Policy to by used by the `Session`.
+    /// See [`reconnect_policy`] for the provided policies.
     #[builder(default = "Box::new(ExponentialBackoffWithJitter::default())")]
     reconnect_policy: Box<dyn ReconnectPolicy>,
+    /// Redirect Policy determining which server redirects are followed by the `Session`
+    #[builder(default)]
+    redirect_policy: RedirectPolicy,
     /// Enhanced Authentication Policy to be used by the `Session`
     #[builder(default = "None")]
     enhanced_auth_policy: Option<Box<dyn EnhancedAuthPolicy>>,
@@ -213,13 +304,130 @@ pub struct SessionOptions {
     /// Maximum number of queued outgoing QoS 1 and 2 PUBLISH packets not yet accepted by the MQTT Session
     #[builder(default = "100")]
     publish_qos1_qos2_queue_size: usize,
+    /// Whether the topics of outgoing PUBLISHes are replaced by MQTT topic aliases, up to the
+    /// topic alias maximum in the server's CONNACK. The first PUBLISH to a topic on each connection
+    /// carries the full topic and subsequent ones only its alias, saving bytes when publishing
+    /// repeatedly to long topics. Disabled by default since not all servers handle topic aliases
+    /// well.
+    #[builder(default = "false")]
+    outgoing_topic_aliases: bool,
     /// Indicates if the Session should use features specific for use with the AIO MQTT Broker
     #[builder(default = "Some(AIOBrokerFeaturesBuilder::default().build().unwrap())")]
     aio_broker_features: Option<AIOBrokerFeatures>,
+    /// Whether the [`Session`] continues after the server discards the MQTT session on a reconnect,
+    /// issuing the `SUBSCRIBE`s of all active subscriptions made through its
+    /// [`SessionManagedClient`]s again. If false, the [`Session`] ends with
+    /// [`SessionErrorKind::SessionLost`] instead.
+    #[builder(default = "true")]
+    resubscribe_on_session_loss: bool,
     /// Injected packet channels for testing purposes
     #[cfg(feature = "test-utils")]
     #[builder(default)]
     injected_packet_channels: Option<InjectedPacketChannels>,
+    /// Injected packet channels for specific server addresses (e.g. redirect targets) for testing
+    /// purposes. Connections to other addresses use `injected_packet_channels`.
+    #[cfg(feature = "test-utils")]
+    #[builder(default)]
+    injected_server_packet_channels: HashMap<ServerAddress, InjectedPacketChannels>,
+}
+
+/// Event describing a change to the connection of a [`Session`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// The server redirected the [`Session`] to another server, which will be used for subsequent
+    /// connection attempts
+    Redirected {
+        /// Address of the server that issued the redirect
+        from: ServerAddress,
+        /// Address of the server the [`Session`] was redirected to
+        to: ServerAddress,
+        /// True if the server has moved permanently ("Server Moved"), false if the redirect is
+        /// temporary ("Use Another Server")
+        permanent: bool,
+    },
+    /// The server requested a redirect that the [`Session`] did not follow. Subsequent connection
+    /// attempts continue to use the same server.
+    RedirectRejected {
+        /// Address of the server that issued the redirect
+        from: ServerAddress,
+        /// Server Reference provided by the server, if any
+        server_reference: Option<String>,
+        /// Reason the redirect was not followed
+        reason: RedirectRejection,
+    },
+    /// The server discarded the MQTT session on a reconnect. The `SUBSCRIBE`s of the active
+    /// subscriptions are issued again on the new MQTT session.
+    SessionLost {
+        /// Number of subscriptions issued again
+        resubscribed: usize,
+    },
+}
+
+/// Cause of the most recent disconnect of a [`Session`], or of the most recent failed attempt to
+/// connect it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisconnectCause {
+    /// The server disconnected with a DISCONNECT packet with the given reason code
+    ServerDisconnect(DisconnectReason),
+    /// The server rejected the connection with a CONNACK with the given reason code
+    ConnectRejected(ConnAckReason),
+    /// The TLS handshake with the server failed
+    TlsHandshakeFailure(String),
+    /// The TCP connection failed or was lost due to an I/O error
+    TcpError(std::io::ErrorKind, String),
+    /// The server did not respond to a PINGREQ within the keep alive
+    KeepAliveTimeout,
+    /// The server did not respond to the CONNECT within the connection timeout
+    ConnectTimeout,
+    /// The server violated the MQTT protocol
+    ProtocolError(String),
+    /// The credentials for the connection could not be obtained
+    CredentialsError(String),
+    /// The application disconnected the [`Session`] with a [`SessionExitHandle`]
+    ApplicationDisconnect,
+}
+
+impl DisconnectCause {
+    fn from_io_error(err: &std::io::Error) -> Self {
+        // TLS errors that are not caused by an underlying I/O error are wrapped as is
+        if let Some(tls_err) = err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<openssl::ssl::Error>())
+        {
+            return DisconnectCause::TlsHandshakeFailure(tls_err.to_string());
+        }
+        DisconnectCause::TcpError(err.kind(), err.to_string())
+    }
+
+    fn from_connect_error(err: &azure_mqtt::error::ConnectError) -> Self {
+        match err {
+            azure_mqtt::error::ConnectError::Protocol(e) => {
+                DisconnectCause::ProtocolError(e.to_string())
+            }
+            azure_mqtt::error::ConnectError::Io(e) => DisconnectCause::from_io_error(e),
+            azure_mqtt::error::ConnectError::Rejected(connack) => {
+                DisconnectCause::ConnectRejected(connack.reason.clone())
+            }
+            azure_mqtt::error::ConnectError::ResponseTimeout => DisconnectCause::ConnectTimeout,
+            azure_mqtt::error::ConnectError::Credentials(e) => {
+                DisconnectCause::CredentialsError(e.to_string())
+            }
+        }
+    }
+
+    fn from_disconnected_event(event: &DisconnectedEvent) -> Self {
+        match event {
+            DisconnectedEvent::ApplicationDisconnect => DisconnectCause::ApplicationDisconnect,
+            DisconnectedEvent::ServerDisconnect(disconnect) => {
+                DisconnectCause::ServerDisconnect(disconnect.reason.clone())
+            }
+            DisconnectedEvent::PingTimeout => DisconnectCause::KeepAliveTimeout,
+            DisconnectedEvent::IoError(e) => DisconnectCause::from_io_error(e),
+            DisconnectedEvent::ProtocolError(e) => DisconnectCause::ProtocolError(e.to_string()),
+        }
+    }
 }
 
 /// Client that manages connections over a single MQTT session.
@@ -243,8 +451,18 @@ pub struct Session {
     client_id: String,
     /// Receiver dispatcher for incoming publishes
     incoming_pub_dispatcher: Arc<Mutex<IncomingPublishDispatcher>>,
+    /// Active subscriptions, replayed if the MQTT session is lost
+    subscriptions: Arc<Mutex<ActiveSubscriptions>>,
+    /// Whether the Session continues with a new MQTT session if the MQTT session is lost
+    resubscribe_on_session_loss: bool,
     /// Reconnect policy
     reconnect_policy: Box<dyn ReconnectPolicy>,
+    /// Redirect policy
+    redirect_policy: RedirectPolicy,
+    /// Hostname from the connection settings, used to evaluate redirects
+    configured_hostname: String,
+    /// Sender for connection events
+    connection_events_tx: broadcast::Sender<ConnectionEvent>,
     /// Enhanced authentication policy
     enhanced_auth_policy: Option<Arc<dyn EnhancedAuthPolicy>>,
     /// Current state
@@ -261,6 +479,7 @@ impl Session {
     #[allow(clippy::missing_panics_doc)] // TODO: Remove once a better way to handle auth policy failure
     pub fn new(options: SessionOptions) -> Result<Self, SessionConfigError> {
         let client_id = options.connection_settings.client_id.clone();
+        let configured_hostname = options.connection_settings.hostname.clone();
 
         // Add AIO metric and features to user properties when using AIO MQTT broker features
         // CONSIDER: user properties from being supported on SessionOptions or ConnectionSettings
@@ -302,7 +521,7 @@ impl Session {
                 .map(|eap| Arc::from(eap) as Arc<dyn EnhancedAuthPolicy>)
         };
 
-        let (client_options, connect_parameters) = options
+        let (mut client_options, connect_parameters) = options
             .connection_settings
             .into_azure_mqtt_connect_parameters(
                 user_properties,
@@ -311,7 +530,10 @@ impl Session {
                 options.publish_qos1_qos2_queue_size,
                 #[cfg(feature = "test-utils")]
                 options.injected_packet_channels,
+                #[cfg(feature = "test-utils")]
+                options.injected_server_packet_channels,
             )?;
+        client_options.outgoing_topic_aliases = options.outgoing_topic_aliases;
 
         let (client, connect_handle, receiver) = azure_mqtt::client::new_client(client_options);
         let incoming_pub_dispatcher = Arc::new(Mutex::new(IncomingPublishDispatcher::default()));
@@ -327,7 +549,12 @@ impl Session {
             connect_parameters,
             client_id,
             incoming_pub_dispatcher,
+            subscriptions: Arc::new(Mutex::new(ActiveSubscriptions::default())),
+            resubscribe_on_session_loss: options.resubscribe_on_session_loss,
             reconnect_policy: options.reconnect_policy,
+            redirect_policy: options.redirect_policy,
+            configured_hostname,
+            connection_events_tx: broadcast::channel(CONNECTION_EVENT_CAPACITY).0,
             enhanced_auth_policy,
             state: Arc::new(SessionState::default()),
             notify_force_exit: Arc::new(Notify::new()),
@@ -339,13 +566,38 @@ impl Session {
         SessionExitHandle {
             disconnect_handle: Arc::downgrade(&self.disconnect_handle),
             force_exit: self.notify_force_exit.clone(),
+            state: self.state.clone(),
         }
     }
 
+    /// Returns the details of the TLS session negotiated for the most recent connection, including
+    /// the protocol version, cipher suite and the certificate chain presented by the MQTT server.
+    ///
+    /// Returns `None` before the first connection or if the connection does not use TLS.
+    /// As [`Session::run`] consumes the [`Session`], use [`SessionMonitor::tls_info`] to retrieve
+    /// the details while the [`Session`] is running.
+    #[must_use]
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.state.tls_info()
+    }
+
+    /// Returns the properties of the CONNACK of the most recent connection, including the limits
+    /// negotiated with the MQTT server such as the maximum packet size and receive maximum.
+    ///
+    /// Returns `None` before the first connection.
+    /// As [`Session::run`] consumes the [`Session`], use [`SessionMonitor::connack_properties`] to
+    /// retrieve the properties while the [`Session`] is running.
+    #[must_use]
+    pub fn connack_properties(&self) -> Option<ConnAckProperties> {
+        self.state.connack_properties()
+    }
+
     /// Return a new instance of [`SessionMonitor`] that can be used to monitor the session's state
     pub fn create_session_monitor(&self) -> SessionMonitor {
         SessionMonitor {
             state: self.state.clone(),
+            client: self.client.clone(),
+            connection_events_tx: self.connection_events_tx.clone(),
         }
     }
 
@@ -355,6 +607,7 @@ impl Session {
             client_id: self.client_id.clone(),
             client: self.client.clone(),
             dispatcher: self.incoming_pub_dispatcher.clone(),
+            subscriptions: self.subscriptions.clone(),
         }
     }
 
@@ -405,6 +658,9 @@ impl Session {
         let mut clean_start = self.connect_parameters.initial_clean_start;
         let mut prev_connected = false;
         let mut prev_reconnection_attempts = 0;
+        // Start of the current sequence of connect attempts, reported to the reconnect policy
+        let mut reconnection_started_at = tokio::time::Instant::now();
+        let mut consecutive_redirects = 0;
         loop {
             log::debug!("Attempting to connect MQTT session (clean_start={clean_start})");
             let connection_transport_config = self
@@ -420,11 +676,33 @@ impl Session {
                     Ok((connection, connack)) => (connection, connack),
                     Err(e) => {
                         log::warn!("Failed to connect MQTT session: {e:?}");
+                        self.state
+                            .record_disconnect_cause(DisconnectCause::from_connect_error(&e));
+                        // Connect to the server indicated by the CONNACK immediately if redirected
+                        if let azure_mqtt::error::ConnectError::Rejected(connack) = &e {
+                            let permanent = match connack.reason {
+                                ConnAckReason::UseAnotherServer => Some(false),
+                                ConnAckReason::ServerMoved => Some(true),
+                                _ => None,
+                            };
+                            if let Some(permanent) = permanent
+                                && self.follow_redirect(
+                                    permanent,
+                                    connack.properties.server_reference.clone(),
+                                    &mut consecutive_redirects,
+                                )
+                            {
+                                continue;
+                            }
+                        }
                         prev_reconnection_attempts += 1;
 
-                        if let Some(delay) = self
-                            .reconnect_policy
-                            .connect_failure_reconnect_delay(prev_reconnection_attempts, &e)
+                        let attempt = ReconnectAttempt {
+                            prev_attempts: prev_reconnection_attempts,
+                            elapsed: reconnection_started_at.elapsed(),
+                        };
+                        if let Some(delay) =
+                            self.reconnect_policy.reconnect_attempt_delay(&attempt, &e)
                         {
                             log::debug!("Retrying connect in {delay:?}...");
                             tokio::time::sleep(delay).await;
@@ -437,14 +715,29 @@ impl Session {
                 };
 
             // Check to see if the MQTT session has been lost
-            if !connack.session_present && prev_connected {
-                // TODO: try and disconnect here?
+            let session_lost = !connack.session_present && prev_connected;
+            if session_lost {
                 log::info!("MQTT session not present on connection");
-                log::info!("Exiting Session due to MQTT session loss");
-                return Err(SessionErrorKind::SessionLost.into());
+                if !self.resubscribe_on_session_loss {
+                    // TODO: try and disconnect here?
+                    self.state.notify_session_lost();
+                    log::info!("Exiting Session due to MQTT session loss");
+                    return Err(SessionErrorKind::SessionLost.into());
+                }
             }
 
-            self.state.transition_connected();
+            self.incoming_pub_dispatcher
+                .lock()
+                .unwrap()
+                .set_subscription_identifiers_available(
+                    connack.properties.subscription_identifiers_available,
+                );
+            self.state
+                .transition_connected(connection.tls_info().cloned(), connack.properties);
+
+            if session_lost {
+                self.resubscribe();
+            }
 
             // Indicate we have established a connection at least once, and will now attempt
             // to maintain this MQTT session.
@@ -464,10 +757,14 @@ impl Session {
             };
 
             let (connect_handle, disconnected_event) = connection.run_until_disconnect().await;
+            reconnection_started_at = tokio::time::Instant::now();
             self.connect_handle = Some(connect_handle);
             *self.disconnect_handle.lock().unwrap() = None;
             self.reauth_handle = None;
-            self.state.transition_disconnected();
+            self.state
+                .transition_disconnected(DisconnectCause::from_disconnected_event(
+                    &disconnected_event,
+                ));
             if let Some(reauth_jh) = reauth_jh {
                 reauth_jh.abort();
             }
@@ -478,11 +775,32 @@ impl Session {
                     return Ok(());
                 }
                 DisconnectedEvent::ServerDisconnect(disconnect) => {
+                    let permanent = match disconnect.reason {
+                        DisconnectReason::UseAnotherServer => Some(false),
+                        DisconnectReason::ServerMoved => Some(true),
+                        _ => None,
+                    };
+                    if !permanent.is_some_and(|permanent| {
+                        self.follow_redirect(
+                            permanent,
+                            disconnect.properties.server_reference.clone(),
+                            &mut consecutive_redirects,
+                        )
+                    }) {
+                        consecutive_redirects = 0;
+                    }
                     ConnectionLossReason::DisconnectByServer(disconnect)
                 }
-                DisconnectedEvent::PingTimeout => ConnectionLossReason::PingTimeout,
-                DisconnectedEvent::IoError(io_err) => ConnectionLossReason::IoError(io_err),
+                DisconnectedEvent::PingTimeout => {
+                    consecutive_redirects = 0;
+                    ConnectionLossReason::PingTimeout
+                }
+                DisconnectedEvent::IoError(io_err) => {
+                    consecutive_redirects = 0;
+                    ConnectionLossReason::IoError(io_err)
+                }
                 DisconnectedEvent::ProtocolError(proto_err) => {
+                    consecutive_redirects = 0;
                     ConnectionLossReason::ProtocolError(proto_err)
                 }
             };
@@ -500,12 +818,110 @@ impl Session {
         }
     }
 
+    /// Issue the `SUBSCRIBE`s of all active subscriptions again after the MQTT session was lost,
+    /// and notify the application of the session loss.
+    ///
+    /// The `SUBSCRIBE`s are issued from a separate task, which logs any that fail.
+    fn resubscribe(&self) {
+        let subscriptions = self.subscriptions.lock().unwrap().snapshot();
+        log::info!(
+            "Continuing with new MQTT session, resubscribing to {} topic filter(s)",
+            subscriptions.len()
+        );
+        let event = ConnectionEvent::SessionLost {
+            resubscribed: subscriptions.len(),
+        };
+        let managed_client = self.create_managed_client();
+        tokio::task::spawn(async move {
+            for subscription in subscriptions {
+                let topic_filter = subscription.topic_filter.clone();
+                let result = match managed_client
+                    .issue_subscribe(
+                        subscription.topic_filter,
+                        subscription.max_qos,
+                        subscription.no_local,
+                        subscription.retain_options,
+                        subscription.properties,
+                    )
+                    .await
+                {
+                    Ok(token) => token.await.map(|_| ()).map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = result {
+                    log::warn!("Failed to resubscribe to {topic_filter:?}: {e}");
+                }
+            }
+        });
+        self.state.notify_session_lost();
+        // NOTE: An error only indicates that there are currently no receivers, which is fine
+        let _ = self.connection_events_tx.send(event);
+    }
+
+    /// Follow a redirect to the server identified by the `server_reference`, if allowed by the
+    /// redirect policy, updating the server address used for subsequent connection attempts.
+    ///
+    /// Returns true if the redirect was followed.
+    fn follow_redirect(
+        &mut self,
+        permanent: bool,
+        server_reference: Option<String>,
+        consecutive_redirects: &mut u32,
+    ) -> bool {
+        let from = self.connect_parameters.server_address();
+        let target = server_reference
+            .as_deref()
+            .and_then(|sr| ServerAddress::from_server_reference(sr, from.tcp_port))
+            .ok_or(RedirectRejection::InvalidServerReference)
+            .and_then(|to| {
+                self.redirect_policy
+                    .check(&self.configured_hostname, &to.hostname)?;
+                if *consecutive_redirects >= self.redirect_policy.max_redirects {
+                    return Err(RedirectRejection::MaxRedirectsReached);
+                }
+                Ok(to)
+            });
+        let event = match target {
+            Ok(to) => {
+                log::info!("Redirected by server from {from} to {to} (permanent={permanent})");
+                *consecutive_redirects += 1;
+                self.connect_parameters.set_server_address(to.clone());
+                ConnectionEvent::Redirected {
+                    from,
+                    to,
+                    permanent,
+                }
+            }
+            Err(reason) => {
+                log::warn!(
+                    "Not following redirect from {from} to server reference {server_reference:?}: {reason}"
+                );
+                ConnectionEvent::RedirectRejected {
+                    from,
+                    server_reference,
+                    reason,
+                }
+            }
+        };
+        let followed = matches!(event, ConnectionEvent::Redirected { .. });
+        // NOTE: An error only indicates that there are currently no receivers, which is fine
+        let _ = self.connection_events_tx.send(event);
+        followed
+    }
+
     /// Helper for connecting
     async fn connect(
         &mut self,
         connection_transport: ConnectionTransportConfig,
         clean_start: bool,
     ) -> Result<(Connection, ConnAck), azure_mqtt::error::ConnectError> {
+        // NOTE: Credentials are obtained on every connection attempt so that they are always fresh
+        let (username, password) = self
+            .connect_parameters
+            .credentials()
+            .await
+            .map_err(|e| azure_mqtt::error::ConnectError::Credentials(Box::new(e)))?;
+
         let ch = self
             .connect_handle
             .take()
@@ -523,8 +939,8 @@ impl Session {
                     clean_start,
                     self.connect_parameters.keep_alive,
                     self.connect_parameters.will.clone(),
-                    self.connect_parameters.username.clone(),
-                    self.connect_parameters.password.clone(),
+                    username,
+                    password,
                     self.connect_parameters.connect_properties.clone(),
                     authentication_info,
                     Some(self.connect_parameters.connection_timeout),
@@ -561,8 +977,8 @@ impl Session {
                     clean_start,
                     self.connect_parameters.keep_alive,
                     self.connect_parameters.will.clone(),
-                    self.connect_parameters.username.clone(),
-                    self.connect_parameters.password.clone(),
+                    username,
+                    password,
                     self.connect_parameters.connect_properties.clone(),
                     Some(self.connect_parameters.connection_timeout),
                 )
@@ -688,6 +1104,14 @@ impl Session {
         }
     }
 }
+impl Drop for Session {
+    fn drop(&mut self) {
+        // The Session is dropped at the end of `run`, once the MQTT client, receiver and
+        // connection resources held by it are released
+        self.state.transition_stopped();
+    }
+}
+
 /// Handle used to end an MQTT session.
 #[derive(Clone)]
 pub struct SessionExitHandle {
@@ -695,6 +1119,8 @@ pub struct SessionExitHandle {
     disconnect_handle: Weak<Mutex<Option<azure_mqtt::client::DisconnectHandle>>>,
     /// Notifier for force exit
     force_exit: Arc<Notify>,
+    /// Current state of the Session
+    state: Arc<SessionState>,
 }
 
 impl SessionExitHandle {
@@ -761,6 +1187,17 @@ impl SessionExitHandle {
             }
         }
     }
+
+    /// Wait until the [`Session`] that created this handle has fully stopped.
+    /// Returns immediately if it has already stopped.
+    ///
+    /// The [`Session`] has stopped once [`Session::run()`] has returned and the resources held by
+    /// the [`Session`] have been released, or once the [`Session`] was dropped without running.
+    /// Use this after [`try_exit`](Self::try_exit) or [`force_exit`](Self::force_exit) to
+    /// sequence further shutdown steps when the result of [`Session::run()`] is awaited elsewhere.
+    pub async fn wait_until_stopped(&self) {
+        self.state.condition_stopped().await;
+    }
 }
 
 /// Monitor for session state changes in the [`Session`].
@@ -769,6 +1206,10 @@ impl SessionExitHandle {
 #[derive(Clone)]
 pub struct SessionMonitor {
     state: Arc<SessionState>,
+    /// Underlying MQTT client, used for outgoing PUBLISH statistics
+    client: azure_mqtt::client::Client,
+    /// Sender for connection events, used to create new receivers
+    connection_events_tx: broadcast::Sender<ConnectionEvent>,
 }
 
 impl SessionMonitor {
@@ -790,4 +1231,76 @@ impl SessionMonitor {
     pub async fn disconnected(&self) {
         self.state.condition_disconnected().await;
     }
+
+    /// Wait until the server next discards the MQTT session of the [`Session`] on a reconnect.
+    ///
+    /// Any state held by the server for the lost MQTT session (e.g. messages queued for delivery)
+    /// is gone. Unless disabled in the [`SessionOptions`], the [`Session`] continues with the new
+    /// MQTT session and issues the `SUBSCRIBE`s of the subscriptions made through
+    /// [`SessionManagedClient`]s again.
+    ///
+    /// The returned future is registered for the notification when this method is called, so a
+    /// session loss that occurs before it is first awaited is not missed.
+    pub fn session_lost(&self) -> impl Future<Output = ()> + Send + '_ {
+        self.state.session_lost_notified()
+    }
+
+    /// Returns the cause of the most recent disconnect of the [`Session`], or of the most recent
+    /// failed attempt to reconnect it while disconnected.
+    ///
+    /// Returns `None` if the [`Session`] has not disconnected or failed to connect yet.
+    #[must_use]
+    pub fn last_disconnect_cause(&self) -> Option<DisconnectCause> {
+        self.state.last_disconnect_cause()
+    }
+
+    /// Wait until the [`Session`] is disconnected, returning the [`DisconnectCause`].
+    /// Returns immediately if already disconnected and a cause is known.
+    ///
+    /// Note that while the [`Session`] is disconnected, the cause may be updated by failed
+    /// attempts to reconnect, see [`SessionMonitor::last_disconnect_cause`].
+    pub async fn disconnected_with_cause(&self) -> DisconnectCause {
+        self.state.condition_disconnected_with_cause().await
+    }
+
+    /// Returns a snapshot of the outgoing PUBLISH flow-control state of the [`Session`],
+    /// including the number of in-flight and queued PUBLISHes and their high water marks.
+    ///
+    /// These values can be used to tune the `publish_qos0_queue_size` and
+    /// `publish_qos1_qos2_queue_size` [`SessionOptions`], as well as the server's receive maximum.
+    #[must_use]
+    pub fn publish_stats(&self) -> PublishStats {
+        self.client.publish_stats()
+    }
+
+    /// Returns the details of the TLS session negotiated for the most recent connection of the
+    /// [`Session`], including the protocol version, cipher suite and the certificate chain
+    /// presented by the MQTT server. The details are updated on each reconnect.
+    ///
+    /// Returns `None` before the first connection or if the connection does not use TLS.
+    #[must_use]
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.state.tls_info()
+    }
+
+    /// Returns the properties of the CONNACK of the most recent connection of the [`Session`],
+    /// including the limits negotiated with the MQTT server such as the maximum packet size,
+    /// receive maximum, topic alias maximum and server keep alive. The properties are updated on
+    /// each reconnect.
+    ///
+    /// Returns `None` before the first connection.
+    #[must_use]
+    pub fn connack_properties(&self) -> Option<ConnAckProperties> {
+        self.state.connack_properties()
+    }
+
+    /// Returns a receiver for the [`ConnectionEvent`]s of the [`Session`] that occur after this
+    /// call, such as server redirects.
+    ///
+    /// If the receiver falls too far behind, the oldest events are dropped and the receiver
+    /// reports that it has lagged.
+    #[must_use]
+    pub fn connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.connection_events_tx.subscribe()
+    }
 }
@@ -0,0 +1,94 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Credential providers for username/password authentication of a [`Session`](crate::session::Session).
+
+use std::fmt::Debug;
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Trait defining interface for providers of username/password credentials.
+///
+/// Used as an alternative to static credentials in
+/// [`MqttConnectionSettings`](crate::aio::connection_settings::MqttConnectionSettings) when the
+/// credentials are short-lived and minted by an external identity provider.
+/// The provider is invoked before every connection attempt (including reconnects), so that fresh
+/// credentials are always used.
+#[async_trait::async_trait]
+pub trait AuthProvider: Debug + Send + Sync {
+    /// Return the [`Credentials`] to use for the next MQTT connection attempt.
+    ///
+    /// # Errors
+    /// Returns an [`AuthError`] if the credentials could not be obtained. This is treated as a
+    /// connection failure, and is provided to the
+    /// [`ReconnectPolicy`](crate::session::reconnect_policy::ReconnectPolicy) of the `Session`.
+    async fn credentials(&self) -> Result<Credentials, AuthError>;
+}
+
+/// Username/password credentials returned by an [`AuthProvider`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Credentials {
+    /// Username for MQTT
+    pub username: Option<String>,
+    /// Password for MQTT
+    pub password: Option<String>,
+    /// Time at which the credentials expire, if known
+    pub expiry: Option<DateTime<Utc>>,
+}
+
+impl Credentials {
+    /// Returns true if the credentials have an expiry that has already passed.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= Utc::now())
+    }
+}
+
+/// Error obtaining [`Credentials`] from an [`AuthProvider`].
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct AuthError {
+    message: String,
+    #[source]
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+}
+
+impl AuthError {
+    /// Create a new [`AuthError`] with the provided message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Create a new [`AuthError`] with the provided message, caused by the provided `source` error.
+    pub fn with_source(
+        message: impl Into<String>,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, false; "no expiry")]
+    #[test_case(Some(Utc::now() + chrono::Duration::hours(1)), false; "future expiry")]
+    #[test_case(Some(Utc::now() - chrono::Duration::hours(1)), true; "past expiry")]
+    fn credentials_is_expired(expiry: Option<DateTime<Utc>>, expected: bool) {
+        let credentials = Credentials {
+            username: Some("username".to_string()),
+            password: Some("password".to_string()),
+            expiry,
+        };
+        assert_eq!(credentials.is_expired(), expected);
+    }
+}
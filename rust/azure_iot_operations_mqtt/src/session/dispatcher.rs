@@ -6,6 +6,7 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
+    num::NonZeroU32,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -58,10 +59,40 @@ impl Future for AckCompletionToken {
 pub type PublishTx = UnboundedSender<(Publish, Option<AckToken>)>;
 pub type PublishRx = UnboundedReceiver<(Publish, Option<AckToken>)>;
 
-#[derive(Default)]
+/// Maximum value of an MQTT subscription identifier
+const MAX_SUBSCRIPTION_IDENTIFIER: u32 = 268_435_455;
+
+/// Receivers for a topic filter, along with the subscription identifier assigned to the filter
+struct FilteredReceivers {
+    txs: Vec<PublishTx>,
+    /// Subscription identifier assigned to the topic filter
+    subscription_identifier: NonZeroU32,
+    /// Whether the topic filter has been subscribed with its assigned subscription identifier
+    subscribed_with_identifier: bool,
+}
+
 pub struct IncomingPublishDispatcher {
-    filtered_txs: HashMap<TopicFilter, Vec<PublishTx>>,
+    filtered_txs: HashMap<TopicFilter, FilteredReceivers>,
     unfiltered_txs: Vec<PublishTx>,
+    /// Topic filters by their assigned subscription identifier
+    subscription_identifiers: HashMap<NonZeroU32, TopicFilter>,
+    /// Next subscription identifier to assign
+    next_subscription_identifier: NonZeroU32,
+    /// Whether the server supports subscription identifiers
+    subscription_identifiers_available: bool,
+}
+
+impl Default for IncomingPublishDispatcher {
+    fn default() -> Self {
+        Self {
+            filtered_txs: HashMap::new(),
+            unfiltered_txs: Vec::new(),
+            subscription_identifiers: HashMap::new(),
+            next_subscription_identifier: NonZeroU32::MIN,
+            // Unknown until the CONNACK is received
+            subscription_identifiers_available: false,
+        }
+    }
 }
 
 impl IncomingPublishDispatcher {
@@ -82,20 +113,76 @@ impl IncomingPublishDispatcher {
         self.prune_filtered_txs();
 
         let (tx, rx) = unbounded_channel();
-        match self.filtered_txs.get_mut(&topic_filter) {
+        if let Some(receivers) = self.filtered_txs.get_mut(&topic_filter) {
             // If the topic filter is already in use, add to the associated vector
-            Some(v) => {
-                v.push(tx);
-                // Otherwise, create a new vector and add
-            }
-            _ => {
-                self.filtered_txs.insert(topic_filter, vec![tx]);
-            }
+            receivers.txs.push(tx);
+        } else {
+            // Otherwise, assign a subscription identifier and create a new vector and add
+            let subscription_identifier = self.assign_subscription_identifier();
+            self.subscription_identifiers
+                .insert(subscription_identifier, topic_filter.clone());
+            self.filtered_txs.insert(
+                topic_filter,
+                FilteredReceivers {
+                    txs: vec![tx],
+                    subscription_identifier,
+                    subscribed_with_identifier: false,
+                },
+            );
         }
 
         rx
     }
 
+    /// Set whether the server supports subscription identifiers, as indicated by the CONNACK.
+    pub fn set_subscription_identifiers_available(&mut self, available: bool) {
+        self.subscription_identifiers_available = available;
+    }
+
+    /// Get the subscription identifier to use when subscribing to the provided topic filter.
+    ///
+    /// Returns `None` if the server does not support subscription identifiers, or if there are no
+    /// receivers for the topic filter. Otherwise, the topic filter is recorded as subscribed with
+    /// the returned identifier, so that publishes carrying it can be routed by identifier.
+    pub fn subscription_identifier_for_subscribe(
+        &mut self,
+        topic_filter: &TopicFilter,
+    ) -> Option<NonZeroU32> {
+        if !self.subscription_identifiers_available {
+            return None;
+        }
+        let receivers = self.filtered_txs.get_mut(topic_filter)?;
+        receivers.subscribed_with_identifier = true;
+        Some(receivers.subscription_identifier)
+    }
+
+    /// Record that the provided topic filter has been subscribed without its assigned
+    /// subscription identifier (e.g. with one provided by the user).
+    pub fn subscribed_without_identifier(&mut self, topic_filter: &TopicFilter) {
+        if let Some(receivers) = self.filtered_txs.get_mut(topic_filter) {
+            receivers.subscribed_with_identifier = false;
+        }
+    }
+
+    /// Assign the next subscription identifier that is not currently in use
+    fn assign_subscription_identifier(&mut self) -> NonZeroU32 {
+        loop {
+            let subscription_identifier = self.next_subscription_identifier;
+            self.next_subscription_identifier =
+                if subscription_identifier.get() >= MAX_SUBSCRIPTION_IDENTIFIER {
+                    NonZeroU32::MIN
+                } else {
+                    subscription_identifier.saturating_add(1)
+                };
+            if !self
+                .subscription_identifiers
+                .contains_key(&subscription_identifier)
+            {
+                return subscription_identifier;
+            }
+        }
+    }
+
     /// Create a new [`PublishRx`] that will receive all dispatched [`Publish`]es that do not
     /// match the topic filters for any other filtered [`PublishRx`]s, for as long as it
     /// is open.
@@ -145,6 +232,36 @@ impl IncomingPublishDispatcher {
         num_dispatches
     }
 
+    /// Get the topic filters to route the publish to based on its subscription identifiers.
+    ///
+    /// Returns `None` if the publish cannot be routed by subscription identifier, in which case
+    /// it must be routed by matching its topic name against the topic filters. Routing by
+    /// identifier is only possible when every topic filter with receivers was subscribed with
+    /// its assigned identifier, and every identifier on the publish is known. Otherwise, a
+    /// receiver whose subscription matched the publish could be missed.
+    fn topic_filters_by_subscription_identifier(
+        &self,
+        publish: &Publish,
+    ) -> Option<Vec<TopicFilter>> {
+        if !self.subscription_identifiers_available
+            || publish.properties.subscription_identifiers.is_empty()
+            || !self
+                .filtered_txs
+                .values()
+                .all(|receivers| receivers.subscribed_with_identifier)
+        {
+            return None;
+        }
+        let mut topic_filters: Vec<TopicFilter> = Vec::new();
+        for subscription_identifier in &publish.properties.subscription_identifiers {
+            let topic_filter = self.subscription_identifiers.get(subscription_identifier)?;
+            if !topic_filters.contains(topic_filter) {
+                topic_filters.push(topic_filter.clone());
+            }
+        }
+        Some(topic_filters)
+    }
+
     /// Dispatch to filtered receivers
     fn dispatch_filtered(
         &mut self,
@@ -154,12 +271,17 @@ impl IncomingPublishDispatcher {
         let mut num_dispatches = 0;
         let mut closed = vec![]; // (topic filter, position in vector)
 
-        let filtered = self
-            .filtered_txs
-            .iter()
-            .filter(|(topic_filter, _)| topic_filter.matches_topic_name(&publish.topic_name));
-        for (topic_filter, v) in filtered {
-            for (pos, tx) in v.iter().enumerate() {
+        // Route by subscription identifier when possible, falling back to topic filter matching
+        let routed_topic_filters = self.topic_filters_by_subscription_identifier(publish);
+        let filtered =
+            self.filtered_txs
+                .iter()
+                .filter(|(topic_filter, _)| match &routed_topic_filters {
+                    Some(routed_topic_filters) => routed_topic_filters.contains(topic_filter),
+                    None => topic_filter.matches_topic_name(&publish.topic_name),
+                });
+        for (topic_filter, receivers) in filtered {
+            for (pos, tx) in receivers.txs.iter().enumerate() {
                 // Send the publish to the receiver, along with an ack token
                 // If the receiver is closed, add it to the list of closed receivers to remove after iteration.
                 // NOTE: Removing closed receivers must be done dynamically because the awaitable send allows
@@ -176,9 +298,11 @@ impl IncomingPublishDispatcher {
         // Remove any closed receivers.
         // NOTE: Do this in reverse order to avoid index issues.
         for (topic_filter, pos) in closed.iter().rev() {
-            if let Some(v) = self.filtered_txs.get_mut(topic_filter) {
-                v.remove(*pos);
-                if v.is_empty() {
+            if let Some(receivers) = self.filtered_txs.get_mut(topic_filter) {
+                receivers.txs.remove(*pos);
+                if receivers.txs.is_empty() {
+                    self.subscription_identifiers
+                        .remove(&receivers.subscription_identifier);
                     self.filtered_txs.remove(topic_filter);
                 }
             }
@@ -223,9 +347,15 @@ impl IncomingPublishDispatcher {
     /// Note that the runtime is O(c * m) and not O(n * m) as it may seem.
     /// (c = capacity, m = max number of duplicate listeners on a filter, n = number of filters).
     fn prune_filtered_txs(&mut self) {
-        self.filtered_txs.retain(|_, v| {
-            v.retain(|tx| !tx.is_closed());
-            !v.is_empty()
+        let subscription_identifiers = &mut self.subscription_identifiers;
+        self.filtered_txs.retain(|_, receivers| {
+            receivers.txs.retain(|tx| !tx.is_closed());
+            if receivers.txs.is_empty() {
+                subscription_identifiers.remove(&receivers.subscription_identifier);
+                false
+            } else {
+                true
+            }
         });
     }
 }
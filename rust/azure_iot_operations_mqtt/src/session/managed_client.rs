@@ -12,6 +12,7 @@ use crate::control_packet::{
 };
 use crate::error::DetachedError;
 use crate::session::dispatcher::{AckToken, IncomingPublishDispatcher, PublishRx};
+use crate::session::subscriptions::{ActiveSubscriptions, Subscription};
 use crate::token::{
     PublishQoS0CompletionToken, PublishQoS1CompletionToken, SubscribeCompletionToken,
     UnsubscribeCompletionToken,
@@ -19,6 +20,10 @@ use crate::token::{
 
 /// An MQTT client that has it's connection state externally managed by a [`Session`](super::Session).
 /// Can be used to send messages and create receivers for incoming messages.
+///
+/// Operations are issued with the packet properties exactly as provided, so this client can also
+/// be used directly for low-level MQTT operations. See the
+/// [module documentation](super#low-level-mqtt-operations) for details.
 #[derive(Clone)]
 pub struct SessionManagedClient {
     // Client ID of the `Session` that manages this client
@@ -27,6 +32,8 @@ pub struct SessionManagedClient {
     pub(crate) client: crate::azure_mqtt::client::Client,
     /// Manager for receivers
     pub(crate) dispatcher: Arc<Mutex<IncomingPublishDispatcher>>,
+    /// Subscriptions to replay if the MQTT session is lost
+    pub(crate) subscriptions: Arc<Mutex<ActiveSubscriptions>>,
 }
 
 impl SessionManagedClient {
@@ -119,12 +126,25 @@ impl SessionManagedClient {
     /// If connection is unavailable, `SUBSCRIBE` will be queued and delivered when connection is
     /// re-established. Blocks if at capacity for queueing.
     ///
+    /// If no [`subscription_identifier`](SubscribeProperties::subscription_identifier) is provided,
+    /// the server supports subscription identifiers, and a filtered receiver exists for the topic
+    /// filter, the `SUBSCRIBE` is issued with the subscription identifier assigned to the topic
+    /// filter by the Session. Incoming `PUBLISH`es are then routed to receivers by their
+    /// subscription identifiers instead of by topic filter matching whenever possible.
+    ///
+    /// The subscription is tracked by the Session until the topic filter is unsubscribed, so that
+    /// it can be issued again if the MQTT session is lost (see
+    /// [`SessionOptions`](super::SessionOptions)).
+    ///
     /// Returns a token that can be awaited to indicate the result of the completion of the
     /// `SUBSCRIBE` operation (i.e. when the corresponding SUBACK is received from the server).
     ///
     /// # Errors
     /// Returns a [`DetachedError`] if the `SUBSCRIBE` could not be issued due to being detached from
     /// the Session
+    ///
+    /// # Panics
+    /// Panics if internal state is invalid (this should not be possible).
     pub async fn subscribe(
         &self,
         topic_filter: TopicFilter,
@@ -133,6 +153,36 @@ impl SessionManagedClient {
         retain_options: RetainOptions,
         properties: SubscribeProperties,
     ) -> Result<SubscribeCompletionToken, DetachedError> {
+        self.subscriptions.lock().unwrap().insert(Subscription {
+            topic_filter: topic_filter.clone(),
+            max_qos,
+            no_local,
+            retain_options: retain_options.clone(),
+            properties: properties.clone(),
+        });
+        self.issue_subscribe(topic_filter, max_qos, no_local, retain_options, properties)
+            .await
+    }
+
+    /// Issue the `SUBSCRIBE` for a subscription, assigning it a subscription identifier if needed.
+    /// Used both for new subscriptions and to replay tracked subscriptions after a session loss.
+    pub(crate) async fn issue_subscribe(
+        &self,
+        topic_filter: TopicFilter,
+        max_qos: QoS,
+        no_local: bool,
+        retain_options: RetainOptions,
+        mut properties: SubscribeProperties,
+    ) -> Result<SubscribeCompletionToken, DetachedError> {
+        {
+            let mut dispatcher = self.dispatcher.lock().unwrap();
+            if properties.subscription_identifier.is_some() {
+                dispatcher.subscribed_without_identifier(&topic_filter);
+            } else {
+                properties.subscription_identifier =
+                    dispatcher.subscription_identifier_for_subscribe(&topic_filter);
+            }
+        }
         self.client
             .subscribe(topic_filter, max_qos, no_local, retain_options, properties)
             .await
@@ -149,16 +199,23 @@ impl SessionManagedClient {
     /// # Errors
     /// Returns a [`DetachedError`] if the `UNSUBSCRIBE` could not be issued due to being detached
     /// from the Session
+    ///
+    /// # Panics
+    /// Panics if internal state is invalid (this should not be possible).
     pub async fn unsubscribe(
         &self,
         topic_filter: TopicFilter,
         properties: UnsubscribeProperties,
     ) -> Result<UnsubscribeCompletionToken, DetachedError> {
+        self.subscriptions.lock().unwrap().remove(&topic_filter);
         self.client.unsubscribe(topic_filter, properties).await
     }
 }
 
 /// Receive and acknowledge incoming [`Publish`]es
+///
+/// The subscription identifiers of the subscriptions that matched a received [`Publish`], if any,
+/// are available in its [`subscription_identifiers`](PublishProperties::subscription_identifiers).
 pub struct SessionPubReceiver {
     /// Receiver for incoming publishes
     pub_rx: PublishRx,
@@ -178,6 +235,14 @@ impl SessionPubReceiver {
         self.pub_rx.recv().await
     }
 
+    /// Receive the next incoming [`Publish`] delivered to this receiver, along with an
+    /// [`AckToken`] if received at QoS 1, only if one has already been delivered.
+    /// Returns `None` if there is no [`Publish`] waiting to be received.
+    /// The [`AckToken`] can be used to manually acknowledge the [`Publish`].
+    pub fn try_recv_manual_ack(&mut self) -> Option<(Publish, Option<AckToken>)> {
+        self.pub_rx.try_recv().ok()
+    }
+
     /// Close this receiver, dropping all undelivered [`Publish`]es.
     /// Any [`Publish`]es undelivered that required acknowledgement will be automatically
     /// acknowledged on drop.
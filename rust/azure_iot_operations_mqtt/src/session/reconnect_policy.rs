@@ -22,6 +22,16 @@ pub enum ConnectionLossReason {
     ProtocolError(ProtocolError),
 }
 
+/// Progress of the reconnect attempts since the [`Session`](crate::session::Session) was started
+/// or last lost its connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReconnectAttempt {
+    /// Number of failed connect attempts, including the one that just failed.
+    pub prev_attempts: u32,
+    /// Time elapsed since the first connect attempt, or since the connection was lost.
+    pub elapsed: Duration,
+}
+
 /// Trait defining interface for reconnect policies.
 pub trait ReconnectPolicy: Send {
     /// Get the next reconnect delay after a failure to connect.
@@ -32,6 +42,20 @@ pub trait ReconnectPolicy: Send {
         error: &ConnectError,
     ) -> Option<Duration>;
 
+    /// Get the next reconnect delay after a failure to connect, given the progress of the
+    /// reconnect attempts. This is the method invoked by the [`Session`](crate::session::Session).
+    /// Returns None if no reconnect should be attempted.
+    ///
+    /// Defaults to [`ReconnectPolicy::connect_failure_reconnect_delay`] with the number of
+    /// previous attempts. Override it to make decisions based on the elapsed time.
+    fn reconnect_attempt_delay(
+        &self,
+        attempt: &ReconnectAttempt,
+        error: &ConnectError,
+    ) -> Option<Duration> {
+        self.connect_failure_reconnect_delay(attempt.prev_attempts, error)
+    }
+
     /// Get the next reconnect delay after a connection loss.
     /// Returns None if no reconnect should be attempted.
     fn connection_loss_reconnect_delay(&self, reason: &ConnectionLossReason) -> Option<Duration>;
@@ -104,3 +128,175 @@ impl ReconnectPolicy for ExponentialBackoffWithJitter {
         Some(Duration::from_secs(0))
     }
 }
+
+/// A reconnect policy that waits a fixed interval between reconnect attempts.
+#[derive(Clone)]
+pub struct FixedInterval {
+    /// The time to wait between reconnect attempts.
+    pub interval: Duration,
+    /// The max number of reconnect attempts before giving up.
+    pub max_reconnect_attempts: Option<u32>,
+}
+
+impl Default for FixedInterval {
+    /// Indefinite reconnect, with a wait time of 5 seconds.
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_reconnect_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy for FixedInterval {
+    fn connect_failure_reconnect_delay(
+        &self,
+        prev_attempts: u32,
+        _error: &ConnectError,
+    ) -> Option<Duration> {
+        match self.max_reconnect_attempts {
+            Some(max_attempts) if prev_attempts >= max_attempts => None,
+            _ => Some(self.interval),
+        }
+    }
+
+    fn connection_loss_reconnect_delay(&self, _reason: &ConnectionLossReason) -> Option<Duration> {
+        Some(Duration::from_secs(0))
+    }
+}
+
+/// A reconnect policy that gives up once a cap on the reconnect attempts or on the time spent
+/// reconnecting is reached, and otherwise defers to an inner policy.
+///
+/// Giving up causes [`Session::run`](crate::session::Session::run) to return a
+/// [`SessionErrorKind::ReconnectHalted`](crate::session::SessionErrorKind::ReconnectHalted) error.
+pub struct CappedRetry {
+    /// The policy determining the delay between reconnect attempts while within the caps.
+    pub inner: Box<dyn ReconnectPolicy>,
+    /// The max number of reconnect attempts before giving up.
+    pub max_reconnect_attempts: Option<u32>,
+    /// The max time spent reconnecting, after which no further reconnect is attempted.
+    pub max_elapsed: Option<Duration>,
+}
+
+impl CappedRetry {
+    /// Create a new [`CappedRetry`] policy using [`ExponentialBackoffWithJitter`] between
+    /// attempts, giving up after `max_reconnect_attempts` attempts or after `max_elapsed` has passed.
+    #[must_use]
+    pub fn new(max_reconnect_attempts: Option<u32>, max_elapsed: Option<Duration>) -> Self {
+        Self {
+            inner: Box::new(ExponentialBackoffWithJitter::default()),
+            max_reconnect_attempts,
+            max_elapsed,
+        }
+    }
+}
+
+impl ReconnectPolicy for CappedRetry {
+    fn connect_failure_reconnect_delay(
+        &self,
+        prev_attempts: u32,
+        error: &ConnectError,
+    ) -> Option<Duration> {
+        self.reconnect_attempt_delay(
+            &ReconnectAttempt {
+                prev_attempts,
+                elapsed: Duration::ZERO,
+            },
+            error,
+        )
+    }
+
+    fn reconnect_attempt_delay(
+        &self,
+        attempt: &ReconnectAttempt,
+        error: &ConnectError,
+    ) -> Option<Duration> {
+        if self
+            .max_reconnect_attempts
+            .is_some_and(|max_attempts| attempt.prev_attempts >= max_attempts)
+        {
+            return None;
+        }
+        let delay = self.inner.reconnect_attempt_delay(attempt, error)?;
+        // Don't wait for an attempt that would start after the time cap
+        match self.max_elapsed {
+            Some(max_elapsed) if attempt.elapsed + delay >= max_elapsed => None,
+            _ => Some(delay),
+        }
+    }
+
+    fn connection_loss_reconnect_delay(&self, reason: &ConnectionLossReason) -> Option<Duration> {
+        self.inner.connection_loss_reconnect_delay(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect_error() -> ConnectError {
+        ConnectError::Io(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+    }
+
+    #[test]
+    fn fixed_interval() {
+        let policy = FixedInterval {
+            interval: Duration::from_secs(2),
+            max_reconnect_attempts: Some(3),
+        };
+        for prev_attempts in 1..3 {
+            assert_eq!(
+                policy.connect_failure_reconnect_delay(prev_attempts, &connect_error()),
+                Some(Duration::from_secs(2))
+            );
+        }
+        assert_eq!(
+            policy.connect_failure_reconnect_delay(3, &connect_error()),
+            None
+        );
+    }
+
+    #[test]
+    fn capped_retry_max_attempts() {
+        let policy = CappedRetry {
+            inner: Box::new(FixedInterval::default()),
+            max_reconnect_attempts: Some(2),
+            max_elapsed: None,
+        };
+        let attempt = |prev_attempts| ReconnectAttempt {
+            prev_attempts,
+            elapsed: Duration::from_secs(3600),
+        };
+        assert_eq!(
+            policy.reconnect_attempt_delay(&attempt(1), &connect_error()),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policy.reconnect_attempt_delay(&attempt(2), &connect_error()),
+            None
+        );
+    }
+
+    #[test]
+    fn capped_retry_max_elapsed() {
+        let policy = CappedRetry {
+            inner: Box::new(FixedInterval::default()),
+            max_reconnect_attempts: None,
+            max_elapsed: Some(Duration::from_secs(60)),
+        };
+        let attempt = |elapsed| ReconnectAttempt {
+            prev_attempts: 100,
+            elapsed: Duration::from_secs(elapsed),
+        };
+        assert_eq!(
+            policy.reconnect_attempt_delay(&attempt(54), &connect_error()),
+            Some(Duration::from_secs(5))
+        );
+        // The next attempt would start at the cap
+        assert_eq!(
+            policy.reconnect_attempt_delay(&attempt(55), &connect_error()),
+            None
+        );
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Redirect policies for a [`Session`](crate::session::Session).
+//!
+//! An MQTT server can redirect a client to another server by refusing a connection or
+//! disconnecting with a reason of "Use Another Server" (temporary) or "Server Moved" (permanent),
+//! along with a Server Reference identifying the other server. The [`RedirectPolicy`] determines
+//! which of these redirects the [`Session`](crate::session::Session) will follow.
+
+use std::fmt;
+
+/// Default maximum number of consecutive redirects that will be followed
+pub const DEFAULT_MAX_REDIRECTS: u32 = 3;
+
+/// Address of an MQTT server
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ServerAddress {
+    /// Hostname or IP address of the server
+    pub hostname: String,
+    /// TCP port of the server
+    pub tcp_port: u16,
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hostname.contains(':') {
+            write!(f, "[{}]:{}", self.hostname, self.tcp_port)
+        } else {
+            write!(f, "{}:{}", self.hostname, self.tcp_port)
+        }
+    }
+}
+
+impl ServerAddress {
+    /// Parse the address of the server identified by a Server Reference.
+    ///
+    /// The Server Reference may contain several references separated by spaces, in which case
+    /// only the first one is used. A reference consists of a hostname or IP address, optionally
+    /// followed by a port (IPv6 addresses with a port must be enclosed in brackets). If no port
+    /// is specified, `default_port` is used.
+    ///
+    /// Returns `None` if the Server Reference is not valid.
+    #[must_use]
+    pub fn from_server_reference(server_reference: &str, default_port: u16) -> Option<Self> {
+        let reference = server_reference.split_whitespace().next()?;
+        let (hostname, tcp_port) = if let Some(rest) = reference.strip_prefix('[') {
+            // Bracketed IPv6 address, with an optional port
+            let (hostname, rest) = rest.split_once(']')?;
+            let tcp_port = match rest {
+                "" => default_port,
+                _ => rest.strip_prefix(':')?.parse().ok()?,
+            };
+            (hostname, tcp_port)
+        } else if reference.matches(':').count() > 1 {
+            // Unbracketed IPv6 address, which cannot have a port
+            (reference, default_port)
+        } else if let Some((hostname, port)) = reference.split_once(':') {
+            (hostname, port.parse().ok()?)
+        } else {
+            (reference, default_port)
+        };
+        if hostname.is_empty()
+            || tcp_port == 0
+            || hostname
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '/' | '@' | '[' | ']'))
+        {
+            return None;
+        }
+        Some(Self {
+            hostname: hostname.to_ascii_lowercase(),
+            tcp_port,
+        })
+    }
+}
+
+/// Policy determining which server redirects a [`Session`](crate::session::Session) will follow.
+///
+/// A redirect to a host is rejected if the host matches an entry in `denied_hosts`, and is
+/// otherwise followed if it matches an entry in `allowed_hosts`, or if `allow_same_domain` is set
+/// and the host is in the same domain as the configured hostname of the
+/// [`Session`](crate::session::Session).
+///
+/// Host entries are either a hostname (e.g. `broker.contoso.com`), matched exactly, or a wildcard
+/// (e.g. `*.contoso.com`), matching any subdomain of the given domain. Matching is case-insensitive.
+///
+/// Credentials and TLS settings of the [`Session`](crate::session::Session) are used for the new
+/// server as well. The TLS server name (SNI) follows the new host, unless a TLS server name was
+/// explicitly set in the connection settings.
+#[derive(Clone, Debug)]
+pub struct RedirectPolicy {
+    /// The max number of consecutive redirects that will be followed.
+    /// Set to 0 to never follow redirects.
+    pub max_redirects: u32,
+    /// Follow redirects to hosts in the same domain as the configured hostname.
+    pub allow_same_domain: bool,
+    /// Hosts that redirects will be followed to, regardless of domain.
+    pub allowed_hosts: Vec<String>,
+    /// Hosts that redirects will never be followed to. Takes precedence over all other settings.
+    pub denied_hosts: Vec<String>,
+}
+
+impl Default for RedirectPolicy {
+    /// Follow up to 3 consecutive redirects, to hosts in the same domain only.
+    fn default() -> Self {
+        Self {
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            allow_same_domain: true,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// A policy that never follows redirects.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            max_redirects: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Determine if a redirect to the `target` host should be followed, given the `configured`
+    /// hostname of the [`Session`](crate::session::Session).
+    ///
+    /// # Errors
+    /// Returns a [`RedirectRejection`] describing why the redirect should not be followed.
+    pub fn check(&self, configured: &str, target: &str) -> Result<(), RedirectRejection> {
+        if self.max_redirects == 0 {
+            return Err(RedirectRejection::Disabled);
+        }
+        if self.denied_hosts.iter().any(|p| host_matches(p, target)) {
+            return Err(RedirectRejection::DeniedHost);
+        }
+        if self.allowed_hosts.iter().any(|p| host_matches(p, target))
+            || (self.allow_same_domain && same_domain(configured, target))
+        {
+            Ok(())
+        } else {
+            Err(RedirectRejection::HostNotAllowed)
+        }
+    }
+}
+
+/// Reason a redirect was not followed by a [`Session`](crate::session::Session)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectRejection {
+    /// The [`RedirectPolicy`] does not follow redirects
+    Disabled,
+    /// The Server Reference provided by the server is not a valid server address
+    InvalidServerReference,
+    /// The redirect target is on the deny list of the [`RedirectPolicy`]
+    DeniedHost,
+    /// The redirect target is not allowed by the [`RedirectPolicy`]
+    HostNotAllowed,
+    /// The max number of consecutive redirects of the [`RedirectPolicy`] has been reached
+    MaxRedirectsReached,
+}
+
+impl fmt::Display for RedirectRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectRejection::Disabled => write!(f, "redirects are disabled"),
+            RedirectRejection::InvalidServerReference => write!(f, "invalid server reference"),
+            RedirectRejection::DeniedHost => write!(f, "host is denied by the redirect policy"),
+            RedirectRejection::HostNotAllowed => {
+                write!(f, "host is not allowed by the redirect policy")
+            }
+            RedirectRejection::MaxRedirectsReached => {
+                write!(f, "max number of consecutive redirects reached")
+            }
+        }
+    }
+}
+
+/// Returns true if `host` matches the host `pattern`
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        host.strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+    } else {
+        host == pattern
+    }
+}
+
+/// Returns true if `target` is in the same domain as `configured`.
+///
+/// The domain of a hostname is everything after its first label (e.g. `contoso.com` for
+/// `broker.contoso.com`). Single-label hostnames and IP addresses only match themselves.
+fn same_domain(configured: &str, target: &str) -> bool {
+    let configured = configured.to_ascii_lowercase();
+    let target = target.to_ascii_lowercase();
+    if configured == target {
+        return true;
+    }
+    if configured.parse::<std::net::IpAddr>().is_ok() {
+        return false;
+    }
+    match configured.split_once('.') {
+        Some((_, domain)) if domain.contains('.') => host_matches(&format!("*.{domain}"), &target),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("broker2.contoso.com", 1883, Some(("broker2.contoso.com", 1883)); "hostname only")]
+    #[test_case("broker2.contoso.com:8883", 1883, Some(("broker2.contoso.com", 8883)); "hostname and port")]
+    #[test_case("Broker2.Contoso.com:8883", 1883, Some(("broker2.contoso.com", 8883)); "mixed case")]
+    #[test_case("broker2.contoso.com:8883 broker3.contoso.com", 1883, Some(("broker2.contoso.com", 8883)); "multiple references")]
+    #[test_case("10.0.0.2:8883", 1883, Some(("10.0.0.2", 8883)); "ipv4 and port")]
+    #[test_case("[fe80::1]:8883", 1883, Some(("fe80::1", 8883)); "bracketed ipv6 and port")]
+    #[test_case("[fe80::1]", 1883, Some(("fe80::1", 1883)); "bracketed ipv6")]
+    #[test_case("fe80::1", 1883, Some(("fe80::1", 1883)); "ipv6")]
+    #[test_case("", 1883, None; "empty")]
+    #[test_case(":8883", 1883, None; "port only")]
+    #[test_case("broker2.contoso.com:port", 1883, None; "invalid port")]
+    #[test_case("broker2.contoso.com:0", 1883, None; "zero port")]
+    #[test_case("mqtts://broker2.contoso.com", 1883, None; "url")]
+    #[test_case("[fe80::1]8883", 1883, None; "bracketed ipv6 missing colon")]
+    fn parse_server_reference(
+        server_reference: &str,
+        default_port: u16,
+        expected: Option<(&str, u16)>,
+    ) {
+        assert_eq!(
+            ServerAddress::from_server_reference(server_reference, default_port),
+            expected.map(|(hostname, tcp_port)| ServerAddress {
+                hostname: hostname.to_string(),
+                tcp_port,
+            })
+        );
+    }
+
+    #[test_case("broker.contoso.com", "broker2.contoso.com", Ok(()); "same domain")]
+    #[test_case("broker.contoso.com", "broker.contoso.com", Ok(()); "same host")]
+    #[test_case("broker.contoso.com", "a.b.contoso.com", Ok(()); "subdomain of same domain")]
+    #[test_case("broker.contoso.com", "broker.fabrikam.com", Err(RedirectRejection::HostNotAllowed); "different domain")]
+    #[test_case("broker.contoso.com", "evilcontoso.com", Err(RedirectRejection::HostNotAllowed); "domain suffix")]
+    #[test_case("broker.contoso.com", "contoso.com", Err(RedirectRejection::HostNotAllowed); "parent domain")]
+    #[test_case("contoso.com", "broker.com", Err(RedirectRejection::HostNotAllowed); "top level domain only")]
+    #[test_case("localhost", "otherhost", Err(RedirectRejection::HostNotAllowed); "single label")]
+    #[test_case("10.0.0.1", "10.0.0.2", Err(RedirectRejection::HostNotAllowed); "ip address")]
+    fn default_policy(configured: &str, target: &str, expected: Result<(), RedirectRejection>) {
+        assert_eq!(
+            RedirectPolicy::default().check(configured, target),
+            expected
+        );
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let policy = RedirectPolicy {
+            allowed_hosts: vec!["*.fabrikam.com".to_string(), "10.0.0.2".to_string()],
+            denied_hosts: vec![
+                "legacy.contoso.com".to_string(),
+                "*.internal.fabrikam.com".to_string(),
+            ],
+            ..RedirectPolicy::default()
+        };
+        let configured = "broker.contoso.com";
+        assert_eq!(policy.check(configured, "broker2.contoso.com"), Ok(()));
+        assert_eq!(policy.check(configured, "broker.fabrikam.com"), Ok(()));
+        assert_eq!(policy.check(configured, "BROKER.Fabrikam.com"), Ok(()));
+        assert_eq!(policy.check(configured, "10.0.0.2"), Ok(()));
+        assert_eq!(
+            policy.check(configured, "fabrikam.com"),
+            Err(RedirectRejection::HostNotAllowed)
+        );
+        assert_eq!(
+            policy.check(configured, "legacy.contoso.com"),
+            Err(RedirectRejection::DeniedHost)
+        );
+        assert_eq!(
+            policy.check(configured, "a.internal.fabrikam.com"),
+            Err(RedirectRejection::DeniedHost)
+        );
+
+        let policy = RedirectPolicy {
+            allow_same_domain: false,
+            ..policy
+        };
+        assert_eq!(
+            policy.check(configured, "broker2.contoso.com"),
+            Err(RedirectRejection::HostNotAllowed)
+        );
+        assert_eq!(policy.check(configured, "broker.fabrikam.com"), Ok(()));
+    }
+
+    #[test]
+    fn disabled_policy() {
+        assert_eq!(
+            RedirectPolicy::disabled().check("broker.contoso.com", "broker2.contoso.com"),
+            Err(RedirectRejection::Disabled)
+        );
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Graceful shutdown of a [`Session`] on process termination signals, such as the SIGTERM sent
+//! by Kubernetes to the containers of a pod that is being stopped.
+
+use std::io;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::session::{Session, SessionExitHandle, SessionMonitor};
+
+/// Default time allowed to drain and exit, which fits within the default Kubernetes termination
+/// grace period of 30 seconds
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(25);
+
+/// Fraction of the grace period (one over this divisor) reserved for exiting the Session after
+/// draining
+const EXIT_GRACE_PERIOD_DIVISOR: u32 = 5;
+
+/// Interval at which outgoing PUBLISHes are checked while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Signal that requested the shutdown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownSignal {
+    /// SIGTERM, as sent by Kubernetes when stopping a pod
+    Terminate,
+    /// SIGINT, or Ctrl+C on platforms other than Unix
+    Interrupt,
+}
+
+/// Outcome of a [`GracefulShutdown`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShutdownOutcome {
+    /// Whether all queued and in-flight outgoing PUBLISHes were acknowledged before exiting
+    pub drained: bool,
+    /// Whether the [`Session`] exited gracefully, ending the MQTT session on the server
+    pub graceful: bool,
+    /// Whether the [`Session`] stopped within the grace period
+    pub stopped: bool,
+}
+
+/// Ends a [`Session`] cleanly when the process is asked to terminate.
+///
+/// On shutdown, outgoing PUBLISHes that are queued or awaiting acknowledgement are given the
+/// chance to complete, and then the [`Session`] is exited gracefully if connected, or forcefully
+/// otherwise. All of this completes within the grace period, after which the exit is forced.
+#[derive(Clone)]
+pub struct GracefulShutdown {
+    exit_handle: SessionExitHandle,
+    session_monitor: SessionMonitor,
+    grace_period: Duration,
+}
+
+impl GracefulShutdown {
+    /// Creates a [`GracefulShutdown`] for the given [`Session`] that completes within `grace_period`.
+    ///
+    /// The grace period should be shorter than the time the process is given to terminate (e.g.
+    /// `terminationGracePeriodSeconds` in Kubernetes), to leave time for the application itself
+    /// to exit. See [`DEFAULT_GRACE_PERIOD`].
+    #[must_use]
+    pub fn new(session: &Session, grace_period: Duration) -> Self {
+        Self {
+            exit_handle: session.create_exit_handle(),
+            session_monitor: session.create_session_monitor(),
+            grace_period,
+        }
+    }
+
+    /// Waits for SIGTERM or SIGINT (Ctrl+C on platforms other than Unix), then shuts down the
+    /// [`Session`] as described in [`GracefulShutdown::shutdown`].
+    ///
+    /// The signal handlers are installed when this future is first polled, so it should be
+    /// spawned before [`Session::run`] is awaited. Once installed, the signals no longer
+    /// terminate the process, so the application must exit once [`Session::run`] returns.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if the signal handlers could not be installed.
+    pub async fn run(self) -> Result<(ShutdownSignal, ShutdownOutcome), io::Error> {
+        let signal = wait_for_signal().await?;
+        log::info!("Received {signal:?} signal, shutting down session");
+        let outcome = self.shutdown().await;
+        Ok((signal, outcome))
+    }
+
+    /// Shuts down the [`Session`] without waiting for a signal.
+    ///
+    /// Waits for queued and in-flight outgoing PUBLISHes to complete, then exits the [`Session`]
+    /// and waits for it to stop. The last fifth of the grace period is reserved for the exit, so
+    /// draining stops early if needed. If the grace period elapses first, the exit is forced.
+    pub async fn shutdown(&self) -> ShutdownOutcome {
+        let deadline = Instant::now() + self.grace_period;
+        // Part of the grace period is reserved for the exit, so that the Session can still
+        // disconnect gracefully if the drain takes the rest of it
+        let drain_deadline = deadline - self.grace_period / EXIT_GRACE_PERIOD_DIVISOR;
+
+        let drained = tokio::time::timeout_at(drain_deadline, self.drain())
+            .await
+            .is_ok();
+        if !drained {
+            log::warn!(
+                "Outgoing PUBLISHes not drained within the grace period: {:?}",
+                self.session_monitor.publish_stats()
+            );
+        }
+
+        let graceful = self.exit_handle.force_exit();
+        let stopped = tokio::time::timeout_at(deadline, self.exit_handle.wait_until_stopped())
+            .await
+            .is_ok();
+        if !stopped {
+            log::warn!("Session did not stop within the grace period, forcing exit");
+            self.exit_handle.force_exit();
+        }
+
+        ShutdownOutcome {
+            drained,
+            graceful,
+            stopped,
+        }
+    }
+
+    /// Waits until there are no queued or in-flight outgoing PUBLISHes
+    async fn drain(&self) {
+        loop {
+            let stats = self.session_monitor.publish_stats();
+            if stats.inflight == 0 && stats.queued == 0 {
+                return;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> Result<ShutdownSignal, io::Error> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => Ok(ShutdownSignal::Terminate),
+        _ = interrupt.recv() => Ok(ShutdownSignal::Interrupt),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> Result<ShutdownSignal, io::Error> {
+    tokio::signal::ctrl_c().await?;
+    Ok(ShutdownSignal::Interrupt)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wait_for_sigterm() {
+        let signal = tokio::task::spawn(wait_for_signal());
+        // Let the handlers be installed before raising the signal
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // SAFETY: raising a signal for which a handler is installed has no other effect
+        assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+        let signal = tokio::time::timeout(Duration::from_secs(5), signal)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(signal, ShutdownSignal::Terminate);
+    }
+}
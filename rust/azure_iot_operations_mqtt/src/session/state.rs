@@ -6,7 +6,10 @@
 use std::fmt;
 use std::sync::RwLock;
 
-use tokio::sync::Notify;
+use tokio::sync::{Notify, futures::Notified};
+
+use crate::azure_mqtt::{packet::ConnAckProperties, transport::TlsInfo};
+use crate::session::DisconnectCause;
 
 /// Information used to track the state of the Session.
 pub struct SessionState {
@@ -14,6 +17,16 @@ pub struct SessionState {
     connected: RwLock<bool>,
     /// Notifier indicating a state change
     state_change: Notify,
+    /// Details of the TLS session of the most recent connection
+    tls_info: RwLock<Option<TlsInfo>>,
+    /// Properties of the CONNACK of the most recent connection
+    connack_properties: RwLock<Option<ConnAckProperties>>,
+    /// Whether the Session has stopped and released its resources
+    stopped: RwLock<bool>,
+    /// Cause of the most recent disconnect or failed connect attempt
+    last_disconnect_cause: RwLock<Option<DisconnectCause>>,
+    /// Notifier indicating that the MQTT session was lost on a reconnect
+    session_lost: Notify,
 }
 
 impl SessionState {
@@ -44,8 +57,70 @@ impl SessionState {
         }
     }
 
-    /// Update the state to reflect a connection
-    pub fn transition_connected(&self) {
+    /// Return the cause of the most recent disconnect or failed connect attempt, if any
+    pub fn last_disconnect_cause(&self) -> Option<DisconnectCause> {
+        self.last_disconnect_cause.read().unwrap().clone()
+    }
+
+    /// Wait until the Session is disconnected and return the cause of the disconnect.
+    /// Returns immediately if the Session is already disconnected and the cause is known.
+    pub async fn condition_disconnected_with_cause(&self) -> DisconnectCause {
+        loop {
+            // Register for the notification before checking the state to avoid missing it
+            let notified = self.state_change.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if !self.is_connected()
+                && let Some(cause) = self.last_disconnect_cause()
+            {
+                break cause;
+            }
+            notified.await;
+        }
+    }
+
+    /// Return true if the Session has stopped
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.read().unwrap()
+    }
+
+    /// Wait until the Session has stopped.
+    /// Returns immediately if the Session has already stopped.
+    pub async fn condition_stopped(&self) {
+        loop {
+            // The stop is only notified once, so register for the notification before checking
+            // the state to avoid missing it
+            let notified = self.state_change.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_stopped() {
+                break;
+            }
+            notified.await;
+        }
+    }
+
+    /// Return the details of the TLS session of the most recent connection, if it used TLS
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.tls_info.read().unwrap().clone()
+    }
+
+    /// Return the properties of the CONNACK of the most recent connection
+    pub fn connack_properties(&self) -> Option<ConnAckProperties> {
+        self.connack_properties.read().unwrap().clone()
+    }
+
+    /// Update the state to reflect a connection with the given TLS session details and CONNACK
+    /// properties
+    pub fn transition_connected(
+        &self,
+        tls_info: Option<TlsInfo>,
+        connack_properties: ConnAckProperties,
+    ) {
+        // Update the connection details before notifying waiters of the connection
+        *self.tls_info.write().unwrap() = tls_info;
+        *self.connack_properties.write().unwrap() = Some(connack_properties);
+
         // Acquire write lock for duration of method to ensure correctness of logging
         let mut connected = self.connected.write().unwrap();
 
@@ -60,8 +135,11 @@ impl SessionState {
         log::debug!("{:?}", *connected);
     }
 
-    /// Update the state to reflect a disconnection
-    pub fn transition_disconnected(&self) {
+    /// Update the state to reflect a disconnection with the given cause
+    pub fn transition_disconnected(&self, cause: DisconnectCause) {
+        // Update the cause before notifying waiters of the disconnection
+        *self.last_disconnect_cause.write().unwrap() = Some(cause);
+
         // Acquire write lock for duration of method to ensure correctness of logging
         let mut connected = self.connected.write().unwrap();
 
@@ -71,6 +149,32 @@ impl SessionState {
         }
         log::debug!("{:?}", *connected);
     }
+
+    /// Record the cause of a failed connect attempt while disconnected
+    pub fn record_disconnect_cause(&self, cause: DisconnectCause) {
+        *self.last_disconnect_cause.write().unwrap() = Some(cause);
+        self.state_change.notify_waiters();
+    }
+
+    /// Return a future that completes when the MQTT session is next lost on a reconnect.
+    /// The future is registered for the notification as soon as it is created.
+    pub fn session_lost_notified(&self) -> Notified<'_> {
+        self.session_lost.notified()
+    }
+
+    /// Notify waiters that the MQTT session was lost on a reconnect
+    pub fn notify_session_lost(&self) {
+        self.session_lost.notify_waiters();
+    }
+
+    /// Update the state to reflect that the Session has stopped
+    pub fn transition_stopped(&self) {
+        let mut stopped = self.stopped.write().unwrap();
+        if !*stopped {
+            *stopped = true;
+            self.state_change.notify_waiters();
+        }
+    }
 }
 
 impl Default for SessionState {
@@ -79,6 +183,11 @@ impl Default for SessionState {
         Self {
             connected: RwLock::new(false),
             state_change: Notify::new(),
+            tls_info: RwLock::new(None),
+            connack_properties: RwLock::new(None),
+            stopped: RwLock::new(false),
+            last_disconnect_cause: RwLock::new(None),
+            session_lost: Notify::new(),
         }
     }
 }
@@ -89,6 +198,7 @@ impl fmt::Debug for SessionState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SessionState")
             .field("connected", &self.is_connected())
+            .field("stopped", &self.is_stopped())
             .finish()
     }
 }
@@ -0,0 +1,50 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Tracking of the subscriptions of a [`crate::session::Session`], used to replay them when the
+//! MQTT session is lost.
+
+use std::collections::HashMap;
+
+use crate::control_packet::{QoS, RetainOptions, SubscribeProperties, TopicFilter};
+
+/// Parameters of a `SUBSCRIBE` issued through a
+/// [`SessionManagedClient`](crate::session::SessionManagedClient)
+#[derive(Clone)]
+pub struct Subscription {
+    pub topic_filter: TopicFilter,
+    pub max_qos: QoS,
+    pub no_local: bool,
+    pub retain_options: RetainOptions,
+    /// Properties as provided by the application, before a subscription identifier is assigned
+    /// by the Session
+    pub properties: SubscribeProperties,
+}
+
+/// Subscriptions that are currently active, keyed by topic filter.
+///
+/// A subscription is active from the time its `SUBSCRIBE` is issued until an `UNSUBSCRIBE` is
+/// issued for its topic filter. Subscribing again to the same topic filter replaces it, as it does
+/// on the server.
+#[derive(Default)]
+pub struct ActiveSubscriptions {
+    subscriptions: HashMap<TopicFilter, Subscription>,
+}
+
+impl ActiveSubscriptions {
+    /// Record a subscription, replacing any existing subscription to the same topic filter
+    pub fn insert(&mut self, subscription: Subscription) {
+        self.subscriptions
+            .insert(subscription.topic_filter.clone(), subscription);
+    }
+
+    /// Remove the subscription to the given topic filter, if any
+    pub fn remove(&mut self, topic_filter: &TopicFilter) {
+        self.subscriptions.remove(topic_filter);
+    }
+
+    /// Return a snapshot of the active subscriptions
+    pub fn snapshot(&self) -> Vec<Subscription> {
+        self.subscriptions.values().cloned().collect()
+    }
+}
@@ -208,30 +208,15 @@ impl MockServer {
     /// Panic if the next packet received is not a SUBSCRIBE packet.
     /// Send a SUBACK packet granting the requested QoS in response.
     pub async fn expect_subscribe_and_accept(&self) {
-        match self.from_client_rx.recv().await {
-            Some(mqtt_proto::Packet::Subscribe(subscribe)) => {
-                //let granted_qos = match subscribe.
-                let rc_vec = subscribe
-                    .subscribe_to
-                    .iter()
-                    .map(|st| match st.options.maximum_qos {
-                        mqtt_proto::QoS::AtMostOnce => mqtt_proto::SubscribeReasonCode::GrantedQoS0,
-                        mqtt_proto::QoS::AtLeastOnce => {
-                            mqtt_proto::SubscribeReasonCode::GrantedQoS1
-                        }
-                        mqtt_proto::QoS::ExactlyOnce => {
-                            mqtt_proto::SubscribeReasonCode::GrantedQoS2
-                        }
-                    })
-                    .collect();
+        let subscribe = self.expect_subscribe().await;
+        self.accept_subscribe(&subscribe);
+    }
 
-                self.to_client_tx
-                    .send(mqtt_proto::Packet::SubAck(mqtt_proto::SubAck {
-                        packet_identifier: subscribe.packet_identifier,
-                        reason_codes: rc_vec,
-                        other_properties: mqtt_proto::SubAckOtherProperties::default(),
-                    }));
-            }
+    /// Panic if the next packet received is not a SUBSCRIBE packet.
+    /// Return the received SUBSCRIBE packet for further inspection.
+    pub async fn expect_subscribe(&self) -> mqtt_proto::Subscribe<Bytes> {
+        match self.from_client_rx.recv().await {
+            Some(mqtt_proto::Packet::Subscribe(subscribe)) => subscribe,
             Some(other) => {
                 panic!("Expected SUBSCRIBE packet, but received different packet: {other:?}",);
             }
@@ -241,6 +226,61 @@ impl MockServer {
         }
     }
 
+    /// Send a SUBACK packet granting the QoS requested in the provided SUBSCRIBE packet.
+    pub fn accept_subscribe(&self, subscribe: &mqtt_proto::Subscribe<Bytes>) {
+        let rc_vec = subscribe
+            .subscribe_to
+            .iter()
+            .map(|st| match st.options.maximum_qos {
+                mqtt_proto::QoS::AtMostOnce => mqtt_proto::SubscribeReasonCode::GrantedQoS0,
+                mqtt_proto::QoS::AtLeastOnce => mqtt_proto::SubscribeReasonCode::GrantedQoS1,
+                mqtt_proto::QoS::ExactlyOnce => mqtt_proto::SubscribeReasonCode::GrantedQoS2,
+            })
+            .collect();
+
+        self.to_client_tx
+            .send(mqtt_proto::Packet::SubAck(mqtt_proto::SubAck {
+                packet_identifier: subscribe.packet_identifier,
+                reason_codes: rc_vec,
+                other_properties: mqtt_proto::SubAckOtherProperties::default(),
+            }));
+    }
+
+    /// Panic if the next packet received is not an UNSUBSCRIBE packet.
+    /// Send a successful UNSUBACK packet in response.
+    pub async fn expect_unsubscribe_and_accept(&self) -> mqtt_proto::Unsubscribe<Bytes> {
+        let unsubscribe = self.expect_unsubscribe().await;
+        self.accept_unsubscribe(&unsubscribe);
+        unsubscribe
+    }
+
+    /// Panic if the next packet received is not an UNSUBSCRIBE packet.
+    /// Return the received UNSUBSCRIBE packet for further inspection.
+    pub async fn expect_unsubscribe(&self) -> mqtt_proto::Unsubscribe<Bytes> {
+        match self.from_client_rx.recv().await {
+            Some(mqtt_proto::Packet::Unsubscribe(unsubscribe)) => unsubscribe,
+            Some(other) => {
+                panic!("Expected UNSUBSCRIBE packet, but received different packet: {other:?}",);
+            }
+            None => {
+                panic!("Expected UNSUBSCRIBE packet, but connection was closed");
+            }
+        }
+    }
+
+    /// Send a successful UNSUBACK packet for each topic filter in the provided UNSUBSCRIBE packet.
+    pub fn accept_unsubscribe(&self, unsubscribe: &mqtt_proto::Unsubscribe<Bytes>) {
+        self.to_client_tx
+            .send(mqtt_proto::Packet::UnsubAck(mqtt_proto::UnsubAck {
+                packet_identifier: unsubscribe.packet_identifier,
+                reason_codes: vec![
+                    mqtt_proto::UnsubAckReasonCode::Success;
+                    unsubscribe.unsubscribe_from.len()
+                ],
+                other_properties: mqtt_proto::UnsubAckOtherProperties::default(),
+            }));
+    }
+
     /// Panic if the next packet received is not a PUBACK packet.
     /// Return the received PUBACK packet for further inspection.
     pub async fn expect_puback(&self) -> mqtt_proto::PubAck<Bytes> {
@@ -255,6 +295,20 @@ impl MockServer {
         }
     }
 
+    /// Panic if the next packet received is not a PUBLISH packet.
+    /// Return the received PUBLISH packet for further inspection.
+    pub async fn expect_publish(&self) -> mqtt_proto::Publish<Bytes> {
+        match self.from_client_rx.recv().await {
+            Some(mqtt_proto::Packet::Publish(publish)) => publish,
+            Some(other) => {
+                panic!("Expected PUBLISH packet, but received different packet: {other:?}",);
+            }
+            None => {
+                panic!("Expected PUBLISH packet, but connection was closed");
+            }
+        }
+    }
+
     /// Panic if the next packet received is not an AUTH packet.
     /// Return the received AUTH packet for further inspection.
     pub async fn expect_auth_and_accept(&self) -> mqtt_proto::Auth<Bytes> {
@@ -309,6 +363,35 @@ impl MockServer {
         self.to_client_tx.send(mqtt_proto::Packet::Publish(publish));
     }
 
+    /// Send a PUBACK packet with Success reason code to the client
+    pub fn send_puback(&self, packet_identifier: mqtt_proto::PacketIdentifier) {
+        self.to_client_tx
+            .send(mqtt_proto::Packet::PubAck(mqtt_proto::PubAck {
+                packet_identifier,
+                reason_code: mqtt_proto::PubAckReasonCode::Success,
+                other_properties: mqtt_proto::PubAckOtherProperties::default(),
+            }));
+    }
+
+    /// Send a PUBACK packet with the given reason code to the client
+    pub fn send_puback_with_reason(
+        &self,
+        packet_identifier: mqtt_proto::PacketIdentifier,
+        reason_code: mqtt_proto::PubAckReasonCode,
+    ) {
+        self.to_client_tx
+            .send(mqtt_proto::Packet::PubAck(mqtt_proto::PubAck {
+                packet_identifier,
+                reason_code,
+                other_properties: mqtt_proto::PubAckOtherProperties::default(),
+            }));
+    }
+
+    /// Send a SUBACK packet to the client
+    pub fn send_suback(&self, suback: mqtt_proto::SubAck<Bytes>) {
+        self.to_client_tx.send(mqtt_proto::Packet::SubAck(suback));
+    }
+
     /// Send a DISCONNECT packet to the client
     pub fn send_disconnect(&self, disconnect: mqtt_proto::Disconnect<Bytes>) {
         self.to_client_tx
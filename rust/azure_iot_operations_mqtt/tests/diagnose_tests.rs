@@ -0,0 +1,151 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "diagnostics")]
+
+use std::{env, path::PathBuf, time::Duration};
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::aio::diagnostics::{DiagnosisReport, DiagnosisStage, StageOutcome};
+
+fn settings_builder(client_id: &str, tcp_port: u16) -> MqttConnectionSettingsBuilder {
+    MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("localhost")
+        .tcp_port(tcp_port)
+        .connection_timeout(Duration::from_secs(5))
+        .use_tls(false)
+}
+
+/// Returns a port on localhost that nothing is listening on
+fn unused_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+/// Writes a SAT file containing a JWT with the given expiry, as seconds since the epoch
+fn sat_file(name: &str, exp: i64) -> PathBuf {
+    let encode = |s: &str| {
+        openssl::base64::encode_block(s.as_bytes())
+            .trim_end_matches('=')
+            .replace('+', "-")
+            .replace('/', "_")
+    };
+    let token = format!(
+        "{}.{}.signature",
+        encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+        encode(&format!(r#"{{"aud":["aio-internal"],"exp":{exp}}}"#))
+    );
+    let path = env::temp_dir().join(format!("{name}-{}.sat", std::process::id()));
+    std::fs::write(&path, token).unwrap();
+    path
+}
+
+fn outcome(report: &DiagnosisReport, stage: DiagnosisStage) -> &StageOutcome {
+    &report.stage(stage).unwrap().outcome
+}
+
+#[tokio::test]
+async fn diagnose_wrong_port() {
+    let settings = settings_builder("diagnose_wrong_port", unused_port())
+        .build()
+        .unwrap();
+    let report = settings.diagnose().await;
+
+    assert_eq!(
+        outcome(&report, DiagnosisStage::DnsResolution),
+        &StageOutcome::Passed
+    );
+    assert!(!report.resolved_addresses.is_empty());
+    assert!(matches!(
+        outcome(&report, DiagnosisStage::TcpConnect),
+        StageOutcome::Failed(_)
+    ));
+    assert!(matches!(
+        outcome(&report, DiagnosisStage::TlsHandshake),
+        StageOutcome::Skipped(_)
+    ));
+    assert!(matches!(
+        outcome(&report, DiagnosisStage::MqttConnect),
+        StageOutcome::Skipped(_)
+    ));
+    assert_eq!(
+        report.first_failure().unwrap().stage,
+        DiagnosisStage::TcpConnect
+    );
+    assert!(!report.passed());
+}
+
+#[tokio::test]
+async fn diagnose_bad_ca_path() {
+    let settings = settings_builder("diagnose_bad_ca_path", unused_port())
+        .use_tls(true)
+        .ca_file("/nonexistent/ca.pem".to_string())
+        .build()
+        .unwrap();
+    let report = settings.diagnose().await;
+
+    // The TLS configuration is reported as invalid even though the broker can't be reached
+    let StageOutcome::Failed(reason) = outcome(&report, DiagnosisStage::TlsHandshake) else {
+        panic!("TLS stage did not fail: {report}");
+    };
+    assert!(reason.contains("TLS configuration"), "{reason}");
+    assert!(report.server_certificate_chain.is_empty());
+}
+
+#[tokio::test]
+async fn diagnose_expired_sat() {
+    let path = sat_file("diagnose_expired_sat", 1_000_000_000);
+    let settings = settings_builder("diagnose_expired_sat", unused_port())
+        .sat_file(path.to_str().unwrap().to_string())
+        .build()
+        .unwrap();
+    let report = settings.diagnose().await;
+    std::fs::remove_file(&path).unwrap();
+
+    let StageOutcome::Failed(reason) = outcome(&report, DiagnosisStage::Credentials) else {
+        panic!("credentials stage did not fail: {report}");
+    };
+    assert!(reason.starts_with("SAT expired at"), "{reason}");
+    assert_eq!(
+        report.sat_expiry,
+        chrono::DateTime::from_timestamp(1_000_000_000, 0)
+    );
+}
+
+#[tokio::test]
+async fn diagnose_missing_sat() {
+    let settings = settings_builder("diagnose_missing_sat", unused_port())
+        .sat_file("/nonexistent/token".to_string())
+        .build()
+        .unwrap();
+    let report = settings.diagnose().await;
+
+    assert!(matches!(
+        outcome(&report, DiagnosisStage::Credentials),
+        StageOutcome::Failed(_)
+    ));
+    assert_eq!(report.sat_expiry, None);
+}
+
+#[tokio::test]
+async fn diagnose_success() {
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        log::warn!("This test is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return;
+    }
+    let settings = settings_builder("network_test_diagnose", 1883)
+        .build()
+        .unwrap();
+    let report = settings.diagnose().await;
+
+    assert!(report.passed(), "{report}");
+    assert_eq!(
+        outcome(&report, DiagnosisStage::TlsHandshake),
+        &StageOutcome::Skipped("TLS is disabled".to_string())
+    );
+    assert_eq!(
+        outcome(&report, DiagnosisStage::MqttConnect),
+        &StageOutcome::Passed
+    );
+}
@@ -2,7 +2,12 @@
 // Licensed under the MIT License.
 
 use std::{
+    collections::HashMap,
     num::{NonZeroU16, NonZeroU32},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
     time::Duration,
 };
 
@@ -12,9 +17,16 @@ use futures::FutureExt;
 use azure_iot_operations_mqtt::azure_mqtt::mqtt_proto;
 use azure_iot_operations_mqtt::{
     aio::connection_settings::{MqttConnectionSettings, MqttConnectionSettingsBuilder},
-    control_packet::AuthenticationInfo,
+    control_packet::{
+        AuthenticationInfo, ConnAckReason, DisconnectReason, QoS, RetainOptions,
+        SubscribeProperties, TopicFilter, UnsubscribeProperties,
+    },
     error::{SessionErrorKind, SessionExitErrorKind},
-    session::{Session, SessionOptionsBuilder},
+    session::{
+        ConnectionEvent, DisconnectCause, Session, SessionManagedClient, SessionOptionsBuilder,
+        auth_provider::{AuthError, AuthProvider, Credentials},
+        redirect_policy::{RedirectPolicy, RedirectRejection, ServerAddress},
+    },
     test_utils::{
         IncomingPacketsTx, InjectedPacketChannels, MockEnhancedAuthPolicy,
         MockEnhancedAuthPolicyController, MockReconnectPolicy, MockReconnectPolicyController,
@@ -83,6 +95,131 @@ fn quick_setup_enhanced_auth(
     )
 }
 
+fn quick_setup_auth_provider(
+    client_id: &str,
+    auth_provider: RotatingAuthProvider,
+) -> (Session, MockServer, MockReconnectPolicyController) {
+    let (mock_server, injected_packet_channels) = setup_mock_server();
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .auth_provider(auth_provider)
+        .build()
+        .unwrap();
+    let (mock_reconnect_policy, mock_reconnect_policy_controller) = MockReconnectPolicy::new();
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .reconnect_policy(Box::new(mock_reconnect_policy))
+        .injected_packet_channels(Some(injected_packet_channels))
+        .build()
+        .unwrap();
+    let session = Session::new(session_options).unwrap();
+    (session, mock_server, mock_reconnect_policy_controller)
+}
+
+fn quick_setup_redirect(
+    client_id: &str,
+    redirect_policy: RedirectPolicy,
+) -> (
+    MqttConnectionSettings,
+    Session,
+    MockServer,
+    MockServer,
+    MockReconnectPolicyController,
+) {
+    let (mock_server, injected_packet_channels) = setup_mock_server();
+    let (redirect_mock_server, redirect_injected_packet_channels) = setup_mock_server();
+    let connection_settings = connection_settings_builder_preset(client_id)
+        .hostname("broker.contoso.com")
+        .build()
+        .unwrap();
+    let (mock_reconnect_policy, mock_reconnect_policy_controller) = MockReconnectPolicy::new();
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings.clone())
+        .reconnect_policy(Box::new(mock_reconnect_policy))
+        .redirect_policy(redirect_policy)
+        .injected_packet_channels(Some(injected_packet_channels))
+        .injected_server_packet_channels(HashMap::from([(
+            redirect_server_address(),
+            redirect_injected_packet_channels,
+        )]))
+        .build()
+        .unwrap();
+    let session = Session::new(session_options).unwrap();
+    (
+        connection_settings,
+        session,
+        mock_server,
+        redirect_mock_server,
+        mock_reconnect_policy_controller,
+    )
+}
+
+fn server_address() -> ServerAddress {
+    ServerAddress {
+        hostname: "broker.contoso.com".to_string(),
+        tcp_port: 1883,
+    }
+}
+
+fn redirect_server_address() -> ServerAddress {
+    ServerAddress {
+        hostname: "broker2.contoso.com".to_string(),
+        tcp_port: 8883,
+    }
+}
+
+fn redirect_connack(server_reference: &str) -> mqtt_proto::ConnAck<Bytes> {
+    mqtt_proto::ConnAck {
+        reason_code: mqtt_proto::ConnectReasonCode::Refused(
+            mqtt_proto::ConnectionRefusedReason::UseAnotherServer,
+        ),
+        other_properties: mqtt_proto::ConnAckOtherProperties {
+            server_reference: Some(server_reference.into()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Auth provider that issues new credentials on every invocation, or fails if set to do so
+#[derive(Clone, Debug, Default)]
+struct RotatingAuthProvider {
+    issued: Arc<AtomicUsize>,
+    fail: Arc<AtomicBool>,
+}
+
+impl RotatingAuthProvider {
+    fn set_fail(&self, fail: bool) {
+        self.fail.store(fail, Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for RotatingAuthProvider {
+    async fn credentials(&self) -> Result<Credentials, AuthError> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(AuthError::new("identity provider unavailable"));
+        }
+        let n = self.issued.fetch_add(1, Ordering::SeqCst);
+        Ok(Credentials {
+            username: Some(format!("rotating-username-{n}")),
+            password: Some(format!("rotating-password-{n}")),
+            expiry: None,
+        })
+    }
+}
+
+fn assert_connect_credentials(connect: &mqtt_proto::Connect<Bytes>, n: usize) {
+    assert_eq!(
+        connect.username,
+        Some(format!("rotating-username-{n}").as_str().into())
+    );
+    assert_eq!(
+        connect.password,
+        Some(format!("rotating-password-{n}").as_bytes().into())
+    );
+}
+
 fn setup_mock_server() -> (MockServer, InjectedPacketChannels) {
     let incoming_packets_tx = IncomingPacketsTx::default();
     let outgoing_packets_rx = OutgoingPacketsRx::default();
@@ -201,6 +338,7 @@ async fn connect_and_exit_standard_auth() {
     let exit_handle = session.create_exit_handle();
     let monitor = session.create_session_monitor();
     assert!(!monitor.is_connected());
+    assert!(session.tls_info().is_none());
 
     // Start the session run loop
     let run_f = tokio::task::spawn(session.run());
@@ -211,6 +349,8 @@ async fn connect_and_exit_standard_auth() {
 
     // Wait for connection to be established by Session in response to CONNACK
     monitor.connected().await;
+    // The mock transport does not use TLS
+    assert!(monitor.tls_info().is_none());
 
     // End the session
     assert!(matches!(exit_handle.try_exit(), Ok(())));
@@ -384,6 +524,61 @@ async fn connection_loss_server_disconnect_reconnect() {
     assert!(matches!(e.kind(), SessionErrorKind::ReconnectHalted));
 }
 
+#[tokio::test]
+async fn disconnect_cause_server_disconnect_and_connect_rejected() {
+    let (_, session, mock_server, mock_rp_controller) =
+        quick_setup_standard_auth("test-disconnect-cause-client");
+    mock_rp_controller.manual_mode(true);
+    let monitor = session.create_session_monitor();
+    assert_eq!(monitor.last_disconnect_cause(), None);
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    monitor.connected().await;
+
+    // The cause of the disconnect is provided to waiters when the server disconnects
+    let monitor_clone = monitor.clone();
+    let disconnected_f =
+        tokio::task::spawn(async move { monitor_clone.disconnected_with_cause().await });
+    mock_rp_controller.set_next_delay(Some(Duration::from_secs(1)));
+    let connection_loss_f = mock_rp_controller.connection_loss_notified();
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::ServerBusy,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    connection_loss_f.await;
+    let expected_cause = DisconnectCause::ServerDisconnect(DisconnectReason::ServerBusy);
+    assert_eq!(disconnected_f.await.unwrap(), expected_cause);
+    assert_eq!(
+        monitor.last_disconnect_cause(),
+        Some(expected_cause.clone())
+    );
+    // Returns immediately when already disconnected
+    assert_eq!(monitor.disconnected_with_cause().await, expected_cause);
+
+    // A failed reconnect attempt updates the cause
+    mock_server.expect_connect().await;
+    mock_rp_controller.set_next_delay(None);
+    let connect_failure_f = mock_rp_controller.connect_failure_notified();
+    mock_server.send_connack(mqtt_proto::ConnAck {
+        reason_code: mqtt_proto::ConnectReasonCode::Refused(
+            mqtt_proto::ConnectionRefusedReason::NotAuthorized,
+        ),
+        other_properties: mqtt_proto::ConnAckOtherProperties::default(),
+    });
+    connect_failure_f.await;
+    assert_eq!(
+        monitor.last_disconnect_cause(),
+        Some(DisconnectCause::ConnectRejected(
+            ConnAckReason::NotAuthorized
+        ))
+    );
+
+    let e = run_f.await.unwrap().unwrap_err();
+    assert!(matches!(e.kind(), SessionErrorKind::ReconnectHalted));
+}
+
 // TODO: disconnect with Ping timeout, IO error(s), protocol error(s)
 
 #[tokio::test]
@@ -462,6 +657,47 @@ async fn try_exit_while_disconnected() {
     mock_server.expect_no_packet();
 }
 
+#[tokio::test]
+async fn wait_until_stopped_after_exit() {
+    let (_, session, mock_server, _) =
+        quick_setup_standard_auth("test-wait-until-stopped-after-exit-client");
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    monitor.connected().await;
+
+    // The Session has not stopped while it is running
+    let stopped_f = tokio::task::spawn({
+        let exit_handle = exit_handle.clone();
+        async move { exit_handle.wait_until_stopped().await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!stopped_f.is_finished());
+
+    // The Session has stopped once it has exited
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    mock_server.expect_disconnect().await;
+    stopped_f.await.unwrap();
+    assert!(run_f.await.unwrap().is_ok());
+
+    // Waiting again returns immediately
+    exit_handle.wait_until_stopped().now_or_never().unwrap();
+}
+
+#[tokio::test]
+async fn wait_until_stopped_never_run() {
+    let (_, session, _, _) = quick_setup_standard_auth("test-wait-until-stopped-never-run-client");
+    let exit_handle = session.create_exit_handle();
+    assert!(exit_handle.wait_until_stopped().now_or_never().is_none());
+
+    // A Session dropped without running has stopped
+    drop(session);
+    exit_handle.wait_until_stopped().now_or_never().unwrap();
+}
+
 #[tokio::test]
 async fn force_exit_never_run() {
     let (_, session, mock_server, _) =
@@ -665,3 +901,475 @@ async fn reauth_on_successive_connections() {
     tokio::time::sleep(Duration::from_secs(1)).await;
     mock_server.expect_no_packet();
 }
+
+#[tokio::test]
+async fn auth_provider_rotating_credentials_reconnect() {
+    let auth_provider = RotatingAuthProvider::default();
+    let (session, mock_server, _) = quick_setup_auth_provider(
+        "test-auth-provider-rotating-credentials-reconnect-client",
+        auth_provider,
+    );
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+
+    // Validate that the CONNECT packet contains the first credentials from the provider
+    let connect = mock_server.expect_connect_and_accept(true).await;
+    assert_connect_credentials(&connect, 0);
+    monitor.connected().await;
+
+    // Disconnect the session and wait for disconnect
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    monitor.disconnected().await;
+
+    // Validate that the reconnect uses fresh credentials from the provider
+    let connect = mock_server.expect_connect_and_accept(true).await;
+    assert_connect_credentials(&connect, 1);
+    monitor.connected().await;
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn auth_provider_error_connect_failure() {
+    let auth_provider = RotatingAuthProvider::default();
+    auth_provider.set_fail(true);
+    let (session, mock_server, mock_rp_controller) = quick_setup_auth_provider(
+        "test-auth-provider-error-connect-failure-client",
+        auth_provider.clone(),
+    );
+    mock_rp_controller.manual_mode(true);
+    mock_rp_controller.set_next_delay(Some(Duration::from_secs(1)));
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+
+    // The provider failure is reported to the reconnect policy as a connect failure,
+    // and no CONNECT is sent
+    mock_rp_controller.connect_failure_notified().await;
+    assert!(!monitor.is_connected());
+    mock_server.expect_no_packet();
+
+    // Once the provider recovers, the reconnect attempt uses its credentials
+    auth_provider.set_fail(false);
+    let connect = mock_server.expect_connect_and_accept(true).await;
+    assert_connect_credentials(&connect, 0);
+    monitor.connected().await;
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn connect_failure_redirect_to_another_server() {
+    let (connection_settings, session, mock_server, redirect_mock_server, _) = quick_setup_redirect(
+        "test-connect-failure-redirect-client",
+        RedirectPolicy::default(),
+    );
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+    let mut connection_events = monitor.connection_events();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+
+    // The first server refuses the connection, redirecting to the second server
+    let connect = mock_server
+        .expect_connect_and_respond(redirect_connack("broker2.contoso.com:8883"))
+        .await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, false));
+    assert_eq!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::Redirected {
+            from: server_address(),
+            to: redirect_server_address(),
+            permanent: false,
+        }
+    );
+
+    // The Session connects to the second server immediately, with the same credentials
+    let connect = redirect_mock_server.expect_connect_and_accept(false).await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, false));
+    monitor.connected().await;
+    mock_server.expect_no_packet();
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = redirect_mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn connection_loss_redirect_to_another_server() {
+    let (connection_settings, session, mock_server, redirect_mock_server, _) = quick_setup_redirect(
+        "test-connection-loss-redirect-client",
+        RedirectPolicy::default(),
+    );
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+    let mut connection_events = monitor.connection_events();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(false).await;
+    monitor.connected().await;
+
+    // The first server disconnects, indicating it has moved to the second server
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::ServerMoved,
+        other_properties: mqtt_proto::DisconnectOtherProperties {
+            server_reference: Some("broker2.contoso.com:8883".into()),
+            ..Default::default()
+        },
+    });
+    monitor.disconnected().await;
+    assert_eq!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::Redirected {
+            from: server_address(),
+            to: redirect_server_address(),
+            permanent: true,
+        }
+    );
+
+    // The Session reconnects to the second server
+    let connect = redirect_mock_server.expect_connect_and_accept(true).await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, true));
+    monitor.connected().await;
+    mock_server.expect_no_packet();
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = redirect_mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn connect_failure_redirect_rejected_by_policy() {
+    let (connection_settings, session, mock_server, redirect_mock_server, mock_rp_controller) =
+        quick_setup_redirect(
+            "test-connect-failure-redirect-rejected-client",
+            RedirectPolicy::default(),
+        );
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+    let mut connection_events = monitor.connection_events();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+
+    // The first server redirects to a server in another domain, which is not allowed by default
+    mock_server.expect_connect().await;
+    let connect_failure_f = mock_rp_controller.connect_failure_notified();
+    mock_server.send_connack(redirect_connack("broker.fabrikam.com"));
+
+    // The connect failure is handled by the reconnect policy, and the same server is used again
+    connect_failure_f.await;
+    assert_eq!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::RedirectRejected {
+            from: server_address(),
+            server_reference: Some("broker.fabrikam.com".to_string()),
+            reason: RedirectRejection::HostNotAllowed,
+        }
+    );
+    let connect = mock_server.expect_connect_and_accept(false).await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, false));
+    monitor.connected().await;
+    redirect_mock_server.expect_no_packet();
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn connect_failure_redirect_max_redirects_reached() {
+    let (connection_settings, session, mock_server, redirect_mock_server, mock_rp_controller) =
+        quick_setup_redirect(
+            "test-connect-failure-max-redirects-client",
+            RedirectPolicy {
+                max_redirects: 1,
+                ..RedirectPolicy::default()
+            },
+        );
+    let exit_handle = session.create_exit_handle();
+    let monitor = session.create_session_monitor();
+    let mut connection_events = monitor.connection_events();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+
+    // The first server redirects to the second server
+    mock_server
+        .expect_connect_and_respond(redirect_connack("broker2.contoso.com:8883"))
+        .await;
+    assert!(matches!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::Redirected { .. }
+    ));
+
+    // The second server redirects back to the first server, exceeding the max redirects
+    redirect_mock_server.expect_connect().await;
+    let connect_failure_f = mock_rp_controller.connect_failure_notified();
+    redirect_mock_server.send_connack(redirect_connack("broker.contoso.com:1883"));
+    connect_failure_f.await;
+    assert_eq!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::RedirectRejected {
+            from: redirect_server_address(),
+            server_reference: Some("broker.contoso.com:1883".to_string()),
+            reason: RedirectRejection::MaxRedirectsReached,
+        }
+    );
+
+    // The reconnect attempt is made to the second server
+    let connect = redirect_mock_server.expect_connect_and_accept(false).await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, false));
+    monitor.connected().await;
+    mock_server.expect_no_packet();
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = redirect_mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    monitor.disconnected().await;
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+/// Subscribe to the topic filter through the managed client, accepting the SUBSCRIBE on the server
+async fn subscribe_and_accept(
+    managed_client: &SessionManagedClient,
+    mock_server: &MockServer,
+    topic_filter: &str,
+) {
+    let (subscribe_result, ()) = tokio::join!(
+        async {
+            managed_client
+                .subscribe(
+                    TopicFilter::new(topic_filter).unwrap(),
+                    QoS::AtLeastOnce,
+                    false,
+                    RetainOptions::default(),
+                    SubscribeProperties::default(),
+                )
+                .await
+                .unwrap()
+                .await
+        },
+        mock_server.expect_subscribe_and_accept()
+    );
+    subscribe_result.unwrap();
+}
+
+#[tokio::test]
+async fn connection_loss_session_lost_resubscribe() {
+    let (connection_settings, session, mock_server, mock_rp_controller) =
+        quick_setup_standard_auth("test-connection-loss-session-lost-resubscribe-client");
+    mock_rp_controller.manual_mode(true);
+    let monitor = session.create_session_monitor();
+    let managed_client = session.create_managed_client();
+    let mut connection_events = monitor.connection_events();
+    let mut receiver = managed_client
+        .create_filtered_pub_receiver(TopicFilter::new("test/resubscribe/+").unwrap());
+    let exit_handle = session.create_exit_handle();
+
+    // Start the session run loop
+    let run_f = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    monitor.connected().await;
+
+    // Subscribe to two topic filters, then unsubscribe from one of them
+    subscribe_and_accept(&managed_client, &mock_server, "test/resubscribe/+").await;
+    subscribe_and_accept(&managed_client, &mock_server, "test/unsubscribed").await;
+    let (unsubscribe_result, _) = tokio::join!(
+        async {
+            managed_client
+                .unsubscribe(
+                    TopicFilter::new("test/unsubscribed").unwrap(),
+                    UnsubscribeProperties::default(),
+                )
+                .await
+                .unwrap()
+                .await
+        },
+        mock_server.expect_unsubscribe_and_accept()
+    );
+    unsubscribe_result.unwrap();
+
+    // Lose the connection, and reconnect to a server that no longer has the MQTT session
+    let session_lost_f = monitor.session_lost();
+    mock_rp_controller.set_next_delay(Some(Duration::ZERO));
+    let connection_loss_f = mock_rp_controller.connection_loss_notified();
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    connection_loss_f.await;
+    let connect = mock_server.expect_connect_and_accept(false).await;
+    assert_eq!(connect, expected_connect(&connection_settings, None, true));
+
+    // The Session continues, notifies the session loss and resubscribes to the active subscription only
+    session_lost_f.await;
+    assert_eq!(
+        connection_events.recv().await.unwrap(),
+        ConnectionEvent::SessionLost { resubscribed: 1 }
+    );
+    let subscribe = mock_server.expect_subscribe().await;
+    assert_eq!(subscribe.subscribe_to.len(), 1);
+    assert_eq!(
+        subscribe.subscribe_to[0].topic_filter.to_string(),
+        "test/resubscribe/+"
+    );
+    mock_server.accept_subscribe(&subscribe);
+    assert!(monitor.is_connected());
+
+    // The receiver keeps receiving messages on the new MQTT session
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic("test/resubscribe/1"),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtMostOnce,
+        retain: false,
+        payload: Bytes::from("after session loss"),
+        other_properties: mqtt_proto::PublishOtherProperties::default(),
+    });
+    let publish = receiver.recv().await.unwrap();
+    assert_eq!(publish.payload, Bytes::from("after session loss"));
+    mock_server.expect_no_packet();
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    assert!(run_f.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn connection_loss_session_lost_exit_without_resubscribe() {
+    let (mock_server, injected_packet_channels) = setup_mock_server();
+    let connection_settings =
+        connection_settings_builder_preset("test-connection-loss-session-lost-exit-client")
+            .build()
+            .unwrap();
+    let (mock_reconnect_policy, mock_rp_controller) = MockReconnectPolicy::new();
+    let session = Session::new(
+        SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .reconnect_policy(Box::new(mock_reconnect_policy))
+            .resubscribe_on_session_loss(false)
+            .injected_packet_channels(Some(injected_packet_channels))
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    mock_rp_controller.manual_mode(true);
+    let monitor = session.create_session_monitor();
+    let managed_client = session.create_managed_client();
+
+    // Start the session run loop and subscribe
+    let run_f = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    monitor.connected().await;
+    subscribe_and_accept(&managed_client, &mock_server, "test/resubscribe/+").await;
+
+    // Lose the connection, and reconnect to a server that no longer has the MQTT session
+    let session_lost_f = monitor.session_lost();
+    mock_rp_controller.set_next_delay(Some(Duration::ZERO));
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    mock_server.expect_connect_and_accept(false).await;
+
+    // The Session notifies the session loss and exits without resubscribing
+    session_lost_f.await;
+    let e = run_f.await.unwrap().unwrap_err();
+    assert!(matches!(e.kind(), SessionErrorKind::SessionLost));
+    mock_server.expect_no_packet();
+}
+
+#[tokio::test]
+async fn connack_properties_updated_on_reconnect() {
+    let (_, session, mock_server, mock_rp_controller) =
+        quick_setup_standard_auth("test-connack-properties-updated-on-reconnect-client");
+    mock_rp_controller.manual_mode(true);
+    let monitor = session.create_session_monitor();
+    let exit_handle = session.create_exit_handle();
+    assert!(session.connack_properties().is_none());
+
+    // Start the session run loop, and connect to a server with negotiated limits
+    let run_f = tokio::task::spawn(session.run());
+    mock_server
+        .expect_connect_and_respond(mqtt_proto::ConnAck {
+            reason_code: mqtt_proto::ConnectReasonCode::Success {
+                session_present: false,
+            },
+            other_properties: mqtt_proto::ConnAckOtherProperties {
+                maximum_packet_size: NonZeroU32::new(1024).unwrap(),
+                receive_maximum: NonZeroU16::new(10).unwrap(),
+                topic_alias_maximum: 5,
+                ..Default::default()
+            },
+        })
+        .await;
+    monitor.connected().await;
+    let connack_properties = monitor.connack_properties().unwrap();
+    assert_eq!(connack_properties.maximum_packet_size.get(), 1024);
+    assert_eq!(connack_properties.receive_maximum.get(), 10);
+    assert_eq!(connack_properties.topic_alias_maximum, 5);
+
+    // Lose the connection, and reconnect to a server with different limits
+    mock_rp_controller.set_next_delay(Some(Duration::ZERO));
+    let connection_loss_f = mock_rp_controller.connection_loss_notified();
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    connection_loss_f.await;
+    mock_server
+        .expect_connect_and_respond(mqtt_proto::ConnAck {
+            reason_code: mqtt_proto::ConnectReasonCode::Success {
+                session_present: true,
+            },
+            other_properties: mqtt_proto::ConnAckOtherProperties {
+                maximum_packet_size: NonZeroU32::new(4096).unwrap(),
+                ..Default::default()
+            },
+        })
+        .await;
+    monitor.connected().await;
+
+    // The properties of the most recent CONNACK are returned
+    let connack_properties = monitor.connack_properties().unwrap();
+    assert_eq!(connack_properties.maximum_packet_size.get(), 4096);
+    assert_eq!(connack_properties.receive_maximum, NonZeroU16::MAX);
+    assert_eq!(connack_properties.topic_alias_maximum, 0);
+
+    // End the session
+    assert!(matches!(exit_handle.try_exit(), Ok(())));
+    let disconnect = mock_server.expect_disconnect().await;
+    assert_eq!(disconnect, session_end_disconnect());
+    assert!(run_f.await.unwrap().is_ok());
+}
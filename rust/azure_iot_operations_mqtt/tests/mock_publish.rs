@@ -0,0 +1,238 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{num::NonZeroU16, time::Duration};
+
+use azure_iot_operations_mqtt::azure_mqtt::mqtt_proto;
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    control_packet::{PublishProperties, TopicName},
+    session::{PublishStats, Session, SessionManagedClient, SessionOptionsBuilder},
+    test_utils::{
+        IncomingPacketsTx, InjectedPacketChannels, MockReconnectPolicy, MockServer,
+        OutgoingPacketsRx,
+    },
+};
+use bytes::Bytes;
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+#[tokio::test]
+async fn publish_stats_inflight_and_queued() {
+    let (session, mock_server) = setup_client_and_mock_server("publish_stats_test_client");
+    let managed_client = session.create_managed_client();
+    let monitor = session.create_session_monitor();
+    assert_eq!(monitor.publish_stats(), PublishStats::default());
+
+    // PUBLISHes made before the Session is running are queued, not in-flight
+    let mut completion_tokens = vec![];
+    for i in 0..3 {
+        let ct = managed_client
+            .publish_qos1(
+                TopicName::new("test/topic").unwrap(),
+                false,
+                format!("Publish {i}"),
+                PublishProperties::default(),
+            )
+            .await
+            .unwrap();
+        completion_tokens.push(ct);
+    }
+    let stats = monitor.publish_stats();
+    assert_eq!(stats.queued, 3);
+    assert_eq!(stats.queued_high_water_mark, 3);
+    assert_eq!(stats.inflight, 0);
+    assert_eq!(stats.inflight_high_water_mark, 0);
+
+    // Once connected, the queued PUBLISHes are sent and become in-flight
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let mut packet_identifiers = vec![];
+    for _ in 0..3 {
+        let publish = mock_server.expect_publish().await;
+        match publish.packet_identifier_dup_qos {
+            mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) => {
+                packet_identifiers.push(packet_identifier);
+            }
+            other => panic!("Expected QoS 1 PUBLISH, but received {other:?}"),
+        }
+    }
+    let stats = monitor.publish_stats();
+    assert_eq!(stats.queued, 0);
+    assert_eq!(stats.queued_high_water_mark, 3);
+    assert_eq!(stats.inflight, 3);
+    assert_eq!(stats.inflight_high_water_mark, 3);
+
+    // Acknowledged PUBLISHes are no longer in-flight, but the high water marks remain
+    for packet_identifier in packet_identifiers {
+        mock_server.send_puback(packet_identifier);
+    }
+    for ct in completion_tokens {
+        tokio::time::timeout(Duration::from_secs(1), ct)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let stats = monitor.publish_stats();
+    assert_eq!(stats.queued, 0);
+    assert_eq!(stats.queued_high_water_mark, 3);
+    assert_eq!(stats.inflight, 0);
+    assert_eq!(stats.inflight_high_water_mark, 3);
+}
+
+/// Publishes a QoS 1 message to `topic` and returns the PUBLISH received by the mock server
+async fn publish_and_expect(
+    managed_client: &SessionManagedClient,
+    mock_server: &MockServer,
+    topic: &str,
+) -> mqtt_proto::Publish<Bytes> {
+    managed_client
+        .publish_qos1(
+            TopicName::new(topic).unwrap(),
+            false,
+            "payload",
+            PublishProperties::default(),
+        )
+        .await
+        .unwrap();
+    mock_server.expect_publish().await
+}
+
+fn puback(mock_server: &MockServer, publish: &mqtt_proto::Publish<Bytes>) {
+    match publish.packet_identifier_dup_qos {
+        mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) => {
+            mock_server.send_puback(packet_identifier);
+        }
+        other => panic!("Expected QoS 1 PUBLISH, but received {other:?}"),
+    }
+}
+
+fn connack_with_topic_alias_maximum(
+    session_present: bool,
+    topic_alias_maximum: u16,
+) -> mqtt_proto::ConnAck<Bytes> {
+    mqtt_proto::ConnAck {
+        reason_code: mqtt_proto::ConnectReasonCode::Success { session_present },
+        other_properties: mqtt_proto::ConnAckOtherProperties {
+            topic_alias_maximum,
+            ..Default::default()
+        },
+    }
+}
+
+#[tokio::test]
+async fn outgoing_topic_aliases() {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id("topic_alias_test_client")
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let (mock_reconnect_policy, mock_reconnect_policy_controller) = MockReconnectPolicy::new();
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .reconnect_policy(Box::new(mock_reconnect_policy))
+        .outgoing_topic_aliases(true)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let managed_client = session.create_managed_client();
+    let monitor = session.create_session_monitor();
+    tokio::task::spawn(session.run());
+    mock_server
+        .expect_connect_and_respond(connack_with_topic_alias_maximum(false, 1))
+        .await;
+
+    // The first PUBLISH to a topic carries the topic and a new alias, later ones only the alias
+    let publish = publish_and_expect(&managed_client, &mock_server, "test/alias/a").await;
+    assert_eq!(publish.topic_name.as_str(), "test/alias/a");
+    assert_eq!(
+        publish.other_properties.topic_alias.map(NonZeroU16::get),
+        Some(1)
+    );
+    puback(&mock_server, &publish);
+    let aliased_publish = publish_and_expect(&managed_client, &mock_server, "test/alias/a").await;
+    assert_eq!(aliased_publish.topic_name.as_str(), "");
+    assert_eq!(
+        aliased_publish
+            .other_properties
+            .topic_alias
+            .map(NonZeroU16::get),
+        Some(1)
+    );
+
+    // The server's topic alias maximum is exhausted, so other topics are sent without an alias
+    let publish = publish_and_expect(&managed_client, &mock_server, "test/alias/b").await;
+    assert_eq!(publish.topic_name.as_str(), "test/alias/b");
+    assert_eq!(publish.other_properties.topic_alias, None);
+    puback(&mock_server, &publish);
+
+    // Aliases do not carry over to a new connection, so the unacknowledged PUBLISH is replayed
+    // with its topic, and aliases are assigned again
+    mock_reconnect_policy_controller.set_next_delay(Some(Duration::ZERO));
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    monitor.disconnected().await;
+    mock_server
+        .expect_connect_and_respond(connack_with_topic_alias_maximum(true, 1))
+        .await;
+    let replayed_publish = mock_server.expect_publish().await;
+    assert!(matches!(
+        replayed_publish.packet_identifier_dup_qos,
+        mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(_, true)
+    ));
+    assert_eq!(replayed_publish.topic_name.as_str(), "test/alias/a");
+    assert_eq!(replayed_publish.other_properties.topic_alias, None);
+    puback(&mock_server, &replayed_publish);
+    let publish = publish_and_expect(&managed_client, &mock_server, "test/alias/b").await;
+    assert_eq!(publish.topic_name.as_str(), "test/alias/b");
+    assert_eq!(
+        publish.other_properties.topic_alias.map(NonZeroU16::get),
+        Some(1)
+    );
+    puback(&mock_server, &publish);
+}
+
+#[tokio::test]
+async fn outgoing_topic_aliases_disabled_by_default() {
+    let (session, mock_server) = setup_client_and_mock_server("topic_alias_disabled_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server
+        .expect_connect_and_respond(connack_with_topic_alias_maximum(false, 10))
+        .await;
+
+    for _ in 0..2 {
+        let publish = publish_and_expect(&managed_client, &mock_server, "test/alias/a").await;
+        assert_eq!(publish.topic_name.as_str(), "test/alias/a");
+        assert_eq!(publish.other_properties.topic_alias, None);
+        puback(&mock_server, &publish);
+    }
+}
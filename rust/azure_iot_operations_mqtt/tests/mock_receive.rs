@@ -3,6 +3,8 @@
 
 #![allow(clippy::similar_names)]
 
+use std::num::NonZeroU32;
+
 use azure_iot_operations_mqtt::azure_mqtt::mqtt_proto;
 use bytes::Bytes;
 use futures_util::FutureExt;
@@ -10,8 +12,8 @@ use tokio_test::{assert_pending, assert_ready};
 
 use azure_iot_operations_mqtt::{
     aio::connection_settings::MqttConnectionSettingsBuilder,
-    control_packet::TopicFilter,
-    session::{Session, SessionOptionsBuilder, SessionPubReceiver},
+    control_packet::{QoS, RetainOptions, SubscribeProperties, TopicFilter},
+    session::{Session, SessionManagedClient, SessionOptionsBuilder, SessionPubReceiver},
     test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
 };
 
@@ -935,6 +937,182 @@ async fn dispatch_rules_filter_matching() {
     assert!(mock_server.expect_puback().await.packet_identifier == 4);
 }
 
+fn proto_publish_qos0_with_subscription_identifiers(
+    topic_name: impl AsRef<str>,
+    counter: u16,
+    subscription_identifiers: Vec<NonZeroU32>,
+) -> mqtt_proto::Publish<Bytes> {
+    let mut publish = proto_publish_qos0(topic_name, counter);
+    publish.other_properties.subscription_identifiers = subscription_identifiers;
+    publish
+}
+
+/// Subscribe to the topic filter and return the subscription identifier the SUBSCRIBE was issued with
+async fn subscribe_and_get_subscription_identifier(
+    managed_client: &SessionManagedClient,
+    mock_server: &MockServer,
+    topic_filter: &str,
+) -> Option<NonZeroU32> {
+    let (subscribe_result, subscription_identifier) = tokio::join!(
+        async {
+            managed_client
+                .subscribe(
+                    TopicFilter::new(topic_filter).unwrap(),
+                    QoS::AtLeastOnce,
+                    false,
+                    RetainOptions::default(),
+                    SubscribeProperties::default(),
+                )
+                .await
+                .unwrap()
+                .await
+        },
+        async {
+            let subscribe = mock_server.expect_subscribe().await;
+            mock_server.accept_subscribe(&subscribe);
+            subscribe.other_properties.subscription_identifier
+        }
+    );
+    subscribe_result.unwrap();
+    subscription_identifier
+}
+
+#[tokio::test]
+async fn overlapping_filters_routed_by_subscription_identifier() {
+    let (session, mock_server) =
+        setup_client_and_mock_server("subscription_identifier_routing_test_client");
+    let managed_client = session.create_managed_client();
+    let monitor = session.create_session_monitor();
+
+    // Start the session run loop. The server supports subscription identifiers by default.
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    // Support is known once connected
+    monitor.connected().await;
+
+    // Create receivers for overlapping filters and subscribe to them
+    let mut receiver1 =
+        managed_client.create_filtered_pub_receiver(TopicFilter::new("sport/tennis/+").unwrap());
+    let mut receiver2 =
+        managed_client.create_filtered_pub_receiver(TopicFilter::new("sport/#").unwrap());
+    let subscription_identifier1 =
+        subscribe_and_get_subscription_identifier(&managed_client, &mock_server, "sport/tennis/+")
+            .await
+            .expect("Expected subscription identifier to be assigned");
+    let subscription_identifier2 =
+        subscribe_and_get_subscription_identifier(&managed_client, &mock_server, "sport/#")
+            .await
+            .expect("Expected subscription identifier to be assigned");
+    assert_ne!(subscription_identifier1, subscription_identifier2);
+
+    // A publish matching both filters is only delivered to the receivers of the subscriptions
+    // identified on the publish, and the identifiers are available on the delivered publish
+    let proto_publish1 = proto_publish_qos0_with_subscription_identifiers(
+        "sport/tennis/player1",
+        1,
+        vec![subscription_identifier1],
+    );
+    let expected_publish1 = proto_publish1.clone().into();
+    mock_server.send_publish(proto_publish1);
+    let publish = receiver1.recv().await.unwrap();
+    assert_eq!(publish, expected_publish1);
+    assert_eq!(
+        publish.properties.subscription_identifiers,
+        vec![subscription_identifier1]
+    );
+    assert!(receiver2.recv().now_or_never().is_none());
+
+    let proto_publish2 = proto_publish_qos0_with_subscription_identifiers(
+        "sport/tennis/player1",
+        2,
+        vec![subscription_identifier2],
+    );
+    let expected_publish2 = proto_publish2.clone().into();
+    mock_server.send_publish(proto_publish2);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish2);
+    assert!(receiver1.recv().now_or_never().is_none());
+
+    let proto_publish3 = proto_publish_qos0_with_subscription_identifiers(
+        "sport/tennis/player1",
+        3,
+        vec![subscription_identifier1, subscription_identifier2],
+    );
+    let expected_publish3 = proto_publish3.clone().into();
+    mock_server.send_publish(proto_publish3);
+    assert_eq!(receiver1.recv().await.unwrap(), expected_publish3);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish3);
+
+    // A publish with an unknown subscription identifier falls back to topic filter matching
+    let proto_publish4 = proto_publish_qos0_with_subscription_identifiers(
+        "sport/tennis/player1",
+        4,
+        vec![NonZeroU32::new(999).unwrap()],
+    );
+    let expected_publish4 = proto_publish4.clone().into();
+    mock_server.send_publish(proto_publish4);
+    assert_eq!(receiver1.recv().await.unwrap(), expected_publish4);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish4);
+
+    // A publish without subscription identifiers falls back to topic filter matching
+    let proto_publish5 = proto_publish_qos0("sport/hockey/player1", 5);
+    let expected_publish5 = proto_publish5.clone().into();
+    mock_server.send_publish(proto_publish5);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish5);
+    assert!(receiver1.recv().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn overlapping_filters_routed_by_topic_without_subscription_identifier_support() {
+    let (session, mock_server) =
+        setup_client_and_mock_server("subscription_identifier_unsupported_test_client");
+    let managed_client = session.create_managed_client();
+    let monitor = session.create_session_monitor();
+
+    // Start the session run loop. The server does not support subscription identifiers.
+    tokio::task::spawn(session.run());
+    mock_server
+        .expect_connect_and_respond(mqtt_proto::ConnAck {
+            reason_code: mqtt_proto::ConnectReasonCode::Success {
+                session_present: true,
+            },
+            other_properties: mqtt_proto::ConnAckOtherProperties {
+                subscription_identifiers_available: false,
+                ..Default::default()
+            },
+        })
+        .await;
+    monitor.connected().await;
+
+    // Create receivers for overlapping filters and subscribe to them without identifiers
+    let mut receiver1 =
+        managed_client.create_filtered_pub_receiver(TopicFilter::new("sport/tennis/+").unwrap());
+    let mut receiver2 =
+        managed_client.create_filtered_pub_receiver(TopicFilter::new("sport/#").unwrap());
+    assert!(
+        subscribe_and_get_subscription_identifier(&managed_client, &mock_server, "sport/tennis/+")
+            .await
+            .is_none()
+    );
+    assert!(
+        subscribe_and_get_subscription_identifier(&managed_client, &mock_server, "sport/#")
+            .await
+            .is_none()
+    );
+
+    // A publish matching both filters is delivered to both receivers
+    let proto_publish1 = proto_publish_qos0("sport/tennis/player1", 1);
+    let expected_publish1 = proto_publish1.clone().into();
+    mock_server.send_publish(proto_publish1);
+    assert_eq!(receiver1.recv().await.unwrap(), expected_publish1);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish1);
+
+    let proto_publish2 = proto_publish_qos0("sport/hockey/player1", 2);
+    let expected_publish2 = proto_publish2.clone().into();
+    mock_server.send_publish(proto_publish2);
+    assert_eq!(receiver2.recv().await.unwrap(), expected_publish2);
+    assert!(receiver1.recv().now_or_never().is_none());
+}
+
 // TODO:
 // - drops / transport disconnects + ack tokens + completion tokens
 // - auto-ack when dropped without having been received?
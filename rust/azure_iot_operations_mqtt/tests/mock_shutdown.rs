@@ -0,0 +1,126 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "signal")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::azure_mqtt::mqtt_proto;
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    control_packet::{PublishProperties, TopicName},
+    session::{
+        Session, SessionOptionsBuilder,
+        shutdown::{GracefulShutdown, ShutdownOutcome},
+    },
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+#[tokio::test]
+async fn shutdown_drains_before_disconnect() {
+    let (session, mock_server) = setup_client_and_mock_server("shutdown_drain_test_client");
+    let managed_client = session.create_managed_client();
+    let shutdown = GracefulShutdown::new(&session, Duration::from_secs(10));
+    let session_jh = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(false).await;
+
+    let completion_token = managed_client
+        .publish_qos1(
+            TopicName::new("test/topic").unwrap(),
+            false,
+            "payload",
+            PublishProperties::default(),
+        )
+        .await
+        .unwrap();
+    let publish = mock_server.expect_publish().await;
+    let shutdown_jh = tokio::task::spawn(async move { shutdown.shutdown().await });
+
+    // The Session does not disconnect while the PUBLISH is in-flight
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    mock_server.expect_no_packet();
+    assert!(!shutdown_jh.is_finished());
+
+    match publish.packet_identifier_dup_qos {
+        mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) => {
+            mock_server.send_puback(packet_identifier);
+        }
+        other => panic!("Expected QoS 1 PUBLISH, but received {other:?}"),
+    }
+    completion_token.await.unwrap();
+    tokio::time::timeout(Duration::from_secs(2), mock_server.expect_disconnect())
+        .await
+        .unwrap();
+
+    let outcome = tokio::time::timeout(Duration::from_secs(5), shutdown_jh)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        outcome,
+        ShutdownOutcome {
+            drained: true,
+            graceful: true,
+            stopped: true,
+        }
+    );
+    assert!(session_jh.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn shutdown_within_grace_period() {
+    let (session, mock_server) = setup_client_and_mock_server("shutdown_grace_test_client");
+    let managed_client = session.create_managed_client();
+    let shutdown = GracefulShutdown::new(&session, Duration::from_millis(500));
+    let session_jh = tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(false).await;
+
+    // The PUBLISH is never acknowledged, so the Session exits once the grace period is spent
+    // waiting for it
+    let _completion_token = managed_client
+        .publish_qos1(
+            TopicName::new("test/topic").unwrap(),
+            false,
+            "payload",
+            PublishProperties::default(),
+        )
+        .await
+        .unwrap();
+    mock_server.expect_publish().await;
+
+    let start = tokio::time::Instant::now();
+    let outcome = shutdown.shutdown().await;
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(!outcome.drained);
+    assert!(outcome.graceful);
+    assert!(outcome.stopped);
+    tokio::time::timeout(Duration::from_secs(2), mock_server.expect_disconnect())
+        .await
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(2), session_jh)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,245 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Counter client using commands and telemetry defined at runtime from a JSON configuration,
+//! interoperating with the generated counter server sample.
+
+use std::{env, time::Duration};
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{
+    Session, SessionExitHandle, SessionManagedClient, SessionOptionsBuilder,
+};
+use azure_iot_operations_protocol::{
+    application::{ApplicationContext, ApplicationContextBuilder},
+    dynamic::{DynamicCommand, DynamicTelemetry, JsonSchema},
+};
+use serde_json::{Value, json};
+use tokio::time::sleep;
+
+/// Definitions of the counter commands and telemetry, as could be loaded from a file.
+const DEFINITIONS: &str = r#"{
+    "commands": {
+        "readCounter": {
+            "requestTopicPattern": "rpc/command-samples/{executorId}/{commandName}",
+            "responseSchema": {
+                "type": "object",
+                "properties": { "CounterResponse": { "type": "integer" } },
+                "required": ["CounterResponse"]
+            }
+        },
+        "increment": {
+            "requestTopicPattern": "rpc/command-samples/{executorId}/{commandName}",
+            "requestSchema": {
+                "type": "object",
+                "properties": { "IncrementValue": { "type": "integer" } },
+                "required": ["IncrementValue"]
+            },
+            "responseSchema": {
+                "type": "object",
+                "properties": { "CounterResponse": { "type": "integer" } },
+                "required": ["CounterResponse"]
+            }
+        }
+    },
+    "telemetry": {
+        "topicPattern": "telemetry/telemetry-samples/counterValue",
+        "schema": {
+            "type": "object",
+            "properties": { "CounterValue": { "type": "integer" } }
+        }
+    }
+}"#;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .init();
+
+    // Create a session
+    let connection_settings = MqttConnectionSettingsBuilder::from_environment()?.build()?;
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()?;
+    let session = Session::new(session_options)?;
+
+    let application_context = ApplicationContextBuilder::default().build()?;
+
+    // Load the command and telemetry definitions
+    let definitions: Value = serde_json::from_str(DEFINITIONS)?;
+    let read_counter = command_definition("readCounter", &definitions["commands"]["readCounter"])?;
+    let increment = command_definition("increment", &definitions["commands"]["increment"])?;
+    let telemetry = DynamicTelemetry::new(
+        definitions["telemetry"]["topicPattern"]
+            .as_str()
+            .ok_or("missing telemetry topic pattern")?,
+    )
+    .schema(JsonSchema::new(definitions["telemetry"]["schema"].clone())?);
+
+    // Use the managed client to run telemetry checks in another task
+    let counter_telemetry_check_handle = tokio::task::spawn(counter_telemetry_check(
+        application_context.clone(),
+        session.create_managed_client(),
+        session.create_exit_handle(),
+        telemetry,
+    ));
+
+    // Use the managed client to run command invocations in another task
+    let increment_and_check_handle = tokio::task::spawn(increment_and_check(
+        application_context,
+        session.create_managed_client(),
+        read_counter,
+        increment,
+    ));
+
+    // Wait for all tasks to finish and run the session
+    let (telemetry_result, increment_result, session_result) = tokio::join!(
+        counter_telemetry_check_handle,
+        increment_and_check_handle,
+        session.run()
+    );
+    telemetry_result?;
+    increment_result?;
+    session_result?;
+    Ok(())
+}
+
+/// Create a [`DynamicCommand`] from its JSON definition.
+fn command_definition(
+    name: &str,
+    definition: &Value,
+) -> Result<DynamicCommand, Box<dyn std::error::Error>> {
+    let mut command = DynamicCommand::new(
+        name,
+        definition["requestTopicPattern"]
+            .as_str()
+            .ok_or("missing request topic pattern")?,
+    );
+    if let Some(schema) = definition.get("requestSchema") {
+        command = command.request_schema(JsonSchema::new(schema.clone())?);
+    }
+    if let Some(schema) = definition.get("responseSchema") {
+        command = command.response_schema(JsonSchema::new(schema.clone())?);
+    }
+    Ok(command)
+}
+
+/// Wait for the associated telemetry. Then exit the session.
+async fn counter_telemetry_check(
+    application_context: ApplicationContext,
+    client: SessionManagedClient,
+    exit_handle: SessionExitHandle,
+    telemetry: DynamicTelemetry,
+) {
+    // Create receiver
+    let mut counter_value_receiver = telemetry
+        .receiver(application_context, client, false)
+        .unwrap();
+
+    log::info!("Waiting for associated telemetry");
+    let mut telemetry_count = 0;
+
+    loop {
+        tokio::select! {
+            telemetry_res = counter_value_receiver.recv() => {
+                let (message, ack_token) = telemetry_res.unwrap().unwrap();
+
+                log::info!("Telemetry reported counter value: {}", message.payload["CounterValue"]);
+
+                // Acknowledge the message
+                if let Some(ack_token) = ack_token {
+                    ack_token.ack().await.unwrap();
+                }
+
+                telemetry_count += 1;
+            },
+            () = sleep(Duration::from_secs(5))=> {
+                if telemetry_count >= 15 {
+                    break;
+                }
+                panic!("Telemetry not finished");
+            }
+        }
+    }
+
+    log::info!("Telemetry finished");
+    counter_value_receiver.shutdown().await.unwrap();
+
+    // Exit the session now that we're done
+    exit_handle.try_exit().unwrap();
+}
+
+/// Send a read request, 15 increment requests, and another read request and wait for their responses.
+async fn increment_and_check(
+    application_context: ApplicationContext,
+    client: SessionManagedClient,
+    read_counter: DynamicCommand,
+    increment: DynamicCommand,
+) {
+    // Create invokers
+    let increment_invoker = increment
+        .invoker(application_context.clone(), client.clone())
+        .unwrap();
+    let read_counter_invoker = read_counter.invoker(application_context, client).unwrap();
+
+    // Get the target executor ID from the environment
+    let target_executor_id = env::var("COUNTER_SERVER_ID").unwrap();
+
+    // Initial counter read from the server
+    log::info!("Calling readCounter");
+    let read_counter_request = read_counter_invoker
+        .request_builder()
+        .timeout(Duration::from_secs(10))
+        .executor_id(&target_executor_id)
+        .build()
+        .unwrap();
+    let read_counter_response = read_counter_invoker
+        .invoke(read_counter_request)
+        .await
+        .unwrap();
+    log::info!(
+        "Counter value: {}",
+        read_counter_response.payload["CounterResponse"]
+    );
+
+    // Increment the counter 15 times on the server
+    for _ in 0..15 {
+        log::info!("Calling increment");
+        let increment_request = increment_invoker
+            .request_builder()
+            .timeout(Duration::from_secs(10))
+            .executor_id(&target_executor_id)
+            .payload(json!({ "IncrementValue": 1 }))
+            .unwrap()
+            .build()
+            .unwrap();
+        let increment_response = increment_invoker.invoke(increment_request).await.unwrap();
+        log::info!(
+            "Counter value after increment: {}",
+            increment_response.payload["CounterResponse"]
+        );
+    }
+
+    // Final counter read from the server
+    log::info!("Calling readCounter");
+    let read_counter_request = read_counter_invoker
+        .request_builder()
+        .timeout(Duration::from_secs(10))
+        .executor_id(&target_executor_id)
+        .build()
+        .unwrap();
+    let read_counter_response = read_counter_invoker
+        .invoke(read_counter_request)
+        .await
+        .unwrap();
+    log::info!(
+        "Counter value: {}",
+        read_counter_response.payload["CounterResponse"]
+    );
+
+    read_counter_invoker.shutdown().await.unwrap();
+    increment_invoker.shutdown().await.unwrap();
+}
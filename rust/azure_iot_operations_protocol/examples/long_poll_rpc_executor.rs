@@ -0,0 +1,125 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+use std::time::Duration;
+
+use env_logger::Builder;
+use tokio::sync::watch;
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_protocol::rpc_command;
+use azure_iot_operations_protocol::rpc_command::executor::HeldRequestOutcome;
+
+const CLIENT_ID: &str = "aio_example_long_poll_executor_client";
+const HOSTNAME: &str = "localhost";
+const PORT: u16 = 1883;
+const REQUEST_TOPIC_PATTERN: &str = "topic/for/wait";
+const EVENT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .init();
+
+    // Create a Session
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(CLIENT_ID)
+        .hostname(HOSTNAME)
+        .tcp_port(PORT)
+        .keep_alive(Duration::from_secs(5))
+        .use_tls(false)
+        .build()?;
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()?;
+    let session = Session::new(session_options).unwrap();
+
+    // Create an ApplicationContext
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    // Create an RPC command Executor for the 'waitForEvent' command, whose response is only sent
+    // once the next event happens
+    let wait_executor_options = rpc_command::executor::OptionsBuilder::default()
+        .request_topic_pattern(REQUEST_TOPIC_PATTERN)
+        .command_name("waitForEvent")
+        .build()
+        .unwrap();
+    let wait_executor: rpc_command::Executor<Vec<u8>, Vec<u8>> =
+        application_context.executor(session.create_managed_client(), wait_executor_options)?;
+
+    // Events are published on a watch channel, so that every waiting request sees the next one
+    let (event_tx, event_rx) = watch::channel(0u64);
+
+    // Run the Session, the event source and the Executor loop concurrently
+    tokio::select! {
+        r1 = wait_executor_loop(wait_executor, event_rx) => r1.map_err(|e| e as Box<dyn std::error::Error>)?,
+        () = event_source(event_tx) => {},
+        r2 = session.run() => r2?,
+    }
+
+    Ok(())
+}
+
+/// Produce an event periodically
+async fn event_source(event_tx: watch::Sender<u64>) {
+    let mut interval = tokio::time::interval(EVENT_INTERVAL);
+    loop {
+        interval.tick().await;
+        event_tx.send_modify(|event| *event += 1);
+        log::info!("Event {} happened", *event_tx.borrow());
+    }
+}
+
+/// Hold incoming 'waitForEvent' command requests until the next event happens
+async fn wait_executor_loop(
+    mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>>,
+    event_rx: watch::Receiver<u64>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    while let Some(recv_result) = executor.recv().await {
+        // Hold the request, and respond to it from its own task once the next event happens
+        let held = recv_result?.hold();
+        let mut event_rx = event_rx.clone();
+        event_rx.mark_unchanged();
+        tokio::task::spawn(async move {
+            let event = tokio::select! {
+                () = held.expired() => {
+                    // The invoker has given up waiting, so there is nothing to send
+                    log::info!("'waitForEvent' request expired before the next event");
+                    return;
+                }
+                changed = event_rx.changed() => {
+                    if changed.is_err() {
+                        // The event source has stopped, so the request is dropped
+                        return;
+                    }
+                    *event_rx.borrow_and_update()
+                }
+            };
+            let response = rpc_command::executor::ResponseBuilder::default()
+                .payload(event.to_string().into_bytes())
+                .unwrap()
+                .build()
+                .unwrap();
+            match held.complete(response).await {
+                Ok(HeldRequestOutcome::Completed) => {
+                    log::info!("Sent event {event} to 'waitForEvent' command request");
+                }
+                Ok(HeldRequestOutcome::Expired) => {
+                    log::info!("'waitForEvent' request expired as event {event} happened");
+                }
+                Err(e) => {
+                    log::error!("Error sending response to 'waitForEvent' command request: {e}");
+                }
+            }
+        });
+    }
+
+    // Shut down if there are no more requests
+    executor.shutdown().await?;
+
+    Ok(())
+}
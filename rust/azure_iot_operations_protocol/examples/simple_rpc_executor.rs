@@ -49,11 +49,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()
         .unwrap();
     let incr_executor: rpc_command::Executor<IncrRequestPayload, IncrResponsePayload> =
-        rpc_command::Executor::new(
-            application_context,
-            session.create_managed_client(),
-            incr_executor_options,
-        )?;
+        application_context.executor(session.create_managed_client(), incr_executor_options)?;
 
     // Run the Session and the Executor loop concurrently
     tokio::select! {
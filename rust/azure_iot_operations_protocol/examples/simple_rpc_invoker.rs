@@ -52,11 +52,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()
         .unwrap();
     let incr_invoker: rpc_command::Invoker<IncrRequestPayload, IncrResponsePayload> =
-        rpc_command::Invoker::new(
-            application_context,
-            session.create_managed_client(),
-            incr_invoker_options,
-        )?;
+        application_context.invoker(session.create_managed_client(), incr_invoker_options)?;
 
     // Run the Session and and the 'increment' command invoker concurrently
     tokio::select! {
@@ -55,11 +55,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )]))
         .auto_ack(false)
         .build()?;
-    let receiver: telemetry::Receiver<SampleTelemetry> = telemetry::Receiver::new(
-        application_context,
-        session.create_managed_client(),
-        receiver_options,
-    )?;
+    let receiver: telemetry::Receiver<SampleTelemetry> = application_context
+        .telemetry_receiver(session.create_managed_client(), receiver_options)?;
 
     // Run the Session and the telemetry loop concurrently
     tokio::select! {
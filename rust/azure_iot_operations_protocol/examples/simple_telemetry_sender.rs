@@ -50,11 +50,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let sender_options = telemetry::sender::OptionsBuilder::default()
         .topic_pattern(TOPIC)
         .build()?;
-    let telemetry_sender: telemetry::Sender<SampleTelemetry> = telemetry::Sender::new(
-        application_context,
-        session.create_managed_client(),
-        sender_options,
-    )?;
+    let telemetry_sender: telemetry::Sender<SampleTelemetry> =
+        application_context.telemetry_sender(session.create_managed_client(), sender_options)?;
 
     // Run the session and the telemetry loop concurrently
     tokio::select! {
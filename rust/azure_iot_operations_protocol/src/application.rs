@@ -8,7 +8,56 @@ use std::{
     time::Duration,
 };
 
-use crate::common::hybrid_logical_clock::{DEFAULT_MAX_CLOCK_DRIFT, HLCError, HybridLogicalClock};
+use azure_iot_operations_mqtt::session::SessionManagedClient;
+
+use crate::{
+    common::{
+        aio_protocol_error::AIOProtocolError,
+        hybrid_logical_clock::{
+            DEFAULT_MAX_CLOCK_DRIFT, HLCError, HybridLogicalClock, validate_node_id,
+        },
+        payload_serialize::PayloadSerialize,
+    },
+    rpc_command, telemetry,
+};
+
+/// Default message expiry assumed for command requests that don't specify one
+pub(crate) const DEFAULT_MESSAGE_EXPIRY: Duration = Duration::from_secs(10);
+
+/// Default additional time to keep a command response cached after the command expires
+pub(crate) const DEFAULT_CACHE_EXPIRY_BUFFER: Duration = Duration::from_secs(60);
+
+/// Minimum allowed default message expiry
+const MIN_DEFAULT_MESSAGE_EXPIRY: Duration = Duration::from_secs(1);
+
+/// Maximum allowed default message expiry
+const MAX_DEFAULT_MESSAGE_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum allowed cache expiry buffer
+const MAX_CACHE_EXPIRY_BUFFER: Duration = Duration::from_secs(10 * 60);
+
+/// Validates that a default message expiry is within bounds
+pub(crate) fn validate_default_message_expiry(
+    default_message_expiry: Duration,
+) -> Result<(), String> {
+    if !(MIN_DEFAULT_MESSAGE_EXPIRY..=MAX_DEFAULT_MESSAGE_EXPIRY).contains(&default_message_expiry)
+    {
+        return Err(format!(
+            "default_message_expiry must be between {MIN_DEFAULT_MESSAGE_EXPIRY:?} and {MAX_DEFAULT_MESSAGE_EXPIRY:?}, got {default_message_expiry:?}"
+        ));
+    }
+    Ok(())
+}
+
+/// Validates that a cache expiry buffer is within bounds
+pub(crate) fn validate_cache_expiry_buffer(cache_expiry_buffer: Duration) -> Result<(), String> {
+    if cache_expiry_buffer > MAX_CACHE_EXPIRY_BUFFER {
+        return Err(format!(
+            "cache_expiry_buffer must be at most {MAX_CACHE_EXPIRY_BUFFER:?}, got {cache_expiry_buffer:?}"
+        ));
+    }
+    Ok(())
+}
 
 /// Struct containing the application-level [`HybridLogicalClock`].
 pub struct ApplicationHybridLogicalClock {
@@ -28,6 +77,19 @@ impl ApplicationHybridLogicalClock {
         }
     }
 
+    /// Creates a new [`ApplicationHybridLogicalClock`] with the provided maximum clock drift and
+    /// node identifier, instead of a randomly generated one.
+    ///
+    /// See [`ApplicationContextBuilder::hlc_node_id`] for the requirements on the node identifier
+    /// and the implications of sharing it.
+    #[must_use]
+    pub fn new_with_node_id(max_clock_drift: Duration, node_id: impl Into<String>) -> Self {
+        Self {
+            hlc: Mutex::new(HybridLogicalClock::new_with_node_id(node_id.into())),
+            max_clock_drift,
+        }
+    }
+
     /// Reads the current value of the [`ApplicationHybridLogicalClock`]
     /// and returns a new [`HybridLogicalClock`] that is a snapshot of
     /// the current value of the [`ApplicationHybridLogicalClock`].
@@ -76,9 +138,320 @@ impl ApplicationHybridLogicalClock {
 /// Struct containing the application context for the Azure IoT Operations SDK.
 ///
 /// <div class="warning"> There must be a max of one per session and there should only be one per application (which may contain multiple sessions). </div>
+///
+/// Envoys can be created directly from the [`ApplicationContext`], which ensures that they all
+/// share the same [`ApplicationHybridLogicalClock`]:
+/// ```
+/// # use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+/// # use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+/// # use azure_iot_operations_protocol::rpc_command;
+/// # use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+/// # let connection_settings = MqttConnectionSettingsBuilder::default()
+/// #     .client_id("test_client")
+/// #     .hostname("localhost")
+/// #     .build().unwrap();
+/// # let session_options = SessionOptionsBuilder::default()
+/// #     .connection_settings(connection_settings)
+/// #     .build().unwrap();
+/// # let mqtt_session = Session::new(session_options).unwrap();
+/// let application_context = ApplicationContextBuilder::default().build().unwrap();
+/// let invoker_options = rpc_command::invoker::OptionsBuilder::default()
+///   .request_topic_pattern("test/request")
+///   .command_name("test_command")
+///   .build().unwrap();
+/// # tokio_test::block_on(async {
+/// let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = application_context
+///   .invoker(mqtt_session.create_managed_client(), invoker_options)
+///   .unwrap();
+/// # })
+/// ```
 #[derive(Builder, Clone)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct ApplicationContext {
     /// The [`ApplicationHybridLogicalClock`] used by the application.
     #[builder(default = "Arc::new(ApplicationHybridLogicalClock::new(DEFAULT_MAX_CLOCK_DRIFT))")]
     pub application_hlc: Arc<ApplicationHybridLogicalClock>,
+    /// Message expiry assumed by executors for command requests that don't specify one, rounded
+    /// up to the nearest second. Must be between 1 second and 1 hour. Default is 10 seconds.
+    ///
+    /// Can be overridden per executor with
+    /// [`default_message_expiry`](rpc_command::executor::OptionsBuilder::default_message_expiry).
+    #[builder(default = "DEFAULT_MESSAGE_EXPIRY")]
+    pub default_message_expiry: Duration,
+    /// Additional time executors keep a command response cached after the command expires, to
+    /// respond to duplicate requests. Must be at most 10 minutes. Default is 60 seconds.
+    ///
+    /// Can be overridden per executor with
+    /// [`cache_expiry_buffer`](rpc_command::executor::OptionsBuilder::cache_expiry_buffer).
+    #[builder(default = "DEFAULT_CACHE_EXPIRY_BUFFER")]
+    pub cache_expiry_buffer: Duration,
+}
+
+impl ApplicationContextBuilder {
+    /// Set the node identifier used in all [`HybridLogicalClock`] timestamps from this
+    /// [`ApplicationContext`], instead of a randomly generated one. This keeps the identity of the
+    /// application stable across restarts, and makes timestamps reproducible in tests.
+    ///
+    /// Equivalent to setting [`application_hlc`](ApplicationContextBuilder::application_hlc) to
+    /// an [`ApplicationHybridLogicalClock`] with the [`DEFAULT_MAX_CLOCK_DRIFT`] and this node
+    /// identifier. The node identifier must be between 1 and 256 characters long and consist only
+    /// of ASCII letters, digits, `-`, `_` and `.`.
+    ///
+    /// <div class="warning"> The node identifier must be unique among all running applications.
+    /// Updates against a timestamp with the same node identifier are ignored, so two processes
+    /// sharing a node identifier don't advance their clocks based on each other's timestamps, and
+    /// can issue identical timestamps for unrelated events, breaking causal ordering. </div>
+    pub fn hlc_node_id(&mut self, node_id: impl Into<String>) -> &mut Self {
+        self.application_hlc = Some(Arc::new(ApplicationHybridLogicalClock::new_with_node_id(
+            DEFAULT_MAX_CLOCK_DRIFT,
+            node_id,
+        )));
+        self
+    }
+
+    /// Validate the [`ApplicationContext`].
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if
+    /// [`default_message_expiry`](ApplicationContextBuilder::default_message_expiry) or
+    /// [`cache_expiry_buffer`](ApplicationContextBuilder::cache_expiry_buffer) is out of bounds,
+    /// or if the node identifier of the [`application_hlc`](ApplicationContextBuilder::application_hlc)
+    /// is not legal.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(application_hlc) = &self.application_hlc {
+            validate_node_id(&application_hlc.read().node_id)?;
+        }
+        if let Some(default_message_expiry) = self.default_message_expiry {
+            validate_default_message_expiry(default_message_expiry)?;
+        }
+        if let Some(cache_expiry_buffer) = self.cache_expiry_buffer {
+            validate_cache_expiry_buffer(cache_expiry_buffer)?;
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationContext {
+    /// Creates a new [`rpc_command::Invoker`] that uses this [`ApplicationContext`].
+    ///
+    /// Equivalent to [`rpc_command::Invoker::new`] with a clone of this [`ApplicationContext`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] in the same cases as [`rpc_command::Invoker::new`]
+    pub fn invoker<TReq, TResp>(
+        &self,
+        client: SessionManagedClient,
+        options: rpc_command::invoker::Options,
+    ) -> Result<rpc_command::Invoker<TReq, TResp>, AIOProtocolError>
+    where
+        TReq: PayloadSerialize + 'static,
+        TResp: PayloadSerialize + 'static,
+    {
+        rpc_command::Invoker::new(self.clone(), client, options)
+    }
+
+    /// Creates a new [`rpc_command::Executor`] that uses this [`ApplicationContext`].
+    ///
+    /// Equivalent to [`rpc_command::Executor::new`] with a clone of this [`ApplicationContext`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] in the same cases as [`rpc_command::Executor::new`]
+    pub fn executor<TReq, TResp>(
+        &self,
+        client: SessionManagedClient,
+        options: rpc_command::executor::Options,
+    ) -> Result<rpc_command::Executor<TReq, TResp>, AIOProtocolError>
+    where
+        TReq: PayloadSerialize + Send + 'static,
+        TResp: PayloadSerialize + Send + 'static,
+    {
+        rpc_command::Executor::new(self.clone(), client, options)
+    }
+
+    /// Creates a new [`telemetry::Sender`] that uses this [`ApplicationContext`].
+    ///
+    /// Equivalent to [`telemetry::Sender::new`] with a clone of this [`ApplicationContext`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] in the same cases as [`telemetry::Sender::new`]
+    pub fn telemetry_sender<T>(
+        &self,
+        client: SessionManagedClient,
+        options: telemetry::sender::Options<T>,
+    ) -> Result<telemetry::Sender<T>, AIOProtocolError>
+    where
+        T: PayloadSerialize,
+    {
+        telemetry::Sender::new(self.clone(), client, options)
+    }
+
+    /// Creates a new [`telemetry::Receiver`] that uses this [`ApplicationContext`].
+    ///
+    /// Equivalent to [`telemetry::Receiver::new`] with a clone of this [`ApplicationContext`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] in the same cases as [`telemetry::Receiver::new`]
+    pub fn telemetry_receiver<T>(
+        &self,
+        client: SessionManagedClient,
+        options: telemetry::receiver::Options,
+    ) -> Result<telemetry::Receiver<T>, AIOProtocolError>
+    where
+        T: PayloadSerialize + Send + Sync + 'static,
+    {
+        telemetry::Receiver::new(self.clone(), client, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+    use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+
+    use test_case::test_case;
+
+    use super::*;
+    use crate::common::aio_protocol_error::AIOProtocolErrorKind;
+
+    // TODO: This should return a mock ManagedClient instead.
+    // Until that's possible, need to return a Session so that the Session doesn't go out of
+    // scope and render the ManagedClient unable to to be used correctly.
+    fn create_session() -> Session {
+        let connection_settings = MqttConnectionSettingsBuilder::default()
+            .hostname("localhost")
+            .client_id("test_client")
+            .build()
+            .unwrap();
+        let session_options = SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .build()
+            .unwrap();
+        Session::new(session_options).unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_envoys_from_context() {
+        let session = create_session();
+        let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+        let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = application_context
+            .invoker(
+                session.create_managed_client(),
+                rpc_command::invoker::OptionsBuilder::default()
+                    .request_topic_pattern("test/request")
+                    .command_name("test_command")
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = application_context
+            .executor(
+                session.create_managed_client(),
+                rpc_command::executor::OptionsBuilder::default()
+                    .request_topic_pattern("test/request")
+                    .command_name("test_command")
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let sender: telemetry::Sender<Vec<u8>> = application_context
+            .telemetry_sender(
+                session.create_managed_client(),
+                telemetry::sender::OptionsBuilder::default()
+                    .topic_pattern("test/telemetry")
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let receiver: telemetry::Receiver<Vec<u8>> = application_context
+            .telemetry_receiver(
+                session.create_managed_client(),
+                telemetry::receiver::OptionsBuilder::default()
+                    .topic_pattern("test/telemetry")
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        drop((invoker, executor, sender, receiver));
+    }
+
+    #[tokio::test]
+    async fn create_invoker_from_context_invalid_options() {
+        let session = create_session();
+        let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+        let result: Result<rpc_command::Invoker<Vec<u8>, Vec<u8>>, _> = application_context
+            .invoker(
+                session.create_managed_client(),
+                rpc_command::invoker::OptionsBuilder::default()
+                    .request_topic_pattern("test/request")
+                    .command_name(String::new())
+                    .build()
+                    .unwrap(),
+            );
+        let error = result.err().unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+        assert_eq!(error.property_name, Some("command_name".to_string()));
+    }
+
+    #[test]
+    fn hlc_node_id() {
+        let application_context = ApplicationContextBuilder::default()
+            .hlc_node_id("test-node")
+            .build()
+            .unwrap();
+        assert_eq!(
+            application_context.application_hlc.read().node_id,
+            "test-node"
+        );
+        let timestamp = application_context.application_hlc.update_now().unwrap();
+        assert!(timestamp.ends_with(":test-node"));
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("test:node"; "separator")]
+    #[test_case("test node"; "whitespace")]
+    fn hlc_node_id_invalid(node_id: &str) {
+        assert!(
+            ApplicationContextBuilder::default()
+                .hlc_node_id(node_id)
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn default_expiry_settings() {
+        let application_context = ApplicationContextBuilder::default().build().unwrap();
+        assert_eq!(
+            application_context.default_message_expiry,
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            application_context.cache_expiry_buffer,
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test_case(Duration::from_secs(1), true; "min default message expiry")]
+    #[test_case(Duration::from_secs(3600), true; "max default message expiry")]
+    #[test_case(Duration::ZERO, false; "zero default message expiry")]
+    #[test_case(Duration::from_millis(999), false; "below min default message expiry")]
+    #[test_case(Duration::from_millis(3_600_001), false; "above max default message expiry")]
+    fn default_message_expiry_bounds(default_message_expiry: Duration, valid: bool) {
+        let result = ApplicationContextBuilder::default()
+            .default_message_expiry(default_message_expiry)
+            .build();
+        assert_eq!(result.is_ok(), valid);
+    }
+
+    #[test_case(Duration::ZERO, true; "zero cache expiry buffer")]
+    #[test_case(Duration::from_secs(600), true; "max cache expiry buffer")]
+    #[test_case(Duration::from_millis(600_001), false; "above max cache expiry buffer")]
+    fn cache_expiry_buffer_bounds(cache_expiry_buffer: Duration, valid: bool) {
+        let result = ApplicationContextBuilder::default()
+            .cache_expiry_buffer(cache_expiry_buffer)
+            .build();
+        assert_eq!(result.is_ok(), valid);
+    }
 }
@@ -12,6 +12,12 @@ pub mod payload_serialize;
 /// This module contains the error type for the Azure IoT Operations Protocol.
 pub mod aio_protocol_error;
 
+/// This module contains the end-to-end integrity check of message payloads.
+pub mod payload_checksum;
+
+/// This module contains the monotonic time source used for expiration and timeout logic.
+pub(crate) mod clock;
+
 /// This module contains the topic processor functions for the Azure IoT Operations Protocol
 pub(crate) mod topic_processor;
 
@@ -5,6 +5,9 @@ use std::error::Error;
 use std::fmt;
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 use crate::common::{
     hybrid_logical_clock::{HLCError, HLCErrorKind, ParseHLCError},
     topic_processor::{TopicPatternError, TopicPatternErrorKind},
@@ -13,7 +16,7 @@ use crate::common::{
 use super::user_properties::ProtocolReservedUserProperty;
 
 /// Represents the kind of error that occurs in an Azure IoT Operations Protocol
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AIOProtocolErrorKind {
     /// A required MQTT header property is missing on a received message
     HeaderMissing,
@@ -42,7 +45,8 @@ pub enum AIOProtocolErrorKind {
 }
 
 /// Represents the possible types of the value of a property in a [`AIOProtocolError`]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum Value {
     /// A 32-bit integer value
     Integer(i32),
@@ -86,6 +90,8 @@ pub struct AIOProtocolError {
     /// The acceptable major protocol versions for the command executor if it rejected the
     /// command request, or for the command invoker if it rejected the command response.
     pub supported_protocol_major_versions: Option<Vec<u16>>,
+    /// The correlation id of the command request relevant to the error being reported, if known
+    pub correlation_id: Option<Uuid>,
 }
 
 impl fmt::Display for AIOProtocolError {
@@ -252,6 +258,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -281,6 +288,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -310,6 +318,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -340,6 +349,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -368,6 +378,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -397,6 +408,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -425,6 +437,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -457,6 +470,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -486,6 +500,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -515,6 +530,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -542,6 +558,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -572,6 +589,7 @@ impl AIOProtocolError {
             command_name,
             protocol_version: Some(protocol_version),
             supported_protocol_major_versions: Some(supported_protocol_major_versions),
+            correlation_id: None,
         };
         e.ensure_error_message();
         e
@@ -617,3 +635,449 @@ impl From<ParseHLCError> for AIOProtocolError {
         )
     }
 }
+
+/// Version of the JSON representation of an [`AIOProtocolError`] produced by
+/// [`AIOProtocolError::to_json`]. Incremented whenever the shape of [`AIOProtocolErrorJson`]
+/// changes in a way that isn't backwards compatible.
+pub const AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Options controlling what is included in the JSON representation of an [`AIOProtocolError`]
+#[derive(Debug, Clone, Default)]
+pub struct AIOProtocolErrorJsonOptions {
+    /// The messages of the source errors of a [`PayloadInvalid`](AIOProtocolErrorKind::PayloadInvalid)
+    /// error may contain content of the payload that failed to serialize or deserialize, so they
+    /// are omitted by default. If set, they are included, each truncated to at most this many bytes.
+    pub payload_snippet_len: Option<usize>,
+}
+
+/// Stable, serializable representation of an [`AIOProtocolError`]
+///
+/// The nested error of an [`AIOProtocolError`] is rendered as the messages of its chain of
+/// source errors, outermost first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AIOProtocolErrorJson {
+    /// Version of this representation, see [`AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// The specific kind of error that occurred
+    pub kind: AIOProtocolErrorKind,
+    /// The error message
+    pub message: String,
+    /// True if the error was identified immediately after the API was called, prior to any attempted network communication
+    pub is_shallow: bool,
+    /// True if the error was detected by a remote component
+    pub is_remote: bool,
+    /// The messages of the chain of errors that caused this error, outermost first
+    #[serde(default)]
+    pub source_chain: Vec<String>,
+    /// The name of a MQTT header that is missing or has an invalid value
+    #[serde(default)]
+    pub header_name: Option<String>,
+    /// The value of a MQTT header that is invalid
+    #[serde(default)]
+    pub header_value: Option<String>,
+    /// The name of a timeout condition that elapsed
+    #[serde(default)]
+    pub timeout_name: Option<String>,
+    /// The duration of a timeout condition that elapsed, in milliseconds
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// The name of a function argument or a field in a struct or enum, configuration file, or environment variable that is missing or has an invalid value
+    #[serde(default)]
+    pub property_name: Option<String>,
+    /// The value of a function argument or a field in a struct or enum, configuration file, or environment variable that is invalid
+    #[serde(default)]
+    pub property_value: Option<Value>,
+    /// The name of a command relevant to the error being reported
+    #[serde(default)]
+    pub command_name: Option<String>,
+    /// The protocol version of the command request or response that was not supported.
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+    /// The acceptable major protocol versions for the command executor or invoker that rejected the message
+    #[serde(default)]
+    pub supported_protocol_major_versions: Option<Vec<u16>>,
+    /// The correlation id of the command request relevant to the error being reported, if known
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+/// Error reconstructed from a message in the source chain of an [`AIOProtocolErrorJson`]
+#[derive(Debug)]
+struct SourceMessage {
+    message: String,
+    source: Option<Box<SourceMessage>>,
+}
+
+impl fmt::Display for SourceMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SourceMessage {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+/// Truncates `s` to at most `len` bytes without splitting a character
+fn truncate_to_char_boundary(s: &str, len: usize) -> &str {
+    if s.len() <= len {
+        return s;
+    }
+    let mut end = len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+impl AIOProtocolError {
+    /// Returns the serializable representation of this error.
+    ///
+    /// Values that may contain payload content are only included as allowed by `options`.
+    #[must_use]
+    pub fn to_json_representation(
+        &self,
+        options: &AIOProtocolErrorJsonOptions,
+    ) -> AIOProtocolErrorJson {
+        let mut source_chain = Vec::new();
+        let mut source = self.source();
+        while let Some(error) = source {
+            source_chain.push(error.to_string());
+            source = error.source();
+        }
+        if self.kind == AIOProtocolErrorKind::PayloadInvalid {
+            match options.payload_snippet_len {
+                Some(len) => {
+                    for message in &mut source_chain {
+                        *message = truncate_to_char_boundary(message, len).to_string();
+                    }
+                }
+                None => source_chain.clear(),
+            }
+        }
+
+        AIOProtocolErrorJson {
+            schema_version: AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION,
+            kind: self.kind.clone(),
+            message: self.to_string(),
+            is_shallow: self.is_shallow,
+            is_remote: self.is_remote,
+            source_chain,
+            header_name: self.header_name.clone(),
+            header_value: self.header_value.clone(),
+            timeout_name: self.timeout_name.clone(),
+            timeout_ms: self
+                .timeout_value
+                .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX)),
+            property_name: self.property_name.clone(),
+            property_value: self.property_value.clone(),
+            command_name: self.command_name.clone(),
+            protocol_version: self.protocol_version.clone(),
+            supported_protocol_major_versions: self.supported_protocol_major_versions.clone(),
+            correlation_id: self.correlation_id.map(|id| id.to_string()),
+        }
+    }
+
+    /// Returns the JSON representation of this error. See [`AIOProtocolErrorJson`] for its shape.
+    ///
+    /// Values that may contain payload content are only included as allowed by `options`.
+    ///
+    /// # Panics
+    /// Panics if the representation fails to serialize, which should not be possible since it
+    /// only contains strings, numbers, and enums without data that can't be represented in JSON.
+    #[must_use]
+    pub fn to_json(&self, options: &AIOProtocolErrorJsonOptions) -> String {
+        serde_json::to_string(&self.to_json_representation(options))
+            .expect("AIOProtocolErrorJson is always serializable")
+    }
+
+    /// Reconstructs an [`AIOProtocolError`] from its JSON representation.
+    ///
+    /// The nested error of the returned error is a chain of errors displaying the messages of the
+    /// serialized source chain.
+    ///
+    /// # Errors
+    /// [`serde_json::Error`] if `json` is not a valid representation of an [`AIOProtocolError`], or
+    /// if its schema version is newer than [`AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION`]
+    pub fn from_json(json: &str) -> Result<AIOProtocolError, serde_json::Error> {
+        let representation: AIOProtocolErrorJson = serde_json::from_str(json)?;
+        if representation.schema_version > AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported schema version {}, expected at most {AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION}",
+                representation.schema_version
+            )));
+        }
+        Ok(representation.into())
+    }
+}
+
+impl From<AIOProtocolErrorJson> for AIOProtocolError {
+    fn from(representation: AIOProtocolErrorJson) -> Self {
+        let nested_error = representation
+            .source_chain
+            .into_iter()
+            .rev()
+            .fold(None, |source, message| {
+                Some(Box::new(SourceMessage { message, source }))
+            })
+            .map(|e| e as Box<dyn Error + Send + Sync>);
+
+        AIOProtocolError {
+            message: Some(representation.message),
+            kind: representation.kind,
+            is_shallow: representation.is_shallow,
+            is_remote: representation.is_remote,
+            nested_error,
+            header_name: representation.header_name,
+            header_value: representation.header_value,
+            timeout_name: representation.timeout_name,
+            timeout_value: representation.timeout_ms.map(Duration::from_millis),
+            property_name: representation.property_name,
+            property_value: representation.property_value,
+            command_name: representation.command_name,
+            protocol_version: representation.protocol_version,
+            supported_protocol_major_versions: representation.supported_protocol_major_versions,
+            correlation_id: representation
+                .correlation_id
+                .and_then(|id| Uuid::parse_str(&id).ok()),
+        }
+    }
+}
+
+impl Serialize for AIOProtocolError {
+    /// Serializes the representation returned by [`AIOProtocolError::to_json_representation`]
+    /// with the default options, which omit values that may contain payload content
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json_representation(&AIOProtocolErrorJsonOptions::default())
+            .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+
+    /// Error with a source, used to build nested error chains
+    #[derive(Debug)]
+    struct TestError {
+        message: &'static str,
+        source: Option<Box<TestError>>,
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.message)
+        }
+    }
+
+    impl Error for TestError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.source
+                .as_ref()
+                .map(|e| e.as_ref() as &(dyn Error + 'static))
+        }
+    }
+
+    fn nested_error() -> Box<dyn Error + Send + Sync> {
+        Box::new(TestError {
+            message: "outer",
+            source: Some(Box::new(TestError {
+                message: "inner",
+                source: None,
+            })),
+        })
+    }
+
+    fn payload_error() -> Box<dyn Error + Send + Sync> {
+        Box::new(TestError {
+            message: "invalid value 'secret-payload' at column 7",
+            source: None,
+        })
+    }
+
+    /// Expected representation, with the fields that vary between kinds merged in
+    fn expected(overrides: &serde_json::Value) -> serde_json::Value {
+        let mut expected = json!({
+            "schema_version": 1,
+            "is_shallow": false,
+            "is_remote": false,
+            "source_chain": [],
+            "header_name": null,
+            "header_value": null,
+            "timeout_name": null,
+            "timeout_ms": null,
+            "property_name": null,
+            "property_value": null,
+            "command_name": "test_command",
+            "protocol_version": null,
+            "supported_protocol_major_versions": null,
+            "correlation_id": null,
+        });
+        for (key, value) in overrides.as_object().unwrap() {
+            expected[key] = value.clone();
+        }
+        expected
+    }
+
+    #[test_case(
+        &AIOProtocolError::new_header_missing_error("__ts", true, None, Some("test_command".to_string())),
+        &json!({"kind": "HeaderMissing", "message": "The MQTT header '__ts' is missing", "is_remote": true, "header_name": "__ts"});
+        "header_missing")]
+    #[test_case(
+        &AIOProtocolError::new_header_invalid_error("__ts", "bad", false, None, Some("test_command".to_string())),
+        &json!({"kind": "HeaderInvalid", "message": "The MQTT header '__ts' has an invalid value: 'bad'", "header_name": "__ts", "header_value": "bad"});
+        "header_invalid")]
+    #[test_case(
+        &AIOProtocolError::new_payload_invalid_error(false, false, Some(payload_error()), None, Some("test_command".to_string())),
+        &json!({"kind": "PayloadInvalid", "message": "Serialization or deserialization of the MQTT payload failed"});
+        "payload_invalid")]
+    #[test_case(
+        &AIOProtocolError::new_timeout_error(false, None, "command_timeout", Duration::from_secs(10), None, Some("test_command".to_string())),
+        &json!({"kind": "Timeout", "message": "The timeout 'command_timeout' elapsed after 10000 ms", "timeout_name": "command_timeout", "timeout_ms": 10000});
+        "timeout")]
+    #[test_case(
+        &AIOProtocolError::new_cancellation_error(false, Some(nested_error()), None, Some("test_command".to_string())),
+        &json!({"kind": "Cancellation", "message": "The operation was cancelled", "source_chain": ["outer", "inner"]});
+        "cancellation")]
+    #[test_case(
+        &AIOProtocolError::new_configuration_invalid_error(None, "timeout", Value::Integer(0), None, Some("test_command".to_string())),
+        &json!({"kind": "ConfigurationInvalid", "message": "The property 'timeout' has an invalid value: Integer(0)", "is_shallow": true, "property_name": "timeout", "property_value": {"type": "Integer", "value": 0}});
+        "configuration_invalid")]
+    #[test_case(
+        &AIOProtocolError::new_state_invalid_error("Counter", Some(Value::Boolean(true)), None, Some("test_command".to_string())),
+        &json!({"kind": "StateInvalid", "message": "Invalid state in property 'Counter'", "is_shallow": true, "property_name": "Counter", "property_value": {"type": "Boolean", "value": true}});
+        "state_invalid")]
+    #[test_case(
+        &AIOProtocolError::new_internal_logic_error(false, true, None, "index", Some(Value::Float(1.5)), None, Some("test_command".to_string())),
+        &json!({"kind": "InternalLogicError", "message": "Internal logic error in property 'index'", "is_remote": true, "property_name": "index", "property_value": {"type": "Float", "value": 1.5}});
+        "internal_logic_error")]
+    #[test_case(
+        &AIOProtocolError::new_unknown_error(true, false, None, Some("Unknown status 599".to_string()), Some("test_command".to_string())),
+        &json!({"kind": "UnknownError", "message": "Unknown status 599", "is_remote": true});
+        "unknown_error")]
+    #[test_case(
+        &AIOProtocolError::new_execution_exception_error(Some("field"), Some(Value::String("value".to_string())), Some("Application error".to_string()), Some("test_command".to_string())),
+        &json!({"kind": "ExecutionException", "message": "Application error", "is_remote": true, "property_name": "field", "property_value": {"type": "String", "value": "value"}});
+        "execution_exception")]
+    #[test_case(
+        &AIOProtocolError::new_mqtt_error(None, nested_error(), Some("test_command".to_string())),
+        &json!({"kind": "ClientError", "message": "An MQTT communication error occurred", "source_chain": ["outer", "inner"]});
+        "client_error")]
+    #[test_case(
+        &AIOProtocolError::new_unsupported_version_error(None, "2.0".to_string(), vec![1], Some("test_command".to_string()), false, true),
+        &json!({"kind": "UnsupportedVersion", "message": "Received data with an unsupported protocol version '2.0', but only major protocol versions '[1]' are supported.", "is_remote": true, "protocol_version": "2.0", "supported_protocol_major_versions": [1]});
+        "unsupported_version")]
+    fn json_representation(error: &AIOProtocolError, overrides: &serde_json::Value) {
+        assert_eq!(serde_json::to_value(error).unwrap(), expected(overrides));
+    }
+
+    #[test]
+    fn json_correlation_id() {
+        let correlation_id = Uuid::new_v4();
+        let mut error = AIOProtocolError::new_timeout_error(
+            false,
+            None,
+            "command_timeout",
+            Duration::from_secs(10),
+            None,
+            None,
+        );
+        error.correlation_id = Some(correlation_id);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap()["correlation_id"],
+            json!(correlation_id.to_string())
+        );
+    }
+
+    #[test_case(None, &[]; "omitted by default")]
+    #[test_case(Some(13), &["invalid value"]; "truncated")]
+    #[test_case(Some(15), &["invalid value '"]; "truncated at length")]
+    #[test_case(Some(100), &["invalid value 'secret-payload' at column 7"]; "shorter than length")]
+    fn json_payload_snippets(payload_snippet_len: Option<usize>, source_chain: &[&str]) {
+        let error = AIOProtocolError::new_payload_invalid_error(
+            false,
+            false,
+            Some(payload_error()),
+            None,
+            None,
+        );
+        let representation = error.to_json_representation(&AIOProtocolErrorJsonOptions {
+            payload_snippet_len,
+        });
+        assert_eq!(representation.source_chain, source_chain);
+    }
+
+    #[test]
+    fn json_payload_snippet_char_boundary() {
+        let error = AIOProtocolError::new_payload_invalid_error(
+            false,
+            false,
+            Some(Box::new(TestError {
+                message: "a\u{e9}b",
+                source: None,
+            })),
+            None,
+            None,
+        );
+        let representation = error.to_json_representation(&AIOProtocolErrorJsonOptions {
+            payload_snippet_len: Some(2),
+        });
+        assert_eq!(representation.source_chain, ["a"]);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut error = AIOProtocolError::new_mqtt_error(
+            None,
+            nested_error(),
+            Some("test_command".to_string()),
+        );
+        error.correlation_id = Some(Uuid::new_v4());
+        error.timeout_value = Some(Duration::from_millis(1500));
+
+        let json = error.to_json(&AIOProtocolErrorJsonOptions::default());
+        let reconstructed = AIOProtocolError::from_json(&json).unwrap();
+        assert_eq!(reconstructed.to_string(), error.to_string());
+        assert_eq!(reconstructed.kind, error.kind);
+        assert_eq!(reconstructed.command_name, error.command_name);
+        assert_eq!(reconstructed.correlation_id, error.correlation_id);
+        assert_eq!(reconstructed.timeout_value, error.timeout_value);
+        let source = reconstructed.source().unwrap();
+        assert_eq!(source.to_string(), "outer");
+        assert_eq!(source.source().unwrap().to_string(), "inner");
+        assert!(source.source().unwrap().source().is_none());
+
+        // Serializing the reconstructed error produces the same representation
+        assert_eq!(
+            reconstructed.to_json(&AIOProtocolErrorJsonOptions::default()),
+            json
+        );
+    }
+
+    #[test]
+    fn from_json_unsupported_schema_version() {
+        let mut representation =
+            serde_json::to_value(AIOProtocolError::new_mqtt_error(None, nested_error(), None))
+                .unwrap();
+        representation["schema_version"] = json!(AIO_PROTOCOL_ERROR_JSON_SCHEMA_VERSION + 1);
+        assert!(AIOProtocolError::from_json(&representation.to_string()).is_err());
+    }
+
+    #[test]
+    fn from_json_optional_fields_omitted() {
+        let error = AIOProtocolError::from_json(
+            r#"{"schema_version":1,"kind":"Cancellation","message":"cancelled","is_shallow":false,"is_remote":false}"#,
+        )
+        .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::Cancellation);
+        assert_eq!(error.to_string(), "cancelled");
+        assert!(error.source().is_none());
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Monotonic time source for protocol logic.
+//!
+//! Command expiration, timeouts, cache expiry and response timing must read the current time with
+//! [`now`] and wait with `tokio::time` (`sleep`, `timeout`, ...) rather than using
+//! [`std::time::Instant`]. The clock is tokio's, so it is the real monotonic clock at runtime,
+//! while tests can freeze it with `#[tokio::test(start_paused = true)]` and move it forward
+//! deterministically with `tokio::time::advance` instead of sleeping. For example:
+//!
+//! ```ignore
+//! #[tokio::test(start_paused = true)]
+//! async fn test_entry_expires() {
+//!     let expiration_time = clock::now() + Duration::from_secs(10);
+//!     tokio::time::advance(Duration::from_secs(10)).await;
+//!     assert_eq!(expiration_time, clock::now());
+//! }
+//! ```
+//!
+//! Note that wall-clock time ([`std::time::SystemTime`]), as used for [`HybridLogicalClock`]
+//! timestamps, is not affected by pausing tokio's clock.
+//!
+//! [`HybridLogicalClock`]: crate::common::hybrid_logical_clock::HybridLogicalClock
+
+pub(crate) use tokio::time::Instant;
+
+/// Returns the current [`Instant`] of the clock, which is paused and advanced along with tokio's
+/// clock in tests.
+#[must_use]
+pub(crate) fn now() -> Instant {
+    Instant::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_now_follows_paused_clock() {
+        let start = now();
+        assert_eq!(now(), start);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(now(), start + Duration::from_secs(30));
+    }
+}
@@ -1,10 +1,11 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::time::SystemTime;
+use std::{collections::BTreeMap, time::SystemTime};
 
 use azure_iot_operations_mqtt::aio::cloud_event::{
-    CloudEventFields, DEFAULT_CLOUD_EVENT_SPEC_VERSION,
+    self as aio_cloud_event, CloudEventFields, DATA_REF_EXTENSION,
+    DEFAULT_CLOUD_EVENT_SPEC_VERSION, validate_extension,
 };
 use chrono::{DateTime, SecondsFormat, Utc};
 use uuid::Uuid;
@@ -53,6 +54,10 @@ pub struct CloudEvent {
     /// for any specific event if the source context has internal sub-structure.
     #[builder(default = "CloudEventSubject::PublishTopic")]
     subject: CloudEventSubject,
+    /// Extension attributes, keyed by attribute name. Names must only contain lowercase letters
+    /// and digits and must not collide with the names of core attributes.
+    #[builder(default)]
+    extensions: BTreeMap<String, String>,
     #[builder(private)]
     _default_event_type: String,
 }
@@ -76,6 +81,69 @@ impl CloudEventBuilder {
         }
     }
 
+    /// Add a single extension attribute, replacing any existing value for `name`.
+    pub fn extension<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.extensions
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Derive attributes of an aggregate event from the source events it was produced from.
+    ///
+    /// Only attributes that have not already been set on the builder are derived:
+    /// - `time` is the earliest time of the source events
+    /// - `source` is the source shared by all source events, or otherwise their longest common
+    ///   path prefix (e.g. `aio://sensors` for `aio://sensors/1` and `aio://sensors/2`), if any
+    /// - `data_schema` is the data schema shared by all source events, if any
+    /// - extension attributes with the same value on all source events are carried over, other
+    ///   than `dataref`, which refers to the data of a single event
+    ///
+    /// `id`, `spec_version`, `event_type` and `subject` are never derived.
+    pub fn derive_from_sources<'a, I>(&mut self, sources: I) -> &mut Self
+    where
+        I: IntoIterator<Item = &'a aio_cloud_event::CloudEvent>,
+    {
+        let sources: Vec<&aio_cloud_event::CloudEvent> = sources.into_iter().collect();
+        let Some((first, rest)) = sources.split_first() else {
+            return self;
+        };
+
+        if self.time.is_none()
+            && let Some(earliest) = sources.iter().filter_map(|event| event.time).min()
+        {
+            self.time = Some(Some(earliest));
+        }
+
+        if self.source.is_none() {
+            self.source = combined_source(sources.iter().map(|event| event.source.as_str()));
+        }
+
+        if self.data_schema.is_none()
+            && first.data_schema.is_some()
+            && rest
+                .iter()
+                .all(|event| event.data_schema == first.data_schema)
+        {
+            self.data_schema = Some(first.data_schema.clone());
+        }
+
+        let extensions = self.extensions.get_or_insert_with(BTreeMap::new);
+        for (name, value) in &first.extensions {
+            if name != DATA_REF_EXTENSION
+                && rest
+                    .iter()
+                    .all(|event| event.extension(name) == Some(value))
+            {
+                extensions
+                    .entry(name.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+
+        self
+    }
+
     fn custom_default_event_type(&self) -> String {
         self._default_event_type.clone().expect("This CloudEventBuilder must be initialized with a default event type or one must be set on the builder")
     }
@@ -108,12 +176,37 @@ impl CloudEventBuilder {
             CloudEventFields::Subject.validate(subject, &spec_version)?;
         }
 
+        if let Some(extensions) = &self.extensions {
+            for (name, value) in extensions {
+                validate_extension(name, value)?;
+            }
+        }
+
         // time does not need to be validated because converting it to an rfc3339 compliant string will always succeed
 
         Ok(())
     }
 }
 
+/// Source shared by all `sources`, or otherwise their longest common path prefix, as long as the
+/// prefix still identifies a path rather than only a URI scheme and authority
+fn combined_source<'a>(mut sources: impl Iterator<Item = &'a str>) -> Option<String> {
+    let first = sources.next()?;
+    let mut common: Vec<&str> = first.split('/').collect();
+    for source in sources {
+        let shared = common
+            .iter()
+            .zip(source.split('/'))
+            .take_while(|(a, b)| *a == b)
+            .count();
+        common.truncate(shared);
+    }
+    match common.last() {
+        Some(last) if !last.is_empty() && !last.ends_with(':') => Some(common.join("/")),
+        _ => None,
+    }
+}
+
 impl CloudEvent {
     /// Get [`CloudEvent`] as user properties for an MQTT publish
     #[must_use]
@@ -145,6 +238,136 @@ impl CloudEvent {
         if let Some(data_schema) = self.data_schema {
             headers.push((CloudEventFields::DataSchema.to_string(), data_schema));
         }
+        headers.extend(self.extensions);
         headers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_mqtt::aio::cloud_event::PARTITION_KEY_EXTENSION;
+    use test_case::test_case;
+
+    use super::*;
+
+    fn source_event(
+        source: &str,
+        time: Option<DateTime<Utc>>,
+        extensions: &[(&str, &str)],
+    ) -> aio_cloud_event::CloudEvent {
+        let mut builder = aio_cloud_event::CloudEventBuilder::default();
+        builder
+            .source(source)
+            .event_type("test.event")
+            .time(time)
+            .data_schema(Some("aio://schemas/sensor".to_string()));
+        for (name, value) in extensions {
+            builder.extension(*name, *value);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test_case(&["aio://sensors/1", "aio://sensors/1"], Some("aio://sensors/1"); "shared")]
+    #[test_case(&["aio://sensors/1", "aio://sensors/2"], Some("aio://sensors"); "common_path")]
+    #[test_case(&["aio://line/1/sensor/1", "aio://line/1/sensor/2", "aio://line/2"], Some("aio://line"); "three_sources")]
+    #[test_case(&["/sensors/1", "/sensors/2"], Some("/sensors"); "relative")]
+    #[test_case(&["aio://sensor1", "aio://sensor2"], None; "authority_only")]
+    #[test_case(&["sensor-1", "sensor-2"], None; "no_common_prefix")]
+    #[test_case(&["aio://sensors/1", "https://sensors/1"], None; "different_scheme")]
+    #[test_case(&[], None; "no_sources")]
+    fn test_combined_source(sources: &[&str], expected: Option<&str>) {
+        assert_eq!(
+            combined_source(sources.iter().copied()),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn test_derive_from_sources() {
+        let earliest = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let sources = [
+            source_event(
+                "aio://sensors/1",
+                Some(earliest + chrono::Duration::seconds(5)),
+                &[(PARTITION_KEY_EXTENSION, "line-1"), ("traceparent", "a")],
+            ),
+            source_event(
+                "aio://sensors/2",
+                Some(earliest),
+                &[(PARTITION_KEY_EXTENSION, "line-1"), ("traceparent", "b")],
+            ),
+            source_event(
+                "aio://sensors/3",
+                None,
+                &[(PARTITION_KEY_EXTENSION, "line-1"), ("traceparent", "c")],
+            ),
+        ];
+
+        let cloud_event = CloudEventBuilder::new("test.aggregate".to_string())
+            .derive_from_sources(&sources)
+            .build()
+            .unwrap();
+
+        assert_eq!(cloud_event.source, "aio://sensors");
+        assert_eq!(cloud_event.time, Some(earliest));
+        assert_eq!(
+            cloud_event.data_schema,
+            Some("aio://schemas/sensor".to_string())
+        );
+        assert_eq!(cloud_event.event_type, "test.aggregate");
+        // Only extensions shared by all sources are carried over
+        assert_eq!(
+            cloud_event.extensions,
+            BTreeMap::from([(PARTITION_KEY_EXTENSION.to_string(), "line-1".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_derive_from_sources_does_not_override() {
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let sources = [
+            source_event(
+                "aio://sensors/1",
+                Some(time),
+                &[(PARTITION_KEY_EXTENSION, "line-1")],
+            ),
+            source_event(
+                "aio://sensors/1",
+                Some(time),
+                &[(PARTITION_KEY_EXTENSION, "line-1")],
+            ),
+        ];
+
+        let cloud_event = CloudEventBuilder::new("test.aggregate".to_string())
+            .source("aio://aggregator")
+            .time(None)
+            .extension(PARTITION_KEY_EXTENSION, "line-2")
+            .derive_from_sources(&sources)
+            .build()
+            .unwrap();
+
+        assert_eq!(cloud_event.source, "aio://aggregator");
+        assert_eq!(cloud_event.time, None);
+        assert_eq!(
+            cloud_event.extensions.get(PARTITION_KEY_EXTENSION),
+            Some(&"line-2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_derive_from_sources_without_common_source() {
+        let sources = [
+            source_event("aio://sensor1", None, &[]),
+            source_event("aio://sensor2", None, &[]),
+        ];
+
+        // The source must be set explicitly when it can't be derived
+        let result = CloudEventBuilder::new("test.aggregate".to_string())
+            .derive_from_sources(&sources)
+            .build();
+        assert!(matches!(
+            result,
+            Err(CloudEventBuilderError::UninitializedField("source"))
+        ));
+    }
+}
@@ -13,6 +13,9 @@ use uuid::Uuid;
 /// Recommended default value for max clock drift if not specified.
 pub const DEFAULT_MAX_CLOCK_DRIFT: Duration = Duration::from_secs(60);
 
+/// Maximum length of a custom [`node_id`](HybridLogicalClock::node_id).
+const MAX_NODE_ID_LENGTH: usize = 256;
+
 /// Hybrid Logical Clock (HLC) generating unique timestamps
 #[derive(Clone, Debug, PartialEq)]
 pub struct HybridLogicalClock {
@@ -43,6 +46,17 @@ impl HybridLogicalClock {
         }
     }
 
+    /// Creates a new [`HybridLogicalClock`] with the current timestamp, a counter of 0,
+    /// and the provided node identifier
+    #[must_use]
+    pub(crate) fn new_with_node_id(node_id: String) -> Self {
+        Self {
+            timestamp: now_ms_precision(),
+            counter: 0,
+            node_id,
+        }
+    }
+
     /// Updates the [`HybridLogicalClock`] based on another [`HybridLogicalClock`].
     /// Self will be set to the latest timestamp between itself, other, and the current time, and
     /// its counter will also be updated accordingly.
@@ -214,6 +228,29 @@ impl FromStr for HybridLogicalClock {
     }
 }
 
+/// Validates that a custom node identifier is legal: between 1 and 256 characters, consisting only
+/// of ASCII letters, digits, `-`, `_` and `.`, so that it can't be confused with the separators of
+/// the string representation of a [`HybridLogicalClock`].
+///
+/// # Errors
+/// Returns a `String` describing the error if the node identifier is not legal
+pub(crate) fn validate_node_id(node_id: &str) -> Result<(), String> {
+    if node_id.is_empty() || node_id.len() > MAX_NODE_ID_LENGTH {
+        return Err(format!(
+            "HLC node id must be between 1 and {MAX_NODE_ID_LENGTH} characters long"
+        ));
+    }
+    if let Some(c) = node_id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(format!(
+            "HLC node id '{node_id}' contains the invalid character {c:?}"
+        ));
+    }
+    Ok(())
+}
+
 /// All HLCs are rounded to the nearest millisecond to avoid issues with
 /// string comparison, so now should also be rounded to the nearest millisecond.
 fn now_ms_precision() -> SystemTime {
@@ -656,4 +693,21 @@ mod tests {
         let parsed_hlc = hlc_str.parse::<HybridLogicalClock>().unwrap();
         assert_eq!(parsed_hlc, hlc);
     }
+
+    #[test_case("node-1"; "simple")]
+    #[test_case("Gateway_01.site-a"; "mixed")]
+    #[test_case(&Uuid::new_v4().to_string(); "uuid")]
+    #[test_case(&"a".repeat(256); "max length")]
+    fn test_validate_node_id_valid(node_id: &str) {
+        assert!(validate_node_id(node_id).is_ok());
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case(&"a".repeat(257); "too long")]
+    #[test_case("node:1"; "separator")]
+    #[test_case("node 1"; "whitespace")]
+    #[test_case("nöde"; "non ascii")]
+    fn test_validate_node_id_invalid(node_id: &str) {
+        assert!(validate_node_id(node_id).is_err());
+    }
 }
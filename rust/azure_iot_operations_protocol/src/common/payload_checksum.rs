@@ -0,0 +1,154 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! End-to-end integrity check of message payloads.
+//!
+//! A sender configured to do so attaches a CRC-32C checksum of the serialized payload, exactly as
+//! published, to the message as the `__ck` user property. Receivers verify the checksum before the
+//! payload is processed in any other way. Messages without the user property are not checked.
+
+use bytes::Bytes;
+use thiserror::Error;
+
+/// Error indicating that the payload of a received message does not match the checksum attached
+/// to it by the sender.
+#[derive(Debug, Error)]
+#[error("Payload integrity check failed: expected checksum '{expected}', computed '{computed}'")]
+pub struct IntegrityCheckFailed {
+    /// Checksum attached to the message by the sender
+    pub expected: String,
+    /// Checksum computed over the received payload
+    pub computed: String,
+    /// Raw payload of the message, as received
+    pub payload: Bytes,
+}
+
+/// Reflected CRC-32C (Castagnoli) polynomial
+const CRC32C_POLYNOMIAL: u32 = 0x82F6_3B78;
+
+/// Lookup table for computing the CRC-32C one byte at a time
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        #[allow(clippy::cast_possible_truncation)]
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32C_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32C checksum of `data`
+fn crc32c(data: &[u8]) -> u32 {
+    !crc32c_update(!0u32, data)
+}
+
+/// Feeds `data` into a CRC-32C computation in progress
+fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, byte| {
+        CRC32C_TABLE[usize::from(crc.to_le_bytes()[0] ^ byte)] ^ (crc >> 8)
+    })
+}
+
+/// Computes the checksum of a serialized payload, formatted as the value of the `__ck` user
+/// property: 8 lowercase hexadecimal digits
+pub(crate) fn checksum(payload: &[u8]) -> String {
+    format!("{:08x}", crc32c(payload))
+}
+
+/// Computes the same checksum as [`checksum`] over a payload that is fed in pieces
+pub(crate) struct ChecksumHasher {
+    crc: u32,
+}
+
+impl ChecksumHasher {
+    pub(crate) fn new() -> Self {
+        Self { crc: !0u32 }
+    }
+
+    /// Feeds the next piece of the payload
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.crc = crc32c_update(self.crc, data);
+    }
+
+    /// Returns the checksum of all pieces fed so far
+    pub(crate) fn finish(&self) -> String {
+        format!("{:08x}", !self.crc)
+    }
+}
+
+/// Verifies a received payload against the checksum attached by the sender, if any.
+///
+/// # Errors
+/// Returns [`IntegrityCheckFailed`] if `expected` is present and does not match the checksum of
+/// `payload`
+pub(crate) fn verify(payload: &Bytes, expected: Option<&str>) -> Result<(), IntegrityCheckFailed> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let computed = checksum(payload);
+    if computed == expected {
+        Ok(())
+    } else {
+        Err(IntegrityCheckFailed {
+            expected: expected.to_string(),
+            computed,
+            payload: payload.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(b"", "00000000"; "empty")]
+    #[test_case(b"123456789", "e3069283"; "check value")]
+    #[test_case(&[0u8; 32], "8a9136aa"; "zeros")]
+    fn test_checksum(payload: &[u8], expected: &str) {
+        assert_eq!(checksum(payload), expected);
+    }
+
+    #[test]
+    fn test_checksum_hasher_matches_checksum() {
+        let mut hasher = ChecksumHasher::new();
+        hasher.update(b"1234");
+        hasher.update(b"");
+        hasher.update(b"56789");
+        assert_eq!(hasher.finish(), checksum(b"123456789"));
+        assert_eq!(ChecksumHasher::new().finish(), checksum(b""));
+    }
+
+    #[test]
+    fn test_verify_matching_checksum() {
+        let payload = Bytes::from_static(b"123456789");
+        assert!(verify(&payload, Some("e3069283")).is_ok());
+    }
+
+    #[test]
+    fn test_verify_no_checksum() {
+        let payload = Bytes::from_static(b"123456789");
+        assert!(verify(&payload, None).is_ok());
+    }
+
+    #[test]
+    fn test_verify_mismatched_checksum() {
+        let payload = Bytes::from_static(b"123456788");
+        let error = verify(&payload, Some("e3069283")).unwrap_err();
+        assert_eq!(error.expected, "e3069283");
+        assert_eq!(error.computed, checksum(b"123456788"));
+        assert_eq!(error.payload, payload);
+    }
+}
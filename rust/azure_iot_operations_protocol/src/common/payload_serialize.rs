@@ -1,7 +1,17 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Content type of payloads serialized as JSON, e.g. by [`Json`]
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+/// Content type of payloads serialized as CBOR, e.g. by [`Cbor`]
+pub const CBOR_CONTENT_TYPE: &str = "application/cbor";
 
 /// Format indicator for serialization and deserialization.
 #[repr(u8)]
@@ -28,6 +38,33 @@ impl TryFrom<Option<u8>> for FormatIndicator {
     }
 }
 
+impl FormatIndicator {
+    /// Returns the format indicator for payloads of the given content type: UTF-8 encoded
+    /// character data for JSON (see [`is_json_content_type`]) and `text/*` content types, and
+    /// unspecified bytes for any other content type, including CBOR.
+    ///
+    /// # Examples
+    /// ```
+    /// # use azure_iot_operations_protocol::common::payload_serialize::FormatIndicator;
+    /// assert_eq!(
+    ///     FormatIndicator::for_content_type("application/json"),
+    ///     FormatIndicator::Utf8EncodedCharacterData
+    /// );
+    /// assert_eq!(
+    ///     FormatIndicator::for_content_type("application/cbor"),
+    ///     FormatIndicator::UnspecifiedBytes
+    /// );
+    /// ```
+    #[must_use]
+    pub fn for_content_type(content_type: &str) -> Self {
+        if is_json_content_type(content_type) || content_type.starts_with("text/") {
+            FormatIndicator::Utf8EncodedCharacterData
+        } else {
+            FormatIndicator::UnspecifiedBytes
+        }
+    }
+}
+
 impl From<FormatIndicator> for azure_iot_operations_mqtt::control_packet::PayloadFormatIndicator {
     fn from(value: FormatIndicator) -> Self {
         match value {
@@ -196,6 +233,188 @@ impl PayloadSerialize for Vec<u8> {
     }
 }
 
+/// Returns true if the content type is `media_type`, optionally with a suffix or parameters.
+fn is_media_type(content_type: &str, media_type: &str) -> bool {
+    content_type.starts_with(media_type)
+        && matches!(
+            content_type.chars().nth(media_type.len()),
+            None | Some('+' | ';')
+        )
+}
+
+/// Returns true if the content type is `application/json`, optionally with a suffix or parameters.
+#[must_use]
+pub fn is_json_content_type(content_type: &str) -> bool {
+    is_media_type(content_type, JSON_CONTENT_TYPE)
+}
+
+/// Returns true if the content type is `application/cbor`, optionally with parameters.
+#[must_use]
+pub fn is_cbor_content_type(content_type: &str) -> bool {
+    is_media_type(content_type, CBOR_CONTENT_TYPE)
+}
+
+/// Returns a description of the mismatch if the format indicator of a serialized payload
+/// contradicts its content type, i.e. binary CBOR data indicated as UTF-8 encoded character data,
+/// which MQTT servers and receivers may reject as invalid UTF-8.
+pub(crate) fn format_indicator_mismatch(serialized_payload: &SerializedPayload) -> Option<String> {
+    (serialized_payload.format_indicator == FormatIndicator::Utf8EncodedCharacterData
+        && is_cbor_content_type(&serialized_payload.content_type))
+    .then(|| {
+        format!(
+            "Content type '{}' is binary, so the format indicator must be {:?}",
+            serialized_payload.content_type,
+            FormatIndicator::UnspecifiedBytes
+        )
+    })
+}
+
+/// Provided convenience wrapper implementing [`PayloadSerialize`] for any type implementing
+/// [`Serialize`] and [`DeserializeOwned`], serialized as JSON with the `application/json` content
+/// type.
+///
+/// Payloads without a content type are deserialized as JSON, and payloads with any other content
+/// type are rejected.
+///
+/// # Examples
+/// ```
+/// # use azure_iot_operations_protocol::common::payload_serialize::{Json, PayloadSerialize, FormatIndicator};
+/// #[derive(Clone, serde::Serialize, serde::Deserialize)]
+/// struct SensorData {
+///     temperature: f64,
+/// }
+///
+/// let serialized = Json(SensorData { temperature: 21.5 }).serialize().unwrap();
+/// assert_eq!(serialized.content_type, "application/json");
+/// assert_eq!(serialized.payload, br#"{"temperature":21.5}"#);
+///
+/// let Json(sensor_data) = Json::<SensorData>::deserialize(
+///     &serialized.payload,
+///     Some(&serialized.content_type),
+///     &serialized.format_indicator,
+/// ).unwrap();
+/// assert_eq!(sensor_data.temperature, 21.5);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Json<T>(pub T);
+
+impl<T> PayloadSerialize for Json<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    type Error = serde_json::Error;
+
+    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
+        Ok(SerializedPayload {
+            payload: serde_json::to_vec(&self.0)?,
+            content_type: JSON_CONTENT_TYPE.to_string(),
+            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<Self::Error>> {
+        if let Some(content_type) = content_type
+            && !is_json_content_type(content_type)
+        {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "Invalid content type: '{content_type}'. Must be '{JSON_CONTENT_TYPE}'"
+            )));
+        }
+        Ok(Json(serde_json::from_slice(payload)?))
+    }
+}
+
+/// Provided convenience wrapper implementing [`PayloadSerialize`] for any type implementing
+/// [`Serialize`] and [`DeserializeOwned`], serialized as CBOR with the `application/cbor` content
+/// type.
+///
+/// Payloads without a content type are deserialized as CBOR, and payloads with any other content
+/// type are rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cbor<T>(pub T);
+
+/// Error serializing or deserializing a [`Cbor`] payload
+#[derive(thiserror::Error, Debug)]
+pub enum CborError {
+    /// The value could not be serialized as CBOR
+    #[error(transparent)]
+    Serialize(#[from] ciborium::ser::Error<std::io::Error>),
+    /// The payload could not be deserialized from CBOR
+    #[error(transparent)]
+    Deserialize(#[from] ciborium::de::Error<std::io::Error>),
+}
+
+impl<T> PayloadSerialize for Cbor<T>
+where
+    T: Serialize + DeserializeOwned + Clone,
+{
+    type Error = CborError;
+
+    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
+        let mut payload = Vec::new();
+        ciborium::into_writer(&self.0, &mut payload)?;
+        Ok(SerializedPayload {
+            payload,
+            content_type: CBOR_CONTENT_TYPE.to_string(),
+            format_indicator: FormatIndicator::UnspecifiedBytes,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<Self::Error>> {
+        if let Some(content_type) = content_type
+            && !is_cbor_content_type(content_type)
+        {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "Invalid content type: '{content_type}'. Must be '{CBOR_CONTENT_TYPE}'"
+            )));
+        }
+        Ok(Cbor(
+            ciborium::from_reader(payload).map_err(CborError::from)?,
+        ))
+    }
+}
+
+macro_rules! impl_wrapper {
+    ($wrapper:ident) => {
+        impl<T> $wrapper<T> {
+            /// Returns the wrapped value
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T> From<T> for $wrapper<T> {
+            fn from(value: T) -> Self {
+                $wrapper(value)
+            }
+        }
+
+        impl<T> Deref for $wrapper<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $wrapper<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+    };
+}
+impl_wrapper!(Json);
+impl_wrapper!(Cbor);
+
 #[cfg(test)]
 use mockall::mock;
 #[cfg(test)]
@@ -224,7 +443,10 @@ pub static DESERIALIZE_MTX: Mutex<()> = Mutex::new(());
 mod tests {
     use test_case::test_case;
 
-    use crate::common::payload_serialize::FormatIndicator;
+    use crate::common::payload_serialize::{
+        CBOR_CONTENT_TYPE, Cbor, CborError, DeserializationError, FormatIndicator,
+        JSON_CONTENT_TYPE, Json, PayloadSerialize, SerializedPayload, format_indicator_mismatch,
+    };
 
     #[test_case(FormatIndicator::UnspecifiedBytes; "UnspecifiedBytes")]
     #[test_case(FormatIndicator::Utf8EncodedCharacterData; "Utf8EncodedCharacterData")]
@@ -247,6 +469,122 @@ mod tests {
         assert!(&FormatIndicator::try_from(value).is_err());
     }
 
+    #[test_case("application/json", FormatIndicator::Utf8EncodedCharacterData; "json")]
+    #[test_case("application/json; charset=utf-8", FormatIndicator::Utf8EncodedCharacterData; "json_with_parameters")]
+    #[test_case("text/plain; charset=utf-8", FormatIndicator::Utf8EncodedCharacterData; "text")]
+    #[test_case("application/cbor", FormatIndicator::UnspecifiedBytes; "cbor")]
+    #[test_case("application/octet-stream", FormatIndicator::UnspecifiedBytes; "octet_stream")]
+    fn test_format_indicator_for_content_type(content_type: &str, expected: FormatIndicator) {
+        assert_eq!(FormatIndicator::for_content_type(content_type), expected);
+    }
+
+    #[test_case("application/cbor", FormatIndicator::Utf8EncodedCharacterData, true; "cbor_utf8")]
+    #[test_case("application/cbor", FormatIndicator::UnspecifiedBytes, false; "cbor_bytes")]
+    #[test_case("application/json", FormatIndicator::Utf8EncodedCharacterData, false; "json_utf8")]
+    #[test_case("application/json", FormatIndicator::UnspecifiedBytes, false; "json_bytes")]
+    fn test_format_indicator_mismatch(
+        content_type: &str,
+        format_indicator: FormatIndicator,
+        expect_mismatch: bool,
+    ) {
+        let serialized_payload = SerializedPayload {
+            payload: Vec::new(),
+            content_type: content_type.to_string(),
+            format_indicator,
+        };
+        assert_eq!(
+            format_indicator_mismatch(&serialized_payload).is_some(),
+            expect_mismatch
+        );
+    }
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct SensorData {
+        name: String,
+        temperature: f64,
+    }
+
+    fn sensor_data() -> SensorData {
+        SensorData {
+            name: "sensor".to_string(),
+            temperature: 21.5,
+        }
+    }
+
+    #[test_case(None; "no_content_type")]
+    #[test_case(Some("application/json"); "json")]
+    #[test_case(Some("application/json; charset=utf-8"); "json_with_parameters")]
+    fn test_json_round_trip(content_type: Option<&str>) {
+        let serialized = Json(sensor_data()).serialize().unwrap();
+        assert_eq!(serialized.content_type, JSON_CONTENT_TYPE);
+        assert_eq!(
+            serialized.format_indicator,
+            FormatIndicator::Utf8EncodedCharacterData
+        );
+        let deserialized = Json::<SensorData>::deserialize(
+            &serialized.payload,
+            content_type.map(ToString::to_string).as_ref(),
+            &serialized.format_indicator,
+        )
+        .unwrap();
+        assert_eq!(deserialized.into_inner(), sensor_data());
+    }
+
+    #[test_case(None; "no_content_type")]
+    #[test_case(Some("application/cbor"); "cbor")]
+    fn test_cbor_round_trip(content_type: Option<&str>) {
+        let serialized = Cbor(sensor_data()).serialize().unwrap();
+        assert_eq!(serialized.content_type, CBOR_CONTENT_TYPE);
+        assert_eq!(
+            serialized.format_indicator,
+            FormatIndicator::UnspecifiedBytes
+        );
+        let deserialized = Cbor::<SensorData>::deserialize(
+            &serialized.payload,
+            content_type.map(ToString::to_string).as_ref(),
+            &serialized.format_indicator,
+        )
+        .unwrap();
+        assert_eq!(deserialized.name, "sensor");
+        assert_eq!(*deserialized, sensor_data());
+    }
+
+    #[test]
+    fn test_unsupported_content_type() {
+        let json = Json(sensor_data()).serialize().unwrap();
+        assert!(matches!(
+            Cbor::<SensorData>::deserialize(
+                &json.payload,
+                Some(&json.content_type),
+                &json.format_indicator
+            ),
+            Err(DeserializationError::UnsupportedContentType(_))
+        ));
+        let cbor = Cbor(sensor_data()).serialize().unwrap();
+        assert!(matches!(
+            Json::<SensorData>::deserialize(
+                &cbor.payload,
+                Some(&cbor.content_type),
+                &cbor.format_indicator
+            ),
+            Err(DeserializationError::UnsupportedContentType(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_payload() {
+        assert!(matches!(
+            Json::<SensorData>::deserialize(b"{", None, &FormatIndicator::UnspecifiedBytes),
+            Err(DeserializationError::InvalidPayload(_))
+        ));
+        assert!(matches!(
+            Cbor::<SensorData>::deserialize(&[0xff], None, &FormatIndicator::UnspecifiedBytes),
+            Err(DeserializationError::InvalidPayload(
+                CborError::Deserialize(_)
+            ))
+        ));
+    }
+
     #[test_case(FormatIndicator::UnspecifiedBytes; "UnspecifiedBytes")]
     #[test_case(FormatIndicator::Utf8EncodedCharacterData; "Utf8EncodedCharacterData")]
     fn test_to_from_mqtt_format_indicator(prop: FormatIndicator) {
@@ -86,6 +86,20 @@ pub(crate) enum ProtocolReservedUserProperty {
     /// This property is only used when a command executor rejects a command invocation because the
     /// requested protocol version either wasn't supported or was malformed.
     RequestProtocolVersion,
+    /// User property indicating the priority of a telemetry message, from 0 (lowest) to 9 (highest).
+    Priority,
+    /// User property indicating that the invoker of a command request does not expect a response.
+    /// The value is ignored; presence of the key is what matters.
+    NoResponse,
+    /// User property indicating the sequence number of a telemetry message among the messages
+    /// sent by the same sender instance on the same topic.
+    SequenceNumber,
+    /// User property identifying the sender instance that numbered a telemetry message. A new
+    /// instance restarts numbering from zero.
+    SenderInstanceId,
+    /// User property indicating the CRC-32C checksum of the payload of a request, response or
+    /// telemetry message, as 8 lowercase hexadecimal digits.
+    PayloadChecksum,
 }
 
 impl Display for ProtocolReservedUserProperty {
@@ -102,6 +116,11 @@ impl Display for ProtocolReservedUserProperty {
             ProtocolReservedUserProperty::ProtocolVersion => write!(f, "__protVer"),
             ProtocolReservedUserProperty::SupportedMajorVersions => write!(f, "__supProtMajVer"),
             ProtocolReservedUserProperty::RequestProtocolVersion => write!(f, "__requestProtVer"),
+            ProtocolReservedUserProperty::Priority => write!(f, "__pri"),
+            ProtocolReservedUserProperty::NoResponse => write!(f, "__noResp"),
+            ProtocolReservedUserProperty::SequenceNumber => write!(f, "__seq"),
+            ProtocolReservedUserProperty::SenderInstanceId => write!(f, "__seqInst"),
+            ProtocolReservedUserProperty::PayloadChecksum => write!(f, "__ck"),
         }
     }
 }
@@ -121,6 +140,11 @@ impl FromStr for ProtocolReservedUserProperty {
             "__protVer" => Ok(ProtocolReservedUserProperty::ProtocolVersion),
             "__supProtMajVer" => Ok(ProtocolReservedUserProperty::SupportedMajorVersions),
             "__requestProtVer" => Ok(ProtocolReservedUserProperty::RequestProtocolVersion),
+            "__pri" => Ok(ProtocolReservedUserProperty::Priority),
+            "__noResp" => Ok(ProtocolReservedUserProperty::NoResponse),
+            "__seq" => Ok(ProtocolReservedUserProperty::SequenceNumber),
+            "__seqInst" => Ok(ProtocolReservedUserProperty::SenderInstanceId),
+            "__ck" => Ok(ProtocolReservedUserProperty::PayloadChecksum),
             _ => Err(()),
         }
     }
@@ -185,6 +209,11 @@ mod tests {
     #[test_case(ProtocolReservedUserProperty::ProtocolVersion; "protocol_version")]
     #[test_case(ProtocolReservedUserProperty::SupportedMajorVersions; "supported_major_versions")]
     #[test_case(ProtocolReservedUserProperty::RequestProtocolVersion; "request_protocol_version")]
+    #[test_case(ProtocolReservedUserProperty::Priority; "priority")]
+    #[test_case(ProtocolReservedUserProperty::NoResponse; "no_response")]
+    #[test_case(ProtocolReservedUserProperty::SequenceNumber; "sequence_number")]
+    #[test_case(ProtocolReservedUserProperty::SenderInstanceId; "sender_instance_id")]
+    #[test_case(ProtocolReservedUserProperty::PayloadChecksum; "payload_checksum")]
     fn test_to_from_string(prop: ProtocolReservedUserProperty) {
         assert_eq!(
             prop,
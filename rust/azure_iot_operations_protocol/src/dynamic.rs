@@ -0,0 +1,294 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Commands and telemetry defined at runtime, operating on JSON payloads.
+//!
+//! These envoys allow prototyping without running the protocol compiler. A [`DynamicCommand`] or
+//! [`DynamicTelemetry`] is defined from a name and topic pattern (e.g. as loaded from a
+//! configuration file), and creates envoys that wrap the typed [`rpc_command`](crate::rpc_command)
+//! and [`telemetry`](crate::telemetry) envoys with [`serde_json::Value`] payloads. Wire behavior
+//! is identical to that of generated code using the `application/json` content type.
+//!
+//! A [`JsonSchema`] can optionally be attached to each direction of a definition to validate
+//! payloads at runtime.
+
+use std::sync::Arc;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::common::{
+    aio_protocol_error::{self, AIOProtocolError},
+    payload_serialize::{
+        DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+        is_json_content_type,
+    },
+};
+
+/// This module contains the dynamic command definition and envoys.
+mod command;
+
+/// This module contains the dynamic telemetry definition and envoys.
+mod telemetry;
+
+pub use command::{
+    DynamicCommand, DynamicCommandExecutor, DynamicCommandInvoker, DynamicExecutorRequest,
+    DynamicExecutorResponse, DynamicExecutorResponseBuilder, DynamicInvokerRequest,
+    DynamicInvokerRequestBuilder, SCHEMA_VALIDATION_ERROR_CODE,
+};
+pub use telemetry::{
+    DynamicTelemetry, DynamicTelemetryMessage, DynamicTelemetryMessageBuilder,
+    DynamicTelemetryReceiver, DynamicTelemetrySender,
+};
+
+/// Content type of all dynamic payloads.
+pub use crate::common::payload_serialize::JSON_CONTENT_TYPE;
+
+/// JSON payloads are serialized with the `application/json` content type.
+///
+/// [`Value::Null`] is serialized as an empty payload and an empty payload is deserialized as
+/// [`Value::Null`], matching generated code for commands without a request or response payload.
+impl PayloadSerialize for Value {
+    type Error = serde_json::Error;
+
+    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
+        let payload = if self.is_null() {
+            Vec::new()
+        } else {
+            serde_json::to_vec(&self)?
+        };
+        Ok(SerializedPayload {
+            payload,
+            content_type: JSON_CONTENT_TYPE.to_string(),
+            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<Self::Error>> {
+        if let Some(content_type) = content_type
+            && !is_json_content_type(content_type)
+        {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "Invalid content type: '{content_type}'. Must be '{JSON_CONTENT_TYPE}'"
+            )));
+        }
+        if payload.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(payload).map_err(DeserializationError::InvalidPayload)
+    }
+}
+
+/// Error indicating that a JSON payload does not conform to a [`JsonSchema`].
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct SchemaValidationError(String);
+
+/// Compiled JSON schema used to validate dynamic payloads at runtime.
+#[derive(Clone)]
+pub struct JsonSchema {
+    schema: Value,
+    validator: Arc<jsonschema::Validator>,
+}
+
+impl JsonSchema {
+    /// Compile a new [`JsonSchema`] from its JSON representation.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the schema is not a valid JSON schema.
+    pub fn new(schema: Value) -> Result<Self, AIOProtocolError> {
+        let validator = jsonschema::validator_for(&schema).map_err(|e| {
+            AIOProtocolError::new_configuration_invalid_error(
+                Some(Box::new(SchemaValidationError(e.to_string()))),
+                "schema",
+                aio_protocol_error::Value::String(schema.to_string()),
+                Some(format!("Invalid JSON schema: {e}")),
+                None,
+            )
+        })?;
+        Ok(Self {
+            schema,
+            validator: Arc::new(validator),
+        })
+    }
+
+    /// JSON representation of the schema.
+    #[must_use]
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Validate a JSON value against the schema.
+    ///
+    /// # Errors
+    /// [`SchemaValidationError`] describing every violation of the schema by the value.
+    pub fn validate(&self, value: &Value) -> Result<(), SchemaValidationError> {
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(value)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{path}: {e}")
+                }
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaValidationError(errors.join("; ")))
+        }
+    }
+}
+
+impl std::fmt::Debug for JsonSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonSchema")
+            .field("schema", &self.schema)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Validates a payload against an optional schema, returning a
+/// [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+/// error if it does not conform.
+fn validate_payload(
+    schema: Option<&JsonSchema>,
+    payload: &Value,
+    is_shallow: bool,
+    message: &str,
+    command_name: Option<&str>,
+) -> Result<(), AIOProtocolError> {
+    if let Some(schema) = schema {
+        schema.validate(payload).map_err(|e| {
+            AIOProtocolError::new_payload_invalid_error(
+                is_shallow,
+                false,
+                Some(Box::new(e)),
+                Some(message.to_string()),
+                command_name.map(ToString::to_string),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::common::aio_protocol_error::AIOProtocolErrorKind;
+
+    #[test]
+    fn serialize_json_value() {
+        let serialized = json!({"CounterValue": 1}).serialize().unwrap();
+        assert_eq!(serialized.payload, br#"{"CounterValue":1}"#);
+        assert_eq!(serialized.content_type, JSON_CONTENT_TYPE);
+        assert_eq!(
+            serialized.format_indicator,
+            FormatIndicator::Utf8EncodedCharacterData
+        );
+    }
+
+    #[test]
+    fn serialize_null_is_empty() {
+        let serialized = Value::Null.serialize().unwrap();
+        assert!(serialized.payload.is_empty());
+        assert_eq!(
+            Value::deserialize(
+                &[],
+                Some(&JSON_CONTENT_TYPE.to_string()),
+                &FormatIndicator::Utf8EncodedCharacterData
+            )
+            .unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test_case(None; "no content type")]
+    #[test_case(Some("application/json"); "json")]
+    #[test_case(Some("application/json; charset=utf-8"); "json with parameters")]
+    #[test_case(Some("application/json+custom"); "json with suffix")]
+    fn deserialize_json_value(content_type: Option<&str>) {
+        let content_type = content_type.map(ToString::to_string);
+        let value = Value::deserialize(
+            br#"{"CounterValue":1}"#,
+            content_type.as_ref(),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        )
+        .unwrap();
+        assert_eq!(value, json!({"CounterValue": 1}));
+    }
+
+    #[test_case("application/octet-stream"; "octet stream")]
+    #[test_case("application/jsonx"; "prefix match")]
+    fn deserialize_unsupported_content_type(content_type: &str) {
+        let result = Value::deserialize(
+            b"{}",
+            Some(&content_type.to_string()),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        );
+        assert!(matches!(
+            result,
+            Err(DeserializationError::UnsupportedContentType(_))
+        ));
+    }
+
+    #[test]
+    fn deserialize_invalid_json() {
+        let result = Value::deserialize(b"{", None, &FormatIndicator::UnspecifiedBytes);
+        assert!(matches!(
+            result,
+            Err(DeserializationError::InvalidPayload(_))
+        ));
+    }
+
+    #[test]
+    fn json_schema_validation() {
+        let schema = JsonSchema::new(json!({
+            "type": "object",
+            "properties": { "IncrementValue": { "type": "integer" } },
+            "required": ["IncrementValue"]
+        }))
+        .unwrap();
+        assert!(schema.validate(&json!({"IncrementValue": 1})).is_ok());
+
+        let error = schema
+            .validate(&json!({"IncrementValue": "one"}))
+            .unwrap_err();
+        assert!(error.to_string().contains("/IncrementValue"));
+        assert!(schema.validate(&json!({})).is_err());
+    }
+
+    #[test]
+    fn json_schema_invalid() {
+        let error = JsonSchema::new(json!({"type": "not-a-type"})).unwrap_err();
+        assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+        assert_eq!(error.property_name, Some("schema".to_string()));
+    }
+
+    #[test]
+    fn validate_payload_without_schema() {
+        assert!(validate_payload(None, &json!("anything"), true, "invalid", None).is_ok());
+    }
+
+    #[test]
+    fn validate_payload_with_schema() {
+        let schema = JsonSchema::new(json!({"type": "string"})).unwrap();
+        let error =
+            validate_payload(Some(&schema), &json!(1), false, "invalid", Some("cmd")).unwrap_err();
+        assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+        assert!(!error.is_shallow);
+        assert!(!error.is_remote);
+        assert_eq!(error.command_name, Some("cmd".to_string()));
+    }
+}
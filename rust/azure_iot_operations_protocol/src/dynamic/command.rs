@@ -0,0 +1,700 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{collections::HashMap, time::Duration};
+
+use azure_iot_operations_mqtt::session::SessionManagedClient;
+use serde_json::Value;
+
+use crate::{
+    application::ApplicationContext,
+    common::{
+        aio_protocol_error::{self, AIOProtocolError},
+        hybrid_logical_clock::HybridLogicalClock,
+    },
+    dynamic::{JsonSchema, validate_payload},
+    rpc_command::{self, executor, invoker},
+};
+
+/// Application error code of the response sent by a [`DynamicCommandExecutor`] when a request
+/// payload does not conform to the request schema.
+pub const SCHEMA_VALIDATION_ERROR_CODE: &str = "SchemaValidationFailed";
+
+/// Runtime definition of a command with JSON request and response payloads.
+///
+/// Creates a [`DynamicCommandInvoker`] or [`DynamicCommandExecutor`] for the command.
+///
+/// # Example
+/// ```
+/// # use azure_iot_operations_protocol::dynamic::{DynamicCommand, JsonSchema};
+/// # use serde_json::json;
+/// let increment = DynamicCommand::new("increment", "rpc/command-samples/{executorId}/{commandName}")
+///     .request_schema(
+///         JsonSchema::new(json!({
+///             "type": "object",
+///             "properties": { "IncrementValue": { "type": "integer" } },
+///             "required": ["IncrementValue"]
+///         }))
+///         .unwrap(),
+///     );
+/// ```
+#[derive(Clone, Debug)]
+pub struct DynamicCommand {
+    name: String,
+    request_topic_pattern: String,
+    topic_namespace: Option<String>,
+    topic_token_map: HashMap<String, String>,
+    is_idempotent: bool,
+    request_schema: Option<JsonSchema>,
+    response_schema: Option<JsonSchema>,
+}
+
+impl DynamicCommand {
+    /// Create a new [`DynamicCommand`] with the given command name and request topic pattern.
+    ///
+    /// The `{commandName}` token is replaced with the command name, `{executorId}` with the client
+    /// ID of the executor and `{invokerClientId}` with the client ID of the invoker, as in
+    /// generated code.
+    #[must_use]
+    pub fn new(name: impl Into<String>, request_topic_pattern: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            request_topic_pattern: request_topic_pattern.into(),
+            topic_namespace: None,
+            topic_token_map: HashMap::new(),
+            is_idempotent: false,
+            request_schema: None,
+            response_schema: None,
+        }
+    }
+
+    /// Topic namespace to be prepended to the request topic pattern.
+    #[must_use]
+    pub fn topic_namespace(mut self, topic_namespace: impl Into<String>) -> Self {
+        self.topic_namespace = Some(topic_namespace.into());
+        self
+    }
+
+    /// Topic token keys/values to be permanently replaced in the request topic pattern.
+    #[must_use]
+    pub fn topic_token_map(mut self, topic_token_map: HashMap<String, String>) -> Self {
+        self.topic_token_map = topic_token_map;
+        self
+    }
+
+    /// Whether the command is idempotent. Default is false.
+    #[must_use]
+    pub fn is_idempotent(mut self, is_idempotent: bool) -> Self {
+        self.is_idempotent = is_idempotent;
+        self
+    }
+
+    /// Schema that request payloads must conform to.
+    #[must_use]
+    pub fn request_schema(mut self, request_schema: JsonSchema) -> Self {
+        self.request_schema = Some(request_schema);
+        self
+    }
+
+    /// Schema that response payloads must conform to.
+    #[must_use]
+    pub fn response_schema(mut self, response_schema: JsonSchema) -> Self {
+        self.response_schema = Some(response_schema);
+        self
+    }
+
+    /// Name of the command.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates the topic token map for an envoy of the command, with the client ID of the envoy
+    /// replaced in `client_id_token`.
+    fn topic_token_map_for(
+        &self,
+        client_id_token: &str,
+        client_id: &str,
+    ) -> HashMap<String, String> {
+        let mut topic_token_map = self.topic_token_map.clone();
+        topic_token_map.insert(client_id_token.to_string(), client_id.to_string());
+        topic_token_map.insert("commandName".to_string(), self.name.clone());
+        topic_token_map
+    }
+
+    /// Creates a new [`DynamicCommandInvoker`] for the command.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the command definition results in invalid [`invoker::Options`]. See [`rpc_command::Invoker::new`].
+    pub fn invoker(
+        &self,
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+    ) -> Result<DynamicCommandInvoker, AIOProtocolError> {
+        let mut invoker_options_builder = invoker::OptionsBuilder::default();
+        if let Some(topic_namespace) = &self.topic_namespace {
+            invoker_options_builder.topic_namespace(topic_namespace.clone());
+        }
+        let invoker_options = invoker_options_builder
+            .request_topic_pattern(self.request_topic_pattern.clone())
+            .command_name(self.name.clone())
+            .topic_token_map(self.topic_token_map_for("invokerClientId", client.client_id()))
+            .build()
+            .map_err(|e| invalid_options_error(e, &self.name))?;
+
+        Ok(DynamicCommandInvoker {
+            invoker: rpc_command::Invoker::new(application_context, client, invoker_options)?,
+            command_name: self.name.clone(),
+            request_schema: self.request_schema.clone(),
+            response_schema: self.response_schema.clone(),
+        })
+    }
+
+    /// Creates a new [`DynamicCommandExecutor`] for the command.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the command definition results in invalid [`executor::Options`]. See [`rpc_command::Executor::new`].
+    pub fn executor(
+        &self,
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+    ) -> Result<DynamicCommandExecutor, AIOProtocolError> {
+        let mut executor_options_builder = executor::OptionsBuilder::default();
+        if let Some(topic_namespace) = &self.topic_namespace {
+            executor_options_builder.topic_namespace(topic_namespace.clone());
+        }
+        let executor_options = executor_options_builder
+            .request_topic_pattern(self.request_topic_pattern.clone())
+            .command_name(self.name.clone())
+            .is_idempotent(self.is_idempotent)
+            .topic_token_map(self.topic_token_map_for("executorId", client.client_id()))
+            .build()
+            .map_err(|e| invalid_options_error(e, &self.name))?;
+
+        Ok(DynamicCommandExecutor {
+            executor: rpc_command::Executor::new(application_context, client, executor_options)?,
+            request_schema: self.request_schema.clone(),
+            response_schema: self.response_schema.clone(),
+        })
+    }
+}
+
+/// Creates the error returned when the options of an envoy of a [`DynamicCommand`] are invalid.
+fn invalid_options_error(
+    error: impl std::error::Error + Send + Sync + 'static,
+    command_name: &str,
+) -> AIOProtocolError {
+    AIOProtocolError::new_configuration_invalid_error(
+        Some(Box::new(error)),
+        "command_name",
+        aio_protocol_error::Value::String(command_name.to_string()),
+        None,
+        Some(command_name.to_string()),
+    )
+}
+
+/// Command request sent by a [`DynamicCommandInvoker`].
+/// Created with a [`DynamicInvokerRequestBuilder`].
+#[derive(Clone, Debug)]
+pub struct DynamicInvokerRequest(invoker::Request<Value>);
+
+/// Builder for [`DynamicInvokerRequest`]. Created with [`DynamicCommandInvoker::request_builder`].
+pub struct DynamicInvokerRequestBuilder {
+    inner_builder: invoker::RequestBuilder<Value>,
+    topic_tokens: HashMap<String, String>,
+    has_payload: bool,
+    command_name: String,
+    request_schema: Option<JsonSchema>,
+}
+
+impl DynamicInvokerRequestBuilder {
+    /// Custom user data to set on the request
+    pub fn custom_user_data(&mut self, custom_user_data: Vec<(String, String)>) -> &mut Self {
+        self.inner_builder.custom_user_data(custom_user_data);
+        self
+    }
+
+    /// Cloud event for the request
+    pub fn cloud_event(&mut self, cloud_event: Option<invoker::RequestCloudEvent>) -> &mut Self {
+        self.inner_builder.cloud_event(cloud_event);
+        self
+    }
+
+    /// Topic token keys/values to be replaced into the publish topic of the request message.
+    pub fn topic_tokens(&mut self, topic_tokens: HashMap<String, String>) -> &mut Self {
+        self.topic_tokens.extend(topic_tokens);
+        self
+    }
+
+    /// Timeout for the request
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.inner_builder.timeout(timeout);
+        self
+    }
+
+    /// Target executor ID, replaced in the `{executorId}` token of the request topic
+    pub fn executor_id(&mut self, executor_id: &str) -> &mut Self {
+        self.topic_tokens
+            .insert("executorId".to_string(), executor_id.to_string());
+        self
+    }
+
+    /// Payload of the request. If not set, the request has an empty payload.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if the payload does not conform to the request schema or cannot be serialized
+    pub fn payload(&mut self, payload: Value) -> Result<&mut Self, AIOProtocolError> {
+        validate_payload(
+            self.request_schema.as_ref(),
+            &payload,
+            true,
+            "Request payload does not conform to the request schema",
+            Some(&self.command_name),
+        )?;
+        self.inner_builder.payload(payload)?;
+        self.has_payload = true;
+        Ok(self)
+    }
+
+    /// Builds a new [`DynamicInvokerRequest`]
+    ///
+    /// # Errors
+    /// If a required field has not been initialized, or if no payload was set and an empty
+    /// payload does not conform to the request schema
+    pub fn build(&mut self) -> Result<DynamicInvokerRequest, invoker::RequestBuilderError> {
+        if !self.has_payload {
+            self.payload(Value::Null)
+                .map_err(|e| invoker::RequestBuilderError::ValidationError(e.to_string()))?;
+        }
+        self.inner_builder.topic_tokens(self.topic_tokens.clone());
+        self.inner_builder.build().map(DynamicInvokerRequest)
+    }
+}
+
+/// Command Invoker for a [`DynamicCommand`]
+pub struct DynamicCommandInvoker {
+    invoker: rpc_command::Invoker<Value, Value>,
+    command_name: String,
+    request_schema: Option<JsonSchema>,
+    response_schema: Option<JsonSchema>,
+}
+
+impl DynamicCommandInvoker {
+    /// Creates a [`DynamicInvokerRequestBuilder`] for a request validated against the request
+    /// schema of the command.
+    #[must_use]
+    pub fn request_builder(&self) -> DynamicInvokerRequestBuilder {
+        DynamicInvokerRequestBuilder {
+            inner_builder: invoker::RequestBuilder::default(),
+            topic_tokens: HashMap::new(),
+            has_payload: false,
+            command_name: self.command_name.clone(),
+            request_schema: self.request_schema.clone(),
+        }
+    }
+
+    /// Invokes the [`DynamicInvokerRequest`]
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if there is a failure invoking the request. See [`rpc_command::Invoker::invoke`].
+    ///
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if the response payload does not conform to the response schema
+    pub async fn invoke(
+        &self,
+        request: DynamicInvokerRequest,
+    ) -> Result<invoker::Response<Value>, AIOProtocolError> {
+        let response = self.invoker.invoke(request.0).await?;
+        validate_payload(
+            self.response_schema.as_ref(),
+            &response.payload,
+            false,
+            "Response payload does not conform to the response schema",
+            Some(&self.command_name),
+        )?;
+        Ok(response)
+    }
+
+    /// Shutdown the [`DynamicCommandInvoker`]. See [`rpc_command::Invoker::shutdown`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the unsubscribe fails or if the unsuback reason code doesn't indicate success.
+    pub async fn shutdown(&self) -> Result<(), AIOProtocolError> {
+        self.invoker.shutdown().await
+    }
+}
+
+/// Command response sent by a [`DynamicCommandExecutor`].
+/// Created with a [`DynamicExecutorResponseBuilder`].
+#[derive(Clone, Debug)]
+pub struct DynamicExecutorResponse(executor::Response<Value>);
+
+/// Builder for [`DynamicExecutorResponse`]. Created with [`DynamicExecutorRequest::response_builder`].
+pub struct DynamicExecutorResponseBuilder {
+    inner_builder: executor::ResponseBuilder<Value>,
+    has_payload: bool,
+    response_schema: Option<JsonSchema>,
+}
+
+impl DynamicExecutorResponseBuilder {
+    /// Custom user data to set on the response
+    pub fn custom_user_data(&mut self, custom_user_data: Vec<(String, String)>) -> &mut Self {
+        self.inner_builder.custom_user_data(custom_user_data);
+        self
+    }
+
+    /// Cloud event for the response
+    pub fn cloud_event(&mut self, cloud_event: Option<executor::ResponseCloudEvent>) -> &mut Self {
+        self.inner_builder.cloud_event(cloud_event);
+        self
+    }
+
+    /// Payload of the response. If not set, the response has an empty payload.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if the payload does not conform to the response schema or cannot be serialized
+    pub fn payload(&mut self, payload: Value) -> Result<&mut Self, AIOProtocolError> {
+        validate_payload(
+            self.response_schema.as_ref(),
+            &payload,
+            true,
+            "Response payload does not conform to the response schema",
+            None,
+        )?;
+        self.inner_builder.payload(payload)?;
+        self.has_payload = true;
+        Ok(self)
+    }
+
+    /// Builds a new [`DynamicExecutorResponse`]
+    ///
+    /// # Errors
+    /// If a required field has not been initialized, or if no payload was set and an empty
+    /// payload does not conform to the response schema
+    pub fn build(&mut self) -> Result<DynamicExecutorResponse, executor::ResponseBuilderError> {
+        if !self.has_payload {
+            self.payload(Value::Null)
+                .map_err(|e| executor::ResponseBuilderError::ValidationError(e.to_string()))?;
+        }
+        self.inner_builder.build().map(DynamicExecutorResponse)
+    }
+}
+
+/// Command request received by a [`DynamicCommandExecutor`].
+pub struct DynamicExecutorRequest {
+    request: executor::Request<Value, Value>,
+    response_schema: Option<JsonSchema>,
+}
+
+impl DynamicExecutorRequest {
+    /// Payload of the command request.
+    #[must_use]
+    pub fn payload(&self) -> &Value {
+        &self.request.payload
+    }
+
+    /// Custom user data set as custom MQTT User Properties on the request message.
+    #[must_use]
+    pub fn custom_user_data(&self) -> &[(String, String)] {
+        &self.request.custom_user_data
+    }
+
+    /// Timestamp of the command request.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<&HybridLogicalClock> {
+        self.request.timestamp.as_ref()
+    }
+
+    /// Client ID of the invoker of the command request, if present.
+    #[must_use]
+    pub fn invoker_id(&self) -> Option<&str> {
+        self.request.invoker_id.as_deref()
+    }
+
+    /// Resolved topic tokens from the incoming request's topic.
+    #[must_use]
+    pub fn topic_tokens(&self) -> &HashMap<String, String> {
+        &self.request.topic_tokens
+    }
+
+    /// Check if the command response is no longer expected.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.request.is_cancelled()
+    }
+
+    /// Creates a [`DynamicExecutorResponseBuilder`] for a response validated against the
+    /// response schema of the command.
+    #[must_use]
+    pub fn response_builder(&self) -> DynamicExecutorResponseBuilder {
+        DynamicExecutorResponseBuilder {
+            inner_builder: executor::ResponseBuilder::default(),
+            has_payload: false,
+            response_schema: self.response_schema.clone(),
+        }
+    }
+
+    /// Consumes the command request and reports the response to the executor. See
+    /// [`executor::Request::complete`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if the response could not be sent.
+    pub async fn complete(self, response: DynamicExecutorResponse) -> Result<(), AIOProtocolError> {
+        self.request.complete(response.0).await
+    }
+}
+
+/// Command Executor for a [`DynamicCommand`]
+pub struct DynamicCommandExecutor {
+    executor: rpc_command::Executor<Value, Value>,
+    request_schema: Option<JsonSchema>,
+    response_schema: Option<JsonSchema>,
+}
+
+impl DynamicCommandExecutor {
+    /// Receive the next [`DynamicExecutorRequest`] or [`None`] if there will be no more requests.
+    ///
+    /// Requests with a payload that does not conform to the request schema are not returned.
+    /// Instead, they are responded to with an application error with the code
+    /// [`SCHEMA_VALIDATION_ERROR_CODE`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if there is a failure receiving a request. See [`rpc_command::Executor::recv`].
+    pub async fn recv(&mut self) -> Option<Result<DynamicExecutorRequest, AIOProtocolError>> {
+        loop {
+            let request = match self.executor.recv().await? {
+                Ok(request) => request,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if let Some(request_schema) = &self.request_schema
+                && let Err(e) = request_schema.validate(&request.payload)
+            {
+                log::warn!("Request payload does not conform to the request schema: {e}");
+                tokio::task::spawn(respond_with_validation_error(request, e.to_string()));
+                continue;
+            }
+
+            return Some(Ok(DynamicExecutorRequest {
+                request,
+                response_schema: self.response_schema.clone(),
+            }));
+        }
+    }
+
+    /// Shutdown the [`DynamicCommandExecutor`]. See [`rpc_command::Executor::shutdown`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the unsubscribe fails or if the unsuback reason code doesn't indicate success.
+    pub async fn shutdown(&mut self) -> Result<(), AIOProtocolError> {
+        self.executor.shutdown().await
+    }
+}
+
+/// Responds to a request with an application error describing the schema violation.
+async fn respond_with_validation_error(request: executor::Request<Value, Value>, message: String) {
+    let custom_user_data = rpc_command::ApplicationError {
+        code: SCHEMA_VALIDATION_ERROR_CODE.to_string(),
+        detail: Some(message),
+        severity: rpc_command::ApplicationErrorSeverity::Error,
+    }
+    .into_headers();
+    let response = executor::ResponseBuilder::default()
+        .payload(Value::Null)
+        .and_then(|builder| {
+            builder
+                .custom_user_data(custom_user_data)
+                .build()
+                .map_err(|e| {
+                    AIOProtocolError::new_internal_logic_error(
+                        true,
+                        false,
+                        None,
+                        "response",
+                        None,
+                        Some(format!(
+                            "Failed to build schema validation error response: {e}"
+                        )),
+                        None,
+                    )
+                })
+        });
+    match response {
+        Ok(response) => {
+            if let Err(e) = request.complete(response).await {
+                log::warn!("Failed to send schema validation error response: {e}");
+            }
+        }
+        Err(e) => log::error!("{e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+    use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+    };
+
+    fn get_session() -> Session {
+        let connection_settings = MqttConnectionSettingsBuilder::default()
+            .hostname("localhost")
+            .client_id("test_client")
+            .build()
+            .unwrap();
+        let session_options = SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .build()
+            .unwrap();
+        Session::new(session_options).unwrap()
+    }
+
+    fn increment_command() -> DynamicCommand {
+        DynamicCommand::new(
+            "increment",
+            "rpc/command-samples/{executorId}/{commandName}",
+        )
+        .request_schema(
+            JsonSchema::new(json!({
+                "type": "object",
+                "properties": { "IncrementValue": { "type": "integer" } },
+                "required": ["IncrementValue"]
+            }))
+            .unwrap(),
+        )
+        .response_schema(
+            JsonSchema::new(json!({
+                "type": "object",
+                "properties": { "CounterResponse": { "type": "integer" } },
+                "required": ["CounterResponse"]
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn create_invoker_and_executor() {
+        let session = get_session();
+        let command = increment_command();
+        assert_eq!(command.name(), "increment");
+
+        command
+            .invoker(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+        command
+            .executor(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_invoker_invalid_pattern() {
+        let session = get_session();
+        let command = DynamicCommand::new("increment", "rpc/{invalid token}");
+
+        let error = command
+            .invoker(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    }
+
+    #[test]
+    fn topic_token_map_includes_command_tokens() {
+        let command =
+            DynamicCommand::new("increment", "rpc/{ex:group}/{commandName}").topic_token_map(
+                HashMap::from([("ex:group".to_string(), "samples".to_string())]),
+            );
+
+        let topic_token_map = command.topic_token_map_for("executorId", "server");
+        assert_eq!(topic_token_map.get("ex:group").unwrap(), "samples");
+        assert_eq!(topic_token_map.get("executorId").unwrap(), "server");
+        assert_eq!(topic_token_map.get("commandName").unwrap(), "increment");
+    }
+
+    #[tokio::test]
+    async fn request_builder_validates_payload() {
+        let session = get_session();
+        let invoker = increment_command()
+            .invoker(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+
+        invoker
+            .request_builder()
+            .executor_id("server")
+            .timeout(Duration::from_secs(10))
+            .payload(json!({"IncrementValue": 1}))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = invoker
+            .request_builder()
+            .payload(json!({"IncrementValue": "one"}))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+        assert_eq!(error.command_name, Some("increment".to_string()));
+
+        // An empty payload does not conform to the request schema
+        assert!(
+            invoker
+                .request_builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn request_builder_empty_payload() {
+        let session = get_session();
+        let invoker = DynamicCommand::new("readCounter", "rpc/{executorId}/{commandName}")
+            .invoker(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+
+        invoker
+            .request_builder()
+            .executor_id("server")
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn response_builder_validates_payload() {
+        let mut builder = DynamicExecutorResponseBuilder {
+            inner_builder: executor::ResponseBuilder::default(),
+            has_payload: false,
+            response_schema: increment_command().response_schema,
+        };
+        let error = builder.payload(json!({"Counter": 1})).err().unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+        assert!(builder.build().is_err());
+
+        builder.payload(json!({"CounterResponse": 1})).unwrap();
+        builder.build().unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use azure_iot_operations_mqtt::{
+    control_packet::QoS, session::SessionManagedClient, token::AckToken,
+};
+use serde_json::Value;
+
+use crate::{
+    application::ApplicationContext,
+    common::aio_protocol_error::AIOProtocolError,
+    dynamic::{JsonSchema, validate_payload},
+    telemetry::{
+        self,
+        middleware::{RawMessage, ReceiveMiddleware, Rejection, RejectionBehavior},
+        receiver, sender,
+    },
+};
+
+/// Runtime definition of telemetry with a JSON payload.
+///
+/// Creates a [`DynamicTelemetrySender`] or [`DynamicTelemetryReceiver`] for the telemetry.
+#[derive(Clone, Debug)]
+pub struct DynamicTelemetry {
+    topic_pattern: String,
+    topic_namespace: Option<String>,
+    topic_token_map: HashMap<String, String>,
+    schema: Option<JsonSchema>,
+}
+
+impl DynamicTelemetry {
+    /// Create a new [`DynamicTelemetry`] with the given topic pattern.
+    ///
+    /// The `{senderId}` token is replaced with the client ID of the sender, as in generated code.
+    #[must_use]
+    pub fn new(topic_pattern: impl Into<String>) -> Self {
+        Self {
+            topic_pattern: topic_pattern.into(),
+            topic_namespace: None,
+            topic_token_map: HashMap::new(),
+            schema: None,
+        }
+    }
+
+    /// Topic namespace to be prepended to the topic pattern.
+    #[must_use]
+    pub fn topic_namespace(mut self, topic_namespace: impl Into<String>) -> Self {
+        self.topic_namespace = Some(topic_namespace.into());
+        self
+    }
+
+    /// Topic token keys/values to be permanently replaced in the topic pattern.
+    #[must_use]
+    pub fn topic_token_map(mut self, topic_token_map: HashMap<String, String>) -> Self {
+        self.topic_token_map = topic_token_map;
+        self
+    }
+
+    /// Schema that telemetry payloads must conform to.
+    #[must_use]
+    pub fn schema(mut self, schema: JsonSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Creates a new [`DynamicTelemetrySender`] for the telemetry.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the telemetry definition results in invalid [`sender::Options`]. See [`telemetry::Sender::new`].
+    pub fn sender(
+        &self,
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+    ) -> Result<DynamicTelemetrySender, AIOProtocolError> {
+        let mut topic_token_map = self.topic_token_map.clone();
+        topic_token_map.insert("senderId".to_string(), client.client_id().to_string());
+
+        let mut sender_options_builder = sender::OptionsBuilder::default();
+        if let Some(topic_namespace) = &self.topic_namespace {
+            sender_options_builder.topic_namespace(topic_namespace.clone());
+        }
+        let sender_options = sender_options_builder
+            .topic_pattern(self.topic_pattern.clone())
+            .topic_token_map(topic_token_map)
+            .build()
+            .map_err(|e| invalid_options_error(e, &self.topic_pattern))?;
+
+        Ok(DynamicTelemetrySender {
+            sender: telemetry::Sender::new(application_context, client, sender_options)?,
+            schema: self.schema.clone(),
+        })
+    }
+
+    /// Creates a new [`DynamicTelemetryReceiver`] for the telemetry.
+    ///
+    /// If `auto_ack` is true, received telemetry messages are acknowledged automatically.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the telemetry definition results in invalid [`receiver::Options`]. See [`telemetry::Receiver::new`].
+    pub fn receiver(
+        &self,
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+        auto_ack: bool,
+    ) -> Result<DynamicTelemetryReceiver, AIOProtocolError> {
+        let mut receiver_options_builder = receiver::OptionsBuilder::default();
+        if let Some(topic_namespace) = &self.topic_namespace {
+            receiver_options_builder.topic_namespace(topic_namespace.clone());
+        }
+        if let Some(schema) = &self.schema {
+            let middleware: Arc<dyn ReceiveMiddleware> = Arc::new(SchemaValidation(schema.clone()));
+            receiver_options_builder
+                .middleware(vec![middleware])
+                .rejection_behavior(RejectionBehavior::Error);
+        }
+        let receiver_options = receiver_options_builder
+            .topic_pattern(self.topic_pattern.clone())
+            .topic_token_map(self.topic_token_map.clone())
+            .auto_ack(auto_ack)
+            .build()
+            .map_err(|e| invalid_options_error(e, &self.topic_pattern))?;
+
+        Ok(DynamicTelemetryReceiver(telemetry::Receiver::new(
+            application_context,
+            client,
+            receiver_options,
+        )?))
+    }
+}
+
+/// Creates the error returned when the options of an envoy of a [`DynamicTelemetry`] are invalid.
+fn invalid_options_error(
+    error: impl std::error::Error + Send + Sync + 'static,
+    topic_pattern: &str,
+) -> AIOProtocolError {
+    AIOProtocolError::new_configuration_invalid_error(
+        Some(Box::new(error)),
+        "topic_pattern",
+        crate::common::aio_protocol_error::Value::String(topic_pattern.to_string()),
+        None,
+        None,
+    )
+}
+
+/// [`ReceiveMiddleware`] rejecting telemetry messages with a payload that does not conform to a
+/// [`JsonSchema`].
+///
+/// Payloads that are not valid JSON are passed through, to be handled by payload deserialization.
+struct SchemaValidation(JsonSchema);
+
+impl ReceiveMiddleware for SchemaValidation {
+    fn process(&self, message: &mut RawMessage) -> Result<(), Rejection> {
+        let payload = if message.payload.is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice(&message.payload) {
+                Ok(payload) => payload,
+                Err(_) => return Ok(()),
+            }
+        };
+        self.0.validate(&payload).map_err(|e| {
+            Rejection::new(format!(
+                "Telemetry payload does not conform to the schema: {e}"
+            ))
+        })
+    }
+}
+
+/// Telemetry message sent by a [`DynamicTelemetrySender`].
+/// Created with a [`DynamicTelemetryMessageBuilder`].
+#[derive(Clone, Debug)]
+pub struct DynamicTelemetryMessage(sender::Message<Value>);
+
+/// Builder for [`DynamicTelemetryMessage`]. Created with [`DynamicTelemetrySender::message_builder`].
+pub struct DynamicTelemetryMessageBuilder {
+    inner_builder: sender::MessageBuilder<Value>,
+    topic_tokens: HashMap<String, String>,
+    schema: Option<JsonSchema>,
+}
+
+impl DynamicTelemetryMessageBuilder {
+    /// Quality of Service of the telemetry message. Can only be `AtMostOnce` or `AtLeastOnce`.
+    pub fn qos(&mut self, qos: QoS) -> &mut Self {
+        self.inner_builder.qos(qos);
+        self
+    }
+
+    /// Custom user data to set on the message
+    pub fn custom_user_data(&mut self, custom_user_data: Vec<(String, String)>) -> &mut Self {
+        self.inner_builder.custom_user_data(custom_user_data);
+        self
+    }
+
+    /// Topic token keys/values to be replaced into the publish topic of the telemetry message.
+    pub fn topic_tokens(&mut self, topic_tokens: HashMap<String, String>) -> &mut Self {
+        self.topic_tokens.extend(topic_tokens);
+        self
+    }
+
+    /// Time before message expires
+    pub fn message_expiry(&mut self, message_expiry: Duration) -> &mut Self {
+        self.inner_builder.message_expiry(message_expiry);
+        self
+    }
+
+    /// Cloud event for the message
+    pub fn cloud_event(&mut self, cloud_event: Option<sender::CloudEvent>) -> &mut Self {
+        self.inner_builder.cloud_event(cloud_event);
+        self
+    }
+
+    /// Payload of the message
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if the payload does not conform to the schema or cannot be serialized
+    pub fn payload(&mut self, payload: Value) -> Result<&mut Self, AIOProtocolError> {
+        validate_payload(
+            self.schema.as_ref(),
+            &payload,
+            true,
+            "Telemetry payload does not conform to the schema",
+            None,
+        )?;
+        self.inner_builder.payload(payload)?;
+        Ok(self)
+    }
+
+    /// Builds a new [`DynamicTelemetryMessage`]
+    ///
+    /// # Errors
+    /// If a required field has not been initialized
+    pub fn build(&mut self) -> Result<DynamicTelemetryMessage, sender::MessageBuilderError> {
+        self.inner_builder.topic_tokens(self.topic_tokens.clone());
+        self.inner_builder.build().map(DynamicTelemetryMessage)
+    }
+}
+
+/// Telemetry Sender for a [`DynamicTelemetry`]
+pub struct DynamicTelemetrySender {
+    sender: telemetry::Sender<Value>,
+    schema: Option<JsonSchema>,
+}
+
+impl DynamicTelemetrySender {
+    /// Creates a [`DynamicTelemetryMessageBuilder`] for a message validated against the schema of
+    /// the telemetry.
+    #[must_use]
+    pub fn message_builder(&self) -> DynamicTelemetryMessageBuilder {
+        DynamicTelemetryMessageBuilder {
+            inner_builder: sender::MessageBuilder::default(),
+            topic_tokens: HashMap::new(),
+            schema: self.schema.clone(),
+        }
+    }
+
+    /// Sends a [`DynamicTelemetryMessage`]. See [`telemetry::Sender::send`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if there is a failure sending the message.
+    pub async fn send(&self, message: DynamicTelemetryMessage) -> Result<(), AIOProtocolError> {
+        self.sender.send(message.0).await
+    }
+
+    /// Sends a [`DynamicTelemetryMessage`], returning the [`SendResult`](telemetry::SendResult).
+    /// See [`telemetry::Sender::send_with_result`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if there is a failure sending the message.
+    pub async fn send_with_result(
+        &self,
+        message: DynamicTelemetryMessage,
+    ) -> Result<telemetry::SendResult, AIOProtocolError> {
+        self.sender.send_with_result(message.0).await
+    }
+}
+
+/// Telemetry Receiver for a [`DynamicTelemetry`]
+pub struct DynamicTelemetryReceiver(telemetry::Receiver<Value>);
+
+impl DynamicTelemetryReceiver {
+    /// Receives the next telemetry message. See [`telemetry::Receiver::recv`].
+    ///
+    /// Messages with a payload that does not conform to the schema are acknowledged and an error
+    /// is returned in place of them.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if the payload of a message does not conform to the schema
+    ///
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
+    pub async fn recv(
+        &mut self,
+    ) -> Option<Result<(receiver::Message<Value>, Option<AckToken>), AIOProtocolError>> {
+        self.0.recv().await
+    }
+
+    /// Shutdown the [`DynamicTelemetryReceiver`]. See [`telemetry::Receiver::shutdown`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the unsubscribe fails or if the unsuback reason code doesn't indicate success.
+    pub async fn shutdown(&mut self) -> Result<(), AIOProtocolError> {
+        self.0.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+    use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+    use bytes::Bytes;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+    };
+
+    fn get_session() -> Session {
+        let connection_settings = MqttConnectionSettingsBuilder::default()
+            .hostname("localhost")
+            .client_id("test_client")
+            .build()
+            .unwrap();
+        let session_options = SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .build()
+            .unwrap();
+        Session::new(session_options).unwrap()
+    }
+
+    fn counter_telemetry() -> DynamicTelemetry {
+        DynamicTelemetry::new("telemetry/telemetry-samples/counterValue").schema(
+            JsonSchema::new(json!({
+                "type": "object",
+                "properties": { "CounterValue": { "type": "integer" } }
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn raw_message(payload: &[u8]) -> RawMessage {
+        RawMessage::new(
+            Bytes::copy_from_slice(payload),
+            Some("application/json".to_string()),
+            vec![],
+            "telemetry/telemetry-samples/counterValue".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn create_sender_and_receiver() {
+        let session = get_session();
+        let telemetry = counter_telemetry();
+
+        telemetry
+            .sender(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+        telemetry
+            .receiver(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+                true,
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_sender_invalid_pattern() {
+        let session = get_session();
+        let error = DynamicTelemetry::new("")
+            .sender(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    }
+
+    #[tokio::test]
+    async fn message_builder_validates_payload() {
+        let session = get_session();
+        let sender = counter_telemetry()
+            .sender(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+            )
+            .unwrap();
+
+        sender
+            .message_builder()
+            .payload(json!({"CounterValue": 1}))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = sender
+            .message_builder()
+            .payload(json!({"CounterValue": "one"}))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+    }
+
+    #[test]
+    fn schema_validation_middleware() {
+        let middleware = SchemaValidation(counter_telemetry().schema.unwrap());
+
+        assert!(
+            middleware
+                .process(&mut raw_message(br#"{"CounterValue":1}"#))
+                .is_ok()
+        );
+        assert!(
+            middleware
+                .process(&mut raw_message(br#"{"CounterValue":"one"}"#))
+                .is_err()
+        );
+        // Empty payloads are validated as null
+        assert!(middleware.process(&mut raw_message(b"")).is_err());
+        // Invalid JSON is left to payload deserialization
+        assert!(middleware.process(&mut raw_message(b"{")).is_ok());
+    }
+}
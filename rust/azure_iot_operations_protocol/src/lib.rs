@@ -8,6 +8,8 @@
 
 pub mod application;
 pub mod common;
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
 pub mod rpc_command;
 pub mod telemetry;
 
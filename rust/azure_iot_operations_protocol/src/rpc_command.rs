@@ -13,9 +13,21 @@ pub mod invoker;
 /// This module contains the command executor implementation.
 pub mod executor;
 
-/// Re-export the command invoker and executor for ease of use.
+/// This module contains the command router implementation.
+pub mod router;
+
+/// This module contains the application error carried on command responses.
+pub mod application_error;
+
+/// This module contains the chunked transfer of large request payloads.
+pub mod chunked;
+
+/// Re-export the command invoker, executor and router for ease of use.
 pub use executor::Executor;
 pub use invoker::Invoker;
+pub use router::CommandRouter;
+
+pub use application_error::{ApplicationError, ApplicationErrorSeverity};
 
 /// Protocol version used by all command envoys in this module
 pub(crate) const RPC_COMMAND_PROTOCOL_VERSION: ProtocolVersion =
@@ -0,0 +1,255 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// User property carrying the [`ApplicationError`] code
+pub(crate) const APPLICATION_ERROR_CODE_HEADER: &str = "AppErrCode";
+/// User property carrying the [`ApplicationError`] detail
+pub(crate) const APPLICATION_ERROR_PAYLOAD_HEADER: &str = "AppErrPayload";
+/// User property carrying the [`ApplicationError`] severity
+pub(crate) const APPLICATION_ERROR_SEVERITY_HEADER: &str = "AppErrSeverity";
+
+/// Domain error returned by a command handler, carried on the custom user data of a command
+/// response.
+///
+/// Build one with an [`ApplicationErrorBuilder`] and add it to a response with
+/// [`into_headers`](Self::into_headers), and recover it on the invoker side with
+/// [`from_headers`](Self::from_headers) or
+/// [`Response::application_error`](crate::rpc_command::invoker::Response::application_error).
+#[derive(Builder, Clone, Debug, PartialEq, Eq)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct ApplicationError {
+    /// Application defined error code. Must not be empty.
+    pub code: String,
+    /// Additional detail about the error, conventionally a JSON object/value/array.
+    /// Use [`deserialize_detail`](Self::deserialize_detail) to read structured detail.
+    #[builder(default, setter(custom))]
+    pub detail: Option<String>,
+    /// Severity of the error
+    #[builder(default)]
+    pub severity: ApplicationErrorSeverity,
+}
+
+/// Severity of an [`ApplicationError`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ApplicationErrorSeverity {
+    /// The command completed, but with a condition the invoker should be aware of
+    Warning,
+    /// The command failed
+    #[default]
+    Error,
+    /// The command failed, and retrying it is not expected to succeed
+    Critical,
+}
+
+impl fmt::Display for ApplicationErrorSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplicationErrorSeverity::Warning => write!(f, "warning"),
+            ApplicationErrorSeverity::Error => write!(f, "error"),
+            ApplicationErrorSeverity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+impl FromStr for ApplicationErrorSeverity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(ApplicationErrorSeverity::Warning),
+            "error" => Ok(ApplicationErrorSeverity::Error),
+            "critical" => Ok(ApplicationErrorSeverity::Critical),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ApplicationErrorBuilder {
+    /// Set the detail of the error by serializing `detail` as JSON.
+    ///
+    /// # Errors
+    /// Returns a [`serde_json::Error`] if `detail` fails to serialize
+    pub fn detail<T: Serialize>(&mut self, detail: &T) -> Result<&mut Self, serde_json::Error> {
+        self.detail = Some(Some(serde_json::to_string(detail)?));
+        Ok(self)
+    }
+
+    /// Set the detail of the error as-is, for detail that isn't JSON or is already serialized.
+    pub fn raw_detail(&mut self, detail: impl Into<String>) -> &mut Self {
+        self.detail = Some(Some(detail.into()));
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(code) = &self.code
+            && code.trim().is_empty()
+        {
+            return Err("code cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationError {
+    /// Get the [`ApplicationError`] as user properties to add to the `custom_user_data` of a
+    /// command response.
+    ///
+    /// A detail that is empty or only whitespace is omitted, as is the default
+    /// [`ApplicationErrorSeverity::Error`] severity.
+    #[must_use]
+    pub fn into_headers(self) -> Vec<(String, String)> {
+        let mut headers = vec![(APPLICATION_ERROR_CODE_HEADER.to_string(), self.code)];
+        if let Some(detail) = self.detail
+            && !detail.trim().is_empty()
+        {
+            headers.push((APPLICATION_ERROR_PAYLOAD_HEADER.to_string(), detail));
+        }
+        if self.severity != ApplicationErrorSeverity::default() {
+            headers.push((
+                APPLICATION_ERROR_SEVERITY_HEADER.to_string(),
+                self.severity.to_string(),
+            ));
+        }
+        headers
+    }
+
+    /// Parse an [`ApplicationError`] from the `custom_user_data` of a command response.
+    ///
+    /// Returns [`None`] if there is no application error code, or it is empty. A missing or
+    /// unrecognized severity is treated as [`ApplicationErrorSeverity::Error`].
+    #[must_use]
+    pub fn from_headers(custom_user_data: &[(String, String)]) -> Option<Self> {
+        let mut code = None;
+        let mut detail = None;
+        let mut severity = ApplicationErrorSeverity::default();
+
+        for (key, value) in custom_user_data {
+            match key.as_str() {
+                APPLICATION_ERROR_CODE_HEADER => code = Some(value.clone()),
+                APPLICATION_ERROR_PAYLOAD_HEADER => detail = Some(value.clone()),
+                APPLICATION_ERROR_SEVERITY_HEADER => {
+                    severity = value.parse().unwrap_or_else(|()| {
+                        log::warn!("Unrecognized application error severity '{value}'");
+                        ApplicationErrorSeverity::default()
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        code.filter(|code| !code.trim().is_empty())
+            .map(|code| ApplicationError {
+                code,
+                detail,
+                severity,
+            })
+    }
+
+    /// Deserialize the detail of the error from JSON.
+    ///
+    /// Returns [`None`] if the error has no detail.
+    ///
+    /// # Errors
+    /// Returns a [`serde_json::Error`] if the detail is not valid JSON for `T`
+    #[must_use]
+    pub fn deserialize_detail<T: DeserializeOwned>(&self) -> Option<Result<T, serde_json::Error>> {
+        self.detail
+            .as_deref()
+            .map(|detail| serde_json::from_str(detail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OutOfRange {
+        min: u32,
+        max: u32,
+    }
+
+    #[test]
+    fn empty_code_fails() {
+        assert!(
+            ApplicationErrorBuilder::default()
+                .code(" ")
+                .build()
+                .is_err()
+        );
+        assert!(ApplicationErrorBuilder::default().build().is_err());
+    }
+
+    #[test_case(ApplicationErrorSeverity::Warning; "warning")]
+    #[test_case(ApplicationErrorSeverity::Error; "error")]
+    #[test_case(ApplicationErrorSeverity::Critical; "critical")]
+    fn headers_round_trip(severity: ApplicationErrorSeverity) {
+        let error = ApplicationErrorBuilder::default()
+            .code("OutOfRange")
+            .detail(&OutOfRange { min: 1, max: 10 })
+            .unwrap()
+            .severity(severity)
+            .build()
+            .unwrap();
+
+        let mut custom_user_data = vec![("other".to_string(), "value".to_string())];
+        custom_user_data.extend(error.clone().into_headers());
+
+        let parsed = ApplicationError::from_headers(&custom_user_data).unwrap();
+        assert_eq!(parsed, error);
+        assert_eq!(
+            parsed.deserialize_detail::<OutOfRange>().unwrap().unwrap(),
+            OutOfRange { min: 1, max: 10 }
+        );
+    }
+
+    #[test]
+    fn default_severity_and_blank_detail_not_rendered() {
+        let headers = ApplicationErrorBuilder::default()
+            .code("500")
+            .raw_detail("  ")
+            .build()
+            .unwrap()
+            .into_headers();
+        assert_eq!(
+            headers,
+            vec![(APPLICATION_ERROR_CODE_HEADER.to_string(), "500".to_string())]
+        );
+    }
+
+    #[test_case(&[]; "no code")]
+    #[test_case(&[(APPLICATION_ERROR_CODE_HEADER, "")]; "empty code")]
+    #[test_case(&[(APPLICATION_ERROR_PAYLOAD_HEADER, "detail")]; "detail without code")]
+    fn from_headers_none(headers: &[(&str, &str)]) {
+        let headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        assert!(ApplicationError::from_headers(&headers).is_none());
+    }
+
+    #[test]
+    fn from_headers_unknown_severity() {
+        let headers = vec![
+            (
+                APPLICATION_ERROR_CODE_HEADER.to_string(),
+                "5888".to_string(),
+            ),
+            (
+                APPLICATION_ERROR_SEVERITY_HEADER.to_string(),
+                "catastrophic".to_string(),
+            ),
+        ];
+        let error = ApplicationError::from_headers(&headers).unwrap();
+        assert_eq!(error.severity, ApplicationErrorSeverity::Error);
+        assert!(error.detail.is_none());
+        assert!(error.deserialize_detail::<OutOfRange>().is_none());
+    }
+}
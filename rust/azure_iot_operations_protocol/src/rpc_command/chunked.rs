@@ -0,0 +1,898 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Transfer of request payloads that are too large for a single command request, as a sequence of
+//! chunk requests.
+//!
+//! The invoker splits the payload with [`Invoker::invoke_chunked`] into requests that share a
+//! transfer id ([`CHUNK_TRANSFER_ID_HEADER`]) and are numbered from zero
+//! ([`CHUNK_SEQUENCE_HEADER`]). The final chunk also carries the number of chunks
+//! ([`CHUNK_COUNT_HEADER`]) and the checksum of the whole payload ([`CHUNK_CHECKSUM_HEADER`]).
+//! Each chunk is sent once the previous one has been accepted.
+//!
+//! The application hosting the executor passes every received request to a [`ChunkAssembler`],
+//! which buffers the chunks, answers every chunk but the final one with an accepted response
+//! ([`CHUNK_STATUS_HEADER`]), and returns an [`AssembledRequest`] once all chunks have arrived.
+//! Completing the [`AssembledRequest`] responds to the final chunk, and is the response returned
+//! by [`Invoker::invoke_chunked`]. Chunks redelivered by the broker are answered by the
+//! [`Executor`](crate::rpc_command::Executor)'s cache as usual, and chunks that arrive out of
+//! order are buffered until the missing chunks arrive.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use uuid::Uuid;
+
+use crate::{
+    common::{
+        aio_protocol_error::AIOProtocolError,
+        clock::{self, Instant},
+        hybrid_logical_clock::HybridLogicalClock,
+        payload_checksum::{ChecksumHasher, checksum},
+        payload_serialize::PayloadSerialize,
+    },
+    rpc_command::{
+        Invoker,
+        application_error::{ApplicationError, ApplicationErrorBuilder},
+        executor::{self, RequestParts, Responder, ResponseBuilder},
+        invoker::{RequestBuilder, RequestBuilderError, Response},
+    },
+};
+
+/// User property carrying the id shared by all chunks of a transfer
+pub const CHUNK_TRANSFER_ID_HEADER: &str = "ChunkTransferId";
+/// User property carrying the zero-based sequence number of a chunk within its transfer
+pub const CHUNK_SEQUENCE_HEADER: &str = "ChunkSeq";
+/// User property carrying the number of chunks in the transfer, only set on the final chunk
+pub const CHUNK_COUNT_HEADER: &str = "ChunkCount";
+/// User property carrying the checksum of the whole payload, only set on the final chunk
+pub const CHUNK_CHECKSUM_HEADER: &str = "ChunkChecksum";
+/// User property set to [`CHUNK_STATUS_ACCEPTED`] on the response to every chunk but the final one
+pub const CHUNK_STATUS_HEADER: &str = "ChunkStatus";
+/// Value of the [`CHUNK_STATUS_HEADER`] on the response to an accepted chunk
+pub const CHUNK_STATUS_ACCEPTED: &str = "Accepted";
+/// Code of the [`ApplicationError`] responded to a chunk that can't be assembled
+pub const CHUNK_TRANSFER_FAILED_ERROR_CODE: &str = "ChunkTransferFailed";
+
+const CHUNK_HEADERS: [&str; 4] = [
+    CHUNK_TRANSFER_ID_HEADER,
+    CHUNK_SEQUENCE_HEADER,
+    CHUNK_COUNT_HEADER,
+    CHUNK_CHECKSUM_HEADER,
+];
+
+/// Metadata of a request whose payload is sent in chunks with [`Invoker::invoke_chunked`]
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct ChunkedRequest {
+    /// User data set as custom MQTT User Properties on every chunk request.
+    /// Default is an empty vector.
+    #[builder(default)]
+    custom_user_data: Vec<(String, String)>,
+    /// Topic token keys/values to be replaced into the publish topic of every chunk request.
+    #[builder(default)]
+    topic_tokens: HashMap<String, String>,
+    /// Timeout of each chunk request, must be one second or greater. The timeout of the final
+    /// chunk also covers the handling of the assembled request by the executor.
+    timeout: Duration,
+    /// Id shared by all chunks of the transfer. Default is a new random UUID.
+    #[builder(default = "Uuid::new_v4()")]
+    transfer_id: Uuid,
+}
+
+impl ChunkedRequestBuilder {
+    /// Validate the chunked request.
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if `custom_user_data` contains one of the chunk
+    /// user properties. The remaining fields are validated when the first chunk request is built.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(custom_user_data) = &self.custom_user_data {
+            for (key, _) in custom_user_data {
+                if CHUNK_HEADERS.contains(&key.as_str()) {
+                    return Err(format!(
+                        "Invalid user data property '{key}' is reserved for chunked transfers"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`Invoker::invoke_chunked`]
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Error)]
+pub enum ChunkedTransferError {
+    /// The chunk size is zero
+    #[error("Chunk size must be greater than zero")]
+    InvalidChunkSize,
+    /// Reading the payload failed
+    #[error("Failed to read chunk {sequence} of the payload")]
+    Read {
+        /// Sequence number of the chunk being read
+        sequence: u32,
+        /// Error returned by the reader
+        #[source]
+        source: std::io::Error,
+    },
+    /// A chunk request could not be built from the [`ChunkedRequest`]
+    #[error(transparent)]
+    InvalidRequest(#[from] RequestBuilderError),
+    /// Invoking a chunk request failed. No further chunks are sent.
+    #[error("Invocation of chunk {sequence} failed")]
+    Invoke {
+        /// Sequence number of the chunk
+        sequence: u32,
+        /// Error returned by the invocation
+        #[source]
+        source: AIOProtocolError,
+    },
+    /// The executor responded to an intermediate chunk with an [`ApplicationError`], e.g. with
+    /// code [`CHUNK_TRANSFER_FAILED_ERROR_CODE`] if its [`ChunkAssembler`] could not buffer it
+    #[error("Chunk {sequence} was rejected by the executor with code '{}'", .application_error.code)]
+    Rejected {
+        /// Sequence number of the chunk
+        sequence: u32,
+        /// Error responded by the executor
+        application_error: ApplicationError,
+    },
+    /// The executor responded to an intermediate chunk without accepting it, which indicates that
+    /// it does not assemble chunked transfers
+    #[error("Chunk {sequence} was not accepted by the executor")]
+    NotAccepted {
+        /// Sequence number of the chunk
+        sequence: u32,
+    },
+}
+
+impl<TResp> Invoker<Vec<u8>, TResp>
+where
+    TResp: PayloadSerialize + 'static,
+{
+    /// Invokes a command whose payload is read from `payload` and sent in chunks of `chunk_size`
+    /// bytes, for payloads too large for a single request. See the [module](crate::rpc_command::chunked) documentation
+    /// for the convention, which the executor must follow with a [`ChunkAssembler`].
+    ///
+    /// Chunks are invoked one at a time. Returns the response to the final chunk, which is the
+    /// response of the executor to the assembled request.
+    ///
+    /// # Arguments
+    /// * `request_meta` - [`ChunkedRequest`] applied to every chunk request
+    /// * `payload` - Reader of the payload, read up to its end
+    /// * `chunk_size` - Maximum number of payload bytes in a chunk request
+    ///
+    /// # Errors
+    /// [`ChunkedTransferError::InvalidChunkSize`] if `chunk_size` is zero
+    ///
+    /// [`ChunkedTransferError::Read`] if reading `payload` fails
+    ///
+    /// [`ChunkedTransferError::InvalidRequest`] if the chunk requests can't be built from
+    /// `request_meta`, e.g. because its timeout is invalid
+    ///
+    /// [`ChunkedTransferError::Invoke`] if invoking a chunk fails, including if the executor
+    /// responds to the final chunk with an error. See [`Invoker::invoke`].
+    ///
+    /// [`ChunkedTransferError::Rejected`] or [`ChunkedTransferError::NotAccepted`] if the
+    /// executor doesn't accept an intermediate chunk
+    pub async fn invoke_chunked(
+        &self,
+        request_meta: ChunkedRequest,
+        mut payload: impl AsyncRead + Unpin,
+        chunk_size: usize,
+    ) -> Result<Response<TResp>, ChunkedTransferError> {
+        if chunk_size == 0 {
+            return Err(ChunkedTransferError::InvalidChunkSize);
+        }
+        let transfer_id = request_meta.transfer_id.to_string();
+        let mut hasher = ChecksumHasher::new();
+        let mut sequence: u32 = 0;
+
+        let mut chunk = read_chunk(&mut payload, chunk_size)
+            .await
+            .map_err(|source| ChunkedTransferError::Read { sequence, source })?;
+        loop {
+            // A chunk shorter than the chunk size ends the payload, otherwise read ahead to find
+            // out whether this chunk is the final one
+            let next_chunk = if chunk.len() < chunk_size {
+                None
+            } else {
+                let next_chunk = read_chunk(&mut payload, chunk_size)
+                    .await
+                    .map_err(|source| ChunkedTransferError::Read {
+                        sequence: sequence + 1,
+                        source,
+                    })?;
+                (!next_chunk.is_empty()).then_some(next_chunk)
+            };
+            hasher.update(&chunk);
+
+            let mut custom_user_data = request_meta.custom_user_data.clone();
+            custom_user_data.push((CHUNK_TRANSFER_ID_HEADER.to_string(), transfer_id.clone()));
+            custom_user_data.push((CHUNK_SEQUENCE_HEADER.to_string(), sequence.to_string()));
+            if next_chunk.is_none() {
+                custom_user_data.push((CHUNK_COUNT_HEADER.to_string(), (sequence + 1).to_string()));
+                custom_user_data.push((CHUNK_CHECKSUM_HEADER.to_string(), hasher.finish()));
+            }
+            let request = RequestBuilder::default()
+                .payload(chunk)
+                .map_err(|source| ChunkedTransferError::Invoke { sequence, source })?
+                .custom_user_data(custom_user_data)
+                .topic_tokens(request_meta.topic_tokens.clone())
+                .timeout(request_meta.timeout)
+                .build()?;
+            let response = self
+                .invoke(request)
+                .await
+                .map_err(|source| ChunkedTransferError::Invoke { sequence, source })?;
+
+            let Some(next_chunk) = next_chunk else {
+                return Ok(response);
+            };
+            if let Some(application_error) = response.application_error() {
+                return Err(ChunkedTransferError::Rejected {
+                    sequence,
+                    application_error,
+                });
+            }
+            if !response
+                .custom_user_data
+                .iter()
+                .any(|(key, value)| key == CHUNK_STATUS_HEADER && value == CHUNK_STATUS_ACCEPTED)
+            {
+                return Err(ChunkedTransferError::NotAccepted { sequence });
+            }
+            chunk = next_chunk;
+            sequence += 1;
+        }
+    }
+}
+
+/// Reads up to `chunk_size` bytes, fewer only if the end of `reader` is reached
+async fn read_chunk(
+    reader: &mut (impl AsyncRead + Unpin),
+    chunk_size: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(chunk_size);
+    reader
+        .take(u64::try_from(chunk_size).unwrap_or(u64::MAX))
+        .read_to_end(&mut chunk)
+        .await?;
+    Ok(chunk)
+}
+
+/// Where a [`ChunkAssembler`] buffers chunks until their transfer is complete
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ChunkBuffer {
+    /// Chunks are kept in memory
+    #[default]
+    Memory,
+    /// Chunks are written to files in the given directory, which must exist. The files are
+    /// removed once their transfer is assembled or abandoned.
+    Disk(PathBuf),
+}
+
+/// Chunk Assembler Options struct
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct ChunkAssemblerOptions {
+    /// Where chunks are buffered. Default is [`ChunkBuffer::Memory`].
+    #[builder(default)]
+    buffer: ChunkBuffer,
+    /// Maximum number of payload bytes buffered across all incomplete transfers. A chunk that
+    /// would exceed it is rejected and its transfer abandoned. Default is 64 MiB.
+    #[builder(default = "64 * 1024 * 1024")]
+    max_buffered_bytes: usize,
+    /// Time an incomplete transfer is kept after its most recent chunk was received before it is
+    /// abandoned. Default is 60 seconds.
+    #[builder(default = "Duration::from_secs(60)")]
+    transfer_timeout: Duration,
+}
+
+impl ChunkAssemblerOptionsBuilder {
+    /// Validate the chunk assembler options.
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if `max_buffered_bytes` or `transfer_timeout` is
+    /// zero.
+    fn validate(&self) -> Result<(), String> {
+        if self.max_buffered_bytes == Some(0) {
+            return Err("max_buffered_bytes must be greater than zero".to_string());
+        }
+        if self
+            .transfer_timeout
+            .is_some_and(|timeout| timeout.is_zero())
+        {
+            return Err("transfer_timeout must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Request assembled from all chunks of a transfer by a [`ChunkAssembler`].
+///
+/// Metadata other than the payload is taken from the final chunk, without the chunk user
+/// properties. If dropped, the executor responds to the final chunk as it would to a dropped
+/// [`Request`](executor::Request).
+pub struct AssembledRequest<TResp>
+where
+    TResp: PayloadSerialize,
+{
+    /// Assembled payload of the transfer.
+    pub payload: Vec<u8>,
+    /// Content Type of the final chunk request.
+    pub content_type: Option<String>,
+    /// Custom user data set as custom MQTT User Properties on the final chunk request.
+    pub custom_user_data: Vec<(String, String)>,
+    /// Timestamp of the final chunk request.
+    pub timestamp: Option<HybridLogicalClock>,
+    /// If present, contains the client ID of the invoker of the command.
+    pub invoker_id: Option<String>,
+    /// Resolved static and dynamic topic tokens from the final chunk request's topic.
+    pub topic_tokens: HashMap<String, String>,
+    /// Id of the transfer, or `None` if the request was not sent in chunks.
+    pub transfer_id: Option<Uuid>,
+    /// Number of chunks the payload was sent in.
+    pub chunk_count: u32,
+    responder: Responder<TResp>,
+}
+
+impl<TResp> AssembledRequest<TResp>
+where
+    TResp: PayloadSerialize,
+{
+    /// Consumes the assembled request and responds to its final chunk.
+    ///
+    /// # Errors
+    /// See [`Request::complete`](executor::Request::complete).
+    pub async fn complete(
+        self,
+        response: executor::Response<TResp>,
+    ) -> Result<(), AIOProtocolError> {
+        self.responder.complete(response).await
+    }
+
+    /// Check if the command response is no longer expected.
+    ///
+    /// Returns true if the response is no longer expected, otherwise returns false.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.responder.is_cancelled()
+    }
+}
+
+/// Chunk user properties of a chunk request
+struct ChunkHeaders {
+    transfer_id: Uuid,
+    sequence: u32,
+    /// Number of chunks and checksum of the payload, present on the final chunk
+    last: Option<(u32, String)>,
+}
+
+impl ChunkHeaders {
+    /// Parses the chunk user properties of a request.
+    ///
+    /// Returns `Ok(None)` if the request is not a chunk, or a `String` describing the error if
+    /// the chunk user properties are invalid.
+    fn parse(custom_user_data: &[(String, String)]) -> Result<Option<Self>, String> {
+        let get = |header: &str| {
+            custom_user_data
+                .iter()
+                .find(|(key, _)| key == header)
+                .map(|(_, value)| value.as_str())
+        };
+        let Some(transfer_id) = get(CHUNK_TRANSFER_ID_HEADER) else {
+            return Ok(None);
+        };
+        let transfer_id = Uuid::from_str(transfer_id)
+            .map_err(|_| format!("Invalid transfer id '{transfer_id}'"))?;
+        let sequence =
+            get(CHUNK_SEQUENCE_HEADER).ok_or_else(|| format!("Missing {CHUNK_SEQUENCE_HEADER}"))?;
+        let sequence =
+            u32::from_str(sequence).map_err(|_| format!("Invalid sequence number '{sequence}'"))?;
+        let last = match (get(CHUNK_COUNT_HEADER), get(CHUNK_CHECKSUM_HEADER)) {
+            (None, None) => None,
+            (Some(count), Some(checksum)) => {
+                let count =
+                    u32::from_str(count).map_err(|_| format!("Invalid chunk count '{count}'"))?;
+                if sequence.checked_add(1) != Some(count) {
+                    return Err(format!(
+                        "Chunk count {count} does not follow the final sequence number {sequence}"
+                    ));
+                }
+                Some((count, checksum.to_string()))
+            }
+            _ => {
+                return Err(format!(
+                    "{CHUNK_COUNT_HEADER} and {CHUNK_CHECKSUM_HEADER} must be set together"
+                ));
+            }
+        };
+        Ok(Some(Self {
+            transfer_id,
+            sequence,
+            last,
+        }))
+    }
+}
+
+/// Buffered chunk payload
+enum StoredChunk {
+    Memory(Vec<u8>),
+    Disk(PathBuf),
+}
+
+/// Final chunk of a transfer, held until all other chunks have arrived
+struct FinalChunk<TResp>
+where
+    TResp: PayloadSerialize,
+{
+    parts: RequestParts<Vec<u8>>,
+    responder: Responder<TResp>,
+    chunk_count: u32,
+    checksum: String,
+}
+
+/// Incomplete transfer
+struct Transfer<TResp>
+where
+    TResp: PayloadSerialize,
+{
+    chunks: BTreeMap<u32, StoredChunk>,
+    buffered_bytes: usize,
+    final_chunk: Option<FinalChunk<TResp>>,
+    last_activity: Instant,
+}
+
+/// Reassembles the chunk requests sent with [`Invoker::invoke_chunked`] into a single
+/// [`AssembledRequest`].
+///
+/// The application passes every request received from the
+/// [`Executor`](crate::rpc_command::Executor) to [`process`](Self::process), which responds to
+/// intermediate chunks itself. Requests that are not chunks are returned as an
+/// [`AssembledRequest`] of one chunk right away, so the same handler serves both.
+///
+/// A chunk that can't be assembled is responded to with an [`ApplicationError`] with code
+/// [`CHUNK_TRANSFER_FAILED_ERROR_CODE`], so the response payload type must implement [`Default`]
+/// for the error and accepted responses.
+pub struct ChunkAssembler<TResp>
+where
+    TResp: PayloadSerialize + Default + Send + 'static,
+{
+    options: ChunkAssemblerOptions,
+    transfers: HashMap<Uuid, Transfer<TResp>>,
+    buffered_bytes: usize,
+}
+
+impl<TResp> ChunkAssembler<TResp>
+where
+    TResp: PayloadSerialize + Default + Send + 'static,
+{
+    /// Creates a new [`ChunkAssembler`]
+    #[must_use]
+    pub fn new(options: ChunkAssemblerOptions) -> Self {
+        Self {
+            options,
+            transfers: HashMap::new(),
+            buffered_bytes: 0,
+        }
+    }
+
+    /// Returns the number of transfers that have not received all of their chunks yet
+    #[must_use]
+    pub fn incomplete_transfers(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Returns the number of payload bytes buffered across all incomplete transfers
+    #[must_use]
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    /// Processes a request received from the [`Executor`](crate::rpc_command::Executor).
+    ///
+    /// Returns the [`AssembledRequest`] if the request is not a chunk, or if it is the last chunk
+    /// of its transfer to arrive. Otherwise the chunk is buffered and `None` is returned, after
+    /// an accepted response is sent for intermediate chunks or an error response for chunks that
+    /// can't be assembled. Expired transfers are abandoned first, see
+    /// [`expire_incomplete`](Self::expire_incomplete).
+    pub async fn process(
+        &mut self,
+        request: executor::Request<Vec<u8>, TResp>,
+    ) -> Option<AssembledRequest<TResp>> {
+        self.expire_incomplete().await;
+
+        let (mut parts, responder) = request.into_parts();
+        let headers = match ChunkHeaders::parse(&parts.custom_user_data) {
+            Ok(Some(headers)) => headers,
+            Ok(None) => {
+                return Some(AssembledRequest {
+                    payload: parts.payload,
+                    content_type: parts.content_type,
+                    custom_user_data: parts.custom_user_data,
+                    timestamp: parts.timestamp,
+                    invoker_id: parts.invoker_id,
+                    topic_tokens: parts.topic_tokens,
+                    transfer_id: None,
+                    chunk_count: 1,
+                    responder,
+                });
+            }
+            Err(reason) => {
+                log::warn!("Rejecting invalid chunk: {reason}");
+                respond_rejected(responder, reason);
+                return None;
+            }
+        };
+        let transfer_id = headers.transfer_id;
+        let sequence = headers.sequence;
+        let chunk = std::mem::take(&mut parts.payload);
+
+        let transfer = self
+            .transfers
+            .entry(transfer_id)
+            .or_insert_with(|| Transfer {
+                chunks: BTreeMap::new(),
+                buffered_bytes: 0,
+                final_chunk: None,
+                last_activity: clock::now(),
+            });
+        transfer.last_activity = clock::now();
+
+        // A chunk invoked again, e.g. after its response was lost. Chunks redelivered by the
+        // broker are answered by the executor's cache and never get here.
+        if transfer.chunks.contains_key(&sequence) {
+            log::debug!("Duplicate chunk {sequence} of transfer {transfer_id}");
+            match (headers.last, &mut transfer.final_chunk) {
+                (Some((chunk_count, checksum)), Some(final_chunk)) => {
+                    // Respond to the latest invocation of the final chunk, the previous one has
+                    // timed out on the invoker
+                    *final_chunk = FinalChunk {
+                        parts,
+                        responder,
+                        chunk_count,
+                        checksum,
+                    };
+                }
+                _ => respond_accepted(responder),
+            }
+            return None;
+        }
+
+        let expected_count = headers
+            .last
+            .as_ref()
+            .map(|(chunk_count, _)| *chunk_count)
+            .or(transfer.final_chunk.as_ref().map(|f| f.chunk_count));
+        let beyond_final = expected_count.is_some_and(|chunk_count| {
+            sequence >= chunk_count
+                || transfer
+                    .chunks
+                    .keys()
+                    .next_back()
+                    .is_some_and(|highest| *highest >= chunk_count)
+        });
+        if beyond_final {
+            let reason =
+                format!("Chunk {sequence} of transfer {transfer_id} is beyond the final chunk");
+            log::warn!("{reason}");
+            self.abandon(transfer_id, &reason).await;
+            respond_rejected(responder, reason);
+            return None;
+        }
+
+        if self.buffered_bytes + chunk.len() > self.options.max_buffered_bytes {
+            let reason = format!(
+                "Chunk {sequence} of transfer {transfer_id} exceeds the maximum buffered bytes"
+            );
+            log::warn!("{reason}");
+            self.abandon(transfer_id, &reason).await;
+            respond_rejected(responder, reason);
+            return None;
+        }
+
+        let chunk_len = chunk.len();
+        let stored_chunk = match &self.options.buffer {
+            ChunkBuffer::Memory => StoredChunk::Memory(chunk),
+            ChunkBuffer::Disk(directory) => {
+                let path = directory.join(format!("{transfer_id}.{sequence}.chunk"));
+                if let Err(e) = tokio::fs::write(&path, &chunk).await {
+                    let reason =
+                        format!("Failed to buffer chunk {sequence} of transfer {transfer_id}");
+                    log::warn!("{reason}: {e}");
+                    self.abandon(transfer_id, &reason).await;
+                    respond_rejected(responder, reason);
+                    return None;
+                }
+                StoredChunk::Disk(path)
+            }
+        };
+        self.buffered_bytes += chunk_len;
+        // The transfer can't have been abandoned while the chunk was buffered
+        let transfer = self.transfers.get_mut(&transfer_id)?;
+        transfer.buffered_bytes += chunk_len;
+        transfer.chunks.insert(sequence, stored_chunk);
+
+        match headers.last {
+            Some((chunk_count, checksum)) => {
+                transfer.final_chunk = Some(FinalChunk {
+                    parts,
+                    responder,
+                    chunk_count,
+                    checksum,
+                });
+            }
+            None => respond_accepted(responder),
+        }
+
+        let is_complete = transfer.final_chunk.as_ref().is_some_and(|final_chunk| {
+            usize::try_from(final_chunk.chunk_count).ok() == Some(transfer.chunks.len())
+        });
+        if is_complete {
+            self.assemble(transfer_id).await
+        } else {
+            None
+        }
+    }
+
+    /// Abandons the transfers that have not received a chunk within the
+    /// [`transfer_timeout`](ChunkAssemblerOptionsBuilder::transfer_timeout), responding to their
+    /// final chunk with an error if it has arrived. Called by [`process`](Self::process), and
+    /// should also be called periodically if requests stop arriving.
+    ///
+    /// Returns the number of transfers abandoned.
+    pub async fn expire_incomplete(&mut self) -> usize {
+        let now = clock::now();
+        let expired: Vec<Uuid> = self
+            .transfers
+            .iter()
+            .filter(|(_, transfer)| {
+                now.duration_since(transfer.last_activity) >= self.options.transfer_timeout
+            })
+            .map(|(transfer_id, _)| *transfer_id)
+            .collect();
+        for transfer_id in &expired {
+            log::warn!("Transfer {transfer_id} expired before all chunks were received");
+            self.abandon(*transfer_id, &format!("Transfer {transfer_id} expired"))
+                .await;
+        }
+        expired.len()
+    }
+
+    /// Removes a transfer and its buffered chunks, responding to its final chunk with an error if
+    /// it has arrived
+    async fn abandon(&mut self, transfer_id: Uuid, reason: &str) {
+        let Some(transfer) = self.transfers.remove(&transfer_id) else {
+            return;
+        };
+        self.buffered_bytes -= transfer.buffered_bytes;
+        for stored_chunk in transfer.chunks.into_values() {
+            if let StoredChunk::Disk(path) = stored_chunk {
+                remove_chunk_file(&path).await;
+            }
+        }
+        if let Some(final_chunk) = transfer.final_chunk {
+            respond_rejected(final_chunk.responder, reason.to_string());
+        }
+    }
+
+    /// Removes a complete transfer and assembles its chunks into an [`AssembledRequest`]
+    async fn assemble(&mut self, transfer_id: Uuid) -> Option<AssembledRequest<TResp>> {
+        let transfer = self.transfers.remove(&transfer_id)?;
+        self.buffered_bytes -= transfer.buffered_bytes;
+        let final_chunk = transfer.final_chunk?;
+
+        let mut payload = Vec::with_capacity(transfer.buffered_bytes);
+        let mut read_error = None;
+        for (sequence, stored_chunk) in transfer.chunks {
+            match stored_chunk {
+                StoredChunk::Memory(chunk) => payload.extend_from_slice(&chunk),
+                StoredChunk::Disk(path) => {
+                    if read_error.is_none() {
+                        match tokio::fs::read(&path).await {
+                            Ok(chunk) => payload.extend_from_slice(&chunk),
+                            Err(e) => read_error = Some((sequence, e)),
+                        }
+                    }
+                    remove_chunk_file(&path).await;
+                }
+            }
+        }
+        if let Some((sequence, e)) = read_error {
+            let reason =
+                format!("Failed to read buffered chunk {sequence} of transfer {transfer_id}");
+            log::warn!("{reason}: {e}");
+            respond_rejected(final_chunk.responder, reason);
+            return None;
+        }
+
+        let computed = checksum(&payload);
+        if computed != final_chunk.checksum {
+            let reason = format!(
+                "Checksum '{computed}' of transfer {transfer_id} does not match the expected checksum '{}'",
+                final_chunk.checksum
+            );
+            log::warn!("{reason}");
+            respond_rejected(final_chunk.responder, reason);
+            return None;
+        }
+
+        let mut parts = final_chunk.parts;
+        parts
+            .custom_user_data
+            .retain(|(key, _)| !CHUNK_HEADERS.contains(&key.as_str()));
+        Some(AssembledRequest {
+            payload,
+            content_type: parts.content_type,
+            custom_user_data: parts.custom_user_data,
+            timestamp: parts.timestamp,
+            invoker_id: parts.invoker_id,
+            topic_tokens: parts.topic_tokens,
+            transfer_id: Some(transfer_id),
+            chunk_count: final_chunk.chunk_count,
+            responder: final_chunk.responder,
+        })
+    }
+}
+
+async fn remove_chunk_file(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        log::warn!(
+            "Failed to remove buffered chunk file {}: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Responds to an intermediate chunk with an accepted response, without waiting for the response
+/// to be published
+fn respond_accepted<TResp>(responder: Responder<TResp>)
+where
+    TResp: PayloadSerialize + Default + Send + 'static,
+{
+    respond(
+        responder,
+        vec![(
+            CHUNK_STATUS_HEADER.to_string(),
+            CHUNK_STATUS_ACCEPTED.to_string(),
+        )],
+    );
+}
+
+/// Responds to a chunk that can't be assembled with an [`ApplicationError`], without waiting for
+/// the response to be published
+fn respond_rejected<TResp>(responder: Responder<TResp>, reason: String)
+where
+    TResp: PayloadSerialize + Default + Send + 'static,
+{
+    let custom_user_data = ApplicationErrorBuilder::default()
+        .code(CHUNK_TRANSFER_FAILED_ERROR_CODE)
+        .raw_detail(reason)
+        .build()
+        .map(ApplicationError::into_headers)
+        .unwrap_or_default();
+    respond(responder, custom_user_data);
+}
+
+fn respond<TResp>(responder: Responder<TResp>, custom_user_data: Vec<(String, String)>)
+where
+    TResp: PayloadSerialize + Default + Send + 'static,
+{
+    let response = match ResponseBuilder::default().payload(TResp::default()) {
+        Ok(builder) => builder.custom_user_data(custom_user_data).build(),
+        Err(e) => {
+            // Dropping the responder sends the executor's dropped request response instead
+            log::warn!("Failed to serialize chunk response payload: {e}");
+            return;
+        }
+    };
+    let Ok(response) = response else {
+        return;
+    };
+    tokio::task::spawn(async move {
+        if let Err(e) = responder.complete(response).await {
+            log::warn!("Failed to respond to chunk: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    const TRANSFER_ID: &str = "6a3c7f1e-8d52-4b0a-9f6e-2c1d3b4a5e6f";
+
+    #[test]
+    fn parse_not_a_chunk() {
+        assert!(
+            ChunkHeaders::parse(&headers(&[("other", "value")]))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parse_intermediate_and_final_chunk() {
+        let intermediate = ChunkHeaders::parse(&headers(&[
+            (CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID),
+            (CHUNK_SEQUENCE_HEADER, "3"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(intermediate.transfer_id.to_string(), TRANSFER_ID);
+        assert_eq!(intermediate.sequence, 3);
+        assert!(intermediate.last.is_none());
+
+        let last = ChunkHeaders::parse(&headers(&[
+            (CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID),
+            (CHUNK_SEQUENCE_HEADER, "3"),
+            (CHUNK_COUNT_HEADER, "4"),
+            (CHUNK_CHECKSUM_HEADER, "e3069283"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(last.last, Some((4, "e3069283".to_string())));
+    }
+
+    #[test_case(&[(CHUNK_TRANSFER_ID_HEADER, "not-a-uuid"), (CHUNK_SEQUENCE_HEADER, "0")]; "invalid transfer id")]
+    #[test_case(&[(CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID)]; "missing sequence")]
+    #[test_case(&[(CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID), (CHUNK_SEQUENCE_HEADER, "-1")]; "invalid sequence")]
+    #[test_case(&[(CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID), (CHUNK_SEQUENCE_HEADER, "0"), (CHUNK_COUNT_HEADER, "1")]; "count without checksum")]
+    #[test_case(&[(CHUNK_TRANSFER_ID_HEADER, TRANSFER_ID), (CHUNK_SEQUENCE_HEADER, "0"), (CHUNK_COUNT_HEADER, "2"), (CHUNK_CHECKSUM_HEADER, "00000000")]; "count not following sequence")]
+    fn parse_invalid_chunk(pairs: &[(&str, &str)]) {
+        assert!(ChunkHeaders::parse(&headers(pairs)).is_err());
+    }
+
+    #[test]
+    fn chunked_request_rejects_chunk_headers() {
+        assert!(
+            ChunkedRequestBuilder::default()
+                .custom_user_data(headers(&[(CHUNK_SEQUENCE_HEADER, "0")]))
+                .timeout(Duration::from_secs(1))
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn chunk_assembler_options_validation() {
+        assert!(
+            ChunkAssemblerOptionsBuilder::default()
+                .max_buffered_bytes(0usize)
+                .build()
+                .is_err()
+        );
+        assert!(
+            ChunkAssemblerOptionsBuilder::default()
+                .transfer_timeout(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+        assert!(ChunkAssemblerOptionsBuilder::default().build().is_ok());
+    }
+
+    #[tokio::test]
+    async fn read_chunk_stops_at_end() {
+        let mut reader: &[u8] = b"0123456789";
+        assert_eq!(read_chunk(&mut reader, 4).await.unwrap(), b"0123");
+        assert_eq!(read_chunk(&mut reader, 4).await.unwrap(), b"4567");
+        assert_eq!(read_chunk(&mut reader, 4).await.unwrap(), b"89");
+        assert!(read_chunk(&mut reader, 4).await.unwrap().is_empty());
+    }
+}
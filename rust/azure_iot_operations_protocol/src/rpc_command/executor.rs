@@ -1,13 +1,20 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
+use std::fmt::Write;
+use std::num::NonZeroU16;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, marker::PhantomData, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::Entry},
+    marker::PhantomData,
+    time::Duration,
+};
 
 use azure_iot_operations_mqtt::{
     aio::cloud_event as aio_cloud_event,
-    session::{SessionManagedClient, SessionPubReceiver},
+    session::{SessionManagedClient, SessionMonitor, SessionPubReceiver},
 };
 use azure_iot_operations_mqtt::{
     control_packet::{PublishProperties, QoS, TopicFilter, TopicName},
@@ -15,20 +22,25 @@ use azure_iot_operations_mqtt::{
 };
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use tokio::sync::oneshot;
-use tokio::time::{Instant, timeout};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
 use tokio_util::sync::{CancellationToken, DropGuard};
 
 use crate::{
     ProtocolVersion,
-    application::{ApplicationContext, ApplicationHybridLogicalClock},
+    application::{
+        ApplicationContext, ApplicationHybridLogicalClock, validate_cache_expiry_buffer,
+        validate_default_message_expiry,
+    },
     common::{
-        aio_protocol_error::{AIOProtocolError, Value},
+        aio_protocol_error::{AIOProtocolError, AIOProtocolErrorKind, Value},
+        clock::{self, Instant},
         cloud_event as protocol_cloud_event,
         hybrid_logical_clock::{HLCErrorKind, HybridLogicalClock},
-        is_invalid_utf8,
+        is_invalid_utf8, payload_checksum,
         payload_serialize::{
             DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+            format_indicator_mismatch,
         },
         topic_processor::{TopicPattern, contains_invalid_char, is_valid_replacement},
         user_properties::{
@@ -38,15 +50,13 @@ use crate::{
     rpc_command::{
         DEFAULT_RPC_COMMAND_PROTOCOL_VERSION, DEFAULT_RPC_RESPONSE_CLOUD_EVENT_EVENT_TYPE,
         RPC_COMMAND_PROTOCOL_VERSION, StatusCode,
+        application_error::{ApplicationError, ApplicationErrorBuilder},
     },
     supported_protocol_major_versions_to_string,
 };
 
-/// Default message expiry interval only for when the message expiry interval is not present
-const DEFAULT_MESSAGE_EXPIRY_INTERVAL_SECONDS: u32 = 10;
-
-/// Additional time in seconds to extend cache entry expiration beyond the command expiration time
-const CACHE_EXPIRY_BUFFER_SECONDS: u64 = 60;
+/// Prefix of the keys used to claim command requests in a [`DistributedDedupStore`]
+const DISTRIBUTED_DEDUP_KEY_PREFIX: &str = "aio-executor-dedup";
 
 /// Message for when expiration time is unable to be calculated, internal logic error
 const INTERNAL_LOGIC_EXPIRATION_ERROR: &str =
@@ -70,12 +80,29 @@ struct ResponseArguments {
     request_protocol_version: Option<String>,
     cached_key: Option<CacheKey>,
     cache_lookup_result: CacheLookupResult,
+    /// The invoker does not expect a response, so none is published
+    no_response: bool,
+    /// Content type of the response if it is an error response
+    error_response_content_type: String,
+    /// Response sent if the application drops the request without completing it
+    dropped_request_response: DroppedRequestResponse,
+    /// Message expiry interval of the response if the command expiration time could not be
+    /// calculated
+    default_message_expiry_interval: u32,
+    /// Additional time to keep the response cached after the command expires
+    cache_expiry_buffer: Duration,
+    /// Whether to attach a checksum of the serialized payload to the response
+    payload_checksum: bool,
+    /// Monitor of the session responses are published on, if checked before publishing
+    response_client_monitor: Option<SessionMonitor>,
 }
 
 /// Command Executor Request struct.
 /// Used by the [`Executor`]
 ///
-/// If dropped, executor will send an error response to the invoker
+/// If dropped, executor will respond to the invoker as configured by
+/// [`dropped_request_response`](OptionsBuilder::dropped_request_response), by default with an
+/// error response
 pub struct Request<TReq, TResp>
 where
     TReq: PayloadSerialize,
@@ -95,6 +122,23 @@ where
     pub invoker_id: Option<String>,
     /// Resolved static and dynamic topic tokens from the incoming request's topic.
     pub topic_tokens: HashMap<String, String>,
+    /// Whether the invoker does not expect a response (fire-and-forget). The request must still be
+    /// completed, but no response is published to the invoker.
+    pub no_response: bool,
+    /// Whether the request was redelivered by the broker (DUP flag in MQTT publish), e.g. after a
+    /// reconnect. Redelivered requests that have already been completed are answered from the
+    /// [`Executor`]'s cache and never delivered, so a redelivered request may have been
+    /// partially processed before the connection was lost.
+    pub duplicate: bool,
+    /// Response topic set on the request message, if any. Responses are published to it by the
+    /// [`Executor`].
+    pub response_topic: Option<String>,
+    /// Correlation data set on the request message, if any.
+    pub correlation_data: Option<Bytes>,
+    /// Identifiers of the subscriptions the request message was delivered for.
+    pub subscription_identifiers: Vec<u32>,
+    /// Topic alias used by the broker to deliver the request message, if any.
+    pub topic_alias: Option<u16>,
     // Internal handle used to respond to the invoker. Kept private so that all response logic
     // lives on `Responder` and `Request` simply delegates to it.
     responder: Responder<TResp>,
@@ -126,6 +170,15 @@ where
     ///
     /// [`AIOProtocolError`] of kind [`InternalLogicError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::InternalLogicError)
     /// if the response publish completion fails. This should not happen.
+    ///
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the response's QoS is `AtMostOnce` and the [`Executor`] is not idempotent. The request is
+    /// dropped, so an error response is sent to the invoker.
+    ///
+    /// [`AIOProtocolError`] of kind [`StateInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::StateInvalid)
+    /// if the [`response_client_monitor`](OptionsBuilder::response_client_monitor) is set and the
+    /// response client is disconnected. The response is cached, so it is published if the request
+    /// is redelivered once the response client reconnects.
     pub async fn complete(self, response: Response<TResp>) -> Result<(), AIOProtocolError> {
         self.responder.complete(response).await
     }
@@ -158,6 +211,12 @@ where
             timestamp,
             invoker_id,
             topic_tokens,
+            no_response,
+            duplicate,
+            response_topic,
+            correlation_data,
+            subscription_identifiers,
+            topic_alias,
             responder,
         } = self;
 
@@ -170,11 +229,42 @@ where
                 timestamp,
                 invoker_id,
                 topic_tokens,
+                no_response,
+                duplicate,
+                response_topic,
+                correlation_data,
+                subscription_identifiers,
+                topic_alias,
             },
             responder,
         )
     }
 
+    /// Holds the command request, so that it can be completed later from any task, for
+    /// long-poll style commands whose response is only sent once something happens (e.g. "wait
+    /// for the next event").
+    ///
+    /// Unlike [`Request::complete`], completing the returned [`HeldRequest`] after the request has
+    /// expired is not an error, and dropping it once expired does not send an error response. See
+    /// [`HeldRequest`] for details.
+    #[must_use]
+    pub fn hold(self) -> HeldRequest<TReq, TResp> {
+        let (request, responder) = self.into_parts();
+        responder
+            .counters
+            .held_requests
+            .fetch_add(1, Ordering::Relaxed);
+        HeldRequest {
+            request,
+            held: HeldGuard {
+                counters: responder.counters.clone(),
+                expiration_time: responder.expiration_time,
+                completed: false,
+            },
+            responder,
+        }
+    }
+
     /// Check if the command response is no longer expected.
     ///
     /// Returns true if the response is no longer expected, otherwise returns false.
@@ -182,6 +272,23 @@ where
     pub fn is_cancelled(&self) -> bool {
         self.responder.is_cancelled()
     }
+
+    /// Waits until the command response is no longer expected, so that the handler can abort
+    /// work whose result would be discarded.
+    ///
+    /// See [`Responder::cancelled`].
+    pub async fn cancelled(&self) {
+        self.responder.cancelled().await;
+    }
+
+    /// Returns a [`CancellationToken`] that is cancelled once the command response is no longer
+    /// expected, for passing to work that doesn't borrow the [`Request`].
+    ///
+    /// See [`Responder::cancellation_token`].
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.responder.cancellation_token()
+    }
 }
 
 /// Owned data extracted from a [`Request`] via [`Request::into_parts`].
@@ -204,20 +311,39 @@ pub struct RequestParts<TReq> {
     pub invoker_id: Option<String>,
     /// Resolved static and dynamic topic tokens from the incoming request's topic.
     pub topic_tokens: HashMap<String, String>,
+    /// Whether the invoker does not expect a response (fire-and-forget).
+    pub no_response: bool,
+    /// Whether the request was redelivered by the broker (DUP flag in MQTT publish).
+    pub duplicate: bool,
+    /// Response topic set on the request message, if any.
+    pub response_topic: Option<String>,
+    /// Correlation data set on the request message, if any.
+    pub correlation_data: Option<Bytes>,
+    /// Identifiers of the subscriptions the request message was delivered for.
+    pub subscription_identifiers: Vec<u32>,
+    /// Topic alias used by the broker to deliver the request message, if any.
+    pub topic_alias: Option<u16>,
 }
 
 /// Handle used to respond to a [`Request`] after its data has been extracted via
 /// [`Request::into_parts`].
 ///
-/// If dropped without calling [`Responder::complete`], the executor will send an error response
-/// to the invoker (identical to dropping the [`Request`]).
+/// If dropped without calling [`Responder::complete`], the executor will respond to the invoker
+/// identically to dropping the [`Request`].
 pub struct Responder<TResp>
 where
     TResp: PayloadSerialize,
 {
     command_name: String,
+    is_idempotent: bool,
     response_tx: oneshot::Sender<Response<TResp>>,
     publish_completion_rx: oneshot::Receiver<Result<(), AIOProtocolError>>,
+    // Cancelled by the executor once it stops processing the request
+    cancellation_token: CancellationToken,
+    // Time at which the command request expires
+    expiration_time: Instant,
+    // Statistics of the executor, updated for held requests
+    counters: Arc<ExecutorCounters>,
 }
 
 impl<TResp> Responder<TResp>
@@ -245,7 +371,31 @@ where
     ///
     /// [`AIOProtocolError`] of kind [`InternalLogicError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::InternalLogicError)
     /// if the response publish completion fails. This should not happen.
+    ///
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the response's QoS is `AtMostOnce` and the [`Executor`] is not idempotent. The request is
+    /// dropped, so an error response is sent to the invoker.
+    ///
+    /// [`AIOProtocolError`] of kind [`StateInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::StateInvalid)
+    /// if the [`response_client_monitor`](OptionsBuilder::response_client_monitor) is set and the
+    /// response client is disconnected. The response is cached, so it is published if the request
+    /// is redelivered once the response client reconnects.
     pub async fn complete(self, response: Response<TResp>) -> Result<(), AIOProtocolError> {
+        // Duplicate requests to a non-idempotent command rely on the cached response reaching the
+        // invoker, so it can't be sent at most once.
+        if response.qos == QoS::AtMostOnce && !self.is_idempotent {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "qos",
+                Value::String(format!("{:?}", response.qos)),
+                Some(
+                    "Response QoS can only be AtMostOnce if the Command Executor is idempotent"
+                        .to_string(),
+                ),
+                Some(self.command_name),
+            ));
+        }
+
         // We can ignore the error here. If the receiver of the response is dropped it may be
         // because the executor is shutting down in which case the receive below will fail.
         // If the executor is not shutting down, the receive below will succeed and we'll receive a
@@ -274,7 +424,165 @@ where
     /// Returns true if the response is no longer expected, otherwise returns false.
     #[must_use]
     pub fn is_cancelled(&self) -> bool {
-        self.response_tx.is_closed()
+        self.response_tx.is_closed() || self.cancellation_token.is_cancelled()
+    }
+
+    /// Waits until the command response is no longer expected.
+    ///
+    /// This happens when the command request expires, in which case the executor does not publish
+    /// a response, or when the [`Executor`] is shut down. Handlers doing expensive work can select
+    /// on this to abort early, e.g.
+    /// ```ignore
+    /// tokio::select! {
+    ///     () = responder.cancelled() => { /* the result would be discarded */ },
+    ///     result = expensive_work() => { responder.complete(result.into()).await?; },
+    /// }
+    /// ```
+    pub async fn cancelled(&self) {
+        self.cancellation_token.cancelled().await;
+    }
+
+    /// Returns a [`CancellationToken`] that is cancelled once the command response is no longer
+    /// expected, for passing to spawned work that doesn't borrow the [`Responder`].
+    ///
+    /// Cancelling the returned token has no effect on the command request.
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.child_token()
+    }
+}
+
+/// A [`Request`] held by the application via [`Request::hold`], to be completed later from any
+/// task, e.g. once the event a long-poll style command waits for happens.
+///
+/// The [`Executor`] keeps the request in progress while it is held, so duplicates of the request
+/// are not delivered to the application again, and requests serialized after it (see
+/// [`serialize_by_token`](OptionsBuilder::serialize_by_token)) wait for it.
+///
+/// If the request expires before it is completed, the invoker receives no response, as for any
+/// other expired request, and [`HeldRequest::complete`] returns [`HeldRequestOutcome::Expired`].
+/// Use [`HeldRequest::expired`] to stop waiting for the event once the request has expired.
+/// Dropping a [`HeldRequest`] before it expires responds to the invoker as configured by
+/// [`dropped_request_response`](OptionsBuilder::dropped_request_response), like dropping a
+/// [`Request`].
+///
+/// # Example
+/// ```ignore
+/// let held = request.hold();
+/// tokio::select! {
+///     () = held.expired() => { /* the invoker has given up, nothing to send */ },
+///     event = next_event() => { held.complete(event.into()).await?; },
+/// }
+/// ```
+pub struct HeldRequest<TReq, TResp>
+where
+    TResp: PayloadSerialize,
+{
+    /// Data of the held command request.
+    pub request: RequestParts<TReq>,
+    responder: Responder<TResp>,
+    held: HeldGuard,
+}
+
+/// Outcome of completing a [`HeldRequest`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeldRequestOutcome {
+    /// The response was sent to the invoker
+    Completed,
+    /// The request expired before it was completed, so no response was sent
+    Expired,
+}
+
+/// Counts a [`HeldRequest`] as no longer held once dropped or completed, and as expired if it
+/// expired before it was completed.
+struct HeldGuard {
+    counters: Arc<ExecutorCounters>,
+    expiration_time: Instant,
+    completed: bool,
+}
+
+impl Drop for HeldGuard {
+    fn drop(&mut self) {
+        self.counters.held_requests.fetch_sub(1, Ordering::Relaxed);
+        if !self.completed && clock::now() >= self.expiration_time {
+            self.counters
+                .held_requests_expired
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<TReq, TResp> HeldRequest<TReq, TResp>
+where
+    TResp: PayloadSerialize,
+{
+    /// Consumes the held request and reports the response to the executor, unless the request has
+    /// already expired. An attempt is made to send the response to the invoker.
+    ///
+    /// Returns [`HeldRequestOutcome::Expired`] if the request expired before the response could
+    /// be sent, in which case no response is sent to the invoker.
+    ///
+    /// # Errors
+    /// Returns an [`AIOProtocolError`] in the same cases as [`Responder::complete`], except on
+    /// expiry.
+    pub async fn complete(
+        self,
+        response: Response<TResp>,
+    ) -> Result<HeldRequestOutcome, AIOProtocolError> {
+        if self.is_expired() {
+            // Dropping the responder once expired doesn't send a response
+            return Ok(HeldRequestOutcome::Expired);
+        }
+        let mut held = self.held;
+        match self.responder.complete(response).await {
+            Ok(()) => {
+                held.completed = true;
+                Ok(HeldRequestOutcome::Completed)
+            }
+            Err(e) if e.kind == AIOProtocolErrorKind::Timeout => Ok(HeldRequestOutcome::Expired),
+            Err(e) => {
+                // Not completed, but counted as expired only if it was due to expiry
+                held.completed = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns true if the held request has expired.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        clock::now() >= self.responder.expiration_time
+    }
+
+    /// Returns the time remaining until the held request expires, or zero if it has expired.
+    #[must_use]
+    pub fn time_remaining(&self) -> Duration {
+        self.responder
+            .expiration_time
+            .saturating_duration_since(clock::now())
+    }
+
+    /// Returns a future that completes once the held request expires. It does not borrow the
+    /// [`HeldRequest`], so it can be awaited from another task.
+    pub fn expired(&self) -> impl Future<Output = ()> + Send + 'static {
+        tokio::time::sleep_until(self.responder.expiration_time)
+    }
+
+    /// Waits until the command response is no longer expected, either because the held request
+    /// expired or the [`Executor`] was shut down.
+    ///
+    /// See [`Responder::cancelled`].
+    pub async fn cancelled(&self) {
+        self.responder.cancelled().await;
+    }
+
+    /// Returns a [`CancellationToken`] that is cancelled once the command response is no longer
+    /// expected.
+    ///
+    /// See [`Responder::cancellation_token`].
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.responder.cancellation_token()
     }
 }
 
@@ -334,6 +642,18 @@ where
     /// Cloud event of the response.
     #[builder(default = "None")]
     cloud_event: Option<ResponseCloudEvent>,
+    /// Quality of Service of the response message. Can only be `AtMostOnce` or `AtLeastOnce`.
+    /// Default is `AtLeastOnce`.
+    ///
+    /// `AtMostOnce` can only be used if the [`Executor`] is idempotent, since a lost response to a
+    /// non-idempotent command can only be recovered from the cached response.
+    /// Cached responses sent to duplicate requests use the same QoS.
+    #[builder(default = "QoS::AtLeastOnce")]
+    qos: QoS,
+    /// Message expiry of the response message, if shorter than the remaining time until the
+    /// command expires. Default is the remaining time until the command expires.
+    #[builder(default = "None", setter(custom))]
+    message_expiry_override: Option<Duration>,
 }
 
 /// Cloud Event struct used for the Command Response.
@@ -455,6 +775,29 @@ impl ResponseCloudEventBuilder {
         self.0.subject(value);
         self
     }
+    /// Extension attributes, keyed by attribute name. Names must only contain lowercase letters
+    /// and digits (e.g. `partitionkey` or `dataref`) and must not collide with the names of core
+    /// attributes. Replaces any extension attributes already added.
+    pub fn extensions<VALUE: Into<BTreeMap<String, String>>>(&mut self, value: VALUE) -> &mut Self {
+        self.0.extensions(value);
+        self
+    }
+    /// Add a single extension attribute, replacing any existing value for `name`. See
+    /// [`extensions`](Self::extensions) for the naming rules.
+    pub fn extension<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.0.extension(name, value);
+        self
+    }
+    /// Derive attributes from the source events that an aggregate event was produced from, such
+    /// as the earliest `time` and the combined `source`. Attributes that have already been set are
+    /// not derived, and the source must be set explicitly if the source events don't share one.
+    pub fn derive_from_sources<'a, I>(&mut self, sources: I) -> &mut Self
+    where
+        I: IntoIterator<Item = &'a aio_cloud_event::CloudEvent>,
+    {
+        self.0.derive_from_sources(sources);
+        self
+    }
 }
 
 impl<TResp: PayloadSerialize> ResponseBuilder<TResp> {
@@ -487,6 +830,16 @@ impl<TResp: PayloadSerialize> ResponseBuilder<TResp> {
                         None,
                     ));
                 }
+                // Validate format indicator is consistent with the content type
+                if let Some(mismatch) = format_indicator_mismatch(&serialized_payload) {
+                    return Err(AIOProtocolError::new_configuration_invalid_error(
+                        None,
+                        "format_indicator",
+                        Value::String(format!("{:?}", serialized_payload.format_indicator)),
+                        Some(mismatch),
+                        None,
+                    ));
+                }
                 self.serialized_payload = Some(serialized_payload);
                 self.payload_type = Some(PhantomData);
                 Ok(self)
@@ -494,11 +847,25 @@ impl<TResp: PayloadSerialize> ResponseBuilder<TResp> {
         }
     }
 
+    /// Override the message expiry of the response. Values beyond the remaining time until the
+    /// command expires are clamped to it.
+    ///
+    /// Note: Will be rounded up to the nearest second.
+    pub fn message_expiry_override(&mut self, message_expiry: Duration) -> &mut Self {
+        self.message_expiry_override = Some(Some(if message_expiry.subsec_nanos() != 0 {
+            Duration::from_secs(message_expiry.as_secs().saturating_add(1))
+        } else {
+            message_expiry
+        }));
+        self
+    }
+
     /// Validate the command response.
     ///
     /// # Errors
-    /// Returns a `String` describing the error if any of `custom_user_data`'s keys or values are invalid utf-8
-    /// or a reserved Cloud Event key is used.
+    /// Returns a `String` describing the error if any of `custom_user_data`'s keys or values are invalid utf-8,
+    /// a reserved Cloud Event key is used, the QoS is not `AtMostOnce` or `AtLeastOnce`, or the
+    /// message expiry override is zero.
     fn validate(&self) -> Result<(), String> {
         if let Some(custom_user_data) = &self.custom_user_data {
             for (key, _) in custom_user_data {
@@ -522,6 +889,17 @@ impl<TResp: PayloadSerialize> ResponseBuilder<TResp> {
                 &cloud_event.0.spec_version,
             )?;
         }
+        if let Some(qos) = &self.qos
+            && *qos != QoS::AtMostOnce
+            && *qos != QoS::AtLeastOnce
+        {
+            return Err("QoS must be AtMostOnce or AtLeastOnce".to_string());
+        }
+        if let Some(Some(message_expiry_override)) = &self.message_expiry_override
+            && message_expiry_override.is_zero()
+        {
+            return Err("Message expiry override must be greater than zero".to_string());
+        }
         Ok(())
     }
 }
@@ -534,6 +912,8 @@ impl<TResp: PayloadSerialize> ResponseBuilder<TResp> {
 ///
 /// Returns `Ok(())` if the properties are added to `custom_user_data`. If an error is returned, `custom_user_data` won't get modified.
 ///
+/// Prefer building an [`ApplicationError`](crate::rpc_command::ApplicationError) and adding its [`into_headers`](crate::rpc_command::ApplicationError::into_headers) to `custom_user_data`, which also supports structured detail and a severity.
+///
 /// # Errors
 /// Returns an Error with the `String` "`application_error_code` cannot be empty" if `application_error_code` is an empty string.
 pub fn application_error_headers(
@@ -541,21 +921,13 @@ pub fn application_error_headers(
     application_error_code: String,
     application_error_payload: String,
 ) -> Result<(), String> {
-    const APPLICATION_ERROR_CODE_HEADER: &str = "AppErrCode";
-    const APPLICATION_ERROR_PAYLOAD_HEADER: &str = "AppErrPayload";
+    let application_error = ApplicationErrorBuilder::default()
+        .code(application_error_code)
+        .raw_detail(application_error_payload)
+        .build()
+        .map_err(|_| "application_error_code cannot be empty".to_string())?;
 
-    if application_error_code.trim().is_empty() {
-        return Err("application_error_code cannot be empty".into());
-    }
-
-    custom_user_data.push((APPLICATION_ERROR_CODE_HEADER.into(), application_error_code));
-
-    if !application_error_payload.trim().is_empty() {
-        custom_user_data.push((
-            APPLICATION_ERROR_PAYLOAD_HEADER.into(),
-            application_error_payload,
-        ));
-    }
+    custom_user_data.extend(application_error.into_headers());
 
     Ok(())
 }
@@ -578,6 +950,9 @@ enum CacheEntry {
         serialized_payload: SerializedPayload,
         properties: PublishProperties,
         expiration_time: Instant,
+        qos: QoS,
+        /// Message expiry override of the response in seconds, if any
+        message_expiry_override: Option<u32>,
     },
     /// Indicates that the request is in progress
     InProgress {
@@ -596,6 +971,7 @@ enum CacheLookupResult {
         serialized_payload: SerializedPayload,
         properties: PublishProperties,
         response_message_expiry_interval: u32,
+        qos: QoS,
     },
     /// The cache entry is in progress
     InProgress(CancellationToken),
@@ -603,11 +979,80 @@ enum CacheLookupResult {
     NotFound,
 }
 
+/// Snapshot of the statistics of an [`Executor`], returned by [`Executor::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutorStats {
+    /// Number of command requests received, including duplicates
+    pub requests_received: u64,
+    /// Number of duplicate requests answered with a cached response
+    pub cache_hits: u64,
+    /// Number of duplicate requests received while the original request was still in progress
+    pub in_progress_dupes: u64,
+    /// Number of expired or abandoned entries evicted from the cache
+    pub expired_entries_evicted: u64,
+    /// Number of responses published, and acknowledged if published with QoS 1
+    pub responses_published: u64,
+    /// Number of responses that could not be published
+    pub responses_failed: u64,
+    /// Number of entries in the cache, both cached responses and requests in progress. Includes
+    /// expired entries that have not been evicted yet.
+    pub cache_entries: usize,
+    /// Number of requests currently held by the application (see [`Request::hold`]). Held
+    /// requests are in progress, so they are also counted in `cache_entries`.
+    pub held_requests: usize,
+    /// Number of held requests that expired before they were completed, whether they were
+    /// completed or dropped afterwards
+    pub held_requests_expired: u64,
+}
+
+/// Counters behind [`ExecutorStats`], shared by the [`Executor`], its [`Cache`] and the tasks
+/// processing its requests.
+#[derive(Debug, Default)]
+struct ExecutorCounters {
+    requests_received: AtomicU64,
+    cache_hits: AtomicU64,
+    in_progress_dupes: AtomicU64,
+    expired_entries_evicted: AtomicU64,
+    responses_published: AtomicU64,
+    responses_failed: AtomicU64,
+    cache_entries: AtomicUsize,
+    held_requests: AtomicUsize,
+    held_requests_expired: AtomicU64,
+}
+
+impl ExecutorCounters {
+    /// Count the outcome of publishing a response
+    fn response_published(&self, published: bool) {
+        if published {
+            self.responses_published.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.responses_failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> ExecutorStats {
+        ExecutorStats {
+            requests_received: self.requests_received.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            in_progress_dupes: self.in_progress_dupes.load(Ordering::Relaxed),
+            expired_entries_evicted: self.expired_entries_evicted.load(Ordering::Relaxed),
+            responses_published: self.responses_published.load(Ordering::Relaxed),
+            responses_failed: self.responses_failed.load(Ordering::Relaxed),
+            cache_entries: self.cache_entries.load(Ordering::Relaxed),
+            held_requests: self.held_requests.load(Ordering::Relaxed),
+            held_requests_expired: self.held_requests_expired.load(Ordering::Relaxed),
+        }
+    }
+}
+
 /// The Command Executor Cache struct.
 ///
 /// Used to cache command responses and determine if a command request is a duplicate.
-#[derive(Clone)]
-struct Cache(Arc<Mutex<HashMap<CacheKey, CacheEntry>>>);
+#[derive(Clone, Default)]
+struct Cache {
+    entries: Arc<Mutex<HashMap<CacheKey, CacheEntry>>>,
+    counters: Arc<ExecutorCounters>,
+}
 
 impl Cache {
     /// Get the status of a cache entry from the [`Cache`].
@@ -617,7 +1062,7 @@ impl Cache {
     ///
     /// Returns a [`CacheLookupResult`] indicating the result of the get.
     fn get(&self, key: &CacheKey) -> CacheLookupResult {
-        let cache = self.0.lock().unwrap();
+        let cache = self.entries.lock().unwrap();
 
         match cache.get(key) {
             Some(entry) => {
@@ -626,6 +1071,8 @@ impl Cache {
                         serialized_payload,
                         properties,
                         expiration_time,
+                        qos,
+                        message_expiry_override,
                     } => {
                         let response_message_expiry_interval =
                             get_response_message_expiry_interval(*expiration_time);
@@ -634,10 +1081,16 @@ impl Cache {
                         if let Some(response_message_expiry_interval) =
                             response_message_expiry_interval
                         {
+                            self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
                             CacheLookupResult::Cached {
                                 serialized_payload: serialized_payload.clone(),
                                 properties: properties.clone(),
-                                response_message_expiry_interval,
+                                // Duplicate responses honor the message expiry override of the response
+                                response_message_expiry_interval: message_expiry_override
+                                    .map_or(response_message_expiry_interval, |o| {
+                                        o.min(response_message_expiry_interval)
+                                    }),
+                                qos: *qos,
                             }
                         } else {
                             // Entry has expired, return not found
@@ -650,6 +1103,9 @@ impl Cache {
                         // If the entry is in progress, it means it has not been completed yet
                         // any duplicate requests should await the cancellation token to complete
                         // their acknowledgement
+                        self.counters
+                            .in_progress_dupes
+                            .fetch_add(1, Ordering::Relaxed);
                         CacheLookupResult::InProgress(processing_cancellation_token.clone())
                     }
                 }
@@ -664,7 +1120,8 @@ impl Cache {
     /// `key` - The cache key to set the cache entry for.
     /// `entry` - The cache entry to set.
     fn set(&self, key: CacheKey, entry: CacheEntry) {
-        let mut cache = self.0.lock().unwrap();
+        let mut cache = self.entries.lock().unwrap();
+        let len_before_eviction = cache.len();
         cache.retain(|_, entry| {
             match entry {
                 CacheEntry::Cached {
@@ -683,10 +1140,141 @@ impl Cache {
                 }
             }
         });
+        self.counters.expired_entries_evicted.fetch_add(
+            (len_before_eviction - cache.len()) as u64,
+            Ordering::Relaxed,
+        );
         cache.insert(key, entry);
+        self.counters
+            .cache_entries
+            .store(cache.len(), Ordering::Relaxed);
+    }
+}
+
+/// Store shared by [`Executor`] replicas in the same service group, used to claim command
+/// requests so that a request redelivered to a different replica is not executed more than once.
+#[async_trait::async_trait]
+pub trait DistributedDedupStore: Send + Sync {
+    /// Claim `key` for the duration of `ttl`, only if it has not already been claimed.
+    ///
+    /// Returns `Ok(true)` if the key was claimed, or `Ok(false)` if it was already claimed.
+    ///
+    /// # Errors
+    /// Returns an error if the store could not be reached or did not complete the claim.
+    async fn try_claim(
+        &self,
+        key: String,
+        ttl: Duration,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+#[async_trait::async_trait]
+impl<T: DistributedDedupStore + ?Sized> DistributedDedupStore for Arc<T> {
+    async fn try_claim(
+        &self,
+        key: String,
+        ttl: Duration,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).try_claim(key, ttl).await
+    }
+}
+
+/// Behavior of an [`Executor`] when a command request has already been claimed by another
+/// replica in the service group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistributedDuplicateBehavior {
+    /// Acknowledge the request without responding, leaving the response to the replica that
+    /// claimed it.
+    Ignore,
+    /// Wait for the provided duration, then respond with a Service Unavailable error. If the
+    /// replica that claimed the request has already responded, the invoker disregards this response.
+    RespondAfter(Duration),
+}
+
+/// Behavior of an [`Executor`] when the [`DistributedDedupStore`] cannot be used to claim a
+/// command request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistributedDedupFailurePolicy {
+    /// Execute the command, relying only on the local deduplication of the [`Executor`].
+    FailOpen,
+    /// Do not execute the command, and respond with a Service Unavailable error.
+    FailClosed,
+}
+
+/// Options for deduplicating command requests across [`Executor`] replicas sharing a service group
+#[derive(Builder, Clone)]
+#[builder(setter(into))]
+pub struct DistributedDedupOptions {
+    /// Store used to claim command requests
+    #[builder(setter(custom))]
+    store: Arc<dyn DistributedDedupStore>,
+    /// Behavior when a command request has already been claimed by another replica
+    #[builder(default = "DistributedDuplicateBehavior::Ignore")]
+    duplicate_behavior: DistributedDuplicateBehavior,
+    /// Behavior when the store cannot be used to claim a command request
+    #[builder(default = "DistributedDedupFailurePolicy::FailOpen")]
+    failure_policy: DistributedDedupFailurePolicy,
+}
+
+impl DistributedDedupOptionsBuilder {
+    /// Set the [`DistributedDedupStore`] used to claim command requests
+    pub fn store(&mut self, store: impl DistributedDedupStore + 'static) -> &mut Self {
+        self.store = Some(Arc::new(store));
+        self
     }
 }
 
+/// Outcome of claiming a command request with the [`DistributedDedupStore`]
+enum DistributedClaim {
+    /// The request can be executed by this executor
+    Execute,
+    /// The request was claimed by another executor, acknowledge it without responding
+    Ignore,
+    /// The request cannot be executed by this executor, respond with an error after the delay
+    Reject(Duration),
+}
+
+/// Derive the [`DistributedDedupStore`] key for a command request.
+fn distributed_dedup_key(
+    service_group_id: &str,
+    command_name: &str,
+    correlation_data: &Bytes,
+) -> String {
+    let correlation_data_hex =
+        correlation_data
+            .iter()
+            .fold(String::with_capacity(32), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            });
+    format!(
+        "{DISTRIBUTED_DEDUP_KEY_PREFIX}:{service_group_id}:{command_name}:{correlation_data_hex}"
+    )
+}
+
+/// Default content type of error responses sent by the [`Executor`]
+pub const DEFAULT_ERROR_RESPONSE_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Response the [`Executor`] sends to the invoker when the application drops a [`Request`] (or its
+/// [`Responder`]) without completing it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DroppedRequestResponse {
+    /// An error response with an Internal Server Error status, flagged as an application error.
+    /// The invoker receives an [`AIOProtocolError`] of kind
+    /// [`ExecutionException`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ExecutionException).
+    #[default]
+    InternalServerError,
+    /// A No Content response carrying the [`ApplicationError`] on its custom user data, which the
+    /// invoker can read with
+    /// [`Response::application_error`](crate::rpc_command::invoker::Response::application_error).
+    /// The response has no payload, so the invoker's response payload type must deserialize from
+    /// an empty payload.
+    ApplicationError(ApplicationError),
+    /// No response is sent and the request is acknowledged, so the invoker times out. The response
+    /// is not cached, so a duplicate of the request is delivered to the application again.
+    None,
+}
+
 /// Command Executor Options struct
 #[allow(unused)]
 #[derive(Builder, Clone)]
@@ -709,6 +1297,246 @@ pub struct Options {
     /// Service group ID
     #[builder(default = "None")]
     service_group_id: Option<String>,
+    /// Deduplicate command requests across all executors in the service group, so that a request
+    /// redelivered to a different executor is not executed again. Requires
+    /// [`service_group_id`](OptionsBuilder::service_group_id) to be set.
+    #[builder(default = "None")]
+    distributed_dedup: Option<DistributedDedupOptions>,
+    /// Content type of error responses, which have no payload
+    #[builder(default = "DEFAULT_ERROR_RESPONSE_CONTENT_TYPE.to_string()")]
+    error_response_content_type: String,
+    /// Content types accepted on command requests, matched case-insensitively. A subtype of `*`
+    /// (e.g. `application/*`) matches any subtype. Requests with any other content type are
+    /// rejected with an Unsupported Media Type error response before their payload is
+    /// deserialized. If empty, any content type is passed to the request payload deserializer.
+    #[builder(default)]
+    accepted_content_types: Vec<String>,
+    /// Whether requests without a content type are accepted when
+    /// [`accepted_content_types`](OptionsBuilder::accepted_content_types) is not empty
+    #[builder(default = "true")]
+    allow_missing_content_type: bool,
+    /// Response sent to the invoker when the application drops a [`Request`] without completing it
+    #[builder(default)]
+    dropped_request_response: DroppedRequestResponse,
+    /// Topic token identifying the resource a request acts on, e.g. `assetName`. If set, a request
+    /// is only returned from [`Executor::recv`] once all prior requests with the same value for
+    /// the token have completed (or expired), while requests with different values are returned
+    /// as they arrive. Queued requests keep expiring while they wait, and time out as usual if
+    /// they expire before being returned. Must be a token of the
+    /// [`request_topic_pattern`](OptionsBuilder::request_topic_pattern).
+    #[builder(default = "None")]
+    serialize_by_token: Option<String>,
+    /// Message expiry assumed for requests that don't specify one, overriding the
+    /// [`ApplicationContext`]'s
+    /// [`default_message_expiry`](crate::application::ApplicationContext::default_message_expiry).
+    /// Must be between 1 second and 1 hour.
+    #[builder(default = "None")]
+    default_message_expiry: Option<Duration>,
+    /// Additional time responses stay cached after their command expires, overriding the
+    /// [`ApplicationContext`]'s
+    /// [`cache_expiry_buffer`](crate::application::ApplicationContext::cache_expiry_buffer).
+    /// Must be at most 10 minutes.
+    #[builder(default = "None")]
+    cache_expiry_buffer: Option<Duration>,
+    /// Whether to attach a checksum of the serialized payload to each response, so that the
+    /// invoker can detect corrupted payloads, see
+    /// [`payload_checksum`](crate::common::payload_checksum). Default is `false`.
+    ///
+    /// Checksums attached to requests are verified regardless of this option.
+    #[builder(default = "false")]
+    payload_checksum: bool,
+    /// Major protocol versions accepted on command requests. Requests with any other major version
+    /// are rejected with a Version Not Supported error response that advertises these versions.
+    /// Must not be empty. Default is the versions supported by this SDK.
+    #[builder(default = "SUPPORTED_PROTOCOL_VERSIONS.to_vec()")]
+    supported_protocol_versions: Vec<u16>,
+    /// MQTT client that responses (including cached responses to duplicate requests) are published
+    /// on, e.g. to respond on a different broker than requests are received on when the invoker is
+    /// reachable through a bridge. Requests are still subscribed to and received on the client the
+    /// [`Executor`] is created with, which is also used if this is not set.
+    #[builder(default = "None")]
+    response_client: Option<SessionManagedClient>,
+    /// Monitor of the session of the [`response_client`](OptionsBuilder::response_client). If set,
+    /// completing a request while that session is disconnected fails instead of queueing the
+    /// response until it reconnects. Requires the
+    /// [`response_client`](OptionsBuilder::response_client) to be set.
+    #[builder(default = "None")]
+    response_client_monitor: Option<SessionMonitor>,
+    /// Minimum time between receiving a request and publishing an error response generated by the
+    /// [`Executor`] for it (e.g. for an invalid request, an unsupported content type or protocol
+    /// version), so that the latency of the response does not reveal which check failed or whether
+    /// the request reached the application. Responses to requests that are returned from
+    /// [`Executor::recv`] are unaffected. The delay is capped by the expiry of the request. Default
+    /// is no delay.
+    #[builder(default = "None")]
+    uniform_error_response_delay: Option<Duration>,
+}
+
+/// Requests that have not completed yet for a value of the topic token an [`Executor`] serializes
+/// requests by
+struct PendingRequests {
+    /// Number of requests received that have not completed yet, including the one being processed
+    count: usize,
+    /// Cancelled once the most recently received request has completed
+    last_completed: CancellationToken,
+}
+
+/// Serializes the requests returned from [`Executor::recv`] by the value of a topic token, see
+/// [`serialize_by_token`](OptionsBuilder::serialize_by_token)
+struct RequestSerializer<TReq, TResp>
+where
+    TReq: PayloadSerialize,
+    TResp: PayloadSerialize,
+{
+    /// Topic token whose value identifies the resource a request acts on
+    token: String,
+    /// Requests that have not completed yet, by token value
+    pending: Arc<Mutex<HashMap<String, PendingRequests>>>,
+    /// Used to return queued requests once the prior requests for the same value have completed.
+    /// Dropped once there will be no more requests, so that the receiver closes once all queued
+    /// requests have been returned.
+    ready_tx: Option<mpsc::UnboundedSender<Request<TReq, TResp>>>,
+    ready_rx: mpsc::UnboundedReceiver<Request<TReq, TResp>>,
+}
+
+impl<TReq, TResp> RequestSerializer<TReq, TResp>
+where
+    TReq: PayloadSerialize + Send + 'static,
+    TResp: PayloadSerialize + Send + 'static,
+{
+    fn new(token: String) -> Self {
+        let (ready_tx, ready_rx) = mpsc::unbounded_channel();
+        Self {
+            token,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            ready_tx: Some(ready_tx),
+            ready_rx,
+        }
+    }
+
+    /// Track a request that is ready to be returned to the application. Returns the request if
+    /// it can be returned right away, otherwise it is queued until the prior requests with the
+    /// same token value have completed.
+    ///
+    /// `processing_cancellation_token` is cancelled once the request has completed or expired.
+    fn serialize(
+        &self,
+        request: Request<TReq, TResp>,
+        processing_cancellation_token: CancellationToken,
+        executor_cancellation_token: CancellationToken,
+    ) -> Option<Request<TReq, TResp>> {
+        let Some(value) = request.topic_tokens.get(&self.token).cloned() else {
+            return Some(request);
+        };
+
+        let completed = CancellationToken::new();
+        let prior_completed = match self.pending.lock().unwrap().entry(value.clone()) {
+            Entry::Occupied(mut entry) => {
+                let pending = entry.get_mut();
+                pending.count += 1;
+                Some(std::mem::replace(
+                    &mut pending.last_completed,
+                    completed.clone(),
+                ))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(PendingRequests {
+                    count: 1,
+                    last_completed: completed.clone(),
+                });
+                None
+            }
+        };
+        let (request, queued_request) = if prior_completed.is_some() {
+            log::debug!(
+                "[{}] Queueing request until prior requests for {}={value} complete",
+                request.responder.command_name,
+                self.token
+            );
+            (None, Some(request))
+        } else {
+            (Some(request), None)
+        };
+
+        tokio::task::spawn({
+            let pending = self.pending.clone();
+            let ready_tx = self.ready_tx.clone();
+            async move {
+                tokio::select! {
+                    () = executor_cancellation_token.cancelled() => { /* executor dropped */ },
+                    () = async {
+                        if let Some(prior_completed) = prior_completed {
+                            prior_completed.cancelled().await;
+                        }
+                        if let Some(queued_request) = queued_request {
+                            if processing_cancellation_token.is_cancelled() {
+                                // The request expired while queued and has already timed out
+                                log::debug!(
+                                    "[{}] Request for {value} expired while queued",
+                                    queued_request.responder.command_name
+                                );
+                            } else if let Some(ready_tx) = ready_tx {
+                                // Ignore error as the executor may have been dropped
+                                let _ = ready_tx.send(queued_request);
+                            }
+                        }
+                        processing_cancellation_token.cancelled().await;
+                    } => {},
+                }
+                completed.cancel();
+                let mut pending = pending.lock().unwrap();
+                if let Entry::Occupied(mut entry) = pending.entry(value) {
+                    entry.get_mut().count -= 1;
+                    if entry.get().count == 0 {
+                        entry.remove();
+                    }
+                }
+            }
+        });
+
+        request
+    }
+
+    /// Number of requests for the token value that are queued behind a prior request
+    fn queue_depth(&self, token_value: &str) -> usize {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(token_value)
+            .map_or(0, |pending| pending.count - 1)
+    }
+}
+
+/// Content types accepted on command requests by an [`Executor`]
+struct ContentTypeAllowList {
+    /// Lowercase accepted content types, subtypes may be `*`
+    accepted_content_types: Vec<String>,
+    allow_missing_content_type: bool,
+}
+
+impl ContentTypeAllowList {
+    /// Returns whether a request with the given content type is accepted. Parameters of the
+    /// content type (e.g. `; charset=utf-8`) are ignored.
+    fn accepts(&self, content_type: Option<&String>) -> bool {
+        let Some(content_type) = content_type else {
+            return self.allow_missing_content_type;
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.accepted_content_types
+            .iter()
+            .any(|accepted| match accepted.strip_suffix("/*") {
+                Some("*") => true,
+                Some(accepted_type) => media_type
+                    .split_once('/')
+                    .is_some_and(|(media_type, _)| media_type == accepted_type),
+                None => *accepted == media_type,
+            })
+    }
 }
 
 /// Command Executor struct
@@ -753,6 +1581,9 @@ where
     application_hlc: Arc<ApplicationHybridLogicalClock>,
     mqtt_client: SessionManagedClient,
     mqtt_receiver: SessionPubReceiver,
+    /// Client responses are published on, the same as `mqtt_client` unless configured otherwise
+    response_client: SessionManagedClient,
+    response_client_monitor: Option<SessionMonitor>,
     is_idempotent: bool,
     request_topic_pattern: TopicPattern,
     request_topic_filter: TopicFilter,
@@ -760,6 +1591,19 @@ where
     request_payload_type: PhantomData<TReq>,
     response_payload_type: PhantomData<TResp>,
     cache: Cache,
+    distributed_dedup: Option<(String, DistributedDedupOptions)>,
+    error_response_content_type: String,
+    content_type_allow_list: Option<ContentTypeAllowList>,
+    dropped_request_response: DroppedRequestResponse,
+    request_serializer: Option<RequestSerializer<TReq, TResp>>,
+    /// Message expiry interval in seconds assumed for requests that don't specify one
+    default_message_expiry_interval: u32,
+    cache_expiry_buffer: Duration,
+    payload_checksum: bool,
+    supported_protocol_versions: Vec<u16>,
+    /// Minimum time after receiving a request before an error response generated by the executor
+    /// is published
+    uniform_error_response_delay: Option<Duration>,
     // Describes state
     state: State,
     // Information to manage state
@@ -771,6 +1615,8 @@ where
 enum State {
     New,
     Subscribed,
+    /// The request topic is covered by a subscription owned by a [`CommandRouter`](super::router::CommandRouter)
+    SharedSubscription,
     ShutdownSuccessful,
 }
 
@@ -796,6 +1642,19 @@ where
     ///   [`topic_namespace`](OptionsBuilder::topic_namespace)
     ///   are Some and invalid or contain a token with no valid replacement
     /// - [`topic_token_map`](OptionsBuilder::topic_token_map) is not empty and contains invalid key(s) and/or token(s)
+    /// - [`error_response_content_type`](OptionsBuilder::error_response_content_type) is empty or
+    ///   contains invalid characters
+    /// - [`accepted_content_types`](OptionsBuilder::accepted_content_types) contains an entry that
+    ///   is not of the form `type/subtype` or contains invalid characters
+    /// - [`serialize_by_token`](OptionsBuilder::serialize_by_token) is not a token of the
+    ///   [`request_topic_pattern`](OptionsBuilder::request_topic_pattern)
+    /// - [`default_message_expiry`](OptionsBuilder::default_message_expiry) (or the
+    ///   [`ApplicationContext`]'s, if not set) is not between 1 second and 1 hour
+    /// - [`cache_expiry_buffer`](OptionsBuilder::cache_expiry_buffer) (or the
+    ///   [`ApplicationContext`]'s, if not set) is more than 10 minutes
+    /// - [`supported_protocol_versions`](OptionsBuilder::supported_protocol_versions) is empty
+    /// - [`response_client_monitor`](OptionsBuilder::response_client_monitor) is set without a
+    ///   [`response_client`](OptionsBuilder::response_client)
     pub fn new(
         application_context: ApplicationContext,
         client: SessionManagedClient,
@@ -815,6 +1674,63 @@ where
             ));
         }
 
+        if executor_options.error_response_content_type.is_empty()
+            || is_invalid_utf8(&executor_options.error_response_content_type)
+        {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "error_response_content_type",
+                Value::String(executor_options.error_response_content_type),
+                None,
+                Some(executor_options.command_name),
+            ));
+        }
+
+        let content_type_allow_list = if executor_options.accepted_content_types.is_empty() {
+            None
+        } else {
+            let mut accepted_content_types = Vec::new();
+            for content_type in executor_options.accepted_content_types {
+                let is_valid = content_type.split_once('/').is_some_and(|(t, subtype)| {
+                    !t.trim().is_empty() && !subtype.trim().is_empty() && !subtype.contains('/')
+                });
+                if !is_valid || is_invalid_utf8(&content_type) {
+                    return Err(AIOProtocolError::new_configuration_invalid_error(
+                        None,
+                        "accepted_content_types",
+                        Value::String(content_type),
+                        None,
+                        Some(executor_options.command_name),
+                    ));
+                }
+                accepted_content_types.push(content_type.trim().to_ascii_lowercase());
+            }
+            Some(ContentTypeAllowList {
+                accepted_content_types,
+                allow_missing_content_type: executor_options.allow_missing_content_type,
+            })
+        };
+
+        // Distributed deduplication is scoped to the service group
+        let distributed_dedup = match (
+            executor_options.distributed_dedup,
+            &executor_options.service_group_id,
+        ) {
+            (Some(distributed_dedup), Some(service_group_id)) => {
+                Some((service_group_id.clone(), distributed_dedup))
+            }
+            (Some(_), None) => {
+                return Err(AIOProtocolError::new_configuration_invalid_error(
+                    None,
+                    "service_group_id",
+                    Value::String(String::new()),
+                    Some("service_group_id must be set to use distributed_dedup".to_string()),
+                    Some(executor_options.command_name),
+                ));
+            }
+            (None, _) => None,
+        };
+
         // Create a new Command Pattern, validates topic pattern and options
         let request_topic_pattern = TopicPattern::new(
             &executor_options.request_topic_pattern,
@@ -836,25 +1752,176 @@ where
             )
         })?;
 
-        // Get pub sub and receiver from the mqtt session
-        let mqtt_receiver = client.create_filtered_pub_receiver(request_topic_filter.clone());
+        let request_serializer = match executor_options.serialize_by_token {
+            Some(token)
+                if executor_options
+                    .request_topic_pattern
+                    .contains(&format!("{{{token}}}")) =>
+            {
+                Some(RequestSerializer::new(token))
+            }
+            Some(token) => {
+                return Err(AIOProtocolError::new_configuration_invalid_error(
+                    None,
+                    "serialize_by_token",
+                    Value::String(token),
+                    Some("Token is not in the request topic pattern".to_string()),
+                    Some(executor_options.command_name),
+                ));
+            }
+            None => None,
+        };
 
-        // Create Command executor
-        Ok(Executor {
-            application_hlc: application_context.application_hlc,
-            mqtt_client: client,
-            mqtt_receiver,
-            is_idempotent: executor_options.is_idempotent,
-            request_topic_pattern,
-            request_topic_filter,
-            command_name: executor_options.command_name,
-            request_payload_type: PhantomData,
-            response_payload_type: PhantomData,
-            cache: Cache(Arc::new(Mutex::new(HashMap::new()))),
-            state: State::New,
-            cancellation_token: CancellationToken::new(),
-        })
-    }
+        let default_message_expiry = executor_options
+            .default_message_expiry
+            .unwrap_or(application_context.default_message_expiry);
+        if let Err(e) = validate_default_message_expiry(default_message_expiry) {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "default_message_expiry",
+                Value::String(format!("{default_message_expiry:?}")),
+                Some(e),
+                Some(executor_options.command_name),
+            ));
+        }
+        // Rounded up to the nearest second, at most an hour so it always fits in a u32
+        let default_message_expiry_interval = u32::try_from(
+            default_message_expiry.as_secs() + u64::from(default_message_expiry.subsec_nanos() > 0),
+        )
+        .unwrap_or(u32::MAX);
+
+        let cache_expiry_buffer = executor_options
+            .cache_expiry_buffer
+            .unwrap_or(application_context.cache_expiry_buffer);
+        if let Err(e) = validate_cache_expiry_buffer(cache_expiry_buffer) {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "cache_expiry_buffer",
+                Value::String(format!("{cache_expiry_buffer:?}")),
+                Some(e),
+                Some(executor_options.command_name),
+            ));
+        }
+
+        if executor_options.supported_protocol_versions.is_empty() {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "supported_protocol_versions",
+                Value::String(String::new()),
+                Some("At least one protocol version must be supported".to_string()),
+                Some(executor_options.command_name),
+            ));
+        }
+
+        if executor_options.response_client_monitor.is_some()
+            && executor_options.response_client.is_none()
+        {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "response_client_monitor",
+                Value::String(String::new()),
+                Some("A response client monitor requires a response client".to_string()),
+                Some(executor_options.command_name),
+            ));
+        }
+
+        // Get pub sub and receiver from the mqtt session
+        let mqtt_receiver = client.create_filtered_pub_receiver(request_topic_filter.clone());
+        let response_client = executor_options
+            .response_client
+            .unwrap_or_else(|| client.clone());
+
+        // Create Command executor
+        Ok(Executor {
+            application_hlc: application_context.application_hlc,
+            mqtt_client: client,
+            mqtt_receiver,
+            response_client,
+            response_client_monitor: executor_options.response_client_monitor,
+            is_idempotent: executor_options.is_idempotent,
+            request_topic_pattern,
+            request_topic_filter,
+            command_name: executor_options.command_name,
+            request_payload_type: PhantomData,
+            response_payload_type: PhantomData,
+            cache: Cache::default(),
+            distributed_dedup,
+            error_response_content_type: executor_options.error_response_content_type,
+            content_type_allow_list,
+            dropped_request_response: executor_options.dropped_request_response,
+            request_serializer,
+            default_message_expiry_interval,
+            cache_expiry_buffer,
+            payload_checksum: executor_options.payload_checksum,
+            supported_protocol_versions: executor_options.supported_protocol_versions,
+            uniform_error_response_delay: executor_options.uniform_error_response_delay,
+            state: State::New,
+            cancellation_token: CancellationToken::new(),
+        })
+    }
+
+    /// Create a new [`Executor`] that relies on a subscription owned by a
+    /// [`CommandRouter`](super::router::CommandRouter) rather than subscribing to the request
+    /// topic itself.
+    pub(crate) fn new_with_shared_subscription(
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+        executor_options: Options,
+    ) -> Result<Self, AIOProtocolError> {
+        let mut executor = Self::new(application_context, client, executor_options)?;
+        executor.state = State::SharedSubscription;
+        Ok(executor)
+    }
+
+    /// Number of received requests with the given value for the
+    /// [`serialize_by_token`](OptionsBuilder::serialize_by_token) topic token that are queued
+    /// until a prior request with the same value completes. Always 0 if requests are not
+    /// serialized.
+    #[must_use]
+    pub fn queue_depth(&self, token_value: &str) -> usize {
+        self.request_serializer
+            .as_ref()
+            .map_or(0, |request_serializer| {
+                request_serializer.queue_depth(token_value)
+            })
+    }
+
+    /// Returns true if the [`Executor`] is subscribed to the request topic, or relies on a
+    /// subscription owned by a [`CommandRouter`](super::router::CommandRouter).
+    ///
+    /// An [`Executor`] subscribes when [`start`](Self::start) or [`recv`](Self::recv) is first
+    /// called, and is no longer subscribed after a successful [`shutdown`](Self::shutdown).
+    #[must_use]
+    pub fn is_subscribed(&self) -> bool {
+        matches!(self.state, State::Subscribed | State::SharedSubscription)
+    }
+
+    /// Returns a snapshot of the statistics of the [`Executor`], such as how many duplicate
+    /// requests were answered from the cache and the current number of cache entries, which can
+    /// be used to tune message expiry intervals.
+    #[must_use]
+    pub fn stats(&self) -> ExecutorStats {
+        self.cache.counters.snapshot()
+    }
+
+    /// Start the [`Executor`]. Subscribes to the request topic and returns once the subscription
+    /// has been acknowledged, so that no requests published afterwards are missed.
+    ///
+    /// Calling this method is optional: if it is not called, the [`Executor`] subscribes on the
+    /// first call to [`recv`](Self::recv). Does nothing if the [`Executor`] is already subscribed
+    /// or has been shut down. If the method returns an error, it may be called again to attempt
+    /// the subscribe again.
+    ///
+    /// Returns Ok(()) on success, otherwise returns [`AIOProtocolError`].
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
+    pub async fn start(&mut self) -> Result<(), AIOProtocolError> {
+        if State::New == self.state {
+            self.try_subscribe().await?;
+            self.state = State::Subscribed;
+        }
+        Ok(())
+    }
 
     /// Shutdown the [`Executor`]. Unsubscribes from the request topic.
     ///
@@ -870,8 +1937,9 @@ where
         self.mqtt_receiver.close();
 
         match self.state {
-            State::New | State::ShutdownSuccessful => {
-                // If subscribe has not been called or shutdown was successful, do not unsubscribe
+            State::New | State::SharedSubscription | State::ShutdownSuccessful => {
+                // If subscribe has not been called, the subscription is not owned by this
+                // executor, or shutdown was successful, do not unsubscribe
                 self.state = State::ShutdownSuccessful;
             }
             State::Subscribed => {
@@ -935,7 +2003,7 @@ where
     ///
     /// Returns Ok(()) on success, otherwise returns [`AIOProtocolError`].
     /// # Errors
-    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
     async fn try_subscribe(&mut self) -> Result<(), AIOProtocolError> {
         let subscribe_result = self
             .mqtt_client
@@ -951,7 +2019,7 @@ where
         match subscribe_result {
             Ok(sub_ct) => match sub_ct.await {
                 Ok(suback) => {
-                    suback.as_result().map_err(|e| {
+                    suback.as_result_with_qos(QoS::AtLeastOnce).map_err(|e| {
                         log::error!("[{}] Executor suback error: {suback:?}", self.command_name);
                         AIOProtocolError::new_mqtt_error(
                             Some("MQTT error on command executor suback".to_string()),
@@ -989,6 +2057,9 @@ where
 
     /// Receive a command request or [`None`] if there will be no more requests.
     ///
+    /// If [`serialize_by_token`](OptionsBuilder::serialize_by_token) is set, a request is not
+    /// returned until the prior requests with the same token value have completed.
+    ///
     /// If there are messages:
     /// - Returns Ok([`Request`]) on success
     /// - Returns [`AIOProtocolError`] on error.
@@ -998,20 +2069,27 @@ where
     /// # Errors
     /// [`AIOProtocolError`] of kind [`UnknownError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::UnknownError) if an error occurs while receiving the message.
     ///
-    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
     ///
     /// [`AIOProtocolError`] of kind [`InternalLogicError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::InternalLogicError) if the command expiration time cannot be calculated.
     pub async fn recv(&mut self) -> Option<Result<Request<TReq, TResp>, AIOProtocolError>> {
         // Subscribe to the request topic if not already subscribed
-        if State::New == self.state {
-            if let Err(e) = self.try_subscribe().await {
-                return Some(Err(e));
-            }
-            self.state = State::Subscribed;
+        if let Err(e) = self.start().await {
+            return Some(Err(e));
         }
 
-        loop {
-            match self.mqtt_receiver.recv_manual_ack().await {
+        'receive: loop {
+            let received = if let Some(request_serializer) = &mut self.request_serializer {
+                tokio::select! {
+                    // Return queued requests first, they have been waiting longer
+                    biased;
+                    Some(request) = request_serializer.ready_rx.recv() => return Some(Ok(request)),
+                    received = self.mqtt_receiver.recv_manual_ack() => received,
+                }
+            } else {
+                self.mqtt_receiver.recv_manual_ack().await
+            };
+            match received {
                 Some((m, ack_token)) => {
                     let Some(ack_token) = ack_token else {
                         // No ack token, ignore the message. This should never happen as the executor
@@ -1022,7 +2100,11 @@ where
                         );
                         continue;
                     };
-                    let pkid = match m.qos {
+                    self.cache
+                        .counters
+                        .requests_received
+                        .fetch_add(1, Ordering::Relaxed);
+                    let (pkid, duplicate) = match m.qos {
                         azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtMostOnce
                         | azure_iot_operations_mqtt::control_packet::DeliveryQoS::ExactlyOnce(_) => {
                             // This should never happen as the executor should always receive QoS 1 messages
@@ -1034,16 +2116,17 @@ where
                         }
                         azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtLeastOnce(
                             delivery_info,
-                        ) => delivery_info.packet_identifier.get(),
+                        ) => (delivery_info.packet_identifier.get(), delivery_info.dup),
                     };
                     // Process the request
                     log::debug!("[{}][pkid: {}] Received request", self.command_name, pkid);
-                    let message_received_time = Instant::now();
+                    let message_received_time = clock::now();
 
                     // Create a cancellation token to be used to track cache entry state, once the
                     // entry has gone from InProgress to Cached or removed, this token will be cancelled
                     let processing_cancellation_token = CancellationToken::new();
                     let processing_cancellation_token_clone = processing_cancellation_token.clone();
+                    let processing_completed = processing_cancellation_token.clone();
                     // This drop guard will stay alive until the request processing is complete,
                     // whether a timeout occurs, or we have an ok or error response.
                     let processing_drop_guard = processing_cancellation_token.drop_guard();
@@ -1051,6 +2134,26 @@ where
                     // Clone properties
                     let properties = m.properties;
 
+                    // Received properties surfaced on the request as-is, before validation
+                    let received_response_topic = properties
+                        .response_topic
+                        .as_ref()
+                        .map(|t| t.as_str().to_string());
+                    let received_correlation_data = properties.correlation_data.clone();
+                    let subscription_identifiers = properties
+                        .subscription_identifiers
+                        .iter()
+                        .map(|id| id.get())
+                        .collect();
+                    let topic_alias = properties.topic_alias.map(NonZeroU16::get);
+
+                    // A fire-and-forget request has no response topic. The request topic is used in
+                    // its place to key the request in the cache so that duplicates are still detected.
+                    let no_response = properties.user_properties.iter().any(|(key, _)| {
+                        ProtocolReservedUserProperty::from_str(key)
+                            == Ok(ProtocolReservedUserProperty::NoResponse)
+                    });
+
                     // Get response topic
                     let response_topic = if let Some(rt) = properties.response_topic {
                         if !is_valid_replacement(rt.as_str()) {
@@ -1070,6 +2173,8 @@ where
                             continue;
                         }
                         rt
+                    } else if no_response {
+                        m.topic_name.clone()
                     } else {
                         log::warn!(
                             "[{}][pkid: {}] Response topic missing, command response will not be published",
@@ -1102,6 +2207,13 @@ where
                         request_protocol_version: None,
                         cached_key: None,
                         cache_lookup_result: CacheLookupResult::NotFound,
+                        no_response,
+                        error_response_content_type: self.error_response_content_type.clone(),
+                        dropped_request_response: self.dropped_request_response.clone(),
+                        default_message_expiry_interval: self.default_message_expiry_interval,
+                        cache_expiry_buffer: self.cache_expiry_buffer,
+                        payload_checksum: self.payload_checksum,
+                        response_client_monitor: self.response_client_monitor.clone(),
                     };
 
                     // Get message expiry interval
@@ -1111,7 +2223,7 @@ where
                             message_received_time.checked_add(Duration::from_secs(ct.into()))
                         }
                         _ => message_received_time.checked_add(Duration::from_secs(u64::from(
-                            DEFAULT_MESSAGE_EXPIRY_INTERVAL_SECONDS,
+                            self.default_message_expiry_interval,
                         ))),
                     };
 
@@ -1153,6 +2265,7 @@ where
                             Some("Correlation Data".to_string());
                     }
 
+                    let mut distributed_claim = DistributedClaim::Execute;
                     'process_request: {
                         // If the cache key was not created it means the correlation data was invalid
                         let Some(cache_key) = &response_arguments.cached_key else {
@@ -1216,20 +2329,22 @@ where
                                     "Unparsable protocol version value provided: {protocol_version}."
                                 ));
                                 response_arguments.supported_protocol_major_versions =
-                                    Some(SUPPORTED_PROTOCOL_VERSIONS.to_vec());
+                                    Some(self.supported_protocol_versions.clone());
                                 response_arguments.request_protocol_version =
                                     Some(protocol_version.clone());
                                 break 'process_request;
                             }
                         }
                         // Check that the version (or the default version if one isn't provided) is supported
-                        if !request_protocol_version.is_supported(SUPPORTED_PROTOCOL_VERSIONS) {
+                        if !request_protocol_version.is_supported(&self.supported_protocol_versions)
+                        {
                             response_arguments.status_code = StatusCode::VersionNotSupported;
                             response_arguments.status_message = Some(format!(
-                                "The command executor that received the request only supports major protocol versions '{SUPPORTED_PROTOCOL_VERSIONS:?}', but '{request_protocol_version}' was sent on the request."
+                                "The command executor that received the request only supports major protocol versions '{:?}', but '{request_protocol_version}' was sent on the request.",
+                                self.supported_protocol_versions
                             ));
                             response_arguments.supported_protocol_major_versions =
-                                Some(SUPPORTED_PROTOCOL_VERSIONS.to_vec());
+                                Some(self.supported_protocol_versions.clone());
                             response_arguments.request_protocol_version =
                                 Some(request_protocol_version.to_string());
                             break 'process_request;
@@ -1238,6 +2353,7 @@ where
                         let mut user_data = Vec::new();
                         let mut timestamp = None;
                         let mut invoker_id = None;
+                        let mut checksum = None;
                         for (key, value) in properties.user_properties {
                             match ProtocolReservedUserProperty::from_str(&key) {
                                 Ok(ProtocolReservedUserProperty::Timestamp) => {
@@ -1283,7 +2399,13 @@ where
                                 Ok(ProtocolReservedUserProperty::SourceId) => {
                                     invoker_id = Some(value);
                                 }
-                                Ok(ProtocolReservedUserProperty::ProtocolVersion) => {
+                                Ok(ProtocolReservedUserProperty::PayloadChecksum) => {
+                                    checksum = Some(value);
+                                }
+                                Ok(
+                                    ProtocolReservedUserProperty::ProtocolVersion
+                                    | ProtocolReservedUserProperty::NoResponse,
+                                ) => {
                                     // skip, already processed
                                 }
                                 Err(()) => {
@@ -1307,10 +2429,49 @@ where
                             }
                         }
 
+                        // Verify the payload against the checksum attached by the invoker, if any
+                        if let Err(e) = payload_checksum::verify(&m.payload, checksum.as_deref()) {
+                            log::warn!(
+                                "[{}][pkid: {}] Rejecting command request from invoker {}: {e}",
+                                self.command_name,
+                                pkid,
+                                invoker_id.as_deref().unwrap_or("None"),
+                            );
+                            response_arguments.status_code = StatusCode::BadRequest;
+                            response_arguments.status_message = Some(e.to_string());
+                            response_arguments.invalid_property_name =
+                                Some(ProtocolReservedUserProperty::PayloadChecksum.to_string());
+                            response_arguments.invalid_property_value = Some(e.expected);
+                            break 'process_request;
+                        }
+
                         let topic_tokens = self
                             .request_topic_pattern
                             .parse_tokens(m.topic_name.as_str());
 
+                        // Reject content types outside of the allow list before deserializing
+                        if let Some(content_type_allow_list) = &self.content_type_allow_list
+                            && !content_type_allow_list.accepts(properties.content_type.as_ref())
+                        {
+                            log::warn!(
+                                "[{}][pkid: {}] Rejecting command request from invoker {} with unaccepted content type {:?}",
+                                self.command_name,
+                                pkid,
+                                invoker_id.as_deref().unwrap_or("None"),
+                                properties.content_type
+                            );
+                            response_arguments.status_code = StatusCode::UnsupportedMediaType;
+                            response_arguments.status_message = Some(format!(
+                                "Content type {:?} is not accepted by the command executor",
+                                properties.content_type
+                            ));
+                            response_arguments.invalid_property_name =
+                                Some("Content Type".to_string());
+                            response_arguments.invalid_property_value =
+                                Some(properties.content_type.unwrap_or("None".to_string()));
+                            break 'process_request;
+                        }
+
                         // Deserialize payload
                         let format_indicator = properties.payload_format_indicator.into();
                         let payload = match TReq::deserialize(
@@ -1340,6 +2501,26 @@ where
                             },
                         };
 
+                        // Claim the request across the service group before executing it
+                        if let Some(correlation_data) = &response_arguments.correlation_data
+                            && let Some(message_expiry_interval) =
+                                response_arguments.message_expiry_interval
+                        {
+                            distributed_claim = self
+                                .distributed_claim(correlation_data, message_expiry_interval, pkid)
+                                .await;
+                            if let DistributedClaim::Reject(_) = distributed_claim {
+                                response_arguments.status_code = StatusCode::ServiceUnavailable;
+                                response_arguments.status_message = Some(
+                                    "Request could not be claimed by this executor in the service group"
+                                        .to_string(),
+                                );
+                            }
+                            if !matches!(distributed_claim, DistributedClaim::Execute) {
+                                break 'process_request;
+                            }
+                        }
+
                         let (response_tx, response_rx) = oneshot::channel();
                         let (publish_completion_tx, publish_completion_rx) = oneshot::channel();
 
@@ -1351,10 +2532,22 @@ where
                             timestamp,
                             invoker_id,
                             topic_tokens,
+                            no_response,
+                            duplicate,
+                            response_topic: received_response_topic,
+                            correlation_data: received_correlation_data,
+                            subscription_identifiers,
+                            topic_alias,
                             responder: Responder {
                                 command_name: self.command_name.clone(),
+                                is_idempotent: self.is_idempotent,
                                 response_tx,
                                 publish_completion_rx,
+                                // Processing completes when the request expires, the response is
+                                // published, or the executor is dropped
+                                cancellation_token: processing_completed.child_token(),
+                                expiration_time: command_expiration_time,
+                                counters: self.cache.counters.clone(),
                             },
                         };
 
@@ -1363,7 +2556,7 @@ where
                             // Elapsed returns zero if the time has not passed
                             tokio::task::spawn({
                                 let app_hlc_clone = self.application_hlc.clone();
-                                let client_clone = self.mqtt_client.clone();
+                                let client_clone = self.response_client.clone();
                                 let cache_clone = self.cache.clone();
                                 let executor_cancellation_token_clone =
                                     self.cancellation_token.clone();
@@ -1385,6 +2578,17 @@ where
                                     }
                                 }
                             });
+                            if let Some(request_serializer) = &self.request_serializer {
+                                match request_serializer.serialize(
+                                    command_request,
+                                    processing_completed,
+                                    self.cancellation_token.clone(),
+                                ) {
+                                    Some(command_request) => return Some(Ok(command_request)),
+                                    // Queued until the prior requests with the same token value complete
+                                    None => continue 'receive,
+                                }
+                            }
                             return Some(Ok(command_request));
                         }
                     }
@@ -1395,15 +2599,46 @@ where
                         continue;
                     };
 
+                    // Another executor in the service group claimed the request and will respond,
+                    // so only acknowledge it
+                    if let DistributedClaim::Ignore = distributed_claim {
+                        drop(processing_drop_guard);
+                        tokio::task::spawn(handle_ack(
+                            ack_token,
+                            self.cancellation_token.clone(),
+                            pkid,
+                        ));
+                        continue;
+                    }
+                    let response_delay = match distributed_claim {
+                        DistributedClaim::Reject(delay) => delay,
+                        _ => Duration::ZERO,
+                    };
+
                     match response_arguments.cache_lookup_result {
                         CacheLookupResult::Cached {
                             serialized_payload,
                             properties,
                             response_message_expiry_interval,
+                            qos,
                         } => {
+                            // Nothing to resend for a duplicate fire-and-forget request
+                            if response_arguments.no_response {
+                                log::debug!(
+                                    "[{}][pkid: {}] Duplicate request without response",
+                                    self.command_name,
+                                    pkid
+                                );
+                                tokio::task::spawn(handle_ack(
+                                    ack_token,
+                                    self.cancellation_token.clone(),
+                                    pkid,
+                                ));
+                                continue;
+                            }
                             // Process the duplicate command
                             tokio::task::spawn({
-                                let client_clone = self.mqtt_client.clone();
+                                let client_clone = self.response_client.clone();
                                 let executor_cancellation_token_clone =
                                     self.cancellation_token.clone();
                                 async move {
@@ -1411,10 +2646,12 @@ where
                                         () = executor_cancellation_token_clone.cancelled() => { /* executor dropped */},
                                         () = Self::process_duplicate_command(
                                             client_clone,
+                                            response_arguments.response_client_monitor,
                                             response_arguments.response_topic,
                                             serialized_payload,
                                             properties,
                                             response_message_expiry_interval,
+                                            qos,
                                             response_arguments.command_name,
                                             pkid,
                                         ) => {
@@ -1441,24 +2678,36 @@ where
 
                             // Check the command has not expired, if it has, we do not respond to the invoker.
                             if command_expiration_time.elapsed().is_zero() {
+                                // Error responses are not published earlier than the uniform delay
+                                // after the request was received, so their latency doesn't reveal
+                                // the failure, but no later than the request expires
+                                let mut respond_at = clock::now() + response_delay;
+                                if let Some(uniform_delay) = self.uniform_error_response_delay {
+                                    respond_at =
+                                        respond_at.max(message_received_time + uniform_delay);
+                                }
+                                let respond_at = respond_at.min(command_expiration_time);
                                 tokio::task::spawn({
                                     let app_hlc_clone = self.application_hlc.clone();
-                                    let client_clone = self.mqtt_client.clone();
+                                    let client_clone = self.response_client.clone();
                                     let cache_clone = self.cache.clone();
                                     let executor_cancellation_token_clone =
                                         self.cancellation_token.clone();
                                     async move {
                                         tokio::select! {
                                             () = executor_cancellation_token_clone.cancelled() => { /* executor dropped */},
-                                            () = Self::process_command(
-                                                app_hlc_clone,
-                                                client_clone,
-                                                pkid,
-                                                response_arguments,
-                                                (None, None),
-                                                cache_clone,
-                                                processing_drop_guard,
-                                            ) => {
+                                            () = async {
+                                                tokio::time::sleep_until(respond_at).await;
+                                                Self::process_command(
+                                                    app_hlc_clone,
+                                                    client_clone,
+                                                    pkid,
+                                                    response_arguments,
+                                                    (None, None),
+                                                    cache_clone,
+                                                    processing_drop_guard,
+                                                ).await;
+                                            } => {
                                                 // Finished processing command
                                                 handle_ack(ack_token, executor_cancellation_token_clone, pkid).await;
                                             },
@@ -1482,20 +2731,82 @@ where
                     }
                 }
                 _ => {
-                    // There will be no more requests
-                    return None;
+                    // There will be no more requests, but queued requests are still returned
+                    return self.recv_queued().await;
+                }
+            }
+        }
+    }
+
+    /// Receives the next queued request once no more requests will be received, or [`None`] if
+    /// there are no more queued requests.
+    async fn recv_queued(&mut self) -> Option<Result<Request<TReq, TResp>, AIOProtocolError>> {
+        let request_serializer = self.request_serializer.as_mut()?;
+        request_serializer.ready_tx = None;
+        request_serializer.ready_rx.recv().await.map(Ok)
+    }
+
+    /// Claim a command request with the [`DistributedDedupStore`], if distributed deduplication
+    /// is configured, to determine if this executor should execute it.
+    async fn distributed_claim(
+        &self,
+        correlation_data: &Bytes,
+        message_expiry_interval: u32,
+        pkid: u16,
+    ) -> DistributedClaim {
+        let Some((service_group_id, distributed_dedup)) = &self.distributed_dedup else {
+            return DistributedClaim::Execute;
+        };
+        let key = distributed_dedup_key(service_group_id, &self.command_name, correlation_data);
+        // The claim must outlive any redelivery of the request
+        let ttl = Duration::from_secs(u64::from(message_expiry_interval))
+            .saturating_add(self.cache_expiry_buffer);
+        match distributed_dedup.store.try_claim(key, ttl).await {
+            Ok(true) => DistributedClaim::Execute,
+            Ok(false) => {
+                log::info!(
+                    "[{}][pkid: {}] Request already claimed by another executor in the service group",
+                    self.command_name,
+                    pkid
+                );
+                match distributed_dedup.duplicate_behavior {
+                    DistributedDuplicateBehavior::Ignore => DistributedClaim::Ignore,
+                    DistributedDuplicateBehavior::RespondAfter(delay) => {
+                        DistributedClaim::Reject(delay)
+                    }
                 }
             }
+            Err(e) => match distributed_dedup.failure_policy {
+                DistributedDedupFailurePolicy::FailOpen => {
+                    log::warn!(
+                        "[{}][pkid: {}] Unable to claim request with distributed dedup store, falling back to local deduplication: {e}",
+                        self.command_name,
+                        pkid
+                    );
+                    DistributedClaim::Execute
+                }
+                DistributedDedupFailurePolicy::FailClosed => {
+                    log::warn!(
+                        "[{}][pkid: {}] Unable to claim request with distributed dedup store, request will not be executed: {e}",
+                        self.command_name,
+                        pkid
+                    );
+                    DistributedClaim::Reject(Duration::ZERO)
+                }
+            },
         }
     }
 
     /// Process a duplicate command by sending the cached response.
+    #[allow(clippy::too_many_arguments)]
     async fn process_duplicate_command(
         client: SessionManagedClient,
+        client_monitor: Option<SessionMonitor>,
         response_topic: TopicName,
         serialized_payload: SerializedPayload,
         mut publish_properties: PublishProperties,
         response_message_expiry_interval: u32,
+        qos: QoS,
         command_name: String,
         pkid: u16,
     ) {
@@ -1505,6 +2816,39 @@ where
 
         publish_properties.message_expiry_interval = Some(response_message_expiry_interval);
 
+        if client_monitor.is_some_and(|monitor| !monitor.is_connected()) {
+            log::warn!(
+                "[{command_name}][pkid: {pkid}] Response client is disconnected, cached command response not published"
+            );
+            return;
+        }
+
+        if qos == QoS::AtMostOnce {
+            match client
+                .publish_qos0(
+                    response_topic,
+                    false,
+                    serialized_payload.payload,
+                    publish_properties,
+                )
+                .await
+            {
+                Ok(publish_completion_token) => {
+                    if let Err(e) = publish_completion_token.await {
+                        log::warn!(
+                            "[{command_name}][pkid: {pkid}] Publish completion error for cached command response: {e}"
+                        );
+                    }
+                }
+                Err(e) => {
+                    log::warn!(
+                        "[{command_name}][pkid: {pkid}] Client error on cached command response publish: {e}"
+                    );
+                }
+            }
+            return;
+        }
+
         match client
             .publish_qos1(
                 response_topic,
@@ -1554,10 +2898,17 @@ where
         _processing_drop_guard: DropGuard,
     ) {
         let (response_rx, completion_tx) = application_channels;
-        let mut serialized_payload = SerializedPayload::default();
+        // Replaced by the serialized response if the application responds, otherwise the error
+        // response has no payload but still needs a valid content type
+        let mut serialized_payload = SerializedPayload {
+            content_type: response_arguments.error_response_content_type.clone(),
+            ..SerializedPayload::default()
+        };
         let mut publish_properties = PublishProperties::default();
 
         let mut user_properties: Vec<(String, String)> = Vec::new();
+        let mut response_qos = QoS::AtLeastOnce;
+        let mut message_expiry_override = None;
         'process_response: {
             let Some(command_expiration_time) = response_arguments.command_expiration_time else {
                 break 'process_response;
@@ -1565,19 +2916,51 @@ where
             if let Some(response_rx) = response_rx {
                 // Wait for response
                 let response = if let Ok(response_timer) = timeout(
-                    command_expiration_time.duration_since(Instant::now()),
+                    command_expiration_time.duration_since(clock::now()),
                     response_rx,
                 )
                 .await
                 {
                     if let Ok(response_app) = response_timer {
                         response_app
+                    } else if clock::now() >= command_expiration_time {
+                        // The sender was dropped by the application once the request expired
+                        // (e.g. a held request that was not completed in time), which is handled
+                        // like a timeout rather than like a dropped request
+                        log::warn!(
+                            "[{}][pkid: {}] Command request dropped after expiring",
+                            response_arguments.command_name,
+                            pkid
+                        );
+                        return;
                     } else {
                         // Happens when the sender is dropped by the application.
-                        response_arguments.status_code = StatusCode::InternalServerError;
-                        response_arguments.status_message =
-                            Some("Request has been dropped by the application".to_string());
-                        response_arguments.is_application_error = true;
+                        match std::mem::take(&mut response_arguments.dropped_request_response) {
+                            DroppedRequestResponse::InternalServerError => {
+                                response_arguments.status_code = StatusCode::InternalServerError;
+                                response_arguments.status_message =
+                                    Some("Request has been dropped by the application".to_string());
+                                response_arguments.is_application_error = true;
+                            }
+                            DroppedRequestResponse::ApplicationError(application_error) => {
+                                log::warn!(
+                                    "[{}][pkid: {}] Request has been dropped by the application, sending application error {}",
+                                    response_arguments.command_name,
+                                    pkid,
+                                    application_error.code
+                                );
+                                response_arguments.status_code = StatusCode::NoContent;
+                                user_properties.extend(application_error.into_headers());
+                            }
+                            DroppedRequestResponse::None => {
+                                log::warn!(
+                                    "[{}][pkid: {}] Request has been dropped by the application, no response will be sent",
+                                    response_arguments.command_name,
+                                    pkid
+                                );
+                                return;
+                            }
+                        }
                         break 'process_response;
                     }
                 } else {
@@ -1606,6 +2989,10 @@ where
                 };
 
                 user_properties = response.custom_user_data;
+                response_qos = response.qos;
+                message_expiry_override = response
+                    .message_expiry_override
+                    .map(|o| u32::try_from(o.as_secs()).unwrap_or(u32::MAX));
 
                 // Cloud Events headers
                 if let Some(cloud_event) = response.cloud_event {
@@ -1709,6 +3096,13 @@ where
             String::new(),
         ));
 
+        if response_arguments.payload_checksum {
+            user_properties.push((
+                ProtocolReservedUserProperty::PayloadChecksum.to_string(),
+                payload_checksum::checksum(&serialized_payload.payload),
+            ));
+        }
+
         // Create publish properties
         publish_properties.payload_format_indicator = serialized_payload.format_indicator.into();
         publish_properties.topic_alias = None;
@@ -1726,7 +3120,12 @@ where
                 // Check if the entry has expired
                 if let Some(response_message_expiry_interval) = response_message_expiry_interval {
                     publish_properties.message_expiry_interval =
-                        Some(response_message_expiry_interval);
+                        Some(apply_message_expiry_override(
+                            response_message_expiry_interval,
+                            message_expiry_override,
+                            &response_arguments.command_name,
+                            pkid,
+                        ));
                 } else {
                     log::warn!(
                         "[{}][pkid: {}] Command request timed out",
@@ -1758,7 +3157,9 @@ where
                         serialized_payload: serialized_payload.clone(),
                         properties: publish_properties.clone(),
                         expiration_time: command_expiration_time
-                            + Duration::from_secs(CACHE_EXPIRY_BUFFER_SECONDS),
+                            + response_arguments.cache_expiry_buffer,
+                        qos: response_qos,
+                        message_expiry_override,
                     };
                     log::debug!(
                         "[{}][pkid: {}] Caching response",
@@ -1772,9 +3173,103 @@ where
                 // Happens when the command expiration time was not able to be calculated.
                 // We don't cache the response in this case. Note that we did not set a in progress
                 // entry for this case since it requires a valid expiration time.
-                publish_properties.message_expiry_interval =
-                    Some(DEFAULT_MESSAGE_EXPIRY_INTERVAL_SECONDS);
+                publish_properties.message_expiry_interval = Some(apply_message_expiry_override(
+                    response_arguments.default_message_expiry_interval,
+                    message_expiry_override,
+                    &response_arguments.command_name,
+                    pkid,
+                ));
+            }
+        }
+
+        if response_arguments.no_response {
+            log::debug!(
+                "[{}][pkid: {}] Invoker does not expect a response, command response will not be published",
+                response_arguments.command_name,
+                pkid
+            );
+            if let Some(completion_tx) = completion_tx {
+                // Ignore error as receiver may have been dropped
+                let _ = completion_tx.send(Ok(()));
+            }
+            return;
+        }
+
+        // The response is still cached, so it is published if the request is redelivered once the
+        // response client reconnects
+        if response_arguments
+            .response_client_monitor
+            .as_ref()
+            .is_some_and(|monitor| !monitor.is_connected())
+        {
+            log::error!(
+                "[{}][pkid: {}] Response client is disconnected, command response not published",
+                response_arguments.command_name,
+                pkid
+            );
+            cache.counters.response_published(false);
+            if let Some(completion_tx) = completion_tx {
+                // Ignore error as receiver may have been dropped
+                let _ = completion_tx.send(Err(AIOProtocolError::new_state_invalid_error(
+                    "response_client",
+                    None,
+                    Some(
+                        "Response client is disconnected, the command response cannot be published"
+                            .to_string(),
+                    ),
+                    Some(response_arguments.command_name.clone()),
+                )));
+            }
+            return;
+        }
+
+        if response_qos == QoS::AtMostOnce {
+            match client
+                .publish_qos0(
+                    response_arguments.response_topic,
+                    false,
+                    serialized_payload.payload,
+                    publish_properties,
+                )
+                .await
+            {
+                Ok(publish_completion_token) => {
+                    let result = publish_completion_token.await.map_err(|e| {
+                        log::error!(
+                            "[{}][pkid: {}] Command response Publish completion error: {e}",
+                            response_arguments.command_name,
+                            pkid
+                        );
+                        AIOProtocolError::new_mqtt_error(
+                            Some("MQTT error on command executor response publish".to_string()),
+                            Box::new(e),
+                            Some(response_arguments.command_name.clone()),
+                        )
+                    });
+                    cache.counters.response_published(result.is_ok());
+                    if let Some(completion_tx) = completion_tx {
+                        // Ignore error as receiver may have been dropped
+                        let _ = completion_tx.send(result);
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "[{}][pkid: {}] Client error on command executor response publish: {e}",
+                        response_arguments.command_name,
+                        pkid
+                    );
+                    cache.counters.response_published(false);
+                    if let Some(completion_tx) = completion_tx {
+                        // Ignore error as receiver may have been dropped
+                        let _ = completion_tx.send(Err(AIOProtocolError::new_mqtt_error(
+                            Some("MQTT error on command executor response publish".to_string()),
+                            Box::new(e),
+                            Some(response_arguments.command_name.clone()),
+                        )));
+                    }
+                }
             }
+            return;
         }
 
         // Try to publish
@@ -1793,6 +3288,7 @@ where
                     Ok(puback) => {
                         match puback.as_result() {
                             Ok(()) => {
+                                cache.counters.response_published(true);
                                 if let Some(completion_tx) = completion_tx {
                                     // We ignore the error as the receiver may have been dropped indicating that the
                                     // application is not interested in the completion of the publish.
@@ -1805,6 +3301,7 @@ where
                                     response_arguments.command_name,
                                     pkid
                                 );
+                                cache.counters.response_published(false);
                                 if let Some(completion_tx) = completion_tx {
                                     // Ignore error as receiver may have been dropped
                                     let _ =
@@ -1826,6 +3323,7 @@ where
                             response_arguments.command_name,
                             pkid
                         );
+                        cache.counters.response_published(false);
                         if let Some(completion_tx) = completion_tx {
                             // Ignore error as receiver may have been dropped
                             let _ = completion_tx.send(Err(AIOProtocolError::new_mqtt_error(
@@ -1843,6 +3341,7 @@ where
                     response_arguments.command_name,
                     pkid
                 );
+                cache.counters.response_published(false);
                 // Notify error publishing
                 if let Some(completion_tx) = completion_tx {
                     // Ignore error as receiver may have been dropped
@@ -1898,10 +3397,30 @@ where
     }
 }
 
+/// Applies the message expiry override of a [`Response`] to the message expiry interval derived
+/// from the command expiration. Overrides beyond the derived interval are clamped to it.
+fn apply_message_expiry_override(
+    message_expiry_interval: u32,
+    message_expiry_override: Option<u32>,
+    command_name: &str,
+    pkid: u16,
+) -> u32 {
+    match message_expiry_override {
+        Some(message_expiry_override) if message_expiry_override > message_expiry_interval => {
+            log::warn!(
+                "[{command_name}][pkid: {pkid}] Response message expiry override of {message_expiry_override}s exceeds the remaining {message_expiry_interval}s until the command expires, clamping to {message_expiry_interval}s"
+            );
+            message_expiry_interval
+        }
+        Some(message_expiry_override) => message_expiry_override,
+        None => message_expiry_interval,
+    }
+}
+
 fn get_response_message_expiry_interval(command_expiration_time: Instant) -> Option<u32> {
     // Calculate the remaining time until the command expires
     let response_message_expiry_interval =
-        command_expiration_time.saturating_duration_since(Instant::now());
+        command_expiration_time.saturating_duration_since(clock::now());
 
     // Check if the entry has expired
     if response_message_expiry_interval.is_zero() {
@@ -1993,7 +3512,10 @@ mod tests {
 
     use super::*;
     use crate::application::ApplicationContextBuilder;
-    use crate::common::{aio_protocol_error::AIOProtocolErrorKind, payload_serialize::MockPayload};
+    use crate::common::{
+        aio_protocol_error::AIOProtocolErrorKind,
+        payload_serialize::{DESERIALIZE_MTX, MockPayload},
+    };
 
     // TODO: This should return a mock ManagedClient instead.
     // Until that's possible, need to return a Session so that the Session doesn't go out of
@@ -2038,10 +3560,20 @@ mod tests {
             timestamp: None,
             invoker_id: Some("test_invoker_id".to_string()),
             topic_tokens: HashMap::from([("commandName".to_string(), "test".to_string())]),
+            no_response: false,
+            duplicate: false,
+            response_topic: None,
+            correlation_data: None,
+            subscription_identifiers: Vec::new(),
+            topic_alias: None,
             responder: Responder {
                 command_name: "test_command_name".to_string(),
+                is_idempotent: false,
                 response_tx,
                 publish_completion_rx,
+                cancellation_token: CancellationToken::new(),
+                expiration_time: clock::now() + Duration::from_secs(10),
+                counters: Arc::default(),
             },
         };
 
@@ -2049,6 +3581,14 @@ mod tests {
     }
 
     fn build_test_response() -> Response<MockPayload> {
+        build_test_response_with(QoS::AtLeastOnce, None).unwrap()
+    }
+
+    /// Builds a [`Response`] with the given QoS and message expiry override for testing
+    fn build_test_response_with(
+        qos: QoS,
+        message_expiry_override: Option<Duration>,
+    ) -> Result<Response<MockPayload>, ResponseBuilderError> {
         let mut mock_response_payload = MockPayload::new();
         mock_response_payload
             .expect_serialize()
@@ -2061,11 +3601,15 @@ mod tests {
             })
             .times(1);
 
-        ResponseBuilder::default()
+        let mut response_builder = ResponseBuilder::default();
+        response_builder
             .payload(mock_response_payload)
             .unwrap()
-            .build()
-            .unwrap()
+            .qos(qos);
+        if let Some(message_expiry_override) = message_expiry_override {
+            response_builder.message_expiry_override(message_expiry_override);
+        }
+        response_builder.build()
     }
 
     #[tokio::test]
@@ -2096,6 +3640,7 @@ mod tests {
         );
 
         assert!(!executor.is_idempotent);
+        assert_eq!(executor.stats(), ExecutorStats::default());
     }
 
     #[tokio::test]
@@ -2236,22 +3781,585 @@ mod tests {
         }
     }
 
+    struct MockDistributedDedupStore;
+
+    #[async_trait::async_trait]
+    impl DistributedDedupStore for MockDistributedDedupStore {
+        async fn try_claim(
+            &self,
+            _key: String,
+            _ttl: Duration,
+        ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(true)
+        }
+    }
+
+    #[test_case(Some("test_service_group"), true; "with service group")]
+    #[test_case(None, false; "without service group")]
     #[tokio::test]
-    async fn test_shutdown_without_subscribe() {
+    async fn test_new_distributed_dedup(service_group_id: Option<&str>, expect_ok: bool) {
         let session = create_session();
-        let executor_options = OptionsBuilder::default()
-            .request_topic_pattern("test/request")
+        let managed_client = session.create_managed_client();
+        let mut executor_options_builder = OptionsBuilder::default();
+        executor_options_builder
+            .request_topic_pattern("test/{commandName}/{executorId}/request")
             .command_name("test_command_name")
-            .build()
-            .unwrap();
-        let mut executor: Executor<MockPayload, MockPayload> = Executor::new(
+            .topic_token_map(create_topic_tokens())
+            .distributed_dedup(
+                DistributedDedupOptionsBuilder::default()
+                    .store(MockDistributedDedupStore)
+                    .build()
+                    .unwrap(),
+            );
+        if let Some(service_group_id) = service_group_id {
+            executor_options_builder.service_group_id(service_group_id);
+        }
+        let executor_options = executor_options_builder.build().unwrap();
+
+        let executor: Result<Executor<MockPayload, MockPayload>, AIOProtocolError> = Executor::new(
             ApplicationContextBuilder::default().build().unwrap(),
-            session.create_managed_client(),
+            managed_client,
             executor_options,
-        )
-        .unwrap();
-        assert!(executor.shutdown().await.is_ok());
-    }
+        );
+        match executor {
+            Ok(executor) => {
+                assert!(expect_ok);
+                let (service_group, distributed_dedup) =
+                    executor.distributed_dedup.as_ref().unwrap();
+                assert_eq!(service_group, "test_service_group");
+                assert_eq!(
+                    distributed_dedup.duplicate_behavior,
+                    DistributedDuplicateBehavior::Ignore
+                );
+                assert_eq!(
+                    distributed_dedup.failure_policy,
+                    DistributedDedupFailurePolicy::FailOpen
+                );
+            }
+            Err(e) => {
+                assert!(!expect_ok);
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(e.property_name, Some("service_group_id".to_string()));
+            }
+        }
+    }
+
+    #[test_case(None, Some(DEFAULT_ERROR_RESPONSE_CONTENT_TYPE); "default")]
+    #[test_case(Some("text/plain"), Some("text/plain"); "custom")]
+    #[test_case(Some(""), None; "empty")]
+    #[test_case(Some("text/plain\u{0000}"), None; "invalid character")]
+    #[tokio::test]
+    async fn test_new_error_response_content_type(
+        error_response_content_type: Option<&str>,
+        expected: Option<&str>,
+    ) {
+        let session = create_session();
+        let managed_client = session.create_managed_client();
+        let mut executor_options_builder = OptionsBuilder::default();
+        executor_options_builder
+            .request_topic_pattern("test/request")
+            .command_name("test_command_name");
+        if let Some(error_response_content_type) = error_response_content_type {
+            executor_options_builder.error_response_content_type(error_response_content_type);
+        }
+        let executor_options = executor_options_builder.build().unwrap();
+
+        let executor: Result<Executor<MockPayload, MockPayload>, AIOProtocolError> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            executor_options,
+        );
+        match (executor, expected) {
+            (Ok(executor), Some(expected)) => {
+                assert_eq!(executor.error_response_content_type, expected);
+            }
+            (Err(e), None) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(
+                    e.property_name,
+                    Some("error_response_content_type".to_string())
+                );
+            }
+            _ => panic!("Unexpected result for {error_response_content_type:?}"),
+        }
+    }
+
+    #[test_case(None, None, Ok((10, Duration::from_secs(60))); "defaults")]
+    #[test_case(Some(Duration::from_secs(30)), None, Ok((30, Duration::from_secs(60))); "executor default message expiry")]
+    #[test_case(Some(Duration::from_millis(2_500)), None, Ok((3, Duration::from_secs(60))); "default message expiry rounded up")]
+    #[test_case(None, Some(Duration::ZERO), Ok((10, Duration::ZERO)); "executor cache expiry buffer")]
+    #[test_case(Some(Duration::from_millis(500)), None, Err("default_message_expiry"); "default message expiry too short")]
+    #[test_case(Some(Duration::from_secs(3_601)), None, Err("default_message_expiry"); "default message expiry too long")]
+    #[test_case(None, Some(Duration::from_secs(601)), Err("cache_expiry_buffer"); "cache expiry buffer too long")]
+    #[tokio::test]
+    async fn test_new_expiry_settings(
+        default_message_expiry: Option<Duration>,
+        cache_expiry_buffer: Option<Duration>,
+        expected: Result<(u32, Duration), &str>,
+    ) {
+        let session = create_session();
+        let managed_client = session.create_managed_client();
+        let mut executor_options_builder = OptionsBuilder::default();
+        executor_options_builder
+            .request_topic_pattern("test/request")
+            .command_name("test_command_name");
+        if let Some(default_message_expiry) = default_message_expiry {
+            executor_options_builder.default_message_expiry(default_message_expiry);
+        }
+        if let Some(cache_expiry_buffer) = cache_expiry_buffer {
+            executor_options_builder.cache_expiry_buffer(cache_expiry_buffer);
+        }
+        let executor_options = executor_options_builder.build().unwrap();
+
+        let executor: Result<Executor<MockPayload, MockPayload>, AIOProtocolError> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            executor_options,
+        );
+        match (executor, expected) {
+            (Ok(executor), Ok((default_message_expiry_interval, cache_expiry_buffer))) => {
+                assert_eq!(
+                    executor.default_message_expiry_interval,
+                    default_message_expiry_interval
+                );
+                assert_eq!(executor.cache_expiry_buffer, cache_expiry_buffer);
+            }
+            (Err(e), Err(property_name)) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(e.property_name, Some(property_name.to_string()));
+            }
+            _ => {
+                panic!("Unexpected result for {default_message_expiry:?}, {cache_expiry_buffer:?}")
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_expiry_settings_from_application_context() {
+        let session = create_session();
+        let executor: Executor<MockPayload, MockPayload> = Executor::new(
+            ApplicationContextBuilder::default()
+                .default_message_expiry(Duration::from_secs(20))
+                .cache_expiry_buffer(Duration::from_secs(120))
+                .build()
+                .unwrap(),
+            session.create_managed_client(),
+            OptionsBuilder::default()
+                .request_topic_pattern("test/request")
+                .command_name("test_command_name")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(executor.default_message_expiry_interval, 20);
+        assert_eq!(executor.cache_expiry_buffer, Duration::from_secs(120));
+    }
+
+    #[test_case(&["application/json"], true; "exact")]
+    #[test_case(&[" Application/JSON "], true; "mixed_case_and_whitespace")]
+    #[test_case(&["application/*", "*/*"], true; "wildcards")]
+    #[test_case(&["application"], false; "missing_subtype")]
+    #[test_case(&["/json"], false; "missing_type")]
+    #[test_case(&["application/"], false; "empty_subtype")]
+    #[test_case(&["application/json/x"], false; "extra_separator")]
+    #[test_case(&["application/json\u{0000}"], false; "invalid_character")]
+    #[tokio::test]
+    async fn test_new_accepted_content_types(accepted_content_types: &[&str], expect_ok: bool) {
+        let session = create_session();
+        let managed_client = session.create_managed_client();
+        let executor_options = OptionsBuilder::default()
+            .request_topic_pattern("test/request")
+            .command_name("test_command_name")
+            .accepted_content_types(
+                accepted_content_types
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            )
+            .build()
+            .unwrap();
+
+        let executor: Result<Executor<MockPayload, MockPayload>, AIOProtocolError> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            executor_options,
+        );
+        match executor {
+            Ok(executor) => {
+                assert!(expect_ok);
+                assert!(executor.content_type_allow_list.is_some());
+            }
+            Err(e) => {
+                assert!(!expect_ok);
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(e.property_name, Some("accepted_content_types".to_string()));
+            }
+        }
+    }
+
+    #[test_case(Some("application/json"), true; "exact")]
+    #[test_case(Some("APPLICATION/Json"), true; "case_insensitive")]
+    #[test_case(Some("application/json; charset=utf-8"), true; "parameters_ignored")]
+    #[test_case(Some("text/csv"), true; "wildcard_subtype")]
+    #[test_case(Some("text"), false; "wildcard_without_subtype")]
+    #[test_case(Some("application/xml"), false; "not_accepted")]
+    #[test_case(Some("application/json+x"), false; "subtype_prefix_not_accepted")]
+    #[test_case(None, true; "missing")]
+    fn test_content_type_allow_list_accepts(content_type: Option<&str>, expected: bool) {
+        let content_type_allow_list = ContentTypeAllowList {
+            accepted_content_types: vec!["application/json".to_string(), "text/*".to_string()],
+            allow_missing_content_type: true,
+        };
+        assert_eq!(
+            content_type_allow_list.accepts(content_type.map(ToString::to_string).as_ref()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_content_type_allow_list_any_type_wildcard() {
+        let content_type_allow_list = ContentTypeAllowList {
+            accepted_content_types: vec!["*/*".to_string()],
+            allow_missing_content_type: false,
+        };
+        assert!(content_type_allow_list.accepts(Some(&"image/png".to_string())));
+        assert!(!content_type_allow_list.accepts(None));
+    }
+
+    #[test_case(Some("application/json"), true, true; "exact_match")]
+    #[test_case(Some("Application/Cbor"), true, true; "wildcard_match")]
+    #[test_case(Some("text/plain"), true, false; "not_accepted")]
+    #[test_case(None, true, true; "missing_allowed")]
+    #[test_case(None, false, false; "missing_rejected")]
+    #[tokio::test]
+    async fn test_recv_accepted_content_types(
+        content_type: Option<&str>,
+        allow_missing_content_type: bool,
+        expect_accepted: bool,
+    ) {
+        use azure_iot_operations_mqtt::{
+            azure_mqtt::mqtt_proto,
+            test_utils::{
+                IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx,
+            },
+        };
+
+        // Get mutexes for checking static PayloadSerialize calls
+        let _deserialize_mutex = DESERIALIZE_MTX.lock();
+
+        let incoming_packets_tx = IncomingPacketsTx::default();
+        let outgoing_packets_rx = OutgoingPacketsRx::default();
+        let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+        let session = Session::new(
+            SessionOptionsBuilder::default()
+                .connection_settings(
+                    MqttConnectionSettingsBuilder::default()
+                        .hostname("localhost")
+                        .client_id("test_server")
+                        .build()
+                        .unwrap(),
+                )
+                .injected_packet_channels(Some(InjectedPacketChannels {
+                    incoming_packets_tx,
+                    outgoing_packets_rx,
+                }))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        let managed_client = session.create_managed_client();
+        tokio::task::spawn(session.run());
+        mock_server.expect_connect_and_accept(true).await;
+
+        let mut executor: Executor<MockPayload, MockPayload> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            OptionsBuilder::default()
+                .request_topic_pattern("test/request")
+                .command_name("test_command_name")
+                .accepted_content_types(vec![
+                    "application/json".to_string(),
+                    "application/*".to_string(),
+                ])
+                .allow_missing_content_type(allow_missing_content_type)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        // The allow list is evaluated before the payload is deserialized
+        let mock_payload_deserialize_ctx = MockPayload::deserialize_context();
+        if expect_accepted {
+            mock_payload_deserialize_ctx
+                .expect()
+                .returning(|_, _, _| Ok(MockPayload::default()))
+                .once();
+        } else {
+            mock_payload_deserialize_ctx.expect().never();
+        }
+
+        let request_publish = mqtt_proto::Publish {
+            topic_name: mqtt_proto::topic("test/request"),
+            packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                false,
+            ),
+            retain: false,
+            payload: Bytes::from_static(b"{}"),
+            other_properties: mqtt_proto::PublishOtherProperties {
+                message_expiry_interval: Some(10),
+                correlation_data: Some([1u8; 16].as_slice().into()),
+                response_topic: Some(mqtt_proto::topic("test/response")),
+                content_type: content_type.map(Into::into),
+                user_properties: vec![
+                    ("__protVer".into(), "1.0".into()),
+                    ("__srcId".into(), "test_invoker_id".into()),
+                ],
+                ..Default::default()
+            },
+        };
+
+        if expect_accepted {
+            let (request, ()) = tokio::join!(executor.recv(), async {
+                mock_server.expect_subscribe_and_accept().await;
+                mock_server.send_publish(request_publish);
+            });
+            let request = request.unwrap().unwrap();
+            assert_eq!(request.content_type.as_deref(), content_type);
+        } else {
+            let (recv_result, response) = tokio::join!(
+                tokio::time::timeout(Duration::from_millis(500), executor.recv()),
+                async {
+                    mock_server.expect_subscribe_and_accept().await;
+                    mock_server.send_publish(request_publish);
+                    mock_server.expect_publish().await
+                }
+            );
+            // The rejected request is never delivered to the application
+            assert!(recv_result.is_err());
+
+            let user_property = |key: ProtocolReservedUserProperty| {
+                response
+                    .other_properties
+                    .user_properties
+                    .iter()
+                    .find(|(k, _)| k.as_ref() == key.to_string())
+                    .map(|(_, v)| v.as_ref().to_string())
+            };
+            assert_eq!(response.topic_name.as_str(), "test/response");
+            assert_eq!(
+                user_property(ProtocolReservedUserProperty::Status),
+                Some((StatusCode::UnsupportedMediaType as u16).to_string())
+            );
+            assert_eq!(
+                user_property(ProtocolReservedUserProperty::InvalidPropertyName),
+                Some("Content Type".to_string())
+            );
+            assert_eq!(
+                user_property(ProtocolReservedUserProperty::InvalidPropertyValue),
+                Some(content_type.unwrap_or("None").to_string())
+            );
+        }
+    }
+
+    #[test_case(false; "first_delivery")]
+    #[test_case(true; "redelivery")]
+    #[tokio::test]
+    async fn test_recv_duplicate_flag(dup: bool) {
+        use azure_iot_operations_mqtt::{
+            azure_mqtt::mqtt_proto,
+            test_utils::{
+                IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx,
+            },
+        };
+
+        // Get mutexes for checking static PayloadSerialize calls
+        let _deserialize_mutex = DESERIALIZE_MTX.lock();
+
+        let incoming_packets_tx = IncomingPacketsTx::default();
+        let outgoing_packets_rx = OutgoingPacketsRx::default();
+        let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+        let session = Session::new(
+            SessionOptionsBuilder::default()
+                .connection_settings(
+                    MqttConnectionSettingsBuilder::default()
+                        .hostname("localhost")
+                        .client_id("test_server")
+                        .build()
+                        .unwrap(),
+                )
+                .injected_packet_channels(Some(InjectedPacketChannels {
+                    incoming_packets_tx,
+                    outgoing_packets_rx,
+                }))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        let managed_client = session.create_managed_client();
+        tokio::task::spawn(session.run());
+        mock_server.expect_connect_and_accept(true).await;
+
+        let mut executor: Executor<MockPayload, MockPayload> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            OptionsBuilder::default()
+                .request_topic_pattern("test/request")
+                .command_name("test_command_name")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let mock_payload_deserialize_ctx = MockPayload::deserialize_context();
+        mock_payload_deserialize_ctx
+            .expect()
+            .returning(|_, _, _| Ok(MockPayload::default()))
+            .once();
+
+        // Redeliveries are always delivered, since the executor's cache handles duplicates of
+        // requests that were already completed
+        let (request, ()) = tokio::join!(executor.recv(), async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(mqtt_proto::Publish {
+                topic_name: mqtt_proto::topic("test/request"),
+                packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                    mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                    dup,
+                ),
+                retain: false,
+                payload: Bytes::from_static(b"{}"),
+                other_properties: mqtt_proto::PublishOtherProperties {
+                    message_expiry_interval: Some(10),
+                    correlation_data: Some([1u8; 16].as_slice().into()),
+                    response_topic: Some(mqtt_proto::topic("test/response")),
+                    user_properties: vec![("__protVer".into(), "1.0".into())],
+                    ..Default::default()
+                },
+            });
+        });
+        let request = request.unwrap().unwrap();
+        assert_eq!(request.duplicate, dup);
+        let (parts, _responder) = request.into_parts();
+        assert_eq!(parts.duplicate, dup);
+    }
+
+    #[tokio::test]
+    async fn test_recv_publish_properties() {
+        use azure_iot_operations_mqtt::{
+            azure_mqtt::mqtt_proto,
+            test_utils::{
+                IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx,
+            },
+        };
+
+        // Get mutexes for checking static PayloadSerialize calls
+        let _deserialize_mutex = DESERIALIZE_MTX.lock();
+
+        let incoming_packets_tx = IncomingPacketsTx::default();
+        let outgoing_packets_rx = OutgoingPacketsRx::default();
+        let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+        let session = Session::new(
+            SessionOptionsBuilder::default()
+                .connection_settings(
+                    MqttConnectionSettingsBuilder::default()
+                        .hostname("localhost")
+                        .client_id("test_server")
+                        .build()
+                        .unwrap(),
+                )
+                .injected_packet_channels(Some(InjectedPacketChannels {
+                    incoming_packets_tx,
+                    outgoing_packets_rx,
+                }))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        let managed_client = session.create_managed_client();
+        tokio::task::spawn(session.run());
+        mock_server.expect_connect_and_accept(true).await;
+
+        let mut executor: Executor<MockPayload, MockPayload> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            OptionsBuilder::default()
+                .request_topic_pattern("test/request")
+                .command_name("test_command_name")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let mock_payload_deserialize_ctx = MockPayload::deserialize_context();
+        mock_payload_deserialize_ctx
+            .expect()
+            .returning(|_, _, _| Ok(MockPayload::default()))
+            .once();
+
+        let (request, ()) = tokio::join!(executor.recv(), async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(mqtt_proto::Publish {
+                topic_name: mqtt_proto::topic("test/request"),
+                packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                    mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                    false,
+                ),
+                retain: false,
+                payload: Bytes::from_static(b"{}"),
+                other_properties: mqtt_proto::PublishOtherProperties {
+                    message_expiry_interval: Some(10),
+                    topic_alias: Some(3.try_into().unwrap()),
+                    correlation_data: Some([1u8; 16].as_slice().into()),
+                    response_topic: Some(mqtt_proto::topic("test/response")),
+                    user_properties: vec![("__protVer".into(), "1.0".into())],
+                    subscription_identifiers: vec![5.try_into().unwrap(), 7.try_into().unwrap()],
+                    ..Default::default()
+                },
+            });
+        });
+        let request = request.unwrap().unwrap();
+        assert_eq!(request.response_topic.as_deref(), Some("test/response"));
+        assert_eq!(
+            request.correlation_data,
+            Some(Bytes::from_static(&[1u8; 16]))
+        );
+        assert_eq!(request.subscription_identifiers, vec![5, 7]);
+        assert_eq!(request.topic_alias, Some(3));
+        let (parts, _responder) = request.into_parts();
+        assert_eq!(parts.response_topic.as_deref(), Some("test/response"));
+        assert_eq!(parts.subscription_identifiers, vec![5, 7]);
+    }
+
+    #[test]
+    fn test_distributed_dedup_key() {
+        let correlation_data = Bytes::from(vec![
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0xff,
+        ]);
+        assert_eq!(
+            distributed_dedup_key("group", "command", &correlation_data),
+            "aio-executor-dedup:group:command:000102030405060708090a0b0c0d0eff"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_without_subscribe() {
+        let session = create_session();
+        let executor_options = OptionsBuilder::default()
+            .request_topic_pattern("test/request")
+            .command_name("test_command_name")
+            .build()
+            .unwrap();
+        let mut executor: Executor<MockPayload, MockPayload> = Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            executor_options,
+        )
+        .unwrap();
+        assert!(executor.shutdown().await.is_ok());
+    }
 
     // Command Response tests
     #[test]
@@ -2305,6 +4413,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_response_serialization_cbor_utf8_format_indicator_error() {
+        let mut mock_response_payload = MockPayload::new();
+        mock_response_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: vec![0xa1, 0x61, 0x61, 0xff],
+                    content_type: "application/cbor".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let mut binding = ResponseBuilder::default();
+        let resp_builder = binding.payload(mock_response_payload);
+        match resp_builder {
+            Err(e) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert!(e.is_shallow);
+                assert!(!e.is_remote);
+                assert_eq!(e.property_name, Some("format_indicator".to_string()));
+            }
+            Ok(_) => {
+                panic!("Expected error");
+            }
+        }
+    }
+
     #[test]
     fn test_response_invalid_custom_user_data_cloud_event_header() {
         let mut mock_response_payload = MockPayload::new();
@@ -2352,11 +4489,74 @@ mod tests {
         assert!(r.custom_user_data.is_empty());
         assert!(r.cloud_event.is_none());
         assert!(r.serialized_payload.payload.is_empty());
+        assert_eq!(r.qos, QoS::AtLeastOnce);
+        assert!(r.message_expiry_override.is_none());
+    }
+
+    #[test]
+    fn test_response_qos_exactly_once_error() {
+        assert!(matches!(
+            build_test_response_with(QoS::ExactlyOnce, None),
+            Err(ResponseBuilderError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_response_message_expiry_override_zero_error() {
+        assert!(matches!(
+            build_test_response_with(QoS::AtLeastOnce, Some(Duration::ZERO)),
+            Err(ResponseBuilderError::ValidationError(_))
+        ));
+    }
+
+    #[test_case(Duration::from_secs(2), Duration::from_secs(2); "whole seconds")]
+    #[test_case(Duration::from_millis(1_500), Duration::from_secs(2); "rounded up")]
+    #[test_case(Duration::from_millis(1), Duration::from_secs(1); "rounded up to one second")]
+    fn test_response_message_expiry_override(
+        message_expiry_override: Duration,
+        expected: Duration,
+    ) {
+        let r = build_test_response_with(QoS::AtMostOnce, Some(message_expiry_override)).unwrap();
+        assert_eq!(r.qos, QoS::AtMostOnce);
+        assert_eq!(r.message_expiry_override, Some(expected));
+    }
+
+    #[test_case(10, None, 10; "no override")]
+    #[test_case(10, Some(5), 5; "shorter override")]
+    #[test_case(10, Some(10), 10; "equal override")]
+    #[test_case(10, Some(30), 10; "longer override clamped")]
+    fn test_apply_message_expiry_override(
+        message_expiry_interval: u32,
+        message_expiry_override: Option<u32>,
+        expected: u32,
+    ) {
+        assert_eq!(
+            apply_message_expiry_override(
+                message_expiry_interval,
+                message_expiry_override,
+                "test_command_name",
+                1
+            ),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_at_most_once_not_idempotent_error() {
+        let (request, _response_rx, _publish_completion_tx) =
+            build_test_request(MockPayload::new());
+
+        let error = request
+            .complete(build_test_response_with(QoS::AtMostOnce, None).unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+        assert_eq!(error.property_name, Some("qos".to_string()));
     }
 
     #[tokio::test]
     async fn test_cache_not_found() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+        let cache = Cache::default();
         let key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2365,9 +4565,9 @@ mod tests {
         assert!(matches!(status, CacheLookupResult::NotFound));
     }
 
-    #[test]
-    fn test_cache_found_complete() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_found_complete() {
+        let cache = Cache::default();
         let key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2380,7 +4580,9 @@ mod tests {
         let entry = CacheEntry::Cached {
             serialized_payload: entered_serialized_payload.clone(),
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() + Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         cache.set(key.clone(), entry.clone());
         let status = cache.get(&key);
@@ -2389,13 +4591,12 @@ mod tests {
                 serialized_payload,
                 properties,
                 response_message_expiry_interval,
+                ..
             } => {
                 assert_eq!(serialized_payload, entered_serialized_payload);
                 assert_eq!(properties, PublishProperties::default());
-                // The expiry interval should be between 1 and 60 seconds, 60 seconds should not pass
-                // before this check is made and we will always round up to the nearest second.
-                let range = 1..=60;
-                assert!(range.contains(&response_message_expiry_interval));
+                // No time has passed since the entry was set
+                assert_eq!(response_message_expiry_interval, 60);
             }
             _ => {
                 panic!("Expected cached entry");
@@ -2403,9 +4604,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_cache_found_in_progress() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_found_in_progress() {
+        let cache = Cache::default();
         let key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2423,9 +4624,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_cache_expired_entry_not_found() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_expired_entry_not_found() {
+        let cache = Cache::default();
         let key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2437,9 +4638,15 @@ mod tests {
                 format_indicator: FormatIndicator::Utf8EncodedCharacterData,
             },
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() - Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         cache.set(key.clone(), entry);
+        assert!(matches!(cache.get(&key), CacheLookupResult::Cached { .. }));
+
+        // The entry expires exactly at its expiration time
+        tokio::time::advance(Duration::from_secs(60)).await;
         let status = cache.get(&key);
         assert!(matches!(status, CacheLookupResult::NotFound));
 
@@ -2452,7 +4659,9 @@ mod tests {
         let new_entry = CacheEntry::Cached {
             serialized_payload: new_serialized_payload.clone(),
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() + Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         // The cache should never see another entry with the same key, this is for testing purposes only.
         cache.set(key.clone(), new_entry.clone());
@@ -2463,13 +4672,12 @@ mod tests {
                 serialized_payload,
                 properties,
                 response_message_expiry_interval,
+                ..
             } => {
                 assert_eq!(serialized_payload, new_serialized_payload);
                 assert_eq!(properties, PublishProperties::default());
-                // The expiry interval should be between 1 and 60 seconds, 60 seconds should not pass
-                // before this check is made and we will always round up to the nearest second.
-                let range = 1..=60;
-                assert!(range.contains(&response_message_expiry_interval));
+                // No time has passed since the entry was set
+                assert_eq!(response_message_expiry_interval, 60);
             }
             _ => {
                 panic!("Expected cached entry");
@@ -2477,9 +4685,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_cache_expired_entry_not_found_with_different_key_set() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_expired_entry_not_found_with_different_key_set() {
+        let cache = Cache::default();
         let old_key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2491,9 +4699,18 @@ mod tests {
                 format_indicator: FormatIndicator::Utf8EncodedCharacterData,
             },
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() - Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         cache.set(old_key.clone(), old_entry);
+        assert!(matches!(
+            cache.get(&old_key),
+            CacheLookupResult::Cached { .. }
+        ));
+
+        // The entry expires exactly at its expiration time
+        tokio::time::advance(Duration::from_secs(60)).await;
         let status = cache.get(&old_key);
         assert!(matches!(status, CacheLookupResult::NotFound));
 
@@ -2510,7 +4727,9 @@ mod tests {
         let new_entry = CacheEntry::Cached {
             serialized_payload: new_serialized_payload.clone(),
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() + Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         cache.set(new_key.clone(), new_entry.clone());
 
@@ -2523,13 +4742,12 @@ mod tests {
                 serialized_payload,
                 properties,
                 response_message_expiry_interval,
+                ..
             } => {
                 assert_eq!(serialized_payload, new_serialized_payload);
                 assert_eq!(properties, PublishProperties::default());
-                // The expiry interval should be between 1 and 60 seconds, 60 seconds should not pass
-                // before this check is made and we will always round up to the nearest second.
-                let range = 1..=60;
-                assert!(range.contains(&response_message_expiry_interval));
+                // No time has passed since the entry was set
+                assert_eq!(response_message_expiry_interval, 60);
             }
             _ => {
                 panic!("Expected cached entry");
@@ -2537,9 +4755,9 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_cache_in_progress_found_with_different_key_set() {
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_in_progress_found_with_different_key_set() {
+        let cache = Cache::default();
         let old_key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
             correlation_data: Bytes::from("test_correlation_data"),
@@ -2565,7 +4783,9 @@ mod tests {
         let new_entry = CacheEntry::Cached {
             serialized_payload: new_serialized_payload.clone(),
             properties: PublishProperties::default(),
-            expiration_time: Instant::now() + Duration::from_secs(60),
+            expiration_time: clock::now() + Duration::from_secs(60),
+            qos: QoS::AtLeastOnce,
+            message_expiry_override: None,
         };
         cache.set(new_key.clone(), new_entry.clone());
 
@@ -2578,13 +4798,12 @@ mod tests {
                 serialized_payload,
                 properties,
                 response_message_expiry_interval,
+                ..
             } => {
                 assert_eq!(serialized_payload, new_serialized_payload);
                 assert_eq!(properties, PublishProperties::default());
-                // The expiry interval should be between 1 and 60 seconds, 60 seconds should not pass
-                // before this check is made and we will always round up to the nearest second.
-                let range = 1..=60;
-                assert!(range.contains(&response_message_expiry_interval));
+                // No time has passed since the entry was set
+                assert_eq!(response_message_expiry_interval, 60);
             }
             _ => {
                 panic!("Expected cached entry");
@@ -2592,10 +4811,10 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_cache_in_progress_notified_completion() {
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_in_progress_notified_completion() {
         // This tests the verified flow of registering to completion in case a dupe comes in
-        let cache = Cache(Arc::new(Mutex::new(HashMap::new())));
+        let cache = Cache::default();
         let processing_cancellation_token = CancellationToken::new();
         let key = CacheKey {
             response_topic: TopicName::new("test_response_topic").unwrap(),
@@ -2686,34 +4905,606 @@ mod tests {
         assert_eq!(custom_user_data.len(), 0);
     }
 
-    #[test]
-    fn test_get_response_message_expiry_interval_not_expired() {
+    #[tokio::test(start_paused = true)]
+    async fn test_get_response_message_expiry_interval_not_expired() {
         let response_message_expiry_interval =
-            get_response_message_expiry_interval(Instant::now() + Duration::from_secs(10));
+            get_response_message_expiry_interval(clock::now() + Duration::from_secs(10));
 
-        let range = 1..=10;
-        // Should be between 1 and 10 seconds
-        assert!(range.contains(&response_message_expiry_interval.unwrap()));
+        assert_eq!(response_message_expiry_interval, Some(10));
     }
 
-    #[test]
-    fn test_get_response_message_expiry_inteval_expired() {
+    #[tokio::test(start_paused = true)]
+    async fn test_get_response_message_expiry_inteval_expired() {
+        let command_expiration_time = clock::now() + Duration::from_secs(10);
+        tokio::time::advance(Duration::from_secs(20)).await;
+
         let response_message_expiry_interval =
-            get_response_message_expiry_interval(Instant::now() - Duration::from_secs(10));
+            get_response_message_expiry_interval(command_expiration_time);
 
         assert!(response_message_expiry_interval.is_none());
     }
 
-    #[test]
-    fn test_get_response_message_expiry_interval_at_limit() {
+    #[tokio::test(start_paused = true)]
+    async fn test_get_response_message_expiry_interval_at_limit() {
         // When the message_expiry_interval property is received it is bounded to a u32 meaning that the
         // maximum value is u32::MAX seconds. We test that the function correctly handles this upper limit.
         let response_message_expiry_interval = get_response_message_expiry_interval(
-            Instant::now() + Duration::from_secs(u64::from(u32::MAX)),
+            clock::now() + Duration::from_secs(u64::from(u32::MAX)),
+        );
+
+        assert_eq!(response_message_expiry_interval, Some(u32::MAX));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_get_response_message_expiry_interval_rounds_up() {
+        let command_expiration_time = clock::now() + Duration::from_secs(10);
+        assert_eq!(
+            get_response_message_expiry_interval(command_expiration_time),
+            Some(10)
+        );
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(
+            get_response_message_expiry_interval(command_expiration_time),
+            Some(10)
+        );
+
+        tokio::time::advance(Duration::from_millis(999)).await;
+        assert_eq!(
+            get_response_message_expiry_interval(command_expiration_time),
+            Some(9)
+        );
+
+        tokio::time::advance(Duration::from_millis(8_999)).await;
+        assert_eq!(
+            get_response_message_expiry_interval(command_expiration_time),
+            Some(1)
+        );
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(
+            get_response_message_expiry_interval(command_expiration_time),
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_stats_hits_and_dupes() {
+        let cache = Cache::default();
+        assert_eq!(cache.counters.snapshot(), ExecutorStats::default());
+
+        let cached_key = test_cache_key();
+        let in_progress_key = CacheKey {
+            response_topic: TopicName::new("test_response_topic").unwrap(),
+            correlation_data: Bytes::from("in_progress_correlation_data"),
+        };
+        cache.set(
+            cached_key.clone(),
+            CacheEntry::Cached {
+                serialized_payload: SerializedPayload::default(),
+                properties: PublishProperties::default(),
+                expiration_time: clock::now() + Duration::from_secs(60),
+                qos: QoS::AtLeastOnce,
+                message_expiry_override: None,
+            },
+        );
+        cache.set(
+            in_progress_key.clone(),
+            CacheEntry::InProgress {
+                processing_cancellation_token: CancellationToken::new(),
+            },
+        );
+
+        assert!(matches!(
+            cache.get(&cached_key),
+            CacheLookupResult::Cached { .. }
+        ));
+        assert!(matches!(
+            cache.get(&cached_key),
+            CacheLookupResult::Cached { .. }
+        ));
+        assert!(matches!(
+            cache.get(&in_progress_key),
+            CacheLookupResult::InProgress(_)
+        ));
+
+        // Lookups of unknown or expired entries are not hits
+        let unknown_key = CacheKey {
+            response_topic: TopicName::new("test_response_topic").unwrap(),
+            correlation_data: Bytes::from("unknown_correlation_data"),
+        };
+        assert!(matches!(
+            cache.get(&unknown_key),
+            CacheLookupResult::NotFound
+        ));
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(matches!(
+            cache.get(&cached_key),
+            CacheLookupResult::NotFound
+        ));
+
+        assert_eq!(
+            cache.counters.snapshot(),
+            ExecutorStats {
+                cache_hits: 2,
+                in_progress_dupes: 1,
+                cache_entries: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_stats_expired_entries_evicted() {
+        let cache = Cache::default();
+        let processing_cancellation_token = CancellationToken::new();
+        cache.set(
+            test_cache_key(),
+            CacheEntry::Cached {
+                serialized_payload: SerializedPayload::default(),
+                properties: PublishProperties::default(),
+                expiration_time: clock::now() + Duration::from_secs(60),
+                qos: QoS::AtLeastOnce,
+                message_expiry_override: None,
+            },
+        );
+        cache.set(
+            CacheKey {
+                response_topic: TopicName::new("test_response_topic").unwrap(),
+                correlation_data: Bytes::from("in_progress_correlation_data"),
+            },
+            CacheEntry::InProgress {
+                processing_cancellation_token: processing_cancellation_token.clone(),
+            },
+        );
+        assert_eq!(cache.counters.snapshot().cache_entries, 2);
+        assert_eq!(cache.counters.snapshot().expired_entries_evicted, 0);
+
+        // The cached entry expires and the in progress entry is abandoned, so both are evicted
+        // on the next set
+        tokio::time::advance(Duration::from_secs(61)).await;
+        processing_cancellation_token.cancel();
+        cache.set(
+            CacheKey {
+                response_topic: TopicName::new("test_response_topic").unwrap(),
+                correlation_data: Bytes::from("new_correlation_data"),
+            },
+            CacheEntry::InProgress {
+                processing_cancellation_token: CancellationToken::new(),
+            },
         );
 
-        let range = 1..=u32::MAX; // note, range is memory efficient
-        assert!(range.contains(&response_message_expiry_interval.unwrap()));
+        let stats = cache.counters.snapshot();
+        assert_eq!(stats.expired_entries_evicted, 2);
+        assert_eq!(stats.cache_entries, 1);
+    }
+
+    #[test]
+    fn test_stats_responses_published() {
+        let cache = Cache::default();
+        cache.counters.response_published(true);
+        cache.counters.response_published(true);
+        cache.counters.response_published(false);
+
+        // Counters are shared by clones of the cache
+        let stats = cache.clone().counters.snapshot();
+        assert_eq!(stats.responses_published, 2);
+        assert_eq!(stats.responses_failed, 1);
+    }
+
+    fn test_cache_key() -> CacheKey {
+        CacheKey {
+            response_topic: TopicName::new("test_response_topic").unwrap(),
+            correlation_data: Bytes::from("test_correlation_data"),
+        }
+    }
+
+    /// Builds the [`ResponseArguments`] of a request that expires after `message_expiry_interval`
+    fn build_test_response_arguments(message_expiry_interval: u32) -> ResponseArguments {
+        ResponseArguments {
+            command_name: "test_command_name".to_string(),
+            response_topic: TopicName::new("test_response_topic").unwrap(),
+            correlation_data: Some(Bytes::from("test_correlation_data")),
+            status_code: StatusCode::Ok,
+            status_message: None,
+            is_application_error: false,
+            invalid_property_name: None,
+            invalid_property_value: None,
+            command_expiration_time: Some(
+                clock::now() + Duration::from_secs(message_expiry_interval.into()),
+            ),
+            message_expiry_interval: Some(message_expiry_interval),
+            supported_protocol_major_versions: None,
+            request_protocol_version: None,
+            cached_key: Some(test_cache_key()),
+            cache_lookup_result: CacheLookupResult::NotFound,
+            no_response: false,
+            error_response_content_type: DEFAULT_ERROR_RESPONSE_CONTENT_TYPE.to_string(),
+            dropped_request_response: DroppedRequestResponse::default(),
+            default_message_expiry_interval: 10,
+            cache_expiry_buffer: Duration::from_secs(60),
+            payload_checksum: false,
+            response_client_monitor: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_times_out_at_command_expiration() {
+        let session = create_session();
+        let cache = Cache::default();
+        let processing_cancellation_token = CancellationToken::new();
+        let (_response_tx, response_rx) = oneshot::channel();
+        let (completion_tx, mut completion_rx) = oneshot::channel();
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                build_test_response_arguments(5),
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                processing_cancellation_token.clone().drop_guard(),
+            ));
+
+        // Still waiting for the application just before the command expires
+        tokio::time::advance(Duration::from_millis(4_999)).await;
+        tokio::task::yield_now().await;
+        assert!(completion_rx.try_recv().is_err());
+        assert!(!processing_cancellation_token.is_cancelled());
+
+        // Timed out as soon as the command expires
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let error = completion_rx.await.unwrap().unwrap_err();
+        assert_eq!(error.kind, AIOProtocolErrorKind::Timeout);
+        assert_eq!(error.timeout_value, Some(Duration::from_secs(5)));
+
+        process_task.await.unwrap();
+        assert!(processing_cancellation_token.is_cancelled());
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::NotFound
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_error_response_content_type() {
+        let session = create_session();
+        let cache = Cache::default();
+        let (response_tx, response_rx) = oneshot::channel::<Response<MockPayload>>();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+        let mut response_arguments = build_test_response_arguments(5);
+        response_arguments.error_response_content_type = "text/plain".to_string();
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                response_arguments,
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                CancellationToken::new().drop_guard(),
+            ));
+
+        // The application drops the request without responding, so an error response is sent
+        drop(response_tx);
+
+        // Wait for the error response to be cached
+        let mut cached_properties = None;
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+            if let CacheLookupResult::Cached { properties, .. } = cache.get(&test_cache_key()) {
+                cached_properties = Some(properties);
+                break;
+            }
+        }
+        // The publish can't complete without a running session
+        process_task.abort();
+
+        let properties = cached_properties.expect("Expected cached entry");
+        assert_eq!(properties.content_type, Some("text/plain".to_string()));
+        assert!(properties.user_properties.contains(&(
+            ProtocolReservedUserProperty::Status.to_string(),
+            (StatusCode::InternalServerError as u16).to_string()
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_dropped_request_application_error() {
+        let session = create_session();
+        let cache = Cache::default();
+        let (response_tx, response_rx) = oneshot::channel::<Response<MockPayload>>();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+        let application_error = ApplicationErrorBuilder::default()
+            .code("RequestAbandoned")
+            .build()
+            .unwrap();
+        let mut response_arguments = build_test_response_arguments(5);
+        response_arguments.dropped_request_response =
+            DroppedRequestResponse::ApplicationError(application_error.clone());
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                response_arguments,
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                CancellationToken::new().drop_guard(),
+            ));
+
+        drop(response_tx);
+
+        // Wait for the response to be cached
+        let mut cached_properties = None;
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+            if let CacheLookupResult::Cached { properties, .. } = cache.get(&test_cache_key()) {
+                cached_properties = Some(properties);
+                break;
+            }
+        }
+        // The publish can't complete without a running session
+        process_task.abort();
+
+        let properties = cached_properties.expect("Expected cached entry");
+        assert!(properties.user_properties.contains(&(
+            ProtocolReservedUserProperty::Status.to_string(),
+            (StatusCode::NoContent as u16).to_string()
+        )));
+        assert!(
+            !properties
+                .user_properties
+                .iter()
+                .any(|(key, _)| *key == ProtocolReservedUserProperty::StatusMessage.to_string())
+        );
+        assert_eq!(
+            ApplicationError::from_headers(&properties.user_properties),
+            Some(application_error)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_dropped_request_no_response() {
+        let session = create_session();
+        let cache = Cache::default();
+        let processing_cancellation_token = CancellationToken::new();
+        let (response_tx, response_rx) = oneshot::channel::<Response<MockPayload>>();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+        let mut response_arguments = build_test_response_arguments(5);
+        response_arguments.dropped_request_response = DroppedRequestResponse::None;
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                response_arguments,
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                processing_cancellation_token.clone().drop_guard(),
+            ));
+
+        drop(response_tx);
+
+        // Processing finishes without publishing or caching a response
+        process_task.await.unwrap();
+        assert!(processing_cancellation_token.is_cancelled());
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::NotFound
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_caches_response_until_expiry_buffer() {
+        let session = create_session();
+        let cache = Cache::default();
+        let processing_cancellation_token = CancellationToken::new();
+        let (response_tx, response_rx) = oneshot::channel();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                build_test_response_arguments(5),
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                processing_cancellation_token.drop_guard(),
+            ));
+
+        // Respond halfway through the command's lifetime
+        tokio::time::advance(Duration::from_millis(2_500)).await;
+        assert!(response_tx.send(build_test_response()).is_ok());
+
+        // Wait for the response to be cached
+        let mut cached = None;
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+            if let CacheLookupResult::Cached {
+                properties,
+                response_message_expiry_interval,
+                ..
+            } = cache.get(&test_cache_key())
+            {
+                cached = Some((properties, response_message_expiry_interval));
+                break;
+            }
+        }
+        // The publish can't complete without a running session
+        process_task.abort();
+
+        let (properties, response_message_expiry_interval) = cached.expect("Expected cached entry");
+        // 2.5 seconds remain until the command expires, rounded up
+        assert_eq!(properties.message_expiry_interval, Some(3));
+        // The cache entry outlives the command by the default cache expiry buffer
+        assert_eq!(response_message_expiry_interval, 63);
+
+        // Cached until exactly the cache expiry buffer after the command expires
+        tokio::time::advance(Duration::from_millis(62_499)).await;
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::Cached {
+                response_message_expiry_interval: 1,
+                ..
+            }
+        ));
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::NotFound
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_caches_response_until_configured_expiry_buffer() {
+        let session = create_session();
+        let cache = Cache::default();
+        let processing_cancellation_token = CancellationToken::new();
+        let (response_tx, response_rx) = oneshot::channel();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+        let mut response_arguments = build_test_response_arguments(5);
+        response_arguments.cache_expiry_buffer = Duration::from_secs(5);
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                response_arguments,
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                processing_cancellation_token.drop_guard(),
+            ));
+
+        assert!(response_tx.send(build_test_response()).is_ok());
+
+        // Wait for the response to be cached
+        let mut response_message_expiry_interval = None;
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+            if let CacheLookupResult::Cached {
+                response_message_expiry_interval: interval,
+                ..
+            } = cache.get(&test_cache_key())
+            {
+                response_message_expiry_interval = Some(interval);
+                break;
+            }
+        }
+        // The publish can't complete without a running session
+        process_task.abort();
+
+        // The cache entry outlives the command by the configured buffer
+        assert_eq!(response_message_expiry_interval, Some(10));
+        tokio::time::advance(Duration::from_millis(9_999)).await;
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::Cached { .. }
+        ));
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(matches!(
+            cache.get(&test_cache_key()),
+            CacheLookupResult::NotFound
+        ));
+    }
+
+    /// Runs [`Executor::process_command`] for a request that expires after 5 seconds with the given
+    /// response until the response is cached, returning the cache.
+    async fn process_command_until_cached(response: Response<MockPayload>) -> Cache {
+        let session = create_session();
+        let cache = Cache::default();
+        let (response_tx, response_rx) = oneshot::channel();
+        let (completion_tx, _completion_rx) = oneshot::channel();
+
+        let process_task =
+            tokio::task::spawn(Executor::<MockPayload, MockPayload>::process_command(
+                ApplicationContextBuilder::default()
+                    .build()
+                    .unwrap()
+                    .application_hlc,
+                session.create_managed_client(),
+                1,
+                build_test_response_arguments(5),
+                (Some(response_rx), Some(completion_tx)),
+                cache.clone(),
+                CancellationToken::new().drop_guard(),
+            ));
+        assert!(response_tx.send(response).is_ok());
+
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+            if matches!(
+                cache.get(&test_cache_key()),
+                CacheLookupResult::Cached { .. }
+            ) {
+                break;
+            }
+        }
+        // The publish can't complete without a running session
+        process_task.abort();
+        cache
+    }
+
+    #[test_case(QoS::AtLeastOnce, None, 5, 65; "no override")]
+    #[test_case(QoS::AtMostOnce, None, 5, 65; "at most once")]
+    #[test_case(QoS::AtLeastOnce, Some(Duration::from_secs(2)), 2, 2; "shorter override")]
+    #[test_case(QoS::AtMostOnce, Some(Duration::from_secs(30)), 5, 30; "longer override clamped")]
+    #[tokio::test(start_paused = true)]
+    async fn test_process_command_response_overrides(
+        qos: QoS,
+        message_expiry_override: Option<Duration>,
+        expected_message_expiry_interval: u32,
+        expected_cached_message_expiry_interval: u32,
+    ) {
+        let cache = process_command_until_cached(
+            build_test_response_with(qos, message_expiry_override).unwrap(),
+        )
+        .await;
+
+        let CacheLookupResult::Cached {
+            properties,
+            response_message_expiry_interval,
+            qos: cached_qos,
+            ..
+        } = cache.get(&test_cache_key())
+        else {
+            panic!("Expected cached entry");
+        };
+        // The response is published with the overrides applied
+        assert_eq!(
+            properties.message_expiry_interval,
+            Some(expected_message_expiry_interval)
+        );
+        // Duplicate requests are responded to with the same overrides, where the override
+        // applies to the cache entry's remaining lifetime rather than the command's
+        assert_eq!(cached_qos, qos);
+        assert_eq!(
+            response_message_expiry_interval,
+            expected_cached_message_expiry_interval
+        );
     }
 
     #[tokio::test]
@@ -2786,6 +5577,23 @@ mod tests {
         assert!(responder.is_cancelled());
     }
 
+    #[tokio::test]
+    async fn test_request_cancellation_token() {
+        let (request, _response_rx, _publish_completion_tx) =
+            build_test_request(MockPayload::new());
+        let handler_token = request.cancellation_token();
+
+        // Cancelling the handler's token does not cancel the request
+        handler_token.cancel();
+        assert!(!request.is_cancelled());
+
+        let handler_token = request.cancellation_token();
+        request.responder.cancellation_token.cancel();
+        assert!(request.is_cancelled());
+        assert!(handler_token.is_cancelled());
+        request.cancelled().await;
+    }
+
     #[tokio::test]
     async fn test_into_parts_dropping_responder_sends_no_response() {
         let (request, response_rx, _publish_completion_tx) = build_test_request(MockPayload::new());
@@ -2809,6 +5617,12 @@ mod tests {
             timestamp: None,
             invoker_id: None,
             topic_tokens: HashMap::new(),
+            no_response: false,
+            duplicate: false,
+            response_topic: None,
+            correlation_data: None,
+            subscription_identifiers: Vec::new(),
+            topic_alias: None,
         };
 
         assert!(cloud_event_from_request_parts(&parts).is_err());
@@ -2829,6 +5643,12 @@ mod tests {
             timestamp: None,
             invoker_id: None,
             topic_tokens: HashMap::new(),
+            no_response: false,
+            duplicate: false,
+            response_topic: None,
+            correlation_data: None,
+            subscription_identifiers: Vec::new(),
+            topic_alias: None,
         };
 
         let cloud_event =
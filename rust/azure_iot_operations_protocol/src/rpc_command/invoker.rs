@@ -1,7 +1,13 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use std::{collections::HashMap, marker::PhantomData, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use azure_iot_operations_mqtt::{
     aio::cloud_event as aio_cloud_event,
@@ -20,8 +26,10 @@ use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use crate::common::{
+    clock::{self, Instant},
     cloud_event as protocol_cloud_event,
     dispatcher::Dispatcher,
+    payload_checksum,
     user_properties::{BrokerReservedUserProperty, validate_invoker_user_properties},
 };
 use crate::{
@@ -33,6 +41,7 @@ use crate::{
         is_invalid_utf8,
         payload_serialize::{
             DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+            format_indicator_mismatch,
         },
         topic_processor::{TopicPattern, contains_invalid_char},
         user_properties::ProtocolReservedUserProperty,
@@ -41,6 +50,9 @@ use crate::{
     rpc_command::{
         DEFAULT_RPC_COMMAND_PROTOCOL_VERSION, DEFAULT_RPC_REQUEST_CLOUD_EVENT_EVENT_TYPE,
         RPC_COMMAND_PROTOCOL_VERSION, StatusCode, StatusCodeParseError,
+        application_error::{
+            APPLICATION_ERROR_CODE_HEADER, APPLICATION_ERROR_PAYLOAD_HEADER, ApplicationError,
+        },
     },
 };
 
@@ -72,9 +84,45 @@ where
     /// to give the executor information on when the invoke request might expire.
     #[builder(setter(custom))]
     timeout: Duration,
+    /// How long [`Invoker::invoke`] waits for the response, if it should wait longer than the
+    /// [`timeout`](RequestBuilder::timeout) used as the request's `message_expiry_interval`, e.g.
+    /// when requests may be queued by the broker for longer than the executor takes to respond.
+    /// Must not be shorter than the `timeout`. Not used by [`Invoker::invoke_no_response`].
+    /// Default is `None`, which waits for the `timeout`.
+    #[builder(default = "None", setter(into, strip_option))]
+    response_timeout: Option<Duration>,
     /// Cloud event of the request.
     #[builder(default = "None")]
     cloud_event: Option<RequestCloudEvent>,
+    /// Correlation id of the request, sent as the 16 byte correlation data of the request message
+    /// and used to match the response to the request. Must not be nil, and must not be in use by
+    /// another invocation in progress on the same [`Invoker`] (including invocations of clones of
+    /// this request). Default is a new random UUID.
+    #[builder(default = "Uuid::new_v4()")]
+    correlation_id: Uuid,
+    /// Whether the request is fire-and-forget, set with [`RequestBuilder::no_response`].
+    #[builder(setter(custom), default)]
+    no_response: bool,
+    /// Content type that the response must have, like an HTTP `Accept` header. It is checked
+    /// before the response payload is deserialized, comparing only the media type (i.e. ignoring
+    /// any parameters such as `charset`) without regard to case. Responses without a payload are
+    /// not checked. Default is `None`, which accepts any content type.
+    #[builder(default = "None", setter(into, strip_option))]
+    accept_content_type: Option<String>,
+    /// Instant at which the request was built, used for [`ResponseTiming`]
+    #[builder(private, default = "clock::now()")]
+    built_at: Instant,
+}
+
+impl<TReq: PayloadSerialize> Request<TReq> {
+    /// Returns the correlation id of the request, which will be sent as the correlation data of
+    /// the request message.
+    ///
+    /// This can be used to correlate logs with the executor before the invocation completes.
+    #[must_use]
+    pub fn correlation_id(&self) -> Uuid {
+        self.correlation_id
+    }
 }
 
 /// Cloud Event struct used for the Command Request.
@@ -196,6 +244,29 @@ impl RequestCloudEventBuilder {
         self.0.subject(value);
         self
     }
+    /// Extension attributes, keyed by attribute name. Names must only contain lowercase letters
+    /// and digits (e.g. `partitionkey` or `dataref`) and must not collide with the names of core
+    /// attributes. Replaces any extension attributes already added.
+    pub fn extensions<VALUE: Into<BTreeMap<String, String>>>(&mut self, value: VALUE) -> &mut Self {
+        self.0.extensions(value);
+        self
+    }
+    /// Add a single extension attribute, replacing any existing value for `name`. See
+    /// [`extensions`](Self::extensions) for the naming rules.
+    pub fn extension<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.0.extension(name, value);
+        self
+    }
+    /// Derive attributes from the source events that an aggregate event was produced from, such
+    /// as the earliest `time` and the combined `source`. Attributes that have already been set are
+    /// not derived, and the source must be set explicitly if the source events don't share one.
+    pub fn derive_from_sources<'a, I>(&mut self, sources: I) -> &mut Self
+    where
+        I: IntoIterator<Item = &'a aio_cloud_event::CloudEvent>,
+    {
+        self.0.derive_from_sources(sources);
+        self
+    }
 }
 
 impl<TReq: PayloadSerialize> RequestBuilder<TReq> {
@@ -228,6 +299,16 @@ impl<TReq: PayloadSerialize> RequestBuilder<TReq> {
                         None,
                     ));
                 }
+                // Validate format indicator is consistent with the content type
+                if let Some(mismatch) = format_indicator_mismatch(&serialized_payload) {
+                    return Err(AIOProtocolError::new_configuration_invalid_error(
+                        None,
+                        "format_indicator",
+                        Value::String(format!("{:?}", serialized_payload.format_indicator)),
+                        Some(mismatch),
+                        None,
+                    ));
+                }
                 self.serialized_payload = Some(serialized_payload);
                 self.payload_type = Some(PhantomData);
                 Ok(self)
@@ -235,6 +316,21 @@ impl<TReq: PayloadSerialize> RequestBuilder<TReq> {
         }
     }
 
+    /// Make the command request fire-and-forget: the executor will process it but not publish a
+    /// response. The request must be sent with [`Invoker::invoke_no_response`], which completes once
+    /// the broker has acknowledged the request.
+    ///
+    /// Like all command requests, delivery is at-least-once: the request may be delivered to the
+    /// executor more than once, e.g. if the executor reconnects before acknowledging it. Duplicates
+    /// received by the same executor within the request's timeout are not executed again, but a
+    /// duplicate received by a restarted executor may be. The request is not executed if its
+    /// timeout elapses before the executor receives it. There is no way for the invoker to know
+    /// whether or when the request was executed.
+    pub fn no_response(&mut self) -> &mut Self {
+        self.no_response = Some(true);
+        self
+    }
+
     /// Set the timeout for the command
     ///
     /// Note: Will be rounded up to the nearest second.
@@ -254,6 +350,9 @@ impl<TReq: PayloadSerialize> RequestBuilder<TReq> {
     /// Returns a `String` describing the error if
     ///     - any of `custom_user_data`'s keys or values are invalid utf-8 or the key is reserved
     ///     - timeout is zero or > `u32::max`
+    ///     - `response_timeout` is shorter than the timeout
+    ///     - `correlation_id` is nil
+    ///     - `accept_content_type` is empty or invalid utf-8
     fn validate(&self) -> Result<(), String> {
         if let Some(custom_user_data) = &self.custom_user_data {
             for (key, _) in custom_user_data {
@@ -275,6 +374,25 @@ impl<TReq: PayloadSerialize> RequestBuilder<TReq> {
                     return Err("Timeout in seconds must be less than or equal to u32::max to be used as message_expiry_interval".to_string());
                 }
             }
+            if let Some(Some(response_timeout)) = &self.response_timeout
+                && response_timeout < timeout
+            {
+                return Err(format!(
+                    "Response timeout {response_timeout:?} must not be shorter than the timeout {timeout:?}"
+                ));
+            }
+        }
+        if let Some(correlation_id) = &self.correlation_id
+            && correlation_id.is_nil()
+        {
+            return Err("Correlation id must not be nil".to_string());
+        }
+        if let Some(Some(accept_content_type)) = &self.accept_content_type
+            && (accept_content_type.trim().is_empty() || is_invalid_utf8(accept_content_type))
+        {
+            return Err(format!(
+                "Accepted content type '{accept_content_type}' must be a non-empty, valid UTF-8 string"
+            ));
         }
         // If there's a cloud event, make sure the content type is valid for the cloud event spec version
         if let Some(Some(cloud_event)) = &self.cloud_event
@@ -308,6 +426,99 @@ where
     pub timestamp: Option<HybridLogicalClock>,
     /// If present, contains the client ID of the executor of the command.
     pub executor_id: Option<String>,
+    /// Breakdown of where time was spent during the invocation.
+    /// [`None`] if [`response_timing`](OptionsBuilder::response_timing) is disabled on the [`Invoker`].
+    pub timing: Option<ResponseTiming>,
+}
+
+impl<TResp: PayloadSerialize> Response<TResp> {
+    /// Get the [`ApplicationError`] reported by the executor, if the response carries one.
+    ///
+    /// See [`ApplicationError::from_headers`].
+    #[must_use]
+    pub fn application_error(&self) -> Option<ApplicationError> {
+        ApplicationError::from_headers(&self.custom_user_data)
+    }
+}
+
+/// Breakdown of where time was spent during a command invocation.
+///
+/// Local durations are measured with a monotonic clock on the invoker. The executor processing
+/// time is estimated from the [`HybridLogicalClock`] timestamps of the request and response, and
+/// is therefore only as accurate as the synchronization between the invoker and executor clocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseTiming {
+    /// Time from the [`Request`] being built to it being published.
+    pub build_to_publish: Duration,
+    /// Time from the request being published to its PUBACK being received.
+    pub publish_to_puback: Duration,
+    /// Time from the request being published to the response being received.
+    pub request_sent_to_response_received: Duration,
+    /// Estimate of the time the executor spent processing the request, computed as the response
+    /// timestamp minus the request timestamp. [`None`] if the response has no timestamp.
+    ///
+    /// Since the executor's clock is advanced to at least the request timestamp, an executor clock
+    /// that is behind the invoker's results in an underestimate.
+    pub executor_processing_estimate: Option<Duration>,
+    /// `true` if the [`executor_processing_estimate`](Self::executor_processing_estimate) is
+    /// unreliable due to clock drift, i.e. if it exceeds the locally measured round trip.
+    pub clock_drift_detected: bool,
+}
+
+impl ResponseTiming {
+    /// Resolution of [`HybridLogicalClock`] timestamps on the wire
+    const TIMESTAMP_RESOLUTION: Duration = Duration::from_millis(1);
+
+    fn new(
+        built_at: Instant,
+        published_at: Instant,
+        puback_at: Instant,
+        response_received_at: Instant,
+        request_timestamp: Option<&HybridLogicalClock>,
+        response_timestamp: Option<&HybridLogicalClock>,
+    ) -> Self {
+        let request_sent_to_response_received =
+            response_received_at.saturating_duration_since(published_at);
+        let (executor_processing_estimate, clock_drift_detected) =
+            match (request_timestamp, response_timestamp) {
+                (Some(request_timestamp), Some(response_timestamp)) => {
+                    Self::estimate_executor_processing(
+                        request_timestamp,
+                        response_timestamp,
+                        request_sent_to_response_received,
+                    )
+                }
+                _ => (None, false),
+            };
+        Self {
+            build_to_publish: published_at.saturating_duration_since(built_at),
+            publish_to_puback: puback_at.saturating_duration_since(published_at),
+            request_sent_to_response_received,
+            executor_processing_estimate,
+            clock_drift_detected,
+        }
+    }
+
+    /// Returns the estimated executor processing time, and whether clock drift makes it unreliable.
+    fn estimate_executor_processing(
+        request_timestamp: &HybridLogicalClock,
+        response_timestamp: &HybridLogicalClock,
+        round_trip: Duration,
+    ) -> (Option<Duration>, bool) {
+        match response_timestamp
+            .timestamp
+            .duration_since(request_timestamp.timestamp)
+        {
+            // The executor can't have spent longer processing than the whole round trip took,
+            // allowing for the timestamps being truncated to their resolution
+            Ok(estimate) => (
+                Some(estimate),
+                estimate > round_trip + Self::TIMESTAMP_RESOLUTION,
+            ),
+            // The response can't precede the request unless the timestamps aren't from the same clocks
+            Err(_) => (Some(Duration::ZERO), true),
+        }
+    }
 }
 
 /// Cloud Event struct derived from the Command Response.
@@ -333,13 +544,12 @@ pub fn cloud_event_from_response<TResp: PayloadSerialize>(
 /// Returns a [`(Option<String>, Option<String>)`] tuple where:
 /// - the first element is the application error code (or [`None`] if not present), and
 /// - the second element is the application error payload (or [`None`] if not present).
+///
+/// Prefer [`Response::application_error`] or [`ApplicationError::from_headers`], which also parse the severity.
 #[must_use]
 pub fn application_error_headers(
     custom_user_data: &Vec<(String, String)>,
 ) -> (Option<String>, Option<String>) {
-    const APPLICATION_ERROR_CODE_HEADER: &str = "AppErrCode";
-    const APPLICATION_ERROR_PAYLOAD_HEADER: &str = "AppErrPayload";
-
     let mut app_error_code: Option<String> = None;
     let mut app_error_payload: Option<String> = None;
 
@@ -400,6 +610,7 @@ impl From<RemoteError> for AIOProtocolError {
             command_name: None, // Will need to update this after return
             protocol_version: Some(value.protocol_version.to_string()),
             supported_protocol_major_versions: value.supported_protocol_major_versions,
+            correlation_id: None,
         };
 
         match value.status_code {
@@ -479,7 +690,7 @@ where
     Err(RemoteError),
 }
 
-impl<TResp> TryFrom<Publish> for CommandResult<TResp>
+impl<TResp> CommandResult<TResp>
 where
     TResp: PayloadSerialize,
 {
@@ -489,9 +700,13 @@ where
     // NOTE 2: This of course would make moving to other error models more complex, but the
     // eventual implementation of a ChunkBuffer could get us around this by including the command
     // name on the buffer.
-    type Error = AIOProtocolError;
-
-    fn try_from(value: Publish) -> Result<CommandResult<TResp>, Self::Error> {
+    /// Validates and parses a response [`Publish`], rejecting it if its major protocol version is
+    /// not one of the `supported_protocol_versions`.
+    fn from_publish(
+        value: Publish,
+        supported_protocol_versions: &[u16],
+        accept_content_type: Option<&str>,
+    ) -> Result<CommandResult<TResp>, AIOProtocolError> {
         // NOTE: User properties are parsed out into a new HashMap because:
         // 1) It makes the code more readable/maintanable to do HashMap lookups
         // 2) When this logic is extracted to a ChunkBuffer, it will be more memory efficient as
@@ -512,6 +727,7 @@ where
             ProtocolReservedUserProperty::ProtocolVersion,
             ProtocolReservedUserProperty::SupportedMajorVersions,
             ProtocolReservedUserProperty::RequestProtocolVersion,
+            ProtocolReservedUserProperty::PayloadChecksum,
         ];
         let mut response_custom_user_data = vec![];
         let mut response_aio_data = HashMap::new();
@@ -552,7 +768,7 @@ where
                                 "Received a response with an unparsable protocol version number: {protocol_version}"
                             )),
                             protocol_version.clone(),
-                            SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+                            supported_protocol_versions.to_vec(),
                             None,
                             false,
                             false,
@@ -562,17 +778,34 @@ where
                 None => DEFAULT_RPC_COMMAND_PROTOCOL_VERSION,
             }
         };
-        if !protocol_version.is_supported(SUPPORTED_PROTOCOL_VERSIONS) {
+        if !protocol_version.is_supported(supported_protocol_versions) {
             return Err(AIOProtocolError::new_unsupported_version_error(
                 None,
                 protocol_version.to_string(),
-                SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+                supported_protocol_versions.to_vec(),
                 None,
                 false,
                 false,
             ));
         }
 
+        // Verify the payload against the checksum attached by the executor, if any
+        payload_checksum::verify(
+            &value.payload,
+            response_aio_data
+                .get(&ProtocolReservedUserProperty::PayloadChecksum)
+                .map(String::as_str),
+        )
+        .map_err(|e| {
+            AIOProtocolError::new_payload_invalid_error(
+                false,
+                false,
+                Some(Box::new(e)),
+                Some("Response payload does not match its checksum".to_string()),
+                None,
+            )
+        })?;
+
         // Check the status code.
         // We will use this to determine which data format to serialize to.
         let status_code = {
@@ -647,6 +880,24 @@ where
                     ));
                 }
 
+                if let Some(accept_content_type) = accept_content_type
+                    && matches!(status_code, StatusCode::Ok)
+                    && !content_type
+                        .as_deref()
+                        .is_some_and(|c| media_type_matches(c, accept_content_type))
+                {
+                    let content_type = content_type.unwrap_or("None".to_string());
+                    return Err(AIOProtocolError::new_header_invalid_error(
+                        "Content Type",
+                        &content_type,
+                        false,
+                        Some(format!(
+                            "Response content type '{content_type}' is not the accepted content type '{accept_content_type}'"
+                        )),
+                        None,
+                    ));
+                }
+
                 let payload = match TResp::deserialize(
                     &value.payload,
                     content_type.as_ref(),
@@ -680,6 +931,7 @@ where
                     custom_user_data: response_custom_user_data,
                     timestamp,
                     executor_id: response_aio_data.remove(&ProtocolReservedUserProperty::SourceId),
+                    timing: None,
                 })
             }
             // RemoteError
@@ -736,6 +988,20 @@ pub struct Options {
     /// based on the request topic in the form: `clients/<client_id>/<request_topic>`
     #[builder(default = "None")]
     response_topic_suffix: Option<String>,
+    /// Whether to record a [`ResponseTiming`] breakdown on each [`Response`].
+    /// Disabling this skips the extra bookkeeping on every invoke. Default is `true`.
+    #[builder(default = "true")]
+    response_timing: bool,
+    /// Whether to attach a checksum of the serialized payload to each request, so that the
+    /// executor can detect corrupted payloads, see [`payload_checksum`](crate::common::payload_checksum).
+    /// Default is `false`.
+    #[builder(default = "false")]
+    payload_checksum: bool,
+    /// Major protocol versions accepted on command responses. Responses with any other major
+    /// version are rejected with an Unsupported Version error. Must not be empty. Default is the
+    /// versions supported by this SDK.
+    #[builder(default = "SUPPORTED_PROTOCOL_VERSIONS.to_vec()")]
+    supported_protocol_versions: Vec<u16>,
 }
 
 /// Command Invoker struct
@@ -788,6 +1054,9 @@ where
     request_topic_pattern: TopicPattern,
     response_topic_pattern: TopicPattern,
     response_topic_filter: TopicFilter,
+    response_timing: bool,
+    payload_checksum: bool,
+    supported_protocol_versions: Vec<u16>,
     request_payload_type: PhantomData<TReq>,
     response_payload_type: PhantomData<TResp>,
     // Describes state
@@ -834,6 +1103,7 @@ where
     ///   [`response_topic_suffix`](OptionsBuilder::response_topic_suffix),
     ///   are Some and invalid or contain a token with no valid replacement
     /// - [`topic_token_map`](OptionsBuilder::topic_token_map) isn't empty and contains invalid key(s)/token(s)
+    /// - [`supported_protocol_versions`](OptionsBuilder::supported_protocol_versions) is empty
     pub fn new(
         application_context: ApplicationContext,
         client: SessionManagedClient,
@@ -852,6 +1122,16 @@ where
             ));
         }
 
+        if invoker_options.supported_protocol_versions.is_empty() {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "supported_protocol_versions",
+                Value::String(String::new()),
+                Some("At least one protocol version must be supported".to_string()),
+                Some(invoker_options.command_name),
+            ));
+        }
+
         // If no response_topic_pattern is specified, generate one based on the request_topic_pattern, response_topic_prefix, and response_topic_suffix
         let mut response_topic_pattern;
         if let Some(pattern) = invoker_options.response_topic_pattern {
@@ -945,6 +1225,9 @@ where
             request_topic_pattern,
             response_topic_pattern,
             response_topic_filter,
+            response_timing: invoker_options.response_timing,
+            payload_checksum: invoker_options.payload_checksum,
+            supported_protocol_versions: invoker_options.supported_protocol_versions,
             request_payload_type: PhantomData,
             response_payload_type: PhantomData,
             state_mutex: invoker_state_mutex,
@@ -959,12 +1242,17 @@ where
     /// # Arguments
     /// * `request` - [`Request`] to invoke
     /// # Errors
+    /// All errors include the [`correlation_id`](Request::correlation_id) of the request.
     ///
     /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](AIOProtocolErrorKind::ConfigurationInvalid) if
     /// - any [`topic_tokens`](RequestBuilder::topic_tokens) are invalid
+    /// - the [`correlation_id`](RequestBuilder::correlation_id) is already in use by another invocation in progress on this [`Invoker`]
+    /// - the request was built with [`no_response`](RequestBuilder::no_response)
     ///
     /// [`AIOProtocolError`] of kind [`PayloadInvalid`](AIOProtocolErrorKind::PayloadInvalid) if
     /// - [`response_payload`][Response::payload] deserialization fails
+    /// - The response payload does not match the checksum attached by the executor, in which case
+    ///   the nested error is an [`IntegrityCheckFailed`](crate::common::payload_checksum::IntegrityCheckFailed)
     /// - The response has a [`UserProperty::Status`] of [`StatusCode::NoContent`] but the payload isn't empty
     /// - The response has a [`UserProperty::Status`] of [`StatusCode::BadRequest`] and there is no [`UserProperty::InvalidPropertyName`] or [`UserProperty::InvalidPropertyValue`] specified
     ///
@@ -975,6 +1263,7 @@ where
     /// [`AIOProtocolError`] of kind [`ClientError`](AIOProtocolErrorKind::ClientError) if
     /// - The subscribe fails
     /// - The suback reason code doesn't indicate success.
+    /// - The granted QoS of the subscription is lower than the requested QoS 1.
     /// - The publish fails
     /// - The puback reason code doesn't indicate success.
     ///
@@ -982,6 +1271,7 @@ where
     ///
     /// [`AIOProtocolError`] of kind [`HeaderInvalid`](AIOProtocolErrorKind::HeaderInvalid) if
     /// - The response's `content_type` isn't supported
+    /// - The response's `content_type` isn't the request's [`accept_content_type`](RequestBuilder::accept_content_type)
     /// - The response has a [`UserProperty::Timestamp`] that is malformed
     /// - The response has a [`UserProperty::Status`] that can't be parsed as an integer
     /// - The response has a [`UserProperty::Status`] of [`StatusCode::BadRequest`] and a [`UserProperty::InvalidPropertyValue`] is specified
@@ -1008,30 +1298,222 @@ where
         &self,
         request: Request<TReq>,
     ) -> Result<Response<TResp>, AIOProtocolError> {
-        // Get the timeout duration to use
-        let command_timeout = request.timeout;
+        // Get the timeout duration to use, and the name it is reported under if it elapses
+        let (command_timeout, timeout_name) = match request.response_timeout {
+            Some(response_timeout) => (response_timeout, "response_timeout"),
+            None => (request.timeout, self.command_name.as_str()),
+        };
+        let correlation_id = request.correlation_id;
+
+        if request.no_response {
+            let mut e = AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "no_response",
+                Value::Boolean(true),
+                Some(
+                    "Request does not expect a response, use invoke_no_response to send it"
+                        .to_string(),
+                ),
+                Some(self.command_name.clone()),
+            );
+            e.correlation_id = Some(correlation_id);
+            return Err(e);
+        }
 
         // Call invoke, wrapped within a timeout
-        let invoke_result = time::timeout(request.timeout, self.invoke_internal(request)).await;
+        let invoke_result = time::timeout(command_timeout, self.invoke_internal(request)).await;
 
         // Return the timeout error or the result from the command invocation.
         match invoke_result {
             Ok(result) => match result {
                 Ok(response) => Ok(response),
-                Err(e) => Err(e),
+                Err(mut e) => {
+                    // Add correlation id to the error
+                    e.correlation_id = Some(correlation_id);
+                    Err(e)
+                }
             },
             Err(e) => {
                 log::error!(
-                    "[{command_name}] Command invoke timed out after {command_timeout:?}",
+                    "[{command_name}] Command invoke with correlation id {correlation_id} timed out after {command_timeout:?}",
                     command_name = self.command_name,
                 );
-                Err(AIOProtocolError::new_timeout_error(
+                let mut e = AIOProtocolError::new_timeout_error(
                     false,
                     Some(Box::new(e)),
-                    &self.command_name,
+                    timeout_name,
                     command_timeout,
                     None,
                     Some(self.command_name.clone()),
+                );
+                e.correlation_id = Some(correlation_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Invokes a fire-and-forget command, built with [`RequestBuilder::no_response`].
+    ///
+    /// The request is published without a response topic and the invoker does not subscribe for
+    /// or wait for a response. Returns `Ok(())` once the broker has acknowledged the request,
+    /// which does not indicate that the executor has received or executed it. See
+    /// [`RequestBuilder::no_response`] for the delivery semantics.
+    /// # Arguments
+    /// * `request` - [`Request`] to invoke
+    /// # Errors
+    /// All errors include the [`correlation_id`](Request::correlation_id) of the request.
+    ///
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](AIOProtocolErrorKind::ConfigurationInvalid) if
+    /// - any [`topic_tokens`](RequestBuilder::topic_tokens) are invalid
+    /// - the request was not built with [`no_response`](RequestBuilder::no_response)
+    ///
+    /// [`AIOProtocolError`] of kind [`Timeout`](AIOProtocolErrorKind::Timeout) if the request is not acknowledged by the broker within the request's timeout
+    ///
+    /// [`AIOProtocolError`] of kind [`ClientError`](AIOProtocolErrorKind::ClientError) if
+    /// - The publish fails
+    /// - The puback reason code doesn't indicate success.
+    ///
+    /// [`AIOProtocolError`] of kind [`Cancellation`](AIOProtocolErrorKind::Cancellation) if the [`Invoker`] has been shutdown
+    ///
+    /// [`AIOProtocolError`] of kind [`InternalLogicError`](AIOProtocolErrorKind::InternalLogicError) if
+    /// the [`ApplicationHybridLogicalClock`]'s counter would be incremented and overflow beyond [`u64::MAX`]
+    ///
+    /// [`AIOProtocolError`] of kind [`StateInvalid`](AIOProtocolErrorKind::StateInvalid) if
+    /// the [`ApplicationHybridLogicalClock`] is too far in the future
+    pub async fn invoke_no_response(&self, request: Request<TReq>) -> Result<(), AIOProtocolError> {
+        let command_timeout = request.timeout;
+        let correlation_id = request.correlation_id;
+
+        let result = if request.no_response {
+            match time::timeout(request.timeout, self.invoke_no_response_internal(request)).await {
+                Ok(result) => result,
+                Err(e) => {
+                    log::error!(
+                        "[{command_name}] Command invoke with correlation id {correlation_id} timed out after {command_timeout:?}",
+                        command_name = self.command_name,
+                    );
+                    Err(AIOProtocolError::new_timeout_error(
+                        false,
+                        Some(Box::new(e)),
+                        &self.command_name,
+                        command_timeout,
+                        None,
+                        Some(self.command_name.clone()),
+                    ))
+                }
+            }
+        } else {
+            Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "no_response",
+                Value::Boolean(false),
+                Some("Request expects a response, use invoke to send it".to_string()),
+                Some(self.command_name.clone()),
+            ))
+        };
+
+        result.map_err(|mut e| {
+            // Add correlation id to the error
+            e.correlation_id = Some(correlation_id);
+            e
+        })
+    }
+
+    async fn invoke_no_response_internal(
+        &self,
+        mut request: Request<TReq>,
+    ) -> Result<(), AIOProtocolError> {
+        let message_expiry_interval: u32 = match request.timeout.as_secs().try_into() {
+            Ok(val) => val,
+            Err(_) => {
+                // should be validated in RequestBuilder
+                unreachable!();
+            }
+        };
+
+        // Get request topic. Validates dynamic topic tokens
+        let request_topic = self
+            .request_topic_pattern
+            .as_publish_topic(&request.topic_tokens)
+            .map_err(|e| {
+                AIOProtocolError::config_invalid_from_topic_pattern_error(
+                    e,
+                    "request_topic_pattern",
+                )
+            })?;
+
+        match *self.state_mutex.lock().await {
+            State::New | State::Subscribed => { /* No subscription is needed without a response */ }
+            State::ShutdownInitiated | State::ShutdownSuccessful => {
+                return Err(AIOProtocolError::new_cancellation_error(
+                    false,
+                    None,
+                    Some(
+                        "Command Invoker has been shutdown and can no longer invoke commands"
+                            .to_string(),
+                    ),
+                    Some(self.command_name.clone()),
+                ));
+            }
+        }
+
+        let timestamp_str = self.application_hlc.update_now()?;
+        self.add_request_user_properties(&mut request, request_topic.as_str(), timestamp_str);
+        request.custom_user_data.push((
+            ProtocolReservedUserProperty::NoResponse.to_string(),
+            String::new(),
+        ));
+
+        // The correlation data is still sent so that the executor can deduplicate the request
+        let publish_properties = PublishProperties {
+            correlation_data: Some(Bytes::copy_from_slice(request.correlation_id.as_bytes())),
+            response_topic: None,
+            payload_format_indicator: request.serialized_payload.format_indicator.into(),
+            content_type: Some(request.serialized_payload.content_type.clone()),
+            message_expiry_interval: Some(message_expiry_interval),
+            user_properties: request.custom_user_data,
+            topic_alias: None,
+            subscription_identifiers: Vec::new(),
+        };
+
+        let publish_completion_token = self
+            .mqtt_client
+            .publish_qos1(
+                request_topic,
+                false,
+                request.serialized_payload.payload,
+                publish_properties,
+            )
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "[{}] Client error while publishing Invoker Command Request: {e}",
+                    self.command_name
+                );
+                AIOProtocolError::new_mqtt_error(
+                    Some("Client error on command invoker request publish".to_string()),
+                    Box::new(e),
+                    Some(self.command_name.clone()),
+                )
+            })?;
+
+        match publish_completion_token.await {
+            Ok(puback) => puback.as_result().map_err(|e| {
+                AIOProtocolError::new_mqtt_error(
+                    Some("MQTT Puback indicated failure".to_string()),
+                    Box::new(e),
+                    Some(self.command_name.clone()),
+                )
+            }),
+            Err(e) => {
+                log::error!(
+                    "[{}] Command Request publish completion error: {e}",
+                    self.command_name
+                );
+                Err(AIOProtocolError::new_mqtt_error(
+                    Some("MQTT Error on command invoke publish".to_string()),
+                    Box::new(e),
+                    Some(self.command_name.clone()),
                 ))
             }
         }
@@ -1041,7 +1523,7 @@ where
     ///
     /// Returns `Ok()` on success, otherwise returns [`AIOProtocolError`].
     /// # Errors
-    /// [`AIOProtocolError`] of kind [`ClientError`](AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    /// [`AIOProtocolError`] of kind [`ClientError`](AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
     async fn subscribe_to_response_filter(&self) -> Result<(), AIOProtocolError> {
         // Send subscribe
         let subscribe_result = self
@@ -1059,7 +1541,7 @@ where
                 // Wait for suback
                 match sub_ct.await {
                     Ok(suback) => {
-                        suback.as_result().map_err(|e| {
+                        suback.as_result_with_qos(QoS::AtLeastOnce).map_err(|e| {
                             log::error!("[{}] Invoker suback error: {suback:?}", self.command_name);
                             AIOProtocolError::new_mqtt_error(
                                 Some("MQTT Error on command invoker suback".to_string()),
@@ -1096,6 +1578,49 @@ where
         Ok(())
     }
 
+    /// Adds the internal user properties and cloud event headers to the request.
+    fn add_request_user_properties(
+        &self,
+        request: &mut Request<TReq>,
+        request_topic: &str,
+        timestamp_str: String,
+    ) {
+        request.custom_user_data.push((
+            ProtocolReservedUserProperty::SourceId.to_string(),
+            self.mqtt_client.client_id().to_string(),
+        ));
+        request.custom_user_data.push((
+            ProtocolReservedUserProperty::Timestamp.to_string(),
+            timestamp_str,
+        ));
+        request.custom_user_data.push((
+            ProtocolReservedUserProperty::ProtocolVersion.to_string(),
+            RPC_COMMAND_PROTOCOL_VERSION.to_string(),
+        ));
+        request.custom_user_data.push((
+            BrokerReservedUserProperty::Partition.to_string(),
+            self.mqtt_client.client_id().to_string(),
+        ));
+        request.custom_user_data.push((
+            BrokerReservedUserProperty::HighPriority.to_string(),
+            String::new(),
+        ));
+        if self.payload_checksum {
+            request.custom_user_data.push((
+                ProtocolReservedUserProperty::PayloadChecksum.to_string(),
+                payload_checksum::checksum(&request.serialized_payload.payload),
+            ));
+        }
+
+        // Cloud Events headers
+        if let Some(cloud_event) = request.cloud_event.take() {
+            let cloud_event_headers = cloud_event.0.into_headers(request_topic);
+            for (key, value) in cloud_event_headers {
+                request.custom_user_data.push((key, value));
+            }
+        }
+    }
+
     async fn invoke_internal(
         &self,
         mut request: Request<TReq>,
@@ -1136,36 +1661,14 @@ where
 
         // Get updated timestamp
         let timestamp_str = self.application_hlc.update_now()?;
+        // Keep the request timestamp to estimate the executor processing time from the response timestamp
+        let request_timestamp = if self.response_timing {
+            HybridLogicalClock::from_str(&timestamp_str).ok()
+        } else {
+            None
+        };
 
-        // Add internal user properties
-        request.custom_user_data.push((
-            ProtocolReservedUserProperty::SourceId.to_string(),
-            self.mqtt_client.client_id().to_string(),
-        ));
-        request.custom_user_data.push((
-            ProtocolReservedUserProperty::Timestamp.to_string(),
-            timestamp_str,
-        ));
-        request.custom_user_data.push((
-            ProtocolReservedUserProperty::ProtocolVersion.to_string(),
-            RPC_COMMAND_PROTOCOL_VERSION.to_string(),
-        ));
-        request.custom_user_data.push((
-            BrokerReservedUserProperty::Partition.to_string(),
-            self.mqtt_client.client_id().to_string(),
-        ));
-        request.custom_user_data.push((
-            BrokerReservedUserProperty::HighPriority.to_string(),
-            String::new(),
-        ));
-
-        // Cloud Events headers
-        if let Some(cloud_event) = request.cloud_event {
-            let cloud_event_headers = cloud_event.0.into_headers(request_topic.as_str());
-            for (key, value) in cloud_event_headers {
-                request.custom_user_data.push((key, value));
-            }
-        }
+        self.add_request_user_properties(&mut request, request_topic.as_str(), timestamp_str);
 
         // Subscribe to the response topic if we're not already subscribed and the invoker hasn't been shutdown
         {
@@ -1191,21 +1694,28 @@ where
             // Allow other concurrent invoke commands to acquire the invoker_state lock
         }
 
-        // Create correlation id and receiver for response
-        let (correlation_data, mut response_rx) = {
-            loop {
-                let correlation_id = Uuid::new_v4();
-                let correlation_data = Bytes::copy_from_slice(correlation_id.as_bytes());
-
-                // Create receiver for response
-                if let Ok(rx) = self
-                    .response_dispatcher
-                    .register_receiver(correlation_data.clone())
-                {
-                    break (correlation_data, rx);
-                }
-                // Otherwise, loop again; Correlation ID wasn't unique, retry with a new correlation_id
-            }
+        // Create receiver for response
+        let correlation_data = Bytes::copy_from_slice(request.correlation_id.as_bytes());
+        let Ok(mut response_rx) = self
+            .response_dispatcher
+            .register_receiver(correlation_data.clone())
+        else {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "correlation_id",
+                Value::String(request.correlation_id.to_string()),
+                Some(format!(
+                    "Correlation id {} is already in use by another command invocation in progress",
+                    request.correlation_id
+                )),
+                Some(self.command_name.clone()),
+            ));
+        };
+        // Unregister the receiver for this correlation data when the invoke completes, times out,
+        // or is cancelled, so that the correlation id can be used again
+        let response_registration = ResponseRegistration {
+            response_dispatcher: &self.response_dispatcher,
+            correlation_data: correlation_data.clone(),
         };
 
         // Create MQTT Properties
@@ -1221,6 +1731,7 @@ where
         };
 
         // Send publish
+        let published_at = self.response_timing.then(clock::now);
         let publish_result = self
             .mqtt_client
             .publish_qos1(
@@ -1236,6 +1747,7 @@ where
         let pub_task = tokio::task::spawn({
             let command_name = self.command_name.clone();
             let ct = cancellation_token.clone();
+            let response_timing = self.response_timing;
             async move {
                 match publish_result {
                     Ok(publish_completion_token) => {
@@ -1257,7 +1769,7 @@ where
                                 match publish_completion_token_result {
                                     Ok(puback) => {
                                         // if puback is Ok, continue and wait for the response
-                                        puback.as_result().map_err(|e| {
+                                        puback.as_result().map(|()| response_timing.then(clock::now)).map_err(|e| {
                                             AIOProtocolError::new_mqtt_error(
                                                 Some("MQTT Puback indicated failure".to_string()),
                                                 Box::new(e),
@@ -1294,6 +1806,7 @@ where
         let response_task = tokio::task::spawn({
             let command_name = self.command_name.clone();
             let ct = cancellation_token.clone();
+            let response_timing = self.response_timing;
             async move {
                 // wait for incoming pub
                 tokio::select! {
@@ -1311,7 +1824,7 @@ where
                     },
                     res = response_rx.recv() => {
                         // we know the correlation id matches, otherwise it wouldn't have been dispatched to us
-                        res.ok_or_else(|| {
+                        res.map(|rsp_pub| (rsp_pub, response_timing.then(clock::now))).ok_or_else(|| {
                             log::error!(
                                 "[{command_name}] Command Invoker has been shutdown and will no longer receive a response"
                             );
@@ -1331,13 +1844,12 @@ where
         });
 
         // wait for pub to be completed and response to be received, immediately returning any errors returned.
-        let rsp_pub = {
+        let (puback_at, (rsp_pub, response_received_at)) = {
             let res = tokio::try_join!(flatten(pub_task), flatten(response_task));
             // Unregister the receiver for this correlation data before possibly returning, since we will no longer be listening on it
-            self.response_dispatcher
-                .unregister_receiver(&correlation_data);
+            drop(response_registration);
             match res {
-                Ok(((), rsp_pub)) => rsp_pub,
+                Ok(res) => res,
                 // Return any error that occurs
                 Err(e) => {
                     return Err(e);
@@ -1346,15 +1858,19 @@ where
         };
 
         // validate and parse the response pub that is for this request
-        let command_result: CommandResult<TResp> =
-            rsp_pub.try_into().map_err(|mut e: AIOProtocolError| {
-                // Add command name to the error
-                e.command_name = Some(self.command_name.clone());
-                e
-            })?;
+        let command_result = CommandResult::<TResp>::from_publish(
+            rsp_pub,
+            &self.supported_protocol_versions,
+            request.accept_content_type.as_deref(),
+        )
+        .map_err(|mut e| {
+            // Add command name to the error
+            e.command_name = Some(self.command_name.clone());
+            e
+        })?;
 
         match command_result {
-            CommandResult::Ok(response) => {
+            CommandResult::Ok(mut response) => {
                 // Update application HLC
                 if let Some(hlc) = &response.timestamp {
                     self.application_hlc.update(hlc).map_err(|e| {
@@ -1363,6 +1879,18 @@ where
                         aio_error
                     })?;
                 }
+                if let (Some(published_at), Some(puback_at), Some(response_received_at)) =
+                    (published_at, puback_at, response_received_at)
+                {
+                    response.timing = Some(ResponseTiming::new(
+                        request.built_at,
+                        published_at,
+                        puback_at,
+                        response_received_at,
+                        request_timestamp.as_ref(),
+                        response.timestamp.as_ref(),
+                    ));
+                }
                 Ok(response)
             }
             CommandResult::Err(remote_e) => {
@@ -1572,6 +2100,20 @@ async fn drop_unsubscribe(
     *invoker_state_mutex_guard = State::ShutdownSuccessful;
 }
 
+/// Registration of the response receiver for an invoke's correlation data, which is unregistered
+/// when dropped.
+struct ResponseRegistration<'a> {
+    response_dispatcher: &'a Dispatcher<Publish, Bytes>,
+    correlation_data: Bytes,
+}
+
+impl Drop for ResponseRegistration<'_> {
+    fn drop(&mut self) {
+        self.response_dispatcher
+            .unregister_receiver(&self.correlation_data);
+    }
+}
+
 /// convenience fn to flatten the result of a `JoinHandle`
 async fn flatten<T>(
     handle: JoinHandle<Result<T, AIOProtocolError>>,
@@ -1586,8 +2128,19 @@ async fn flatten<T>(
     }
 }
 
+/// Returns true if `content_type` has the media type of `accepted`, ignoring any parameters and
+/// case, e.g. `application/json; charset=utf-8` matches `application/json`
+fn media_type_matches(content_type: &str, accepted: &str) -> bool {
+    fn media_type(content_type: &str) -> &str {
+        content_type.split(';').next().unwrap_or_default().trim()
+    }
+    media_type(content_type).eq_ignore_ascii_case(media_type(accepted))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::UNIX_EPOCH;
+
     use test_case::test_case;
     // TODO: This dependency on MqttConnectionSettingsBuilder should be removed in lieu of using a true mock
     use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
@@ -1997,6 +2550,61 @@ mod tests {
         }
     }
 
+    // Tests failure: Invocation waits for the response timeout rather than the timeout, and the
+    // `Timeout` error names the response timeout
+    #[tokio::test]
+    async fn test_invoke_times_out_response_timeout() {
+        let session = create_session();
+        let managed_client = session.create_managed_client();
+        let invoker_options = OptionsBuilder::default()
+            .request_topic_pattern("test/req/topic")
+            .command_name("test_command_name")
+            .topic_token_map(create_topic_tokens())
+            .build()
+            .unwrap();
+
+        let invoker: Invoker<MockPayload, MockPayload> = Invoker::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            invoker_options,
+        )
+        .unwrap();
+
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: Vec::new(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let start = std::time::Instant::now();
+        let response = invoker
+            .invoke(
+                RequestBuilder::default()
+                    .payload(mock_request_payload)
+                    .unwrap()
+                    .timeout(Duration::from_secs(1))
+                    .response_timeout(Duration::from_secs(2))
+                    .build()
+                    .unwrap(),
+            )
+            .await;
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        match response {
+            Ok(_) => panic!("Expected error"),
+            Err(e) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::Timeout);
+                assert_eq!(e.timeout_name, Some("response_timeout".to_string()));
+                assert!(e.timeout_value == Some(Duration::from_secs(2)));
+            }
+        }
+    }
+
     // Tests failure: Invocation times out (valid timeout value less than a second but not zero specified on invoke)
     // and a `Timeout` error is returned
     #[tokio::test]
@@ -2293,6 +2901,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_request_serialization_cbor_utf8_format_indicator_error() {
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: vec![0xa1, 0x61, 0x61, 0xff],
+                    content_type: "application/cbor".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let mut binding = RequestBuilder::default();
+        let req_builder = binding.payload(mock_request_payload);
+        match req_builder {
+            Err(e) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert!(e.is_shallow);
+                assert!(!e.is_remote);
+                assert_eq!(e.property_name, Some("format_indicator".to_string()));
+            }
+            Ok(_) => {
+                panic!("Expected error");
+            }
+        }
+    }
+
     /// Tests failure: Timeout specified as 0 (invalid value) on invoke and an `ArgumentInvalid` error is returned
     #[test_case(Duration::from_secs(0); "invoke_timeout_0")]
     /// Tests failure: Timeout specified as > `u32::max` (invalid value) on invoke and an `ArgumentInvalid` error is returned
@@ -2343,6 +2980,38 @@ mod tests {
         assert!(request_builder_result.is_err());
     }
 
+    /// Tests failure: Response timeout shorter than the timeout is rejected, while an equal or
+    /// longer one is accepted
+    #[test_case(Duration::from_secs(1), false; "response_timeout_shorter")]
+    #[test_case(Duration::from_secs(2), true; "response_timeout_equal")]
+    #[test_case(Duration::from_secs(60), true; "response_timeout_longer")]
+    fn test_request_response_timeout(response_timeout: Duration, valid: bool) {
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: Vec::new(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let request_builder_result = RequestBuilder::default()
+            .payload(mock_request_payload)
+            .unwrap()
+            .timeout(Duration::from_secs(2))
+            .response_timeout(response_timeout)
+            .build();
+
+        assert_eq!(request_builder_result.is_ok(), valid);
+        if let Ok(request) = request_builder_result {
+            assert_eq!(request.timeout, Duration::from_secs(2));
+            assert_eq!(request.response_timeout, Some(response_timeout));
+        }
+    }
+
     #[test]
     fn test_request_defaults() {
         let mut mock_request_payload = MockPayload::new();
@@ -2370,6 +3039,65 @@ mod tests {
         assert!(r.topic_tokens.is_empty());
         assert!(r.cloud_event.is_none());
         assert!(r.serialized_payload.payload.is_empty());
+        assert!(!r.correlation_id().is_nil());
+        assert!(!r.no_response);
+    }
+
+    #[test_case(Uuid::new_v4(), true; "request_correlation_id_valid")]
+    #[test_case(Uuid::nil(), false; "request_correlation_id_nil")]
+    fn test_request_correlation_id(correlation_id: Uuid, is_valid: bool) {
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: Vec::new(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let request_builder_result = RequestBuilder::default()
+            .payload(mock_request_payload)
+            .unwrap()
+            .timeout(Duration::from_secs(2))
+            .correlation_id(correlation_id)
+            .build();
+
+        if is_valid {
+            assert_eq!(
+                request_builder_result.unwrap().correlation_id(),
+                correlation_id
+            );
+        } else {
+            assert!(request_builder_result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_request_no_response() {
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: Vec::new(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let request = RequestBuilder::default()
+            .payload(mock_request_payload)
+            .unwrap()
+            .timeout(Duration::from_secs(2))
+            .no_response()
+            .build()
+            .unwrap();
+
+        assert!(request.no_response);
     }
 
     /// Tests success: `application_error_headers()` returns no Application Error Code and Payload since `custom_user_data` has none.
@@ -2419,6 +3147,156 @@ mod tests {
         assert_eq!(application_error_code, Some(error_code_content.into()));
         assert!(application_error_payload.is_none());
     }
+
+    /// Tests success: `Response::application_error()` parses the Application Error Code, Payload and Severity.
+    #[test]
+    fn test_response_application_error() {
+        let mut response = Response {
+            payload: MockPayload::default(),
+            content_type: None,
+            format_indicator: FormatIndicator::UnspecifiedBytes,
+            custom_user_data: vec![("AppErrCode".into(), "5888".into())],
+            timestamp: None,
+            executor_id: None,
+            timing: None,
+        };
+        let application_error = response.application_error().unwrap();
+        assert_eq!(application_error.code, "5888");
+        assert!(application_error.detail.is_none());
+        assert_eq!(
+            application_error.severity,
+            crate::rpc_command::ApplicationErrorSeverity::Error
+        );
+
+        response.custom_user_data = vec![
+            ("AppErrCode".into(), "5888".into()),
+            ("AppErrPayload".into(), r#"{"retryAfter":5}"#.into()),
+            ("AppErrSeverity".into(), "warning".into()),
+        ];
+        let application_error = response.application_error().unwrap();
+        assert_eq!(
+            application_error
+                .deserialize_detail::<serde_json::Value>()
+                .unwrap()
+                .unwrap()["retryAfter"],
+            5
+        );
+        assert_eq!(
+            application_error.severity,
+            crate::rpc_command::ApplicationErrorSeverity::Warning
+        );
+
+        response.custom_user_data = Vec::new();
+        assert!(response.application_error().is_none());
+    }
+
+    fn hlc_at(ms_since_epoch: u64, counter: u64, node_id: &str) -> HybridLogicalClock {
+        HybridLogicalClock {
+            timestamp: UNIX_EPOCH + Duration::from_millis(ms_since_epoch),
+            counter,
+            node_id: node_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_response_timing_breakdown() {
+        let built_at = clock::now();
+        let published_at = built_at + Duration::from_millis(5);
+        let puback_at = published_at + Duration::from_millis(10);
+        let response_received_at = published_at + Duration::from_millis(300);
+        let timing = ResponseTiming::new(
+            built_at,
+            published_at,
+            puback_at,
+            response_received_at,
+            Some(&hlc_at(1_000, 0, "invoker")),
+            Some(&hlc_at(1_250, 0, "executor")),
+        );
+        assert_eq!(timing.build_to_publish, Duration::from_millis(5));
+        assert_eq!(timing.publish_to_puback, Duration::from_millis(10));
+        assert_eq!(
+            timing.request_sent_to_response_received,
+            Duration::from_millis(300)
+        );
+        assert_eq!(
+            timing.executor_processing_estimate,
+            Some(Duration::from_millis(250))
+        );
+        assert!(!timing.clock_drift_detected);
+    }
+
+    #[test]
+    fn test_response_timing_without_response_timestamp() {
+        let now = clock::now();
+        let timing =
+            ResponseTiming::new(now, now, now, now, Some(&hlc_at(1_000, 0, "invoker")), None);
+        assert_eq!(timing.executor_processing_estimate, None);
+        assert!(!timing.clock_drift_detected);
+    }
+
+    #[test_case(1_000, 1_000, 0, Some(0), false; "same timestamp within round trip")]
+    #[test_case(1_000, 1_101, 100, Some(101), false; "estimate within timestamp resolution of round trip")]
+    #[test_case(1_000, 1_500, 100, Some(500), true; "executor clock ahead")]
+    #[test_case(1_000, 900, 100, Some(0), true; "response before request")]
+    fn test_estimate_executor_processing(
+        request_ms: u64,
+        response_ms: u64,
+        round_trip_ms: u64,
+        expected_estimate_ms: Option<u64>,
+        expected_clock_drift: bool,
+    ) {
+        let (estimate, clock_drift_detected) = ResponseTiming::estimate_executor_processing(
+            &hlc_at(request_ms, 0, "invoker"),
+            &hlc_at(response_ms, 1, "executor"),
+            Duration::from_millis(round_trip_ms),
+        );
+        assert_eq!(estimate, expected_estimate_ms.map(Duration::from_millis));
+        assert_eq!(clock_drift_detected, expected_clock_drift);
+    }
+
+    #[test_case("application/json", "application/json", true; "equal")]
+    #[test_case("Application/JSON", "application/json", true; "case insensitive")]
+    #[test_case("application/json; charset=utf-8", "application/json", true; "parameters ignored")]
+    #[test_case("application/json", "application/cbor", false; "different")]
+    #[test_case("application/json-seq", "application/json", false; "prefix")]
+    fn test_media_type_matches(content_type: &str, accepted: &str, expected: bool) {
+        assert_eq!(media_type_matches(content_type, accepted), expected);
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case(" "; "whitespace")]
+    #[test_case("application/json\u{0000}"; "invalid utf8")]
+    fn test_request_invalid_accept_content_type(accept_content_type: &str) {
+        let mut mock_request_payload = MockPayload::new();
+        mock_request_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: Vec::new(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let request_builder_result = RequestBuilder::default()
+            .payload(mock_request_payload)
+            .unwrap()
+            .timeout(Duration::from_secs(1))
+            .accept_content_type(accept_content_type)
+            .build();
+        assert!(request_builder_result.is_err());
+    }
+
+    #[test]
+    fn test_response_timing_option_default() {
+        let invoker_options = OptionsBuilder::default()
+            .request_topic_pattern("test/req/topic")
+            .command_name("test_command_name")
+            .build()
+            .unwrap();
+        assert!(invoker_options.response_timing);
+    }
 }
 
 // Command Request tests
@@ -0,0 +1,475 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use azure_iot_operations_mqtt::{
+    control_packet::{QoS, TopicFilter},
+    session::SessionManagedClient,
+};
+use tokio::task::JoinSet;
+
+use crate::{
+    application::ApplicationContext,
+    common::{
+        aio_protocol_error::{AIOProtocolError, Value},
+        payload_serialize::PayloadSerialize,
+        topic_processor::TopicPattern,
+    },
+    rpc_command::executor::{self, Executor, Request},
+};
+
+/// Topic token that is resolved to the command name of each handler of a [`CommandRouter`]
+pub const COMMAND_NAME_TOKEN: &str = "commandName";
+
+type RouteFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Command Router Options struct
+#[derive(Builder, Clone)]
+#[builder(setter(into, strip_option))]
+pub struct Options {
+    /// Topic pattern for the command requests of all handlers.
+    /// Must align with [topic-structure.md](https://github.com/Azure/iot-operations-sdks/blob/main/doc/reference/topic-structure.md)
+    /// and contain the `{commandName}` token.
+    request_topic_pattern: String,
+    /// Optional Topic namespace to be prepended to the topic pattern
+    #[builder(default = "None")]
+    topic_namespace: Option<String>,
+    /// Topic token keys/values to be permanently replaced in the topic pattern
+    #[builder(default)]
+    topic_token_map: HashMap<String, String>,
+    /// Service group ID
+    #[builder(default = "None")]
+    service_group_id: Option<String>,
+}
+
+/// Command Router struct
+///
+/// Dispatches command requests for multiple commands, each with their own request and response
+/// types, from a single subscription on the request topic pattern. The `{commandName}` token of
+/// the request topic pattern is used to route each request to the handler registered for that
+/// command.
+///
+/// Requests for a command without a registered handler are acknowledged, but not responded to.
+///
+/// # Example
+/// ```
+/// # use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+/// # use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+/// # use azure_iot_operations_protocol::rpc_command;
+/// # use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+/// # let mut connection_settings = MqttConnectionSettingsBuilder::default()
+/// #     .client_id("test_server")
+/// #     .hostname("localhost")
+/// #     .tcp_port(1883u16)
+/// #     .build().unwrap();
+/// # let mut session_options = SessionOptionsBuilder::default()
+/// #     .connection_settings(connection_settings)
+/// #     .build().unwrap();
+/// # let mqtt_session = Session::new(session_options).unwrap();
+/// # let application_context = ApplicationContextBuilder::default().build().unwrap();
+/// let router_options = rpc_command::router::OptionsBuilder::default()
+///   .request_topic_pattern("test/{commandName}/request")
+///   .build().unwrap();
+/// # tokio_test::block_on(async {
+/// let mut router = rpc_command::CommandRouter::new(application_context, mqtt_session.create_managed_client(), router_options).unwrap();
+/// router.add_handler("echo", |request: rpc_command::executor::Request<Vec<u8>, Vec<u8>>| async move {
+///     let response = rpc_command::executor::ResponseBuilder::default()
+///       .payload(request.payload.clone()).unwrap()
+///       .build().unwrap();
+///     let _ = request.complete(response).await;
+/// }).unwrap();
+/// // router.run().await.unwrap();
+/// # });
+/// ```
+pub struct CommandRouter {
+    application_context: ApplicationContext,
+    mqtt_client: SessionManagedClient,
+    options: Options,
+    request_topic_filter: TopicFilter,
+    routes: HashMap<String, RouteFuture>,
+    is_subscribed: bool,
+}
+
+impl CommandRouter {
+    /// Create a new [`CommandRouter`].
+    ///
+    /// # Arguments
+    /// * `application_context` - [`ApplicationContext`] that the command router is part of.
+    /// * `client` - The MQTT client to use for communication.
+    /// * `router_options` - Configuration options.
+    ///
+    /// Returns Ok([`CommandRouter`]) on success, otherwise returns [`AIOProtocolError`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid) if:
+    /// - [`request_topic_pattern`](OptionsBuilder::request_topic_pattern) does not contain the
+    ///   `{commandName}` token, or [`topic_token_map`](OptionsBuilder::topic_token_map) contains
+    ///   a replacement for it
+    /// - [`request_topic_pattern`](OptionsBuilder::request_topic_pattern),
+    ///   [`topic_namespace`](OptionsBuilder::topic_namespace)
+    ///   are Some and invalid or contain a token with no valid replacement
+    /// - [`topic_token_map`](OptionsBuilder::topic_token_map) is not empty and contains invalid key(s) and/or token(s)
+    pub fn new(
+        application_context: ApplicationContext,
+        client: SessionManagedClient,
+        router_options: Options,
+    ) -> Result<Self, AIOProtocolError> {
+        if !router_options
+            .request_topic_pattern
+            .contains(&format!("{{{COMMAND_NAME_TOKEN}}}"))
+            || router_options
+                .topic_token_map
+                .contains_key(COMMAND_NAME_TOKEN)
+        {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "router_options.request_topic_pattern",
+                Value::String(router_options.request_topic_pattern.clone()),
+                Some(format!(
+                    "Request topic pattern must contain an unreplaced '{{{COMMAND_NAME_TOKEN}}}' token"
+                )),
+                None,
+            ));
+        }
+
+        let request_topic_pattern = TopicPattern::new(
+            &router_options.request_topic_pattern,
+            router_options.service_group_id.clone(),
+            router_options.topic_namespace.as_deref(),
+            &router_options.topic_token_map,
+        )
+        .map_err(|e| {
+            AIOProtocolError::config_invalid_from_topic_pattern_error(
+                e,
+                "router_options.request_topic_pattern",
+            )
+        })?;
+
+        let request_topic_filter = request_topic_pattern.as_subscribe_topic().map_err(|e| {
+            AIOProtocolError::config_invalid_from_topic_pattern_error(
+                e,
+                "router_options.request_topic_pattern",
+            )
+        })?;
+
+        Ok(Self {
+            application_context,
+            mqtt_client: client,
+            options: router_options,
+            request_topic_filter,
+            routes: HashMap::new(),
+            is_subscribed: false,
+        })
+    }
+
+    /// Register a handler for the command requests of `command_name`.
+    ///
+    /// Each received [`Request`] is passed to the handler on a new task, and the handler is
+    /// responsible for completing it.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid) if:
+    /// - `command_name` is empty, whitespace or invalid
+    /// - a handler has already been registered for `command_name`
+    pub fn add_handler<TReq, TResp, F, Fut>(
+        &mut self,
+        command_name: impl Into<String>,
+        handler: F,
+    ) -> Result<&mut Self, AIOProtocolError>
+    where
+        TReq: PayloadSerialize + Send + Sync + 'static,
+        TResp: PayloadSerialize + Send + Sync + 'static,
+        F: Fn(Request<TReq, TResp>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let command_name = command_name.into();
+        if self.routes.contains_key(&command_name) {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "command_name",
+                Value::String(command_name.clone()),
+                Some(format!(
+                    "A handler is already registered for command '{command_name}'"
+                )),
+                Some(command_name),
+            ));
+        }
+
+        let mut topic_token_map = self.options.topic_token_map.clone();
+        topic_token_map.insert(COMMAND_NAME_TOKEN.to_string(), command_name.clone());
+        let mut executor_options_builder = executor::OptionsBuilder::default();
+        executor_options_builder
+            .request_topic_pattern(self.options.request_topic_pattern.clone())
+            .command_name(command_name.clone())
+            .topic_token_map(topic_token_map);
+        if let Some(topic_namespace) = &self.options.topic_namespace {
+            executor_options_builder.topic_namespace(topic_namespace.clone());
+        }
+        if let Some(service_group_id) = &self.options.service_group_id {
+            executor_options_builder.service_group_id(service_group_id.clone());
+        }
+        let executor_options = executor_options_builder.build().map_err(|e| {
+            AIOProtocolError::new_configuration_invalid_error(
+                Some(Box::new(e)),
+                "command_name",
+                Value::String(command_name.clone()),
+                None,
+                Some(command_name.clone()),
+            )
+        })?;
+
+        // The executor registers its receiver now, so no requests are missed once the router
+        // subscribes
+        let mut executor: Executor<TReq, TResp> = Executor::new_with_shared_subscription(
+            self.application_context.clone(),
+            self.mqtt_client.clone(),
+            executor_options,
+        )?;
+
+        let route_command_name = command_name.clone();
+        let route = Box::pin(async move {
+            while let Some(request) = executor.recv().await {
+                match request {
+                    Ok(request) => {
+                        tokio::task::spawn(handler(request));
+                    }
+                    Err(e) => {
+                        log::warn!("[{route_command_name}] Command router receive error: {e}");
+                    }
+                }
+            }
+            log::info!("[{route_command_name}] Command router handler ended");
+        });
+        self.routes.insert(command_name, route);
+        Ok(self)
+    }
+
+    /// Subscribes to the request topic and dispatches command requests to the registered handlers.
+    ///
+    /// Returns once there will be no more requests for any of the handlers.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
+    pub async fn run(mut self) -> Result<(), AIOProtocolError> {
+        let subscribe_result = self
+            .mqtt_client
+            .subscribe(
+                self.request_topic_filter.clone(),
+                QoS::AtLeastOnce,
+                false,
+                azure_iot_operations_mqtt::control_packet::RetainOptions::default(),
+                azure_iot_operations_mqtt::control_packet::SubscribeProperties::default(),
+            )
+            .await;
+
+        match subscribe_result {
+            Ok(sub_ct) => match sub_ct.await {
+                Ok(suback) => {
+                    suback.as_result_with_qos(QoS::AtLeastOnce).map_err(|e| {
+                        log::error!("Command router suback error: {suback:?}");
+                        AIOProtocolError::new_mqtt_error(
+                            Some("MQTT error on command router suback".to_string()),
+                            Box::new(e),
+                            None,
+                        )
+                    })?;
+                }
+                Err(e) => {
+                    log::error!("Command router subscribe completion error: {e}");
+                    return Err(AIOProtocolError::new_mqtt_error(
+                        Some("MQTT error on command router subscribe".to_string()),
+                        Box::new(e),
+                        None,
+                    ));
+                }
+            },
+            Err(e) => {
+                log::error!("Client error while subscribing in command router: {e}");
+                return Err(AIOProtocolError::new_mqtt_error(
+                    Some("Client error on command router subscribe".to_string()),
+                    Box::new(e),
+                    None,
+                ));
+            }
+        }
+        self.is_subscribed = true;
+
+        let mut join_set = JoinSet::new();
+        for (_, route) in self.routes.drain() {
+            join_set.spawn(route);
+        }
+        while join_set.join_next().await.is_some() {}
+        Ok(())
+    }
+}
+
+impl Drop for CommandRouter {
+    fn drop(&mut self) {
+        // If the router has subscribed, attempt to unsubscribe
+        if self.is_subscribed {
+            tokio::spawn({
+                let request_topic = self.request_topic_filter.clone();
+                let mqtt_client = self.mqtt_client.clone();
+                async move {
+                    match mqtt_client
+                        .unsubscribe(
+                            request_topic.clone(),
+                            azure_iot_operations_mqtt::control_packet::UnsubscribeProperties::default(),
+                        )
+                        .await
+                    {
+                        Ok(_) => {
+                            log::debug!(
+                                "Command router Unsubscribe sent on topic {request_topic}. Unsuback may still be pending."
+                            );
+                        }
+                        Err(e) => {
+                            log::warn!(
+                                "Command router Unsubscribe error on topic {request_topic}: {e}"
+                            );
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        application::ApplicationContextBuilder,
+        common::{aio_protocol_error::AIOProtocolErrorKind, payload_serialize::MockPayload},
+    };
+    use azure_iot_operations_mqtt::{
+        aio::connection_settings::MqttConnectionSettingsBuilder,
+        session::{Session, SessionOptionsBuilder},
+    };
+
+    fn create_session() -> Session {
+        let connection_settings = MqttConnectionSettingsBuilder::default()
+            .hostname("localhost")
+            .client_id("test_server")
+            .build()
+            .unwrap();
+        let session_options = SessionOptionsBuilder::default()
+            .connection_settings(connection_settings)
+            .build()
+            .unwrap();
+        Session::new(session_options).unwrap()
+    }
+
+    fn create_router(session: &Session) -> CommandRouter {
+        let router_options = OptionsBuilder::default()
+            .request_topic_pattern("test/{commandName}/request")
+            .build()
+            .unwrap();
+        CommandRouter::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            router_options,
+        )
+        .unwrap()
+    }
+
+    async fn noop_handler<TReq: PayloadSerialize, TResp: PayloadSerialize>(
+        _request: Request<TReq, TResp>,
+    ) {
+    }
+
+    #[tokio::test]
+    async fn test_new_and_add_handlers() {
+        let session = create_session();
+        let mut router = create_router(&session);
+        assert_eq!(
+            router.request_topic_filter.as_str(),
+            "test/+/request".to_string()
+        );
+
+        // Handlers with different request and response types
+        router
+            .add_handler("command1", noop_handler::<MockPayload, MockPayload>)
+            .unwrap()
+            .add_handler("command2", noop_handler::<Vec<u8>, MockPayload>)
+            .unwrap();
+        assert_eq!(router.routes.len(), 2);
+    }
+
+    #[test_case("test/request"; "missing_command_name_token")]
+    #[test_case("test/{executorId}/request"; "other_token")]
+    #[test_case(""; "empty")]
+    fn test_new_invalid_request_topic_pattern(request_topic_pattern: &str) {
+        let session = create_session();
+        let router_options = OptionsBuilder::default()
+            .request_topic_pattern(request_topic_pattern)
+            .build()
+            .unwrap();
+        match CommandRouter::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            router_options,
+        ) {
+            Ok(_) => panic!("Expected error"),
+            Err(e) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(
+                    e.property_name,
+                    Some("router_options.request_topic_pattern".to_string())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_command_name_token_replaced() {
+        let session = create_session();
+        let router_options = OptionsBuilder::default()
+            .request_topic_pattern("test/{commandName}/request")
+            .topic_token_map(HashMap::from([(
+                COMMAND_NAME_TOKEN.to_string(),
+                "command".to_string(),
+            )]))
+            .build()
+            .unwrap();
+        assert!(
+            CommandRouter::new(
+                ApplicationContextBuilder::default().build().unwrap(),
+                session.create_managed_client(),
+                router_options,
+            )
+            .is_err()
+        );
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("command name"; "invalid_char")]
+    #[tokio::test]
+    async fn test_add_handler_invalid_command_name(command_name: &str) {
+        let session = create_session();
+        let mut router = create_router(&session);
+        let e = router
+            .add_handler(command_name, noop_handler::<MockPayload, MockPayload>)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    }
+
+    #[tokio::test]
+    async fn test_add_handler_duplicate_command_name() {
+        let session = create_session();
+        let mut router = create_router(&session);
+        router
+            .add_handler("command1", noop_handler::<MockPayload, MockPayload>)
+            .unwrap();
+        let e = router
+            .add_handler("command1", noop_handler::<Vec<u8>, Vec<u8>>)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+        assert_eq!(e.property_name, Some("command_name".to_string()));
+    }
+}
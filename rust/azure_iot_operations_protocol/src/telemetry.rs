@@ -10,9 +10,19 @@ pub mod sender;
 /// This module contains the telemetry receiver implementation.
 pub mod receiver;
 
+/// This module contains middleware for the telemetry receiver.
+pub mod middleware;
+
+/// This module contains the telemetry receiver for several topic patterns.
+pub mod multi_receiver;
+
+/// This module contains the batching of telemetry messages.
+pub mod batch;
+
 /// Re-export the telemetry sender and receiver for ease of use.
+pub use multi_receiver::{MultiReceiver, MultiReceiverBuilder};
 pub use receiver::Receiver;
-pub use sender::Sender;
+pub use sender::{BatchSendResult, SendResult, SendWarning, Sender};
 
 /// Protocol version used by all envoys in this module
 pub(crate) const TELEMETRY_PROTOCOL_VERSION: ProtocolVersion =
@@ -23,3 +33,8 @@ pub(crate) const DEFAULT_TELEMETRY_PROTOCOL_VERSION: ProtocolVersion =
 
 /// Default `CloudEvent` event type for AIO telemetry.
 pub const DEFAULT_TELEMETRY_CLOUD_EVENT_EVENT_TYPE: &str = "ms.aio.telemetry";
+
+/// Highest priority of a telemetry message. Priorities range from 0 (lowest) to this value.
+pub const MAX_TELEMETRY_PRIORITY: u8 = 9;
+/// Default priority given by a [`Receiver`] to telemetry messages that don't specify one.
+pub const DEFAULT_TELEMETRY_PRIORITY: u8 = 4;
@@ -0,0 +1,618 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Batching of several telemetry messages into a single MQTT message.
+//!
+//! A batch is sent with [`Sender::send_batch`], or accumulated by a [`BatchingSender`], and
+//! received as a [`Batch`] payload that can be unbatched with [`Batch::unbatch`].
+//!
+//! # Envelope
+//! A batch is published with the [`BATCH_CONTENT_TYPE`] content type. Its payload is a JSON array
+//! with one object per telemetry message, in the order they were sent:
+//!
+//! ```json
+//! [
+//!   {
+//!     "contentType": "application/json",
+//!     "formatIndicator": 1,
+//!     "userProperties": [["building", "42"], ["__ts", "1700000000000:0:client"]],
+//!     "payload": "eyJ0ZW1wZXJhdHVyZSI6MjF9"
+//!   }
+//! ]
+//! ```
+//!
+//! - `contentType` and `formatIndicator` are those of the serialized payload of the message
+//! - `userProperties` are, in order, the custom user data of the message, its cloud event headers,
+//!   and its timestamp and priority protocol user properties
+//! - `payload` is the serialized payload of the message, base64 encoded
+//!
+//! The MQTT message of a batch carries the protocol user properties that apply to the batch as a
+//! whole (protocol version, source id, timestamp, sequence number, checksum), and its message
+//! expiry is the shortest of the batched messages.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    common::{
+        aio_protocol_error::{AIOProtocolError, Value},
+        hybrid_logical_clock::HybridLogicalClock,
+        payload_serialize::{
+            DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+        },
+        user_properties::ProtocolReservedUserProperty,
+    },
+    telemetry::{
+        MAX_TELEMETRY_PRIORITY,
+        receiver::{CloudEvent, CloudEventParseError},
+        sender::{BatchSendResult, Message, SendResult, Sender},
+    },
+};
+
+/// Content type of a batch of telemetry messages
+pub const BATCH_CONTENT_TYPE: &str = "application/vnd.microsoft.aio.telemetry-batch+json";
+
+/// Maximum packet size allowed by MQTT
+pub const MAX_MQTT_PACKET_SIZE: u32 = 268_435_455;
+
+/// Upper bound of the size of a batch publish besides its topic, client id, partition key and
+/// envelope: fixed header, correlation data, content type, message expiry and protocol user properties
+pub(crate) const PUBLISH_OVERHEAD: usize = 512;
+
+/// Telemetry message in the envelope of a batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvelopeItem {
+    content_type: String,
+    format_indicator: u8,
+    #[serde(default)]
+    user_properties: Vec<(String, String)>,
+    #[serde(with = "base64_payload")]
+    payload: Vec<u8>,
+}
+
+mod base64_payload {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payload: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(payload))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Encodes a telemetry message as an item of a batch envelope
+pub(crate) fn encode_item(
+    serialized_payload: &SerializedPayload,
+    user_properties: Vec<(String, String)>,
+) -> Result<Vec<u8>, AIOProtocolError> {
+    serde_json::to_vec(&EnvelopeItem {
+        content_type: serialized_payload.content_type.clone(),
+        format_indicator: serialized_payload.format_indicator as u8,
+        user_properties,
+        payload: serialized_payload.payload.clone(),
+    })
+    .map_err(|e| {
+        AIOProtocolError::new_payload_invalid_error(
+            true,
+            false,
+            Some(e.into()),
+            Some("Telemetry message could not be batched".to_string()),
+            None,
+        )
+    })
+}
+
+/// Writes the envelope of a batch from encoded items
+#[derive(Debug, Default)]
+pub(crate) struct EnvelopeWriter {
+    /// Encoded items, separated by commas
+    items: Vec<u8>,
+    message_count: usize,
+    message_expiry_interval: Option<u32>,
+}
+
+impl EnvelopeWriter {
+    /// Length of the envelope if `item` was added to it
+    pub(crate) fn len_with(&self, item: &[u8]) -> usize {
+        let separator = usize::from(self.message_count > 0);
+        self.items.len() + separator + item.len() + "[]".len()
+    }
+
+    /// Adds an encoded item to the envelope, along with the message expiry interval of its message
+    pub(crate) fn push(&mut self, item: &[u8], message_expiry_interval: u32) {
+        if self.message_count > 0 {
+            self.items.push(b',');
+        }
+        self.items.extend_from_slice(item);
+        self.message_count += 1;
+        self.message_expiry_interval = Some(
+            self.message_expiry_interval
+                .map_or(message_expiry_interval, |m| m.min(message_expiry_interval)),
+        );
+    }
+
+    pub(crate) fn message_count(&self) -> usize {
+        self.message_count
+    }
+
+    /// Returns the envelope, and the shortest message expiry interval of its messages
+    pub(crate) fn finish(self) -> (Vec<u8>, u32) {
+        let mut envelope = Vec::with_capacity(self.items.len() + 2);
+        envelope.push(b'[');
+        envelope.extend(self.items);
+        envelope.push(b']');
+        (envelope, self.message_expiry_interval.unwrap_or_default())
+    }
+}
+
+/// Payload of a received batch of telemetry messages, with the [`BATCH_CONTENT_TYPE`] content type.
+///
+/// Use a [`Receiver<Batch>`](crate::telemetry::Receiver) to receive batches, and
+/// [`Batch::unbatch`] to get the telemetry messages they contain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Batch {
+    items: Vec<EnvelopeItem>,
+}
+
+impl Batch {
+    /// Returns the number of telemetry messages in the batch
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if the batch contains no telemetry messages
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Deserializes the telemetry messages of the batch, in the order they were sent.
+    ///
+    /// # Errors
+    /// [`UnbatchError`] if a message has an invalid format indicator, timestamp, or payload that
+    /// can't be deserialized into `T`
+    pub fn unbatch<T: PayloadSerialize>(&self) -> Result<Vec<BatchedMessage<T>>, UnbatchError> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                BatchedMessage::try_from(item).map_err(|source| UnbatchError { index, source })
+            })
+            .collect()
+    }
+}
+
+impl PayloadSerialize for Batch {
+    type Error = serde_json::Error;
+
+    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
+        Ok(SerializedPayload {
+            content_type: BATCH_CONTENT_TYPE.to_string(),
+            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+            payload: serde_json::to_vec(&self.items)?,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<Self::Error>> {
+        if content_type.is_none_or(|ct| ct != BATCH_CONTENT_TYPE) {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "Invalid content type: '{content_type:?}'. Must be '{BATCH_CONTENT_TYPE}'"
+            )));
+        }
+        Ok(Batch {
+            items: serde_json::from_slice(payload)?,
+        })
+    }
+}
+
+/// Telemetry message unbatched from a [`Batch`].
+///
+/// Properties of the MQTT message, such as the sender id or topic, are shared by all messages of
+/// the batch and found on the received [`Message<Batch>`](crate::telemetry::receiver::Message).
+#[derive(Debug)]
+pub struct BatchedMessage<T: PayloadSerialize> {
+    /// Payload of the telemetry message
+    pub payload: T,
+    /// Content Type of the telemetry message
+    pub content_type: Option<String>,
+    /// Format Indicator of the telemetry message
+    pub format_indicator: FormatIndicator,
+    /// Custom user data of the telemetry message, including its cloud event headers
+    pub custom_user_data: Vec<(String, String)>,
+    /// Timestamp of the telemetry message
+    pub timestamp: Option<HybridLogicalClock>,
+    /// Priority of the telemetry message, if set by the sender
+    pub priority: Option<u8>,
+}
+
+impl<T: PayloadSerialize> TryFrom<&EnvelopeItem> for BatchedMessage<T> {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn try_from(item: &EnvelopeItem) -> Result<Self, Self::Error> {
+        let mut custom_user_data = Vec::with_capacity(item.user_properties.len());
+        let mut timestamp = None;
+        let mut priority = None;
+        for (key, value) in &item.user_properties {
+            match ProtocolReservedUserProperty::from_str(key) {
+                Ok(ProtocolReservedUserProperty::Timestamp) => {
+                    timestamp = Some(HybridLogicalClock::from_str(value)?);
+                }
+                // An invalid priority is treated as if no priority had been set, as by the Receiver
+                Ok(ProtocolReservedUserProperty::Priority) => {
+                    priority = value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|p| *p <= MAX_TELEMETRY_PRIORITY);
+                }
+                _ => custom_user_data.push((key.clone(), value.clone())),
+            }
+        }
+
+        let format_indicator = FormatIndicator::try_from(Some(item.format_indicator))?;
+        let content_type = Some(item.content_type.clone()).filter(|ct| !ct.is_empty());
+        let payload = T::deserialize(&item.payload, content_type.as_ref(), &format_indicator)
+            .map_err(|e| format!("{e:?}"))?;
+
+        Ok(BatchedMessage {
+            payload,
+            content_type,
+            format_indicator,
+            custom_user_data,
+            timestamp,
+            priority,
+        })
+    }
+}
+
+/// Parse a [`CloudEvent`] from a [`BatchedMessage`].
+/// Note that this will return an error if the [`BatchedMessage`] does not contain the required fields for a [`CloudEvent`].
+///
+/// # Errors
+/// [`CloudEventParseError`] if
+/// - the [`BatchedMessage`] does not contain the required fields for a [`CloudEvent`].
+/// - any of the field values are not valid for a [`CloudEvent`].
+pub fn cloud_event_from_batched_telemetry<T: PayloadSerialize>(
+    telemetry: &BatchedMessage<T>,
+) -> Result<CloudEvent, CloudEventParseError> {
+    CloudEvent::try_from((
+        &telemetry.custom_user_data,
+        telemetry.content_type.as_deref(),
+    ))
+}
+
+/// Error unbatching a telemetry message from a [`Batch`]
+#[derive(Debug, thiserror::Error)]
+#[error("Batched telemetry message {index} is invalid: {source}")]
+pub struct UnbatchError {
+    index: usize,
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl UnbatchError {
+    /// Returns the index in the [`Batch`] of the invalid telemetry message
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Batching Sender Options struct
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+#[allow(clippy::struct_field_names)]
+pub struct BatchingOptions {
+    /// Maximum number of telemetry messages in a batch. Default is 100.
+    #[builder(default = "100")]
+    max_messages: usize,
+    /// Maximum total size of the serialized payloads of the telemetry messages in a batch, in
+    /// bytes. A batch is sent as soon as it reaches this size. Default is 64 KiB.
+    #[builder(default = "64 * 1024")]
+    max_bytes: usize,
+    /// Maximum time a telemetry message waits for other messages to be batched with before being
+    /// sent. Default is 100 milliseconds.
+    #[builder(default = "Duration::from_millis(100)")]
+    max_latency: Duration,
+}
+
+impl BatchingOptionsBuilder {
+    /// Validate the batching options.
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if `max_messages` or `max_bytes` is zero
+    fn validate(&self) -> Result<(), String> {
+        if self.max_messages == Some(0) {
+            return Err("max_messages must be greater than zero".to_string());
+        }
+        if self.max_bytes == Some(0) {
+            return Err("max_bytes must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Result of a telemetry message sent by a [`BatchingSender`], shared by all messages published
+/// in the same batch
+pub type BatchedSendResult = Result<SendResult, Arc<AIOProtocolError>>;
+
+/// Telemetry message waiting to be batched, along with where to report its result
+type PendingMessage<T> = (Message<T>, oneshot::Sender<BatchedSendResult>);
+
+/// Completion token of a telemetry message sent by a [`BatchingSender`], which resolves to the
+/// result of the publish of its batch
+#[must_use]
+pub struct BatchCompletionToken(oneshot::Receiver<BatchedSendResult>);
+
+impl Future for BatchCompletionToken {
+    type Output = BatchedSendResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            result.unwrap_or_else(|_| {
+                Err(Arc::new(AIOProtocolError::new_internal_logic_error(
+                    true,
+                    false,
+                    None,
+                    "batching_task",
+                    None,
+                    Some("Batching task stopped before sending the telemetry message".to_string()),
+                    None,
+                )))
+            })
+        })
+    }
+}
+
+/// Telemetry sender accumulating messages into batches sent with [`Sender::send_batch`].
+///
+/// A batch is sent as soon as it reaches the [`max_messages`](BatchingOptionsBuilder::max_messages)
+/// or [`max_bytes`](BatchingOptionsBuilder::max_bytes) of the [`BatchingOptions`], or once its
+/// first message has waited for the [`max_latency`](BatchingOptionsBuilder::max_latency).
+/// Batches are sent one at a time, in order, and messages sent meanwhile are batched together.
+pub struct BatchingSender<T: PayloadSerialize + Send + Sync + 'static> {
+    message_tx: mpsc::UnboundedSender<PendingMessage<T>>,
+    batching_task: tokio::task::JoinHandle<()>,
+}
+
+impl<T: PayloadSerialize + Send + Sync + 'static> BatchingSender<T> {
+    /// Creates a new [`BatchingSender`] sending batches with `sender`.
+    ///
+    /// Must be called from within a Tokio runtime, on which the batches are sent.
+    #[must_use]
+    pub fn new(sender: Sender<T>, options: BatchingOptions) -> Self {
+        let (message_tx, message_rx) = mpsc::unbounded_channel();
+        let batching_task = tokio::task::spawn(run_batching(sender, options, message_rx));
+        Self {
+            message_tx,
+            batching_task,
+        }
+    }
+
+    /// Adds a [`Message`] to the current batch.
+    ///
+    /// Returns a [`BatchCompletionToken`] resolving to the result of the publish of the batch the
+    /// message is sent in. Dropping the token doesn't prevent the message from being sent.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if the message is retained or persisted, which isn't supported for batched messages
+    pub fn send(&self, message: Message<T>) -> Result<BatchCompletionToken, AIOProtocolError> {
+        if message.is_retained() {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "retain",
+                Value::Boolean(true),
+                Some("Batched telemetry messages cannot be retained or persisted".to_string()),
+                None,
+            ));
+        }
+        let (result_tx, result_rx) = oneshot::channel();
+        // If the batching task has stopped, the token resolves to an error
+        let _ = self.message_tx.send((message, result_tx));
+        Ok(BatchCompletionToken(result_rx))
+    }
+
+    /// Sends the current batch without waiting any longer, and stops batching.
+    /// Returns once all messages have been sent.
+    pub async fn shutdown(self) {
+        drop(self.message_tx);
+        if let Err(e) = self.batching_task.await {
+            log::error!("Telemetry batching task failed: {e}");
+        }
+    }
+}
+
+/// Accumulates messages into batches until all [`BatchingSender`] handles are dropped
+async fn run_batching<T: PayloadSerialize + Send + Sync + 'static>(
+    sender: Sender<T>,
+    options: BatchingOptions,
+    mut message_rx: mpsc::UnboundedReceiver<PendingMessage<T>>,
+) {
+    while let Some(first) = message_rx.recv().await {
+        let deadline = tokio::time::Instant::now() + options.max_latency;
+        let mut batch_bytes = first.0.payload_len();
+        let mut pending = vec![first];
+        while pending.len() < options.max_messages && batch_bytes < options.max_bytes {
+            match tokio::time::timeout_at(deadline, message_rx.recv()).await {
+                Ok(Some(next)) => {
+                    batch_bytes += next.0.payload_len();
+                    pending.push(next);
+                }
+                // Closed or max latency reached
+                Ok(None) | Err(_) => break,
+            }
+        }
+        send_pending(&sender, pending).await;
+    }
+}
+
+/// Sends pending messages as batches and reports the result of each batch to its messages
+async fn send_pending<T: PayloadSerialize>(sender: &Sender<T>, pending: Vec<PendingMessage<T>>) {
+    let (messages, result_txs): (Vec<_>, Vec<_>) = pending.into_iter().unzip();
+    match sender.send_batch(messages).await {
+        Ok(results) => {
+            let mut result_txs = result_txs.into_iter();
+            for BatchSendResult {
+                message_count,
+                result,
+            } in results
+            {
+                let result = result.map_err(Arc::new);
+                for result_tx in result_txs.by_ref().take(message_count) {
+                    // The completion token may have been dropped
+                    let _ = result_tx.send(result.clone());
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Telemetry batch could not be sent: {e}");
+            let e = Arc::new(e);
+            for result_tx in result_txs {
+                let _ = result_tx.send(Err(e.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(payload: &[u8], user_properties: Vec<(String, String)>) -> Vec<u8> {
+        encode_item(
+            &SerializedPayload {
+                content_type: "application/octet-stream".to_string(),
+                format_indicator: FormatIndicator::UnspecifiedBytes,
+                payload: payload.to_vec(),
+            },
+            user_properties,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let timestamp = HybridLogicalClock::new();
+        let mut writer = EnvelopeWriter::default();
+        writer.push(
+            &item(
+                &[1, 2, 3],
+                vec![
+                    ("custom".to_string(), "value".to_string()),
+                    (
+                        ProtocolReservedUserProperty::Timestamp.to_string(),
+                        timestamp.to_string(),
+                    ),
+                    (
+                        ProtocolReservedUserProperty::Priority.to_string(),
+                        "7".to_string(),
+                    ),
+                ],
+            ),
+            30,
+        );
+        writer.push(&item(&[4], Vec::new()), 10);
+        assert_eq!(writer.message_count(), 2);
+
+        let expected_len = writer.len_with(&[]) - 1;
+        let (envelope, message_expiry_interval) = writer.finish();
+        assert_eq!(envelope.len(), expected_len);
+        assert_eq!(message_expiry_interval, 10);
+
+        let batch = Batch::deserialize(
+            &envelope,
+            Some(&BATCH_CONTENT_TYPE.to_string()),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        )
+        .unwrap();
+        assert_eq!(batch.len(), 2);
+        let messages = batch.unbatch::<Vec<u8>>().unwrap();
+        assert_eq!(messages[0].payload, vec![1, 2, 3]);
+        assert_eq!(
+            messages[0].custom_user_data,
+            vec![("custom".to_string(), "value".to_string())]
+        );
+        assert_eq!(messages[0].timestamp, Some(timestamp));
+        assert_eq!(messages[0].priority, Some(7));
+        assert_eq!(messages[1].payload, vec![4]);
+        assert!(messages[1].custom_user_data.is_empty());
+        assert_eq!(messages[1].timestamp, None);
+
+        let reserialized = batch.clone().serialize().unwrap();
+        assert_eq!(reserialized.content_type, BATCH_CONTENT_TYPE);
+        assert_eq!(reserialized.payload, envelope);
+    }
+
+    #[test]
+    fn test_unsupported_content_type() {
+        assert!(matches!(
+            Batch::deserialize(
+                b"[]",
+                Some(&"application/json".to_string()),
+                &FormatIndicator::Utf8EncodedCharacterData,
+            ),
+            Err(DeserializationError::UnsupportedContentType(_))
+        ));
+    }
+
+    #[test]
+    fn test_unbatch_invalid_item() {
+        let mut writer = EnvelopeWriter::default();
+        writer.push(&item(&[1], Vec::new()), 10);
+        writer.push(
+            &item(
+                &[2],
+                vec![(
+                    ProtocolReservedUserProperty::Timestamp.to_string(),
+                    "not a timestamp".to_string(),
+                )],
+            ),
+            10,
+        );
+        let batch = Batch::deserialize(
+            &writer.finish().0,
+            Some(&BATCH_CONTENT_TYPE.to_string()),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        )
+        .unwrap();
+        assert_eq!(batch.unbatch::<Vec<u8>>().unwrap_err().index(), 1);
+    }
+
+    #[test]
+    fn test_batching_options_validation() {
+        assert!(BatchingOptionsBuilder::default().build().is_ok());
+        assert!(
+            BatchingOptionsBuilder::default()
+                .max_messages(0usize)
+                .build()
+                .is_err()
+        );
+        assert!(
+            BatchingOptionsBuilder::default()
+                .max_bytes(0usize)
+                .build()
+                .is_err()
+        );
+    }
+}
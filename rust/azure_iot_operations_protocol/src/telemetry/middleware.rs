@@ -0,0 +1,255 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Middleware applied by a telemetry [`Receiver`](super::Receiver) to every received message
+//! before the payload is deserialized.
+
+use std::{io::Read, panic::AssertUnwindSafe, sync::Arc};
+
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use thiserror::Error;
+
+/// User property indicating the encoding applied to the payload of a telemetry message.
+pub const CONTENT_ENCODING_USER_PROPERTY: &str = "content-encoding";
+/// Value of the [`CONTENT_ENCODING_USER_PROPERTY`] for gzip compressed payloads.
+pub const GZIP_CONTENT_ENCODING: &str = "gzip";
+
+/// Raw contents of a received telemetry message, prior to payload deserialization.
+#[derive(Clone, Debug)]
+pub struct RawMessage {
+    /// Raw payload of the telemetry message
+    pub payload: Bytes,
+    /// Content Type of the telemetry message
+    pub content_type: Option<String>,
+    /// MQTT User Properties of the telemetry message
+    pub user_properties: Vec<(String, String)>,
+    /// Topic the telemetry message was received on
+    topic: String,
+}
+
+impl RawMessage {
+    pub(crate) fn new(
+        payload: Bytes,
+        content_type: Option<String>,
+        user_properties: Vec<(String, String)>,
+        topic: String,
+    ) -> Self {
+        Self {
+            payload,
+            content_type,
+            user_properties,
+            topic,
+        }
+    }
+
+    /// Topic the telemetry message was received on
+    #[must_use]
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+}
+
+/// Error indicating that a [`ReceiveMiddleware`] rejected a received telemetry message.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct Rejection(String);
+
+impl Rejection {
+    /// Create a new [`Rejection`] with the provided reason.
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self(reason.into())
+    }
+}
+
+/// Trait for transforming or rejecting received telemetry messages before delivery.
+///
+/// Middleware is run in the order it was registered on the
+/// [`OptionsBuilder`](super::receiver::OptionsBuilder), with each middleware receiving the
+/// output of the previous one.
+pub trait ReceiveMiddleware: Send + Sync {
+    /// Process a received telemetry message, modifying it in place as needed.
+    ///
+    /// # Errors
+    /// Returns a [`Rejection`] if the message should not be delivered. No further middleware
+    /// will be run on a rejected message.
+    fn process(&self, message: &mut RawMessage) -> Result<(), Rejection>;
+}
+
+/// Describes how a telemetry [`Receiver`](super::Receiver) handles messages rejected by a
+/// [`ReceiveMiddleware`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RejectionBehavior {
+    /// The message is acknowledged and counted, but not delivered.
+    #[default]
+    Ack,
+    /// The message is acknowledged, and an error is delivered in place of it.
+    Error,
+}
+
+/// Runs the provided middleware on the message in order, stopping at the first rejection.
+/// A panicking middleware is treated as a rejection of the message.
+pub(crate) fn apply(
+    middleware: &[Arc<dyn ReceiveMiddleware>],
+    message: &mut RawMessage,
+) -> Result<(), Rejection> {
+    for m in middleware {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| m.process(message))) {
+            Ok(Ok(())) => {}
+            Ok(Err(rejection)) => return Err(rejection),
+            Err(_) => return Err(Rejection::new("Receive middleware panicked")),
+        }
+    }
+    Ok(())
+}
+
+/// [`ReceiveMiddleware`] that decompresses gzip compressed payloads.
+///
+/// Only payloads with the [`CONTENT_ENCODING_USER_PROPERTY`] set to [`GZIP_CONTENT_ENCODING`]
+/// are decompressed, all other messages are passed through unchanged. The user property is
+/// removed once the payload has been decompressed.
+#[derive(Clone, Debug, Default)]
+pub struct GzipDecompression;
+
+impl ReceiveMiddleware for GzipDecompression {
+    fn process(&self, message: &mut RawMessage) -> Result<(), Rejection> {
+        let is_gzip = message.user_properties.iter().any(|(key, value)| {
+            key == CONTENT_ENCODING_USER_PROPERTY && value == GZIP_CONTENT_ENCODING
+        });
+        if !is_gzip {
+            return Ok(());
+        }
+        let mut decompressed = Vec::new();
+        GzDecoder::new(message.payload.as_ref())
+            .read_to_end(&mut decompressed)
+            .map_err(|e| Rejection::new(format!("Payload gzip decompression failed: {e}")))?;
+        message.payload = Bytes::from(decompressed);
+        message
+            .user_properties
+            .retain(|(key, _)| key != CONTENT_ENCODING_USER_PROPERTY);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    struct AppendSuffix(&'static str);
+    impl ReceiveMiddleware for AppendSuffix {
+        fn process(&self, message: &mut RawMessage) -> Result<(), Rejection> {
+            let mut payload = message.payload.to_vec();
+            payload.extend_from_slice(self.0.as_bytes());
+            message.payload = Bytes::from(payload);
+            message
+                .user_properties
+                .push(("suffix".to_string(), self.0.to_string()));
+            Ok(())
+        }
+    }
+
+    struct RejectAll;
+    impl ReceiveMiddleware for RejectAll {
+        fn process(&self, _message: &mut RawMessage) -> Result<(), Rejection> {
+            Err(Rejection::new("rejected"))
+        }
+    }
+
+    struct Panics;
+    impl ReceiveMiddleware for Panics {
+        fn process(&self, _message: &mut RawMessage) -> Result<(), Rejection> {
+            panic!("middleware panic");
+        }
+    }
+
+    fn raw_message(payload: &[u8], user_properties: Vec<(String, String)>) -> RawMessage {
+        RawMessage::new(
+            Bytes::copy_from_slice(payload),
+            Some("text/plain".to_string()),
+            user_properties,
+            "test/telemetry".to_string(),
+        )
+    }
+
+    #[test]
+    fn apply_runs_in_registration_order() {
+        let middleware: Vec<Arc<dyn ReceiveMiddleware>> =
+            vec![Arc::new(AppendSuffix("-a")), Arc::new(AppendSuffix("-b"))];
+        let mut message = raw_message(b"payload", vec![]);
+        apply(&middleware, &mut message).unwrap();
+        assert_eq!(message.payload.as_ref(), b"payload-a-b");
+        assert_eq!(
+            message.user_properties,
+            vec![
+                ("suffix".to_string(), "-a".to_string()),
+                ("suffix".to_string(), "-b".to_string())
+            ]
+        );
+        assert_eq!(message.topic(), "test/telemetry");
+    }
+
+    #[test]
+    fn apply_stops_at_rejection() {
+        let middleware: Vec<Arc<dyn ReceiveMiddleware>> = vec![
+            Arc::new(AppendSuffix("-a")),
+            Arc::new(RejectAll),
+            Arc::new(AppendSuffix("-b")),
+        ];
+        let mut message = raw_message(b"payload", vec![]);
+        let rejection = apply(&middleware, &mut message).unwrap_err();
+        assert_eq!(rejection.to_string(), "rejected");
+        assert_eq!(message.payload.as_ref(), b"payload-a");
+    }
+
+    #[test]
+    fn apply_panic_is_rejection() {
+        let middleware: Vec<Arc<dyn ReceiveMiddleware>> =
+            vec![Arc::new(Panics), Arc::new(AppendSuffix("-a"))];
+        let mut message = raw_message(b"payload", vec![]);
+        assert!(apply(&middleware, &mut message).is_err());
+        assert_eq!(message.payload.as_ref(), b"payload");
+    }
+
+    #[test]
+    fn gzip_decompression() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"compressed payload").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut message = raw_message(
+            &compressed,
+            vec![
+                (
+                    CONTENT_ENCODING_USER_PROPERTY.to_string(),
+                    GZIP_CONTENT_ENCODING.to_string(),
+                ),
+                ("other".to_string(), "value".to_string()),
+            ],
+        );
+        GzipDecompression.process(&mut message).unwrap();
+        assert_eq!(message.payload.as_ref(), b"compressed payload");
+        assert_eq!(
+            message.user_properties,
+            vec![("other".to_string(), "value".to_string())]
+        );
+    }
+
+    #[test]
+    fn gzip_decompression_passthrough_and_invalid() {
+        let mut message = raw_message(b"plain payload", vec![]);
+        GzipDecompression.process(&mut message).unwrap();
+        assert_eq!(message.payload.as_ref(), b"plain payload");
+
+        let mut message = raw_message(
+            b"not gzip",
+            vec![(
+                CONTENT_ENCODING_USER_PROPERTY.to_string(),
+                GZIP_CONTENT_ENCODING.to_string(),
+            )],
+        );
+        assert!(GzipDecompression.process(&mut message).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::task::Poll;
+
+use azure_iot_operations_mqtt::{session::SessionManagedClient, token::AckToken};
+use thiserror::Error;
+
+use crate::{
+    application::ApplicationContext,
+    common::{
+        aio_protocol_error::{AIOProtocolError, Value},
+        payload_serialize::PayloadSerialize,
+    },
+    telemetry::receiver::{Message, Options, Receiver},
+};
+
+/// Error from the receiver of one of the topic patterns of a [`MultiReceiver`]
+#[derive(Debug, Error)]
+#[error("telemetry receiver for topic pattern {pattern_index} ({topic_filter}) failed: {source}")]
+pub struct PatternError {
+    /// Index of the topic pattern, in the order it was added with
+    /// [`MultiReceiverBuilder::pattern`]
+    pub pattern_index: usize,
+    /// Topic filter subscribed to for the topic pattern
+    pub topic_filter: String,
+    /// The error returned by the receiver of the topic pattern
+    #[source]
+    pub source: AIOProtocolError,
+}
+
+/// A [`Receiver`] for one topic pattern of a [`MultiReceiver`], converting its messages to `E`
+#[async_trait::async_trait]
+trait PatternReceiver<E>: Send {
+    async fn subscribe(&mut self) -> Result<(), AIOProtocolError>;
+    async fn recv(&mut self) -> Option<Result<(E, Option<AckToken>), AIOProtocolError>>;
+    async fn shutdown(&mut self) -> Result<(), AIOProtocolError>;
+    fn topic_filter(&self) -> String;
+}
+
+struct TypedPatternReceiver<T, F>
+where
+    T: PayloadSerialize + Send + Sync + 'static,
+{
+    receiver: Receiver<T>,
+    map: F,
+}
+
+#[async_trait::async_trait]
+impl<E, T, F> PatternReceiver<E> for TypedPatternReceiver<T, F>
+where
+    E: Send + 'static,
+    T: PayloadSerialize + Send + Sync + 'static,
+    F: Fn(Message<T>) -> E + Send + Sync + 'static,
+{
+    async fn subscribe(&mut self) -> Result<(), AIOProtocolError> {
+        self.receiver.subscribe_if_new().await
+    }
+
+    async fn recv(&mut self) -> Option<Result<(E, Option<AckToken>), AIOProtocolError>> {
+        self.receiver
+            .recv()
+            .await
+            .map(|result| result.map(|(message, ack_token)| ((self.map)(message), ack_token)))
+    }
+
+    async fn shutdown(&mut self) -> Result<(), AIOProtocolError> {
+        self.receiver.shutdown().await
+    }
+
+    fn topic_filter(&self) -> String {
+        self.receiver.topic_filter().to_string()
+    }
+}
+
+/// Builder for a [`MultiReceiver`].
+///
+/// Each topic pattern is added with its own telemetry receiver [`Options`] and payload type, and a
+/// function converting its messages into the application defined type `E`, usually an enum with a
+/// variant per topic pattern.
+pub struct MultiReceiverBuilder<E> {
+    application_context: ApplicationContext,
+    client: SessionManagedClient,
+    patterns: Vec<Box<dyn PatternReceiver<E>>>,
+}
+
+impl<E: Send + 'static> MultiReceiverBuilder<E> {
+    /// Creates a new [`MultiReceiverBuilder`] for receivers using `client`.
+    #[must_use]
+    pub fn new(application_context: ApplicationContext, client: SessionManagedClient) -> Self {
+        Self {
+            application_context,
+            client,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Adds a topic pattern, received with a [`Receiver`] configured with `receiver_options`,
+    /// whose messages have a payload of type `T` and are converted to `E` with `map`.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if `receiver_options` are invalid. See [`Receiver::new`].
+    pub fn pattern<T, F>(
+        mut self,
+        receiver_options: Options,
+        map: F,
+    ) -> Result<Self, AIOProtocolError>
+    where
+        T: PayloadSerialize + Send + Sync + 'static,
+        F: Fn(Message<T>) -> E + Send + Sync + 'static,
+    {
+        let receiver = Receiver::new(
+            self.application_context.clone(),
+            self.client.clone(),
+            receiver_options,
+        )?;
+        self.patterns
+            .push(Box::new(TypedPatternReceiver { receiver, map }));
+        Ok(self)
+    }
+
+    /// Builds the [`MultiReceiver`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid)
+    /// if no topic pattern was added
+    pub fn build(self) -> Result<MultiReceiver<E>, AIOProtocolError> {
+        if self.patterns.is_empty() {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "patterns",
+                Value::Integer(0),
+                Some("At least one topic pattern must be added".to_string()),
+                None,
+            ));
+        }
+        Ok(MultiReceiver {
+            closed: vec![false; self.patterns.len()],
+            patterns: self.patterns,
+            subscribed: false,
+            next_pattern: 0,
+        })
+    }
+}
+
+/// Receives telemetry messages from several topic patterns, each with its own payload type,
+/// through a single [`recv`](Self::recv).
+///
+/// Create one with a [`MultiReceiverBuilder`]. Each topic pattern is received by its own
+/// [`Receiver`], and the topic patterns are subscribed to together on the first
+/// [`recv`](Self::recv) and unsubscribed from together on [`shutdown`](Self::shutdown).
+///
+/// # Example
+/// ```
+/// # use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+/// # use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+/// # use azure_iot_operations_protocol::telemetry;
+/// # use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+/// # let mut connection_settings = MqttConnectionSettingsBuilder::default()
+/// #     .client_id("test_server")
+/// #     .hostname("mqtt://localhost")
+/// #     .tcp_port(1883u16)
+/// #     .build().unwrap();
+/// # let mut session_options = SessionOptionsBuilder::default()
+/// #     .connection_settings(connection_settings)
+/// #     .build().unwrap();
+/// # let mqtt_session = Session::new(session_options).unwrap();
+/// # let application_context = ApplicationContextBuilder::default().build().unwrap();
+/// # use azure_iot_operations_protocol::common::payload_serialize::{PayloadSerialize, DeserializationError, FormatIndicator, SerializedPayload};
+/// # #[derive(Clone, Debug)]
+/// # struct SensorStatus(String);
+/// # impl PayloadSerialize for SensorStatus {
+/// #   type Error = String;
+/// #   fn serialize(self) -> Result<SerializedPayload, String> { unimplemented!() }
+/// #   fn deserialize(payload: &[u8], _content_type: Option<&String>, _format_indicator: &FormatIndicator) -> Result<Self, DeserializationError<String>> {
+/// #     Ok(SensorStatus(String::from_utf8_lossy(payload).to_string()))
+/// #   }
+/// # }
+/// enum SensorEvent {
+///     Data(telemetry::receiver::Message<Vec<u8>>),
+///     Status(telemetry::receiver::Message<SensorStatus>),
+/// }
+///
+/// let mut receiver = telemetry::MultiReceiverBuilder::new(application_context, mqtt_session.create_managed_client())
+///     .pattern(
+///         telemetry::receiver::OptionsBuilder::default().topic_pattern("sensor/{id}/data").build().unwrap(),
+///         SensorEvent::Data,
+///     ).unwrap()
+///     .pattern(
+///         telemetry::receiver::OptionsBuilder::default().topic_pattern("sensor/{id}/status").build().unwrap(),
+///         SensorEvent::Status,
+///     ).unwrap()
+///     .build().unwrap();
+/// // let (sensor_event, ack_token) = receiver.recv().await.unwrap().unwrap();
+/// ```
+pub struct MultiReceiver<E> {
+    patterns: Vec<Box<dyn PatternReceiver<E>>>,
+    /// Whether the receiver of each topic pattern will return no more messages
+    closed: Vec<bool>,
+    subscribed: bool,
+    /// Topic pattern whose receiver is checked first on the next receive
+    next_pattern: usize,
+}
+
+impl<E> MultiReceiver<E> {
+    /// Receives a telemetry message from any of the topic patterns, converted to `E`, or [`None`]
+    /// if there will be no more messages from any of them. See [`Receiver::recv`].
+    ///
+    /// When messages are available for several topic patterns, the topic patterns take turns, so
+    /// that a busy topic pattern doesn't hold up the others.
+    ///
+    /// Will also subscribe to all the topic patterns if not already subscribed.
+    ///
+    /// # Errors
+    /// [`PatternError`] identifying the topic pattern whose receiver failed, if the subscribe fails
+    /// or its receiver returns an error. See [`Receiver::recv`].
+    pub async fn recv(&mut self) -> Option<Result<(E, Option<AckToken>), PatternError>> {
+        if !self.subscribed {
+            for (pattern_index, pattern) in self.patterns.iter_mut().enumerate() {
+                if let Err(source) = pattern.subscribe().await {
+                    return Some(Err(PatternError {
+                        pattern_index,
+                        topic_filter: pattern.topic_filter(),
+                        source,
+                    }));
+                }
+            }
+            self.subscribed = true;
+        }
+
+        let pattern_count = self.patterns.len();
+        let start = self.next_pattern;
+        let closed = &mut self.closed;
+        let mut receives: Vec<_> = self
+            .patterns
+            .iter_mut()
+            .map(|pattern| pattern.recv())
+            .collect();
+        let (pattern_index, result) = std::future::poll_fn(|cx| {
+            for offset in 0..pattern_count {
+                let pattern_index = (start + offset) % pattern_count;
+                if closed[pattern_index] {
+                    continue;
+                }
+                match receives[pattern_index].as_mut().poll(cx) {
+                    Poll::Ready(Some(result)) => return Poll::Ready(Some((pattern_index, result))),
+                    Poll::Ready(None) => closed[pattern_index] = true,
+                    Poll::Pending => {}
+                }
+            }
+            if closed.iter().all(|closed| *closed) {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await?;
+        drop(receives);
+
+        self.next_pattern = (pattern_index + 1) % pattern_count;
+        Some(result.map_err(|source| PatternError {
+            pattern_index,
+            topic_filter: self.patterns[pattern_index].topic_filter(),
+            source,
+        }))
+    }
+
+    /// Shutdown the receivers of all the topic patterns, unsubscribing from the topic patterns
+    /// that were subscribed to. See [`Receiver::shutdown`].
+    ///
+    /// The receivers of all the topic patterns are shut down even if some fail. If the method
+    /// returns an error, it may be called again to attempt the unsubscribes again.
+    ///
+    /// # Errors
+    /// [`PatternError`] identifying the first topic pattern whose receiver failed to shut down
+    pub async fn shutdown(&mut self) -> Result<(), PatternError> {
+        let mut result = Ok(());
+        for (pattern_index, pattern) in self.patterns.iter_mut().enumerate() {
+            if let Err(source) = pattern.shutdown().await
+                && result.is_ok()
+            {
+                result = Err(PatternError {
+                    pattern_index,
+                    topic_filter: pattern.topic_filter(),
+                    source,
+                });
+            }
+        }
+        result
+    }
+}
@@ -1,6 +1,13 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
-use std::{collections::HashMap, marker::PhantomData, str::FromStr, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    marker::PhantomData,
+    num::{NonZeroU16, NonZeroU32},
+    str::FromStr,
+    sync::Arc,
+};
 
 use azure_iot_operations_mqtt::{
     aio::cloud_event as aio_cloud_event,
@@ -8,19 +15,24 @@ use azure_iot_operations_mqtt::{
     session::{SessionManagedClient, SessionPubReceiver},
     token::AckToken,
 };
+use bytes::Bytes;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     ProtocolVersion,
     application::{ApplicationContext, ApplicationHybridLogicalClock},
     common::{
-        aio_protocol_error::AIOProtocolError,
+        aio_protocol_error::{AIOProtocolError, Value},
         hybrid_logical_clock::HybridLogicalClock,
+        payload_checksum,
         payload_serialize::{FormatIndicator, PayloadSerialize},
         topic_processor::TopicPattern,
         user_properties::ProtocolReservedUserProperty,
     },
-    telemetry::DEFAULT_TELEMETRY_PROTOCOL_VERSION,
+    telemetry::{
+        DEFAULT_TELEMETRY_PRIORITY, DEFAULT_TELEMETRY_PROTOCOL_VERSION, MAX_TELEMETRY_PRIORITY,
+        middleware::{self, RawMessage, ReceiveMiddleware, RejectionBehavior},
+    },
 };
 
 const SUPPORTED_PROTOCOL_VERSIONS: &[u16] = &[1];
@@ -66,8 +78,29 @@ pub struct Message<T: PayloadSerialize> {
     pub topic_tokens: HashMap<String, String>,
     /// Incoming message topic
     pub topic: String,
-    /// Indicates if the message is a duplicate delivery if QoS 1 (DUP flag in MQTT publish)
+    /// Response topic set on the telemetry message, if any.
+    pub response_topic: Option<String>,
+    /// Correlation data set on the telemetry message, if any.
+    pub correlation_data: Option<Bytes>,
+    /// Identifiers of the subscriptions the telemetry message was delivered for.
+    pub subscription_identifiers: Vec<u32>,
+    /// Topic alias used by the broker to deliver the telemetry message, if any.
+    pub topic_alias: Option<u16>,
+    /// Indicates if the message is a duplicate delivery if QoS 1 (DUP flag in MQTT publish), e.g.
+    /// a redelivery by the broker after a reconnect of a message that wasn't acknowledged.
+    /// Always `Some(false)` for QoS 1 if the [`Receiver`] is configured to
+    /// [`Deliver`](RedeliveryPolicy::Deliver) redeliveries.
     pub duplicate: Option<bool>,
+    /// Priority of the telemetry message, if set by the sender.
+    pub priority: Option<u8>,
+    /// Sequence number of the telemetry message, if the sender numbers its messages.
+    pub sequence_number: Option<u64>,
+    /// Id of the sender instance that numbered the telemetry message, if the sender numbers its messages.
+    pub sender_instance_id: Option<String>,
+    /// Number of messages from the same sender instance on the same topic that were not received
+    /// between the previously received message and this one, if any. Only set if the [`Receiver`]
+    /// is configured to [`detect_sequence_gaps`](OptionsBuilder::detect_sequence_gaps).
+    pub gap_detected: Option<u64>,
 }
 
 impl<T> TryFrom<Publish> for Message<T>
@@ -90,6 +123,10 @@ where
             ProtocolReservedUserProperty::Timestamp,
             ProtocolReservedUserProperty::ProtocolVersion,
             ProtocolReservedUserProperty::SourceId,
+            ProtocolReservedUserProperty::Priority,
+            ProtocolReservedUserProperty::SequenceNumber,
+            ProtocolReservedUserProperty::SenderInstanceId,
+            ProtocolReservedUserProperty::PayloadChecksum,
         ];
         let mut telemetry_custom_user_data = vec![];
         let mut telemetry_aio_data = HashMap::new();
@@ -141,6 +178,30 @@ where
             .transpose()
             .map_err(|e| e.to_string())?;
 
+        // Parse priority. An invalid priority is not a reason to reject the message, it is treated
+        // as if no priority had been set
+        let priority = telemetry_aio_data
+            .get(&ProtocolReservedUserProperty::Priority)
+            .and_then(|s| match s.parse::<u8>() {
+                Ok(p) if p <= MAX_TELEMETRY_PRIORITY => Some(p),
+                _ => {
+                    log::warn!("Received a telemetry with an invalid priority: {s}");
+                    None
+                }
+            });
+
+        // Parse sequence number. An invalid sequence number is not a reason to reject the message,
+        // it is treated as if the message had not been numbered
+        let sequence_number = telemetry_aio_data
+            .get(&ProtocolReservedUserProperty::SequenceNumber)
+            .and_then(|s| {
+                s.parse::<u64>()
+                    .inspect_err(|_| {
+                        log::warn!("Received a telemetry with an invalid sequence number: {s}");
+                    })
+                    .ok()
+            });
+
         // Deserialize payload
         let format_indicator = publish_properties.payload_format_indicator.into();
 
@@ -168,12 +229,41 @@ where
             // NOTE: Topic Tokens cannot be created from just a Publish, they need additional information
             topic_tokens: HashMap::default(),
             topic: value.topic_name.as_str().to_string(),
+            response_topic: publish_properties
+                .response_topic
+                .map(|t| t.as_str().to_string()),
+            correlation_data: publish_properties.correlation_data,
+            subscription_identifiers: publish_properties
+                .subscription_identifiers
+                .into_iter()
+                .map(NonZeroU32::get)
+                .collect(),
+            topic_alias: publish_properties.topic_alias.map(NonZeroU16::get),
             duplicate,
+            priority,
+            sequence_number,
+            sender_instance_id: telemetry_aio_data
+                .remove(&ProtocolReservedUserProperty::SenderInstanceId),
+            gap_detected: None,
         };
         Ok(telemetry_message)
     }
 }
 
+/// Describes how a telemetry [`Receiver`] handles messages redelivered by the broker (DUP flag in
+/// MQTT publish), which may have already been processed by the application before a reconnect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RedeliveryPolicy {
+    /// The message is delivered like any other, without indicating that it was redelivered.
+    Deliver,
+    /// The message is delivered with [`duplicate`](Message::duplicate) set.
+    #[default]
+    DeliverFlagged,
+    /// The message is acknowledged, but not delivered. Use this when processing the same message
+    /// twice is worse than missing a message whose processing was interrupted by a reconnect.
+    AutoAckAndDrop,
+}
+
 /// Telemetry Receiver Options struct
 #[derive(Builder, Clone)]
 #[builder(setter(into, strip_option))]
@@ -194,6 +284,119 @@ pub struct Options {
     #[allow(unused)]
     #[builder(default = "None")]
     service_group_id: Option<String>,
+    /// Middleware applied, in order, to every received telemetry message before the payload is
+    /// deserialized
+    #[builder(default)]
+    middleware: Vec<Arc<dyn ReceiveMiddleware>>,
+    /// How telemetry messages rejected by a middleware are handled
+    #[builder(default)]
+    rejection_behavior: RejectionBehavior,
+    /// If true, telemetry messages that have been received but not yet delivered are delivered
+    /// highest priority first instead of in the order they were received.
+    /// Acknowledgements are still sent to the broker in the order messages were received.
+    #[builder(default = "false")]
+    prioritized: bool,
+    /// Priority given to telemetry messages that don't specify one when
+    /// [`prioritized`](OptionsBuilder::prioritized) is true
+    #[builder(default = "DEFAULT_TELEMETRY_PRIORITY")]
+    default_priority: u8,
+    /// Maximum number of received telemetry messages held to be ordered by priority when
+    /// [`prioritized`](OptionsBuilder::prioritized) is true
+    #[builder(default = "DEFAULT_PRIORITY_BUFFER_SIZE")]
+    priority_buffer_size: usize,
+    /// If true, the sequence numbers of received telemetry messages are tracked per sender and
+    /// topic, and messages received after others were missed are annotated with
+    /// [`gap_detected`](Message::gap_detected).
+    #[builder(default = "false")]
+    detect_sequence_gaps: bool,
+    /// How telemetry messages redelivered by the broker are handled
+    #[builder(default)]
+    on_redelivery: RedeliveryPolicy,
+}
+
+/// Default value of [`priority_buffer_size`](OptionsBuilder::priority_buffer_size)
+const DEFAULT_PRIORITY_BUFFER_SIZE: usize = 100;
+
+/// A received telemetry message waiting to be delivered in priority order
+struct PrioritizedMessage<T: PayloadSerialize> {
+    priority: u8,
+    sequence: u64,
+    message: Message<T>,
+    ack_token: Option<AckToken>,
+}
+
+impl<T: PayloadSerialize> PartialEq for PrioritizedMessage<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: PayloadSerialize> Eq for PrioritizedMessage<T> {}
+
+impl<T: PayloadSerialize> PartialOrd for PrioritizedMessage<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: PayloadSerialize> Ord for PrioritizedMessage<T> {
+    /// Higher priority first, then earlier received first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Tracks the sequence numbers of received telemetry messages to detect missed messages
+#[derive(Default)]
+struct SequenceTracker {
+    /// Sender instance id and last sequence number received per sender id and topic
+    last_received: HashMap<(String, String), (String, u64)>,
+    /// Number of gaps detected
+    gaps_detected: u64,
+}
+
+impl SequenceTracker {
+    /// Tracks a received sequence number, returning the number of messages missed since the
+    /// previously received message from the same sender on the same topic, if any.
+    ///
+    /// Messages from a new sender instance restart tracking, and sequence numbers that are not
+    /// ahead of the last received one (e.g. redeliveries) are ignored. Sequence numbers are
+    /// compared with wrapping arithmetic so that wraparound is not reported as a gap.
+    fn track(
+        &mut self,
+        sender_id: &str,
+        topic: &str,
+        sender_instance_id: &str,
+        sequence_number: u64,
+    ) -> Option<u64> {
+        let key = (sender_id.to_string(), topic.to_string());
+        let Some((last_instance_id, last)) = self.last_received.get_mut(&key) else {
+            self.last_received
+                .insert(key, (sender_instance_id.to_string(), sequence_number));
+            return None;
+        };
+        if last_instance_id != sender_instance_id {
+            // The sender restarted and numbers its messages from the start again
+            *last_instance_id = sender_instance_id.to_string();
+            *last = sequence_number;
+            return None;
+        }
+
+        let distance = sequence_number.wrapping_sub(*last);
+        if distance == 0 || distance > u64::MAX / 2 {
+            // Already received, or received out of order after a later message
+            return None;
+        }
+        *last = sequence_number;
+        if distance == 1 {
+            None
+        } else {
+            self.gaps_detected += 1;
+            Some(distance - 1)
+        }
+    }
 }
 
 /// Telemetry Receiver struct
@@ -238,6 +441,19 @@ where
     cancellation_token: CancellationToken,
     // User autoack setting
     auto_ack: bool,
+    // Middleware applied to received messages
+    middleware: Vec<Arc<dyn ReceiveMiddleware>>,
+    rejection_behavior: RejectionBehavior,
+    rejected_count: u64,
+    // Priority ordering of received messages
+    prioritized: bool,
+    default_priority: u8,
+    priority_buffer_size: usize,
+    priority_buffer: BinaryHeap<PrioritizedMessage<T>>,
+    received_count: u64,
+    // Sequence gap detection, if enabled
+    sequence_tracker: Option<SequenceTracker>,
+    on_redelivery: RedeliveryPolicy,
 }
 
 /// Describes state of receiver
@@ -269,12 +485,35 @@ where
     ///   or contain a token with no valid replacement
     /// - [`topic_token_map`](OptionsBuilder::topic_token_map) is not empty
     ///   and contains invalid key(s) and/or token(s)
+    /// - [`default_priority`](OptionsBuilder::default_priority) is greater than [`MAX_TELEMETRY_PRIORITY`]
+    /// - [`priority_buffer_size`](OptionsBuilder::priority_buffer_size) is zero
     #[allow(clippy::needless_pass_by_value)]
     pub fn new(
         application_context: ApplicationContext,
         client: SessionManagedClient,
         receiver_options: Options,
     ) -> Result<Self, AIOProtocolError> {
+        if receiver_options.default_priority > MAX_TELEMETRY_PRIORITY {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "receiver_options.default_priority",
+                Value::Integer(receiver_options.default_priority.into()),
+                Some(format!(
+                    "Default priority must be less than or equal to {MAX_TELEMETRY_PRIORITY}"
+                )),
+                None,
+            ));
+        }
+        if receiver_options.priority_buffer_size == 0 {
+            return Err(AIOProtocolError::new_configuration_invalid_error(
+                None,
+                "receiver_options.priority_buffer_size",
+                Value::Integer(0),
+                Some("Priority buffer size must be greater than zero".to_string()),
+                None,
+            ));
+        }
+
         // Validation for topic pattern and related options done in
         // [`TopicPattern::new`]
         let topic_pattern = TopicPattern::new(
@@ -310,9 +549,38 @@ where
             state: State::New,
             cancellation_token: CancellationToken::new(),
             auto_ack: receiver_options.auto_ack,
+            middleware: receiver_options.middleware,
+            rejection_behavior: receiver_options.rejection_behavior,
+            rejected_count: 0,
+            prioritized: receiver_options.prioritized,
+            default_priority: receiver_options.default_priority,
+            priority_buffer_size: receiver_options.priority_buffer_size,
+            priority_buffer: BinaryHeap::new(),
+            received_count: 0,
+            sequence_tracker: receiver_options
+                .detect_sequence_gaps
+                .then(SequenceTracker::default),
+            on_redelivery: receiver_options.on_redelivery,
         })
     }
 
+    /// Returns the number of telemetry messages that have been rejected by a
+    /// [`ReceiveMiddleware`] since the [`Receiver`] was created.
+    #[must_use]
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected_count
+    }
+
+    /// Returns the number of gaps in the sequence numbers of received telemetry messages that
+    /// have been detected since the [`Receiver`] was created. Always zero unless the [`Receiver`]
+    /// is configured to [`detect_sequence_gaps`](OptionsBuilder::detect_sequence_gaps).
+    #[must_use]
+    pub fn gaps_detected(&self) -> u64 {
+        self.sequence_tracker
+            .as_ref()
+            .map_or(0, |sequence_tracker| sequence_tracker.gaps_detected)
+    }
+
     /// Shutdown the [`Receiver`]. Unsubscribes from the telemetry topic if subscribed.
     ///
     /// Note: If this method is called, the [`Receiver`] will no longer receive telemetry messages
@@ -383,7 +651,7 @@ where
     ///
     /// Returns Ok(()) on success, otherwise returns [`AIOProtocolError`].
     /// # Errors
-    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
     async fn try_subscribe(&mut self) -> Result<(), AIOProtocolError> {
         let subscribe_result = self
             .mqtt_client
@@ -399,7 +667,7 @@ where
         match subscribe_result {
             Ok(sub_ct) => match sub_ct.await {
                 Ok(suback) => {
-                    suback.as_result().map_err(|e| {
+                    suback.as_result_with_qos(QoS::AtLeastOnce).map_err(|e| {
                         log::error!("Telemetry Receiver Suback error: {suback:?}");
                         AIOProtocolError::new_mqtt_error(
                             Some("MQTT error on telemetry receiver suback".to_string()),
@@ -429,6 +697,23 @@ where
         Ok(())
     }
 
+    /// Subscribe to the telemetry topic if the [`Receiver`] has not subscribed yet.
+    ///
+    /// # Errors
+    /// See [`try_subscribe`](Self::try_subscribe)
+    pub(crate) async fn subscribe_if_new(&mut self) -> Result<(), AIOProtocolError> {
+        if self.state == State::New {
+            self.try_subscribe().await?;
+            self.state = State::Subscribed;
+        }
+        Ok(())
+    }
+
+    /// Returns the topic filter the [`Receiver`] subscribes to
+    pub(crate) fn topic_filter(&self) -> &TopicFilter {
+        &self.telemetry_topic
+    }
+
     /// Receives a telemetry message or [`None`] if there will be no more messages.
     /// If there are messages:
     /// - Returns Ok([`Message`], [`Option<AckToken>`]) on success
@@ -442,103 +727,259 @@ where
     /// contains a [`duplicate`](Message::duplicate) field that indicates if the message is a duplicate delivery. It is
     /// left up to the application to handle duplicate messages appropriately.
     ///
+    /// If the [`Receiver`] is [`prioritized`](OptionsBuilder::prioritized), the highest priority message
+    /// out of the messages already received (up to [`priority_buffer_size`](OptionsBuilder::priority_buffer_size))
+    /// is returned. Acknowledgements are still delivered to the broker in the order messages were received,
+    /// so a message's acknowledgement is not sent until all messages received before it have been acknowledged.
+    ///
     /// Will also subscribe to the telemetry topic if not already subscribed.
     ///
     /// # Errors
-    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    /// [`AIOProtocolError`] of kind [`ClientError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails, if the suback reason code doesn't indicate success, or if the granted QoS is lower than the requested QoS 1.
+    ///
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid)
+    /// if a message's payload does not match the checksum attached by the sender, in which case the
+    /// nested error is an [`IntegrityCheckFailed`](payload_checksum::IntegrityCheckFailed). The
+    /// message is acknowledged.
     pub async fn recv(
         &mut self,
     ) -> Option<Result<(Message<T>, Option<AckToken>), AIOProtocolError>> {
         // Subscribe to the telemetry topic if not already subscribed
-        if self.state == State::New {
-            if let Err(e) = self.try_subscribe().await {
-                return Some(Err(e));
+        if let Err(e) = self.subscribe_if_new().await {
+            return Some(Err(e));
+        }
+
+        if self.prioritized {
+            return self.recv_prioritized().await;
+        }
+
+        loop {
+            let (m, ack_token) = self.mqtt_receiver.recv_manual_ack().await?;
+            if let Some(result) = self.process_publish(m, ack_token) {
+                return Some(result);
             }
-            self.state = State::Subscribed;
         }
+    }
 
+    /// Receives the highest priority telemetry message out of the messages that have already been
+    /// received, waiting for a message if there are none.
+    async fn recv_prioritized(
+        &mut self,
+    ) -> Option<Result<(Message<T>, Option<AckToken>), AIOProtocolError>> {
         loop {
-            match self.mqtt_receiver.recv_manual_ack().await {
-                Some((m, mut ack_token)) => {
-                    // Drop the ack token if the user does not desire it
-                    // TODO: change API around this receive to simplify
-                    if self.auto_ack {
-                        // Replace the token with None (if Some)
-                        ack_token.take();
+            // Wait for a message if none are buffered
+            if self.priority_buffer.is_empty() {
+                let (m, ack_token) = self.mqtt_receiver.recv_manual_ack().await?;
+                match self.process_publish(m, ack_token) {
+                    Some(Ok((message, ack_token))) => self.buffer_prioritized(message, ack_token),
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => continue,
+                }
+            }
+
+            // Take any other messages that have already been received, up to the buffer size
+            while self.priority_buffer.len() < self.priority_buffer_size {
+                let Some((m, ack_token)) = self.mqtt_receiver.try_recv_manual_ack() else {
+                    break;
+                };
+                match self.process_publish(m, ack_token) {
+                    Some(Ok((message, ack_token))) => self.buffer_prioritized(message, ack_token),
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => {}
+                }
+            }
+
+            if let Some(prioritized_message) = self.priority_buffer.pop() {
+                return Some(Ok((
+                    prioritized_message.message,
+                    prioritized_message.ack_token,
+                )));
+            }
+        }
+    }
+
+    /// Adds a received message to the priority buffer
+    fn buffer_prioritized(&mut self, message: Message<T>, ack_token: Option<AckToken>) {
+        self.received_count += 1;
+        self.priority_buffer.push(PrioritizedMessage {
+            priority: message.priority.unwrap_or(self.default_priority),
+            sequence: self.received_count,
+            message,
+            ack_token,
+        });
+    }
+
+    /// Processes a received publish into a [`Message`].
+    /// Returns [`None`] if the publish will not be delivered to the application.
+    #[allow(clippy::type_complexity)]
+    fn process_publish(
+        &mut self,
+        mut m: Publish,
+        mut ack_token: Option<AckToken>,
+    ) -> Option<Result<(Message<T>, Option<AckToken>), AIOProtocolError>> {
+        // Drop the ack token if the user does not desire it
+        // TODO: change API around this receive to simplify
+        if self.auto_ack {
+            // Replace the token with None (if Some)
+            ack_token.take();
+        }
+
+        // Get pkid for logging
+        let pkid = match m.qos {
+            azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtMostOnce => {
+                // CONSIDER: maybe we should log with something else, but this matches old behavior
+                // QoS0 doesn't have a packet id, but 0 isn't a valid packet id, and rumqttc used to use 0
+                0
+            }
+            azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtLeastOnce(delivery_info) => {
+                delivery_info.packet_identifier.get()
+            }
+            azure_iot_operations_mqtt::control_packet::DeliveryQoS::ExactlyOnce(_) => {
+                // This should never happen as the telemetry receiver should always receive QoS 1 messages
+                log::warn!("Received QoS 2 telemetry message");
+                return None;
+            }
+        };
+
+        // Process the received message
+        log::debug!("[pkid: {pkid}] Received message");
+
+        let duplicate = m.dup();
+        if duplicate && self.on_redelivery == RedeliveryPolicy::AutoAckAndDrop {
+            log::debug!("[pkid: {pkid}] Dropping redelivered message");
+            self.ack_in_background(ack_token, pkid);
+            return None;
+        }
+
+        // Verify the payload against the checksum attached by the sender, if any. This is done
+        // before any middleware, since the checksum covers the payload as it was published.
+        let checksum_property = ProtocolReservedUserProperty::PayloadChecksum.to_string();
+        let checksum = m
+            .properties
+            .user_properties
+            .iter()
+            .find(|(key, _)| *key == checksum_property)
+            .map(|(_, value)| value.as_str());
+        if let Err(e) = payload_checksum::verify(&m.payload, checksum) {
+            log::warn!("[pkid: {pkid}] {e}");
+            // Ack on error to prevent redelivery
+            self.ack_in_background(ack_token, pkid);
+            return Some(Err(AIOProtocolError::new_payload_invalid_error(
+                false,
+                false,
+                Some(Box::new(e)),
+                Some("Telemetry payload does not match its checksum".to_string()),
+                None,
+            )));
+        }
+
+        // Apply middleware before the payload is deserialized
+        if !self.middleware.is_empty() {
+            let mut raw_message = RawMessage::new(
+                std::mem::take(&mut m.payload),
+                m.properties.content_type.take(),
+                std::mem::take(&mut m.properties.user_properties),
+                m.topic_name.as_str().to_string(),
+            );
+            if let Err(rejection) = middleware::apply(&self.middleware, &mut raw_message) {
+                log::warn!("[pkid: {pkid}] Telemetry rejected by middleware: {rejection}");
+                self.rejected_count += 1;
+                // Ack on rejection to prevent redelivery
+                self.ack_in_background(ack_token, pkid);
+                match self.rejection_behavior {
+                    RejectionBehavior::Ack => return None,
+                    RejectionBehavior::Error => {
+                        return Some(Err(AIOProtocolError::new_payload_invalid_error(
+                            false,
+                            false,
+                            Some(Box::new(rejection)),
+                            Some("Telemetry message rejected by receive middleware".to_string()),
+                            None,
+                        )));
                     }
+                }
+            }
+            m.payload = raw_message.payload;
+            m.properties.content_type = raw_message.content_type;
+            m.properties.user_properties = raw_message.user_properties;
+        }
 
-                    // Get pkid for logging
-                    let pkid = match m.qos {
-                        azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtMostOnce => {
-                            // CONSIDER: maybe we should log with something else, but this matches old behavior
-                            // QoS0 doesn't have a packet id, but 0 isn't a valid packet id, and rumqttc used to use 0
-                            0
-                        }
-                        azure_iot_operations_mqtt::control_packet::DeliveryQoS::AtLeastOnce(
-                            delivery_info,
-                        ) => delivery_info.packet_identifier.get(),
-                        azure_iot_operations_mqtt::control_packet::DeliveryQoS::ExactlyOnce(_) => {
-                            // This should never happen as the telemetry receiver should always receive QoS 1 messages
-                            log::warn!("Received QoS 2 telemetry message");
-                            continue;
-                        }
-                    };
-
-                    // Process the received message
-                    log::debug!("[pkid: {pkid}] Received message");
-
-                    match TryInto::<Message<T>>::try_into(m) {
-                        Ok(mut message) => {
-                            // Update the topic tokens
-                            // NOTE: Tokens can't be added as part of the try_into conversion, as
-                            // it requires knowledge from the Receiver.
-                            message
-                                .topic_tokens
-                                .extend(self.topic_pattern.parse_tokens(&message.topic));
-
-                            // Update application HLC
-                            if let Some(hlc) = &message.timestamp
-                                && let Err(e) = self.application_hlc.update(hlc)
-                            {
-                                log::warn!(
-                                    "[pkid: {pkid}]: Failure updating application HLC against received telemetry HLC {hlc}: {e}"
-                                );
-                            }
-                            return Some(Ok((message, ack_token)));
-                        }
-                        Err(e_string) => {
-                            log::warn!("[pkid: {pkid}] {e_string}");
-
-                            // Ack on error to prevent redelivery
-                            if let Some(ack_token) = ack_token {
-                                tokio::spawn({
-                                    let receiver_cancellation_token_clone =
-                                        self.cancellation_token.clone();
-                                    async move {
-                                        tokio::select! {
-                                            () = receiver_cancellation_token_clone.cancelled() => { /* Received loop cancelled */ },
-                                            ack_res = ack_token.ack() => {
-                                                match ack_res {
-                                                    Ok(_) => { /* Success */ }
-                                                    Err(e) => {
-                                                        log::warn!("[pkid: {pkid}] Telemetry Receiver Ack error {e}");
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                });
-                            }
-                        }
+        match TryInto::<Message<T>>::try_into(m) {
+            Ok(mut message) => {
+                if duplicate && self.on_redelivery == RedeliveryPolicy::Deliver {
+                    message.duplicate = Some(false);
+                }
+
+                // Update the topic tokens
+                // NOTE: Tokens can't be added as part of the try_into conversion, as
+                // it requires knowledge from the Receiver.
+                message
+                    .topic_tokens
+                    .extend(self.topic_pattern.parse_tokens(&message.topic));
+
+                // Detect missed messages from the same sender
+                if let Some(sequence_tracker) = &mut self.sequence_tracker
+                    && let (Some(sender_id), Some(sender_instance_id), Some(sequence_number)) = (
+                        &message.sender_id,
+                        &message.sender_instance_id,
+                        message.sequence_number,
+                    )
+                {
+                    message.gap_detected = sequence_tracker.track(
+                        sender_id,
+                        &message.topic,
+                        sender_instance_id,
+                        sequence_number,
+                    );
+                    if let Some(missed) = message.gap_detected {
+                        log::warn!(
+                            "[pkid: {pkid}] {missed} telemetry message(s) from {sender_id} on topic {} were not received",
+                            message.topic
+                        );
                     }
                 }
-                _ => {
-                    // There will be no more messages
-                    return None;
+
+                // Update application HLC
+                if let Some(hlc) = &message.timestamp
+                    && let Err(e) = self.application_hlc.update(hlc)
+                {
+                    log::warn!(
+                        "[pkid: {pkid}]: Failure updating application HLC against received telemetry HLC {hlc}: {e}"
+                    );
                 }
+                Some(Ok((message, ack_token)))
+            }
+            Err(e_string) => {
+                log::warn!("[pkid: {pkid}] {e_string}");
+
+                // Ack on error to prevent redelivery
+                self.ack_in_background(ack_token, pkid);
+                None
             }
         }
     }
+
+    /// Acks a message that will not be delivered to the application, if it has an [`AckToken`].
+    fn ack_in_background(&self, ack_token: Option<AckToken>, pkid: u16) {
+        if let Some(ack_token) = ack_token {
+            tokio::spawn({
+                let receiver_cancellation_token_clone = self.cancellation_token.clone();
+                async move {
+                    tokio::select! {
+                        () = receiver_cancellation_token_clone.cancelled() => { /* Received loop cancelled */ },
+                        ack_res = ack_token.ack() => {
+                            match ack_res {
+                                Ok(_) => { /* Success */ }
+                                Err(e) => {
+                                    log::warn!("[pkid: {pkid}] Telemetry Receiver Ack error {e}");
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    }
 }
 
 impl<T> Drop for Receiver<T>
@@ -684,6 +1125,36 @@ mod tests {
         }
     }
 
+    #[test_case(MAX_TELEMETRY_PRIORITY + 1, 1, "receiver_options.default_priority"; "default_priority_above_max")]
+    #[test_case(DEFAULT_TELEMETRY_PRIORITY, 0, "receiver_options.priority_buffer_size"; "zero_priority_buffer_size")]
+    fn test_new_invalid_priority_options(
+        default_priority: u8,
+        priority_buffer_size: usize,
+        property_name: &str,
+    ) {
+        let session = get_session();
+        let receiver_options = OptionsBuilder::default()
+            .topic_pattern("test/receiver")
+            .prioritized(true)
+            .default_priority(default_priority)
+            .priority_buffer_size(priority_buffer_size)
+            .build()
+            .unwrap();
+
+        let result: Result<Receiver<MockPayload>, _> = Receiver::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            receiver_options,
+        );
+        match result {
+            Ok(_) => panic!("Expected error"),
+            Err(e) => {
+                assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+                assert_eq!(e.property_name, Some(property_name.to_string()));
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_shutdown_without_subscribe() {
         let session = get_session();
@@ -700,6 +1171,67 @@ mod tests {
         .unwrap();
         assert!(receiver.shutdown().await.is_ok());
     }
+
+    #[test_case(&[0, 1, 2], &[None, None, None]; "consecutive")]
+    #[test_case(&[0, 1, 4], &[None, None, Some(2)]; "gap")]
+    #[test_case(&[0, 1, 1, 0, 2], &[None, None, None, None, None]; "redelivered")]
+    #[test_case(&[u64::MAX - 1, u64::MAX, 0, 1], &[None, None, None, None]; "wraparound")]
+    #[test_case(&[u64::MAX, 1], &[None, Some(1)]; "gap across wraparound")]
+    fn test_sequence_tracker(sequence_numbers: &[u64], expected: &[Option<u64>]) {
+        let mut sequence_tracker = SequenceTracker::default();
+        let gaps: Vec<Option<u64>> = sequence_numbers
+            .iter()
+            .map(|sequence_number| {
+                sequence_tracker.track("sender", "topic", "instance", *sequence_number)
+            })
+            .collect();
+        assert_eq!(gaps, expected);
+        assert_eq!(
+            sequence_tracker.gaps_detected,
+            expected.iter().filter(|gap| gap.is_some()).count() as u64
+        );
+    }
+
+    #[test]
+    fn test_sequence_tracker_sender_restart() {
+        let mut sequence_tracker = SequenceTracker::default();
+        assert_eq!(
+            sequence_tracker.track("sender", "topic", "instance1", 5),
+            None
+        );
+        // A new sender instance restarts numbering without a gap
+        assert_eq!(
+            sequence_tracker.track("sender", "topic", "instance2", 0),
+            None
+        );
+        assert_eq!(
+            sequence_tracker.track("sender", "topic", "instance2", 2),
+            Some(1)
+        );
+        assert_eq!(sequence_tracker.gaps_detected, 1);
+    }
+
+    #[test]
+    fn test_sequence_tracker_per_sender_and_topic() {
+        let mut sequence_tracker = SequenceTracker::default();
+        assert_eq!(
+            sequence_tracker.track("sender1", "topic1", "instance", 0),
+            None
+        );
+        assert_eq!(
+            sequence_tracker.track("sender1", "topic2", "instance", 5),
+            None
+        );
+        assert_eq!(
+            sequence_tracker.track("sender2", "topic1", "instance", 9),
+            None
+        );
+        assert_eq!(
+            sequence_tracker.track("sender1", "topic1", "instance", 1),
+            None
+        );
+        assert_eq!(sequence_tracker.gaps_detected, 0);
+    }
 }
 
 // Test cases for recv telemetry
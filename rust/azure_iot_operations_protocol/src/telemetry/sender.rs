@@ -3,10 +3,16 @@
 
 use std::str::FromStr;
 use std::sync::Arc;
-use std::{collections::HashMap, marker::PhantomData, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    time::Duration,
+};
 
 use azure_iot_operations_mqtt::aio::cloud_event as aio_cloud_event;
-use azure_iot_operations_mqtt::control_packet::{PublishProperties, QoS};
+use azure_iot_operations_mqtt::control_packet::{
+    PubAck, PubAckReason, PublishProperties, QoS, TopicName,
+};
 use azure_iot_operations_mqtt::session::SessionManagedClient;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
@@ -16,16 +22,66 @@ use crate::{
     application::{ApplicationContext, ApplicationHybridLogicalClock},
     common::{
         aio_protocol_error::{AIOProtocolError, Value},
-        cloud_event as protocol_cloud_event, is_invalid_utf8,
-        payload_serialize::{PayloadSerialize, SerializedPayload},
+        cloud_event as protocol_cloud_event, is_invalid_utf8, payload_checksum,
+        payload_serialize::{
+            DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+            format_indicator_mismatch,
+        },
         topic_processor::TopicPattern,
         user_properties::{
             BrokerReservedUserProperty, ProtocolReservedUserProperty, validate_user_properties,
         },
     },
-    telemetry::{DEFAULT_TELEMETRY_CLOUD_EVENT_EVENT_TYPE, TELEMETRY_PROTOCOL_VERSION},
+    telemetry::{
+        DEFAULT_TELEMETRY_CLOUD_EVENT_EVENT_TYPE, MAX_TELEMETRY_PRIORITY,
+        TELEMETRY_PROTOCOL_VERSION, batch,
+    },
 };
 
+/// Outcome of a telemetry [`Message`] sent with [`Sender::send_with_result`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SendResult {
+    /// Warning indicated by the MQTT broker when acknowledging the message, if any.
+    /// Only QoS 1 messages are acknowledged, so this is always `None` for QoS 0 messages.
+    pub warning: Option<SendWarning>,
+}
+
+impl SendResult {
+    fn from_puback(puback: &PubAck) -> Self {
+        let warning = match puback.reason {
+            PubAckReason::NoMatchingSubscribers => Some(SendWarning::NoMatchingSubscribers),
+            _ => None,
+        };
+        Self { warning }
+    }
+
+    /// Returns `true` if the MQTT broker indicated that no subscriber matched the message topic,
+    /// i.e. the message was accepted but will not be received by anyone.
+    #[must_use]
+    pub fn no_matching_subscribers(&self) -> bool {
+        self.warning == Some(SendWarning::NoMatchingSubscribers)
+    }
+}
+
+/// Outcome of one MQTT publish of a batch of telemetry [`Message`]s sent with [`Sender::send_batch`].
+#[derive(Debug)]
+pub struct BatchSendResult {
+    /// Number of messages included in the publish. Messages are published in the order they were
+    /// provided, so the first `message_count` messages not included in previous publishes.
+    pub message_count: usize,
+    /// Outcome of the publish, which applies to all messages included in it
+    pub result: Result<SendResult, AIOProtocolError>,
+}
+
+/// Warning indicated by the MQTT broker on a successfully sent telemetry [`Message`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SendWarning {
+    /// The MQTT broker accepted the message, but there are no subscribers matching its topic
+    /// (PUBACK reason code `0x10`). This may indicate that the consumers of the telemetry are down.
+    NoMatchingSubscribers,
+}
+
 /// Telemetry Message struct.
 /// Used by the [`Sender`].
 #[derive(Builder, Clone, Debug)]
@@ -65,6 +121,24 @@ pub struct Message<T: PayloadSerialize> {
     /// set by default if this option is enabled).
     #[builder(default = "false")]
     persist: bool,
+    /// Priority of the telemetry message, from 0 (lowest) to [`MAX_TELEMETRY_PRIORITY`] (highest).
+    /// Receivers that prioritize messages deliver higher priority messages first when they have
+    /// several buffered. The broker does not reorder messages based on priority.
+    /// Default is no priority, in which case the receiver applies its own default.
+    #[builder(default = "None", setter(strip_option))]
+    priority: Option<u8>,
+}
+
+impl<T: PayloadSerialize> Message<T> {
+    /// Length of the serialized payload of the message
+    pub(crate) fn payload_len(&self) -> usize {
+        self.serialized_payload.payload.len()
+    }
+
+    /// Whether the message is retained or persisted, which isn't supported for batched messages
+    pub(crate) fn is_retained(&self) -> bool {
+        self.retain || self.persist
+    }
 }
 
 /// Cloud Event struct used by the [`Sender`].
@@ -185,6 +259,29 @@ impl CloudEventBuilder {
         self.0.subject(value);
         self
     }
+    /// Extension attributes, keyed by attribute name. Names must only contain lowercase letters
+    /// and digits (e.g. `partitionkey` or `dataref`) and must not collide with the names of core
+    /// attributes. Replaces any extension attributes already added.
+    pub fn extensions<VALUE: Into<BTreeMap<String, String>>>(&mut self, value: VALUE) -> &mut Self {
+        self.0.extensions(value);
+        self
+    }
+    /// Add a single extension attribute, replacing any existing value for `name`. See
+    /// [`extensions`](Self::extensions) for the naming rules.
+    pub fn extension<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.0.extension(name, value);
+        self
+    }
+    /// Derive attributes from the source events that an aggregate event was produced from, such
+    /// as the earliest `time` and the combined `source`. Attributes that have already been set are
+    /// not derived, and the source must be set explicitly if the source events don't share one.
+    pub fn derive_from_sources<'a, I>(&mut self, sources: I) -> &mut Self
+    where
+        I: IntoIterator<Item = &'a aio_cloud_event::CloudEvent>,
+    {
+        self.0.derive_from_sources(sources);
+        self
+    }
 }
 
 impl<T: PayloadSerialize> MessageBuilder<T> {
@@ -217,6 +314,16 @@ impl<T: PayloadSerialize> MessageBuilder<T> {
                         None,
                     ));
                 }
+                // Validate format indicator is consistent with the content type
+                if let Some(mismatch) = format_indicator_mismatch(&serialized_payload) {
+                    return Err(AIOProtocolError::new_configuration_invalid_error(
+                        None,
+                        "format_indicator",
+                        Value::String(format!("{:?}", serialized_payload.format_indicator)),
+                        Some(mismatch),
+                        None,
+                    ));
+                }
                 self.serialized_payload = Some(serialized_payload);
                 self.payload_type = Some(PhantomData);
                 Ok(self)
@@ -246,6 +353,7 @@ impl<T: PayloadSerialize> MessageBuilder<T> {
     ///     - `message_expiry` is > `u32::max`
     ///     - Quality of Service is not `AtMostOnce` or `AtLeastOnce`
     ///     - Persist is enabled when Retain has been explicitly disabled
+    ///     - Priority is greater than [`MAX_TELEMETRY_PRIORITY`]
     fn validate(&self) -> Result<(), String> {
         if let Some(custom_user_data) = &self.custom_user_data {
             for (key, _) in custom_user_data {
@@ -283,15 +391,25 @@ impl<T: PayloadSerialize> MessageBuilder<T> {
         if self.persist == Some(true) && self.retain == Some(false) {
             return Err("Persist cannot be used without retain".to_string());
         }
+        if let Some(Some(priority)) = self.priority
+            && priority > MAX_TELEMETRY_PRIORITY
+        {
+            return Err(format!(
+                "Priority must be less than or equal to {MAX_TELEMETRY_PRIORITY}"
+            ));
+        }
         Ok(())
     }
 }
 
+/// Derives the partition key of a telemetry message from its payload.
+pub type PartitionKeyFn<T> = Arc<dyn Fn(&T) -> String + Send + Sync>;
+
 /// Telemetry Sender Options struct
 #[derive(Builder, Clone)]
 #[builder(setter(into, strip_option))]
 #[allow(clippy::struct_field_names)]
-pub struct Options {
+pub struct Options<T> {
     /// Topic pattern for the telemetry message.
     /// Must align with [topic-structure.md](https://github.com/Azure/iot-operations-sdks/blob/main/doc/reference/topic-structure.md)
     topic_pattern: String,
@@ -301,6 +419,177 @@ pub struct Options {
     /// Topic token keys/values to be permanently replaced in the topic pattern
     #[builder(default)]
     topic_token_map: HashMap<String, String>,
+    /// Optional function deriving the partition key of each telemetry message from its payload.
+    /// The derived key is set as the `$partition` user property, so that all messages with the
+    /// same key are delivered to the same subscriber of a shared subscription.
+    #[builder(default = "None", setter(custom))]
+    partition_key_fn: Option<PartitionKeyFn<T>>,
+    /// If set, each telemetry message is stamped with a sequence number that increases by one
+    /// with every message sent on the same topic, so that receivers can detect lost messages
+    #[builder(default = "None")]
+    sequence_numbers: Option<SequenceNumberOptions>,
+    /// Whether to attach a checksum of the serialized payload to each telemetry message, so that
+    /// receivers can detect corrupted payloads, see
+    /// [`payload_checksum`](crate::common::payload_checksum). Default is `false`.
+    #[builder(default = "false")]
+    payload_checksum: bool,
+    /// Maximum packet size accepted by the MQTT broker. Batches sent with [`Sender::send_batch`]
+    /// are split into several publishes so that none exceeds it. Default is the maximum packet
+    /// size allowed by MQTT, so it should be set to the broker's maximum packet size if lower.
+    #[builder(default = "batch::MAX_MQTT_PACKET_SIZE")]
+    max_packet_size: u32,
+}
+
+impl<T> OptionsBuilder<T> {
+    /// Set a function deriving the partition key of each telemetry message from its payload.
+    ///
+    /// The derived key is set as the `$partition` user property, and takes precedence over any
+    /// `$partition` value provided in the custom user data of a [`Message`].
+    pub fn partition_key_fn(&mut self, partition_key_fn: PartitionKeyFn<T>) -> &mut Self {
+        self.partition_key_fn = Some(Some(partition_key_fn));
+        self
+    }
+}
+
+/// Store persisting the last sequence number sent by a [`Sender`] on each topic, so that
+/// sequence numbering continues across restarts instead of starting over with a new sender instance.
+#[async_trait::async_trait]
+pub trait SequenceNumberStore: Send + Sync {
+    /// Load the sender instance id and the last sequence number saved for `key`, if any.
+    ///
+    /// # Errors
+    /// Returns an error if the store could not be reached or did not complete the load.
+    async fn load(
+        &self,
+        key: String,
+    ) -> Result<Option<(String, u64)>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Save the sender instance id and the last sequence number sent for `key`.
+    ///
+    /// # Errors
+    /// Returns an error if the store could not be reached or did not complete the save.
+    async fn save(
+        &self,
+        key: String,
+        sender_instance_id: String,
+        sequence_number: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+#[async_trait::async_trait]
+impl<S: SequenceNumberStore + ?Sized> SequenceNumberStore for Arc<S> {
+    async fn load(
+        &self,
+        key: String,
+    ) -> Result<Option<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+        (**self).load(key).await
+    }
+
+    async fn save(
+        &self,
+        key: String,
+        sender_instance_id: String,
+        sequence_number: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        (**self)
+            .save(key, sender_instance_id, sequence_number)
+            .await
+    }
+}
+
+/// Options for stamping the telemetry messages sent by a [`Sender`] with sequence numbers
+#[derive(Builder, Clone, Default)]
+#[builder(setter(into))]
+pub struct SequenceNumberOptions {
+    /// Store persisting the last sequence number sent on each topic. If not set, every new
+    /// [`Sender`] is a new sender instance that numbers its messages from zero.
+    #[builder(default = "None", setter(custom))]
+    store: Option<Arc<dyn SequenceNumberStore>>,
+}
+
+impl SequenceNumberOptionsBuilder {
+    /// Set the [`SequenceNumberStore`] used to persist the last sequence number sent on each topic
+    pub fn store(&mut self, store: impl SequenceNumberStore + 'static) -> &mut Self {
+        self.store = Some(Some(Arc::new(store)));
+        self
+    }
+}
+
+/// Derive the [`SequenceNumberStore`] key for the sequence numbers sent by a client on a topic.
+fn sequence_number_key(client_id: &str, topic: &str) -> String {
+    format!("telemetrySequence:{client_id}:{topic}")
+}
+
+/// Sequence numbering state of a [`Sender`]
+struct SequenceNumbering {
+    /// Id of this sender instance, used for topics without a stored sequence number
+    sender_instance_id: String,
+    store: Option<Arc<dyn SequenceNumberStore>>,
+    /// Sender instance id and last sequence number sent on each topic
+    last_sent: tokio::sync::Mutex<HashMap<String, (String, u64)>>,
+}
+
+impl SequenceNumbering {
+    /// Assigns the next sequence number on `topic`, returning the sender instance id and sequence
+    /// number along with the guard on the numbering state. The guard must be held until the
+    /// message has been published, so that messages are published in sequence number order.
+    async fn next(
+        &self,
+        client_id: &str,
+        topic: &str,
+    ) -> (
+        tokio::sync::MutexGuard<'_, HashMap<String, (String, u64)>>,
+        String,
+        u64,
+    ) {
+        let mut last_sent = self.last_sent.lock().await;
+        let (sender_instance_id, sequence_number) = match last_sent.get(topic) {
+            Some((sender_instance_id, last)) => (sender_instance_id.clone(), last.wrapping_add(1)),
+            None => self.first(client_id, topic).await,
+        };
+
+        if let Some(store) = &self.store
+            && let Err(e) = store
+                .save(
+                    sequence_number_key(client_id, topic),
+                    sender_instance_id.clone(),
+                    sequence_number,
+                )
+                .await
+        {
+            // Not a reason to fail the send. A sender restarting before the next successful save
+            // reuses sequence numbers, which receivers treat as already received rather than as a gap.
+            log::warn!(
+                "Failed to save telemetry sequence number {sequence_number} for topic {topic}: {e}"
+            );
+        }
+
+        last_sent.insert(
+            topic.to_string(),
+            (sender_instance_id.clone(), sequence_number),
+        );
+        (last_sent, sender_instance_id, sequence_number)
+    }
+
+    /// Returns the sender instance id and sequence number of the first message sent on `topic`
+    /// by this [`Sender`], continuing from the stored sequence number if there is one
+    async fn first(&self, client_id: &str, topic: &str) -> (String, u64) {
+        if let Some(store) = &self.store {
+            match store.load(sequence_number_key(client_id, topic)).await {
+                Ok(Some((sender_instance_id, last))) => {
+                    return (sender_instance_id, last.wrapping_add(1));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    // Numbering from zero as a new sender instance can't be mistaken for a gap
+                    log::warn!(
+                        "Failed to load telemetry sequence number for topic {topic}, numbering as a new sender instance: {e}"
+                    );
+                }
+            }
+        }
+        (self.sender_instance_id.clone(), 0)
+    }
 }
 
 /// Telemetry Sender struct
@@ -346,6 +635,10 @@ where
     mqtt_client: SessionManagedClient,
     message_payload_type: PhantomData<T>,
     topic_pattern: TopicPattern,
+    partition_key_fn: Option<PartitionKeyFn<T>>,
+    sequence_numbering: Option<SequenceNumbering>,
+    payload_checksum: bool,
+    max_packet_size: u32,
 }
 
 /// Implementation of Telemetry Sender
@@ -372,7 +665,7 @@ where
     pub fn new(
         application_context: ApplicationContext,
         client: SessionManagedClient,
-        sender_options: Options,
+        sender_options: Options<T>,
     ) -> Result<Self, AIOProtocolError> {
         // Validate parameters
         let topic_pattern = TopicPattern::new(
@@ -393,12 +686,35 @@ where
             mqtt_client: client,
             message_payload_type: PhantomData,
             topic_pattern,
+            partition_key_fn: sender_options.partition_key_fn,
+            sequence_numbering: sender_options.sequence_numbers.map(|sequence_numbers| {
+                SequenceNumbering {
+                    sender_instance_id: Uuid::new_v4().to_string(),
+                    store: sequence_numbers.store,
+                    last_sent: tokio::sync::Mutex::new(HashMap::new()),
+                }
+            }),
+            payload_checksum: sender_options.payload_checksum,
+            max_packet_size: sender_options.max_packet_size,
         })
     }
 
     /// Sends a [`Message`].
     ///
-    /// Returns `Ok(())` on success, otherwise returns [`AIOProtocolError`].
+    /// Returns `Ok(())` on success, otherwise returns [`AIOProtocolError`]. A message accepted by the
+    /// MQTT broker without any matching subscribers is a success; use [`Sender::send_with_result`]
+    /// to detect this.
+    /// # Arguments
+    /// * `message` - [`Message`] to send
+    /// # Errors
+    /// See [`Sender::send_with_result`].
+    pub async fn send(&self, message: Message<T>) -> Result<(), AIOProtocolError> {
+        self.send_with_result(message).await.map(|_| ())
+    }
+
+    /// Sends a [`Message`], returning the [`SendResult`] indicating any warning from the MQTT broker.
+    ///
+    /// Returns `Ok(SendResult)` on success, otherwise returns [`AIOProtocolError`].
     /// # Arguments
     /// * `message` - [`Message`] to send
     /// # Errors
@@ -411,7 +727,18 @@ where
     ///
     /// [`AIOProtocolError`] of kind [`StateInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::StateInvalid) if
     /// - the [`ApplicationHybridLogicalClock`]'s timestamp is too far in the future
-    pub async fn send(&self, mut message: Message<T>) -> Result<(), AIOProtocolError> {
+    ///
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid) if
+    /// - a [`partition_key_fn`](OptionsBuilder::partition_key_fn) is configured and the payload
+    ///   cannot be deserialized to derive the partition key
+    ///
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid) if
+    /// - the partition key derived by the [`partition_key_fn`](OptionsBuilder::partition_key_fn)
+    ///   is empty, whitespace, or not valid UTF-8
+    pub async fn send_with_result(
+        &self,
+        mut message: Message<T>,
+    ) -> Result<SendResult, AIOProtocolError> {
         // Validate parameters. Custom user data, timeout, QoS, and payload serialization have already been validated in TelemetryMessageBuilder
         let message_expiry_interval: u32 = match message.message_expiry.as_secs().try_into() {
             Ok(val) => val,
@@ -432,10 +759,6 @@ where
         // Get updated timestamp
         let timestamp_str = self.application_hlc.update_now()?;
 
-        // Create correlation id
-        let correlation_id = Uuid::new_v4();
-        let correlation_data = Bytes::from(correlation_id.as_bytes().to_vec());
-
         // Cloud Events headers
         // TODO: could set subject here and then convert to mqtt::aio cloud event and then use that into_headers fn
         if let Some(cloud_event) = message.cloud_event {
@@ -445,6 +768,19 @@ where
             }
         }
 
+        // Partition header
+        if let Some(partition_key_fn) = &self.partition_key_fn {
+            let partition_key =
+                derive_partition_key(partition_key_fn, &message.serialized_payload)?;
+            let partition_property = BrokerReservedUserProperty::Partition.to_string();
+            message
+                .custom_user_data
+                .retain(|(key, _)| *key != partition_property);
+            message
+                .custom_user_data
+                .push((partition_property, partition_key));
+        }
+
         // Persist header
         if message.persist {
             message.custom_user_data.push((
@@ -459,49 +795,275 @@ where
             timestamp_str,
         ));
 
-        message.custom_user_data.push((
+        if let Some(priority) = message.priority {
+            message.custom_user_data.push((
+                ProtocolReservedUserProperty::Priority.to_string(),
+                priority.to_string(),
+            ));
+        }
+
+        self.publish(
+            message_topic,
+            message.qos,
+            message.retain,
+            message_expiry_interval,
+            message.serialized_payload,
+            message.custom_user_data,
+        )
+        .await
+    }
+
+    /// Sends several [`Message`]s as batches, each published as a single MQTT message with the
+    /// [`BATCH_CONTENT_TYPE`](batch::BATCH_CONTENT_TYPE) content type. The envelope of a batch is
+    /// documented in the [`batch`] module, and received messages can be unbatched with
+    /// [`Batch::unbatch`](batch::Batch::unbatch).
+    ///
+    /// Messages are published in order, and consecutive messages are batched together as long as
+    /// they have the same topic, QoS and partition key, and the batch doesn't exceed the
+    /// [`max_packet_size`](OptionsBuilder::max_packet_size). The message expiry of a batch is the
+    /// shortest of its messages.
+    ///
+    /// Returns one [`BatchSendResult`] per publish, in order. A failed publish doesn't prevent the
+    /// following batches from being published.
+    ///
+    /// # Arguments
+    /// * `messages` - [`Message`]s to send
+    /// # Errors
+    /// Nothing is published if any of the messages can't be batched.
+    ///
+    /// [`AIOProtocolError`] of kind [`ConfigurationInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::ConfigurationInvalid) if
+    /// - a message is retained or persisted, which isn't supported for batched messages
+    /// - the topic of a message can't be resolved
+    /// - the partition key derived by the [`partition_key_fn`](OptionsBuilder::partition_key_fn)
+    ///   is empty, whitespace, or not valid UTF-8
+    ///
+    /// [`AIOProtocolError`] of kind [`PayloadInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::PayloadInvalid) if
+    /// - a message alone would exceed the [`max_packet_size`](OptionsBuilder::max_packet_size) once batched
+    /// - a [`partition_key_fn`](OptionsBuilder::partition_key_fn) is configured and the payload
+    ///   of a message cannot be deserialized to derive the partition key
+    ///
+    /// [`AIOProtocolError`] of kind [`InternalLogicError`](crate::common::aio_protocol_error::AIOProtocolErrorKind::InternalLogicError) or
+    /// [`StateInvalid`](crate::common::aio_protocol_error::AIOProtocolErrorKind::StateInvalid) if
+    /// the [`ApplicationHybridLogicalClock`] can't provide a timestamp, see [`Sender::send_with_result`]
+    pub async fn send_batch(
+        &self,
+        messages: Vec<Message<T>>,
+    ) -> Result<Vec<BatchSendResult>, AIOProtocolError> {
+        // Prepare all batches before publishing any, so that nothing is published if any message
+        // can't be batched
+        let mut batches: Vec<(TopicName, QoS, Option<String>, batch::EnvelopeWriter)> = Vec::new();
+        for message in messages {
+            if message.is_retained() {
+                return Err(AIOProtocolError::new_configuration_invalid_error(
+                    None,
+                    "retain",
+                    Value::Boolean(true),
+                    Some("Batched telemetry messages cannot be retained or persisted".to_string()),
+                    None,
+                ));
+            }
+            let message_expiry_interval: u32 = match message.message_expiry.as_secs().try_into() {
+                Ok(val) => val,
+                Err(_) => {
+                    // should be validated in TelemetryMessageBuilder
+                    unreachable!();
+                }
+            };
+            let message_topic = self
+                .topic_pattern
+                .as_publish_topic(&message.topic_tokens)
+                .map_err(|e| {
+                    AIOProtocolError::config_invalid_from_topic_pattern_error(e, "message_topic")
+                })?;
+            let partition_key = self
+                .partition_key_fn
+                .as_ref()
+                .map(|partition_key_fn| {
+                    derive_partition_key(partition_key_fn, &message.serialized_payload)
+                })
+                .transpose()?;
+
+            // Per message user properties, preserved in the envelope
+            let mut user_properties = message.custom_user_data;
+            if let Some(cloud_event) = message.cloud_event {
+                user_properties.extend(cloud_event.0.into_headers(message_topic.as_str()));
+            }
+            user_properties.push((
+                ProtocolReservedUserProperty::Timestamp.to_string(),
+                self.application_hlc.update_now()?,
+            ));
+            if let Some(priority) = message.priority {
+                user_properties.push((
+                    ProtocolReservedUserProperty::Priority.to_string(),
+                    priority.to_string(),
+                ));
+            }
+            let item = batch::encode_item(&message.serialized_payload, user_properties)?;
+
+            // Space taken in the packet by everything but the envelope
+            let overhead = batch::PUBLISH_OVERHEAD
+                + message_topic.as_str().len()
+                + self.mqtt_client.client_id().len()
+                + partition_key.as_ref().map_or(0, String::len);
+            let max_envelope_len = (self.max_packet_size as usize).saturating_sub(overhead);
+            if batch::EnvelopeWriter::default().len_with(&item) > max_envelope_len {
+                return Err(AIOProtocolError::new_payload_invalid_error(
+                    true,
+                    false,
+                    None,
+                    Some(format!(
+                        "Batched telemetry message exceeds the maximum packet size of {} bytes",
+                        self.max_packet_size
+                    )),
+                    None,
+                ));
+            }
+
+            match batches.last_mut() {
+                Some((topic, qos, key, writer))
+                    if *topic == message_topic
+                        && *qos == message.qos
+                        && *key == partition_key
+                        && writer.len_with(&item) <= max_envelope_len =>
+                {
+                    writer.push(&item, message_expiry_interval);
+                }
+                _ => {
+                    let mut writer = batch::EnvelopeWriter::default();
+                    writer.push(&item, message_expiry_interval);
+                    batches.push((message_topic, message.qos, partition_key, writer));
+                }
+            }
+        }
+
+        let mut results = Vec::with_capacity(batches.len());
+        for (topic, qos, partition_key, writer) in batches {
+            let message_count = writer.message_count();
+            let result = async {
+                let mut user_properties = Vec::new();
+                if let Some(partition_key) = partition_key {
+                    user_properties.push((
+                        BrokerReservedUserProperty::Partition.to_string(),
+                        partition_key,
+                    ));
+                }
+                user_properties.push((
+                    ProtocolReservedUserProperty::Timestamp.to_string(),
+                    self.application_hlc.update_now()?,
+                ));
+                let (payload, message_expiry_interval) = writer.finish();
+                self.publish(
+                    topic,
+                    qos,
+                    false,
+                    message_expiry_interval,
+                    SerializedPayload {
+                        content_type: batch::BATCH_CONTENT_TYPE.to_string(),
+                        format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                        payload,
+                    },
+                    user_properties,
+                )
+                .await
+            }
+            .await;
+            results.push(BatchSendResult {
+                message_count,
+                result,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Publishes a telemetry message with the given user properties, adding the protocol user
+    /// properties (protocol version, source id, checksum and sequence numbers) to them.
+    async fn publish(
+        &self,
+        topic: TopicName,
+        qos: QoS,
+        retain: bool,
+        message_expiry_interval: u32,
+        serialized_payload: SerializedPayload,
+        mut user_properties: Vec<(String, String)>,
+    ) -> Result<SendResult, AIOProtocolError> {
+        // Create correlation id
+        let correlation_id = Uuid::new_v4();
+        let correlation_data = Bytes::from(correlation_id.as_bytes().to_vec());
+
+        user_properties.push((
             ProtocolReservedUserProperty::ProtocolVersion.to_string(),
             TELEMETRY_PROTOCOL_VERSION.to_string(),
         ));
 
-        message.custom_user_data.push((
+        user_properties.push((
             ProtocolReservedUserProperty::SourceId.to_string(),
             self.mqtt_client.client_id().to_string(),
         ));
 
+        if self.payload_checksum {
+            user_properties.push((
+                ProtocolReservedUserProperty::PayloadChecksum.to_string(),
+                payload_checksum::checksum(&serialized_payload.payload),
+            ));
+        }
+
+        // Sequence number headers. The numbering state stays locked until the message has been
+        // published, so that concurrently sent messages are published in sequence number order.
+        let sequence_guard = match &self.sequence_numbering {
+            Some(sequence_numbering) => {
+                let (guard, sender_instance_id, sequence_number) = sequence_numbering
+                    .next(self.mqtt_client.client_id(), topic.as_str())
+                    .await;
+                user_properties.push((
+                    ProtocolReservedUserProperty::SequenceNumber.to_string(),
+                    sequence_number.to_string(),
+                ));
+                user_properties.push((
+                    ProtocolReservedUserProperty::SenderInstanceId.to_string(),
+                    sender_instance_id,
+                ));
+                Some(guard)
+            }
+            None => None,
+        };
+
         // Create MQTT Properties
         let publish_properties = PublishProperties {
             correlation_data: Some(correlation_data),
             response_topic: None,
-            payload_format_indicator: message.serialized_payload.format_indicator.into(),
-            content_type: Some(message.serialized_payload.content_type.clone()),
+            payload_format_indicator: serialized_payload.format_indicator.into(),
+            content_type: Some(serialized_payload.content_type.clone()),
             message_expiry_interval: Some(message_expiry_interval),
-            user_properties: message.custom_user_data,
+            user_properties,
             topic_alias: None,
             subscription_identifiers: Vec::new(),
         };
 
         // Send publish
-        match message.qos {
+        match qos {
             azure_iot_operations_mqtt::control_packet::QoS::AtMostOnce => {
                 let publish_result = self
                     .mqtt_client
                     .publish_qos0(
-                        message_topic,
-                        message.retain,
-                        message.serialized_payload.payload,
+                        topic,
+                        retain,
+                        serialized_payload.payload,
                         publish_properties,
                     )
                     .await;
+                drop(sequence_guard);
                 match publish_result {
-                    Ok(publish_completion_token) => publish_completion_token.await.map_err(|e| {
-                        log::error!("Telemetry Publish completion error: {e}");
-                        AIOProtocolError::new_mqtt_error(
-                            Some("MQTT Error on telemetry send publish".to_string()),
-                            Box::new(e),
-                            None,
-                        )
-                    }),
+                    Ok(publish_completion_token) => publish_completion_token
+                        .await
+                        .map(|()| SendResult::default())
+                        .map_err(|e| {
+                            log::error!("Telemetry Publish completion error: {e}");
+                            AIOProtocolError::new_mqtt_error(
+                                Some("MQTT Error on telemetry send publish".to_string()),
+                                Box::new(e),
+                                None,
+                            )
+                        }),
                     Err(e) => {
                         log::error!("Telemetry Publish error: {e}");
                         Err(AIOProtocolError::new_mqtt_error(
@@ -516,18 +1078,28 @@ where
                 let publish_result = self
                     .mqtt_client
                     .publish_qos1(
-                        message_topic,
-                        message.retain,
-                        message.serialized_payload.payload,
+                        topic,
+                        retain,
+                        serialized_payload.payload,
                         publish_properties,
                     )
                     .await;
+                drop(sequence_guard);
 
                 match publish_result {
                     Ok(publish_completion_token) => {
                         // Wait for and handle the puback
                         match publish_completion_token.await {
-                            Ok(puback) => puback.as_result().map_err(|e| {
+                            Ok(puback) => puback
+                                .as_result()
+                                .map(|()| {
+                                    let send_result = SendResult::from_puback(&puback);
+                                    if send_result.no_matching_subscribers() {
+                                        log::debug!("Telemetry was sent, but there are no matching subscribers");
+                                    }
+                                    send_result
+                                })
+                                .map_err(|e| {
                                 AIOProtocolError::new_mqtt_error(
                                     Some("MQTT Puback indicated failure".to_string()),
                                     Box::new(e),
@@ -561,9 +1133,58 @@ where
     }
 }
 
+/// Derives the partition key for a telemetry message from its serialized payload.
+///
+/// The payload is deserialized back into `T`, since the [`Message`] only retains the serialized form.
+fn derive_partition_key<T: PayloadSerialize>(
+    partition_key_fn: &PartitionKeyFn<T>,
+    serialized_payload: &SerializedPayload,
+) -> Result<String, AIOProtocolError> {
+    let payload = T::deserialize(
+        &serialized_payload.payload,
+        Some(&serialized_payload.content_type),
+        &serialized_payload.format_indicator,
+    )
+    .map_err(|e| {
+        let nested_error: Box<dyn std::error::Error + Send + Sync> = match e {
+            DeserializationError::InvalidPayload(e) => e.into(),
+            DeserializationError::UnsupportedContentType(message) => message.into(),
+        };
+        AIOProtocolError::new_payload_invalid_error(
+            true,
+            false,
+            Some(nested_error),
+            Some("Payload could not be deserialized to derive the partition key".to_string()),
+            None,
+        )
+    })?;
+    let partition_key = partition_key_fn(&payload);
+    if partition_key.trim().is_empty() {
+        return Err(AIOProtocolError::new_configuration_invalid_error(
+            None,
+            "partition_key",
+            Value::String(partition_key),
+            Some("Derived partition key is empty or whitespace".to_string()),
+            None,
+        ));
+    }
+    if is_invalid_utf8(&partition_key) {
+        return Err(AIOProtocolError::new_configuration_invalid_error(
+            None,
+            "partition_key",
+            Value::String(partition_key.clone()),
+            Some(format!(
+                "Derived partition key '{partition_key}' is not valid UTF-8"
+            )),
+            None,
+        ));
+    }
+    Ok(partition_key)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, time::Duration};
+    use std::{collections::HashMap, sync::Arc, time::Duration};
 
     use test_case::test_case;
 
@@ -573,7 +1194,10 @@ mod tests {
             aio_protocol_error::{AIOProtocolErrorKind, Value},
             payload_serialize::{FormatIndicator, MockPayload, SerializedPayload},
         },
-        telemetry::sender::{OptionsBuilder, Sender},
+        telemetry::{
+            MAX_TELEMETRY_PRIORITY,
+            sender::{OptionsBuilder, PartitionKeyFn, Sender, derive_partition_key},
+        },
     };
     use azure_iot_operations_mqtt::{
         aio::connection_settings::MqttConnectionSettingsBuilder,
@@ -810,6 +1434,34 @@ mod tests {
         assert!(message_builder_result.is_err());
     }
 
+    #[test_case(0, true; "lowest")]
+    #[test_case(MAX_TELEMETRY_PRIORITY, true; "highest")]
+    #[test_case(MAX_TELEMETRY_PRIORITY + 1, false; "above_max")]
+    fn test_message_priority(priority: u8, valid: bool) {
+        let mut mock_telemetry_payload = MockPayload::new();
+        mock_telemetry_payload
+            .expect_serialize()
+            .returning(|| {
+                Ok(SerializedPayload {
+                    payload: String::new().into(),
+                    content_type: "application/json".to_string(),
+                    format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+                })
+            })
+            .times(1);
+
+        let message_builder_result = MessageBuilder::default()
+            .payload(mock_telemetry_payload)
+            .unwrap()
+            .priority(priority)
+            .build();
+
+        assert_eq!(message_builder_result.is_ok(), valid);
+        if let Ok(m) = message_builder_result {
+            assert_eq!(m.priority, Some(priority));
+        }
+    }
+
     #[test]
     fn test_message_defaults() {
         let mut mock_telemetry_payload = MockPayload::new();
@@ -842,6 +1494,59 @@ mod tests {
         assert!(m.custom_user_data.is_empty());
         assert!(m.topic_tokens.is_empty());
         assert!(m.cloud_event.is_none());
+        assert!(m.priority.is_none());
         assert!(m.serialized_payload.payload.is_empty());
     }
+
+    #[test]
+    fn test_new_partition_key_fn() {
+        let session = get_session();
+        let sender_options = OptionsBuilder::default()
+            .topic_pattern("test/test_telemetry")
+            .partition_key_fn(Arc::new(|payload: &Vec<u8>| {
+                String::from_utf8_lossy(payload).to_string()
+            }))
+            .build()
+            .unwrap();
+
+        let sender = Sender::<Vec<u8>>::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            sender_options,
+        )
+        .unwrap();
+        assert!(sender.partition_key_fn.is_some());
+    }
+
+    #[test]
+    fn test_derive_partition_key() {
+        let partition_key_fn: PartitionKeyFn<Vec<u8>> =
+            Arc::new(|payload| format!("sensor-{}", payload[0]));
+        let serialized_payload = SerializedPayload {
+            payload: vec![7],
+            content_type: "application/octet-stream".to_string(),
+            format_indicator: FormatIndicator::UnspecifiedBytes,
+        };
+        assert_eq!(
+            derive_partition_key(&partition_key_fn, &serialized_payload).unwrap(),
+            "sensor-7"
+        );
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("  "; "whitespace")]
+    #[test_case("sensor\u{0000}"; "invalid_utf8")]
+    fn test_derive_partition_key_invalid(partition_key: &'static str) {
+        let partition_key_fn: PartitionKeyFn<Vec<u8>> =
+            Arc::new(move |_| partition_key.to_string());
+        let serialized_payload = SerializedPayload {
+            payload: Vec::new(),
+            content_type: "application/octet-stream".to_string(),
+            format_indicator: FormatIndicator::UnspecifiedBytes,
+        };
+        let e = derive_partition_key(&partition_key_fn, &serialized_payload).unwrap_err();
+        assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+        assert_eq!(e.property_name, Some("partition_key".to_string()));
+        assert!(e.property_value == Some(Value::String(partition_key.to_string())));
+    }
 }
@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::{
+        cloud_event::{DATA_REF_EXTENSION, PARTITION_KEY_EXTENSION},
+        connection_settings::MqttConnectionSettingsBuilder,
+    },
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, rpc_command, telemetry,
+};
+
+const TELEMETRY_TOPIC: &str = "test/telemetry/extensions";
+const REQUEST_TOPIC: &str = "test/request/extensions";
+const TRACE_PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+const DATA_REF: &str = "https://storage.example.com/payloads/1";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Tests that extension attributes of a telemetry cloud event are received by a telemetry receiver
+#[tokio::test]
+async fn telemetry_extensions_round_trip() {
+    let (session, mock_server) = setup_client_and_mock_server("extensions_telemetry_client");
+    let sender: telemetry::Sender<Vec<u8>> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TELEMETRY_TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TELEMETRY_TOPIC)
+            .auto_ack(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let cloud_event = telemetry::sender::CloudEventBuilder::default()
+        .source("aio://test/telemetry")
+        .extension(PARTITION_KEY_EXTENSION, "line-1")
+        .extension(DATA_REF_EXTENSION, DATA_REF)
+        .extension("traceparent", TRACE_PARENT)
+        .build()
+        .unwrap();
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(vec![1, 2, 3])
+        .unwrap()
+        .cloud_event(cloud_event)
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), async {
+        let publish = mock_server.expect_publish().await;
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        }
+        publish
+    });
+    result.unwrap();
+
+    // Deliver the sent telemetry back to the receiver
+    let (message, ()) = tokio::join!(
+        async {
+            let (message, _) = receiver.recv().await.unwrap().unwrap();
+            message
+        },
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(publish);
+        }
+    );
+
+    let cloud_event = telemetry::receiver::cloud_event_from_telemetry(&message).unwrap();
+    assert_eq!(cloud_event.partition_key(), Some("line-1"));
+    assert_eq!(cloud_event.data_ref(), Some(DATA_REF));
+    assert_eq!(cloud_event.extension("traceparent"), Some(TRACE_PARENT));
+    assert_eq!(cloud_event.extensions.len(), 3);
+}
+
+/// Tests that extension attributes of a command request cloud event are received by an executor
+#[tokio::test]
+async fn command_request_extensions_round_trip() {
+    let (session, mock_server) = setup_client_and_mock_server("extensions_command_client");
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let (start_result, ()) =
+        tokio::join!(executor.start(), mock_server.expect_subscribe_and_accept());
+    start_result.unwrap();
+
+    let cloud_event = rpc_command::invoker::RequestCloudEventBuilder::default()
+        .source("aio://test/invoker")
+        .extension(PARTITION_KEY_EXTENSION, "line-1")
+        .extension("traceparent", TRACE_PARENT)
+        .build()
+        .unwrap();
+    let request = rpc_command::invoker::RequestBuilder::default()
+        .payload(Vec::new())
+        .unwrap()
+        .timeout(Duration::from_secs(5))
+        .cloud_event(cloud_event)
+        .build()
+        .unwrap();
+
+    // The invocation never completes, the request is only delivered back to the executor
+    let request_publish = tokio::select! {
+        _ = invoker.invoke(request) => panic!("Invocation completed without a response"),
+        publish = async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_publish().await
+        } => publish,
+    };
+    mock_server.send_publish(request_publish);
+    let request = executor.recv().await.unwrap().unwrap();
+
+    let cloud_event = rpc_command::executor::cloud_event_from_request(&request).unwrap();
+    assert_eq!(cloud_event.source, "aio://test/invoker");
+    assert_eq!(cloud_event.partition_key(), Some("line-1"));
+    assert_eq!(cloud_event.data_ref(), None);
+    assert_eq!(cloud_event.extension("traceparent"), Some(TRACE_PARENT));
+    assert_eq!(cloud_event.extensions.len(), 2);
+}
+
+/// Tests that an extension attribute named after a core attribute is rejected when building
+#[test]
+fn core_attribute_extension_rejected() {
+    let result = telemetry::sender::CloudEventBuilder::default()
+        .source("aio://test/telemetry")
+        .extension("id", "collides-with-core-attribute")
+        .build();
+    assert!(matches!(
+        result,
+        Err(telemetry::sender::CloudEventBuilderError::ValidationError(
+            _
+        ))
+    ));
+
+    let result = rpc_command::invoker::RequestCloudEventBuilder::default()
+        .source("aio://test/invoker")
+        .extension("datacontenttype", "application/json")
+        .build();
+    assert!(matches!(
+        result,
+        Err(rpc_command::invoker::RequestCloudEventBuilderError::ValidationError(_))
+    ));
+}
@@ -0,0 +1,152 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command,
+};
+use bytes::Bytes;
+
+const REQUEST_TOPIC: &str = "test/command/accept";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Expects the request publish from the invoker, acks it and responds with `content_type`
+async fn expect_request_and_respond(
+    mock_server: &MockServer,
+    response_packet_identifier: u16,
+    content_type: Option<&str>,
+) {
+    let request = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        request.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(response_packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from_static(b"{}"),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: content_type.map(Into::into),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+    mock_server.expect_puback().await;
+}
+
+/// Tests that responses are only accepted if their content type matches the accepted content
+/// type of the request, and are otherwise rejected before deserialization
+#[tokio::test]
+async fn invoker_accept_content_type() {
+    let (session, mock_server) = setup_client_and_mock_server("accept_content_type_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let request = |accept_content_type: Option<&str>| {
+        let mut request_builder = rpc_command::invoker::RequestBuilder::default();
+        request_builder
+            .payload(Vec::new())
+            .unwrap()
+            .timeout(Duration::from_secs(5));
+        if let Some(accept_content_type) = accept_content_type {
+            request_builder.accept_content_type(accept_content_type);
+        }
+        request_builder.build().unwrap()
+    };
+
+    // Matching content type
+    let (result, ()) = tokio::join!(
+        invoker.invoke(request(Some("Application/Octet-Stream"))),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_request_and_respond(&mock_server, 1, Some("application/octet-stream")).await;
+        }
+    );
+    assert_eq!(result.unwrap().payload, b"{}");
+
+    // Mismatched content type is rejected before deserialization
+    let (result, ()) = tokio::join!(
+        invoker.invoke(request(Some("application/octet-stream"))),
+        expect_request_and_respond(&mock_server, 2, Some("application/json; charset=utf-8"))
+    );
+    let e = result.unwrap_err();
+    assert_eq!(e.kind, AIOProtocolErrorKind::HeaderInvalid);
+    assert_eq!(e.header_name, Some("Content Type".to_string()));
+    assert_eq!(
+        e.header_value,
+        Some("application/json; charset=utf-8".to_string())
+    );
+    assert!(
+        e.message
+            .unwrap()
+            .contains("is not the accepted content type 'application/octet-stream'")
+    );
+
+    // Missing content type
+    let (result, ()) = tokio::join!(
+        invoker.invoke(request(Some("application/octet-stream"))),
+        expect_request_and_respond(&mock_server, 3, None)
+    );
+    let e = result.unwrap_err();
+    assert_eq!(e.kind, AIOProtocolErrorKind::HeaderInvalid);
+    assert_eq!(e.header_value, Some("None".to_string()));
+
+    // Without an accepted content type, the content type is only checked by deserialization
+    let (result, ()) = tokio::join!(
+        invoker.invoke(request(None)),
+        expect_request_and_respond(&mock_server, 4, Some("application/json"))
+    );
+    let e = result.unwrap_err();
+    assert_eq!(e.kind, AIOProtocolErrorKind::HeaderInvalid);
+    assert!(!e.message.unwrap().contains("accepted content type"));
+}
@@ -0,0 +1,418 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    rpc_command::{
+        self,
+        chunked::{
+            CHUNK_CHECKSUM_HEADER, CHUNK_COUNT_HEADER, CHUNK_SEQUENCE_HEADER,
+            CHUNK_STATUS_ACCEPTED, CHUNK_STATUS_HEADER, CHUNK_TRANSFER_FAILED_ERROR_CODE,
+            CHUNK_TRANSFER_ID_HEADER, ChunkAssembler, ChunkAssemblerOptionsBuilder,
+            ChunkedRequestBuilder,
+        },
+    },
+};
+use bytes::Bytes;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/command/chunked";
+const PAYLOAD_SIZE: usize = 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_COUNT: usize = PAYLOAD_SIZE / CHUNK_SIZE;
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer, OutgoingPacketsRx) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx: outgoing_packets_rx.clone(),
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server, outgoing_packets_rx)
+}
+
+fn user_property<'a>(publish: &'a mqtt_proto::Publish<Bytes>, key: &str) -> Option<&'a str> {
+    publish
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(k, _)| k.as_ref() == key)
+        .map(|(_, v)| v.as_ref())
+}
+
+/// Copies a publish received from one client to be sent to another with the given packet
+/// identifier
+fn forward(
+    publish: &mqtt_proto::Publish<Bytes>,
+    packet_identifier: u16,
+    dup: bool,
+) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: publish.topic_name.clone(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            dup,
+        ),
+        retain: false,
+        payload: publish.payload.clone(),
+        other_properties: publish.other_properties.clone(),
+    }
+}
+
+/// Expects a QoS 1 publish from the client and acknowledges it
+async fn expect_publish_and_ack(mock_server: &MockServer) -> mqtt_proto::Publish<Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 publish");
+    }
+    publish
+}
+
+/// Expects both the response to the request sent with `packet_identifier`, which is acknowledged,
+/// and the acknowledgement of the request, in either order
+async fn expect_response_and_puback(
+    mock_server: &MockServer,
+    outgoing_packets_rx: &OutgoingPacketsRx,
+    packet_identifier: u16,
+) -> mqtt_proto::Publish<Bytes> {
+    let mut response = None;
+    let mut acked = false;
+    while response.is_none() || !acked {
+        match outgoing_packets_rx.recv().await {
+            Some(mqtt_proto::Packet::Publish(publish)) => {
+                if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(response_identifier, _) =
+                    publish.packet_identifier_dup_qos
+                {
+                    mock_server.send_puback(response_identifier);
+                }
+                assert!(response.replace(publish).is_none());
+            }
+            Some(mqtt_proto::Packet::PubAck(puback)) => {
+                assert_eq!(puback.packet_identifier.get(), packet_identifier);
+                acked = true;
+            }
+            other => panic!("Expected response PUBLISH or PUBACK, but received {other:?}"),
+        }
+    }
+    response.unwrap()
+}
+
+fn accepted_response(request: &mqtt_proto::Publish<Bytes>) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.clone().unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::new(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data.clone(),
+            user_properties: vec![
+                ("__stat".into(), "204".into()),
+                ("__protVer".into(), "1.0".into()),
+                (CHUNK_STATUS_HEADER.into(), CHUNK_STATUS_ACCEPTED.into()),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests a 1 MB payload sent in 64 KB chunks by the invoker and reassembled by a
+/// [`ChunkAssembler`] on the executor. The chunk publishes are relayed from the invoker to the
+/// executor with two chunks swapped, one chunk redelivered by the broker and one chunk invoked
+/// again with a new correlation id, and the response to the assembled request is relayed back.
+#[tokio::test]
+async fn chunked_transfer_reassembles_reordered_and_duplicate_chunks() {
+    let (invoker_session, invoker_server, _) =
+        setup_client_and_mock_server("chunked_invoker_client");
+    let (executor_session, executor_server, executor_packets_rx) =
+        setup_client_and_mock_server("chunked_executor_client");
+
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        invoker_session.create_managed_client(),
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("upload")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        executor_session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("upload")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(invoker_session.run());
+    tokio::task::spawn(executor_session.run());
+    invoker_server.expect_connect_and_accept(true).await;
+    executor_server.expect_connect_and_accept(true).await;
+
+    // Application hosting the executor
+    let (assembled_tx, mut assembled_rx) = mpsc::unbounded_channel();
+    tokio::task::spawn(async move {
+        let mut assembler =
+            ChunkAssembler::new(ChunkAssemblerOptionsBuilder::default().build().unwrap());
+        while let Some(request) = executor.recv().await {
+            if let Some(assembled) = assembler.process(request.unwrap()).await {
+                assert_eq!(assembler.incomplete_transfers(), 0);
+                assert_eq!(assembler.buffered_bytes(), 0);
+                assembled_tx
+                    .send((
+                        assembled.payload.clone(),
+                        assembled.transfer_id,
+                        assembled.chunk_count,
+                        assembled.custom_user_data.clone(),
+                    ))
+                    .unwrap();
+                assembled
+                    .complete(
+                        rpc_command::executor::ResponseBuilder::default()
+                            .payload(b"installed".to_vec())
+                            .unwrap()
+                            .build()
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+
+    #[allow(clippy::cast_possible_truncation)]
+    let payload: Vec<u8> = (0..PAYLOAD_SIZE).map(|i| (i % 251) as u8).collect();
+    let transfer_id = Uuid::new_v4();
+    let request_meta = ChunkedRequestBuilder::default()
+        .custom_user_data(vec![("firmware".to_string(), "1.2.3".to_string())])
+        .timeout(Duration::from_secs(30))
+        .transfer_id(transfer_id)
+        .build()
+        .unwrap();
+
+    let (result, ()) = tokio::join!(
+        invoker.invoke_chunked(request_meta, payload.as_slice(), CHUNK_SIZE),
+        async {
+            // The invoker sends each chunk once the previous one has been accepted, so accept
+            // them here and hold on to the final chunk
+            invoker_server.expect_subscribe_and_accept().await;
+            let mut chunks = Vec::new();
+            for sequence in 0..CHUNK_COUNT {
+                let chunk = expect_publish_and_ack(&invoker_server).await;
+                assert_eq!(
+                    user_property(&chunk, CHUNK_TRANSFER_ID_HEADER),
+                    Some(transfer_id.to_string().as_str())
+                );
+                assert_eq!(
+                    user_property(&chunk, CHUNK_SEQUENCE_HEADER),
+                    Some(sequence.to_string().as_str())
+                );
+                assert_eq!(user_property(&chunk, "firmware"), Some("1.2.3"));
+                assert_eq!(chunk.payload.len(), CHUNK_SIZE);
+                if sequence + 1 < CHUNK_COUNT {
+                    assert!(user_property(&chunk, CHUNK_COUNT_HEADER).is_none());
+                    assert!(user_property(&chunk, CHUNK_CHECKSUM_HEADER).is_none());
+                    invoker_server.send_publish(accepted_response(&chunk));
+                    invoker_server.expect_puback().await;
+                } else {
+                    assert_eq!(
+                        user_property(&chunk, CHUNK_COUNT_HEADER),
+                        Some(CHUNK_COUNT.to_string().as_str())
+                    );
+                    assert!(user_property(&chunk, CHUNK_CHECKSUM_HEADER).is_some());
+                }
+                chunks.push(chunk);
+            }
+
+            // Relay the chunks to the executor with chunks 2 and 3 swapped, chunk 5 redelivered
+            // by the broker, and chunk 6 invoked again with a new correlation id
+            executor_server.expect_subscribe_and_accept().await;
+            let mut order: Vec<usize> = (0..CHUNK_COUNT).collect();
+            order.swap(2, 3);
+            let mut packet_identifier = 0;
+            let mut final_response = None;
+            for sequence in order {
+                packet_identifier += 1;
+                executor_server.send_publish(forward(&chunks[sequence], packet_identifier, false));
+                let response = expect_response_and_puback(
+                    &executor_server,
+                    &executor_packets_rx,
+                    packet_identifier,
+                )
+                .await;
+                assert_eq!(
+                    response.other_properties.correlation_data,
+                    chunks[sequence].other_properties.correlation_data
+                );
+                if sequence + 1 == CHUNK_COUNT {
+                    assert!(user_property(&response, CHUNK_STATUS_HEADER).is_none());
+                    final_response = Some(response);
+                    continue;
+                }
+                assert_eq!(
+                    user_property(&response, CHUNK_STATUS_HEADER),
+                    Some(CHUNK_STATUS_ACCEPTED)
+                );
+
+                if sequence == 5 {
+                    packet_identifier += 1;
+                    executor_server.send_publish(forward(&chunks[5], packet_identifier, true));
+                    let cached_response = expect_response_and_puback(
+                        &executor_server,
+                        &executor_packets_rx,
+                        packet_identifier,
+                    )
+                    .await;
+                    assert_eq!(
+                        user_property(&cached_response, CHUNK_STATUS_HEADER),
+                        Some(CHUNK_STATUS_ACCEPTED)
+                    );
+                }
+                if sequence == 6 {
+                    packet_identifier += 1;
+                    let mut reinvoked = forward(&chunks[6], packet_identifier, false);
+                    reinvoked.other_properties.correlation_data =
+                        Some(Uuid::new_v4().as_bytes().as_slice().into());
+                    executor_server.send_publish(reinvoked);
+                    let response = expect_response_and_puback(
+                        &executor_server,
+                        &executor_packets_rx,
+                        packet_identifier,
+                    )
+                    .await;
+                    assert_eq!(
+                        user_property(&response, CHUNK_STATUS_HEADER),
+                        Some(CHUNK_STATUS_ACCEPTED)
+                    );
+                }
+            }
+
+            // Relay the response to the assembled request back to the invoker
+            invoker_server.send_publish(forward(&final_response.unwrap(), 1, false));
+            invoker_server.expect_puback().await;
+        }
+    );
+
+    let response = result.unwrap();
+    assert_eq!(response.payload, b"installed");
+
+    let (assembled_payload, assembled_transfer_id, chunk_count, custom_user_data) =
+        assembled_rx.recv().await.unwrap();
+    assert!(assembled_payload == payload);
+    assert_eq!(assembled_transfer_id, Some(transfer_id));
+    assert_eq!(chunk_count as usize, CHUNK_COUNT);
+    assert!(custom_user_data.contains(&("firmware".to_string(), "1.2.3".to_string())));
+    assert!(
+        !custom_user_data
+            .iter()
+            .any(|(key, _)| key.starts_with("Chunk"))
+    );
+}
+
+/// Tests that an incomplete transfer expires, and that its final chunk is then responded to with
+/// an application error
+#[tokio::test]
+async fn chunk_assembler_expires_incomplete_transfer() {
+    let (session, mock_server, outgoing_packets_rx) =
+        setup_client_and_mock_server("chunked_expiry_client");
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("upload")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut assembler = ChunkAssembler::new(
+        ChunkAssemblerOptionsBuilder::default()
+            .transfer_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap(),
+    );
+
+    // Only the final chunk of a transfer of two chunks arrives
+    let final_chunk = mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from_static(b"tail"),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic("test/command/chunked/response")),
+            correlation_data: Some([3u8; 16].as_slice().into()),
+            user_properties: vec![
+                ("__protVer".into(), "1.0".into()),
+                ("__srcId".into(), "invoker_client".into()),
+                (
+                    CHUNK_TRANSFER_ID_HEADER.into(),
+                    Uuid::new_v4().to_string().as_str().into(),
+                ),
+                (CHUNK_SEQUENCE_HEADER.into(), "1".into()),
+                (CHUNK_COUNT_HEADER.into(), "2".into()),
+                (CHUNK_CHECKSUM_HEADER.into(), "00000000".into()),
+            ],
+            ..Default::default()
+        },
+    };
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(final_chunk);
+    });
+    assert!(assembler.process(request.unwrap().unwrap()).await.is_none());
+    assert_eq!(assembler.incomplete_transfers(), 1);
+    assert_eq!(assembler.buffered_bytes(), 4);
+    mock_server.expect_no_packet();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(assembler.expire_incomplete().await, 1);
+    assert_eq!(assembler.incomplete_transfers(), 0);
+    assert_eq!(assembler.buffered_bytes(), 0);
+
+    let response = expect_response_and_puback(&mock_server, &outgoing_packets_rx, 1).await;
+    assert_eq!(
+        user_property(&response, "AppErrCode"),
+        Some(CHUNK_TRANSFER_FAILED_ERROR_CODE)
+    );
+    assert!(
+        user_property(&response, "AppErrPayload")
+            .unwrap()
+            .contains("expired")
+    );
+}
@@ -0,0 +1,154 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command::{self, invoker::Request},
+};
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/command/correlation";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_request(correlation_id: Option<Uuid>) -> Request<Vec<u8>> {
+    let mut request_builder = rpc_command::invoker::RequestBuilder::default();
+    request_builder
+        .payload(Vec::new())
+        .unwrap()
+        .timeout(Duration::from_secs(1));
+    if let Some(correlation_id) = correlation_id {
+        request_builder.correlation_id(correlation_id);
+    }
+    request_builder.build().unwrap()
+}
+
+/// Expects a request publish from the invoker, acks it, and returns its correlation data
+async fn expect_request_correlation_data(mock_server: &MockServer) -> Vec<u8> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    publish
+        .other_properties
+        .correlation_data
+        .unwrap()
+        .as_ref()
+        .to_vec()
+}
+
+/// Tests that supplied and generated correlation ids are sent as the correlation data of the
+/// request, that a correlation id in use by another invocation in progress is rejected, and that
+/// the correlation id can be used again once the invocation that used it has completed
+#[tokio::test]
+async fn invoker_correlation_ids() {
+    let (session, mock_server) = setup_client_and_mock_server("correlation_id_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    // Supplied correlation id
+    let correlation_id = Uuid::new_v4();
+    let request = create_request(Some(correlation_id));
+    assert_eq!(request.correlation_id(), correlation_id);
+    let (result, ()) = tokio::join!(invoker.invoke(request), async {
+        mock_server.expect_subscribe_and_accept().await;
+        assert_eq!(
+            expect_request_correlation_data(&mock_server).await,
+            correlation_id.as_bytes()
+        );
+
+        // A concurrent invoke with the same correlation id is rejected
+        let duplicate_error = invoker
+            .invoke(create_request(Some(correlation_id)))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            duplicate_error.kind,
+            AIOProtocolErrorKind::ConfigurationInvalid
+        );
+        assert!(duplicate_error.is_shallow);
+        assert_eq!(
+            duplicate_error.property_name,
+            Some("correlation_id".to_string())
+        );
+        assert_eq!(duplicate_error.correlation_id, Some(correlation_id));
+        mock_server.expect_no_packet();
+    });
+
+    // No response is sent, so the first invoke times out with the correlation id on the error
+    let timeout_error = result.unwrap_err();
+    assert_eq!(timeout_error.kind, AIOProtocolErrorKind::Timeout);
+    assert_eq!(timeout_error.correlation_id, Some(correlation_id));
+
+    // Once the invoke has completed, the correlation id can be used again
+    let (result, ()) = tokio::join!(
+        invoker.invoke(create_request(Some(correlation_id))),
+        async {
+            assert_eq!(
+                expect_request_correlation_data(&mock_server).await,
+                correlation_id.as_bytes()
+            );
+        }
+    );
+    assert_eq!(result.unwrap_err().kind, AIOProtocolErrorKind::Timeout);
+
+    // Generated correlation id is readable before the invoke
+    let request = create_request(None);
+    let generated_correlation_id = request.correlation_id();
+    assert!(!generated_correlation_id.is_nil());
+    assert_ne!(generated_correlation_id, correlation_id);
+    let (result, ()) = tokio::join!(invoker.invoke(request), async {
+        assert_eq!(
+            expect_request_correlation_data(&mock_server).await,
+            generated_correlation_id.as_bytes()
+        );
+    });
+    assert_eq!(
+        result.unwrap_err().correlation_id,
+        Some(generated_correlation_id)
+    );
+}
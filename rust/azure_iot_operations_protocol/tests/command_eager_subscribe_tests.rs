@@ -0,0 +1,134 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, rpc_command};
+use bytes::Bytes;
+
+const REQUEST_TOPIC: &str = "test/request";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_executor(session: &Session) -> rpc_command::Executor<Vec<u8>, Vec<u8>> {
+    rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn request_publish() -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from_static(b"request"),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic("test/response")),
+            correlation_data: Some([1u8; 16].as_slice().into()),
+            user_properties: vec![("__protVer".into(), "1.0".into())],
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests that a started executor receives a request published before the first call to `recv`,
+/// without subscribing again
+#[tokio::test]
+async fn executor_start_subscribes_eagerly() {
+    let (session, mock_server) = setup_client_and_mock_server("eager_subscribe_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    assert!(!executor.is_subscribed());
+
+    let (start_result, ()) =
+        tokio::join!(executor.start(), mock_server.expect_subscribe_and_accept());
+    start_result.unwrap();
+    assert!(executor.is_subscribed());
+
+    // Starting again is a no-op
+    executor.start().await.unwrap();
+    mock_server.expect_no_packet();
+
+    mock_server.send_publish(request_publish());
+    let request = executor.recv().await.unwrap().unwrap();
+    assert_eq!(request.payload, b"request".to_vec());
+    mock_server.expect_no_packet();
+
+    let (shutdown_result, _) = tokio::join!(
+        executor.shutdown(),
+        mock_server.expect_unsubscribe_and_accept()
+    );
+    shutdown_result.unwrap();
+    assert!(!executor.is_subscribed());
+}
+
+/// Tests that an executor that is not started still subscribes on the first call to `recv`
+#[tokio::test]
+async fn executor_subscribes_lazily_without_start() {
+    let (session, mock_server) = setup_client_and_mock_server("lazy_subscribe_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(request_publish());
+    });
+    assert_eq!(request.unwrap().unwrap().payload, b"request".to_vec());
+    assert!(executor.is_subscribed());
+}
+
+/// Tests that a failed start leaves the executor unsubscribed
+#[tokio::test]
+async fn executor_start_failure() {
+    let (session, mock_server) = setup_client_and_mock_server("eager_subscribe_failure_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (start_result, ()) = tokio::join!(executor.start(), async {
+        let subscribe = mock_server.expect_subscribe().await;
+        mock_server.send_suback(mqtt_proto::SubAck {
+            packet_identifier: subscribe.packet_identifier,
+            reason_codes: vec![mqtt_proto::SubscribeReasonCode::NotAuthorized],
+            other_properties: mqtt_proto::SubAckOtherProperties::default(),
+        });
+    });
+    assert!(start_result.is_err());
+    assert!(!executor.is_subscribed());
+}
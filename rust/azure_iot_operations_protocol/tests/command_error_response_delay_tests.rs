@@ -0,0 +1,152 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, rpc_command};
+use bytes::Bytes;
+use test_case::test_case;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/request";
+const RESPONSE_TOPIC: &str = "test/response";
+const ERROR_RESPONSE_DELAY: Duration = Duration::from_secs(1);
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_executor(session: &Session) -> rpc_command::Executor<Vec<u8>, Vec<u8>> {
+    rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("lookup")
+            .accepted_content_types(vec!["application/octet-stream".to_string()])
+            .uniform_error_response_delay(ERROR_RESPONSE_DELAY)
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn request_publish(content_type: &str, protocol_version: &str) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::new(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            content_type: Some(content_type.into()),
+            user_properties: vec![("__protVer".into(), protocol_version.into())],
+            ..Default::default()
+        },
+    }
+}
+
+/// Expects the response to a request, acks it, and returns its status code
+async fn expect_response_status(mock_server: &MockServer) -> String {
+    let response = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        response.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    }
+    assert_eq!(response.topic_name.as_str(), RESPONSE_TOPIC);
+    response
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(key, _)| key.as_ref() == "__stat")
+        .map(|(_, value)| value.to_string())
+        .unwrap()
+}
+
+/// Tests that error responses generated by the executor for different failures are not published
+/// before the uniform error response delay has elapsed
+#[test_case("text/plain", "1.0", "415"; "unsupported_media_type")]
+#[test_case("application/octet-stream", "99.0", "505"; "version_not_supported")]
+#[tokio::test]
+async fn error_response_delayed(content_type: &str, protocol_version: &str, status: &str) {
+    let (session, mock_server) = setup_client_and_mock_server("error_response_delay_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    // The request is never returned to the application
+    let recv = tokio::task::spawn(async move { executor.recv().await.map(|r| r.is_ok()) });
+    mock_server.expect_subscribe_and_accept().await;
+    let start = Instant::now();
+    mock_server.send_publish(request_publish(content_type, protocol_version));
+
+    assert_eq!(expect_response_status(&mock_server).await, status);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= ERROR_RESPONSE_DELAY);
+    assert!(elapsed < ERROR_RESPONSE_DELAY + Duration::from_secs(1));
+    mock_server.expect_puback().await;
+    assert!(!recv.is_finished());
+    recv.abort();
+}
+
+/// Tests that responses completed by the application are not delayed
+#[tokio::test]
+async fn application_response_not_delayed() {
+    let (session, mock_server) =
+        setup_client_and_mock_server("application_response_delay_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let handler = tokio::task::spawn(async move {
+        let request = executor.recv().await.unwrap().unwrap();
+        request
+            .complete(
+                rpc_command::executor::ResponseBuilder::default()
+                    .payload(b"value".to_vec())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+    });
+    mock_server.expect_subscribe_and_accept().await;
+    let start = Instant::now();
+    mock_server.send_publish(request_publish("application/octet-stream", "1.0"));
+
+    assert_eq!(expect_response_status(&mock_server).await, "200");
+    assert!(start.elapsed() < ERROR_RESPONSE_DELAY);
+    mock_server.expect_puback().await;
+    handler.await.unwrap();
+}
@@ -0,0 +1,155 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, rpc_command};
+use bytes::Bytes;
+use test_case::test_case;
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/request";
+const RESPONSE_TOPIC: &str = "test/response";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Tests that the error response to a request without a message expiry interval expires after the
+/// default message expiry of the executor, or of the application context if the executor doesn't
+/// override it
+#[test_case(None, 30; "application context default")]
+#[test_case(Some(Duration::from_millis(4_500)), 5; "executor override rounded up")]
+#[tokio::test]
+async fn response_expiry_for_request_without_message_expiry(
+    executor_default_message_expiry: Option<Duration>,
+    expected_message_expiry_interval: u32,
+) {
+    let (session, mock_server) = setup_client_and_mock_server("expiry_settings_test_client");
+    let mut executor_options_builder = rpc_command::executor::OptionsBuilder::default();
+    executor_options_builder
+        .request_topic_pattern(REQUEST_TOPIC)
+        .command_name("test_command");
+    if let Some(default_message_expiry) = executor_default_message_expiry {
+        executor_options_builder.default_message_expiry(default_message_expiry);
+    }
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default()
+            .default_message_expiry(Duration::from_secs(30))
+            .build()
+            .unwrap(),
+        session.create_managed_client(),
+        executor_options_builder.build().unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    // The request is rejected, so it is never returned to the application
+    tokio::task::spawn(async move { executor.recv().await });
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::new(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: None,
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            user_properties: vec![("__protVer".into(), "1.0".into())],
+            ..Default::default()
+        },
+    });
+
+    let response = mock_server.expect_publish().await;
+    assert_eq!(response.topic_name.as_str(), RESPONSE_TOPIC);
+    assert!(
+        response
+            .other_properties
+            .user_properties
+            .iter()
+            .any(|(key, value)| key.as_ref() == "__stat" && value.as_ref() == "400")
+    );
+    assert_eq!(
+        response.other_properties.message_expiry_interval,
+        Some(expected_message_expiry_interval)
+    );
+}
+
+/// Tests that a request still being handled when it expires is cancelled, so that the handler can
+/// abort its work, and that no response is published for it
+#[tokio::test]
+async fn request_cancelled_on_expiry() {
+    let (session, mock_server) = setup_client_and_mock_server("request_cancellation_test_client");
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let handler = tokio::task::spawn(async move {
+        let request = executor.recv().await.unwrap().unwrap();
+        assert!(!request.is_cancelled());
+        let cancellation_token = request.cancellation_token();
+        tokio::time::timeout(Duration::from_secs(5), request.cancelled())
+            .await
+            .expect("request should be cancelled once it expires");
+        assert!(request.is_cancelled());
+        assert!(cancellation_token.is_cancelled());
+    });
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::new(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(1),
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            user_properties: vec![("__protVer".into(), "1.0".into())],
+            ..Default::default()
+        },
+    });
+
+    handler.await.unwrap();
+    // The expired request is acknowledged without a response
+    mock_server.expect_puback().await;
+}
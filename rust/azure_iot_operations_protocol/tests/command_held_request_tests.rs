@@ -0,0 +1,192 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    rpc_command::{self, executor::HeldRequestOutcome},
+};
+use bytes::Bytes;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/request";
+const RESPONSE_TOPIC: &str = "test/response";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_executor(session: &Session) -> rpc_command::Executor<Vec<u8>, Vec<u8>> {
+    rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("wait_for_event")
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn request_publish(message_expiry_interval: u32) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::new(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(message_expiry_interval),
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            user_properties: vec![("__protVer".into(), "1.0".into())],
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests that a held request completed from another task before it expires is answered, and is
+/// counted as held until then
+#[tokio::test]
+async fn held_request_completed_before_expiry() {
+    let (session, mock_server) = setup_client_and_mock_server("held_request_complete_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (event_tx, event_rx) = oneshot::channel::<Vec<u8>>();
+    let (held_tx, held_rx) = oneshot::channel();
+    let handler = tokio::task::spawn(async move {
+        let held = executor.recv().await.unwrap().unwrap().hold();
+        assert!(!held.is_expired());
+        assert!(held.time_remaining() > Duration::ZERO);
+        held_tx.send(executor.stats()).unwrap();
+
+        // The request is completed from another task once the event happens
+        let outcome = tokio::task::spawn(async move {
+            let event = event_rx.await.unwrap();
+            held.complete(
+                rpc_command::executor::ResponseBuilder::default()
+                    .payload(event)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        })
+        .await
+        .unwrap();
+        (outcome, executor.stats())
+    });
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(request_publish(10));
+
+    // The request is held until the event happens
+    let stats = held_rx.await.unwrap();
+    assert_eq!(stats.held_requests, 1);
+    assert_eq!(stats.cache_entries, 1);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    mock_server.expect_no_packet();
+
+    event_tx.send(b"event".to_vec()).unwrap();
+    let response = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        response.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    }
+    assert_eq!(response.topic_name.as_str(), RESPONSE_TOPIC);
+    assert_eq!(response.payload, Bytes::from("event"));
+    assert!(
+        response
+            .other_properties
+            .user_properties
+            .iter()
+            .any(|(key, value)| key.as_ref() == "__stat" && value.as_ref() == "200")
+    );
+
+    // The request is acknowledged once the response is published
+    mock_server.expect_puback().await;
+    let (outcome, stats) = handler.await.unwrap();
+    assert_eq!(outcome, HeldRequestOutcome::Completed);
+    assert_eq!(stats.held_requests, 0);
+    assert_eq!(stats.held_requests_expired, 0);
+}
+
+/// Tests that a held request that expires before the event happens resolves to an expired outcome,
+/// without an error response being published when it is completed or dropped afterwards
+#[tokio::test]
+async fn held_request_expired_before_complete() {
+    let (session, mock_server) = setup_client_and_mock_server("held_request_expiry_test_client");
+    let mut executor = create_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let handler = tokio::task::spawn(async move {
+        // The first held request is completed once it has expired
+        let held = executor.recv().await.unwrap().unwrap().hold();
+        tokio::time::timeout(Duration::from_secs(5), held.expired())
+            .await
+            .expect("held request should expire");
+        assert!(held.is_expired());
+        assert_eq!(held.time_remaining(), Duration::ZERO);
+        let outcome = held
+            .complete(
+                rpc_command::executor::ResponseBuilder::default()
+                    .payload(b"too late".to_vec())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // The second held request is dropped once it has expired
+        let held = executor.recv().await.unwrap().unwrap().hold();
+        held.expired().await;
+        drop(held);
+        (outcome, executor.stats())
+    });
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(request_publish(1));
+    mock_server.expect_puback().await;
+    mock_server.send_publish(request_publish(1));
+    mock_server.expect_puback().await;
+
+    let (outcome, stats) = handler.await.unwrap();
+    assert_eq!(outcome, HeldRequestOutcome::Expired);
+    assert_eq!(stats.held_requests, 0);
+    assert_eq!(stats.held_requests_expired, 2);
+
+    // No response was published for either request before the executor unsubscribed on drop
+    mock_server.expect_unsubscribe().await;
+}
@@ -27,6 +27,7 @@ use azure_iot_operations_protocol::{
 // - without cloud event
 // - TODO: different errors received from the executor (invoker only)
 // - Executor shutdown after subscribed
+// - response timing breakdown with a slow executor
 // - (Executor shutdown before subscribed (no error) has been added in unit tests, connectivity not needed)
 
 /// Create a session, command invoker, command executor, and exit handle for testing
@@ -638,3 +639,219 @@ async fn command_complex_invoke_response_network_tests() {
         .is_ok()
     );
 }
+
+/// Tests a command router dispatching two commands with different payload types from a single subscription
+#[tokio::test]
+async fn command_router_invoke_response_network_tests() {
+    let invoker_id = "command_router_invoke_response_network_tests-rust";
+    let request_topic_pattern = "protocol/tests/router/{commandName}";
+    // The executor created by setup_test is not used, the router handles both commands
+    // setup_test uses the invoker ID as the command name
+    let Ok((session, empty_invoker, _, exit_handle)) = setup_test::<EmptyPayload, EmptyPayload>(
+        invoker_id,
+        &request_topic_pattern.replace("{commandName}", invoker_id),
+    ) else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let monitor = session.create_session_monitor();
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    let data_invoker: rpc_command::Invoker<DataRequestPayload, DataResponsePayload> =
+        rpc_command::Invoker::new(
+            application_context.clone(),
+            session.create_managed_client(),
+            rpc_command::invoker::OptionsBuilder::default()
+                .request_topic_pattern("protocol/tests/router/data")
+                .response_topic_prefix("response".to_string())
+                .command_name("data")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+    let mut router = rpc_command::CommandRouter::new(
+        application_context,
+        session.create_managed_client(),
+        rpc_command::router::OptionsBuilder::default()
+            .request_topic_pattern(request_topic_pattern)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    router
+        .add_handler(
+            invoker_id,
+            |request: rpc_command::executor::Request<EmptyPayload, EmptyPayload>| async move {
+                let response = rpc_command::executor::ResponseBuilder::default()
+                    .payload(EmptyPayload::default())
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                assert!(request.complete(response).await.is_ok());
+            },
+        )
+        .unwrap()
+        .add_handler(
+            "data",
+            |request: rpc_command::executor::Request<DataRequestPayload, DataResponsePayload>| async move {
+                let response = rpc_command::executor::ResponseBuilder::default()
+                    .payload(DataResponsePayload {
+                        old_temperature: request.payload.requested_temperature,
+                        old_color: request.payload.requested_color.clone(),
+                        minutes_to_change: 5,
+                    })
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                assert!(request.complete(response).await.is_ok());
+            },
+        )
+        .unwrap();
+
+    let test_task = tokio::task::spawn({
+        async move {
+            let router_task = tokio::task::spawn(router.run());
+            // briefly wait after connection to let router subscribe before sending requests
+            monitor.connected().await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let empty_result = empty_invoker
+                .invoke(
+                    rpc_command::invoker::RequestBuilder::default()
+                        .payload(EmptyPayload::default())
+                        .unwrap()
+                        .timeout(Duration::from_secs(2))
+                        .build()
+                        .unwrap(),
+                )
+                .await;
+            assert!(empty_result.is_ok(), "result: {empty_result:?}");
+
+            let data_result = data_invoker
+                .invoke(
+                    rpc_command::invoker::RequestBuilder::default()
+                        .payload(DataRequestPayload {
+                            requested_temperature: 78.0,
+                            requested_color: "blue".to_string(),
+                        })
+                        .unwrap()
+                        .timeout(Duration::from_secs(2))
+                        .build()
+                        .unwrap(),
+                )
+                .await;
+            assert!(data_result.is_ok(), "result: {data_result:?}");
+            let data_response = data_result.unwrap();
+            assert_eq!(
+                data_response.payload,
+                DataResponsePayload {
+                    old_temperature: 78.0,
+                    old_color: "blue".to_string(),
+                    minutes_to_change: 5,
+                }
+            );
+
+            // cleanup should be successful
+            assert!(empty_invoker.shutdown().await.is_ok());
+            assert!(data_invoker.shutdown().await.is_ok());
+
+            exit_handle.force_exit();
+            // the router ends once the session has exited
+            assert!(router_task.await.unwrap().is_ok());
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| { e.to_string() }) },
+            async move { session.run().await.map_err(|e| { e.to_string() }) }
+        )
+        .is_ok()
+    );
+}
+
+/// Tests that the response timing breakdown reflects a deliberately slow executor
+#[tokio::test]
+async fn command_response_timing_network_tests() {
+    const EXECUTOR_DELAY: Duration = Duration::from_millis(500);
+    // HLC timestamps are truncated to millisecond precision
+    const TOLERANCE: Duration = Duration::from_millis(1);
+
+    let invoker_id = "command_response_timing_network_tests-rust";
+    let Ok((session, invoker, mut executor, exit_handle)) =
+        setup_test::<EmptyPayload, EmptyPayload>(invoker_id, "protocol/tests/timing/command")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let monitor = session.create_session_monitor();
+
+    let test_task = tokio::task::spawn({
+        async move {
+            // async task to slowly respond to command requests on executor
+            let receive_requests_task = tokio::task::spawn({
+                async move {
+                    let request = executor.recv().await.unwrap().unwrap();
+                    tokio::time::sleep(EXECUTOR_DELAY).await;
+                    let response = rpc_command::executor::ResponseBuilder::default()
+                        .payload(EmptyPayload::default())
+                        .unwrap()
+                        .build()
+                        .unwrap();
+                    assert!(request.complete(response).await.is_ok());
+                    assert!(executor.shutdown().await.is_ok());
+                }
+            });
+            // briefly wait after connection to let executor subscribe before sending requests
+            monitor.connected().await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let request = rpc_command::invoker::RequestBuilder::default()
+                .payload(EmptyPayload::default())
+                .unwrap()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap();
+            let response = invoker.invoke(request).await.unwrap();
+
+            // Validate the timing breakdown accounts for the executor delay
+            let timing = response.timing.unwrap();
+            assert!(!timing.clock_drift_detected, "timing: {timing:?}");
+            let executor_processing_estimate = timing.executor_processing_estimate.unwrap();
+            assert!(
+                executor_processing_estimate + TOLERANCE >= EXECUTOR_DELAY,
+                "timing: {timing:?}"
+            );
+            assert!(
+                executor_processing_estimate
+                    <= timing.request_sent_to_response_received + TOLERANCE,
+                "timing: {timing:?}"
+            );
+            assert!(
+                timing.request_sent_to_response_received >= EXECUTOR_DELAY,
+                "timing: {timing:?}"
+            );
+
+            // wait for the receive_requests_task to finish to ensure any failed asserts are captured.
+            assert!(receive_requests_task.await.is_ok());
+
+            // cleanup should be successful
+            assert!(invoker.shutdown().await.is_ok());
+
+            exit_handle.force_exit();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| { e.to_string() }) },
+            async move { session.run().await.map_err(|e| { e.to_string() }) }
+        )
+        .is_ok()
+    );
+}
@@ -0,0 +1,206 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command::{self, invoker::Request},
+};
+use bytes::Bytes;
+use uuid::Uuid;
+
+const REQUEST_TOPIC: &str = "test/command/no_response";
+const NO_RESPONSE_USER_PROPERTY: &str = "__noResp";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_request(no_response: bool) -> Request<Vec<u8>> {
+    let mut request_builder = rpc_command::invoker::RequestBuilder::default();
+    request_builder
+        .payload(vec![1, 2, 3])
+        .unwrap()
+        .timeout(Duration::from_secs(5));
+    if no_response {
+        request_builder.no_response();
+    }
+    request_builder.build().unwrap()
+}
+
+fn no_response_request_publish(
+    packet_identifier: u16,
+    correlation_id: Uuid,
+) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from(vec![1, 2, 3]),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            correlation_data: Some(correlation_id.as_bytes().as_slice().into()),
+            user_properties: vec![
+                (NO_RESPONSE_USER_PROPERTY.into(), "".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests that a fire-and-forget request is published without a response topic and completes once
+/// acknowledged by the broker, without subscribing to a response topic, and that requests are
+/// only accepted by the invoke method matching how they were built
+#[tokio::test]
+async fn invoker_no_response() {
+    let (session, mock_server) = setup_client_and_mock_server("no_response_invoker_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let request = create_request(true);
+    let correlation_id = request.correlation_id();
+    let (result, ()) = tokio::join!(invoker.invoke_no_response(request), async {
+        // No subscribe to the response topic, the request is published directly
+        let publish = mock_server.expect_publish().await;
+        assert_eq!(publish.topic_name.as_str(), REQUEST_TOPIC);
+        assert!(publish.other_properties.response_topic.is_none());
+        assert_eq!(
+            publish
+                .other_properties
+                .correlation_data
+                .as_ref()
+                .unwrap()
+                .as_ref(),
+            correlation_id.as_bytes()
+        );
+        assert!(
+            publish
+                .other_properties
+                .user_properties
+                .iter()
+                .any(|(key, _)| key.as_ref() == NO_RESPONSE_USER_PROPERTY)
+        );
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        } else {
+            panic!("Expected QoS 1 request publish");
+        }
+    });
+    result.unwrap();
+    mock_server.expect_no_packet();
+
+    // A fire-and-forget request can't be sent with invoke
+    let error = invoker.invoke(create_request(true)).await.unwrap_err();
+    assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    assert_eq!(error.property_name, Some("no_response".to_string()));
+
+    // A request expecting a response can't be sent with invoke_no_response
+    let error = invoker
+        .invoke_no_response(create_request(false))
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    assert_eq!(error.property_name, Some("no_response".to_string()));
+    mock_server.expect_no_packet();
+}
+
+/// Tests that the executor accepts a fire-and-forget request without a response topic, acks it
+/// once completed without publishing a response, and acks a duplicate without executing it again
+#[tokio::test]
+async fn executor_no_response() {
+    let (session, mock_server) = setup_client_and_mock_server("no_response_executor_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let correlation_id = Uuid::new_v4();
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(no_response_request_publish(1, correlation_id));
+    });
+    let request = request.unwrap().unwrap();
+    assert!(request.no_response);
+    assert_eq!(request.payload, vec![1, 2, 3]);
+    assert!(request.custom_user_data.is_empty());
+
+    request
+        .complete(
+            rpc_command::executor::ResponseBuilder::default()
+                .payload(Vec::new())
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // Only the ack of the request is sent, no response is published
+    let puback = mock_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 1);
+    mock_server.expect_no_packet();
+
+    // A redelivered request is acked without being executed again. Duplicates are handled while
+    // waiting for the next request, so no request is returned.
+    mock_server.send_publish(no_response_request_publish(2, correlation_id));
+    let (recv_result, puback) = tokio::join!(
+        tokio::time::timeout(Duration::from_millis(100), executor.recv()),
+        mock_server.expect_puback()
+    );
+    assert!(recv_result.is_err());
+    assert_eq!(puback.packet_identifier.get(), 2);
+    mock_server.expect_no_packet();
+}
@@ -0,0 +1,158 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, rpc_command};
+use bytes::Bytes;
+
+const REQUEST_TOPIC: &str = "test/request";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_executor(
+    session: &Session,
+    supported_protocol_versions: Vec<u16>,
+) -> rpc_command::Executor<Vec<u8>, Vec<u8>> {
+    rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .supported_protocol_versions(supported_protocol_versions)
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn request_publish(protocol_version: &str) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(1).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from_static(b"request"),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic("test/response")),
+            correlation_data: Some([1u8; 16].as_slice().into()),
+            user_properties: vec![("__protVer".into(), protocol_version.into())],
+            ..Default::default()
+        },
+    }
+}
+
+fn user_property<'a>(publish: &'a mqtt_proto::Publish<Bytes>, key: &str) -> Option<&'a str> {
+    publish
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(k, _)| AsRef::<str>::as_ref(k) == key)
+        .map(|(_, v)| v.as_ref())
+}
+
+/// Tests that an executor accepts requests with a major protocol version it is configured to
+/// support beyond the built-in ones
+#[tokio::test]
+async fn executor_accepts_configured_protocol_version() {
+    let (session, mock_server) = setup_client_and_mock_server("protocol_version_accept_client");
+    let mut executor = create_executor(&session, vec![1, 2]);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let (start_result, ()) =
+        tokio::join!(executor.start(), mock_server.expect_subscribe_and_accept());
+    start_result.unwrap();
+
+    mock_server.send_publish(request_publish("2.0"));
+    let request = executor.recv().await.unwrap().unwrap();
+    assert_eq!(request.payload, b"request".to_vec());
+}
+
+/// Tests that an executor rejects requests with a major protocol version it is not configured to
+/// support, advertising the configured versions in the error response
+#[tokio::test]
+async fn executor_rejects_unconfigured_protocol_version() {
+    let (session, mock_server) = setup_client_and_mock_server("protocol_version_reject_client");
+    let mut executor = create_executor(&session, vec![2, 3]);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let (start_result, ()) =
+        tokio::join!(executor.start(), mock_server.expect_subscribe_and_accept());
+    start_result.unwrap();
+
+    mock_server.send_publish(request_publish("1.0"));
+    let response = tokio::select! {
+        _ = executor.recv() => panic!("Request with unsupported protocol version was received"),
+        response = mock_server.expect_publish() => response,
+    };
+    assert_eq!(response.topic_name.as_ref(), "test/response");
+    assert_eq!(user_property(&response, "__stat"), Some("505"));
+    assert_eq!(user_property(&response, "__supProtMajVer"), Some("2 3"));
+    assert_eq!(user_property(&response, "__requestProtVer"), Some("1.0"));
+}
+
+#[test]
+fn empty_supported_protocol_versions_invalid() {
+    let (session, _mock_server) = setup_client_and_mock_server("protocol_version_empty_client");
+
+    let executor_result: Result<rpc_command::Executor<Vec<u8>, Vec<u8>>, _> =
+        rpc_command::Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            rpc_command::executor::OptionsBuilder::default()
+                .request_topic_pattern(REQUEST_TOPIC)
+                .command_name("test_command")
+                .supported_protocol_versions(Vec::new())
+                .build()
+                .unwrap(),
+        );
+    let e = executor_result.err().unwrap();
+    assert_eq!(
+        e.property_name,
+        Some("supported_protocol_versions".to_string())
+    );
+
+    let invoker_result: Result<rpc_command::Invoker<Vec<u8>, Vec<u8>>, _> =
+        rpc_command::Invoker::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            rpc_command::invoker::OptionsBuilder::default()
+                .request_topic_pattern(REQUEST_TOPIC)
+                .command_name("test_command")
+                .supported_protocol_versions(Vec::new())
+                .build()
+                .unwrap(),
+        );
+    let e = invoker_result.err().unwrap();
+    assert_eq!(
+        e.property_name,
+        Some("supported_protocol_versions".to_string())
+    );
+}
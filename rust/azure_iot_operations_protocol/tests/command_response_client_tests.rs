@@ -0,0 +1,212 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command,
+};
+use bytes::Bytes;
+
+const REQUEST_TOPIC: &str = "test/command/bridged";
+const RESPONSE_TOPIC: &str = "test/command/bridged/response";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn request_publish(packet_identifier: u16, dup: bool) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(REQUEST_TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            dup,
+        ),
+        retain: false,
+        payload: Bytes::from_static(b"request"),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some([7u8; 16].as_slice().into()),
+            user_properties: vec![
+                ("__protVer".into(), "1.0".into()),
+                ("__srcId".into(), "invoker_client".into()),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+fn response() -> rpc_command::executor::Response<Vec<u8>> {
+    rpc_command::executor::ResponseBuilder::default()
+        .payload(b"response".to_vec())
+        .unwrap()
+        .build()
+        .unwrap()
+}
+
+/// Expects the response publish on the given mock server, acknowledges it and returns it
+async fn expect_response_and_ack(mock_server: &MockServer) -> mqtt_proto::Publish<Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 response publish");
+    }
+    publish
+}
+
+/// Tests that responses, including cached responses to duplicate requests, are only published on
+/// the response client while requests are subscribed to and acknowledged on the request client
+#[tokio::test]
+async fn executor_responds_on_response_client() {
+    let (request_session, request_server) = setup_client_and_mock_server("bridge_request_client");
+    let (response_session, response_server) =
+        setup_client_and_mock_server("bridge_response_client");
+
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        request_session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .response_client(response_session.create_managed_client())
+            .response_client_monitor(response_session.create_session_monitor())
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(request_session.run());
+    tokio::task::spawn(response_session.run());
+    request_server.expect_connect_and_accept(true).await;
+    response_server.expect_connect_and_accept(true).await;
+
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        request_server.expect_subscribe_and_accept().await;
+        request_server.send_publish(request_publish(1, false));
+    });
+    let request = request.unwrap().unwrap();
+    assert_eq!(request.payload, b"request".to_vec());
+
+    let (complete_result, response_publish) = tokio::join!(
+        request.complete(response()),
+        expect_response_and_ack(&response_server)
+    );
+    complete_result.unwrap();
+    assert_eq!(response_publish.topic_name.as_ref(), RESPONSE_TOPIC);
+    assert_eq!(response_publish.payload.as_ref(), b"response");
+    assert_eq!(
+        response_publish
+            .other_properties
+            .correlation_data
+            .as_ref()
+            .unwrap()
+            .as_ref(),
+        [7u8; 16].as_slice()
+    );
+
+    // The request is acknowledged on the request client, where no response is published
+    let puback = request_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 1);
+    request_server.expect_no_packet();
+
+    // A redelivered request is answered from the cache on the response client as well.
+    // Duplicates are handled while waiting for the next request, so no request is returned.
+    request_server.send_publish(request_publish(2, true));
+    let (recv_result, cached_response_publish) = tokio::join!(
+        tokio::time::timeout(std::time::Duration::from_millis(500), executor.recv()),
+        expect_response_and_ack(&response_server)
+    );
+    assert!(recv_result.is_err());
+    assert_eq!(cached_response_publish.payload.as_ref(), b"response");
+    let puback = request_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 2);
+    request_server.expect_no_packet();
+    response_server.expect_no_packet();
+}
+
+/// Tests that completing a request fails with a clear error when the response client is
+/// disconnected, rather than queueing the response
+#[tokio::test]
+async fn executor_response_client_disconnected() {
+    let (request_session, request_server) =
+        setup_client_and_mock_server("bridge_disconnected_request_client");
+    // The response session is never run, so it stays disconnected
+    let (response_session, response_server) =
+        setup_client_and_mock_server("bridge_disconnected_response_client");
+
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        request_session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC)
+            .command_name("test_command")
+            .response_client(response_session.create_managed_client())
+            .response_client_monitor(response_session.create_session_monitor())
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(request_session.run());
+    request_server.expect_connect_and_accept(true).await;
+
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        request_server.expect_subscribe_and_accept().await;
+        request_server.send_publish(request_publish(1, false));
+    });
+    let request = request.unwrap().unwrap();
+
+    let error = request.complete(response()).await.unwrap_err();
+    assert_eq!(error.kind, AIOProtocolErrorKind::StateInvalid);
+    assert_eq!(error.property_name, Some("response_client".to_string()));
+
+    // The request is still acknowledged, and no response is published on either client
+    let puback = request_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 1);
+    request_server.expect_no_packet();
+    response_server.expect_no_packet();
+}
+
+#[test]
+fn response_client_monitor_requires_response_client() {
+    let (session, _mock_server) = setup_client_and_mock_server("bridge_invalid_client");
+
+    let executor_result: Result<rpc_command::Executor<Vec<u8>, Vec<u8>>, _> =
+        rpc_command::Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            rpc_command::executor::OptionsBuilder::default()
+                .request_topic_pattern(REQUEST_TOPIC)
+                .command_name("test_command")
+                .response_client_monitor(session.create_session_monitor())
+                .build()
+                .unwrap(),
+        );
+    let e = executor_result.err().unwrap();
+    assert_eq!(e.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    assert_eq!(e.property_name, Some("response_client_monitor".to_string()));
+}
@@ -0,0 +1,227 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command::{self, executor::Request},
+};
+use bytes::Bytes;
+use uuid::Uuid;
+
+const REQUEST_TOPIC_PATTERN: &str = "test/{assetName}/write";
+const RESPONSE_TOPIC: &str = "test/response";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn serialized_executor(session: &Session) -> rpc_command::Executor<Vec<u8>, Vec<u8>> {
+    rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC_PATTERN)
+            .command_name("write")
+            .serialize_by_token("assetName")
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn request_publish(
+    packet_identifier: u16,
+    asset_name: &str,
+    message_expiry_interval: u32,
+) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(format!("test/{asset_name}/write")),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from(vec![u8::try_from(packet_identifier).unwrap()]),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(message_expiry_interval),
+            response_topic: Some(mqtt_proto::topic(RESPONSE_TOPIC)),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            user_properties: vec![("__protVer".into(), "1.0".into())],
+            ..Default::default()
+        },
+    }
+}
+
+/// Completes a request, acking the response published to the mock server
+async fn complete(request: Request<Vec<u8>, Vec<u8>>, mock_server: &MockServer) {
+    let (result, ()) = tokio::join!(
+        request.complete(
+            rpc_command::executor::ResponseBuilder::default()
+                .payload(Vec::new())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ),
+        async {
+            let publish = mock_server.expect_publish().await;
+            assert_eq!(publish.topic_name.as_str(), RESPONSE_TOPIC);
+            if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+                publish.packet_identifier_dup_qos
+            {
+                mock_server.send_puback(packet_identifier);
+            } else {
+                panic!("Expected QoS 1 response publish");
+            }
+        }
+    );
+    result.unwrap();
+}
+
+/// Tests that a request for an asset is only returned once the prior request for the same asset
+/// has completed
+#[tokio::test]
+async fn same_token_value_serialized() {
+    let (session, mock_server) = setup_client_and_mock_server("serialized_same_asset_test_client");
+    let mut executor = serialized_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (first, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(request_publish(1, "pump", 10));
+        mock_server.send_publish(request_publish(2, "pump", 10));
+    });
+    let first = first.unwrap().unwrap();
+    assert_eq!(first.payload, vec![1]);
+    assert_eq!(first.topic_tokens.get("assetName").unwrap(), "pump");
+
+    // The second request is queued while the first is being processed
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), executor.recv())
+            .await
+            .is_err()
+    );
+    assert_eq!(executor.queue_depth("pump"), 1);
+
+    complete(first, &mock_server).await;
+    assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+
+    let second = executor.recv().await.unwrap().unwrap();
+    assert_eq!(second.payload, vec![2]);
+    assert_eq!(executor.queue_depth("pump"), 0);
+}
+
+/// Tests that requests for different assets are returned without waiting for each other
+#[tokio::test]
+async fn different_token_values_concurrent() {
+    let (session, mock_server) =
+        setup_client_and_mock_server("serialized_different_assets_test_client");
+    let mut executor = serialized_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (first, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(request_publish(1, "pump", 10));
+        mock_server.send_publish(request_publish(2, "valve", 10));
+    });
+    let first = first.unwrap().unwrap();
+    assert_eq!(first.topic_tokens.get("assetName").unwrap(), "pump");
+
+    // Returned while the request for the other asset is still being processed
+    let second = tokio::time::timeout(Duration::from_secs(1), executor.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.topic_tokens.get("assetName").unwrap(), "valve");
+    assert_eq!(executor.queue_depth("pump"), 0);
+    assert_eq!(executor.queue_depth("valve"), 0);
+
+    complete(second, &mock_server).await;
+    complete(first, &mock_server).await;
+}
+
+/// Tests that a queued request that expires while waiting times out and is never returned, and
+/// doesn't hold up later requests
+#[tokio::test]
+async fn queued_request_expires() {
+    let (session, mock_server) = setup_client_and_mock_server("serialized_expiry_test_client");
+    let mut executor = serialized_executor(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (first, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(request_publish(1, "pump", 10));
+        mock_server.send_publish(request_publish(2, "pump", 1));
+    });
+    let first = first.unwrap().unwrap();
+
+    // The queued request times out without a response while the first is being processed
+    assert!(
+        tokio::time::timeout(Duration::from_millis(1500), executor.recv())
+            .await
+            .is_err()
+    );
+    assert_eq!(executor.queue_depth("pump"), 1);
+
+    // Acks are sent in the order the requests were received
+    complete(first, &mock_server).await;
+    assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+    assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 2);
+    mock_server.expect_no_packet();
+
+    // The expired request is not returned, the next request for the asset is
+    mock_server.send_publish(request_publish(3, "pump", 10));
+    let third = executor.recv().await.unwrap().unwrap();
+    assert_eq!(third.payload, vec![3]);
+    assert_eq!(executor.queue_depth("pump"), 0);
+}
+
+/// Tests that the serialization token must be in the request topic pattern
+#[test]
+fn serialize_by_unknown_token() {
+    let (session, _) = setup_client_and_mock_server("serialized_unknown_token_test_client");
+    let result: Result<rpc_command::Executor<Vec<u8>, Vec<u8>>, _> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(REQUEST_TOPIC_PATTERN)
+            .command_name("write")
+            .serialize_by_token("deviceName")
+            .build()
+            .unwrap(),
+    );
+    let Err(error) = result else {
+        panic!("Expected configuration error");
+    };
+    assert_eq!(error.kind, AIOProtocolErrorKind::ConfigurationInvalid);
+    assert_eq!(error.property_name, Some("serialize_by_token".to_string()));
+}
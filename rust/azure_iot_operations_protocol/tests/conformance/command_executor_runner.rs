@@ -0,0 +1,161 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::azure_mqtt::mqtt_proto;
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    rpc_command::{
+        self,
+        executor::{Request, ResponseBuilder},
+    },
+};
+use tokio::sync::mpsc;
+
+use crate::conformance::{
+    VECTOR_TIMEOUT_SECONDS,
+    conformance_payload::ConformancePayload,
+    conformance_vector::{
+        ConformanceVector, DeliveredMessage, RespondAction, VectorPublish, encode_hex,
+    },
+    setup_client_and_mock_server,
+};
+
+pub const CLIENT_ID: &str = "ConformanceExecutorClient";
+pub const COMMAND_NAME: &str = "echo";
+pub const REQUEST_TOPIC_PATTERN: &str = "conformance/{deviceId}/command/echo";
+
+const SENTINEL_CORRELATION_DATA: [u8; 16] = [0xff; 16];
+
+type ConformanceRequest = Request<ConformancePayload, ConformancePayload>;
+
+/// Delivers the publish of a command executor vector to an executor and checks the outcome
+pub async fn run_command_executor_vector(vector: &ConformanceVector) {
+    tokio::time::timeout(
+        Duration::from_secs(VECTOR_TIMEOUT_SECONDS),
+        run_vector(vector),
+    )
+    .await
+    .unwrap_or_else(|_| panic!("Conformance vector {} timed out", vector.name));
+}
+
+async fn run_vector(vector: &ConformanceVector) {
+    let (session, mock_server) = setup_client_and_mock_server(CLIENT_ID);
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut executor: rpc_command::Executor<ConformancePayload, ConformancePayload> =
+        rpc_command::Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            rpc_command::executor::OptionsBuilder::default()
+                .request_topic_pattern(REQUEST_TOPIC_PATTERN)
+                .command_name(COMMAND_NAME)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+    // Requests are received in the background, as not every vector delivers one
+    let (request_tx, mut request_rx) = mpsc::unbounded_channel();
+    let receive_loop = tokio::task::spawn(async move {
+        while let Some(request) = executor.recv().await {
+            let request = request.expect("Executor returned an error");
+            if request_tx.send(request).is_err() {
+                break;
+            }
+        }
+    });
+
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(vector.publish.to_mqtt(1));
+
+    let expect = &vector.expect;
+    if let Some(expected) = &expect.delivered {
+        let request = request_rx.recv().await.unwrap();
+        expected.check(&delivered_request(&request));
+        expected.check_cloud_event(rpc_command::executor::cloud_event_from_request(&request));
+
+        match expect.respond {
+            RespondAction::Echo => complete_in_background(request, None),
+            RespondAction::Empty => complete_in_background(request, Some(String::new())),
+            RespondAction::Drop => drop(request),
+        }
+    }
+
+    if let Some(expected) = &expect.response {
+        let response = mock_server.expect_publish().await;
+        expected.check(&vector.publish, &VectorPublish::from_mqtt(&response));
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            response.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        }
+    }
+
+    // Any response is published before the request is acknowledged
+    if vector.publish.qos == 1 {
+        assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+    }
+
+    if expect.delivered.is_none() {
+        // The request must not have been delivered ahead of a valid request that follows it
+        mock_server.send_publish(sentinel_request().to_mqtt(2));
+        let request = request_rx.recv().await.unwrap();
+        assert_eq!(
+            request.correlation_data.as_deref(),
+            Some(SENTINEL_CORRELATION_DATA.as_slice()),
+            "Request was delivered to the application"
+        );
+    }
+
+    receive_loop.abort();
+}
+
+fn delivered_request(request: &ConformanceRequest) -> DeliveredMessage {
+    DeliveredMessage {
+        payload: Some(request.payload.0.clone()),
+        content_type: Some(request.content_type.clone()),
+        format_indicator: Some(request.format_indicator as u8),
+        custom_user_data: Some(request.custom_user_data.clone()),
+        source_id: Some(request.invoker_id.clone()),
+        timestamp: Some(request.timestamp.as_ref().map(ToString::to_string)),
+        topic_tokens: Some(request.topic_tokens.clone().into_iter().collect()),
+        duplicate: Some(request.duplicate),
+        subscription_identifiers: Some(request.subscription_identifiers.clone()),
+        no_response: Some(request.no_response),
+        ..Default::default()
+    }
+}
+
+/// Completes the request with its own payload, or with `payload` if set. Completion waits for
+/// the response to be acknowledged, so it can't block the mock server.
+fn complete_in_background(request: ConformanceRequest, payload: Option<String>) {
+    let payload = ConformancePayload(payload.unwrap_or_else(|| request.payload.0.clone()));
+    let response = ResponseBuilder::default()
+        .payload(payload)
+        .unwrap()
+        .build()
+        .unwrap();
+    tokio::task::spawn(async move {
+        let _ = request.complete(response).await;
+    });
+}
+
+fn sentinel_request() -> VectorPublish {
+    VectorPublish {
+        topic: "conformance/sentinel/command/echo".to_string(),
+        payload: Some("{}".to_string()),
+        content_type: None,
+        format_indicator: 1,
+        message_expiry: Some(10),
+        response_topic: Some("conformance/sentinel/response".to_string()),
+        correlation_data: Some(encode_hex(&SENTINEL_CORRELATION_DATA)),
+        user_properties: Vec::new(),
+        subscription_identifiers: Vec::new(),
+        qos: 1,
+        dup: false,
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_protocol::common::payload_serialize::{
+    DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+};
+
+pub const CONTENT_TYPE: &str = "application/json";
+
+/// JSON payload kept as its raw text, so that vectors can compare it exactly.
+///
+/// Deserialization accepts no content type or `application/json`, and requires the payload to be
+/// valid JSON. Serialization does not validate, so an empty payload can be sent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConformancePayload(pub String);
+
+impl PayloadSerialize for ConformancePayload {
+    type Error = String;
+
+    fn serialize(self) -> Result<SerializedPayload, String> {
+        Ok(SerializedPayload {
+            payload: self.0.into_bytes(),
+            content_type: CONTENT_TYPE.to_string(),
+            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<String>> {
+        if let Some(content_type) = content_type
+            && content_type != CONTENT_TYPE
+        {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "Invalid content type: '{content_type}'. Must be '{CONTENT_TYPE}'"
+            )));
+        }
+        let text = String::from_utf8(payload.to_vec())
+            .map_err(|e| DeserializationError::InvalidPayload(e.to_string()))?;
+        serde_json::from_str::<serde_json::Value>(&text)
+            .map_err(|e| DeserializationError::InvalidPayload(e.to_string()))?;
+        Ok(ConformancePayload(text))
+    }
+}
@@ -0,0 +1,530 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use azure_iot_operations_mqtt::{aio::cloud_event::CloudEvent, azure_mqtt::mqtt_proto};
+use bytes::Bytes;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::conformance::optional_field::deserialize_optional_field;
+
+/// A single conformance vector: an MQTT publish received by the SDK and the expected outcome
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConformanceVector {
+    pub name: String,
+    pub description: String,
+    pub publish: VectorPublish,
+    pub expect: VectorExpectation,
+}
+
+/// MQTT publish delivered to the SDK under test
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct VectorPublish {
+    pub topic: String,
+    /// UTF-8 payload. Absent for an empty payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub format_indicator: u8,
+    /// Message expiry interval in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_expiry: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_topic: Option<String>,
+    /// Hex encoded correlation data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_data: Option<String>,
+    /// User properties as `[key, value]` pairs, in the order they are sent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub user_properties: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscription_identifiers: Vec<u32>,
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    /// DUP flag, only valid with QoS 1
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dup: bool,
+}
+
+/// Expected outcome of delivering a [`VectorPublish`] to the SDK under test.
+///
+/// If nothing is `delivered` and no `response` is published, the publish must be acknowledged
+/// and dropped.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct VectorExpectation {
+    /// Message handed to the application
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivered: Option<DeliveredMessage>,
+    /// How the application responds to a delivered command request
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub respond: RespondAction,
+    /// Command response published by the executor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<PublishedResponse>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RespondAction {
+    /// Complete the request with its own payload
+    #[default]
+    Echo,
+    /// Complete the request with an empty payload
+    Empty,
+    /// Drop the request without completing it
+    Drop,
+}
+
+/// Fields of a delivered command request or telemetry message. Only the fields that are present
+/// are checked, and a field set to `null` must be absent from the delivered message.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[allow(clippy::option_option)]
+pub struct DeliveredMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_type: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_indicator: Option<u8>,
+    /// Compared regardless of order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_user_data: Option<Vec<(String, String)>>,
+    /// Client ID of the invoker or sender
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub source_id: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_tokens: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_identifiers: Option<Vec<u32>>,
+    /// Command requests only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_response: Option<bool>,
+    /// Telemetry only
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub priority: Option<Option<u8>>,
+    /// Telemetry only
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sequence_number: Option<Option<u64>>,
+    /// Telemetry only
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sender_instance_id: Option<Option<String>>,
+    /// Cloud event parsed from the message, `null` if no valid cloud event can be parsed
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cloud_event: Option<Option<VectorCloudEvent>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[allow(clippy::option_option)]
+pub struct VectorCloudEvent {
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub spec_version: String,
+    pub id: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub subject: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub data_schema: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub data_content_type: Option<Option<String>>,
+}
+
+/// Command response published by the executor, on the request's response topic and with the
+/// request's correlation data. Only the fields that are present are checked, and a field set to
+/// `null` must be absent from the response.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[allow(clippy::option_option)]
+pub struct PublishedResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_application_error: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub invalid_property_name: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub invalid_property_value: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub supported_major_versions: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub request_protocol_version: Option<Option<String>>,
+    /// UTF-8 payload, `null` for an empty payload
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_field",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub payload: Option<Option<String>>,
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl VectorPublish {
+    /// Builds the publish sent by the mock server
+    pub fn to_mqtt(&self, packet_identifier: u16) -> mqtt_proto::Publish<Bytes> {
+        let packet_identifier_dup_qos = match self.qos {
+            0 => {
+                assert!(!self.dup, "DUP flag must not be set with QoS 0");
+                mqtt_proto::PacketIdentifierDupQoS::AtMostOnce
+            }
+            1 => mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+                self.dup,
+            ),
+            qos => panic!("Unsupported QoS {qos} in conformance vector"),
+        };
+        mqtt_proto::Publish {
+            topic_name: mqtt_proto::topic(&self.topic),
+            packet_identifier_dup_qos,
+            retain: false,
+            payload: Bytes::from(self.payload.clone().unwrap_or_default()),
+            other_properties: mqtt_proto::PublishOtherProperties {
+                payload_is_utf8: self.format_indicator == 1,
+                message_expiry_interval: self.message_expiry,
+                response_topic: self.response_topic.as_ref().map(mqtt_proto::topic),
+                correlation_data: self
+                    .correlation_data
+                    .as_ref()
+                    .map(|hex| decode_hex(hex).as_slice().into()),
+                user_properties: self
+                    .user_properties
+                    .iter()
+                    .map(|(key, value)| (key.as_str().into(), value.as_str().into()))
+                    .collect(),
+                subscription_identifiers: self
+                    .subscription_identifiers
+                    .iter()
+                    .map(|id| (*id).try_into().unwrap())
+                    .collect(),
+                content_type: self.content_type.as_deref().map(Into::into),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Describes a publish sent by the SDK, so that it can be replayed to other SDKs
+    pub fn from_mqtt(publish: &mqtt_proto::Publish<Bytes>) -> VectorPublish {
+        let properties = &publish.other_properties;
+        VectorPublish {
+            topic: publish.topic_name.as_ref().to_string(),
+            payload: (!publish.payload.is_empty())
+                .then(|| String::from_utf8(publish.payload.to_vec()).unwrap()),
+            content_type: properties
+                .content_type
+                .as_ref()
+                .map(|content_type| content_type.as_ref().to_string()),
+            format_indicator: u8::from(properties.payload_is_utf8),
+            message_expiry: properties.message_expiry_interval,
+            response_topic: properties
+                .response_topic
+                .as_ref()
+                .map(|topic| topic.as_ref().to_string()),
+            correlation_data: properties
+                .correlation_data
+                .as_ref()
+                .map(|correlation_data| encode_hex(correlation_data.as_ref())),
+            user_properties: properties
+                .user_properties
+                .iter()
+                .map(|(key, value)| (key.as_ref().to_string(), value.as_ref().to_string()))
+                .collect(),
+            subscription_identifiers: Vec::new(),
+            qos: match publish.packet_identifier_dup_qos {
+                mqtt_proto::PacketIdentifierDupQoS::AtMostOnce => 0,
+                _ => 1,
+            },
+            dup: false,
+        }
+    }
+
+    /// Value of a user property on the publish, if present
+    pub fn user_property(&self, key: &str) -> Option<&str> {
+        self.user_properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl DeliveredMessage {
+    /// Checks the fields of `actual`, the message as delivered, against the expected fields
+    pub fn check(&self, actual: &DeliveredMessage) {
+        fn check_field<T: PartialEq + std::fmt::Debug>(
+            expected: Option<&T>,
+            actual: Option<&T>,
+            field: &str,
+        ) {
+            if let Some(expected) = expected {
+                assert_eq!(actual, Some(expected), "{field}");
+            }
+        }
+
+        check_field(self.payload.as_ref(), actual.payload.as_ref(), "payload");
+        check_field(
+            self.content_type.as_ref(),
+            actual.content_type.as_ref(),
+            "content-type",
+        );
+        check_field(
+            self.format_indicator.as_ref(),
+            actual.format_indicator.as_ref(),
+            "format-indicator",
+        );
+        let sorted = |user_data: &Vec<(String, String)>| {
+            let mut user_data = user_data.clone();
+            user_data.sort();
+            user_data
+        };
+        check_field(
+            self.custom_user_data.as_ref().map(sorted).as_ref(),
+            actual.custom_user_data.as_ref().map(sorted).as_ref(),
+            "custom-user-data",
+        );
+        check_field(
+            self.source_id.as_ref(),
+            actual.source_id.as_ref(),
+            "source-id",
+        );
+        check_field(
+            self.timestamp.as_ref(),
+            actual.timestamp.as_ref(),
+            "timestamp",
+        );
+        check_field(
+            self.topic_tokens.as_ref(),
+            actual.topic_tokens.as_ref(),
+            "topic-tokens",
+        );
+        check_field(
+            self.duplicate.as_ref(),
+            actual.duplicate.as_ref(),
+            "duplicate",
+        );
+        check_field(
+            self.subscription_identifiers.as_ref(),
+            actual.subscription_identifiers.as_ref(),
+            "subscription-identifiers",
+        );
+        check_field(
+            self.no_response.as_ref(),
+            actual.no_response.as_ref(),
+            "no-response",
+        );
+        check_field(self.priority.as_ref(), actual.priority.as_ref(), "priority");
+        check_field(
+            self.sequence_number.as_ref(),
+            actual.sequence_number.as_ref(),
+            "sequence-number",
+        );
+        check_field(
+            self.sender_instance_id.as_ref(),
+            actual.sender_instance_id.as_ref(),
+            "sender-instance-id",
+        );
+    }
+
+    /// Checks the cloud event parsed from the delivered message
+    pub fn check_cloud_event<E: std::fmt::Debug>(&self, cloud_event: Result<CloudEvent, E>) {
+        let Some(expected) = &self.cloud_event else {
+            return;
+        };
+        match (expected, cloud_event) {
+            (None, Err(_)) => {}
+            (None, Ok(cloud_event)) => {
+                panic!("cloud-event: expected no valid cloud event, got {cloud_event:?}")
+            }
+            (Some(_), Err(e)) => panic!("cloud-event: expected a valid cloud event, got {e:?}"),
+            (Some(expected), Ok(cloud_event)) => expected.check(&cloud_event),
+        }
+    }
+}
+
+impl VectorCloudEvent {
+    fn check(&self, cloud_event: &CloudEvent) {
+        assert_eq!(cloud_event.source, self.source, "cloud-event source");
+        assert_eq!(cloud_event.event_type, self.event_type, "cloud-event type");
+        assert_eq!(
+            cloud_event.spec_version, self.spec_version,
+            "cloud-event spec-version"
+        );
+        assert_eq!(cloud_event.id, self.id, "cloud-event id");
+        if let Some(expected) = &self.time {
+            let expected = expected
+                .as_ref()
+                .map(|time| DateTime::parse_from_rfc3339(time).unwrap().to_utc());
+            assert_eq!(cloud_event.time, expected, "cloud-event time");
+        }
+        if let Some(expected) = &self.subject {
+            assert_eq!(&cloud_event.subject, expected, "cloud-event subject");
+        }
+        if let Some(expected) = &self.data_schema {
+            assert_eq!(
+                &cloud_event.data_schema, expected,
+                "cloud-event data-schema"
+            );
+        }
+        if let Some(expected) = &self.data_content_type {
+            assert_eq!(
+                &cloud_event.data_content_type, expected,
+                "cloud-event data-content-type"
+            );
+        }
+    }
+}
+
+impl PublishedResponse {
+    /// Checks a response published by the executor for the request `publish`
+    pub fn check(&self, request: &VectorPublish, response: &VectorPublish) {
+        assert_eq!(
+            Some(&response.topic),
+            request.response_topic.as_ref(),
+            "response topic"
+        );
+        assert_eq!(
+            response.correlation_data, request.correlation_data,
+            "response correlation data"
+        );
+        assert_eq!(
+            response.user_property("__stat"),
+            Some(self.status.to_string().as_str()),
+            "status"
+        );
+        if let Some(expected) = self.is_application_error {
+            assert_eq!(
+                response.user_property("__apErr").unwrap_or("false"),
+                expected.to_string(),
+                "is-application-error"
+            );
+        }
+        for (key, expected, field) in [
+            (
+                "__propName",
+                &self.invalid_property_name,
+                "invalid-property-name",
+            ),
+            (
+                "__propVal",
+                &self.invalid_property_value,
+                "invalid-property-value",
+            ),
+            (
+                "__supProtMajVer",
+                &self.supported_major_versions,
+                "supported-major-versions",
+            ),
+            (
+                "__requestProtVer",
+                &self.request_protocol_version,
+                "request-protocol-version",
+            ),
+        ] {
+            if let Some(expected) = expected {
+                assert_eq!(response.user_property(key), expected.as_deref(), "{field}");
+            }
+        }
+        if let Some(expected) = &self.payload {
+            assert_eq!(&response.payload, expected, "response payload");
+        }
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").unwrap();
+        hex
+    })
+}
+
+pub fn decode_hex(hex: &str) -> Vec<u8> {
+    assert!(hex.len() % 2 == 0, "Odd length hex string: {hex}");
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,122 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, telemetry};
+use tokio::sync::mpsc;
+
+use crate::conformance::{
+    VECTOR_TIMEOUT_SECONDS,
+    conformance_payload::ConformancePayload,
+    conformance_vector::{ConformanceVector, DeliveredMessage, VectorPublish},
+    setup_client_and_mock_server,
+};
+
+pub const CLIENT_ID: &str = "ConformanceReceiverClient";
+pub const TOPIC_PATTERN: &str = "conformance/{deviceId}/telemetry";
+
+const SENTINEL_PAYLOAD: &str = "\"sentinel\"";
+
+/// Delivers the publish of a telemetry receiver vector to a receiver and checks the outcome
+pub async fn run_telemetry_receiver_vector(vector: &ConformanceVector) {
+    assert!(
+        vector.expect.response.is_none(),
+        "Telemetry receiver vectors can't expect a response"
+    );
+    tokio::time::timeout(
+        Duration::from_secs(VECTOR_TIMEOUT_SECONDS),
+        run_vector(vector),
+    )
+    .await
+    .unwrap_or_else(|_| panic!("Conformance vector {} timed out", vector.name));
+}
+
+async fn run_vector(vector: &ConformanceVector) {
+    let (session, mock_server) = setup_client_and_mock_server(CLIENT_ID);
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<ConformancePayload> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC_PATTERN)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    // Messages are received in the background, as not every vector delivers one
+    let (message_tx, mut message_rx) = mpsc::unbounded_channel();
+    let receive_loop = tokio::task::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let (message, _) = message.expect("Receiver returned an error");
+            if message_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.send_publish(vector.publish.to_mqtt(1));
+
+    if let Some(expected) = &vector.expect.delivered {
+        let message = message_rx.recv().await.unwrap();
+        expected.check(&delivered_message(&message));
+        expected.check_cloud_event(telemetry::receiver::cloud_event_from_telemetry(&message));
+    }
+
+    if vector.publish.qos == 1 {
+        assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+    }
+
+    if vector.expect.delivered.is_none() {
+        // The message must not have been delivered ahead of a valid message that follows it
+        mock_server.send_publish(sentinel_message().to_mqtt(2));
+        let message = message_rx.recv().await.unwrap();
+        assert_eq!(
+            message.payload.0, SENTINEL_PAYLOAD,
+            "Message was delivered to the application"
+        );
+    }
+
+    receive_loop.abort();
+}
+
+fn delivered_message(
+    message: &telemetry::receiver::Message<ConformancePayload>,
+) -> DeliveredMessage {
+    DeliveredMessage {
+        payload: Some(message.payload.0.clone()),
+        content_type: Some(message.content_type.clone()),
+        format_indicator: Some(message.format_indicator as u8),
+        custom_user_data: Some(message.custom_user_data.clone()),
+        source_id: Some(message.sender_id.clone()),
+        timestamp: Some(message.timestamp.as_ref().map(ToString::to_string)),
+        topic_tokens: Some(message.topic_tokens.clone().into_iter().collect()),
+        duplicate: message.duplicate,
+        subscription_identifiers: Some(message.subscription_identifiers.clone()),
+        priority: Some(message.priority),
+        sequence_number: Some(message.sequence_number),
+        sender_instance_id: Some(message.sender_instance_id.clone()),
+        ..Default::default()
+    }
+}
+
+fn sentinel_message() -> VectorPublish {
+    VectorPublish {
+        topic: "conformance/sentinel/telemetry".to_string(),
+        payload: Some(SENTINEL_PAYLOAD.to_string()),
+        content_type: None,
+        format_indicator: 1,
+        message_expiry: None,
+        response_topic: None,
+        correlation_data: None,
+        user_properties: Vec::new(),
+        subscription_identifiers: Vec::new(),
+        qos: 1,
+        dup: false,
+    }
+}
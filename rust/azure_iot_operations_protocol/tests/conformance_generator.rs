@@ -0,0 +1,356 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Generates conformance vectors from publishes sent by the Rust telemetry sender and command
+//! invoker, so that other SDKs can check that they accept what the Rust SDK sends.
+//!
+//! The vectors are always checked against the Rust telemetry receiver and command executor. To
+//! regenerate the checked in vectors in `eng/test/conformance/Generated`, run:
+//! `AIO_CONFORMANCE_GENERATE=1 cargo test --test conformance_generator`
+
+mod conformance;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    time::Duration,
+};
+
+use azure_iot_operations_mqtt::{azure_mqtt::mqtt_proto, test_utils::MockServer};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    rpc_command::{self, invoker::RequestCloudEventBuilder},
+    telemetry::{self, sender::CloudEventBuilder},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use conformance::{
+    command_executor_runner::{REQUEST_TOPIC_PATTERN, run_command_executor_vector},
+    conformance_payload::{CONTENT_TYPE, ConformancePayload},
+    conformance_vector::{
+        ConformanceVector, DeliveredMessage, PublishedResponse, VectorCloudEvent,
+        VectorExpectation, VectorPublish,
+    },
+    setup_client_and_mock_server,
+    telemetry_receiver_runner::{TOPIC_PATTERN, run_telemetry_receiver_vector},
+};
+
+const GENERATE_ENV_VAR: &str = "AIO_CONFORMANCE_GENERATE";
+const OUTPUT_DIR: &str = "../../eng/test/conformance/Generated";
+const SENDER_CLIENT_ID: &str = "ConformanceGeneratorClient";
+const DEVICE_ID: &str = "generated-device";
+const CLOUD_EVENT_SOURCE: &str = "aio://conformance/generator";
+const CLOUD_EVENT_TYPE: &str = "ms.aio.conformance";
+const CLOUD_EVENT_ID: &str = "4d5c7a58-0f0a-4c5e-9c1e-7f6b3a2d1e00";
+const CLOUD_EVENT_TIME: &str = "2025-01-01T00:00:00Z";
+const CLOUD_EVENT_DATA_SCHEMA: &str = "aio://conformance/schema";
+
+fn device_topic_tokens() -> HashMap<String, String> {
+    HashMap::from([("deviceId".to_string(), DEVICE_ID.to_string())])
+}
+
+fn cloud_event_time() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(CLOUD_EVENT_TIME)
+        .unwrap()
+        .to_utc()
+}
+
+fn expected_cloud_event(topic: &str) -> VectorCloudEvent {
+    VectorCloudEvent {
+        source: CLOUD_EVENT_SOURCE.to_string(),
+        event_type: CLOUD_EVENT_TYPE.to_string(),
+        spec_version: "1.0".to_string(),
+        id: CLOUD_EVENT_ID.to_string(),
+        time: Some(Some(CLOUD_EVENT_TIME.to_string())),
+        subject: Some(Some(topic.to_string())),
+        data_schema: Some(Some(CLOUD_EVENT_DATA_SCHEMA.to_string())),
+        data_content_type: Some(Some(CONTENT_TYPE.to_string())),
+    }
+}
+
+/// Acknowledges the next publish from the client and returns it
+async fn capture_publish(mock_server: &MockServer) -> mqtt_proto::Publish<Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    }
+    publish
+}
+
+async fn generate_telemetry_vectors() -> Vec<ConformanceVector> {
+    let (session, mock_server) = setup_client_and_mock_server(SENDER_CLIENT_ID);
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let sender: telemetry::Sender<ConformancePayload> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC_PATTERN)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let mut vectors = Vec::new();
+
+    // Telemetry with custom user data
+    let payload = r#"{"temperature":21.5}"#;
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(ConformancePayload(payload.to_string()))
+        .unwrap()
+        .custom_user_data(vec![("unit".to_string(), "celsius".to_string())])
+        .topic_tokens(device_topic_tokens())
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), capture_publish(&mock_server));
+    result.unwrap();
+    let publish = VectorPublish::from_mqtt(&publish);
+    vectors.push(ConformanceVector {
+        name: "GeneratedTelemetrySenderCustomUserData_Delivered".to_string(),
+        description: "Telemetry with custom user data, as sent by the Rust telemetry sender."
+            .to_string(),
+        expect: VectorExpectation {
+            delivered: Some(DeliveredMessage {
+                payload: Some(payload.to_string()),
+                content_type: Some(Some(CONTENT_TYPE.to_string())),
+                format_indicator: Some(1),
+                custom_user_data: Some(vec![("unit".to_string(), "celsius".to_string())]),
+                source_id: Some(Some(SENDER_CLIENT_ID.to_string())),
+                timestamp: Some(publish.user_property("__ts").map(ToString::to_string)),
+                topic_tokens: Some(BTreeMap::from([(
+                    "deviceId".to_string(),
+                    DEVICE_ID.to_string(),
+                )])),
+                priority: Some(None),
+                cloud_event: Some(None),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        publish,
+    });
+
+    // Telemetry with a cloud event and a priority
+    let payload = r#"{"temperature":22.0}"#;
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(ConformancePayload(payload.to_string()))
+        .unwrap()
+        .topic_tokens(device_topic_tokens())
+        .cloud_event(
+            CloudEventBuilder::default()
+                .source(CLOUD_EVENT_SOURCE)
+                .event_type(CLOUD_EVENT_TYPE)
+                .id(CLOUD_EVENT_ID)
+                .time(cloud_event_time())
+                .data_schema(CLOUD_EVENT_DATA_SCHEMA.to_string())
+                .build()
+                .unwrap(),
+        )
+        .priority(3)
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), capture_publish(&mock_server));
+    result.unwrap();
+    let publish = VectorPublish::from_mqtt(&publish);
+    vectors.push(ConformanceVector {
+        name: "GeneratedTelemetrySenderCloudEvent_Delivered".to_string(),
+        description:
+            "Telemetry with a cloud event and a priority, as sent by the Rust telemetry sender."
+                .to_string(),
+        expect: VectorExpectation {
+            delivered: Some(DeliveredMessage {
+                payload: Some(payload.to_string()),
+                source_id: Some(Some(SENDER_CLIENT_ID.to_string())),
+                timestamp: Some(publish.user_property("__ts").map(ToString::to_string)),
+                priority: Some(Some(3)),
+                cloud_event: Some(Some(expected_cloud_event(&publish.topic))),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        publish,
+    });
+
+    vectors
+}
+
+async fn generate_command_vectors() -> Vec<ConformanceVector> {
+    let (session, mock_server) = setup_client_and_mock_server(SENDER_CLIENT_ID);
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let invoker: rpc_command::Invoker<ConformancePayload, ConformancePayload> =
+        rpc_command::Invoker::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            rpc_command::invoker::OptionsBuilder::default()
+                .request_topic_pattern(REQUEST_TOPIC_PATTERN)
+                .command_name("echo")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+    let mut vectors = Vec::new();
+
+    // Fire-and-forget request, which doesn't need a response subscription
+    let payload = r#"{"value":0}"#;
+    let request = rpc_command::invoker::RequestBuilder::default()
+        .payload(ConformancePayload(payload.to_string()))
+        .unwrap()
+        .topic_tokens(device_topic_tokens())
+        .timeout(Duration::from_secs(10))
+        .correlation_id(Uuid::from_u128(0x0001))
+        .no_response()
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(
+        invoker.invoke_no_response(request),
+        capture_publish(&mock_server)
+    );
+    result.unwrap();
+    let publish = VectorPublish::from_mqtt(&publish);
+    vectors.push(ConformanceVector {
+        name: "GeneratedCommandInvokerNoResponse_ExecutedWithoutResponse".to_string(),
+        description: "Fire-and-forget request, as sent by the Rust command invoker.".to_string(),
+        expect: VectorExpectation {
+            delivered: Some(DeliveredMessage {
+                payload: Some(payload.to_string()),
+                source_id: Some(Some(SENDER_CLIENT_ID.to_string())),
+                timestamp: Some(publish.user_property("__ts").map(ToString::to_string)),
+                no_response: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        publish,
+    });
+
+    // Requests expecting a response. The invocations are abandoned once their request is
+    // captured, as the invoker is only used to produce the requests.
+    let requests = [
+        (
+            "GeneratedCommandInvokerCustomUserData_RespondsOk",
+            "Request with custom user data, as sent by the Rust command invoker.",
+            rpc_command::invoker::RequestBuilder::default()
+                .payload(ConformancePayload(r#"{"value":1}"#.to_string()))
+                .unwrap()
+                .custom_user_data(vec![("trace".to_string(), "abc".to_string())])
+                .topic_tokens(device_topic_tokens())
+                .timeout(Duration::from_secs(10))
+                .correlation_id(Uuid::from_u128(0x0002))
+                .build()
+                .unwrap(),
+        ),
+        (
+            "GeneratedCommandInvokerCloudEvent_RespondsOk",
+            "Request with a cloud event, as sent by the Rust command invoker.",
+            rpc_command::invoker::RequestBuilder::default()
+                .payload(ConformancePayload(r#"{"value":2}"#.to_string()))
+                .unwrap()
+                .topic_tokens(device_topic_tokens())
+                .timeout(Duration::from_secs(10))
+                .correlation_id(Uuid::from_u128(0x0003))
+                .cloud_event(
+                    RequestCloudEventBuilder::default()
+                        .source(CLOUD_EVENT_SOURCE)
+                        .event_type(CLOUD_EVENT_TYPE)
+                        .id(CLOUD_EVENT_ID)
+                        .time(cloud_event_time())
+                        .data_schema(CLOUD_EVENT_DATA_SCHEMA.to_string())
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        ),
+    ];
+    for (index, (name, description, request)) in requests.into_iter().enumerate() {
+        let payload = r#"{"value":N}"#.replace('N', &(index + 1).to_string());
+        let has_cloud_event = name.contains("CloudEvent");
+        let publish = tokio::select! {
+            _ = invoker.invoke(request) => panic!("Invocation completed without a response"),
+            publish = async {
+                if index == 0 {
+                    mock_server.expect_subscribe_and_accept().await;
+                }
+                capture_publish(&mock_server).await
+            } => publish,
+        };
+        let publish = VectorPublish::from_mqtt(&publish);
+        vectors.push(ConformanceVector {
+            name: name.to_string(),
+            description: description.to_string(),
+            expect: VectorExpectation {
+                delivered: Some(DeliveredMessage {
+                    payload: Some(payload.clone()),
+                    content_type: Some(Some(CONTENT_TYPE.to_string())),
+                    custom_user_data: (!has_cloud_event)
+                        .then(|| vec![("trace".to_string(), "abc".to_string())]),
+                    source_id: Some(Some(SENDER_CLIENT_ID.to_string())),
+                    timestamp: Some(publish.user_property("__ts").map(ToString::to_string)),
+                    topic_tokens: Some(BTreeMap::from([(
+                        "deviceId".to_string(),
+                        DEVICE_ID.to_string(),
+                    )])),
+                    no_response: Some(false),
+                    cloud_event: Some(
+                        has_cloud_event.then(|| expected_cloud_event(&publish.topic)),
+                    ),
+                    ..Default::default()
+                }),
+                response: Some(PublishedResponse {
+                    status: 200,
+                    is_application_error: Some(false),
+                    invalid_property_name: Some(None),
+                    invalid_property_value: Some(None),
+                    supported_major_versions: None,
+                    request_protocol_version: None,
+                    payload: Some(Some(payload)),
+                }),
+                ..Default::default()
+            },
+            publish,
+        });
+    }
+
+    vectors
+}
+
+fn write_vectors(directory: &str, vectors: &[ConformanceVector]) {
+    let directory = Path::new(OUTPUT_DIR).join(directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    for vector in vectors {
+        let yaml = serde_yaml::to_string(vector).unwrap();
+        std::fs::write(
+            directory.join(format!("{}.yaml", vector.name)),
+            format!("---\n{yaml}...\n"),
+        )
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn generated_vectors() {
+    let telemetry_vectors = generate_telemetry_vectors().await;
+    let command_vectors = generate_command_vectors().await;
+
+    for vector in &telemetry_vectors {
+        run_telemetry_receiver_vector(vector).await;
+    }
+    for vector in &command_vectors {
+        run_command_executor_vector(vector).await;
+    }
+
+    if std::env::var(GENERATE_ENV_VAR).is_ok() {
+        write_vectors("TelemetryReceiver", &telemetry_vectors);
+        write_vectors("CommandExecutor", &command_vectors);
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+mod conformance;
+
+use std::path::Path;
+
+use tokio::runtime::Builder;
+
+use conformance::command_executor_runner::run_command_executor_vector;
+use conformance::conformance_vector::ConformanceVector;
+use conformance::telemetry_receiver_runner::run_telemetry_receiver_vector;
+
+fn parse_vector(contents: &str) -> ConformanceVector {
+    match serde_yaml::from_str(contents) {
+        Ok(vector) => vector,
+        Err(e) => panic!("Invalid conformance vector: {e}"),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+#[allow(clippy::needless_pass_by_value)]
+fn test_command_executor(_path: &Path, contents: String) -> datatest_stable::Result<()> {
+    let vector = parse_vector(&contents);
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(run_command_executor_vector(&vector));
+    Ok(())
+}
+
+#[allow(clippy::unnecessary_wraps)]
+#[allow(clippy::needless_pass_by_value)]
+fn test_telemetry_receiver(_path: &Path, contents: String) -> datatest_stable::Result<()> {
+    let vector = parse_vector(&contents);
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(run_telemetry_receiver_vector(&vector));
+    Ok(())
+}
+
+datatest_stable::harness!(
+    test_command_executor,
+    "../../eng/test/conformance/CommandExecutor",
+    r"^.*\.yaml",
+    test_telemetry_receiver,
+    "../../eng/test/conformance/TelemetryReceiver",
+    r"^.*\.yaml",
+    test_command_executor,
+    "../../eng/test/conformance/Generated/CommandExecutor",
+    r"^.*\.yaml",
+    test_telemetry_receiver,
+    "../../eng/test/conformance/Generated/TelemetryReceiver",
+    r"^.*\.yaml",
+);
@@ -752,6 +752,7 @@ impl CommandExecutorTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
@@ -753,6 +753,7 @@ impl CommandInvokerTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
@@ -784,6 +785,7 @@ impl CommandInvokerTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
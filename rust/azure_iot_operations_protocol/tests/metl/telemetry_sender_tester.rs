@@ -586,6 +586,7 @@ impl TelemetrySenderTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
@@ -617,6 +618,7 @@ impl TelemetrySenderTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
@@ -648,6 +650,7 @@ impl TelemetrySenderTester {
             command_name: None,
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         };
 
         protocol_error.ensure_error_message();
@@ -0,0 +1,331 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::{aio_protocol_error::AIOProtocolErrorKind, payload_checksum::IntegrityCheckFailed},
+    rpc_command, telemetry,
+};
+use bytes::Bytes;
+use uuid::Uuid;
+
+const CHECKSUM_USER_PROPERTY: &str = "__ck";
+/// Payload with a well known CRC-32C checksum
+const PAYLOAD: &[u8] = b"123456789";
+const PAYLOAD_CHECKSUM: &str = "e3069283";
+const CORRUPTED_PAYLOAD: &[u8] = b"123456788";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Gets the value of a user property of a publish
+fn user_property<'a>(publish: &'a mqtt_proto::Publish<Bytes>, key: &str) -> Option<&'a str> {
+    publish
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(k, _)| k.as_ref() == key)
+        .map(|(_, v)| v.as_ref())
+}
+
+/// Acks a QoS 1 publish received by the mock server
+fn puback(mock_server: &MockServer, publish: &mqtt_proto::Publish<Bytes>) {
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 publish");
+    }
+}
+
+fn with_packet_identifier(
+    mut publish: mqtt_proto::Publish<Bytes>,
+    packet_identifier: u16,
+) -> mqtt_proto::Publish<Bytes> {
+    publish.packet_identifier_dup_qos = mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+        mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+        false,
+    );
+    publish
+}
+
+/// Tests that a telemetry sender attaches the checksum of the payload, and that the receiver
+/// delivers intact messages and rejects corrupted ones
+#[tokio::test]
+async fn telemetry_payload_checksum() {
+    const TOPIC: &str = "test/telemetry/checksum";
+    let (session, mock_server) = setup_client_and_mock_server("telemetry_checksum_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client.clone(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let sender: telemetry::Sender<Vec<u8>> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .payload_checksum(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(PAYLOAD.to_vec())
+        .unwrap()
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), async {
+        let publish = mock_server.expect_publish().await;
+        puback(&mock_server, &publish);
+        publish
+    });
+    result.unwrap();
+    assert_eq!(
+        user_property(&publish, CHECKSUM_USER_PROPERTY),
+        Some(PAYLOAD_CHECKSUM)
+    );
+
+    // An intact message is delivered, without the checksum in its custom user data
+    let (message, ()) = tokio::join!(async { receiver.recv().await.unwrap().unwrap().0 }, async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(with_packet_identifier(publish.clone(), 1));
+    });
+    assert_eq!(message.payload, PAYLOAD);
+    assert!(
+        !message
+            .custom_user_data
+            .iter()
+            .any(|(key, _)| key == CHECKSUM_USER_PROPERTY)
+    );
+    assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+
+    // A corrupted message is rejected and acked
+    let mut corrupted = with_packet_identifier(publish.clone(), 2);
+    corrupted.payload = Bytes::from_static(CORRUPTED_PAYLOAD);
+    mock_server.send_publish(corrupted);
+    let Err(error) = receiver.recv().await.unwrap() else {
+        panic!("Expected corrupted telemetry to be rejected");
+    };
+    assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+    let integrity_error = error
+        .nested_error
+        .as_ref()
+        .unwrap()
+        .downcast_ref::<IntegrityCheckFailed>()
+        .unwrap();
+    assert_eq!(integrity_error.expected, PAYLOAD_CHECKSUM);
+    assert_ne!(integrity_error.computed, PAYLOAD_CHECKSUM);
+    assert_eq!(integrity_error.payload.as_ref(), CORRUPTED_PAYLOAD);
+    assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 2);
+
+    // A message without a checksum is not checked
+    let mut unchecked = with_packet_identifier(publish, 3);
+    unchecked.payload = Bytes::from_static(CORRUPTED_PAYLOAD);
+    unchecked
+        .other_properties
+        .user_properties
+        .retain(|(key, _)| key.as_ref() != CHECKSUM_USER_PROPERTY);
+    mock_server.send_publish(unchecked);
+    let (message, _) = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(message.payload, CORRUPTED_PAYLOAD);
+}
+
+fn request_publish(
+    packet_identifier: u16,
+    payload: &'static [u8],
+    checksum: &str,
+) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic("test/request"),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from_static(payload),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            message_expiry_interval: Some(10),
+            response_topic: Some(mqtt_proto::topic("test/response")),
+            correlation_data: Some(Uuid::new_v4().as_bytes().as_slice().into()),
+            user_properties: vec![
+                ("__protVer".into(), "1.0".into()),
+                (CHECKSUM_USER_PROPERTY.into(), checksum.into()),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests that an executor rejects a corrupted request, and attaches the checksum of the payload to
+/// its responses
+#[tokio::test]
+async fn executor_payload_checksum() {
+    let (session, mock_server) = setup_client_and_mock_server("executor_checksum_test_client");
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern("test/request")
+            .command_name("test_command")
+            .payload_checksum(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    // The corrupted request is responded to with an error, the intact one is returned
+    let (request, ()) = tokio::join!(executor.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(request_publish(1, CORRUPTED_PAYLOAD, PAYLOAD_CHECKSUM));
+        let response = mock_server.expect_publish().await;
+        assert_eq!(user_property(&response, "__stat"), Some("400"));
+        assert_eq!(
+            user_property(&response, "__propName"),
+            Some(CHECKSUM_USER_PROPERTY)
+        );
+        assert_eq!(
+            user_property(&response, "__propVal"),
+            Some(PAYLOAD_CHECKSUM)
+        );
+        puback(&mock_server, &response);
+        assert_eq!(mock_server.expect_puback().await.packet_identifier.get(), 1);
+        mock_server.send_publish(request_publish(2, PAYLOAD, PAYLOAD_CHECKSUM));
+    });
+    let request = request.unwrap().unwrap();
+    assert_eq!(request.payload, PAYLOAD);
+    assert!(
+        !request
+            .custom_user_data
+            .iter()
+            .any(|(key, _)| key == CHECKSUM_USER_PROPERTY)
+    );
+
+    let (result, ()) = tokio::join!(
+        request.complete(
+            rpc_command::executor::ResponseBuilder::default()
+                .payload(PAYLOAD.to_vec())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ),
+        async {
+            let response = mock_server.expect_publish().await;
+            assert_eq!(user_property(&response, "__stat"), Some("200"));
+            assert_eq!(
+                user_property(&response, CHECKSUM_USER_PROPERTY),
+                Some(PAYLOAD_CHECKSUM)
+            );
+            puback(&mock_server, &response);
+        }
+    );
+    result.unwrap();
+}
+
+/// Tests that an invoker attaches the checksum of the payload to its requests, and rejects a
+/// corrupted response
+#[tokio::test]
+async fn invoker_payload_checksum() {
+    let (session, mock_server) = setup_client_and_mock_server("invoker_checksum_test_client");
+    let invoker: rpc_command::Invoker<Vec<u8>, Vec<u8>> = rpc_command::Invoker::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        rpc_command::invoker::OptionsBuilder::default()
+            .request_topic_pattern("test/request")
+            .command_name("test_command")
+            .payload_checksum(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, ()) = tokio::join!(
+        invoker.invoke(
+            rpc_command::invoker::RequestBuilder::default()
+                .payload(PAYLOAD.to_vec())
+                .unwrap()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+        ),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            let request = mock_server.expect_publish().await;
+            assert_eq!(
+                user_property(&request, CHECKSUM_USER_PROPERTY),
+                Some(PAYLOAD_CHECKSUM)
+            );
+            puback(&mock_server, &request);
+            mock_server.send_publish(mqtt_proto::Publish {
+                topic_name: request.other_properties.response_topic.unwrap(),
+                packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                    mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                    false,
+                ),
+                retain: false,
+                payload: Bytes::from_static(CORRUPTED_PAYLOAD),
+                other_properties: mqtt_proto::PublishOtherProperties {
+                    correlation_data: request.other_properties.correlation_data,
+                    content_type: Some("application/octet-stream".into()),
+                    user_properties: vec![
+                        ("__stat".into(), "200".into()),
+                        ("__protVer".into(), "1.0".into()),
+                        (CHECKSUM_USER_PROPERTY.into(), PAYLOAD_CHECKSUM.into()),
+                    ],
+                    ..Default::default()
+                },
+            });
+            mock_server.expect_puback().await;
+        }
+    );
+    let error = result.unwrap_err();
+    assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+    let integrity_error = error
+        .nested_error
+        .as_ref()
+        .unwrap()
+        .downcast_ref::<IntegrityCheckFailed>()
+        .unwrap();
+    assert_eq!(integrity_error.expected, PAYLOAD_CHECKSUM);
+    assert_eq!(integrity_error.payload.as_ref(), CORRUPTED_PAYLOAD);
+}
@@ -0,0 +1,103 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+    rpc_command, telemetry,
+};
+
+const TOPIC: &str = "test/subscribe/qos";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Expects a SUBSCRIBE and responds granting QoS 0, regardless of the requested QoS
+async fn expect_subscribe_and_grant_qos0(mock_server: &MockServer) {
+    let subscribe = mock_server.expect_subscribe().await;
+    mock_server.send_suback(mqtt_proto::SubAck {
+        packet_identifier: subscribe.packet_identifier,
+        reason_codes: vec![mqtt_proto::SubscribeReasonCode::GrantedQoS0],
+        other_properties: mqtt_proto::SubAckOtherProperties::default(),
+    });
+}
+
+/// Tests that a command executor refuses to operate when its subscription is downgraded to QoS 0
+#[tokio::test]
+async fn executor_subscription_downgraded() {
+    let (session, mock_server) = setup_client_and_mock_server("executor_downgrade_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(TOPIC)
+            .command_name("test_command")
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let (result, ()) = tokio::join!(
+        executor.recv(),
+        expect_subscribe_and_grant_qos0(&mock_server)
+    );
+    let Some(Err(error)) = result else {
+        panic!("Expected subscribe error");
+    };
+    assert_eq!(error.kind, AIOProtocolErrorKind::ClientError);
+}
+
+/// Tests that a telemetry receiver refuses to operate when its subscription is downgraded to QoS 0
+#[tokio::test]
+async fn telemetry_receiver_subscription_downgraded() {
+    let (session, mock_server) = setup_client_and_mock_server("receiver_downgrade_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let (result, ()) = tokio::join!(
+        receiver.recv(),
+        expect_subscribe_and_grant_qos0(&mock_server)
+    );
+    let Some(Err(error)) = result else {
+        panic!("Expected subscribe error");
+    };
+    assert_eq!(error.kind, AIOProtocolErrorKind::ClientError);
+}
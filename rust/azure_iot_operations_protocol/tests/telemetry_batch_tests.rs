@@ -0,0 +1,302 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::payload_serialize::{FormatIndicator, PayloadSerialize},
+    telemetry::{self, batch},
+};
+
+const TOPIC: &str = "test/telemetry/batch";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn create_sender(session: &Session, max_packet_size: Option<u32>) -> telemetry::Sender<Vec<u8>> {
+    let mut options = telemetry::sender::OptionsBuilder::default();
+    options.topic_pattern(TOPIC);
+    if let Some(max_packet_size) = max_packet_size {
+        options.max_packet_size(max_packet_size);
+    }
+    telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        options.build().unwrap(),
+    )
+    .unwrap()
+}
+
+fn message(payload: Vec<u8>, index: usize) -> telemetry::sender::Message<Vec<u8>> {
+    telemetry::sender::MessageBuilder::default()
+        .payload(payload)
+        .unwrap()
+        .custom_user_data(vec![("index".to_string(), index.to_string())])
+        .build()
+        .unwrap()
+}
+
+/// Expects a QoS 1 publish and acknowledges it with `reason_code`
+async fn expect_publish_and_ack(
+    mock_server: &MockServer,
+    reason_code: mqtt_proto::PubAckReasonCode,
+) -> mqtt_proto::Publish<bytes::Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback_with_reason(packet_identifier, reason_code);
+    } else {
+        panic!("Expected QoS 1 telemetry publish");
+    }
+    publish
+}
+
+/// Tests that a batch is sent as a single publish, and that the receiver unbatches its messages
+/// with their custom user data and cloud event headers
+#[tokio::test]
+async fn batch_loop_back() {
+    let (session, mock_server) = setup_client_and_mock_server("batch_loop_back_test_client");
+    let sender = create_sender(&session, None);
+    let mut receiver: telemetry::Receiver<batch::Batch> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .auto_ack(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let cloud_event = telemetry::sender::CloudEventBuilder::default()
+        .source("aio://test/batch")
+        .build()
+        .unwrap();
+    let messages = vec![
+        message(vec![1], 0),
+        telemetry::sender::MessageBuilder::default()
+            .payload(vec![2, 2])
+            .unwrap()
+            .custom_user_data(vec![("index".to_string(), "1".to_string())])
+            .cloud_event(cloud_event)
+            .priority(7)
+            .build()
+            .unwrap(),
+        message(vec![3, 3, 3], 2),
+    ];
+    let (results, publish) = tokio::join!(
+        sender.send_batch(messages),
+        expect_publish_and_ack(&mock_server, mqtt_proto::PubAckReasonCode::Success)
+    );
+    let results = results.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].message_count, 3);
+    assert!(results[0].result.is_ok());
+    assert_eq!(
+        publish
+            .other_properties
+            .content_type
+            .as_ref()
+            .map(ToString::to_string)
+            .as_deref(),
+        Some(batch::BATCH_CONTENT_TYPE)
+    );
+
+    // Deliver the sent batch back to the receiver
+    let (message, ()) = tokio::join!(
+        async {
+            let (message, _) = receiver.recv().await.unwrap().unwrap();
+            message
+        },
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(publish);
+        }
+    );
+
+    let messages = message.payload.unbatch::<Vec<u8>>().unwrap();
+    assert_eq!(messages.len(), 3);
+    for (index, message) in messages.iter().enumerate() {
+        assert_eq!(
+            message.payload,
+            vec![u8::try_from(index + 1).unwrap(); index + 1]
+        );
+        assert_eq!(
+            message.custom_user_data[0],
+            ("index".to_string(), index.to_string())
+        );
+        assert!(message.timestamp.is_some());
+    }
+    assert_eq!(messages[1].priority, Some(7));
+    let cloud_event = batch::cloud_event_from_batched_telemetry(&messages[1]).unwrap();
+    assert_eq!(cloud_event.source, "aio://test/batch");
+    assert_eq!(
+        cloud_event.data_content_type.as_deref(),
+        Some("application/octet-stream")
+    );
+    assert!(batch::cloud_event_from_batched_telemetry(&messages[0]).is_err());
+}
+
+/// Tests that a batch exceeding the maximum packet size is split into several publishes, each
+/// reporting its own result
+#[tokio::test]
+async fn batch_split_by_max_packet_size() {
+    const MAX_PACKET_SIZE: u32 = 1400;
+    let (session, mock_server) = setup_client_and_mock_server("batch_split_test_client");
+    let sender = create_sender(&session, Some(MAX_PACKET_SIZE));
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    // Each message takes about 340 bytes once encoded, so only two fit per publish
+    let messages = (0..5).map(|i| message(vec![0; 150], i)).collect();
+    let (results, publishes) = tokio::join!(sender.send_batch(messages), async {
+        let mut publishes = Vec::new();
+        publishes.push(
+            expect_publish_and_ack(&mock_server, mqtt_proto::PubAckReasonCode::Success).await,
+        );
+        publishes.push(
+            expect_publish_and_ack(&mock_server, mqtt_proto::PubAckReasonCode::QuotaExceeded).await,
+        );
+        publishes.push(
+            expect_publish_and_ack(&mock_server, mqtt_proto::PubAckReasonCode::Success).await,
+        );
+        publishes
+    });
+    let results = results.unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].result.is_ok());
+    assert!(results[1].result.is_err());
+    assert!(results[2].result.is_ok());
+    assert_eq!(results.iter().map(|r| r.message_count).sum::<usize>(), 5);
+
+    let mut index = 0;
+    for (publish, result) in publishes.iter().zip(&results) {
+        assert!(publish.payload.len() < MAX_PACKET_SIZE as usize);
+        let batch = batch::Batch::deserialize(
+            &publish.payload,
+            publish
+                .other_properties
+                .content_type
+                .as_ref()
+                .map(ToString::to_string)
+                .as_ref(),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        )
+        .unwrap();
+        assert_eq!(batch.len(), result.message_count);
+        for message in batch.unbatch::<Vec<u8>>().unwrap() {
+            assert_eq!(
+                message.custom_user_data[0],
+                ("index".to_string(), index.to_string())
+            );
+            index += 1;
+        }
+    }
+}
+
+/// Tests that nothing is published if a message of the batch can't be batched
+#[tokio::test]
+async fn batch_rejected_without_publishing() {
+    let (session, mock_server) = setup_client_and_mock_server("batch_rejected_test_client");
+    let sender = create_sender(&session, Some(1024));
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let retained = telemetry::sender::MessageBuilder::default()
+        .payload(vec![1])
+        .unwrap()
+        .retain(true)
+        .build()
+        .unwrap();
+    assert!(
+        sender
+            .send_batch(vec![message(vec![1], 0), retained])
+            .await
+            .is_err()
+    );
+
+    let too_large = message(vec![0; 2048], 1);
+    assert!(
+        sender
+            .send_batch(vec![message(vec![1], 0), too_large])
+            .await
+            .is_err()
+    );
+    mock_server.expect_no_packet();
+}
+
+/// Tests that a batching sender sends the messages accumulated up to its maximum number of
+/// messages as one batch, and the messages left after the maximum latency as another
+#[tokio::test]
+async fn batching_sender() {
+    let (session, mock_server) = setup_client_and_mock_server("batching_sender_test_client");
+    let sender = create_sender(&session, None);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let batching_sender = batch::BatchingSender::new(
+        sender,
+        batch::BatchingOptionsBuilder::default()
+            .max_messages(3usize)
+            .max_latency(Duration::from_millis(200))
+            .build()
+            .unwrap(),
+    );
+    let tokens = (0..4)
+        .map(|i| batching_sender.send(message(vec![1], i)).unwrap())
+        .collect::<Vec<_>>();
+
+    let first = expect_publish_and_ack(&mock_server, mqtt_proto::PubAckReasonCode::Success).await;
+    let second = expect_publish_and_ack(
+        &mock_server,
+        mqtt_proto::PubAckReasonCode::NoMatchingSubscribers,
+    )
+    .await;
+    let mut results = Vec::new();
+    for token in tokens {
+        results.push(token.await.unwrap());
+    }
+    assert!(results[..3].iter().all(|r| !r.no_matching_subscribers()));
+    assert!(results[3].no_matching_subscribers());
+
+    for (publish, message_count) in [(first, 3), (second, 1)] {
+        let batch = batch::Batch::deserialize(
+            &publish.payload,
+            Some(&batch::BATCH_CONTENT_TYPE.to_string()),
+            &FormatIndicator::Utf8EncodedCharacterData,
+        )
+        .unwrap();
+        assert_eq!(batch.len(), message_count);
+    }
+
+    batching_sender.shutdown().await;
+    mock_server.expect_no_packet();
+}
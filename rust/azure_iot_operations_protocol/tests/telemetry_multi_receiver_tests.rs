@@ -0,0 +1,268 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::collections::HashMap;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::payload_serialize::{
+        DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
+    },
+    telemetry::{self, receiver::Message},
+};
+use bytes::Bytes;
+
+const DATA_PATTERN: &str = "sensor/{sensorId}/data";
+const ALERT_PATTERN: &str = "site/{siteId}/alerts";
+
+/// Alert payload, a UTF-8 string with its own content type
+#[derive(Clone, Debug, PartialEq)]
+struct Alert(String);
+
+impl PayloadSerialize for Alert {
+    type Error = String;
+
+    fn serialize(self) -> Result<SerializedPayload, String> {
+        Ok(SerializedPayload {
+            payload: self.0.into_bytes(),
+            content_type: "text/plain".to_string(),
+            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
+        })
+    }
+
+    fn deserialize(
+        payload: &[u8],
+        content_type: Option<&String>,
+        _format_indicator: &FormatIndicator,
+    ) -> Result<Self, DeserializationError<String>> {
+        if content_type.is_some_and(|content_type| content_type != "text/plain") {
+            return Err(DeserializationError::UnsupportedContentType(format!(
+                "{content_type:?}"
+            )));
+        }
+        String::from_utf8(payload.to_vec())
+            .map(Alert)
+            .map_err(|e| DeserializationError::InvalidPayload(e.to_string()))
+    }
+}
+
+enum SiteEvent {
+    Data(Message<Vec<u8>>),
+    Alert(Message<Alert>),
+}
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn multi_receiver(session: &Session) -> telemetry::MultiReceiver<SiteEvent> {
+    telemetry::MultiReceiverBuilder::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+    )
+    .pattern(
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(DATA_PATTERN)
+            .build()
+            .unwrap(),
+        SiteEvent::Data,
+    )
+    .unwrap()
+    .pattern(
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(ALERT_PATTERN)
+            .build()
+            .unwrap(),
+        SiteEvent::Alert,
+    )
+    .unwrap()
+    .build()
+    .unwrap()
+}
+
+fn sender<T: PayloadSerialize + Send + Sync + 'static>(
+    session: &Session,
+    topic: &str,
+) -> telemetry::Sender<T> {
+    telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(topic)
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+/// Sends a telemetry message and returns its publish, acked by the mock server
+async fn send<T: PayloadSerialize + Send + Sync + 'static>(
+    sender: &telemetry::Sender<T>,
+    mock_server: &MockServer,
+    payload: T,
+) -> mqtt_proto::Publish<Bytes> {
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(payload)
+        .unwrap()
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), async {
+        let publish = mock_server.expect_publish().await;
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        } else {
+            panic!("Expected QoS 1 telemetry publish");
+        }
+        publish
+    });
+    result.unwrap();
+    publish
+}
+
+/// Tests that messages for two topic patterns with different payload types are received through
+/// a single receiver, and that both topic patterns are subscribed and unsubscribed together
+#[tokio::test]
+async fn interleaved_patterns() {
+    let (session, mock_server) = setup_client_and_mock_server("multi_receiver_test_client");
+    let mut receiver = multi_receiver(&session);
+    let data_sender = sender::<Vec<u8>>(&session, "sensor/pump-7/data");
+    let alert_sender = sender::<Alert>(&session, "site/plant-3/alerts");
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let data_1 = send(&data_sender, &mock_server, vec![1]).await;
+    let alert = send(&alert_sender, &mock_server, Alert("overheat".to_string())).await;
+    let data_2 = send(&data_sender, &mock_server, vec![2]).await;
+
+    // Both topic patterns are subscribed to on the first receive
+    let (first, ()) = tokio::join!(async { receiver.recv().await.unwrap().unwrap() }, async {
+        let subscribe = mock_server.expect_subscribe().await;
+        assert_eq!(
+            subscribe.subscribe_to[0].topic_filter.as_str(),
+            "sensor/+/data"
+        );
+        mock_server.accept_subscribe(&subscribe);
+        let subscribe = mock_server.expect_subscribe().await;
+        assert_eq!(
+            subscribe.subscribe_to[0].topic_filter.as_str(),
+            "site/+/alerts"
+        );
+        mock_server.accept_subscribe(&subscribe);
+        mock_server.send_publish(data_1);
+        mock_server.send_publish(alert);
+        mock_server.send_publish(data_2);
+    });
+    let second = receiver.recv().await.unwrap().unwrap();
+    let third = receiver.recv().await.unwrap().unwrap();
+
+    let mut data_payloads = vec![];
+    let mut alerts = vec![];
+    for (event, _) in [first, second, third] {
+        match event {
+            SiteEvent::Data(message) => {
+                assert_eq!(
+                    message.topic_tokens,
+                    HashMap::from([("sensorId".to_string(), "pump-7".to_string())])
+                );
+                data_payloads.push(message.payload);
+            }
+            SiteEvent::Alert(message) => {
+                assert_eq!(
+                    message.topic_tokens,
+                    HashMap::from([("siteId".to_string(), "plant-3".to_string())])
+                );
+                alerts.push(message.payload);
+            }
+        }
+    }
+    assert_eq!(data_payloads, vec![vec![1], vec![2]]);
+    assert_eq!(alerts, vec![Alert("overheat".to_string())]);
+
+    // The messages were acknowledged as they were received
+    for _ in 0..3 {
+        mock_server.expect_puback().await;
+    }
+
+    // Both topic patterns are unsubscribed from on shutdown
+    let (result, ()) = tokio::join!(receiver.shutdown(), async {
+        let unsubscribe = mock_server.expect_unsubscribe_and_accept().await;
+        assert_eq!(unsubscribe.unsubscribe_from[0].as_str(), "sensor/+/data");
+        let unsubscribe = mock_server.expect_unsubscribe_and_accept().await;
+        assert_eq!(unsubscribe.unsubscribe_from[0].as_str(), "site/+/alerts");
+    });
+    result.unwrap();
+}
+
+/// Tests that a failed subscribe identifies the topic pattern it failed for
+#[tokio::test]
+async fn subscribe_failure_identifies_pattern() {
+    let (session, mock_server) =
+        setup_client_and_mock_server("multi_receiver_subscribe_failure_test_client");
+    let mut receiver = multi_receiver(&session);
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, ()) = tokio::join!(receiver.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        let subscribe = mock_server.expect_subscribe().await;
+        mock_server.send_suback(mqtt_proto::SubAck {
+            packet_identifier: subscribe.packet_identifier,
+            reason_codes: vec![mqtt_proto::SubscribeReasonCode::NotAuthorized],
+            other_properties: mqtt_proto::SubAckOtherProperties::default(),
+        });
+    });
+    let Some(Err(e)) = result else {
+        panic!("Expected subscribe failure");
+    };
+    assert_eq!(e.pattern_index, 1);
+    assert_eq!(e.topic_filter, "site/+/alerts");
+
+    // The failed topic pattern is subscribed to again on the next receive
+    let subscribe = tokio::select! {
+        _ = receiver.recv() => panic!("No message was sent"),
+        subscribe = mock_server.expect_subscribe() => subscribe,
+    };
+    assert_eq!(
+        subscribe.subscribe_to[0].topic_filter.as_str(),
+        "site/+/alerts"
+    );
+}
+
+/// Tests that a multi receiver needs at least one topic pattern
+#[test]
+fn no_patterns() {
+    let (session, _) = setup_client_and_mock_server("multi_receiver_no_patterns_test_client");
+    assert!(
+        telemetry::MultiReceiverBuilder::<SiteEvent>::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+        )
+        .build()
+        .is_err()
+    );
+}
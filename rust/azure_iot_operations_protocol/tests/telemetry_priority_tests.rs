@@ -0,0 +1,145 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    telemetry::{self, MAX_TELEMETRY_PRIORITY},
+};
+use bytes::Bytes;
+
+const TOPIC: &str = "test/telemetry/priority";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+fn telemetry_publish(packet_identifier: u16, priority: Option<u8>) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(TOPIC),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: Bytes::from(vec![u8::try_from(packet_identifier).unwrap()]),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            user_properties: priority
+                .map(|p| vec![("__pri".into(), p.to_string().as_str().into())])
+                .unwrap_or_default(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Tests that telemetry buffered while the consumer is paused is delivered highest priority first,
+/// and that acks are still sent in the order the messages were received
+#[tokio::test]
+async fn prioritized_receiver_delivery_and_ack_order() {
+    let (session, mock_server) = setup_client_and_mock_server("prioritized_receiver_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = ApplicationContextBuilder::default()
+        .build()
+        .unwrap()
+        .telemetry_receiver(
+            managed_client,
+            telemetry::receiver::OptionsBuilder::default()
+                .topic_pattern(TOPIC)
+                .auto_ack(false)
+                .prioritized(true)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+    // Enqueue low then high priority telemetry before the consumer receives anything
+    mock_server.send_publish(telemetry_publish(1, Some(0)));
+    mock_server.send_publish(telemetry_publish(2, None));
+    mock_server.send_publish(telemetry_publish(3, Some(MAX_TELEMETRY_PRIORITY)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The high priority message is delivered first, then the default priority message,
+    // then the low priority message
+    let (first, ()) = tokio::join!(receiver.recv(), mock_server.expect_subscribe_and_accept());
+    let (first, first_ack) = first.unwrap().unwrap();
+    assert_eq!(first.payload, vec![3]);
+    assert_eq!(first.priority, Some(MAX_TELEMETRY_PRIORITY));
+
+    let (second, second_ack) = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(second.payload, vec![2]);
+    assert_eq!(second.priority, None);
+
+    let (third, third_ack) = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(third.payload, vec![1]);
+    assert_eq!(third.priority, Some(0));
+
+    // Acking the last received message does not send its puback before the earlier ones
+    first_ack.unwrap().ack().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    mock_server.expect_no_packet();
+
+    third_ack.unwrap().ack().await.unwrap();
+    assert_eq!(mock_server.expect_puback().await.packet_identifier, 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    mock_server.expect_no_packet();
+
+    second_ack.unwrap().ack().await.unwrap();
+    assert_eq!(mock_server.expect_puback().await.packet_identifier, 2);
+    assert_eq!(mock_server.expect_puback().await.packet_identifier, 3);
+}
+
+/// Tests that a receiver that is not prioritized delivers telemetry in the order it was received
+#[tokio::test]
+async fn unprioritized_receiver_delivery_order() {
+    let (session, mock_server) = setup_client_and_mock_server("unprioritized_receiver_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = ApplicationContextBuilder::default()
+        .build()
+        .unwrap()
+        .telemetry_receiver(
+            managed_client,
+            telemetry::receiver::OptionsBuilder::default()
+                .topic_pattern(TOPIC)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+    mock_server.send_publish(telemetry_publish(1, Some(0)));
+    mock_server.send_publish(telemetry_publish(2, Some(MAX_TELEMETRY_PRIORITY)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (first, ()) = tokio::join!(receiver.recv(), mock_server.expect_subscribe_and_accept());
+    assert_eq!(first.unwrap().unwrap().0.payload, vec![1]);
+    assert_eq!(receiver.recv().await.unwrap().unwrap().0.payload, vec![2]);
+}
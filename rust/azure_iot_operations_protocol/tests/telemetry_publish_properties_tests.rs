@@ -0,0 +1,97 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, telemetry};
+use bytes::Bytes;
+
+const TOPIC: &str = "test/telemetry/properties";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Tests that the publish properties of a received telemetry message are surfaced on the message
+#[tokio::test]
+async fn received_publish_properties() {
+    let (session, mock_server) = setup_client_and_mock_server("properties_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let (message, ()) = tokio::join!(
+        async {
+            let (message, _) = receiver.recv().await.unwrap().unwrap();
+            message
+        },
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(mqtt_proto::Publish {
+                topic_name: mqtt_proto::topic(TOPIC),
+                packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                    mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                    false,
+                ),
+                retain: false,
+                payload: Bytes::from_static(&[1, 2, 3]),
+                other_properties: mqtt_proto::PublishOtherProperties {
+                    topic_alias: Some(2.try_into().unwrap()),
+                    response_topic: Some(mqtt_proto::topic("test/telemetry/reply")),
+                    correlation_data: Some([4u8; 4].as_slice().into()),
+                    subscription_identifiers: vec![9.try_into().unwrap()],
+                    content_type: Some("application/octet-stream".into()),
+                    ..Default::default()
+                },
+            });
+        }
+    );
+    mock_server.expect_puback().await;
+
+    assert_eq!(message.payload, vec![1, 2, 3]);
+    assert_eq!(
+        message.content_type.as_deref(),
+        Some("application/octet-stream")
+    );
+    assert_eq!(
+        message.response_topic.as_deref(),
+        Some("test/telemetry/reply")
+    );
+    assert_eq!(
+        message.correlation_data,
+        Some(Bytes::from_static(&[4u8; 4]))
+    );
+    assert_eq!(message.subscription_identifiers, vec![9]);
+    assert_eq!(message.topic_alias, Some(2));
+}
@@ -0,0 +1,149 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    telemetry::{self, receiver::RedeliveryPolicy},
+};
+use bytes::Bytes;
+use test_case::test_case;
+
+const TOPIC: &str = "test/telemetry/redelivery";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Sends a telemetry message and returns its publish, acked by the mock server
+async fn send(
+    sender: &telemetry::Sender<Vec<u8>>,
+    mock_server: &MockServer,
+    payload: Vec<u8>,
+) -> mqtt_proto::Publish<Bytes> {
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(payload)
+        .unwrap()
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), async {
+        let publish = mock_server.expect_publish().await;
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        } else {
+            panic!("Expected QoS 1 telemetry publish");
+        }
+        publish
+    });
+    result.unwrap();
+    publish
+}
+
+fn with_packet_identifier(
+    mut publish: mqtt_proto::Publish<Bytes>,
+    packet_identifier: u16,
+    dup: bool,
+) -> mqtt_proto::Publish<Bytes> {
+    publish.packet_identifier_dup_qos = mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+        mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+        dup,
+    );
+    publish
+}
+
+/// Tests that a message redelivered by the broker is handled according to the receiver's
+/// redelivery policy, and that it is acknowledged either way
+#[test_case(RedeliveryPolicy::Deliver; "deliver")]
+#[test_case(RedeliveryPolicy::DeliverFlagged; "deliver_flagged")]
+#[test_case(RedeliveryPolicy::AutoAckAndDrop; "auto_ack_and_drop")]
+#[tokio::test]
+async fn redelivered_message(policy: RedeliveryPolicy) {
+    let (session, mock_server) = setup_client_and_mock_server("redelivery_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client.clone(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .on_redelivery(policy)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let sender: telemetry::Sender<Vec<u8>> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let first = send(&sender, &mock_server, vec![1]).await;
+    let redelivered = send(&sender, &mock_server, vec![2]).await;
+    let last = send(&sender, &mock_server, vec![3]).await;
+
+    // Subscribe and receive a first delivery
+    let (message, ()) = tokio::join!(
+        async {
+            let (message, _) = receiver.recv().await.unwrap().unwrap();
+            message
+        },
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(with_packet_identifier(first, 1, false));
+        }
+    );
+    mock_server.expect_puback().await;
+    assert_eq!(message.payload, vec![1]);
+    assert_eq!(message.duplicate, Some(false));
+
+    // Redeliver a message, followed by a first delivery
+    mock_server.send_publish(with_packet_identifier(redelivered, 2, true));
+    mock_server.send_publish(with_packet_identifier(last, 3, false));
+
+    if policy != RedeliveryPolicy::AutoAckAndDrop {
+        let (message, _) = receiver.recv().await.unwrap().unwrap();
+        assert_eq!(message.payload, vec![2]);
+        assert_eq!(
+            message.duplicate,
+            Some(policy == RedeliveryPolicy::DeliverFlagged)
+        );
+    }
+    let (message, _) = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(message.payload, vec![3]);
+    assert_eq!(message.duplicate, Some(false));
+
+    let puback = mock_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 2);
+    let puback = mock_server.expect_puback().await;
+    assert_eq!(puback.packet_identifier.get(), 3);
+}
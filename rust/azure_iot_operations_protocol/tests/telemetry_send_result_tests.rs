@@ -0,0 +1,113 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    control_packet::QoS,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    telemetry::{self, SendWarning},
+};
+use test_case::test_case;
+
+const TOPIC: &str = "test/telemetry/send_result";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// Tests that the PUBACK reason code of a QoS 1 telemetry message is surfaced on the send result
+#[test_case(mqtt_proto::PubAckReasonCode::Success, None; "success")]
+#[test_case(mqtt_proto::PubAckReasonCode::NoMatchingSubscribers, Some(SendWarning::NoMatchingSubscribers); "no_matching_subscribers")]
+#[tokio::test]
+async fn qos1_send_result(
+    reason_code: mqtt_proto::PubAckReasonCode,
+    expected_warning: Option<SendWarning>,
+) {
+    let (session, mock_server) = setup_client_and_mock_server("send_result_qos1_test_client");
+    let sender: telemetry::Sender<Vec<u8>> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(vec![1])
+        .unwrap()
+        .build()
+        .unwrap();
+    let (result, ()) = tokio::join!(sender.send_with_result(message), async {
+        let publish = mock_server.expect_publish().await;
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback_with_reason(packet_identifier, reason_code);
+        } else {
+            panic!("Expected QoS 1 telemetry publish");
+        }
+    });
+
+    let send_result = result.unwrap();
+    assert_eq!(send_result.warning, expected_warning);
+    assert_eq!(
+        send_result.no_matching_subscribers(),
+        expected_warning.is_some()
+    );
+}
+
+/// Tests that a QoS 0 telemetry message, which isn't acknowledged, has no warning
+#[tokio::test]
+async fn qos0_send_result() {
+    let (session, mock_server) = setup_client_and_mock_server("send_result_qos0_test_client");
+    let sender: telemetry::Sender<Vec<u8>> = telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(vec![1])
+        .unwrap()
+        .qos(QoS::AtMostOnce)
+        .build()
+        .unwrap();
+    let (result, _) = tokio::join!(
+        sender.send_with_result(message),
+        mock_server.expect_publish()
+    );
+
+    assert_eq!(result.unwrap().warning, None);
+}
@@ -0,0 +1,246 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionManagedClient, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    telemetry::{
+        self,
+        sender::{SequenceNumberOptionsBuilder, SequenceNumberStore},
+    },
+};
+use bytes::Bytes;
+
+const TOPIC: &str = "test/telemetry/sequence";
+const SEQUENCE_NUMBER_USER_PROPERTY: &str = "__seq";
+const SENDER_INSTANCE_ID_USER_PROPERTY: &str = "__seqInst";
+
+fn setup_client_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    (session, mock_server)
+}
+
+/// In-memory [`SequenceNumberStore`] standing in for the State Store
+#[derive(Default)]
+struct MemorySequenceNumberStore(std::sync::Mutex<HashMap<String, (String, u64)>>);
+
+#[async_trait::async_trait]
+impl SequenceNumberStore for MemorySequenceNumberStore {
+    async fn load(
+        &self,
+        key: String,
+    ) -> Result<Option<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.0.lock().unwrap().get(&key).cloned())
+    }
+
+    async fn save(
+        &self,
+        key: String,
+        sender_instance_id: String,
+        sequence_number: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(key, (sender_instance_id, sequence_number));
+        Ok(())
+    }
+}
+
+fn create_sender(
+    managed_client: SessionManagedClient,
+    store: Option<Arc<MemorySequenceNumberStore>>,
+) -> telemetry::Sender<Vec<u8>> {
+    let mut sequence_number_options = SequenceNumberOptionsBuilder::default();
+    if let Some(store) = store {
+        sequence_number_options.store(store);
+    }
+    telemetry::Sender::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client,
+        telemetry::sender::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .sequence_numbers(sequence_number_options.build().unwrap())
+            .build()
+            .unwrap(),
+    )
+    .unwrap()
+}
+
+fn user_property<'a>(publish: &'a mqtt_proto::Publish<Bytes>, key: &str) -> &'a str {
+    publish
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(k, _)| k.as_ref() == key)
+        .map(|(_, v)| v.as_ref())
+        .unwrap()
+}
+
+/// Sends a telemetry message, acks its publish, and returns the publish
+async fn send(
+    sender: &telemetry::Sender<Vec<u8>>,
+    mock_server: &MockServer,
+) -> mqtt_proto::Publish<Bytes> {
+    let message = telemetry::sender::MessageBuilder::default()
+        .payload(vec![1, 2, 3])
+        .unwrap()
+        .build()
+        .unwrap();
+    let (result, publish) = tokio::join!(sender.send(message), async {
+        let publish = mock_server.expect_publish().await;
+        if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+            publish.packet_identifier_dup_qos
+        {
+            mock_server.send_puback(packet_identifier);
+        } else {
+            panic!("Expected QoS 1 telemetry publish");
+        }
+        publish
+    });
+    result.unwrap();
+    publish
+}
+
+/// Delivers a publish sent by a telemetry sender to the receiver and returns the received message
+async fn deliver(
+    receiver: &mut telemetry::Receiver<Vec<u8>>,
+    mock_server: &MockServer,
+    mut publish: mqtt_proto::Publish<Bytes>,
+    packet_identifier: u16,
+) -> telemetry::receiver::Message<Vec<u8>> {
+    publish.packet_identifier_dup_qos = mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+        mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+        false,
+    );
+    mock_server.send_publish(publish);
+    let (message, ack_token) = receiver.recv().await.unwrap().unwrap();
+    assert!(ack_token.is_none());
+    mock_server.expect_puback().await;
+    message
+}
+
+/// Tests that a dropped telemetry message is detected by the receiver, and that a sender
+/// restarting its numbering as a new sender instance is not reported as a gap
+#[tokio::test]
+async fn dropped_message_and_sender_restart() {
+    let (session, mock_server) = setup_client_and_mock_server("sequence_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let mut receiver: telemetry::Receiver<Vec<u8>> = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        managed_client.clone(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TOPIC)
+            .detect_sequence_gaps(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let sender = create_sender(managed_client.clone(), None);
+
+    let publishes = [
+        send(&sender, &mock_server).await,
+        send(&sender, &mock_server).await,
+        send(&sender, &mock_server).await,
+    ];
+    for (publish, expected) in publishes.iter().zip(["0", "1", "2"]) {
+        assert_eq!(
+            user_property(publish, SEQUENCE_NUMBER_USER_PROPERTY),
+            expected
+        );
+    }
+    let [first, _dropped, third] = publishes;
+
+    // Subscribe and receive the first message
+    let first_publish = first.clone();
+    let (message, ()) = tokio::join!(
+        async {
+            let (message, _) = receiver.recv().await.unwrap().unwrap();
+            message
+        },
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.send_publish(first_publish);
+        }
+    );
+    mock_server.expect_puback().await;
+    assert_eq!(message.sequence_number, Some(0));
+    assert_eq!(message.gap_detected, None);
+
+    // The second message is never delivered
+    let message = deliver(&mut receiver, &mock_server, third, 2).await;
+    assert_eq!(message.sequence_number, Some(2));
+    assert_eq!(message.gap_detected, Some(1));
+    assert_eq!(receiver.gaps_detected(), 1);
+
+    // A redelivered message is not a gap
+    let message = deliver(&mut receiver, &mock_server, first, 3).await;
+    assert_eq!(message.gap_detected, None);
+
+    // The sender restarts, numbering from zero as a new sender instance
+    let restarted_sender = create_sender(managed_client, None);
+    let publish = send(&restarted_sender, &mock_server).await;
+    assert_eq!(user_property(&publish, SEQUENCE_NUMBER_USER_PROPERTY), "0");
+    let message = deliver(&mut receiver, &mock_server, publish, 4).await;
+    assert_eq!(message.sequence_number, Some(0));
+    assert_ne!(message.sender_instance_id, None);
+    assert_eq!(message.gap_detected, None);
+
+    let publish = send(&restarted_sender, &mock_server).await;
+    let message = deliver(&mut receiver, &mock_server, publish, 5).await;
+    assert_eq!(message.sequence_number, Some(1));
+    assert_eq!(message.gap_detected, None);
+    assert_eq!(receiver.gaps_detected(), 1);
+}
+
+/// Tests that a sender with a sequence number store continues numbering across restarts
+#[tokio::test]
+async fn persisted_sequence_numbers() {
+    let (session, mock_server) = setup_client_and_mock_server("sequence_store_test_client");
+    let managed_client = session.create_managed_client();
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+
+    let store = Arc::new(MemorySequenceNumberStore::default());
+    let sender = create_sender(managed_client.clone(), Some(store.clone()));
+    send(&sender, &mock_server).await;
+    let publish = send(&sender, &mock_server).await;
+    assert_eq!(user_property(&publish, SEQUENCE_NUMBER_USER_PROPERTY), "1");
+    let sender_instance_id = user_property(&publish, SENDER_INSTANCE_ID_USER_PROPERTY).to_string();
+    drop(sender);
+
+    // The restarted sender continues the numbering of the same sender instance
+    let restarted_sender = create_sender(managed_client, Some(store));
+    let publish = send(&restarted_sender, &mock_server).await;
+    assert_eq!(user_property(&publish, SEQUENCE_NUMBER_USER_PROPERTY), "2");
+    assert_eq!(
+        user_property(&publish, SENDER_INSTANCE_ID_USER_PROPERTY),
+        sender_instance_id
+    );
+}
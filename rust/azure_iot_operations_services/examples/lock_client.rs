@@ -10,6 +10,7 @@ use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsB
 use azure_iot_operations_mqtt::session::{Session, SessionExitHandle, SessionOptionsBuilder};
 use azure_iot_operations_protocol::application::ApplicationContextBuilder;
 use azure_iot_operations_services::leased_lock::{SetCondition, SetOptions, lock};
+use azure_iot_operations_services::services_builder::ServicesBuilder;
 use azure_iot_operations_services::state_store;
 
 #[tokio::main(flavor = "current_thread")]
@@ -74,24 +75,11 @@ fn create_clients(
 
     let exit_handle = session.create_exit_handle();
 
-    let state_store_client = state_store::Client::new(
-        application_context,
-        session.create_managed_client(),
-        session.create_session_monitor(),
-        crate::state_store::ClientOptionsBuilder::default()
-            .build()
-            .unwrap(),
-    )
-    .unwrap();
-
-    let state_store_client_arc = Arc::new(state_store_client);
-
-    let lock_client = lock::Client::new(
-        state_store_client_arc.clone(),
-        lock_name.as_bytes().to_vec(),
-        client_id.as_bytes().to_vec(),
-    )
-    .unwrap();
+    // Create the lock client, which shares its State Store Client with any other lock clients
+    // created by the same builder
+    let services = ServicesBuilder::new(application_context, &session);
+    let lock_client = services.leased_lock(lock_name.as_bytes().to_vec()).unwrap();
+    let state_store_client_arc = services.lock_state_store().unwrap();
 
     (session, exit_handle, state_store_client_arc, lock_client)
 }
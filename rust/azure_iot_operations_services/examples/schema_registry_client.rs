@@ -9,6 +9,7 @@ use azure_iot_operations_protocol::application::ApplicationContextBuilder;
 use azure_iot_operations_services::schema_registry::{
     self, Format, GetSchemaRequestBuilder, PutSchemaRequestBuilder, SchemaType,
 };
+use azure_iot_operations_services::services_builder::ServicesBuilder;
 use env_logger::Builder;
 use tokio::sync::oneshot;
 
@@ -53,7 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create a Schema Registry Client
     let schema_registry_client =
-        schema_registry::Client::new(application_context, &session.create_managed_client());
+        ServicesBuilder::new(application_context, &session).schema_registry();
 
     // Run the Session and the Schema Registry operations concurrently
     let r = tokio::join!(
@@ -28,12 +28,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create an ApplicationContext
     let application_context = ApplicationContextBuilder::default().build()?;
 
-    // Create a State Store Client
+    // Create a State Store Client. Requests that get no response within 3 seconds are retried
+    // until the timeout of the operation elapses.
     let state_store_client = state_store::Client::new(
         application_context,
         session.create_managed_client(),
         session.create_session_monitor(),
-        state_store::ClientOptionsBuilder::default().build()?,
+        state_store::ClientOptionsBuilder::default()
+            .attempt_timeout(Duration::from_secs(3))
+            .build()?,
     )?;
 
     // Run the Session and the State Store operations concurrently
@@ -51,6 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 async fn state_store_operations(client: state_store::Client) {
     let state_store_key = b"someKey";
     let state_store_value = b"someValue";
+    // Overall timeout of each operation, including any retries
     let timeout = Duration::from_secs(10);
 
     let observe_response = client
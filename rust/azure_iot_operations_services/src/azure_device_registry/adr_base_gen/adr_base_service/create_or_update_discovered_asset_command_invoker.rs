@@ -170,6 +170,7 @@ impl CreateOrUpdateDiscoveredAssetCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(CreateOrUpdateDiscoveredAssetResponse {
@@ -188,6 +189,7 @@ impl CreateOrUpdateDiscoveredAssetCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -220,6 +222,7 @@ impl CreateOrUpdateDiscoveredAssetCommandInvoker {
             command_name: Some("createOrUpdateDiscoveredAsset".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
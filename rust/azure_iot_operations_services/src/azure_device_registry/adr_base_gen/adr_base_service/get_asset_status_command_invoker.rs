@@ -152,6 +152,7 @@ impl GetAssetStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetAssetStatusResponse {
@@ -166,6 +167,7 @@ impl GetAssetStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -198,6 +200,7 @@ impl GetAssetStatusCommandInvoker {
             command_name: Some("getAssetStatus".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -139,6 +139,7 @@ impl GetDeviceCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetDeviceResponse {
@@ -153,6 +154,7 @@ impl GetDeviceCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -185,6 +187,7 @@ impl GetDeviceCommandInvoker {
             command_name: Some("getDevice".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -142,6 +142,7 @@ impl GetDeviceStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetDeviceStatusResponse {
@@ -156,6 +157,7 @@ impl GetDeviceStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -188,6 +190,7 @@ impl GetDeviceStatusCommandInvoker {
             command_name: Some("getDeviceStatus".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -175,6 +175,7 @@ impl SetNotificationPreferenceForAssetUpdatesCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(SetNotificationPreferenceForAssetUpdatesResponse {
@@ -190,6 +191,7 @@ impl SetNotificationPreferenceForAssetUpdatesCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -222,6 +224,7 @@ impl SetNotificationPreferenceForAssetUpdatesCommandInvoker {
             command_name: Some("setNotificationPreferenceForAssetUpdates".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -176,6 +176,7 @@ impl SetNotificationPreferenceForDeviceUpdatesCommandInvoker {
                             custom_user_data: response.custom_user_data,
                             timestamp: response.timestamp,
                             executor_id: response.executor_id,
+                            timing: response.timing,
                         },
                     ))
                 } else {
@@ -192,6 +193,7 @@ impl SetNotificationPreferenceForDeviceUpdatesCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -224,6 +226,7 @@ impl SetNotificationPreferenceForDeviceUpdatesCommandInvoker {
             command_name: Some("setNotificationPreferenceForDeviceUpdates".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -157,6 +157,7 @@ impl UpdateAssetStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(UpdateAssetStatusResponse {
@@ -170,6 +171,7 @@ impl UpdateAssetStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -202,6 +204,7 @@ impl UpdateAssetStatusCommandInvoker {
             command_name: Some("updateAssetStatus".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -159,6 +159,7 @@ impl UpdateDeviceStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(UpdateDeviceStatusResponse {
@@ -172,6 +173,7 @@ impl UpdateDeviceStatusCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -204,6 +206,7 @@ impl UpdateDeviceStatusCommandInvoker {
             command_name: Some("updateDeviceStatus".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -170,6 +170,7 @@ impl CreateOrUpdateDiscoveredDeviceCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(CreateOrUpdateDiscoveredDeviceResponse {
@@ -186,6 +187,7 @@ impl CreateOrUpdateDiscoveredDeviceCommandInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -218,6 +220,7 @@ impl CreateOrUpdateDiscoveredDeviceCommandInvoker {
             command_name: Some("createOrUpdateDiscoveredDevice".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -79,7 +79,7 @@ pub struct Asset {
 }
 
 /// Represents a Discovered Asset in the Azure Device Registry service.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredAsset {
     /// URIs or type definition IDs for the asset type.
     pub asset_type_refs: Vec<String>, // if empty, we can represent as None on generated model.
@@ -158,7 +158,7 @@ pub struct Dataset {
 }
 
 /// Represents a discovered dataset.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredDataset {
     /// Stringified JSON that contains connector-specific properties that describes configuration for the specific dataset.
     pub dataset_configuration: Option<String>,
@@ -190,7 +190,7 @@ pub struct DatasetDataPoint {
 }
 
 /// Represents a data point in a discovered dataset.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredDatasetDataPoint {
     /// Stringified JSON that contains connector-specific configuration for the data point.
     pub data_point_configuration: Option<String>,
@@ -267,7 +267,7 @@ pub struct EventGroup {
 }
 
 /// Represents an event group in an asset.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredEventGroup {
     /// The address of the notifier of the event in the asset (e.g. URL) so that a client can access the event on the asset.
     pub data_source: Option<String>,
@@ -299,7 +299,7 @@ pub struct Event {
 }
 
 /// Represents an event in a discovered asset.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredEvent {
     /// Reference to a data source for a given event.
     pub data_source: Option<String>,
@@ -335,7 +335,7 @@ pub struct ManagementGroup {
 }
 
 /// Represents a discovered management group
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredManagementGroup {
     /// Array of actions that are part of the management group. Each action can have an individual configuration.
     pub actions: Vec<DiscoveredManagementGroupAction>, // if None on generated model, we can represent as empty vec
@@ -375,7 +375,7 @@ pub struct ManagementGroupAction {
 }
 
 /// Represents a discovered management group action
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredManagementGroupAction {
     /// Configuration for the action.
     pub action_configuration: Option<String>,
@@ -409,7 +409,7 @@ pub struct Stream {
 }
 
 /// Represents a stream for a discovered asset.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DiscoveredStream {
     /// Destinations for a stream.
     pub destinations: Vec<EventStreamDestination>, // if empty we can represent as None on generated model.
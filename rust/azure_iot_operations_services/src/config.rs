@@ -0,0 +1,377 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Client for reading and watching application configuration stored in the State Store.
+//!
+//! Configuration values (feature flags, thresholds, etc.) are stored as JSON in the State Store
+//! and parsed into application types. A watched key provides a default value until the key exists,
+//! is re-parsed every time the key changes, and keeps the last good value if a new value fails to
+//! parse.
+//!
+//! To use this client, the `config` feature must be enabled.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::state_store::{self, Operation, SetOptions};
+
+/// Represents an error that occurred in the Azure IoT Operations Config Client.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct Error(#[from] ErrorKind);
+
+impl Error {
+    /// Returns the [`ErrorKind`] of the error.
+    #[must_use]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0
+    }
+}
+
+impl From<state_store::Error> for Error {
+    fn from(error: state_store::Error) -> Self {
+        ErrorKind::StateStoreError(error).into()
+    }
+}
+
+/// Represents the kinds of errors that occur in the Azure IoT Operations Config Client.
+#[derive(Error, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ErrorKind {
+    /// An error occurred in the State Store. See [`state_store::Error`] for more information.
+    #[error(transparent)]
+    StateStoreError(state_store::Error),
+    /// The value could not be serialized as JSON.
+    #[error("value could not be serialized: {0}")]
+    SerializationError(serde_json::Error),
+    /// The value stored in the State Store could not be parsed into the requested type.
+    #[error("value could not be parsed: {0}")]
+    ParseError(serde_json::Error),
+}
+
+/// Updates a watched value from the current value of its key, if any.
+type Updater = Arc<dyn Fn(Option<&[u8]>) + Send + Sync>;
+
+/// Config client struct.
+#[derive(Clone)]
+pub struct Client {
+    state_store: Arc<state_store::Client>,
+    watched_keys: Arc<Mutex<HashMap<Vec<u8>, Updater>>>,
+}
+
+/// Config client implementation
+///
+/// Notes:
+/// Do not call any of the methods of this client after the `state_store` parameter is shutdown.
+/// Calling any of the methods in this implementation after the `state_store` is shutdown results in undefined behavior.
+impl Client {
+    /// Create a new Config Client.
+    #[must_use]
+    pub fn new(state_store: Arc<state_store::Client>) -> Self {
+        Self {
+            state_store,
+            watched_keys: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Gets the value of a key from the State Store, parsed from JSON into `T`.
+    ///
+    /// Note: `timeout` is rounded up to the nearest second.
+    ///
+    /// Returns `Ok(None)` if the key does not exist.
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Get` request fails
+    ///
+    /// [`struct@Error`] of kind [`ParseError`](ErrorKind::ParseError) if the value can't be parsed into `T`
+    pub async fn get_typed<T: DeserializeOwned>(
+        &self,
+        key: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<Option<T>, Error> {
+        self.state_store
+            .get(key, timeout)
+            .await?
+            .response
+            .map(|value| {
+                serde_json::from_slice(&value).map_err(|e| ErrorKind::ParseError(e).into())
+            })
+            .transpose()
+    }
+
+    /// Sets the value of a key in the State Store to `value` serialized as JSON.
+    ///
+    /// Note: `timeout` is rounded up to the nearest second.
+    ///
+    /// Returns `true` if the key was set.
+    /// # Errors
+    /// [`struct@Error`] of kind [`SerializationError`](ErrorKind::SerializationError) if `value` can't be serialized
+    ///
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Set` request fails
+    pub async fn set_typed<T: Serialize>(
+        &self,
+        key: Vec<u8>,
+        value: &T,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        let value = serde_json::to_vec(value).map_err(ErrorKind::SerializationError)?;
+        Ok(self
+            .state_store
+            .set(key, value, timeout, None, SetOptions::default())
+            .await?
+            .response)
+    }
+
+    /// Watches a key in the State Store, returning a [`watch::Receiver`] that holds the value of the
+    /// key parsed from JSON into `T`.
+    ///
+    /// - The receiver holds `default` until the key exists, and again whenever the key is deleted.
+    /// - The value is re-parsed every time the key changes.
+    /// - If a new value fails to parse, the last good value is kept and the error is logged.
+    ///
+    /// The key is observed until all clones of the returned receiver are dropped.
+    /// Note that key observations don't persist across reconnections, see
+    /// [`state_store::Client::observe`]. Use [`Client::refresh`] to re-read the current value.
+    ///
+    /// Note: `timeout` is rounded up to the nearest second, and is also used to unobserve the key.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Observe` or `Get` requests
+    /// fail, including if the key is already being watched or observed by this client
+    ///
+    /// # Panics
+    /// If the lock on the watched keys is poisoned, which should not be possible.
+    pub async fn watch_key<T>(
+        &self,
+        key: Vec<u8>,
+        default: T,
+        timeout: Duration,
+    ) -> Result<watch::Receiver<T>, Error>
+    where
+        T: DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        // Observe before getting the current value so that no changes are missed
+        let mut observation = self
+            .state_store
+            .observe(key.clone(), timeout)
+            .await?
+            .response;
+
+        let current_value = match self.state_store.get(key.clone(), timeout).await {
+            Ok(response) => response.response,
+            Err(e) => {
+                if let Err(e) = self.state_store.unobserve(key.clone(), timeout).await {
+                    log::warn!(
+                        "Failed to unobserve config key {}: {e}",
+                        String::from_utf8_lossy(&key)
+                    );
+                }
+                return Err(e.into());
+            }
+        };
+
+        let (value_tx, value_rx) = watch::channel(default.clone());
+        let value_tx = Arc::new(value_tx);
+        let updater: Updater = Arc::new({
+            let key = key.clone();
+            let value_tx = value_tx.clone();
+            move |value| update_value(&key, &value_tx, &default, value)
+        });
+        updater(current_value.as_deref());
+        self.watched_keys
+            .lock()
+            .unwrap()
+            .insert(key.clone(), updater.clone());
+
+        tokio::task::spawn({
+            let state_store = self.state_store.clone();
+            let watched_keys = self.watched_keys.clone();
+            async move {
+                let key_name = String::from_utf8_lossy(&key).to_string();
+                loop {
+                    tokio::select! {
+                        () = value_tx.closed() => {
+                            log::debug!("All receivers for config key {key_name} dropped");
+                            break;
+                        }
+                        notification = observation.recv_notification() => {
+                            let Some((notification, _)) = notification else {
+                                log::debug!("Notifications for config key {key_name} ended");
+                                break;
+                            };
+                            match notification.operation {
+                                Operation::Set(value) => updater(Some(&value)),
+                                Operation::Del => updater(None),
+                            }
+                        }
+                    }
+                }
+
+                watched_keys.lock().unwrap().remove(&key);
+                if let Err(e) = state_store.unobserve(key, timeout).await {
+                    log::warn!("Failed to unobserve config key {key_name}: {e}");
+                }
+            }
+        });
+
+        Ok(value_rx)
+    }
+
+    /// Re-reads the current value of a watched key from the State Store and updates its
+    /// [`watch::Receiver`].
+    ///
+    /// Note: `timeout` is rounded up to the nearest second.
+    ///
+    /// Returns `true` if the value was refreshed or `false` if the key isn't being watched.
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Get` request fails
+    ///
+    /// # Panics
+    /// If the lock on the watched keys is poisoned, which should not be possible.
+    pub async fn refresh(&self, key: Vec<u8>, timeout: Duration) -> Result<bool, Error> {
+        let Some(updater) = self.watched_keys.lock().unwrap().get(&key).cloned() else {
+            return Ok(false);
+        };
+        let value = self.state_store.get(key, timeout).await?.response;
+        updater(value.as_deref());
+        Ok(true)
+    }
+}
+
+/// Updates `value_tx` from the current value of `key`.
+///
+/// A missing value reverts to `default`, and a value that fails to parse is ignored so that the
+/// last good value is kept.
+fn update_value<T: DeserializeOwned + Clone>(
+    key: &[u8],
+    value_tx: &watch::Sender<T>,
+    default: &T,
+    value: Option<&[u8]>,
+) {
+    match value {
+        None => {
+            value_tx.send_replace(default.clone());
+        }
+        Some(value) => match serde_json::from_slice(value) {
+            Ok(parsed) => {
+                value_tx.send_replace(parsed);
+            }
+            Err(e) => {
+                log::warn!(
+                    "Config key {} has a value of {} bytes that could not be parsed, keeping the last good value: {e}",
+                    String::from_utf8_lossy(key),
+                    value.len()
+                );
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use tokio::sync::watch;
+
+    use super::update_value;
+
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    struct Thresholds {
+        max_temperature: u32,
+        enabled: bool,
+    }
+
+    const KEY: &[u8] = b"thresholds";
+
+    #[test]
+    fn valid_updates() {
+        let default = Thresholds::default();
+        let (value_tx, value_rx) = watch::channel(default.clone());
+
+        update_value(
+            KEY,
+            &value_tx,
+            &default,
+            Some(br#"{"max_temperature": 80, "enabled": true}"#),
+        );
+        assert_eq!(
+            *value_rx.borrow(),
+            Thresholds {
+                max_temperature: 80,
+                enabled: true
+            }
+        );
+
+        update_value(
+            KEY,
+            &value_tx,
+            &default,
+            Some(br#"{"max_temperature": 90, "enabled": false}"#),
+        );
+        assert_eq!(
+            *value_rx.borrow(),
+            Thresholds {
+                max_temperature: 90,
+                enabled: false
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_value_keeps_last_good_value() {
+        let default = Thresholds::default();
+        let (value_tx, mut value_rx) = watch::channel(default.clone());
+
+        update_value(
+            KEY,
+            &value_tx,
+            &default,
+            Some(br#"{"max_temperature": 80, "enabled": true}"#),
+        );
+        value_rx.mark_unchanged();
+
+        for invalid in [
+            &b"not json"[..],
+            br#"{"max_temperature": "hot", "enabled": true}"#,
+            br#"{"enabled": true}"#,
+        ] {
+            update_value(KEY, &value_tx, &default, Some(invalid));
+            assert!(!value_rx.has_changed().unwrap());
+            assert_eq!(value_rx.borrow().max_temperature, 80);
+        }
+
+        update_value(
+            KEY,
+            &value_tx,
+            &default,
+            Some(br#"{"max_temperature": 70, "enabled": true}"#),
+        );
+        assert!(value_rx.has_changed().unwrap());
+        assert_eq!(value_rx.borrow().max_temperature, 70);
+    }
+
+    #[test]
+    fn deletion_reverts_to_default() {
+        let default = Thresholds {
+            max_temperature: 50,
+            enabled: false,
+        };
+        let (value_tx, value_rx) = watch::channel(default.clone());
+
+        update_value(
+            KEY,
+            &value_tx,
+            &default,
+            Some(br#"{"max_temperature": 80, "enabled": true}"#),
+        );
+        assert_eq!(value_rx.borrow().max_temperature, 80);
+
+        update_value(KEY, &value_tx, &default, None);
+        assert_eq!(*value_rx.borrow(), default);
+    }
+}
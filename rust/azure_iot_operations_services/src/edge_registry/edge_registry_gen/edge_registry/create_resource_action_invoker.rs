@@ -154,6 +154,7 @@ impl CreateResourceActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(CreateResourceResponse {
@@ -166,6 +167,7 @@ impl CreateResourceActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -198,6 +200,7 @@ impl CreateResourceActionInvoker {
             command_name: Some("createResource".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
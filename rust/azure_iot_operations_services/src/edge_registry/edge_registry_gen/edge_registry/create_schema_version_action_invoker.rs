@@ -159,6 +159,7 @@ impl CreateSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(CreateSchemaVersionResponse {
@@ -171,6 +172,7 @@ impl CreateSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -203,6 +205,7 @@ impl CreateSchemaVersionActionInvoker {
             command_name: Some("createSchemaVersion".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -168,6 +168,7 @@ impl CreateThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(CreateThingDescriptionVersionResponse {
@@ -179,6 +180,7 @@ impl CreateThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -211,6 +213,7 @@ impl CreateThingDescriptionVersionActionInvoker {
             command_name: Some("createThingDescriptionVersion".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -158,6 +158,7 @@ impl DeleteSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(DeleteSchemaVersionResponse {
@@ -167,6 +168,7 @@ impl DeleteSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -167,6 +167,7 @@ impl DeleteThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(DeleteThingDescriptionVersionResponse {
@@ -176,6 +177,7 @@ impl DeleteThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
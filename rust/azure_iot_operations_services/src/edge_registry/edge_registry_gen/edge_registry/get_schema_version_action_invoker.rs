@@ -155,6 +155,7 @@ impl GetSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetSchemaVersionResponse {
@@ -167,6 +168,7 @@ impl GetSchemaVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -199,6 +201,7 @@ impl GetSchemaVersionActionInvoker {
             command_name: Some("getSchemaVersion".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
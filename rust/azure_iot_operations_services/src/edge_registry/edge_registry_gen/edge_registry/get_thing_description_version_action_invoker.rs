@@ -164,6 +164,7 @@ impl GetThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetThingDescriptionVersionResponse {
@@ -176,6 +177,7 @@ impl GetThingDescriptionVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -208,6 +210,7 @@ impl GetThingDescriptionVersionActionInvoker {
             command_name: Some("getThingDescriptionVersion".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -160,6 +160,7 @@ impl GetThingModelVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(GetThingModelVersionResponse {
@@ -172,6 +173,7 @@ impl GetThingModelVersionActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -204,6 +206,7 @@ impl GetThingModelVersionActionInvoker {
             command_name: Some("getThingModelVersion".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
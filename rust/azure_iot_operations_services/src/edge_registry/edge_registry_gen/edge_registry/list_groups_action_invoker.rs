@@ -139,6 +139,7 @@ impl ListGroupsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(ListGroupsResponse {
@@ -153,6 +154,7 @@ impl ListGroupsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -185,6 +187,7 @@ impl ListGroupsActionInvoker {
             command_name: Some("listGroups".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -152,6 +152,7 @@ impl ListResourcesActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(ListResourcesResponse {
@@ -164,6 +165,7 @@ impl ListResourcesActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -196,6 +198,7 @@ impl ListResourcesActionInvoker {
             command_name: Some("listResources".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
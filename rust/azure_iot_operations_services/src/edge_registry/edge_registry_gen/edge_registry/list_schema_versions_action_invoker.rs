@@ -157,6 +157,7 @@ impl ListSchemaVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(ListSchemaVersionsResponse {
@@ -169,6 +170,7 @@ impl ListSchemaVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -201,6 +203,7 @@ impl ListSchemaVersionsActionInvoker {
             command_name: Some("listSchemaVersions".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -165,6 +165,7 @@ impl ListThingDescriptionVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(ListThingDescriptionVersionsResponse {
@@ -176,6 +177,7 @@ impl ListThingDescriptionVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -208,6 +210,7 @@ impl ListThingDescriptionVersionsActionInvoker {
             command_name: Some("listThingDescriptionVersions".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
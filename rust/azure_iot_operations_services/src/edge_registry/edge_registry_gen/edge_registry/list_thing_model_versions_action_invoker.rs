@@ -163,6 +163,7 @@ impl ListThingModelVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(ListThingModelVersionsResponse {
@@ -175,6 +176,7 @@ impl ListThingModelVersionsActionInvoker {
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -207,6 +209,7 @@ impl ListThingModelVersionsActionInvoker {
             command_name: Some("listThingModelVersions".to_string()),
             protocol_version: None,
             supported_protocol_major_versions: None,
+            correlation_id: None,
         }
     }
 }
@@ -21,6 +21,8 @@ pub type ServiceError = StateStoreServiceError;
 pub mod lease;
 /// Lock Client implementation
 pub mod lock;
+/// Singleton Guard implementation
+pub mod singleton;
 
 /// Represents an error that occurred in the Azure IoT Operations Lease and Lock implementation.
 #[derive(Debug, Error)]
@@ -49,6 +51,9 @@ pub enum ErrorKind {
     /// The lease is already in use by another holder.
     #[error("lease is already held by another holder")]
     LeaseAlreadyHeld,
+    /// The lease is held by another holder, whose name is provided.
+    #[error("lease is already held by {}", String::from_utf8_lossy(.0))]
+    LeaseHeldBy(Vec<u8>),
     /// An error occurred in the AIO Protocol. See [`AIOProtocolError`] for more information.
     #[error(transparent)]
     AIOProtocolError(#[from] AIOProtocolError),
@@ -80,6 +80,20 @@ impl Client {
         &self,
         lease_expiration: Duration,
         request_timeout: Duration,
+    ) -> Result<HybridLogicalClock, Error> {
+        self.internal_set(
+            lease_expiration,
+            request_timeout,
+            SetCondition::OnlyIfEqualOrDoesNotExist,
+        )
+        .await
+    }
+
+    async fn internal_set(
+        &self,
+        lease_expiration: Duration,
+        request_timeout: Duration,
+        set_condition: SetCondition,
     ) -> Result<HybridLogicalClock, Error> {
         let state_store_response = self
             .state_store
@@ -89,7 +103,7 @@ impl Client {
                 request_timeout,
                 None,
                 SetOptions {
-                    set_condition: SetCondition::OnlyIfEqualOrDoesNotExist,
+                    set_condition,
                     expires: Some(lease_expiration),
                     ..Default::default()
                 },
@@ -227,6 +241,34 @@ impl Client {
         Ok(())
     }
 
+    /// Takes over a lease regardless of its current holder, without auto-renewal.
+    ///
+    /// Only intended for taking over a lease whose holder is known to have stopped renewing it.
+    pub(crate) async fn take_over(
+        &self,
+        lease_expiration: Duration,
+        request_timeout: Duration,
+    ) -> Result<HybridLogicalClock, Error> {
+        self.auto_renewal_notify.notify_waiters();
+
+        self.internal_set(
+            lease_expiration,
+            request_timeout,
+            SetCondition::Unconditional,
+        )
+        .await
+    }
+
+    /// Stops the auto-renewal task (if any) and clears the current fencing token without
+    /// contacting the State Store.
+    ///
+    /// # Panics
+    /// If the lock on the `current_fencing_token` is poisoned, which should not be possible.
+    pub(crate) fn cancel_auto_renewal(&self) {
+        self.auto_renewal_notify.notify_waiters();
+        *self.current_fencing_token.lock().unwrap() = None;
+    }
+
     /// Starts observation of any changes on a lease
     ///
     /// Note: `request_timeout` is rounded up to the nearest second.
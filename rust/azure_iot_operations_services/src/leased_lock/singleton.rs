@@ -0,0 +1,217 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Guard for exclusive (singleton) execution across processes.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::timeout;
+
+use crate::leased_lock::{Error, ErrorKind, LeaseObservation, lease};
+use crate::state_store;
+use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+
+/// Determines how [`SingletonGuard::acquire`] behaves when the lease is held by another holder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WaitPolicy {
+    /// Wait until the current holder releases the lease or the lease expires.
+    #[default]
+    Wait,
+    /// Fail immediately, reporting the current holder.
+    FailFast,
+    /// Wait like [`WaitPolicy::Wait`], but take over the lease if the current holder neither
+    /// renews nor releases it within one lease period.
+    StealAfterExpiry,
+}
+
+/// Guard that ensures only one holder executes under a given name at a time.
+///
+/// While the guard is alive, the lease is renewed in the background. Dropping the guard stops the
+/// renewal and releases the lease on a best effort basis (the release requires a Tokio runtime that
+/// keeps running after the drop). If the process exits without releasing the lease, it expires
+/// once its lease period has elapsed.
+pub struct SingletonGuard {
+    lease_client: lease::Client,
+    request_timeout: Duration,
+    released: bool,
+}
+
+impl SingletonGuard {
+    /// Acquires exclusive execution under `name`, as `holder_name`.
+    ///
+    /// `lease` is how long the lease remains held in the State Store if not renewed, it is renewed
+    /// every half `lease` while the guard is alive.
+    /// `wait_policy` determines the behavior if the lease is currently held by another holder.
+    /// `request_timeout` is the maximum time to wait for each response from the State Store service,
+    /// it is rounded up to the nearest second.
+    ///
+    /// Returns the [`SingletonGuard`] once the lease is held.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if
+    /// - either `name` or `holder_name` is empty
+    /// - `lease` is zero
+    /// - the `request_timeout` is zero or > `u32::max`
+    ///
+    /// [`struct@Error`] of kind [`LeaseHeldBy`](ErrorKind::LeaseHeldBy) if the `wait_policy` is
+    /// [`WaitPolicy::FailFast`] and the lease is held by another holder
+    ///
+    /// [`struct@Error`] of kind [`LeaseAlreadyHeld`](ErrorKind::LeaseAlreadyHeld) if the `wait_policy` is
+    /// [`WaitPolicy::FailFast`] and the lease is held by another holder that could not be determined
+    ///
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if the State Store returns an Error response
+    ///
+    /// [`struct@Error`] of kind [`UnexpectedPayload`](ErrorKind::UnexpectedPayload) if the State Store returns a response that isn't valid for the request
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from the command invoker
+    pub async fn acquire(
+        state_store: Arc<state_store::Client>,
+        name: Vec<u8>,
+        holder_name: Vec<u8>,
+        lease: Duration,
+        wait_policy: WaitPolicy,
+        request_timeout: Duration,
+    ) -> Result<Self, Error> {
+        if lease.is_zero() {
+            return Err(Error(ErrorKind::InvalidArgument(
+                "lease is zero".to_string(),
+            )));
+        }
+
+        let lease_client = lease::Client::new(state_store, name, holder_name)?;
+
+        if wait_policy == WaitPolicy::FailFast {
+            match lease_client.acquire(lease, request_timeout, None).await {
+                Ok(_) => {}
+                Err(e) if matches!(e.kind(), ErrorKind::LeaseAlreadyHeld) => {
+                    return Err(held_error(&lease_client, request_timeout).await);
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            // Observe before the first attempt, so that a release right after it is not missed
+            let mut observation = lease_client.observe(request_timeout).await?;
+            let result = wait_and_acquire(
+                &lease_client,
+                &mut observation,
+                lease,
+                wait_policy,
+                request_timeout,
+            )
+            .await;
+            if let Err(e) = lease_client.unobserve(request_timeout).await {
+                log::warn!("Failed to stop observing singleton lease: {e}");
+            }
+            result?;
+        }
+
+        // The lease is held, start renewing it
+        lease_client
+            .acquire(lease, request_timeout, Some(lease / 2))
+            .await?;
+
+        Ok(Self {
+            lease_client,
+            request_timeout,
+            released: false,
+        })
+    }
+
+    /// Gets the fencing token of the held lease.
+    ///
+    /// Returns `None` if a renewal of the lease has failed, in which case exclusive execution
+    /// is no longer guaranteed.
+    #[must_use]
+    pub fn fencing_token(&self) -> Option<HybridLogicalClock> {
+        self.lease_client.current_lease_fencing_token()
+    }
+
+    /// Stops renewing and releases the lease.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if the State Store returns an Error response
+    ///
+    /// [`struct@Error`] of kind [`UnexpectedPayload`](ErrorKind::UnexpectedPayload) if the State Store returns a response that isn't valid for a `V Delete` request
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from the command invoker
+    pub async fn release(mut self) -> Result<(), Error> {
+        self.released = true;
+        self.lease_client.release(self.request_timeout).await
+    }
+}
+
+impl Drop for SingletonGuard {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        self.lease_client.cancel_auto_renewal();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let lease_client = self.lease_client.clone();
+            let request_timeout = self.request_timeout;
+            handle.spawn(async move {
+                if let Err(e) = lease_client.release(request_timeout).await {
+                    log::warn!("Failed to release singleton lease on drop: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// Attempts to acquire the lease until acquired, waiting as per the `wait_policy` in between attempts.
+async fn wait_and_acquire(
+    lease_client: &lease::Client,
+    observation: &mut LeaseObservation,
+    lease: Duration,
+    wait_policy: WaitPolicy,
+    request_timeout: Duration,
+) -> Result<(), Error> {
+    loop {
+        match lease_client.acquire(lease, request_timeout, None).await {
+            Ok(_) => return Ok(()),
+            Err(e) if matches!(e.kind(), ErrorKind::LeaseAlreadyHeld) => {}
+            Err(e) => return Err(e),
+        }
+
+        if let Ok(Some(holder)) = lease_client.get_holder(request_timeout).await {
+            log::info!(
+                "Waiting for singleton lease held by {}",
+                String::from_utf8_lossy(&holder)
+            );
+        }
+
+        // Wait for a release, renewals by the current holder are ignored
+        loop {
+            let notification = if wait_policy == WaitPolicy::StealAfterExpiry {
+                if let Ok(notification) = timeout(lease, observation.recv_notification()).await {
+                    notification
+                } else {
+                    // The holder neither renewed nor released the lease within a lease period
+                    lease_client.take_over(lease, request_timeout).await?;
+                    return Ok(());
+                }
+            } else {
+                observation.recv_notification().await
+            };
+
+            let Some((notification, _)) = notification else {
+                // The observation ended (e.g. on disconnection), re-observe and try again
+                *observation = lease_client.observe(request_timeout).await?;
+                break;
+            };
+
+            if notification.operation == state_store::Operation::Del {
+                break;
+            }
+        }
+    }
+}
+
+/// Builds the error reported when the lease is held by another holder, identifying that holder if possible.
+async fn held_error(lease_client: &lease::Client, request_timeout: Duration) -> Error {
+    match lease_client.get_holder(request_timeout).await {
+        Ok(Some(holder)) => Error(ErrorKind::LeaseHeldBy(holder)),
+        // The holder could not be determined, or released the lease in the meantime
+        _ => Error(ErrorKind::LeaseAlreadyHeld),
+    }
+}
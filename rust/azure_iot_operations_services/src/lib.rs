@@ -11,26 +11,36 @@
 //! - `all`: Enables all features.
 //! - `schema_registry`: Enables the Schema Registry Client.
 //! - `state_store`: Enables the State Store Client.
+//! - `state_store_encryption`: Enables client-side encryption of State Store values. Also enables `state_store`.
 //! - `leased_lock`: Enables the Lease and Lock Clients.
 //! - `azure_device_registry`: Enables the Azure Device Registry client.
 //! - `edge_registry`: Enables the Edge Registry client.
+//! - `config`: Enables the Config Client.
 //!
 //! This example shows how you could import features for only the Schema Registry Client:
 //!
 //! ```toml
 //! azure_iot_operations_services = { version = "<version>", features = ["schema_registry"] }
 //! ```
+//!
+//! The clients of an application can be constructed from a single session with
+//! [`ServicesBuilder`](services_builder::ServicesBuilder), available when the `state_store` or
+//! `schema_registry` feature is enabled.
 
 #![warn(missing_docs)]
 #![allow(clippy::result_large_err)]
 
 #[cfg(feature = "azure_device_registry")]
 pub mod azure_device_registry;
+#[cfg(feature = "config")]
+pub mod config;
 #[cfg(feature = "edge_registry")]
 pub mod edge_registry;
 #[cfg(feature = "leased_lock")]
 pub mod leased_lock;
 #[cfg(feature = "schema_registry")]
 pub mod schema_registry;
+#[cfg(any(feature = "state_store", feature = "schema_registry"))]
+pub mod services_builder;
 #[cfg(feature = "state_store")]
 pub mod state_store;
@@ -55,6 +55,56 @@ pub enum ErrorKind {
     ServiceError(#[from] ServiceError),
 }
 
+/// Outcome of putting a schema with [`Client::put_with_outcome`].
+///
+/// Whether a schema is new is determined from the schemas previously put by the same [`Client`]
+/// (or a clone of it).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PutOutcome {
+    /// The schema is equivalent to one already registered by the client, so nothing new was
+    /// registered.
+    Unchanged,
+    /// A new version of a schema already registered by the client was registered. Contains the
+    /// new version.
+    NewVersion(String),
+    /// A schema not previously registered by the client was registered. Contains its version.
+    Created(String),
+}
+
+/// Returns the normalized form of JSON schema content.
+///
+/// The content is parsed as JSON and re-serialized with object keys sorted, numbers with an
+/// integral value formatted as integers (e.g. `1.0` as `1`), and insignificant whitespace removed.
+/// Array ordering is preserved, since it is meaningful in JSON.
+///
+/// # Errors
+/// [`serde_json::Error`] if `schema_content` is not valid JSON
+#[cfg(feature = "normalize-schemas")]
+pub fn normalize_schema_content(schema_content: &str) -> Result<String, serde_json::Error> {
+    fn normalize_numbers(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Number(number) => {
+                // Only convert floats that can be represented exactly as an integer
+                if let Some(float) = number.as_f64().filter(|f| {
+                    number.is_f64() && f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0
+                }) {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let integer = float as i64;
+                    *number = integer.into();
+                }
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(normalize_numbers),
+            serde_json::Value::Object(members) => members.values_mut().for_each(normalize_numbers),
+            _ => {}
+        }
+    }
+
+    // `serde_json::Map` is ordered by key, so serializing a parsed `Value` sorts the keys
+    let mut value: serde_json::Value = serde_json::from_str(schema_content)?;
+    normalize_numbers(&mut value);
+    serde_json::to_string(&value)
+}
+
 // ~~~~~~~~~~~~~~~~~~~DTDL Equivalent Error~~~~~~~
 
 /// Error codes for schema operations.
@@ -147,6 +197,7 @@ impl TryFrom<(i32, &str)> for ErrorCode {
                 command_name: Some(command_name.to_string()),
                 protocol_version: None,
                 supported_protocol_major_versions: None,
+                correlation_id: None,
             }),
         }
     }
@@ -197,6 +248,7 @@ impl TryFrom<(String, &str)> for ErrorTarget {
                 command_name: Some(command_name.to_string()),
                 protocol_version: None,
                 supported_protocol_major_versions: None,
+                correlation_id: None,
             }),
         }
     }
@@ -395,6 +447,7 @@ impl TryFrom<(String, &str)> for Format {
                 command_name: Some(command_name.to_string()),
                 protocol_version: None,
                 supported_protocol_major_versions: None,
+                correlation_id: None,
             }),
         }
     }
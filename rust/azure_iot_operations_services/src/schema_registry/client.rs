@@ -5,7 +5,7 @@
 //!
 //! To use this client, the `schema_registry` feature must be enabled.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use azure_iot_operations_mqtt::session::SessionManagedClient;
@@ -15,14 +15,18 @@ use azure_iot_operations_protocol::rpc_command;
 use crate::schema_registry::schemaregistry_gen::common_types::options::CommandInvokerOptionsBuilder;
 use crate::schema_registry::schemaregistry_gen::schema_registry::client as sr_client_gen;
 use crate::schema_registry::{
-    Error, ErrorKind, GetSchemaRequest, PutSchemaRequest, Schema, ServiceError,
+    Error, ErrorKind, GetSchemaRequest, PutOutcome, PutSchemaRequest, Schema, ServiceError,
 };
+#[cfg(feature = "normalize-schemas")]
+use crate::schema_registry::{Format, normalize_schema_content};
 
 /// Schema registry client implementation.
 #[derive(Clone)]
 pub struct Client {
     get_command_invoker: Arc<sr_client_gen::GetCommandInvoker>,
     put_command_invoker: Arc<sr_client_gen::PutCommandInvoker>,
+    /// Schemas registered by this client, with the put request that registered them
+    registered_schemas: Arc<Mutex<Vec<(PutSchemaRequest, Schema)>>>,
 }
 
 impl Client {
@@ -48,6 +52,7 @@ impl Client {
                 client.clone(),
                 &options,
             )),
+            registered_schemas: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -106,7 +111,8 @@ impl Client {
     /// * `put_request` - The request to put a schema in the schema registry.
     /// * `timeout` - The duration until the Schema Registry Client stops waiting for a response to the request, it is rounded up to the nearest second.
     ///
-    /// Returns the [`Schema`] that was put if the request was successful.
+    /// Returns the [`Schema`] that was put if the request was successful. See
+    /// [`Client::put_with_outcome`] for how the schema is normalized before it is put.
     ///
     /// # Errors
     /// [`struct@Error`] of kind [`InvalidRequestArgument`](ErrorKind::InvalidRequestArgument)
@@ -121,6 +127,91 @@ impl Client {
         &self,
         put_request: PutSchemaRequest,
         timeout: Duration,
+    ) -> Result<Schema, Error> {
+        self.put_with_outcome(put_request, timeout)
+            .await
+            .map(|(schema, _)| schema)
+    }
+
+    /// Adds or updates a schema in the schema registry service, returning whether anything new
+    /// was registered.
+    ///
+    /// With the `normalize-schemas` feature (enabled by default), the content of
+    /// [`JsonSchemaDraft07`](Format::JsonSchemaDraft07) schemas is normalized before it is put
+    /// (see [`normalize_schema_content`](crate::schema_registry::normalize_schema_content)), so
+    /// that schemas differing only in key ordering, number formatting or whitespace are registered
+    /// as the same schema. If an equivalent schema has already been registered by this client, the
+    /// put is skipped and the registered [`Schema`] is returned with [`PutOutcome::Unchanged`].
+    /// Schemas in other formats, or whose content is not valid JSON, are put as is.
+    ///
+    /// # Arguments
+    /// * `put_request` - The request to put a schema in the schema registry.
+    /// * `timeout` - The duration until the Schema Registry Client stops waiting for a response to the request, it is rounded up to the nearest second.
+    ///
+    /// Returns the [`Schema`] that was put and the [`PutOutcome`] if the request was successful.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidRequestArgument`](ErrorKind::InvalidRequestArgument)
+    /// if the `timeout` is zero or > `u32::max`.
+    ///
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError)
+    /// if there is an error returned by the Schema Registry Service.
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError)
+    /// if there are any underlying errors from the AIO RPC protocol.
+    ///
+    /// # Panics
+    /// If the registered schemas mutex has been poisoned, which should not be possible
+    pub async fn put_with_outcome(
+        &self,
+        put_request: PutSchemaRequest,
+        timeout: Duration,
+    ) -> Result<(Schema, PutOutcome), Error> {
+        #[cfg(feature = "normalize-schemas")]
+        let put_request = {
+            let mut put_request = put_request;
+            if put_request.format == Format::JsonSchemaDraft07
+                && let Ok(schema_content) = normalize_schema_content(&put_request.schema_content)
+            {
+                put_request.schema_content = schema_content;
+                if let Some((_, schema)) = self
+                    .registered_schemas
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|(registered_request, _)| *registered_request == put_request)
+                {
+                    return Ok((schema.clone(), PutOutcome::Unchanged));
+                }
+            }
+            put_request
+        };
+
+        let schema = self.invoke_put(put_request.clone(), timeout).await?;
+
+        let mut registered_schemas = self.registered_schemas.lock().unwrap();
+        let outcome = if registered_schemas.iter().any(|(_, registered_schema)| {
+            registered_schema.name == schema.name && registered_schema.version == schema.version
+        }) {
+            PutOutcome::Unchanged
+        } else if registered_schemas
+            .iter()
+            .any(|(_, registered_schema)| registered_schema.name == schema.name)
+        {
+            PutOutcome::NewVersion(schema.version.clone())
+        } else {
+            PutOutcome::Created(schema.version.clone())
+        };
+        registered_schemas.retain(|(registered_request, _)| *registered_request != put_request);
+        registered_schemas.push((put_request, schema.clone()));
+        Ok((schema, outcome))
+    }
+
+    /// Invokes the put command on the schema registry service.
+    async fn invoke_put(
+        &self,
+        put_request: PutSchemaRequest,
+        timeout: Duration,
     ) -> Result<Schema, Error> {
         let payload = sr_client_gen::PutRequestSchema {
             description: put_request.description,
@@ -327,6 +418,17 @@ mod tests {
         assert_eq!(put_request.version, DEFAULT_SCHEMA_VERSION.to_string());
     }
 
+    #[cfg(feature = "normalize-schemas")]
+    #[test_case::test_case(r#"{ "b": 1, "a": { "d": true, "c": null } }"#, r#"{"a":{"c":null,"d":true},"b":1}"#; "sorted keys")]
+    #[test_case::test_case(r#"{"maximum": 100.0, "minimum": -2.50, "enum": [3.0, 1e2]}"#, r#"{"enum":[3,100],"maximum":100,"minimum":-2.5}"#; "number formatting")]
+    #[test_case::test_case(r#"{"items": [{"type": "string"}, {"type": "integer"}]}"#, r#"{"items":[{"type":"string"},{"type":"integer"}]}"#; "array order preserved")]
+    fn test_normalize_schema_content(schema_content: &str, expected: &str) {
+        assert_eq!(
+            crate::schema_registry::normalize_schema_content(schema_content).unwrap(),
+            expected
+        );
+    }
+
     #[tokio::test]
     async fn test_get_timeout_invalid() {
         let session = create_session();
@@ -0,0 +1,156 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Builder for constructing the service clients of an application from a single [`Session`].
+//!
+//! To use this builder, the `state_store` or `schema_registry` feature must be enabled. Each
+//! constructor is only available with the feature of its client.
+
+#[cfg(feature = "leased_lock")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "state_store")]
+use azure_iot_operations_mqtt::session::SessionMonitor;
+use azure_iot_operations_mqtt::session::{Session, SessionManagedClient};
+use azure_iot_operations_protocol::application::ApplicationContext;
+
+#[cfg(feature = "leased_lock")]
+use crate::leased_lock::{self, lock};
+#[cfg(feature = "schema_registry")]
+use crate::schema_registry;
+#[cfg(feature = "state_store")]
+use crate::state_store;
+
+/// Constructs service clients that all use the same [`ApplicationContext`] and [`Session`].
+///
+/// The managed client and session monitor of the [`Session`] are captured once when the builder
+/// is created, instead of being passed to the constructor of each client. Clients built by a
+/// `ServicesBuilder` behave identically to clients constructed directly with the same arguments.
+///
+/// # Example
+/// ```no_run
+/// # use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+/// # use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+/// # use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+/// # use azure_iot_operations_services::services_builder::ServicesBuilder;
+/// # use azure_iot_operations_services::state_store;
+/// # let connection_settings = MqttConnectionSettingsBuilder::default()
+/// #     .client_id("test_client")
+/// #     .hostname("localhost")
+/// #     .build().unwrap();
+/// # let session_options = SessionOptionsBuilder::default()
+/// #     .connection_settings(connection_settings)
+/// #     .build().unwrap();
+/// # let session = Session::new(session_options).unwrap();
+/// # #[cfg(all(feature = "schema_registry", feature = "leased_lock"))] {
+/// let application_context = ApplicationContextBuilder::default().build().unwrap();
+/// let services = ServicesBuilder::new(application_context, &session);
+/// let state_store_client = services
+///     .state_store(state_store::ClientOptionsBuilder::default().build().unwrap())
+///     .unwrap();
+/// let schema_registry_client = services.schema_registry();
+/// let lock_client = services.leased_lock(b"someLock".to_vec()).unwrap();
+/// # }
+/// ```
+pub struct ServicesBuilder {
+    application_context: ApplicationContext,
+    managed_client: SessionManagedClient,
+    #[cfg(feature = "state_store")]
+    session_monitor: SessionMonitor,
+    /// State Store Client shared by all lock clients, created on first use
+    #[cfg(feature = "leased_lock")]
+    lock_state_store: Mutex<Option<Arc<state_store::Client>>>,
+}
+
+impl ServicesBuilder {
+    /// Create a new `ServicesBuilder` for the clients of an application using `session`.
+    #[must_use]
+    pub fn new(application_context: ApplicationContext, session: &Session) -> Self {
+        Self {
+            application_context,
+            managed_client: session.create_managed_client(),
+            #[cfg(feature = "state_store")]
+            session_monitor: session.create_session_monitor(),
+            #[cfg(feature = "leased_lock")]
+            lock_state_store: Mutex::new(None),
+        }
+    }
+
+    /// Create a new State Store Client.
+    ///
+    /// Equivalent to [`state_store::Client::new`] with the captured application context, managed
+    /// client and session monitor. Each call creates a separate client.
+    ///
+    /// # Errors
+    /// Same as [`state_store::Client::new`].
+    #[cfg(feature = "state_store")]
+    pub fn state_store(
+        &self,
+        options: state_store::ClientOptions,
+    ) -> Result<state_store::Client, state_store::Error> {
+        state_store::Client::new(
+            self.application_context.clone(),
+            self.managed_client.clone(),
+            self.session_monitor.clone(),
+            options,
+        )
+    }
+
+    /// Create a new Schema Registry Client.
+    ///
+    /// Equivalent to [`schema_registry::Client::new`] with the captured application context and
+    /// managed client.
+    #[cfg(feature = "schema_registry")]
+    #[must_use]
+    pub fn schema_registry(&self) -> schema_registry::Client {
+        schema_registry::Client::new(self.application_context.clone(), &self.managed_client)
+    }
+
+    /// Create a new Lock Client for the lock named `lock_name`, held under the client ID of the
+    /// session.
+    ///
+    /// All lock clients created by this builder share a single State Store Client with default
+    /// options, so that only one set of State Store subscriptions is made for all locks. It is
+    /// created on the first call, and is available from
+    /// [`lock_state_store`](Self::lock_state_store) so that it can be shut down.
+    ///
+    /// Notes:
+    /// - There must be one instance of `lock::Client` per lock.
+    ///
+    /// # Errors
+    /// [`struct@leased_lock::Error`] of kind
+    /// [`InvalidArgument`](leased_lock::ErrorKind::InvalidArgument) if `lock_name` is empty.
+    #[cfg(feature = "leased_lock")]
+    pub fn leased_lock(&self, lock_name: Vec<u8>) -> Result<lock::Client, leased_lock::Error> {
+        lock::Client::new(
+            self.lock_state_store()?,
+            lock_name,
+            self.managed_client.client_id().as_bytes().to_vec(),
+        )
+    }
+
+    /// Returns the State Store Client shared by the lock clients created by this builder,
+    /// creating it if no lock client has been created yet.
+    ///
+    /// # Errors
+    /// Same as [`state_store::Client::new`].
+    ///
+    /// # Panics
+    /// If the lock on the shared client is poisoned, which should not be possible.
+    #[cfg(feature = "leased_lock")]
+    pub fn lock_state_store(&self) -> Result<Arc<state_store::Client>, state_store::Error> {
+        let mut lock_state_store = self.lock_state_store.lock().unwrap();
+        if let Some(state_store) = lock_state_store.as_ref() {
+            return Ok(state_store.clone());
+        }
+        let state_store = Arc::new(
+            self.state_store(
+                state_store::ClientOptionsBuilder::default()
+                    .build()
+                    .expect("Default state store options should not fail"),
+            )?,
+        );
+        *lock_state_store = Some(state_store.clone());
+        Ok(state_store)
+    }
+}
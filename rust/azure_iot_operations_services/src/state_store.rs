@@ -4,7 +4,7 @@
 //! Types for State Store operations.
 
 use core::fmt::Debug;
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
 use azure_iot_operations_protocol::{
     common::{aio_protocol_error::AIOProtocolError, hybrid_logical_clock::HybridLogicalClock},
@@ -14,6 +14,9 @@ use thiserror::Error;
 
 /// State Store Client implementation
 mod client;
+#[cfg(feature = "state_store_encryption")]
+pub mod encryption;
+pub mod processed_tracker;
 /// Serialization and deserialization implementations for resp3 state store payloads
 mod resp3;
 
@@ -194,3 +197,88 @@ impl Display for KeyNotification {
         )
     }
 }
+
+/// A command of the State Store Service that support can be detected for with
+/// [`Client::server_capabilities`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServerCommand {
+    /// The `SET` command, used by [`Client::set`]
+    Set,
+    /// The `GET` command, used by [`Client::get`]
+    Get,
+    /// The `DEL` command, used by [`Client::del`] and [`Client::del_if_version`]
+    Del,
+    /// The `VDEL` command, used by [`Client::vdel`] and [`Client::del_if_equal`]
+    VDel,
+    /// The `KEYNOTIFY` command, used by [`Client::observe`] and [`Client::unobserve`]
+    KeyNotify,
+    /// The `DELPREFIX` command, used by [`Client::del_prefix`] to delete keys atomically
+    DelPrefix,
+    /// The `KEYS` command, used by [`Client::del_prefix`] to find the keys to delete if
+    /// `DELPREFIX` is not supported
+    Keys,
+}
+
+impl ServerCommand {
+    /// All commands that support is detected for
+    pub(crate) const ALL: [ServerCommand; 7] = [
+        ServerCommand::Set,
+        ServerCommand::Get,
+        ServerCommand::Del,
+        ServerCommand::VDel,
+        ServerCommand::KeyNotify,
+        ServerCommand::DelPrefix,
+        ServerCommand::Keys,
+    ];
+
+    /// The name of the command in a RESP3 request
+    pub(crate) fn name(self) -> &'static [u8] {
+        match self {
+            ServerCommand::Set => b"SET",
+            ServerCommand::Get => b"GET",
+            ServerCommand::Del => b"DEL",
+            ServerCommand::VDel => b"VDEL",
+            ServerCommand::KeyNotify => b"KEYNOTIFY",
+            ServerCommand::DelPrefix => b"DELPREFIX",
+            ServerCommand::Keys => b"KEYS",
+        }
+    }
+}
+
+/// The commands supported by the State Store Service that a [`Client`] is connected to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerCapabilities {
+    supported_commands: HashSet<ServerCommand>,
+}
+
+impl ServerCapabilities {
+    /// Returns `true` if the State Store Service supports the [`ServerCommand`].
+    #[must_use]
+    pub fn supports(&self, command: ServerCommand) -> bool {
+        self.supported_commands.contains(&command)
+    }
+
+    /// Returns the [`ServerCommand`]s supported by the State Store Service.
+    #[must_use]
+    pub fn supported_commands(&self) -> &HashSet<ServerCommand> {
+        &self.supported_commands
+    }
+
+    /// Records if the State Store Service supports the [`ServerCommand`], based on the response to
+    /// a request for the command without any arguments. Only a State Store Service that doesn't
+    /// recognize the command responds with [`ServiceError::UnknownCommand`].
+    pub(crate) fn add_probe_response(
+        &mut self,
+        command: ServerCommand,
+        response: &resp3::Response,
+    ) {
+        let unknown_command = matches!(
+            response,
+            resp3::Response::Error(e) if matches!(ServiceError::from(e.clone()), ServiceError::UnknownCommand)
+        );
+        if !unknown_command {
+            self.supported_commands.insert(command);
+        }
+    }
+}
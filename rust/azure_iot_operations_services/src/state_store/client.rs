@@ -5,7 +5,11 @@
 //!
 //! To use this client, the `state_store` feature must be enabled.
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
 
 use azure_iot_operations_mqtt::{
     session::{SessionManagedClient, SessionMonitor},
@@ -13,16 +17,18 @@ use azure_iot_operations_mqtt::{
 };
 use azure_iot_operations_protocol::{
     application::ApplicationContext,
+    common::aio_protocol_error::AIOProtocolErrorKind,
     common::dispatcher::{DispatchError, DispatchErrorKind, Dispatcher, Receiver},
     common::hybrid_logical_clock::HybridLogicalClock,
     rpc_command, telemetry,
 };
 use data_encoding::HEXUPPER;
 use derive_builder::Builder;
-use tokio::{sync::Notify, task};
+use tokio::{sync::Notify, task, time::Instant};
 
 use crate::state_store::{
-    self, Error, ErrorKind, FENCING_TOKEN_USER_PROPERTY, PERSIST_USER_PROPERTY, SetOptions,
+    self, Error, ErrorKind, FENCING_TOKEN_USER_PROPERTY, PERSIST_USER_PROPERTY, ServerCapabilities,
+    ServerCommand, ServiceError, SetOptions,
 };
 
 const REQUEST_TOPIC_PATTERN: &str =
@@ -32,6 +38,17 @@ const RESPONSE_TOPIC_SUFFIX: &str = "response";
 const COMMAND_NAME: &str = "invoke";
 // where the encodedClientId is an upper-case hex encoded representation of the MQTT ClientId of the client that initiated the KEYNOTIFY request and encodedKeyName is a hex encoded representation of the key that changed
 const NOTIFICATION_TOPIC_PATTERN: &str = "clients/statestore/v1/FA9AE35F-2F64-47CD-9BFF-08E2B32A0FE8/{encodedClientId}/command/notify/{encodedKeyName}";
+/// Timeout for the `Set` request used to claim a command request for an executor
+const DISTRIBUTED_DEDUP_CLAIM_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout for loading and saving telemetry sequence numbers
+const TELEMETRY_SEQUENCE_NUMBER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout for each request made to refresh the [`ServerCapabilities`] when the session connects
+const SERVER_CAPABILITIES_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout for each request made to observe a key again when the session reconnects
+const REOBSERVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type StateStoreInvoker =
+    rpc_command::Invoker<state_store::resp3::Request, state_store::resp3::Response>;
 
 /// A struct to manage receiving notifications for a key
 #[derive(Debug)]
@@ -59,6 +76,18 @@ impl KeyObservation {
     // that was observed where the receiver was dropped and a key that was never observed
 }
 
+impl futures::Stream for KeyObservation {
+    type Item = (state_store::KeyNotification, Option<AckToken>);
+
+    /// Polls for the next [`state_store::KeyNotification`], as [`KeyObservation::recv_notification`]
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
 /// State Store Client Options struct
 #[derive(Builder, Clone)]
 #[builder(setter(into))]
@@ -66,11 +95,48 @@ pub struct ClientOptions {
     /// If true, key notifications are auto-acknowledged
     #[builder(default = "true")]
     key_notification_auto_ack: bool,
+    /// Maximum duration of a single attempt of a request to the State Store Service, rounded up
+    /// to the nearest second. If set and shorter than the `timeout` of an operation, an attempt
+    /// that times out is retried with a new request until the `timeout` of the operation elapses.
+    /// If `None`, each operation is a single attempt that may take its whole `timeout`.
+    ///
+    /// Note: a retried request may be executed by the State Store Service more than once if only
+    /// its response was lost. Conditional operations such as a `Set` with
+    /// [`OnlyIfDoesNotExist`](state_store::SetCondition::OnlyIfDoesNotExist) or a `V Delete` may
+    /// then report that they were not applied even though an earlier attempt applied them.
+    #[builder(default = "None")]
+    attempt_timeout: Option<Duration>,
+    /// If true, the [`ServerCapabilities`] returned by
+    /// [`server_capabilities`](Client::server_capabilities) are queried from the State Store
+    /// Service each time the session connects, so that they reflect the State Store Service of the
+    /// current connection. If false, they are only queried on the first call to
+    /// [`server_capabilities`](Client::server_capabilities).
+    #[builder(default = "true")]
+    refresh_server_capabilities: bool,
+    /// If true, keys being observed are observed again each time the session reconnects, so that
+    /// each [`KeyObservation`] keeps receiving notifications across connection losses. Changes
+    /// made to a key while the session is disconnected are not notified. If observing a key again
+    /// fails, its [`KeyObservation`] ends.
+    ///
+    /// If false, all [`KeyObservation`]s end when the session disconnects, and the keys must be
+    /// observed again with [`observe`](Client::observe).
+    #[builder(default = "false")]
+    reobserve_on_reconnect: bool,
 }
 
 /// State store client implementation
+///
+/// Each operation takes a `timeout` that bounds the whole operation. By default, an operation is
+/// a single request that may take the whole `timeout`. If an
+/// [`attempt_timeout`](ClientOptionsBuilder::attempt_timeout) shorter than the `timeout` is
+/// configured, each request is instead given at most the `attempt_timeout`, and a request that
+/// times out is retried immediately as a new request until the `timeout` elapses. Only timeouts
+/// are retried; any other error, including an error response from the State Store Service, is
+/// returned without retrying.
 pub struct Client {
-    invoker: rpc_command::Invoker<state_store::resp3::Request, state_store::resp3::Response>,
+    invoker: Arc<StateStoreInvoker>,
+    attempt_timeout: Option<Duration>,
+    server_capabilities: Arc<Mutex<Option<ServerCapabilities>>>,
     notification_dispatcher:
         Arc<Dispatcher<(state_store::KeyNotification, Option<AckToken>), String>>,
     shutdown_notifier: Arc<Notify>,
@@ -86,6 +152,9 @@ impl Client {
     /// </div>
     ///
     /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if the
+    ///     [`attempt_timeout`](ClientOptionsBuilder::attempt_timeout) is zero or > `u32::max` seconds
+    ///
     /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) is possible if
     ///     there are any errors creating the underlying command invoker or telemetry receiver, but it should not happen
     ///
@@ -99,6 +168,16 @@ impl Client {
         session_monitor: SessionMonitor,
         options: ClientOptions,
     ) -> Result<Self, Error> {
+        if let Some(attempt_timeout) = options.attempt_timeout
+            && (attempt_timeout.is_zero()
+                || attempt_timeout.as_secs() + u64::from(attempt_timeout.subsec_nanos() != 0)
+                    > u64::from(u32::MAX))
+        {
+            return Err(Error(ErrorKind::InvalidArgument(format!(
+                "attempt_timeout must be greater than zero and at most u32::max seconds, but was {attempt_timeout:?}"
+            ))));
+        }
+
         // create invoker for commands
         let invoker_options = rpc_command::invoker::OptionsBuilder::default()
             .request_topic_pattern(REQUEST_TOPIC_PATTERN)
@@ -109,11 +188,20 @@ impl Client {
             .build()
             .expect("Unreachable because all parameters that could cause errors are statically provided");
 
-        let invoker: rpc_command::Invoker<
-            state_store::resp3::Request,
-            state_store::resp3::Response,
-        > = rpc_command::Invoker::new(application_context.clone(), client.clone(), invoker_options)
-            .map_err(ErrorKind::from)?;
+        let invoker: Arc<StateStoreInvoker> = Arc::new(
+            rpc_command::Invoker::new(application_context.clone(), client.clone(), invoker_options)
+                .map_err(ErrorKind::from)?,
+        );
+
+        // Query the server capabilities each time the session connects
+        let server_capabilities = Arc::new(Mutex::new(None));
+        if options.refresh_server_capabilities {
+            task::spawn(Self::refresh_server_capabilities_loop(
+                Arc::downgrade(&invoker),
+                server_capabilities.clone(),
+                session_monitor.clone(),
+            ));
+        }
 
         // Create the uppercase hex encoded version of the client ID that is used in the key notification topic
         let encoded_client_id = HEXUPPER.encode(client.client_id().as_bytes());
@@ -135,6 +223,15 @@ impl Client {
         // Create a hashmap of keys being observed and channels to send their notifications to
         let notification_dispatcher = Arc::new(Dispatcher::new());
 
+        // Observe the keys being observed again each time the session reconnects
+        if options.reobserve_on_reconnect {
+            task::spawn(Self::reobserve_on_reconnect_loop(
+                Arc::downgrade(&invoker),
+                notification_dispatcher.clone(),
+                session_monitor.clone(),
+            ));
+        }
+
         // Start the receive key notification loop
         task::spawn({
             let notification_receiver: telemetry::Receiver<state_store::resp3::Operation> =
@@ -148,6 +245,7 @@ impl Client {
                     notification_receiver,
                     notification_dispatcher_clone,
                     session_monitor,
+                    options.reobserve_on_reconnect,
                 )
                 .await;
             }
@@ -155,6 +253,8 @@ impl Client {
 
         Ok(Self {
             invoker,
+            attempt_timeout: options.attempt_timeout,
+            server_capabilities,
             notification_dispatcher,
             shutdown_notifier,
         })
@@ -179,17 +279,151 @@ impl Client {
         Ok(())
     }
 
+    /// Invokes a request on the State Store Service, retrying attempts that time out within
+    /// `timeout` if an [`attempt_timeout`](ClientOptionsBuilder::attempt_timeout) shorter than
+    /// `timeout` is configured. The last attempt is shortened to the time remaining until
+    /// `timeout` elapses, rounded up to the nearest second.
+    async fn invoke(
+        &self,
+        payload: state_store::resp3::Request,
+        custom_user_data: Vec<(String, String)>,
+        timeout: Duration,
+    ) -> Result<rpc_command::invoker::Response<state_store::resp3::Response>, Error> {
+        let build_request = |attempt_timeout: Duration| {
+            rpc_command::invoker::RequestBuilder::default()
+                .payload(payload.clone())
+                .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
+                .timeout(attempt_timeout)
+                .custom_user_data(custom_user_data.clone())
+                .build()
+                .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))
+        };
+
+        // Validates the timeout of the operation, even if the first attempt is shorter
+        let request = build_request(timeout)?;
+        let Some(attempt_timeout) = self.attempt_timeout.filter(|t| *t < timeout) else {
+            return Ok(self
+                .invoker
+                .invoke(request)
+                .await
+                .map_err(ErrorKind::from)?);
+        };
+
+        let deadline = Instant::now() + timeout;
+        let mut request = build_request(attempt_timeout)?;
+        let mut attempt = 1;
+        loop {
+            let result = self.invoker.invoke(request).await;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match result {
+                Err(e) if e.kind == AIOProtocolErrorKind::Timeout && !remaining.is_zero() => {
+                    log::warn!(
+                        "State Store request attempt {attempt} timed out, retrying with {remaining:?} remaining: {e}"
+                    );
+                    attempt += 1;
+                    request = build_request(attempt_timeout.min(remaining))?;
+                }
+                result => return Ok(result.map_err(ErrorKind::from)?),
+            }
+        }
+    }
+
+    /// Gets the [`ServerCapabilities`] of the State Store Service, which can be used to detect if
+    /// a command is supported before using it.
+    ///
+    /// The [`ServerCapabilities`] are cached. Unless disabled with
+    /// [`refresh_server_capabilities`](ClientOptionsBuilder::refresh_server_capabilities), they
+    /// are queried when the session connects and queried again each time it reconnects. If they
+    /// have not been queried yet, or the query failed, they are queried by this call.
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops waiting for the
+    /// response to each request made to query the [`ServerCapabilities`]. It is rounded up to the
+    /// nearest second.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if the `timeout` is zero or > `u32::max`
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from [`rpc_command::Invoker::invoke`]
+    ///
+    /// # Panics
+    /// If the lock on the cached [`ServerCapabilities`] is poisoned, which should not be possible.
+    pub async fn server_capabilities(
+        &self,
+        timeout: Duration,
+    ) -> Result<ServerCapabilities, Error> {
+        if let Some(server_capabilities) = self.server_capabilities.lock().unwrap().clone() {
+            return Ok(server_capabilities);
+        }
+        let server_capabilities = Self::query_server_capabilities(&self.invoker, timeout).await?;
+        *self.server_capabilities.lock().unwrap() = Some(server_capabilities.clone());
+        Ok(server_capabilities)
+    }
+
+    /// Queries the [`ServerCapabilities`] by sending a request without arguments for each
+    /// [`ServerCommand`]
+    async fn query_server_capabilities(
+        invoker: &StateStoreInvoker,
+        timeout: Duration,
+    ) -> Result<ServerCapabilities, Error> {
+        let mut server_capabilities = ServerCapabilities::default();
+        for command in ServerCommand::ALL {
+            let request = rpc_command::invoker::RequestBuilder::default()
+                .payload(state_store::resp3::Request::Probe {
+                    command: command.name(),
+                })
+                .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
+                .timeout(timeout)
+                .build()
+                .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
+            let response = invoker.invoke(request).await.map_err(ErrorKind::from)?;
+            server_capabilities.add_probe_response(command, &response.payload);
+        }
+        Ok(server_capabilities)
+    }
+
+    /// Queries the [`ServerCapabilities`] each time the session connects, until the
+    /// [`state_store::Client`] is dropped
+    async fn refresh_server_capabilities_loop(
+        invoker: Weak<StateStoreInvoker>,
+        server_capabilities: Arc<Mutex<Option<ServerCapabilities>>>,
+        session_monitor: SessionMonitor,
+    ) {
+        loop {
+            session_monitor.connected().await;
+            let Some(invoker) = invoker.upgrade() else {
+                break;
+            };
+            match Self::query_server_capabilities(&invoker, SERVER_CAPABILITIES_TIMEOUT).await {
+                Ok(refreshed) => {
+                    log::debug!("State Store server capabilities refreshed: {refreshed:?}");
+                    *server_capabilities.lock().unwrap() = Some(refreshed);
+                }
+                Err(e) => {
+                    // They will be queried on the next call to `server_capabilities` instead
+                    log::warn!("Error refreshing State Store server capabilities: {e}");
+                    *server_capabilities.lock().unwrap() = None;
+                }
+            }
+            drop(invoker);
+            session_monitor.disconnected().await;
+        }
+    }
+
     /// Sets a key value pair in the State Store Service
     ///
     /// Note: timeout refers to the duration until the State Store Client stops
     /// waiting for a `Set` response from the Service. This value is not linked
     /// to the key in the State Store. It is rounded up to the nearest second.
     ///
+    /// `fencing_token` must be provided if the key was set with a fencing token, and must not be
+    /// lower than it. See [`SetOptions`] for how it applies to conditional `Set`s.
+    ///
     /// Returns `true` if the `Set` completed successfully, or `false` if the `Set` did not occur because of values specified in `SetOptions`
     /// # Errors
     /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
     /// - the `key` is empty
     /// - the `timeout` is zero or > `u32::max`
+    /// - more than one of `set_condition`, `only_if_equal` and `only_if_not_exists` are specified in `options`
     ///
     /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if the State Store returns an Error response
     ///
@@ -209,7 +443,51 @@ impl Client {
                 "key is empty".to_string(),
             )));
         }
+        let conditions = [
+            options.set_condition != state_store::SetCondition::Unconditional,
+            options.only_if_equal.is_some(),
+            options.only_if_not_exists,
+        ];
+        if conditions.into_iter().filter(|c| *c).count() > 1 {
+            return Err(Error(ErrorKind::InvalidArgument(
+                "only one of set_condition, only_if_equal and only_if_not_exists can be specified"
+                    .to_string(),
+            )));
+        }
+
+        let mut options = options;
+        if options.only_if_not_exists {
+            options.only_if_not_exists = false;
+            options.set_condition = state_store::SetCondition::OnlyIfDoesNotExist;
+        }
+        if let Some(expected_value) = options.only_if_equal.take() {
+            // Compare-and-swap: delete the key only if it holds the expected value, then set the
+            // new value only if no other client has set the key in between
+            let deleted = self
+                .vdel(key.clone(), expected_value, fencing_token.clone(), timeout)
+                .await?;
+            if deleted.response != 1 {
+                return Ok(state_store::Response {
+                    version: deleted.version,
+                    response: false,
+                });
+            }
+            options.set_condition = state_store::SetCondition::OnlyIfDoesNotExist;
+        }
+
+        self.set_internal(key, value, timeout, fencing_token, options)
+            .await
+    }
 
+    /// Internal function sending a `Set` request with a single condition in `options`
+    async fn set_internal(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        timeout: Duration,
+        fencing_token: Option<HybridLogicalClock>,
+        options: SetOptions,
+    ) -> Result<state_store::Response<bool>, Error> {
         let mut custom_user_data = vec![];
         if let Some(ft) = fencing_token {
             custom_user_data.push((FENCING_TOKEN_USER_PROPERTY.to_string(), ft.to_string()));
@@ -218,22 +496,17 @@ impl Client {
             custom_user_data.push((PERSIST_USER_PROPERTY.to_string(), true.to_string()));
         }
 
-        let request = rpc_command::invoker::RequestBuilder::default()
-            .payload(state_store::resp3::Request::Set {
-                key,
-                value,
-                options: options.clone(),
-            })
-            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
-            .timeout(timeout)
-            .custom_user_data(custom_user_data)
-            .build()
-            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
         state_store::convert_response(
-            self.invoker
-                .invoke(request)
-                .await
-                .map_err(ErrorKind::from)?,
+            self.invoke(
+                state_store::resp3::Request::Set {
+                    key,
+                    value,
+                    options,
+                },
+                custom_user_data,
+                timeout,
+            )
+            .await?,
             |payload| match payload {
                 state_store::resp3::Response::NotApplied => Ok(false),
                 state_store::resp3::Response::Ok => Ok(true),
@@ -269,17 +542,9 @@ impl Client {
                 "key is empty".to_string(),
             )));
         }
-        let request = rpc_command::invoker::RequestBuilder::default()
-            .payload(state_store::resp3::Request::Get { key })
-            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
-            .timeout(timeout)
-            .build()
-            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
         state_store::convert_response(
-            self.invoker
-                .invoke(request)
-                .await
-                .map_err(ErrorKind::from)?,
+            self.invoke(state_store::resp3::Request::Get { key }, vec![], timeout)
+                .await?,
             |payload| match payload {
                 state_store::resp3::Response::Value(value) => Ok(Some(value)),
                 state_store::resp3::Response::NotFound => Ok(None),
@@ -288,12 +553,64 @@ impl Client {
         )
     }
 
+    /// Gets the values of multiple keys in the State Store Service
+    ///
+    /// The `Get` requests for all keys are sent without waiting for each other's responses, so
+    /// fetching many keys takes a single round trip rather than one per key.
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops
+    /// waiting for the `Get` responses from the Service, and applies to each key. This value is
+    /// not linked to the keys in the State Store. It is rounded up to the nearest second.
+    ///
+    /// Returns the result of the `Get` for each key, in the same order as `keys`. A failure for one
+    /// key does not affect the others. Each result is `Some(<value of the key>)` if the key is found
+    /// or `None` if the key was not found, or an error as described for [`Client::get`].
+    pub async fn get_many(
+        &self,
+        keys: Vec<Vec<u8>>,
+        timeout: Duration,
+    ) -> Vec<Result<state_store::Response<Option<Vec<u8>>>, Error>> {
+        futures::future::join_all(keys.into_iter().map(|key| self.get(key, timeout))).await
+    }
+
+    /// Sets multiple key value pairs in the State Store Service
+    ///
+    /// The `Set` requests for all entries are sent without waiting for each other's responses, so
+    /// setting many keys takes a single round trip rather than one per key. The same `options`
+    /// apply to every entry. Keys that are protected by a fencing token must be set with
+    /// [`Client::set`].
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops
+    /// waiting for the `Set` responses from the Service, and applies to each entry. This value is
+    /// not linked to the keys in the State Store. It is rounded up to the nearest second.
+    ///
+    /// Returns the result of the `Set` for each entry, in the same order as `entries`. A failure
+    /// for one entry does not affect the others. Each result is `true` if the `Set` completed
+    /// successfully, or `false` if the `Set` did not occur because of values specified in
+    /// `SetOptions`, or an error as described for [`Client::set`].
+    pub async fn set_many(
+        &self,
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        timeout: Duration,
+        options: SetOptions,
+    ) -> Vec<Result<state_store::Response<bool>, Error>> {
+        futures::future::join_all(
+            entries
+                .into_iter()
+                .map(|(key, value)| self.set(key, value, timeout, None, options.clone())),
+        )
+        .await
+    }
+
     /// Deletes a key from the State Store Service
     ///
     /// Note: timeout refers to the duration until the State Store Client stops
     /// waiting for a `Delete` response from the Service. This value is not linked
     /// to the key in the State Store. It is rounded up to the nearest second.
     ///
+    /// `fencing_token` must be provided if the key was set with a fencing token, and must not be
+    /// lower than it.
+    ///
     /// Returns the number of keys deleted. Will be `0` if the key was not found, otherwise `1`
     /// # Errors
     /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
@@ -330,6 +647,9 @@ impl Client {
     /// waiting for a `V Delete` response from the Service. This value is not linked
     /// to the key in the State Store. It is rounded up to the nearest second.
     ///
+    /// `fencing_token` must be provided if the key was set with a fencing token, and must not be
+    /// lower than it.
+    ///
     /// Returns the number of keys deleted. Will be `0` if the key was not found, `-1` if the value did not match, otherwise `1`
     /// # Errors
     /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
@@ -361,31 +681,179 @@ impl Client {
         .await
     }
 
+    /// Deletes a key from the State Store Service if and only if its value matches `expected_value`.
+    /// Use this to clean up a key only if it still holds the expected content, without deleting a
+    /// value written concurrently by another client.
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops
+    /// waiting for a `V Delete` response from the Service. This value is not linked
+    /// to the key in the State Store. It is rounded up to the nearest second.
+    ///
+    /// Returns `true` if the key was deleted, or `false` if the key was not found or the value did not match
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
+    /// - the `key` is empty
+    /// - the `timeout` is zero or > `u32::max`
+    ///
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if the State Store returns an Error response
+    ///
+    /// [`struct@Error`] of kind [`UnexpectedPayload`](ErrorKind::UnexpectedPayload) if the State Store returns a response that isn't valid for a `V Delete` request
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from [`rpc_command::Invoker::invoke`]
+    pub async fn del_if_equal(
+        &self,
+        key: Vec<u8>,
+        expected_value: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<state_store::Response<bool>, Error> {
+        let response = self.vdel(key, expected_value, None, timeout).await?;
+        Ok(state_store::Response {
+            version: response.version,
+            response: response.response == 1,
+        })
+    }
+
+    /// Deletes a key from the State Store Service if and only if it is not protected by a fencing
+    /// token newer than `version`. Use this to clean up a key set with `version` as its fencing
+    /// token only if no other client has since taken ownership of it with a newer fencing token.
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops
+    /// waiting for a `Delete` response from the Service. This value is not linked
+    /// to the key in the State Store. It is rounded up to the nearest second.
+    ///
+    /// Returns `true` if the key was deleted, or `false` if the key was not found or is protected by a newer fencing token
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
+    /// - the `key` is empty
+    /// - the `timeout` is zero or > `u32::max`
+    ///
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if the State Store returns an Error response
+    /// other than [`FencingTokenLowerVersion`](ServiceError::FencingTokenLowerVersion)
+    ///
+    /// [`struct@Error`] of kind [`UnexpectedPayload`](ErrorKind::UnexpectedPayload) if the State Store returns a response that isn't valid for a `Delete` request
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from [`rpc_command::Invoker::invoke`]
+    pub async fn del_if_version(
+        &self,
+        key: Vec<u8>,
+        version: HybridLogicalClock,
+        timeout: Duration,
+    ) -> Result<state_store::Response<bool>, Error> {
+        match self.del(key, Some(version), timeout).await {
+            Ok(response) => Ok(state_store::Response {
+                version: response.version,
+                response: response.response == 1,
+            }),
+            Err(Error(ErrorKind::ServiceError(ServiceError::FencingTokenLowerVersion))) => {
+                Ok(state_store::Response {
+                    version: None,
+                    response: false,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deletes all keys that start with `prefix` from the State Store Service
+    ///
+    /// If the State Store Service supports [`ServerCommand::DelPrefix`], the keys are deleted
+    /// atomically in a single request. Otherwise, if it supports [`ServerCommand::Keys`], the keys
+    /// matching the prefix are listed and then deleted one at a time.
+    ///
+    /// <div class="warning">
+    ///
+    /// The fallback of listing and deleting the keys is not atomic. Keys set with the prefix after
+    /// they are listed are not deleted, and if deleting a key fails, the error is returned and the
+    /// keys deleted before the failure remain deleted. Deleting by prefix is idempotent, so the
+    /// call can be repeated to delete the remaining keys. Keys protected by a fencing token cannot
+    /// be deleted without it, and cause the fallback to fail.
+    ///
+    /// </div>
+    ///
+    /// Note: timeout refers to the duration until the State Store Client stops
+    /// waiting for the response to each request made to the Service. It is rounded up to the
+    /// nearest second.
+    ///
+    /// Returns the number of keys deleted. Will be `0` if no key starts with `prefix`
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if:
+    /// - the `prefix` is empty
+    /// - the `timeout` is zero or > `u32::max`
+    ///
+    /// [`struct@Error`] of kind [`ServiceError`](ErrorKind::ServiceError) if:
+    /// - the State Store returns an Error response
+    /// - the State Store supports neither [`ServerCommand::DelPrefix`] nor [`ServerCommand::Keys`],
+    ///   with [`ServiceError::UnknownCommand`]
+    ///
+    /// [`struct@Error`] of kind [`UnexpectedPayload`](ErrorKind::UnexpectedPayload) if the State Store returns a response that isn't valid for the request
+    ///
+    /// [`struct@Error`] of kind [`AIOProtocolError`](ErrorKind::AIOProtocolError) if there are any underlying errors from [`rpc_command::Invoker::invoke`]
+    pub async fn del_prefix(&self, prefix: Vec<u8>, timeout: Duration) -> Result<u64, Error> {
+        if prefix.is_empty() {
+            return Err(Error(ErrorKind::InvalidArgument(
+                "prefix is empty".to_string(),
+            )));
+        }
+        let server_capabilities = self.server_capabilities(timeout).await?;
+
+        if server_capabilities.supports(ServerCommand::DelPrefix) {
+            let response = self
+                .del_internal(
+                    state_store::resp3::Request::DelPrefix { prefix },
+                    None,
+                    timeout,
+                )
+                .await?;
+            return u64::try_from(response.response).map_err(|_| {
+                Error(ErrorKind::UnexpectedPayload(format!(
+                    "{:?}",
+                    response.response
+                )))
+            });
+        }
+
+        if !server_capabilities.supports(ServerCommand::Keys) {
+            return Err(Error(ErrorKind::ServiceError(ServiceError::UnknownCommand)));
+        }
+
+        let keys = state_store::convert_response(
+            self.invoke(
+                state_store::resp3::Request::Keys {
+                    pattern: prefix_pattern(&prefix),
+                },
+                vec![],
+                timeout,
+            )
+            .await?,
+            |payload| match payload {
+                state_store::resp3::Response::Keys(keys) => Ok(keys),
+                _ => Err(()),
+            },
+        )?
+        .response;
+
+        let mut deleted = 0;
+        for key in keys {
+            // Keys deleted by another client in the meantime are not counted
+            if self.del(key, None, timeout).await?.response == 1 {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
     async fn del_internal(
         &self,
         request: state_store::resp3::Request,
         fencing_token: Option<HybridLogicalClock>,
         timeout: Duration,
     ) -> Result<state_store::Response<i64>, Error> {
-        let mut request_builder = rpc_command::invoker::RequestBuilder::default();
-        request_builder
-            .payload(request)
-            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
-            .timeout(timeout);
+        let mut custom_user_data = vec![];
         if let Some(ft) = fencing_token {
-            request_builder.custom_user_data(vec![(
-                FENCING_TOKEN_USER_PROPERTY.to_string(),
-                ft.to_string(),
-            )]);
+            custom_user_data.push((FENCING_TOKEN_USER_PROPERTY.to_string(), ft.to_string()));
         }
-        let request = request_builder
-            .build()
-            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
         state_store::convert_response(
-            self.invoker
-                .invoke(request)
-                .await
-                .map_err(ErrorKind::from)?,
+            self.invoke(request, custom_user_data, timeout).await?,
             |payload| match payload {
                 state_store::resp3::Response::NotFound => Ok(0),
                 state_store::resp3::Response::NotApplied => Ok(-1),
@@ -402,21 +870,16 @@ impl Client {
         timeout: Duration,
     ) -> Result<state_store::Response<()>, Error> {
         // Send invoke request for observe
-        let request = rpc_command::invoker::RequestBuilder::default()
-            .payload(state_store::resp3::Request::KeyNotify {
-                key: key.clone(),
-                options: state_store::resp3::KeyNotifyOptions { stop: false },
-            })
-            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
-            .timeout(timeout)
-            .build()
-            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
-
         state_store::convert_response(
-            self.invoker
-                .invoke(request)
-                .await
-                .map_err(ErrorKind::from)?,
+            self.invoke(
+                state_store::resp3::Request::KeyNotify {
+                    key,
+                    options: state_store::resp3::KeyNotifyOptions { stop: false },
+                },
+                vec![],
+                timeout,
+            )
+            .await?,
             |payload| match payload {
                 state_store::resp3::Response::Ok => Ok(()),
                 _ => Err(()),
@@ -438,7 +901,8 @@ impl Client {
     /// persisted across a nonclean session, the state store internally removes
     /// any key observations when a given client disconnects. This is a known
     /// limitation of the service, see [here](https://learn.microsoft.com/azure/iot-operations/create-edge-apps/concept-about-state-store-protocol#keynotify-notification-topics-and-lifecycle)
-    /// for more information
+    /// for more information. Enable [`reobserve_on_reconnect`](ClientOptionsBuilder::reobserve_on_reconnect)
+    /// to have the client do this automatically.
     ///
     /// </div>
     ///
@@ -524,20 +988,16 @@ impl Client {
             )));
         }
         // Send invoke request for unobserve
-        let request = rpc_command::invoker::RequestBuilder::default()
-            .payload(state_store::resp3::Request::KeyNotify {
-                key: key.clone(),
-                options: state_store::resp3::KeyNotifyOptions { stop: true },
-            })
-            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
-            .timeout(timeout)
-            .build()
-            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
         match state_store::convert_response(
-            self.invoker
-                .invoke(request)
-                .await
-                .map_err(ErrorKind::from)?,
+            self.invoke(
+                state_store::resp3::Request::KeyNotify {
+                    key: key.clone(),
+                    options: state_store::resp3::KeyNotifyOptions { stop: true },
+                },
+                vec![],
+                timeout,
+            )
+            .await?,
             |payload| match payload {
                 state_store::resp3::Response::Ok => Ok(true),
                 state_store::resp3::Response::NotFound => Ok(false),
@@ -568,6 +1028,68 @@ impl Client {
         session_monitor.disconnected().await;
     }
 
+    /// Observes the keys being observed again each time the session reconnects, until the
+    /// [`state_store::Client`] is dropped. Observations that can't be restored are ended.
+    async fn reobserve_on_reconnect_loop(
+        invoker: Weak<StateStoreInvoker>,
+        notification_dispatcher: Arc<
+            Dispatcher<(state_store::KeyNotification, Option<AckToken>), String>,
+        >,
+        session_monitor: SessionMonitor,
+    ) {
+        loop {
+            Self::notify_on_disconnection(&session_monitor).await;
+            session_monitor.connected().await;
+            let Some(invoker) = invoker.upgrade() else {
+                break;
+            };
+            let encoded_key_names = notification_dispatcher.get_all_receiver_ids();
+            if !encoded_key_names.is_empty() {
+                log::info!(
+                    "Session reconnected. Observing {} State Store keys again",
+                    encoded_key_names.len()
+                );
+            }
+            let results =
+                futures::future::join_all(encoded_key_names.iter().map(|encoded_key_name| {
+                    let key = HEXUPPER
+                        .decode(encoded_key_name.as_bytes())
+                        .expect("Observed key names are always hex encoded");
+                    Self::reobserve(&invoker, key)
+                }))
+                .await;
+            for (encoded_key_name, result) in encoded_key_names.iter().zip(results) {
+                if let Err(e) = result {
+                    log::warn!(
+                        "Error observing key {encoded_key_name:?} again, ending its observation: {e}"
+                    );
+                    // This closes the associated notification channel
+                    notification_dispatcher.unregister_receiver(encoded_key_name);
+                }
+            }
+            drop(invoker);
+        }
+    }
+
+    /// Sends the `KeyNotify` request to observe `key` again after the session reconnects
+    async fn reobserve(invoker: &StateStoreInvoker, key: Vec<u8>) -> Result<(), Error> {
+        let request = rpc_command::invoker::RequestBuilder::default()
+            .payload(state_store::resp3::Request::KeyNotify {
+                key,
+                options: state_store::resp3::KeyNotifyOptions { stop: false },
+            })
+            .map_err(|e| ErrorKind::SerializationError(e.to_string()))? // this can't fail
+            .timeout(REOBSERVE_TIMEOUT)
+            .build()
+            .map_err(|e| ErrorKind::InvalidArgument(e.to_string()))?;
+        let response = invoker.invoke(request).await.map_err(ErrorKind::from)?;
+        state_store::convert_response(response, |payload| match payload {
+            state_store::resp3::Response::Ok => Ok(()),
+            _ => Err(()),
+        })
+        .map(|_| ())
+    }
+
     async fn receive_key_notification_loop(
         shutdown_notifier: Arc<Notify>,
         mut receiver: telemetry::Receiver<state_store::resp3::Operation>,
@@ -575,6 +1097,7 @@ impl Client {
             Dispatcher<(state_store::KeyNotification, Option<AckToken>), String>,
         >,
         session_monitor: SessionMonitor,
+        reobserve_on_reconnect: bool,
     ) {
         let mut shutdown_attempt_count = 0;
         loop {
@@ -597,9 +1120,13 @@ impl Client {
                     }
                   },
                   () = Self::notify_on_disconnection(&session_monitor) => {
-                    log::warn!("Session disconnected. Dropping State Store key observations as they won't receive any more notifications and must be recreated");
-                    // This closes all associated notification channels
-                    notification_dispatcher.unregister_all();
+                    if reobserve_on_reconnect {
+                        log::warn!("Session disconnected. State Store key observations will be restored when the session reconnects");
+                    } else {
+                        log::warn!("Session disconnected. Dropping State Store key observations as they won't receive any more notifications and must be recreated");
+                        // This closes all associated notification channels
+                        notification_dispatcher.unregister_all();
+                    }
                   },
                   msg = receiver.recv() => {
                     if let Some(m) = msg {
@@ -660,6 +1187,74 @@ impl Client {
     }
 }
 
+/// Claims command requests for [`rpc_command::Executor`] replicas sharing a service group by
+/// setting the claim key only if it does not already exist in the State Store.
+#[async_trait::async_trait]
+impl rpc_command::executor::DistributedDedupStore for Client {
+    async fn try_claim(
+        &self,
+        key: String,
+        ttl: Duration,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let response = self
+            .set(
+                key.into_bytes(),
+                b"claimed".to_vec(),
+                DISTRIBUTED_DEDUP_CLAIM_TIMEOUT,
+                None,
+                SetOptions {
+                    set_condition: state_store::SetCondition::OnlyIfDoesNotExist,
+                    expires: Some(ttl),
+                    ..SetOptions::default()
+                },
+            )
+            .await?;
+        Ok(response.response)
+    }
+}
+
+/// Persists the last sequence numbers sent by [`telemetry::Sender`]s in the State Store, with
+/// the value of each key set to the sequence number and the sender instance id separated by a space.
+#[async_trait::async_trait]
+impl telemetry::sender::SequenceNumberStore for Client {
+    async fn load(
+        &self,
+        key: String,
+    ) -> Result<Option<(String, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+        let response = self
+            .get(key.into_bytes(), TELEMETRY_SEQUENCE_NUMBER_TIMEOUT)
+            .await?;
+        let Some(value) = response.response else {
+            return Ok(None);
+        };
+        let value = String::from_utf8(value)?;
+        let (sequence_number, sender_instance_id) = value
+            .split_once(' ')
+            .ok_or_else(|| format!("Invalid telemetry sequence number value: {value}"))?;
+        Ok(Some((
+            sender_instance_id.to_string(),
+            sequence_number.parse()?,
+        )))
+    }
+
+    async fn save(
+        &self,
+        key: String,
+        sender_instance_id: String,
+        sequence_number: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.set(
+            key.into_bytes(),
+            format!("{sequence_number} {sender_instance_id}").into_bytes(),
+            TELEMETRY_SEQUENCE_NUMBER_TIMEOUT,
+            None,
+            SetOptions::default(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
 impl Drop for Client {
     fn drop(&mut self) {
         self.shutdown_notifier.notify_one();
@@ -667,6 +1262,20 @@ impl Drop for Client {
     }
 }
 
+/// Builds a `KEYS` pattern matching all keys that start with `prefix`, escaping any glob
+/// characters in the prefix
+fn prefix_pattern(prefix: &[u8]) -> Vec<u8> {
+    let mut pattern = Vec::with_capacity(prefix.len() + 1);
+    for byte in prefix {
+        if matches!(byte, b'*' | b'?' | b'[' | b']' | b'\\') {
+            pattern.push(b'\\');
+        }
+        pattern.push(*byte);
+    }
+    pattern.push(b'*');
+    pattern
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -675,6 +1284,8 @@ mod tests {
     use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
     use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
     use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+    use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+    use test_case::test_case;
 
     use crate::state_store::{Error, ErrorKind, SetOptions};
 
@@ -695,6 +1306,52 @@ mod tests {
         Session::new(session_options).unwrap()
     }
 
+    #[test_case(Duration::ZERO; "zero")]
+    #[test_case(Duration::from_secs(u64::from(u32::MAX) + 1); "more_than_u32_max_seconds")]
+    #[test_case(Duration::from_secs(u64::from(u32::MAX)) + Duration::from_millis(1); "rounded_up_to_more_than_u32_max_seconds")]
+    #[tokio::test]
+    async fn test_new_invalid_attempt_timeout(attempt_timeout: Duration) {
+        let session = create_session();
+        let result = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            session.create_session_monitor(),
+            super::ClientOptionsBuilder::default()
+                .attempt_timeout(attempt_timeout)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            result.err().unwrap(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_invalid_timeout_with_attempt_timeout() {
+        let session = create_session();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            session.create_session_monitor(),
+            super::ClientOptionsBuilder::default()
+                .attempt_timeout(Duration::from_secs(1))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .get(
+                b"testKey".to_vec(),
+                Duration::from_secs(u64::from(u32::MAX) + 1),
+            )
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_set_empty_key() {
         let session = create_session();
@@ -741,6 +1398,54 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_get_many_empty_key() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let mut responses = state_store_client
+            .get_many(vec![vec![]], Duration::from_secs(1))
+            .await;
+        assert_eq!(responses.len(), 1);
+        assert!(matches!(
+            responses.pop().unwrap().unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_set_many_empty_key() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let mut responses = state_store_client
+            .set_many(
+                vec![(vec![], b"value".to_vec())],
+                Duration::from_secs(1),
+                SetOptions::default(),
+            )
+            .await;
+        assert_eq!(responses.len(), 1);
+        assert!(matches!(
+            responses.pop().unwrap().unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_del_empty_key() {
         let session = create_session();
@@ -920,6 +1625,146 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_del_if_equal_empty_key() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_if_equal(vec![], b"testValue".to_vec(), Duration::from_secs(1))
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_del_if_equal_invalid_timeout() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_if_equal(
+                b"testKey".to_vec(),
+                b"testValue".to_vec(),
+                Duration::from_secs(0),
+            )
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_del_if_version_empty_key() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_if_version(vec![], HybridLogicalClock::new(), Duration::from_secs(1))
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_del_if_version_invalid_timeout() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_if_version(
+                b"testKey".to_vec(),
+                HybridLogicalClock::new(),
+                Duration::from_secs(0),
+            )
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_del_prefix_empty_prefix() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_prefix(vec![], Duration::from_secs(1))
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_del_prefix_invalid_timeout() {
+        let session = create_session();
+        let session_monitor = session.create_session_monitor();
+        let managed_client = session.create_managed_client();
+        let state_store_client = super::Client::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            managed_client,
+            session_monitor,
+            super::ClientOptionsBuilder::default().build().unwrap(),
+        )
+        .unwrap();
+        let response = state_store_client
+            .del_prefix(b"testPrefix".to_vec(), Duration::from_secs(0))
+            .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            Error(ErrorKind::InvalidArgument(_))
+        ));
+    }
+
+    #[test_case(b"tenant1/", b"tenant1/*"; "plain")]
+    #[test_case(b"a*b?c[d]e\\", b"a\\*b\\?c\\[d\\]e\\\\*"; "glob_characters_escaped")]
+    fn test_prefix_pattern(prefix: &[u8], expected: &[u8]) {
+        assert_eq!(super::prefix_pattern(prefix), expected);
+    }
+
     #[tokio::test]
     async fn test_observe_invalid_timeout() {
         let session = create_session();
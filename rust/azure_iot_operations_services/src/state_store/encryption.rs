@@ -0,0 +1,912 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Client-side encryption of values stored in the State Store.
+//!
+//! [`EncryptedStateStore`] wraps a [`state_store::Client`] and encrypts values with AES-256-GCM
+//! before they are sent to the State Store Service, and decrypts them when they are read or
+//! observed. State Store keys are **not** encrypted, since the State Store Service needs them to
+//! look up values, so keys must not contain sensitive data.
+//!
+//! Encryption keys are provided by a [`KeyProvider`], such as a [`FileMountKeyProvider`] that
+//! reads keys from a mounted secret and picks up changes to it.
+//!
+//! An encrypted value has the following layout:
+//!
+//! | Field | Length |
+//! | --- | --- |
+//! | Magic bytes `AIOE` | 4 |
+//! | Format version (`1`) | 1 |
+//! | Key id length | 1 |
+//! | Key id (UTF-8) | Key id length |
+//! | Nonce | 12 |
+//! | Ciphertext and authentication tag | Value length + 16 |
+//!
+//! The State Store key and the header are authenticated with the value, so an encrypted value
+//! that is modified or copied to another key fails to decrypt.
+//!
+//! To use this client, the `state_store_encryption` feature must be enabled.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload},
+};
+use azure_iot_operations_mqtt::token::AckToken;
+use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+use data_encoding::BASE64;
+use derive_builder::Builder;
+use thiserror::Error;
+
+use crate::state_store::{
+    self, KeyNotification, KeyObservation, Operation, SetCondition, SetOptions,
+};
+
+/// Magic bytes at the start of every encrypted value
+const MAGIC: &[u8] = b"AIOE";
+/// Version of the encrypted value format
+const FORMAT_VERSION: u8 = 1;
+/// Length of an AES-256 key, in bytes
+const KEY_LENGTH: usize = 32;
+/// Length of an AES-GCM nonce, in bytes
+const NONCE_LENGTH: usize = 12;
+/// Name of the file in a [`FileMountKeyProvider`] directory holding the id of the current key
+pub const CURRENT_KEY_ID_FILE: &str = "current-key-id";
+
+/// Represents an error that occurred in the encrypting State Store client.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct Error(#[from] ErrorKind);
+
+impl Error {
+    /// Returns the [`ErrorKind`] of the error.
+    #[must_use]
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0
+    }
+}
+
+impl From<state_store::Error> for Error {
+    fn from(error: state_store::Error) -> Self {
+        ErrorKind::StateStoreError(error).into()
+    }
+}
+
+/// Represents the kinds of errors that occur in the encrypting State Store client.
+#[derive(Error, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ErrorKind {
+    /// An error occurred in the State Store. See [`struct@state_store::Error`] for more information.
+    #[error(transparent)]
+    StateStoreError(state_store::Error),
+    /// An argument provided for a request was invalid.
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// An encryption key is invalid.
+    #[error("invalid encryption key: {0}")]
+    InvalidKey(String),
+    /// An encryption key could not be loaded, or the key a value was encrypted with is not known.
+    #[error("encryption key unavailable: {0}")]
+    KeyUnavailable(String),
+    /// A value could not be decrypted, because it was modified, was copied from another State
+    /// Store key, or is not a valid encrypted value.
+    #[error("value could not be decrypted: {0}")]
+    DecryptionFailed(String),
+    /// A value is not encrypted, and plaintext values are not allowed. See
+    /// [`allow_plaintext_values`](EncryptedStateStoreOptionsBuilder::allow_plaintext_values).
+    #[error("value is not encrypted")]
+    PlaintextValue,
+}
+
+/// An AES-256 key used to encrypt State Store values, identified by an id that is stored with
+/// every value it encrypts.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    key: [u8; KEY_LENGTH],
+}
+
+impl EncryptionKey {
+    /// Create a new [`EncryptionKey`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidKey`](ErrorKind::InvalidKey) if:
+    /// - the `id` is empty or longer than 255 bytes
+    /// - the `key` is not 32 bytes long
+    pub fn new(id: impl Into<String>, key: &[u8]) -> Result<Self, Error> {
+        let id = id.into();
+        if id.is_empty() || id.len() > usize::from(u8::MAX) {
+            return Err(ErrorKind::InvalidKey(format!(
+                "key id must be between 1 and 255 bytes, but is {} bytes",
+                id.len()
+            ))
+            .into());
+        }
+        let key = key.try_into().map_err(|_| {
+            ErrorKind::InvalidKey(format!(
+                "key '{id}' must be {KEY_LENGTH} bytes, but is {} bytes",
+                key.len()
+            ))
+        })?;
+        Ok(Self { id, key })
+    }
+
+    /// Returns the id of the key
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Provides the keys used to encrypt and decrypt State Store values.
+///
+/// To rotate keys, make a new key current while still providing the previous key, so that values
+/// encrypted with the previous key can be decrypted until they have been re-encrypted with
+/// [`EncryptedStateStore::reencrypt`] or overwritten.
+pub trait KeyProvider: Send + Sync {
+    /// Returns the key used to encrypt new values.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`KeyUnavailable`](ErrorKind::KeyUnavailable) if there is no
+    /// current key
+    fn current_key(&self) -> Result<EncryptionKey, Error>;
+
+    /// Returns the key with the given `id`, or [`None`] if it is not known.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`KeyUnavailable`](ErrorKind::KeyUnavailable) if the keys could
+    /// not be loaded
+    fn key(&self, id: &str) -> Result<Option<EncryptionKey>, Error>;
+}
+
+/// A [`KeyProvider`] with a fixed set of keys.
+#[derive(Clone, Debug)]
+pub struct StaticKeyProvider {
+    current_key: EncryptionKey,
+    previous_keys: Vec<EncryptionKey>,
+}
+
+impl StaticKeyProvider {
+    /// Create a new [`StaticKeyProvider`] that encrypts with `current_key`, and can also decrypt
+    /// values encrypted with any of the `previous_keys`.
+    #[must_use]
+    pub fn new(current_key: EncryptionKey, previous_keys: Vec<EncryptionKey>) -> Self {
+        Self {
+            current_key,
+            previous_keys,
+        }
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key(&self) -> Result<EncryptionKey, Error> {
+        Ok(self.current_key.clone())
+    }
+
+    fn key(&self, id: &str) -> Result<Option<EncryptionKey>, Error> {
+        Ok(std::iter::once(&self.current_key)
+            .chain(&self.previous_keys)
+            .find(|key| key.id == id)
+            .cloned())
+    }
+}
+
+/// A [`KeyProvider`] that reads keys from the files in a directory, such as a mounted Kubernetes
+/// secret.
+///
+/// Each file in the directory is a key, named by its file name, containing either the 32 raw
+/// bytes of the key or the key encoded as base64. The [`CURRENT_KEY_ID_FILE`] file contains the
+/// id of the key used to encrypt new values. Files starting with `.` are ignored, as are files
+/// that don't contain a valid key.
+///
+/// The keys are read again whenever the modification time of the directory changes, so that
+/// updates to a mounted secret are picked up without restarting the application.
+#[derive(Debug)]
+pub struct FileMountKeyProvider {
+    directory: PathBuf,
+    key_ring: Mutex<Option<KeyRing>>,
+}
+
+/// The keys read from a [`FileMountKeyProvider`] directory
+#[derive(Debug)]
+struct KeyRing {
+    modified: SystemTime,
+    current_key_id: Option<String>,
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl FileMountKeyProvider {
+    /// Create a new [`FileMountKeyProvider`] reading keys from `directory`.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`KeyUnavailable`](ErrorKind::KeyUnavailable) if the directory
+    /// can't be read or has no valid current key
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, Error> {
+        let provider = Self {
+            directory: directory.into(),
+            key_ring: Mutex::new(None),
+        };
+        // Fail early on a misconfigured mount instead of on the first operation
+        provider.current_key()?;
+        Ok(provider)
+    }
+
+    /// Runs `f` on the keys, reading them again first if the directory has changed
+    fn with_key_ring<T>(&self, f: impl FnOnce(&KeyRing) -> T) -> Result<T, Error> {
+        let modified = fs::metadata(&self.directory)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| {
+                ErrorKind::KeyUnavailable(format!(
+                    "could not read key directory {}: {e}",
+                    self.directory.display()
+                ))
+            })?;
+
+        let mut key_ring = self.key_ring.lock().unwrap();
+        if key_ring
+            .as_ref()
+            .is_none_or(|key_ring| key_ring.modified != modified)
+        {
+            *key_ring = Some(KeyRing::read(&self.directory, modified)?);
+        }
+        Ok(f(key_ring.as_ref().expect("key ring was just read")))
+    }
+}
+
+impl KeyRing {
+    fn read(directory: &Path, modified: SystemTime) -> Result<Self, Error> {
+        let unavailable = |e: std::io::Error| {
+            ErrorKind::KeyUnavailable(format!(
+                "could not read key directory {}: {e}",
+                directory.display()
+            ))
+        };
+
+        let mut current_key_id = None;
+        let mut keys = HashMap::new();
+        for entry in fs::read_dir(directory).map_err(unavailable)? {
+            let path = entry.map_err(unavailable)?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // Kubernetes secret mounts contain hidden directories and links used for atomic updates
+            if file_name.starts_with('.') || !path.is_file() {
+                continue;
+            }
+            let contents = fs::read(&path).map_err(unavailable)?;
+            if file_name == CURRENT_KEY_ID_FILE {
+                current_key_id = Some(String::from_utf8_lossy(&contents).trim().to_string());
+                continue;
+            }
+            match parse_key_file(file_name, &contents) {
+                Ok(key) => {
+                    keys.insert(key.id.clone(), key);
+                }
+                Err(e) => log::warn!("Ignoring key file {}: {e}", path.display()),
+            }
+        }
+
+        Ok(Self {
+            modified,
+            current_key_id,
+            keys,
+        })
+    }
+}
+
+/// Parses a key file containing either the raw key or the key encoded as base64
+fn parse_key_file(id: &str, contents: &[u8]) -> Result<EncryptionKey, Error> {
+    if contents.len() == KEY_LENGTH {
+        return EncryptionKey::new(id, contents);
+    }
+    let decoded = BASE64
+        .decode(contents.trim_ascii())
+        .map_err(|e| ErrorKind::InvalidKey(format!("key '{id}' is not raw or base64: {e}")))?;
+    EncryptionKey::new(id, &decoded)
+}
+
+impl KeyProvider for FileMountKeyProvider {
+    fn current_key(&self) -> Result<EncryptionKey, Error> {
+        self.with_key_ring(|key_ring| {
+            let Some(current_key_id) = &key_ring.current_key_id else {
+                return Err(ErrorKind::KeyUnavailable(format!(
+                    "{CURRENT_KEY_ID_FILE} file not found in {}",
+                    self.directory.display()
+                ))
+                .into());
+            };
+            key_ring.keys.get(current_key_id).cloned().ok_or_else(|| {
+                ErrorKind::KeyUnavailable(format!("current key '{current_key_id}' not found"))
+                    .into()
+            })
+        })?
+    }
+
+    fn key(&self, id: &str) -> Result<Option<EncryptionKey>, Error> {
+        self.with_key_ring(|key_ring| key_ring.keys.get(id).cloned())
+    }
+}
+
+/// Encrypted State Store Client Options struct
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into))]
+pub struct EncryptedStateStoreOptions {
+    /// If true, values that are not encrypted are returned as they are, so that values written
+    /// before encryption was enabled can still be read. They can be encrypted with
+    /// [`EncryptedStateStore::reencrypt`]. If false, reading a value that is not encrypted
+    /// returns an error of kind [`PlaintextValue`](ErrorKind::PlaintextValue).
+    ///
+    /// Note: a plaintext value that happens to start with the encrypted value magic bytes `AIOE`
+    /// fails to decrypt instead.
+    #[builder(default = "false")]
+    allow_plaintext_values: bool,
+}
+
+/// Encrypts and decrypts values with the keys of a [`KeyProvider`]
+#[derive(Clone)]
+struct ValueCipher {
+    key_provider: Arc<dyn KeyProvider>,
+    allow_plaintext_values: bool,
+}
+
+impl fmt::Debug for ValueCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueCipher")
+            .field("allow_plaintext_values", &self.allow_plaintext_values)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Whether a value is encrypted, and with which key
+#[derive(Debug, PartialEq)]
+enum ValueEncryption {
+    Plaintext,
+    Encrypted { key_id: String },
+}
+
+impl ValueCipher {
+    /// Encrypts `value` for `state_store_key` with the current key
+    fn encrypt(&self, state_store_key: &[u8], value: &[u8]) -> Result<Vec<u8>, Error> {
+        let key = self.key_provider.current_key()?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let mut encrypted =
+            Vec::with_capacity(MAGIC.len() + 2 + key.id.len() + NONCE_LENGTH + value.len() + 16);
+        encrypted.extend_from_slice(MAGIC);
+        encrypted.push(FORMAT_VERSION);
+        // Key id length is validated when the key is created
+        encrypted.push(u8::try_from(key.id.len()).expect("key id is at most 255 bytes"));
+        encrypted.extend_from_slice(key.id.as_bytes());
+        encrypted.extend_from_slice(&nonce);
+
+        let aad = [state_store_key, &encrypted].concat();
+        let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.key))
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| ErrorKind::InvalidArgument("value is too large to encrypt".to_string()))?;
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    /// Decrypts `value` of `state_store_key`, or returns it as-is if it is plaintext and
+    /// plaintext values are allowed
+    fn decrypt(&self, state_store_key: &[u8], value: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let Some(header) = Header::parse(&value)? else {
+            return if self.allow_plaintext_values {
+                Ok(value)
+            } else {
+                Err(ErrorKind::PlaintextValue.into())
+            };
+        };
+        let key = self.key_provider.key(&header.key_id)?.ok_or_else(|| {
+            ErrorKind::KeyUnavailable(format!("key '{}' not found", header.key_id))
+        })?;
+
+        let (header_bytes, ciphertext) = value.split_at(header.length);
+        let aad = [state_store_key, header_bytes].concat();
+        let nonce = Nonce::from_slice(&header_bytes[header.length - NONCE_LENGTH..]);
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.key))
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| {
+                ErrorKind::DecryptionFailed(format!(
+                    "authentication with key '{}' failed",
+                    header.key_id
+                ))
+                .into()
+            })
+    }
+
+    /// Returns whether `value` is encrypted, and with which key
+    fn encryption(value: &[u8]) -> Result<ValueEncryption, Error> {
+        Ok(match Header::parse(value)? {
+            Some(header) => ValueEncryption::Encrypted {
+                key_id: header.key_id,
+            },
+            None => ValueEncryption::Plaintext,
+        })
+    }
+}
+
+/// The header of an encrypted value
+struct Header {
+    key_id: String,
+    /// Length of the header, including the nonce
+    length: usize,
+}
+
+impl Header {
+    /// Parses the header of `value`, or returns [`None`] if it is not an encrypted value
+    fn parse(value: &[u8]) -> Result<Option<Self>, Error> {
+        let Some(rest) = value.strip_prefix(MAGIC) else {
+            return Ok(None);
+        };
+        let malformed = |reason: &str| ErrorKind::DecryptionFailed(reason.to_string());
+
+        let [version, key_id_length, rest @ ..] = rest else {
+            return Err(malformed("header is truncated").into());
+        };
+        if *version != FORMAT_VERSION {
+            return Err(malformed(&format!("unsupported format version {version}")).into());
+        }
+        let key_id_length = usize::from(*key_id_length);
+        if rest.len() < key_id_length + NONCE_LENGTH {
+            return Err(malformed("header is truncated").into());
+        }
+        let key_id = std::str::from_utf8(&rest[..key_id_length])
+            .map_err(|_| malformed("key id is not UTF-8"))?
+            .to_string();
+
+        Ok(Some(Self {
+            key_id,
+            length: MAGIC.len() + 2 + key_id_length + NONCE_LENGTH,
+        }))
+    }
+}
+
+/// A State Store client that encrypts values before they are stored and decrypts them when they
+/// are read or observed.
+///
+/// Values set with this client are authenticated with their State Store key, so values can only
+/// be read through this client with the key they were set with. Since encrypting the same value
+/// twice gives different results, `Set` with
+/// [`OnlyIfEqualOrDoesNotExist`](SetCondition::OnlyIfEqualOrDoesNotExist) and `V Delete` can't
+/// be supported and are not available.
+///
+/// Notes:
+/// Do not call any of the methods of this client after the `state_store` parameter is shutdown.
+/// Calling any of the methods in this implementation after the `state_store` is shutdown results in undefined behavior.
+#[derive(Clone)]
+pub struct EncryptedStateStore {
+    state_store: Arc<state_store::Client>,
+    cipher: ValueCipher,
+}
+
+impl EncryptedStateStore {
+    /// Create a new encrypting State Store client.
+    #[must_use]
+    pub fn new(
+        state_store: Arc<state_store::Client>,
+        key_provider: Arc<dyn KeyProvider>,
+        options: &EncryptedStateStoreOptions,
+    ) -> Self {
+        Self {
+            state_store,
+            cipher: ValueCipher {
+                key_provider,
+                allow_plaintext_values: options.allow_plaintext_values,
+            },
+        }
+    }
+
+    /// Encrypts `value` with the current key and sets it for `key`. See
+    /// [`state_store::Client::set`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if the
+    /// `set_condition` of `options` is
+    /// [`OnlyIfEqualOrDoesNotExist`](SetCondition::OnlyIfEqualOrDoesNotExist)
+    ///
+    /// [`struct@Error`] of kind [`KeyUnavailable`](ErrorKind::KeyUnavailable) if the current key
+    /// can't be provided
+    ///
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Set`
+    /// request fails
+    pub async fn set(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        timeout: Duration,
+        fencing_token: Option<HybridLogicalClock>,
+        options: SetOptions,
+    ) -> Result<state_store::Response<bool>, Error> {
+        if matches!(
+            options.set_condition,
+            SetCondition::OnlyIfEqualOrDoesNotExist
+        ) {
+            return Err(ErrorKind::InvalidArgument(
+                "OnlyIfEqualOrDoesNotExist is not supported for encrypted values".to_string(),
+            )
+            .into());
+        }
+        let value = self.cipher.encrypt(&key, &value)?;
+        Ok(self
+            .state_store
+            .set(key, value, timeout, fencing_token, options)
+            .await?)
+    }
+
+    /// Gets and decrypts the value of `key`. See [`state_store::Client::get`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`KeyUnavailable`](ErrorKind::KeyUnavailable) if the key the
+    /// value was encrypted with can't be provided
+    ///
+    /// [`struct@Error`] of kind [`DecryptionFailed`](ErrorKind::DecryptionFailed) if the value
+    /// can't be decrypted
+    ///
+    /// [`struct@Error`] of kind [`PlaintextValue`](ErrorKind::PlaintextValue) if the value is not
+    /// encrypted and plaintext values are not allowed
+    ///
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Get`
+    /// request fails
+    pub async fn get(
+        &self,
+        key: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<state_store::Response<Option<Vec<u8>>>, Error> {
+        let response = self.state_store.get(key.clone(), timeout).await?;
+        Ok(state_store::Response {
+            version: response.version,
+            response: response
+                .response
+                .map(|value| self.cipher.decrypt(&key, value))
+                .transpose()?,
+        })
+    }
+
+    /// Deletes `key`. See [`state_store::Client::del`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Delete`
+    /// request fails
+    pub async fn del(
+        &self,
+        key: Vec<u8>,
+        fencing_token: Option<HybridLogicalClock>,
+        timeout: Duration,
+    ) -> Result<state_store::Response<i64>, Error> {
+        Ok(self.state_store.del(key, fencing_token, timeout).await?)
+    }
+
+    /// Starts observation of `key`, decrypting the values of `Set` notifications. See
+    /// [`state_store::Client::observe`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the `Observe`
+    /// request fails
+    pub async fn observe(
+        &self,
+        key: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<state_store::Response<EncryptedKeyObservation>, Error> {
+        let response = self.state_store.observe(key, timeout).await?;
+        Ok(state_store::Response {
+            version: response.version,
+            response: EncryptedKeyObservation {
+                key: response.response.key.clone(),
+                observation: response.response,
+                cipher: self.cipher.clone(),
+            },
+        })
+    }
+
+    /// Stops observation of `key`. See [`state_store::Client::unobserve`].
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`StateStoreError`](ErrorKind::StateStoreError) if the
+    /// `Unobserve` request fails
+    pub async fn unobserve(
+        &self,
+        key: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<state_store::Response<bool>, Error> {
+        Ok(self.state_store.unobserve(key, timeout).await?)
+    }
+
+    /// Re-encrypts the value of `key` with the current key, if it is plaintext or was encrypted
+    /// with another key. Use this to finish a key rotation, or to encrypt values written before
+    /// encryption was enabled.
+    ///
+    /// The value is read and set again with two requests, so a change to the value by another
+    /// client between them is overwritten. Provide a `fencing_token` to protect against this.
+    ///
+    /// Returns `true` if the value was re-encrypted, or `false` if the key doesn't exist or its
+    /// value is already encrypted with the current key.
+    ///
+    /// # Errors
+    /// See [`get`](Self::get) and [`set`](Self::set)
+    pub async fn reencrypt(
+        &self,
+        key: Vec<u8>,
+        timeout: Duration,
+        fencing_token: Option<HybridLogicalClock>,
+    ) -> Result<bool, Error> {
+        let Some(value) = self.state_store.get(key.clone(), timeout).await?.response else {
+            return Ok(false);
+        };
+        let current_key_id = self.cipher.key_provider.current_key()?.id;
+        if ValueCipher::encryption(&value)?
+            == (ValueEncryption::Encrypted {
+                key_id: current_key_id,
+            })
+        {
+            return Ok(false);
+        }
+
+        let value = self.cipher.decrypt(&key, value)?;
+        Ok(self
+            .set(key, value, timeout, fencing_token, SetOptions::default())
+            .await?
+            .response)
+    }
+}
+
+/// A struct to manage receiving notifications for a key observed with an [`EncryptedStateStore`]
+#[derive(Debug)]
+pub struct EncryptedKeyObservation {
+    /// The name of the key (for convenience)
+    pub key: Vec<u8>,
+    observation: KeyObservation,
+    cipher: ValueCipher,
+}
+
+impl EncryptedKeyObservation {
+    /// Receives a [`KeyNotification`] with the decrypted value of a `Set`, or [`None`] if there
+    /// will be no more notifications. See [`KeyObservation::recv_notification`].
+    ///
+    /// A notification whose value can't be decrypted is returned as an error, together with its
+    /// [`AckToken`].
+    pub async fn recv_notification(
+        &mut self,
+    ) -> Option<(Result<KeyNotification, Error>, Option<AckToken>)> {
+        let (mut notification, ack_token) = self.observation.recv_notification().await?;
+        if let Operation::Set(value) = notification.operation {
+            match self.cipher.decrypt(&notification.key, value) {
+                Ok(value) => notification.operation = Operation::Set(value),
+                Err(e) => return Some((Err(e), ack_token)),
+            }
+        }
+        Some((Ok(notification), ack_token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const STATE_STORE_KEY: &[u8] = b"recipe";
+    const VALUE: &[u8] = b"secret sauce";
+
+    fn key(id: &str, byte: u8) -> EncryptionKey {
+        EncryptionKey::new(id, &[byte; KEY_LENGTH]).unwrap()
+    }
+
+    fn cipher(
+        current_key: EncryptionKey,
+        previous_keys: Vec<EncryptionKey>,
+        allow_plaintext_values: bool,
+    ) -> ValueCipher {
+        ValueCipher {
+            key_provider: Arc::new(StaticKeyProvider::new(current_key, previous_keys)),
+            allow_plaintext_values,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = cipher(key("k1", 1), vec![], false);
+        let encrypted = cipher.encrypt(STATE_STORE_KEY, VALUE).unwrap();
+
+        assert!(encrypted.starts_with(MAGIC));
+        assert!(!encrypted.windows(VALUE.len()).any(|w| w == VALUE));
+        assert_ne!(encrypted, cipher.encrypt(STATE_STORE_KEY, VALUE).unwrap());
+        assert_eq!(
+            ValueCipher::encryption(&encrypted).unwrap(),
+            ValueEncryption::Encrypted {
+                key_id: "k1".to_string()
+            }
+        );
+        assert_eq!(cipher.decrypt(STATE_STORE_KEY, encrypted).unwrap(), VALUE);
+    }
+
+    #[test]
+    fn empty_value_round_trip() {
+        let cipher = cipher(key("k1", 1), vec![], false);
+        let encrypted = cipher.encrypt(STATE_STORE_KEY, b"").unwrap();
+        assert!(
+            cipher
+                .decrypt(STATE_STORE_KEY, encrypted)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test_case(|v| { let last = v.len() - 1; v[last] ^= 1; }; "ciphertext")]
+    #[test_case(|v| v[10] ^= 1; "nonce")]
+    #[test_case(|v| v[6] = b'x'; "key id")]
+    #[test_case(|v| v.truncate(20); "truncated")]
+    fn tampered_value_fails(tamper: fn(&mut Vec<u8>)) {
+        let cipher = cipher(key("k1", 1), vec![key("x1", 2)], false);
+        let mut encrypted = cipher.encrypt(STATE_STORE_KEY, VALUE).unwrap();
+        tamper(&mut encrypted);
+        assert!(matches!(
+            cipher
+                .decrypt(STATE_STORE_KEY, encrypted)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::DecryptionFailed(_)
+        ));
+    }
+
+    #[test]
+    fn value_copied_to_other_key_fails() {
+        let cipher = cipher(key("k1", 1), vec![], false);
+        let encrypted = cipher.encrypt(STATE_STORE_KEY, VALUE).unwrap();
+        assert!(matches!(
+            cipher.decrypt(b"other", encrypted).unwrap_err().kind(),
+            ErrorKind::DecryptionFailed(_)
+        ));
+    }
+
+    #[test]
+    fn unknown_key_fails() {
+        let encrypted = cipher(key("k1", 1), vec![], false)
+            .encrypt(STATE_STORE_KEY, VALUE)
+            .unwrap();
+        assert!(matches!(
+            cipher(key("k2", 2), vec![], false)
+                .decrypt(STATE_STORE_KEY, encrypted)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::KeyUnavailable(_)
+        ));
+    }
+
+    #[test]
+    fn plaintext_value() {
+        assert!(matches!(
+            cipher(key("k1", 1), vec![], false)
+                .decrypt(STATE_STORE_KEY, VALUE.to_vec())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PlaintextValue
+        ));
+        assert_eq!(
+            cipher(key("k1", 1), vec![], true)
+                .decrypt(STATE_STORE_KEY, VALUE.to_vec())
+                .unwrap(),
+            VALUE
+        );
+        assert_eq!(
+            ValueCipher::encryption(VALUE).unwrap(),
+            ValueEncryption::Plaintext
+        );
+    }
+
+    #[test]
+    fn rotation() {
+        let old_cipher = cipher(key("k1", 1), vec![], false);
+        let encrypted = old_cipher.encrypt(STATE_STORE_KEY, VALUE).unwrap();
+
+        // After rotation the old key is only used to decrypt, and values are re-encrypted with
+        // the new key
+        let new_cipher = cipher(key("k2", 2), vec![key("k1", 1)], false);
+        let decrypted = new_cipher.decrypt(STATE_STORE_KEY, encrypted).unwrap();
+        let reencrypted = new_cipher.encrypt(STATE_STORE_KEY, &decrypted).unwrap();
+        assert_eq!(
+            ValueCipher::encryption(&reencrypted).unwrap(),
+            ValueEncryption::Encrypted {
+                key_id: "k2".to_string()
+            }
+        );
+        assert_eq!(
+            new_cipher
+                .decrypt(STATE_STORE_KEY, reencrypted.clone())
+                .unwrap(),
+            VALUE
+        );
+        assert!(old_cipher.decrypt(STATE_STORE_KEY, reencrypted).is_err());
+    }
+
+    #[test_case("", KEY_LENGTH; "empty id")]
+    #[test_case(&"k".repeat(256), KEY_LENGTH; "long id")]
+    #[test_case("k1", 16; "short key")]
+    fn invalid_key(id: &str, length: usize) {
+        assert!(matches!(
+            EncryptionKey::new(id, &vec![0; length]).unwrap_err().kind(),
+            ErrorKind::InvalidKey(_)
+        ));
+    }
+
+    #[test]
+    fn key_debug_redacted() {
+        assert!(!format!("{:?}", key("k1", 0xAB)).contains("171"));
+    }
+
+    #[test]
+    fn file_mount_key_provider() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("k1"), [1; KEY_LENGTH]).unwrap();
+        fs::write(
+            directory.path().join("k2"),
+            format!("{}\n", BASE64.encode(&[2; KEY_LENGTH])),
+        )
+        .unwrap();
+        fs::write(directory.path().join("not-a-key"), "hello").unwrap();
+        fs::write(directory.path().join(CURRENT_KEY_ID_FILE), "k1\n").unwrap();
+
+        let provider = FileMountKeyProvider::new(directory.path()).unwrap();
+        assert_eq!(provider.current_key().unwrap().id(), "k1");
+        assert_eq!(provider.key("k2").unwrap().unwrap().key, [2; KEY_LENGTH]);
+        assert!(provider.key("not-a-key").unwrap().is_none());
+
+        // Keys are only read again once the directory changes, as it does when a mounted secret
+        // is updated
+        fs::write(directory.path().join(CURRENT_KEY_ID_FILE), "k2").unwrap();
+        assert_eq!(provider.current_key().unwrap().id(), "k1");
+        fs::File::open(directory.path())
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(provider.current_key().unwrap().id(), "k2");
+    }
+
+    #[test]
+    fn file_mount_key_provider_missing_current_key() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("k1"), [1; KEY_LENGTH]).unwrap();
+        assert!(matches!(
+            FileMountKeyProvider::new(directory.path())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::KeyUnavailable(_)
+        ));
+
+        fs::write(directory.path().join(CURRENT_KEY_ID_FILE), "k2").unwrap();
+        assert!(matches!(
+            FileMountKeyProvider::new(directory.path())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::KeyUnavailable(_)
+        ));
+    }
+}
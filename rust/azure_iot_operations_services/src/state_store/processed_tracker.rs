@@ -0,0 +1,523 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Tracking of processed telemetry messages in the State Store, for at-least-once processing
+//! without double-processing across restarts.
+//!
+//! A telemetry message that is processed but not acknowledged before the application restarts is
+//! redelivered by the MQTT broker. A [`ProcessedTracker`] records a marker for each message the
+//! application has finished processing in a State Store key, and a [`TrackedReceiver`] consults
+//! it on delivery, acknowledging and skipping messages that were already processed.
+//!
+//! A message is only acknowledged after its marker has been written to the State Store (which
+//! requires the [`telemetry::Receiver`] to not auto-acknowledge messages), so a message is either
+//! redelivered and skipped because its marker was written, or redelivered and processed again
+//! because the application did not finish processing it.
+//!
+//! The marker of a message is the `source` and `id` of its [`CloudEvent`](telemetry::receiver::CloudEvent),
+//! or else its sender id and timestamp (see [`processed_marker`]). Messages without either are
+//! not tracked and are always delivered.
+//!
+//! The markers of the most recently processed messages are kept, bounded by
+//! [`max_markers`](ProcessedTrackerOptionsBuilder::max_markers) and
+//! [`max_value_size`](ProcessedTrackerOptionsBuilder::max_value_size). The markers of the oldest
+//! messages are pruned first, so a message redelivered after more messages than that were
+//! processed is processed again.
+//!
+//! To use this client, the `state_store` feature must be enabled.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use azure_iot_operations_mqtt::token::AckToken;
+use azure_iot_operations_protocol::{
+    common::{aio_protocol_error::AIOProtocolError, payload_serialize::PayloadSerialize},
+    telemetry,
+};
+use derive_builder::Builder;
+use tokio::sync::Notify;
+
+use crate::state_store::{self, Error, ErrorKind, SetOptions};
+
+/// Separator between the markers in the State Store value
+const MARKER_SEPARATOR: char = '\n';
+/// Delay before retrying to write the markers after a failure
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Processed Tracker Options struct
+#[derive(Builder, Clone, Debug)]
+#[builder(setter(into), build_fn(validate = "Self::validate"))]
+pub struct ProcessedTrackerOptions {
+    /// Maximum number of markers of processed messages that are kept. The markers of the oldest
+    /// processed messages are pruned first. Must be greater than zero.
+    #[builder(default = "1000")]
+    max_markers: usize,
+    /// Maximum size in bytes of the State Store value holding the markers. The markers of the
+    /// oldest processed messages are pruned to keep the value within this size. Must be greater
+    /// than zero.
+    #[builder(default = "32 * 1024")]
+    max_value_size: usize,
+    /// Delay after a message is marked processed before the markers are written to the State
+    /// Store, so that the markers of messages processed within the delay are written together.
+    #[builder(default = "Duration::from_millis(100)")]
+    flush_delay: Duration,
+    /// Timeout of the State Store requests.
+    #[builder(default = "Duration::from_secs(10)")]
+    timeout: Duration,
+}
+
+impl ProcessedTrackerOptionsBuilder {
+    /// Validate the [`ProcessedTrackerOptions`].
+    ///
+    /// # Errors
+    /// Returns a `String` describing the error if `max_markers` or `max_value_size` is zero.
+    fn validate(&self) -> Result<(), String> {
+        if self.max_markers == Some(0) {
+            return Err("max_markers must be greater than zero".to_string());
+        }
+        if self.max_value_size == Some(0) {
+            return Err("max_value_size must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the marker identifying a telemetry message as processed, or [`None`] if the message
+/// can't be identified.
+///
+/// The marker is the `source` and `id` of the [`CloudEvent`](telemetry::receiver::CloudEvent) of
+/// the message if it has one, or else its sender id and timestamp.
+#[must_use]
+pub fn processed_marker<T: PayloadSerialize>(
+    message: &telemetry::receiver::Message<T>,
+) -> Option<String> {
+    let marker = if let Ok(cloud_event) = telemetry::receiver::cloud_event_from_telemetry(message) {
+        format!("ce:{}:{}", cloud_event.source, cloud_event.id)
+    } else {
+        let (Some(sender_id), Some(timestamp)) = (&message.sender_id, &message.timestamp) else {
+            return None;
+        };
+        format!("ts:{sender_id}:{timestamp}")
+    };
+    // The marker must not contain the separator to be stored
+    (!marker.contains(MARKER_SEPARATOR)).then_some(marker)
+}
+
+/// Token for a message delivered by a [`TrackedReceiver`], used to mark the message as processed
+/// with [`ProcessedTracker::mark_processed`].
+///
+/// Dropping the token without marking the message processed acknowledges the message without
+/// recording it as processed.
+pub struct ProcessedToken {
+    marker: Option<String>,
+    ack_token: Option<AckToken>,
+}
+
+impl ProcessedToken {
+    /// Returns the marker of the message, or [`None`] if the message can't be identified and
+    /// isn't tracked.
+    #[must_use]
+    pub fn marker(&self) -> Option<&str> {
+        self.marker.as_deref()
+    }
+}
+
+impl std::fmt::Debug for ProcessedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessedToken")
+            .field("marker", &self.marker)
+            .field("ack_token", &self.ack_token.is_some())
+            .finish()
+    }
+}
+
+/// Markers of the most recently processed messages, oldest first
+#[derive(Debug, Default)]
+struct Markers {
+    order: VecDeque<String>,
+    set: HashSet<String>,
+    /// Whether there are markers that haven't been written to the State Store
+    dirty: bool,
+}
+
+impl Markers {
+    /// Parses the markers from a State Store value
+    fn parse(value: &[u8], max_markers: usize) -> Self {
+        let mut markers = Self::default();
+        for marker in String::from_utf8_lossy(value).split(MARKER_SEPARATOR) {
+            if !marker.is_empty() {
+                markers.insert(marker.to_string(), max_markers);
+            }
+        }
+        markers.dirty = false;
+        markers
+    }
+
+    fn contains(&self, marker: &str) -> bool {
+        self.set.contains(marker)
+    }
+
+    /// Adds a marker, pruning the oldest markers to keep at most `max_markers`
+    fn insert(&mut self, marker: String, max_markers: usize) {
+        if !self.set.insert(marker.clone()) {
+            return;
+        }
+        self.order.push_back(marker);
+        while self.order.len() > max_markers {
+            self.pop_oldest();
+        }
+        self.dirty = true;
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some(oldest) = self.order.pop_front() {
+            self.set.remove(&oldest);
+        }
+    }
+
+    /// Returns the State Store value for the markers, pruning the oldest markers to keep the
+    /// value within `max_value_size`
+    fn serialize(&mut self, max_value_size: usize) -> Vec<u8> {
+        let separators = self.order.len().saturating_sub(1);
+        let mut size = self.order.iter().map(String::len).sum::<usize>() + separators;
+        while size > max_value_size {
+            let Some(oldest) = self.order.front() else {
+                break;
+            };
+            // Each marker but the last is followed by a separator
+            size -= oldest.len() + usize::from(self.order.len() > 1);
+            self.pop_oldest();
+        }
+        self.order
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(&MARKER_SEPARATOR.to_string())
+            .into_bytes()
+    }
+}
+
+/// State shared between the [`ProcessedTracker`] and its flush task
+struct Shared {
+    state_store: Arc<state_store::Client>,
+    key: Vec<u8>,
+    options: ProcessedTrackerOptions,
+    markers: Mutex<Markers>,
+    /// Ack tokens of the messages whose markers haven't been written yet, in the order they were
+    /// marked processed
+    pending_acks: Mutex<Vec<AckToken>>,
+    /// Notified when markers need to be written, or when the tracker is dropped
+    flush_requested: Arc<Notify>,
+    /// Serializes writes of the markers so that an older value can't overwrite a newer one
+    flush_lock: tokio::sync::Mutex<()>,
+}
+
+impl Shared {
+    async fn flush(&self) -> Result<(), Error> {
+        let _flush_guard = self.flush_lock.lock().await;
+
+        // Take the markers and ack tokens together so the acks match the markers written
+        let (value, acks) = {
+            let mut markers = self.markers.lock().unwrap();
+            if !markers.dirty {
+                return Ok(());
+            }
+            markers.dirty = false;
+            let acks = std::mem::take(&mut *self.pending_acks.lock().unwrap());
+            (markers.serialize(self.options.max_value_size), acks)
+        };
+
+        if let Err(e) = self
+            .state_store
+            .set(
+                self.key.clone(),
+                value,
+                self.options.timeout,
+                None,
+                SetOptions::default(),
+            )
+            .await
+        {
+            // Keep the ack tokens, to be acked once the markers are written
+            self.markers.lock().unwrap().dirty = true;
+            self.pending_acks.lock().unwrap().splice(0..0, acks);
+            return Err(e);
+        }
+
+        for ack_token in acks {
+            if let Err(e) = ack_token.ack().await {
+                log::warn!("Failed to acknowledge processed telemetry message: {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // Wake the flush task so it exits
+        self.flush_requested.notify_one();
+    }
+}
+
+/// Records markers of processed telemetry messages in a State Store key.
+///
+/// Markers are written to the State Store in the background, after the
+/// [`flush_delay`](ProcessedTrackerOptionsBuilder::flush_delay), and the messages are acknowledged
+/// once their markers are written. Use [`ProcessedTracker::flush`] to write the markers
+/// immediately, e.g. before shutting down.
+///
+/// Only one [`ProcessedTracker`] should use a State Store key at a time.
+#[derive(Clone)]
+pub struct ProcessedTracker {
+    shared: Arc<Shared>,
+}
+
+impl ProcessedTracker {
+    /// Creates a new [`ProcessedTracker`] that records markers in the State Store `key`, loading
+    /// the markers recorded there previously.
+    ///
+    /// # Errors
+    /// [`struct@Error`] of kind [`InvalidArgument`](ErrorKind::InvalidArgument) if the `key` is empty
+    ///
+    /// [`struct@Error`] if the existing markers can't be read, see [`state_store::Client::get`]
+    pub async fn new(
+        state_store: Arc<state_store::Client>,
+        key: Vec<u8>,
+        options: ProcessedTrackerOptions,
+    ) -> Result<Self, Error> {
+        if key.is_empty() {
+            return Err(Error(ErrorKind::InvalidArgument(
+                "key is empty".to_string(),
+            )));
+        }
+        let markers = match state_store
+            .get(key.clone(), options.timeout)
+            .await?
+            .response
+        {
+            Some(value) => Markers::parse(&value, options.max_markers),
+            None => Markers::default(),
+        };
+
+        let flush_requested = Arc::new(Notify::new());
+        let shared = Arc::new(Shared {
+            state_store,
+            key,
+            options,
+            markers: Mutex::new(markers),
+            pending_acks: Mutex::new(Vec::new()),
+            flush_requested: flush_requested.clone(),
+            flush_lock: tokio::sync::Mutex::new(()),
+        });
+        tokio::task::spawn(Self::flush_task(Arc::downgrade(&shared), flush_requested));
+
+        Ok(Self { shared })
+    }
+
+    /// Returns true if the message with the given marker has been marked processed.
+    ///
+    /// # Panics
+    /// If the markers lock is poisoned, which should not be possible
+    #[must_use]
+    pub fn is_processed(&self, marker: &str) -> bool {
+        self.shared.markers.lock().unwrap().contains(marker)
+    }
+
+    /// Marks the message of the [`ProcessedToken`] as processed. The message is acknowledged once
+    /// its marker has been written to the State Store.
+    ///
+    /// Messages that can't be identified are acknowledged without being recorded.
+    ///
+    /// # Panics
+    /// If the markers lock is poisoned, which should not be possible
+    pub fn mark_processed(&self, token: ProcessedToken) {
+        let Some(marker) = token.marker else {
+            // Dropping the ack token acknowledges the message
+            return;
+        };
+        {
+            let mut markers = self.shared.markers.lock().unwrap();
+            markers.insert(marker, self.shared.options.max_markers);
+            // Marked as dirty even if already processed, so that the ack token is acked
+            markers.dirty = true;
+            if let Some(ack_token) = token.ack_token {
+                self.shared.pending_acks.lock().unwrap().push(ack_token);
+            }
+        }
+        self.shared.flush_requested.notify_one();
+    }
+
+    /// Writes the markers of the messages marked processed to the State Store immediately, and
+    /// acknowledges the messages.
+    ///
+    /// # Errors
+    /// [`struct@Error`] if the markers can't be written, see [`state_store::Client::set`]. The
+    /// messages are acknowledged once the markers are written by a later flush.
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.shared.flush().await
+    }
+
+    /// Writes the markers in the background when requested, until the tracker is dropped
+    async fn flush_task(shared: Weak<Shared>, flush_requested: Arc<Notify>) {
+        loop {
+            flush_requested.notified().await;
+            let Some(shared) = shared.upgrade() else {
+                break;
+            };
+            tokio::time::sleep(shared.options.flush_delay).await;
+            if let Err(e) = shared.flush().await {
+                log::warn!("Failed to write processed telemetry markers, retrying: {e}");
+                tokio::time::sleep(RETRY_DELAY).await;
+                shared.flush_requested.notify_one();
+            }
+        }
+    }
+}
+
+/// A telemetry receiver that skips messages that were already marked processed with a
+/// [`ProcessedTracker`].
+///
+/// Messages that were already processed are acknowledged without being delivered.
+///
+/// The [`telemetry::Receiver`] must be created with
+/// [`auto_ack`](telemetry::receiver::OptionsBuilder::auto_ack) disabled, otherwise messages are
+/// acknowledged as soon as they are delivered rather than once they are marked processed.
+pub struct TrackedReceiver<T>
+where
+    T: PayloadSerialize + Send + Sync + 'static,
+{
+    receiver: telemetry::Receiver<T>,
+    tracker: ProcessedTracker,
+    skipped_count: u64,
+}
+
+impl<T> TrackedReceiver<T>
+where
+    T: PayloadSerialize + Send + Sync + 'static,
+{
+    /// Creates a new [`TrackedReceiver`] that delivers the messages of `receiver` that are not
+    /// marked processed in `tracker`.
+    #[must_use]
+    pub fn new(receiver: telemetry::Receiver<T>, tracker: ProcessedTracker) -> Self {
+        Self {
+            receiver,
+            tracker,
+            skipped_count: 0,
+        }
+    }
+
+    /// Returns the [`ProcessedTracker`] used to mark the delivered messages processed.
+    #[must_use]
+    pub fn tracker(&self) -> &ProcessedTracker {
+        &self.tracker
+    }
+
+    /// Returns the number of messages that were skipped because they were already processed.
+    #[must_use]
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped_count
+    }
+
+    /// Receives the next telemetry message that hasn't been processed yet, or [`None`] if there
+    /// will be no more messages. See [`telemetry::Receiver::recv`].
+    ///
+    /// Once the message is processed, its [`ProcessedToken`] must be passed to
+    /// [`ProcessedTracker::mark_processed`] to record it and acknowledge the message.
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if receiving the message fails, see [`telemetry::Receiver::recv`]
+    pub async fn recv(
+        &mut self,
+    ) -> Option<Result<(telemetry::receiver::Message<T>, ProcessedToken), AIOProtocolError>> {
+        loop {
+            let (message, ack_token) = match self.receiver.recv().await? {
+                Ok(received) => received,
+                Err(e) => return Some(Err(e)),
+            };
+            let marker = processed_marker(&message);
+            if let Some(marker) = &marker
+                && self.tracker.is_processed(marker)
+            {
+                log::debug!("Skipping telemetry message {marker} that was already processed");
+                self.skipped_count += 1;
+                // Dropping the ack token acknowledges the message
+                continue;
+            }
+            return Some(Ok((message, ProcessedToken { marker, ack_token })));
+        }
+    }
+
+    /// Shutdown the underlying [`telemetry::Receiver`]. See [`telemetry::Receiver::shutdown`].
+    ///
+    /// # Errors
+    /// [`AIOProtocolError`] if the shutdown fails, see [`telemetry::Receiver::shutdown`]
+    pub async fn shutdown(&mut self) -> Result<(), AIOProtocolError> {
+        self.receiver.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_parse() {
+        let markers = Markers::parse(b"a\nb\n\nc", 10);
+        assert_eq!(markers.order, ["a", "b", "c"]);
+        assert!(markers.contains("b"));
+        assert!(!markers.dirty);
+    }
+
+    #[test]
+    fn test_markers_parse_pruned_to_max_markers() {
+        let markers = Markers::parse(b"a\nb\nc", 2);
+        assert_eq!(markers.order, ["b", "c"]);
+        assert!(!markers.contains("a"));
+    }
+
+    #[test]
+    fn test_markers_insert_prunes_oldest() {
+        let mut markers = Markers::default();
+        markers.insert("a".to_string(), 2);
+        markers.insert("b".to_string(), 2);
+        markers.insert("a".to_string(), 2);
+        assert_eq!(markers.order, ["a", "b"]);
+        markers.insert("c".to_string(), 2);
+        assert_eq!(markers.order, ["b", "c"]);
+        assert!(!markers.contains("a"));
+        assert!(markers.dirty);
+    }
+
+    #[test]
+    fn test_markers_serialize_prunes_to_max_value_size() {
+        let mut markers = Markers::parse(b"aaa\nbb\nc", 10);
+        assert_eq!(markers.serialize(8), b"aaa\nbb\nc");
+        assert_eq!(markers.serialize(7), b"bb\nc");
+        assert_eq!(markers.order, ["bb", "c"]);
+        assert!(!markers.contains("aaa"));
+        assert_eq!(markers.serialize(1), b"c");
+        assert_eq!(markers.serialize(0), b"");
+        assert!(markers.order.is_empty());
+    }
+
+    #[test]
+    fn test_options_validation() {
+        assert!(
+            ProcessedTrackerOptionsBuilder::default()
+                .max_markers(0_usize)
+                .build()
+                .is_err()
+        );
+        assert!(
+            ProcessedTrackerOptionsBuilder::default()
+                .max_value_size(0_usize)
+                .build()
+                .is_err()
+        );
+        assert!(ProcessedTrackerOptionsBuilder::default().build().is_ok());
+    }
+}
@@ -31,6 +31,16 @@ pub(crate) enum Request {
         key: Vec<u8>,
         options: KeyNotifyOptions,
     },
+    DelPrefix {
+        prefix: Vec<u8>,
+    },
+    Keys {
+        pattern: Vec<u8>,
+    },
+    /// A command without arguments, used to detect if the State Store supports the command
+    Probe {
+        command: &'static [u8],
+    },
 }
 
 /// Options for a `Set` Request
@@ -42,15 +52,49 @@ pub struct SetOptions {
     pub expires: Option<Duration>,
     /// Whether the key should be persisted to disk.
     pub persist: bool,
+    /// If set, the `Set` operation will only execute if the State Store has this key and its
+    /// current value is equal to this value, making the `Set` a compare-and-swap.
+    ///
+    /// The State Store has no native compare-and-swap, so the client first deletes the key if its
+    /// value matches (`VDEL`), then sets the new value only if the key does not exist (`NX`). If
+    /// another client sets the key in between, the new value is not set and `false` is returned.
+    /// If the second step fails (e.g. it times out), the key may be left deleted. Observers of the
+    /// key are notified of both the deletion and the `Set`.
+    ///
+    /// The key is set again rather than modified, so `expires` applies from the time of this
+    /// `Set`, and the key does not expire if `expires` is `None`, regardless of the expiry of the
+    /// value being replaced. The fencing token passed to
+    /// [`Client::set`](crate::state_store::Client::set) is sent with both steps, so the `Set` is
+    /// rejected with [`FencingTokenLowerVersion`](crate::state_store::ServiceError::FencingTokenLowerVersion)
+    /// if the key is protected by a newer fencing token, and the new value is protected by it.
+    ///
+    /// Must not be used together with `only_if_not_exists` or a `set_condition` other than
+    /// [`SetCondition::Unconditional`].
+    pub only_if_equal: Option<Vec<u8>>,
+    /// If `true`, the `Set` operation will only execute if the State Store does not have this key
+    /// already. Equivalent to [`SetCondition::OnlyIfDoesNotExist`].
+    ///
+    /// A key that has expired does not exist. When a fencing token is passed to
+    /// [`Client::set`](crate::state_store::Client::set), the new value is protected by it.
+    ///
+    /// Must not be used together with `only_if_equal` or a `set_condition` other than
+    /// [`SetCondition::Unconditional`].
+    pub only_if_not_exists: bool,
 }
 
 /// Condition for a `Set` Request
-#[derive(Clone, Debug, Default)]
+///
+/// Whether a conditional `Set` was applied is indicated by the `bool` returned from
+/// [`Client::set`](crate::state_store::Client::set). Note that the State Store does not support
+/// a condition to only set a key if it already exists.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum SetCondition {
     /// The `Set` operation will only execute if the State Store does not have this key already.
+    /// Sent as the `NX` argument.
     OnlyIfDoesNotExist,
     /// The `Set` operation will only execute if the State Store does not have this key or it has this key and
     /// the value in the State Store is equal to the value provided for this `Set` operation.
+    /// Sent as the `NEX` argument.
     OnlyIfEqualOrDoesNotExist,
     /// The `Set` operation will execute regardless of if the key exists already and regardless of the value
     /// of this key in the State Store.
@@ -80,6 +124,9 @@ impl PayloadSerialize for Request {
                 Request::KeyNotify { key, options } => serialize_key_notify(&key, &options),
                 Request::Del { key } => serialize_del(&key),
                 Request::VDel { key, value } => serialize_v_del(&key, &value),
+                Request::DelPrefix { prefix } => serialize_del_prefix(&prefix),
+                Request::Keys { pattern } => serialize_keys(&pattern),
+                Request::Probe { command } => serialize_probe(command),
             },
             content_type: "application/octet-stream".to_string(),
             format_indicator: FormatIndicator::UnspecifiedBytes,
@@ -208,6 +255,26 @@ fn serialize_v_del(key: &[u8], value: &[u8]) -> Vec<u8> {
     builder.get_buffer()
 }
 
+/// Builds a RESP3 payload to `DELPREFIX(prefix)`
+fn serialize_del_prefix(prefix: &[u8]) -> Vec<u8> {
+    let mut builder = RequestBufferBuilder::new();
+    // All `DELPREFIX` requests have 2 arguments: `DELPREFIX` and the prefix
+    builder.append_array_number(2);
+    builder.append_argument(b"DELPREFIX");
+    builder.append_argument(prefix);
+    builder.get_buffer()
+}
+
+/// Builds a RESP3 payload to `KEYS(pattern)`
+fn serialize_keys(pattern: &[u8]) -> Vec<u8> {
+    let mut builder = RequestBufferBuilder::new();
+    // All `KEYS` requests have 2 arguments: `KEYS` and the pattern
+    builder.append_array_number(2);
+    builder.append_argument(b"KEYS");
+    builder.append_argument(pattern);
+    builder.get_buffer()
+}
+
 fn serialize_key_notify(key: &[u8], options: &KeyNotifyOptions) -> Vec<u8> {
     let mut num_arguments = 2;
     let mut builder = RequestBufferBuilder::new();
@@ -227,6 +294,15 @@ fn serialize_key_notify(key: &[u8], options: &KeyNotifyOptions) -> Vec<u8> {
     builder.get_buffer()
 }
 
+/// Builds a RESP3 payload of a command without any arguments. A State Store that supports the
+/// command responds with an error for the wrong number of arguments rather than an unknown command.
+fn serialize_probe(command: &[u8]) -> Vec<u8> {
+    let mut builder = RequestBufferBuilder::new();
+    builder.append_array_number(1);
+    builder.append_argument(command);
+    builder.get_buffer()
+}
+
 // ----------------------- Response Types -----------------------
 
 #[derive(Clone, Debug, PartialEq)]
@@ -235,8 +311,10 @@ pub(crate) enum Response {
     Ok,
     /// Successful `Get` response
     Value(Vec<u8>),
-    /// Successful `Del` or `VDel` response. Specifies the number of keys deleted
+    /// Successful `Del`, `VDel` or `DelPrefix` response. Specifies the number of keys deleted
     ValuesDeleted(i64),
+    /// Successful `Keys` response. Specifies the keys matching the pattern
+    Keys(Vec<Vec<u8>>),
     /// 'Set' or `VDel` not applied because of conditions provided
     NotApplied,
     /// Key not found for `Get`, `Del`, or `VDel` or parameters caused the operation to not be applied for `Set` or `VDel`
@@ -255,6 +333,7 @@ impl Response {
     const RESPONSE_KEY_NOT_FOUND: &'static [u8] = b":0\r\n";
     const RESPONSE_LENGTH_PREFIX: &'static [u8] = b"$";
     const DELETE_RESPONSE_PREFIX: &'static [u8] = b":";
+    const KEYS_RESPONSE_PREFIX: &'static [u8] = b"*";
 
     fn parse_error(payload: &[u8]) -> Result<Vec<u8>, String> {
         if let Some(err) = payload.strip_prefix(Self::RESPONSE_ERROR_PREFIX)
@@ -304,6 +383,9 @@ impl PayloadSerialize for Response {
                     ))),
                 }
             }
+            _ if payload.starts_with(Self::KEYS_RESPONSE_PREFIX) => Ok(Response::Keys(
+                parse_array(payload, Self::KEYS_RESPONSE_PREFIX)?,
+            )),
             _ => Err(DeserializationError::InvalidPayload(format!(
                 "Unknown response: {payload:?}"
             ))),
@@ -466,6 +548,51 @@ fn parse_value(payload: &[u8], prefix: &[u8]) -> Result<Vec<u8>, String> {
     }
 }
 
+/// Given a payload, parse the array of values that follows the prefix.
+/// Ex: for a payload of "*2\r\n$4\r\nkey1\r\n$4\r\nkey2\r\n", the prefix would be b"*" and the
+/// values returned would be [b"key1", b"key2"].
+fn parse_array(payload: &[u8], prefix: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let Some(stripped_payload) = payload.strip_prefix(prefix) else {
+        return Err(format!(
+            "Invalid payload, must start with {prefix:?}: {payload:?}"
+        ));
+    };
+    let (num_elements, mut current_index) = get_numeric(stripped_payload)?;
+    current_index += 1; // '\r' that triggered get_numeric to return
+    // '\n' should be next
+    if stripped_payload.get(current_index) != Some(&b'\n') {
+        return Err(format!("Invalid format: {payload:?}"));
+    }
+    current_index += 1;
+
+    let mut values = Vec::new();
+    for _ in 0..num_elements {
+        let element = stripped_payload[current_index..]
+            .strip_prefix(b"$")
+            .ok_or_else(|| format!("Invalid array element format: {payload:?}"))?;
+        let (value_len, mut value_index) = get_numeric(element)?;
+        value_index += 1; // '\r' that triggered get_numeric to return
+        // '\n' should be next, then the value and the closing '\r\n'
+        let value_start = value_index + 1;
+        let value_end = value_start + value_len;
+        if element.get(value_index) != Some(&b'\n')
+            || element.get(value_end..value_end + 2) != Some(RESPONSE_SUFFIX)
+        {
+            return Err(format!("Invalid array element format: {payload:?}"));
+        }
+        values.push(element[value_start..value_end].to_vec());
+        // the '$' prefix and the element
+        current_index += 1 + value_end + 2;
+    }
+
+    if current_index != stripped_payload.len() {
+        return Err(format!(
+            "Array length does not match actual number of elements: {payload:?}"
+        ));
+    }
+    Ok(values)
+}
+
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
@@ -485,6 +612,8 @@ mod tests {
     #[test_case(b":0\r\n", &Response::NotFound; "test_del_no_key")] // same as a vdel response
     #[test_case(b"-ERR syntax error\r\n", &Response::Error(b"syntax error".to_vec()); "test_error_response")]
     #[test_case(b"-ERR \r\n", &Response::Error(b"".to_vec()); "test_empty_error_response_success")]
+    #[test_case(b"*0\r\n", &Response::Keys(vec![]); "test_keys_response_empty")]
+    #[test_case(b"*2\r\n$4\r\nkey1\r\n$0\r\n\r\n", &Response::Keys(vec![b"key1".to_vec(), b"".to_vec()]); "test_keys_response")]
 
     fn test_response_deserialization_success(payload: &[u8], expected: &Response) {
         assert_eq!(
@@ -522,6 +651,11 @@ mod tests {
     #[test_case(b"+hello world\r\n"; "Incorrect OK value")]
     #[test_case(b"+"; "OK response too short")]
     #[test_case(b"OK\r\n"; "OK response doesn't start with plus sign")]
+    #[test_case(b"*"; "Keys response too short")]
+    #[test_case(b"*2\r\n$4\r\nkey1\r\n"; "Keys response missing element")]
+    #[test_case(b"*1\r\n$4\r\nkey1\r\n$4\r\nkey2\r\n"; "Keys response extra element")]
+    #[test_case(b"*1\r\n$4\r\nkey12\r\n"; "Keys response element length not accurate")]
+    #[test_case(b"*1\r\n4\r\nkey1\r\n"; "Keys response element missing dollar sign")]
     #[test_case(b"+OK"; "OK response doesn't end with newline")]
 
     fn test_response_deserialization_failures(payload: &[u8]) {
@@ -569,6 +703,15 @@ mod tests {
     #[test_case(SetOptions {expires: Some(Duration::from_millis(10)), ..Default::default()},
         b"*5\r\n$3\r\nSET\r\n$7\r\ntestkey\r\n$9\r\ntestvalue\r\n$2\r\nPX\r\n$2\r\n10\r\n";
         "expires set")]
+    #[test_case(SetOptions {set_condition: SetCondition::OnlyIfDoesNotExist, expires: Some(Duration::from_millis(10)), ..Default::default()},
+        b"*6\r\n$3\r\nSET\r\n$7\r\ntestkey\r\n$9\r\ntestvalue\r\n$2\r\nNX\r\n$2\r\nPX\r\n$2\r\n10\r\n";
+        "OnlyIfDoesNotExist with expires set")]
+    #[test_case(SetOptions {set_condition: SetCondition::OnlyIfEqualOrDoesNotExist, expires: Some(Duration::from_millis(10)), ..Default::default()},
+        b"*6\r\n$3\r\nSET\r\n$7\r\ntestkey\r\n$9\r\ntestvalue\r\n$3\r\nNEX\r\n$2\r\nPX\r\n$2\r\n10\r\n";
+        "OnlyIfEqualOrDoesNotExist with expires set")]
+    #[test_case(SetOptions {set_condition: SetCondition::OnlyIfDoesNotExist, persist: true, ..Default::default()},
+        b"*4\r\n$3\r\nSET\r\n$7\r\ntestkey\r\n$9\r\ntestvalue\r\n$2\r\nNX\r\n";
+        "OnlyIfDoesNotExist with persist")]
     fn test_serialize_set_options(set_options: SetOptions, expected: &[u8]) {
         assert_eq!(
             Request::serialize(Request::Set {
@@ -585,6 +728,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_probe() {
+        assert_eq!(
+            Request::serialize(Request::Probe { command: b"VDEL" })
+                .unwrap()
+                .payload,
+            b"*1\r\n$4\r\nVDEL\r\n".to_vec()
+        );
+    }
+
     #[test]
     fn test_serialize_empty_set() {
         assert_eq!(
@@ -647,4 +800,34 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_serialize_del_prefix() {
+        assert_eq!(
+            Request::serialize(Request::DelPrefix {
+                prefix: b"tenant1/".to_vec()
+            })
+            .unwrap(),
+            SerializedPayload {
+                payload: b"*2\r\n$9\r\nDELPREFIX\r\n$8\r\ntenant1/\r\n".to_vec(),
+                content_type: "application/octet-stream".to_string(),
+                format_indicator: FormatIndicator::UnspecifiedBytes,
+            }
+        );
+    }
+
+    #[test]
+    fn test_serialize_keys() {
+        assert_eq!(
+            Request::serialize(Request::Keys {
+                pattern: b"tenant1/*".to_vec()
+            })
+            .unwrap(),
+            SerializedPayload {
+                payload: b"*2\r\n$4\r\nKEYS\r\n$9\r\ntenant1/*\r\n".to_vec(),
+                content_type: "application/octet-stream".to_string(),
+                format_indicator: FormatIndicator::UnspecifiedBytes,
+            }
+        );
+    }
 }
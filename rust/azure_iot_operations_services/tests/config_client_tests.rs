@@ -0,0 +1,247 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "config")]
+
+use std::{env, sync::Arc, time::Duration};
+
+use env_logger::Builder;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, timeout};
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{Session, SessionExitHandle, SessionOptionsBuilder};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::{config, state_store};
+
+// Test Scenarios:
+// get_typed/set_typed round trip, and get_typed of a value that doesn't parse
+// watch_key holds the default until the key exists, then follows valid updates
+// watch_key keeps the last good value when an invalid value is set
+// watch_key reverts to the default when the key is deleted
+// refresh of a watched and an unwatched key
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Thresholds {
+    max_temperature: u32,
+    enabled: bool,
+}
+
+fn setup_test(
+    client_id: &str,
+) -> Result<
+    (
+        Session,
+        Arc<state_store::Client>,
+        config::Client,
+        SessionExitHandle,
+    ),
+    (),
+> {
+    let _ = Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .filter_module("azure_iot_operations", log::LevelFilter::Warn)
+        .try_init();
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        log::warn!("This test is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return Err(());
+    }
+
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("localhost")
+        .tcp_port(1883u16)
+        .keep_alive(Duration::from_secs(5))
+        .use_tls(false)
+        .build()
+        .unwrap();
+
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()
+        .unwrap();
+
+    let session = Session::new(session_options).unwrap();
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    let state_store_client = Arc::new(
+        state_store::Client::new(
+            application_context,
+            session.create_managed_client(),
+            session.create_session_monitor(),
+            state_store::ClientOptionsBuilder::default()
+                .build()
+                .unwrap(),
+        )
+        .unwrap(),
+    );
+    let config_client = config::Client::new(state_store_client.clone());
+    let exit_handle = session.create_exit_handle();
+    Ok((session, state_store_client, config_client, exit_handle))
+}
+
+#[tokio::test]
+async fn config_get_set_typed_network_tests() {
+    let Ok((session, state_store_client, config_client, exit_handle)) =
+        setup_test("config_get_set_typed_network_tests-rust")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let key = b"config_get_set_typed_network_tests".to_vec();
+
+    let test_task = tokio::task::spawn(async move {
+        let value = Thresholds {
+            max_temperature: 80,
+            enabled: true,
+        };
+        assert!(
+            config_client
+                .set_typed(key.clone(), &value, TIMEOUT)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            config_client
+                .get_typed::<Thresholds>(key.clone(), TIMEOUT)
+                .await
+                .unwrap(),
+            Some(value)
+        );
+
+        // A value of another type can't be parsed
+        assert!(matches!(
+            config_client
+                .get_typed::<Vec<String>>(key.clone(), TIMEOUT)
+                .await
+                .unwrap_err()
+                .kind(),
+            config::ErrorKind::ParseError(_)
+        ));
+
+        state_store_client
+            .del(key.clone(), None, TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(
+            config_client
+                .get_typed::<Thresholds>(key, TIMEOUT)
+                .await
+                .unwrap(),
+            None
+        );
+
+        state_store_client.shutdown().await.unwrap();
+        exit_handle.try_exit().unwrap();
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| e.to_string()) },
+            async move { session.run().await.map_err(|e| e.to_string()) }
+        )
+        .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn config_watch_key_network_tests() {
+    let Ok((session, state_store_client, config_client, exit_handle)) =
+        setup_test("config_watch_key_network_tests-rust")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let key = b"config_watch_key_network_tests".to_vec();
+
+    let test_task = tokio::task::spawn(async move {
+        // Delete the key in case it was left over from a previous run
+        state_store_client
+            .del(key.clone(), None, TIMEOUT)
+            .await
+            .unwrap();
+
+        let default = Thresholds {
+            max_temperature: 50,
+            enabled: false,
+        };
+        let mut value_rx = config_client
+            .watch_key(key.clone(), default.clone(), TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(*value_rx.borrow_and_update(), default);
+
+        // Valid update
+        let value = Thresholds {
+            max_temperature: 80,
+            enabled: true,
+        };
+        config_client
+            .set_typed(key.clone(), &value, TIMEOUT)
+            .await
+            .unwrap();
+        timeout(TIMEOUT, value_rx.changed()).await.unwrap().unwrap();
+        assert_eq!(*value_rx.borrow_and_update(), value);
+
+        // Invalid interim value keeps the last good value
+        state_store_client
+            .set(
+                key.clone(),
+                b"not json".to_vec(),
+                TIMEOUT,
+                None,
+                state_store::SetOptions::default(),
+            )
+            .await
+            .unwrap();
+        sleep(Duration::from_secs(1)).await;
+        assert!(!value_rx.has_changed().unwrap());
+        assert_eq!(*value_rx.borrow(), value);
+
+        // Refresh re-reads the (still invalid) value
+        assert!(config_client.refresh(key.clone(), TIMEOUT).await.unwrap());
+        assert_eq!(*value_rx.borrow(), value);
+        assert!(
+            !config_client
+                .refresh(b"config_unwatched_key".to_vec(), TIMEOUT)
+                .await
+                .unwrap()
+        );
+
+        // Deletion reverts to the default
+        state_store_client
+            .del(key.clone(), None, TIMEOUT)
+            .await
+            .unwrap();
+        timeout(TIMEOUT, value_rx.changed()).await.unwrap().unwrap();
+        assert_eq!(*value_rx.borrow_and_update(), default);
+
+        // Dropping the receiver stops watching the key, so it can be watched again
+        drop(value_rx);
+        sleep(Duration::from_secs(1)).await;
+        let value_rx = config_client
+            .watch_key(key.clone(), default.clone(), TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(*value_rx.borrow(), default);
+
+        state_store_client.shutdown().await.unwrap();
+        exit_handle.try_exit().unwrap();
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| e.to_string()) },
+            async move { session.run().await.map_err(|e| e.to_string()) }
+        )
+        .is_ok()
+    );
+}
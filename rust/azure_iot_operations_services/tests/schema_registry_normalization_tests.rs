@@ -0,0 +1,203 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(all(feature = "schema_registry", feature = "normalize-schemas"))]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::schema_registry::{
+    self, Format, PutOutcome, PutSchemaRequest, PutSchemaRequestBuilder,
+};
+use serde_json::json;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn setup_client_and_mock_server(client_id: &str) -> (schema_registry::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let client = schema_registry::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        &session.create_managed_client(),
+    );
+    tokio::task::spawn(session.run());
+    (client, mock_server)
+}
+
+fn put_request(schema_content: &str, version: &str) -> PutSchemaRequest {
+    PutSchemaRequestBuilder::default()
+        .schema_content(schema_content)
+        .format(Format::JsonSchemaDraft07)
+        .version(version)
+        .build()
+        .unwrap()
+}
+
+/// Expects a put request from the client, and responds to it as the Schema Registry Service
+/// would, registering the schema under `name`. Returns the content of the schema that was put.
+async fn expect_put_and_respond(
+    mock_server: &MockServer,
+    response_packet_identifier: u16,
+    name: &str,
+) -> String {
+    let request = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        request.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    let request_payload: serde_json::Value = serde_json::from_slice(&request.payload).unwrap();
+    let response_payload = json!({
+        "schema": {
+            "format": request_payload["format"],
+            "name": name,
+            "namespace": "test-namespace",
+            "schemaContent": request_payload["schemaContent"],
+            "schemaType": request_payload["schemaType"],
+            "version": request_payload["version"],
+        }
+    });
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(response_packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: serde_json::to_vec(&response_payload).unwrap().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: Some("application/json".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+    mock_server.expect_puback().await;
+    request_payload["schemaContent"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Tests that putting a schema equivalent to one already registered, but differing in key
+/// ordering, number formatting and whitespace, is skipped
+#[tokio::test]
+async fn put_equivalent_schema_unchanged() {
+    let (client, mock_server) = setup_client_and_mock_server("schema_normalization_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, put_content) = tokio::join!(
+        client.put_with_outcome(
+            put_request(
+                r#"{ "type": "object", "properties": { "temp": { "type": "number", "maximum": 100.0 }, "active": { "type": "boolean" } } }"#,
+                "1",
+            ),
+            TIMEOUT,
+        ),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_put_and_respond(&mock_server, 1, "test-schema").await
+        }
+    );
+    let (schema, outcome) = result.unwrap();
+    assert_eq!(outcome, PutOutcome::Created("1".to_string()));
+    assert_eq!(schema.name, "test-schema");
+    // The content is normalized before it is put
+    assert_eq!(
+        put_content,
+        r#"{"properties":{"active":{"type":"boolean"},"temp":{"maximum":100,"type":"number"}},"type":"object"}"#
+    );
+
+    let (schema, outcome) = client
+        .put_with_outcome(
+            put_request(
+                r#"{"properties":{"active":{"type":"boolean"},"temp":{"maximum":100,"type":"number"}},
+                    "type":"object"}"#,
+                "1",
+            ),
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+    assert_eq!(outcome, PutOutcome::Unchanged);
+    assert_eq!(schema.name, "test-schema");
+    mock_server.expect_no_packet();
+}
+
+/// Tests that putting a new version of a registered schema is reported as such
+#[tokio::test]
+async fn put_new_version() {
+    let (client, mock_server) = setup_client_and_mock_server("schema_new_version_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, _) = tokio::join!(
+        client.put_with_outcome(put_request(r#"{"type": "object"}"#, "1"), TIMEOUT),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_put_and_respond(&mock_server, 1, "test-schema").await
+        }
+    );
+    assert_eq!(result.unwrap().1, PutOutcome::Created("1".to_string()));
+
+    let (result, _) = tokio::join!(
+        client.put_with_outcome(put_request(r#"{"type": "object"}"#, "2"), TIMEOUT),
+        expect_put_and_respond(&mock_server, 2, "test-schema")
+    );
+    assert_eq!(result.unwrap().1, PutOutcome::NewVersion("2".to_string()));
+}
+
+/// Tests that schemas in formats other than JSON Schema are put as is, every time
+#[tokio::test]
+async fn put_non_json_schema_not_normalized() {
+    let (client, mock_server) = setup_client_and_mock_server("schema_delta_client");
+    mock_server.expect_connect_and_accept(true).await;
+    let schema_content = r#"{ "b": 1.0, "a": 2 }"#;
+    let delta_request = PutSchemaRequestBuilder::default()
+        .schema_content(schema_content)
+        .format(Format::Delta1)
+        .build()
+        .unwrap();
+
+    let (result, put_content) = tokio::join!(
+        client.put_with_outcome(delta_request.clone(), TIMEOUT),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_put_and_respond(&mock_server, 1, "delta-schema").await
+        }
+    );
+    assert_eq!(result.unwrap().1, PutOutcome::Created("1".to_string()));
+    assert_eq!(put_content, schema_content);
+
+    // The service returns the already registered schema, so nothing new is registered
+    let (result, _) = tokio::join!(
+        client.put_with_outcome(delta_request, TIMEOUT),
+        expect_put_and_respond(&mock_server, 2, "delta-schema")
+    );
+    assert_eq!(result.unwrap().1, PutOutcome::Unchanged);
+}
@@ -0,0 +1,191 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(all(feature = "schema_registry", feature = "leased_lock"))]
+
+use std::{sync::Arc, time::Duration};
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::{
+    schema_registry::{self, GetSchemaRequestBuilder},
+    services_builder::ServicesBuilder,
+    state_store,
+};
+
+fn setup_session_and_mock_server(client_id: &str) -> (Session, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    (Session::new(options).unwrap(), mock_server)
+}
+
+fn state_store_options() -> state_store::ClientOptions {
+    state_store::ClientOptionsBuilder::default()
+        .refresh_server_capabilities(false)
+        .build()
+        .unwrap()
+}
+
+/// Asserts that two request publishes are the same, other than their correlation data and
+/// timestamp
+fn assert_same_request(
+    request1: &mqtt_proto::Publish<bytes::Bytes>,
+    request2: &mqtt_proto::Publish<bytes::Bytes>,
+) {
+    assert_eq!(request1.topic_name, request2.topic_name);
+    assert_eq!(request1.payload, request2.payload);
+    assert_eq!(
+        request1.other_properties.response_topic,
+        request2.other_properties.response_topic
+    );
+    assert_eq!(
+        request1.other_properties.content_type,
+        request2.other_properties.content_type
+    );
+    let user_property_keys = |request: &mqtt_proto::Publish<bytes::Bytes>| {
+        request
+            .other_properties
+            .user_properties
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(user_property_keys(request1), user_property_keys(request2));
+}
+
+/// Issues a State Store `get` and returns its request publish
+async fn state_store_get_request(
+    state_store_client: state_store::Client,
+    session: Session,
+    mock_server: &MockServer,
+) -> mqtt_proto::Publish<bytes::Bytes> {
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let get = tokio::task::spawn(async move {
+        state_store_client
+            .get(b"testKey".to_vec(), Duration::from_secs(10))
+            .await
+    });
+
+    // Key notification and response subscriptions
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.expect_subscribe_and_accept().await;
+    let request = mock_server.expect_publish().await;
+    get.abort();
+    request
+}
+
+/// Issues a Schema Registry `get` and returns its request publish
+async fn schema_registry_get_request(
+    schema_registry_client: schema_registry::Client,
+    session: Session,
+    mock_server: &MockServer,
+) -> mqtt_proto::Publish<bytes::Bytes> {
+    tokio::task::spawn(session.run());
+    mock_server.expect_connect_and_accept(true).await;
+    let get = tokio::task::spawn(async move {
+        schema_registry_client
+            .get(
+                GetSchemaRequestBuilder::default()
+                    .name("testSchema".to_string())
+                    .build()
+                    .unwrap(),
+                Duration::from_secs(10),
+            )
+            .await
+    });
+
+    mock_server.expect_subscribe_and_accept().await;
+    let request = mock_server.expect_publish().await;
+    get.abort();
+    request
+}
+
+#[tokio::test]
+async fn state_store_client_from_builder_matches_direct() {
+    let client_id = "services_builder_state_store_test_client";
+
+    let (session, mock_server) = setup_session_and_mock_server(client_id);
+    let direct_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store_options(),
+    )
+    .unwrap();
+    let direct_request = state_store_get_request(direct_client, session, &mock_server).await;
+
+    let (session, mock_server) = setup_session_and_mock_server(client_id);
+    let builder_client = ServicesBuilder::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        &session,
+    )
+    .state_store(state_store_options())
+    .unwrap();
+    let builder_request = state_store_get_request(builder_client, session, &mock_server).await;
+
+    assert_same_request(&direct_request, &builder_request);
+}
+
+#[tokio::test]
+async fn schema_registry_client_from_builder_matches_direct() {
+    let client_id = "services_builder_schema_registry_test_client";
+
+    let (session, mock_server) = setup_session_and_mock_server(client_id);
+    let direct_client = schema_registry::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        &session.create_managed_client(),
+    );
+    let direct_request = schema_registry_get_request(direct_client, session, &mock_server).await;
+
+    let (session, mock_server) = setup_session_and_mock_server(client_id);
+    let builder_client = ServicesBuilder::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        &session,
+    )
+    .schema_registry();
+    let builder_request = schema_registry_get_request(builder_client, session, &mock_server).await;
+
+    assert_same_request(&direct_request, &builder_request);
+}
+
+#[tokio::test]
+async fn leased_lock_clients_share_state_store_client() {
+    let (session, _mock_server) =
+        setup_session_and_mock_server("services_builder_leased_lock_test_client");
+    let services = ServicesBuilder::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        &session,
+    );
+
+    let _lock_client1 = services.leased_lock(b"testLock1".to_vec()).unwrap();
+    let _lock_client2 = services.leased_lock(b"testLock2".to_vec()).unwrap();
+    assert!(services.leased_lock(Vec::new()).is_err());
+
+    // The builder and both lock clients hold the same State Store Client
+    let state_store_client = services.lock_state_store().unwrap();
+    assert!(Arc::ptr_eq(
+        &state_store_client,
+        &services.lock_state_store().unwrap()
+    ));
+    assert_eq!(Arc::strong_count(&state_store_client), 4);
+}
@@ -0,0 +1,459 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "leased_lock")]
+
+use std::{env, sync::Arc, time::Duration};
+
+use env_logger::Builder;
+
+use tokio::{sync::oneshot, time::sleep};
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{Session, SessionExitHandle, SessionOptionsBuilder};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::leased_lock::{
+    ErrorKind, lease,
+    singleton::{SingletonGuard, WaitPolicy},
+};
+use azure_iot_operations_services::state_store::{self};
+
+// API:
+// acquire (wait, fail fast, steal after expiry)
+// release
+
+// Test Scenarios:
+// two holders acquire simultaneously with fail fast, second reports the first as holder
+// two holders acquire simultaneously with wait, second acquires after first releases
+// two holders acquire simultaneously with wait, second acquires after first guard is dropped
+// second holder with steal after expiry takes over a lease that is no longer renewed
+
+const LEASE: Duration = Duration::from_secs(3);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn setup_test(test_name: &str) -> bool {
+    let _ = Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .filter_module("azure_iot_operations", log::LevelFilter::Warn)
+        .try_init();
+
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        log::warn!("Test {test_name} is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return false;
+    }
+
+    true
+}
+
+fn initialize_client(client_id: &str) -> (Session, Arc<state_store::Client>, SessionExitHandle) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("localhost")
+        .tcp_port(1883u16)
+        .keep_alive(Duration::from_secs(5))
+        .use_tls(false)
+        .build()
+        .unwrap();
+
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()
+        .unwrap();
+
+    let session = Session::new(session_options).unwrap();
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    let state_store_client = state_store::Client::new(
+        application_context,
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let exit_handle: SessionExitHandle = session.create_exit_handle();
+
+    (session, Arc::new(state_store_client), exit_handle)
+}
+
+#[tokio::test]
+async fn singleton_fail_fast_reports_holder_network_tests() {
+    let test_id = "singleton_fail_fast_reports_holder_network_tests";
+    if !setup_test(test_id) {
+        return;
+    }
+
+    let singleton_name = format!("{test_id}-singleton");
+    let holder_name1 = format!("{test_id}1");
+    let holder_name2 = format!("{test_id}2");
+
+    let (session1, state_store_client1, exit_handle1) = initialize_client(&holder_name1);
+    let (session2, state_store_client2, exit_handle2) = initialize_client(&holder_name2);
+
+    let (acquired_tx, acquired_rx) = oneshot::channel();
+    let (done_tx, done_rx) = oneshot::channel();
+
+    let test_task1 = tokio::task::spawn({
+        let singleton_name = singleton_name.clone();
+        async move {
+            let guard = SingletonGuard::acquire(
+                state_store_client1.clone(),
+                singleton_name.into(),
+                holder_name1.into(),
+                LEASE,
+                WaitPolicy::FailFast,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .unwrap();
+            assert!(guard.fencing_token().is_some());
+
+            acquired_tx.send(()).unwrap();
+            done_rx.await.unwrap();
+
+            assert!(guard.release().await.is_ok());
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client1.shutdown().await.is_ok());
+
+            exit_handle1.try_exit().unwrap();
+        }
+    });
+
+    let test_task2 = tokio::task::spawn({
+        let holder_name1 = format!("{test_id}1");
+        async move {
+            acquired_rx.await.unwrap();
+
+            let error = SingletonGuard::acquire(
+                state_store_client2.clone(),
+                singleton_name.into(),
+                holder_name2.into(),
+                LEASE,
+                WaitPolicy::FailFast,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .err()
+            .unwrap();
+            match error.kind() {
+                ErrorKind::LeaseHeldBy(holder) => {
+                    assert_eq!(holder, &holder_name1.as_bytes().to_vec());
+                }
+                _ => panic!("Unexpected error: {error:?}"),
+            }
+            assert!(error.to_string().contains(&holder_name1));
+
+            done_tx.send(()).unwrap();
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client2.shutdown().await.is_ok());
+
+            exit_handle2.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task1.await.map_err(|e| { e.to_string() }) },
+            async move { test_task2.await.map_err(|e| { e.to_string() }) },
+            async move { session1.run().await.map_err(|e| { e.to_string() }) },
+            async move { session2.run().await.map_err(|e| { e.to_string() }) },
+        )
+        .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn singleton_wait_acquires_after_release_network_tests() {
+    let test_id = "singleton_wait_acquires_after_release_network_tests";
+    if !setup_test(test_id) {
+        return;
+    }
+
+    let singleton_name = format!("{test_id}-singleton");
+    let holder_name1 = format!("{test_id}1");
+    let holder_name2 = format!("{test_id}2");
+
+    let (session1, state_store_client1, exit_handle1) = initialize_client(&holder_name1);
+    let (session2, state_store_client2, exit_handle2) = initialize_client(&holder_name2);
+
+    let (acquired_tx, acquired_rx) = oneshot::channel();
+
+    let test_task1 = tokio::task::spawn({
+        let singleton_name = singleton_name.clone();
+        async move {
+            let guard = SingletonGuard::acquire(
+                state_store_client1.clone(),
+                singleton_name.into(),
+                holder_name1.into(),
+                LEASE,
+                WaitPolicy::Wait,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .unwrap();
+
+            acquired_tx.send(()).unwrap();
+
+            // Hold the guard past the lease to verify it is renewed in the background
+            sleep(LEASE * 2).await;
+            assert!(guard.fencing_token().is_some());
+
+            assert!(guard.release().await.is_ok());
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client1.shutdown().await.is_ok());
+
+            exit_handle1.try_exit().unwrap();
+        }
+    });
+
+    let test_task2 = tokio::task::spawn({
+        async move {
+            acquired_rx.await.unwrap();
+
+            let lease_client = lease::Client::new(
+                state_store_client2.clone(),
+                singleton_name.clone().into(),
+                holder_name2.clone().into(),
+            )
+            .unwrap();
+
+            let guard = SingletonGuard::acquire(
+                state_store_client2.clone(),
+                singleton_name.into(),
+                holder_name2.clone().into(),
+                LEASE,
+                WaitPolicy::Wait,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                lease_client
+                    .get_holder(REQUEST_TIMEOUT)
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                holder_name2.as_bytes().to_vec()
+            );
+
+            assert!(guard.release().await.is_ok());
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client2.shutdown().await.is_ok());
+
+            exit_handle2.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task1.await.map_err(|e| { e.to_string() }) },
+            async move { test_task2.await.map_err(|e| { e.to_string() }) },
+            async move { session1.run().await.map_err(|e| { e.to_string() }) },
+            async move { session2.run().await.map_err(|e| { e.to_string() }) },
+        )
+        .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn singleton_wait_acquires_after_drop_network_tests() {
+    let test_id = "singleton_wait_acquires_after_drop_network_tests";
+    if !setup_test(test_id) {
+        return;
+    }
+
+    let singleton_name = format!("{test_id}-singleton");
+    let holder_name1 = format!("{test_id}1");
+    let holder_name2 = format!("{test_id}2");
+
+    let (session1, state_store_client1, exit_handle1) = initialize_client(&holder_name1);
+    let (session2, state_store_client2, exit_handle2) = initialize_client(&holder_name2);
+
+    let (acquired_tx, acquired_rx) = oneshot::channel();
+    let (done_tx, done_rx) = oneshot::channel();
+
+    let test_task1 = tokio::task::spawn({
+        let singleton_name = singleton_name.clone();
+        async move {
+            let guard = SingletonGuard::acquire(
+                state_store_client1.clone(),
+                singleton_name.into(),
+                holder_name1.into(),
+                // Long enough that the second holder can only acquire if the guard is released on drop
+                Duration::from_secs(60),
+                WaitPolicy::Wait,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .unwrap();
+
+            acquired_tx.send(()).unwrap();
+            sleep(Duration::from_secs(1)).await;
+            drop(guard);
+
+            // Keep the session running until the second holder has acquired
+            done_rx.await.unwrap();
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client1.shutdown().await.is_ok());
+
+            exit_handle1.try_exit().unwrap();
+        }
+    });
+
+    let test_task2 = tokio::task::spawn({
+        async move {
+            acquired_rx.await.unwrap();
+
+            let guard = tokio::time::timeout(
+                Duration::from_secs(30),
+                SingletonGuard::acquire(
+                    state_store_client2.clone(),
+                    singleton_name.into(),
+                    holder_name2.into(),
+                    LEASE,
+                    WaitPolicy::Wait,
+                    REQUEST_TIMEOUT,
+                ),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+            assert!(guard.release().await.is_ok());
+            done_tx.send(()).unwrap();
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client2.shutdown().await.is_ok());
+
+            exit_handle2.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task1.await.map_err(|e| { e.to_string() }) },
+            async move { test_task2.await.map_err(|e| { e.to_string() }) },
+            async move { session1.run().await.map_err(|e| { e.to_string() }) },
+            async move { session2.run().await.map_err(|e| { e.to_string() }) },
+        )
+        .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn singleton_steal_after_expiry_takes_over_stale_lease_network_tests() {
+    let test_id = "singleton_steal_after_expiry_takes_over_stale_lease_network_tests";
+    if !setup_test(test_id) {
+        return;
+    }
+
+    let singleton_name = format!("{test_id}-singleton");
+    let holder_name1 = format!("{test_id}1");
+    let holder_name2 = format!("{test_id}2");
+
+    let (session1, state_store_client1, exit_handle1) = initialize_client(&holder_name1);
+    let (session2, state_store_client2, exit_handle2) = initialize_client(&holder_name2);
+
+    let (acquired_tx, acquired_rx) = oneshot::channel();
+    let (done_tx, done_rx) = oneshot::channel();
+
+    let test_task1 = tokio::task::spawn({
+        let singleton_name = singleton_name.clone();
+        async move {
+            // A holder that acquired a long lease and stopped renewing it (e.g. a hung process)
+            let lease_client = lease::Client::new(
+                state_store_client1.clone(),
+                singleton_name.into(),
+                holder_name1.into(),
+            )
+            .unwrap();
+            assert!(
+                lease_client
+                    .acquire(Duration::from_secs(60), REQUEST_TIMEOUT, None)
+                    .await
+                    .is_ok()
+            );
+
+            acquired_tx.send(()).unwrap();
+            done_rx.await.unwrap();
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client1.shutdown().await.is_ok());
+
+            exit_handle1.try_exit().unwrap();
+        }
+    });
+
+    let test_task2 = tokio::task::spawn({
+        async move {
+            acquired_rx.await.unwrap();
+
+            let lease_client = lease::Client::new(
+                state_store_client2.clone(),
+                singleton_name.clone().into(),
+                holder_name2.clone().into(),
+            )
+            .unwrap();
+
+            let guard = tokio::time::timeout(
+                Duration::from_secs(30),
+                SingletonGuard::acquire(
+                    state_store_client2.clone(),
+                    singleton_name.into(),
+                    holder_name2.clone().into(),
+                    LEASE,
+                    WaitPolicy::StealAfterExpiry,
+                    REQUEST_TIMEOUT,
+                ),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+            assert_eq!(
+                lease_client
+                    .get_holder(REQUEST_TIMEOUT)
+                    .await
+                    .unwrap()
+                    .unwrap(),
+                holder_name2.as_bytes().to_vec()
+            );
+
+            assert!(guard.release().await.is_ok());
+            done_tx.send(()).unwrap();
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client2.shutdown().await.is_ok());
+
+            exit_handle2.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task1.await.map_err(|e| { e.to_string() }) },
+            async move { test_task2.await.map_err(|e| { e.to_string() }) },
+            async move { session1.run().await.map_err(|e| { e.to_string() }) },
+            async move { session2.run().await.map_err(|e| { e.to_string() }) },
+        )
+        .is_ok()
+    );
+}
@@ -0,0 +1,233 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::state_store::{self, ErrorKind};
+
+fn setup_client_and_mock_server(client_id: &str) -> (state_store::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .refresh_server_capabilities(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (state_store_client, mock_server)
+}
+
+/// Expects a request publish from the State Store Client and acks it
+async fn expect_request(mock_server: &MockServer) -> mqtt_proto::Publish<bytes::Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    publish
+}
+
+/// Sends the response to a request publish with the given RESP3 payload
+fn send_response(
+    mock_server: &MockServer,
+    request: mqtt_proto::Publish<bytes::Bytes>,
+    packet_identifier: u16,
+    payload: &[u8],
+) {
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: payload.to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+}
+
+/// Tests that all `Get` requests are sent before any response is received, and that the values
+/// are returned in the order of the keys regardless of the order of the responses
+#[tokio::test]
+async fn get_many_pipelined_in_key_order() {
+    let (state_store_client, mock_server) = setup_client_and_mock_server("get_many_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, ()) = tokio::join!(
+        state_store_client.get_many(
+            vec![b"key1".to_vec(), b"key2".to_vec(), b"key3".to_vec()],
+            Duration::from_secs(10)
+        ),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+
+            let mut requests = vec![
+                expect_request(&mock_server).await,
+                expect_request(&mock_server).await,
+                expect_request(&mock_server).await,
+            ];
+            // Respond in reverse order of the keys, with key2 not found
+            requests.sort_by(|a, b| b.payload.cmp(&a.payload));
+            for (i, request) in requests.into_iter().enumerate() {
+                let payload: &[u8] = if request.payload.ends_with(b"$4\r\nkey2\r\n") {
+                    b"$-1\r\n"
+                } else if request.payload.ends_with(b"$4\r\nkey1\r\n") {
+                    b"$6\r\nvalue1\r\n"
+                } else {
+                    b"$6\r\nvalue3\r\n"
+                };
+                send_response(
+                    &mock_server,
+                    request,
+                    u16::try_from(i + 1).unwrap(),
+                    payload,
+                );
+                mock_server.expect_puback().await;
+            }
+        }
+    );
+    assert_eq!(
+        result
+            .into_iter()
+            .map(|value| value.unwrap().response)
+            .collect::<Vec<_>>(),
+        vec![Some(b"value1".to_vec()), None, Some(b"value3".to_vec())]
+    );
+}
+
+/// Tests that a failure for one key is returned for that key without failing the rest of the batch
+#[tokio::test]
+async fn get_many_partial_failure() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("get_many_error_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, ()) = tokio::join!(
+        state_store_client.get_many(
+            vec![b"key1".to_vec(), b"key2".to_vec()],
+            Duration::from_secs(10)
+        ),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+
+            let first_request = expect_request(&mock_server).await;
+            let second_request = expect_request(&mock_server).await;
+            send_response(&mock_server, first_request, 1, b"$6\r\nvalue1\r\n");
+            mock_server.expect_puback().await;
+            send_response(&mock_server, second_request, 2, b"-ERR syntax error\r\n");
+            mock_server.expect_puback().await;
+        }
+    );
+    let mut result = result.into_iter();
+    assert_eq!(
+        result.next().unwrap().unwrap().response,
+        Some(b"value1".to_vec())
+    );
+    assert!(matches!(
+        result.next().unwrap().unwrap_err().kind(),
+        ErrorKind::ServiceError(_)
+    ));
+}
+
+/// Tests that all `Set` requests are sent before any response is received, and that the results
+/// are returned in the order of the entries, including entries that were not applied or failed
+#[tokio::test]
+async fn set_many_pipelined_in_entry_order() {
+    let (state_store_client, mock_server) = setup_client_and_mock_server("set_many_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, ()) = tokio::join!(
+        state_store_client.set_many(
+            vec![
+                (b"key1".to_vec(), b"value1".to_vec()),
+                (b"key2".to_vec(), b"value2".to_vec()),
+                (b"key3".to_vec(), b"value3".to_vec()),
+            ],
+            Duration::from_secs(10),
+            state_store::SetOptions {
+                set_condition: state_store::SetCondition::OnlyIfDoesNotExist,
+                ..Default::default()
+            },
+        ),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+
+            let mut requests = vec![
+                expect_request(&mock_server).await,
+                expect_request(&mock_server).await,
+                expect_request(&mock_server).await,
+            ];
+            // Respond in reverse order of the entries, with key2 not applied and key3 failed
+            requests.reverse();
+            for (i, request) in requests.into_iter().enumerate() {
+                assert!(request.payload.windows(2).any(|w| w == b"NX"));
+                let payload: &[u8] = if request.payload.windows(4).any(|w| w == b"key2") {
+                    b":-1\r\n"
+                } else if request.payload.windows(4).any(|w| w == b"key3") {
+                    b"-ERR syntax error\r\n"
+                } else {
+                    b"+OK\r\n"
+                };
+                send_response(
+                    &mock_server,
+                    request,
+                    u16::try_from(i + 1).unwrap(),
+                    payload,
+                );
+                mock_server.expect_puback().await;
+            }
+        }
+    );
+    let mut result = result.into_iter();
+    assert!(result.next().unwrap().unwrap().response);
+    assert!(!result.next().unwrap().unwrap().response);
+    assert!(matches!(
+        result.next().unwrap().unwrap_err().kind(),
+        ErrorKind::ServiceError(_)
+    ));
+    assert!(result.next().is_none());
+}
@@ -0,0 +1,353 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{
+        IncomingPacketsTx, InjectedPacketChannels, MockReconnectPolicy, MockServer,
+        OutgoingPacketsRx,
+    },
+};
+use azure_iot_operations_protocol::application::ApplicationContextBuilder;
+use azure_iot_operations_services::state_store::{self, ServerCommand};
+
+const WRONG_NUMBER_OF_ARGUMENTS: &[u8] = b"-ERR wrong number of arguments\r\n";
+const UNKNOWN_COMMAND: &[u8] = b"-ERR unknown command\r\n";
+
+fn setup_client_and_mock_server(
+    client_id: &str,
+    refresh_server_capabilities: bool,
+) -> (state_store::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let (mock_reconnect_policy, _) = MockReconnectPolicy::new();
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .reconnect_policy(Box::new(mock_reconnect_policy))
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .refresh_server_capabilities(refresh_server_capabilities)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (state_store_client, mock_server)
+}
+
+/// Expects a request publish from the State Store Client, acks it and responds to it
+async fn expect_request_and_respond(
+    mock_server: &MockServer,
+    response_packet_identifier: u16,
+    response: &[u8],
+) -> bytes::Bytes {
+    let request = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        request.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(response_packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: response.to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+    mock_server.expect_puback().await;
+    request.payload
+}
+
+/// Expects a probe request for each command, responding as if the commands named in
+/// `unsupported_commands` are not supported
+async fn expect_probes_and_respond(
+    mock_server: &MockServer,
+    first_packet_identifier: u16,
+    unsupported_commands: &[&[u8]],
+) {
+    let expected_probes: [(&[u8], &[u8]); 7] = [
+        (b"SET", b"*1\r\n$3\r\nSET\r\n"),
+        (b"GET", b"*1\r\n$3\r\nGET\r\n"),
+        (b"DEL", b"*1\r\n$3\r\nDEL\r\n"),
+        (b"VDEL", b"*1\r\n$4\r\nVDEL\r\n"),
+        (b"KEYNOTIFY", b"*1\r\n$9\r\nKEYNOTIFY\r\n"),
+        (b"DELPREFIX", b"*1\r\n$9\r\nDELPREFIX\r\n"),
+        (b"KEYS", b"*1\r\n$4\r\nKEYS\r\n"),
+    ];
+    for (packet_identifier, (command, expected_probe)) in
+        (first_packet_identifier..).zip(expected_probes)
+    {
+        let response = if unsupported_commands.contains(&command) {
+            UNKNOWN_COMMAND
+        } else {
+            WRONG_NUMBER_OF_ARGUMENTS
+        };
+        let probe = expect_request_and_respond(mock_server, packet_identifier, response).await;
+        assert_eq!(probe.as_ref(), expected_probe);
+    }
+}
+
+/// Tests that the server capabilities are queried when the session connects, and that the cached
+/// capabilities are returned without making any more requests
+#[tokio::test]
+async fn server_capabilities_populated_on_connect() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("capabilities_on_connect_test_client", true);
+    mock_server.expect_connect_and_accept(true).await;
+
+    // Key notification and response subscriptions
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.expect_subscribe_and_accept().await;
+    expect_probes_and_respond(&mock_server, 1, &[b"KEYNOTIFY"]).await;
+
+    // The capabilities are cached once the last probe response is processed
+    let server_capabilities = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let server_capabilities = state_store_client
+                .server_capabilities(Duration::from_secs(10))
+                .await
+                .unwrap();
+            if !server_capabilities.supported_commands().is_empty() {
+                break server_capabilities;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(server_capabilities.supports(ServerCommand::Set));
+    assert!(server_capabilities.supports(ServerCommand::Get));
+    assert!(server_capabilities.supports(ServerCommand::Del));
+    assert!(server_capabilities.supports(ServerCommand::VDel));
+    assert!(!server_capabilities.supports(ServerCommand::KeyNotify));
+    assert!(server_capabilities.supports(ServerCommand::DelPrefix));
+    assert!(server_capabilities.supports(ServerCommand::Keys));
+    mock_server.expect_no_packet();
+}
+
+/// Tests that the server capabilities are queried again when the session reconnects
+#[tokio::test]
+async fn server_capabilities_refreshed_on_reconnect() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("capabilities_on_reconnect_test_client", true);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+    mock_server.expect_subscribe_and_accept().await;
+    expect_probes_and_respond(&mock_server, 1, &[b"KEYNOTIFY"]).await;
+
+    // Reconnect to a State Store Service that supports `KEYNOTIFY`
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    mock_server.expect_connect().await;
+    mock_server.send_connack(mqtt_proto::ConnAck {
+        reason_code: mqtt_proto::ConnectReasonCode::Success {
+            session_present: true,
+        },
+        other_properties: mqtt_proto::ConnAckOtherProperties::default(),
+    });
+    expect_probes_and_respond(&mock_server, 8, &[]).await;
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !state_store_client
+            .server_capabilities(Duration::from_secs(10))
+            .await
+            .unwrap()
+            .supports(ServerCommand::KeyNotify)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+/// Tests that the server capabilities are queried on the first call if they are not refreshed when
+/// the session connects
+#[tokio::test]
+async fn server_capabilities_queried_on_first_call() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("capabilities_first_call_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+
+    let (server_capabilities, ()) = tokio::join!(
+        state_store_client.server_capabilities(Duration::from_secs(10)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_probes_and_respond(&mock_server, 1, &[]).await;
+        }
+    );
+    let server_capabilities = server_capabilities.unwrap();
+    assert_eq!(server_capabilities.supported_commands().len(), 7);
+
+    // The cached capabilities are returned
+    assert_eq!(
+        state_store_client
+            .server_capabilities(Duration::from_secs(10))
+            .await
+            .unwrap(),
+        server_capabilities
+    );
+    mock_server.expect_no_packet();
+}
+
+/// Tests that keys are deleted by prefix in a single request if `DELPREFIX` is supported
+#[tokio::test]
+async fn del_prefix_atomic() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("del_prefix_atomic_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+
+    let (deleted, ()) = tokio::join!(
+        state_store_client.del_prefix(b"tenant1/".to_vec(), Duration::from_secs(10)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_probes_and_respond(&mock_server, 1, &[]).await;
+            let request = expect_request_and_respond(&mock_server, 8, b":3\r\n").await;
+            assert_eq!(
+                request.as_ref(),
+                b"*2\r\n$9\r\nDELPREFIX\r\n$8\r\ntenant1/\r\n"
+            );
+        }
+    );
+    assert_eq!(deleted.unwrap(), 3);
+    mock_server.expect_no_packet();
+}
+
+/// Tests that keys are listed with `KEYS` and deleted one at a time if `DELPREFIX` is not
+/// supported, and that keys deleted by another client in the meantime are not counted
+#[tokio::test]
+async fn del_prefix_fallback() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("del_prefix_fallback_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+
+    let (deleted, ()) = tokio::join!(
+        state_store_client.del_prefix(b"tenant*/".to_vec(), Duration::from_secs(10)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_probes_and_respond(&mock_server, 1, &[b"DELPREFIX"]).await;
+            let request = expect_request_and_respond(
+                &mock_server,
+                8,
+                b"*2\r\n$10\r\ntenant*/k1\r\n$10\r\ntenant*/k2\r\n",
+            )
+            .await;
+            assert_eq!(
+                request.as_ref(),
+                b"*2\r\n$4\r\nKEYS\r\n$10\r\ntenant\\*/*\r\n"
+            );
+            let request = expect_request_and_respond(&mock_server, 9, b":1\r\n").await;
+            assert_eq!(
+                request.as_ref(),
+                b"*2\r\n$3\r\nDEL\r\n$10\r\ntenant*/k1\r\n"
+            );
+            let request = expect_request_and_respond(&mock_server, 10, b":0\r\n").await;
+            assert_eq!(
+                request.as_ref(),
+                b"*2\r\n$3\r\nDEL\r\n$10\r\ntenant*/k2\r\n"
+            );
+        }
+    );
+    assert_eq!(deleted.unwrap(), 1);
+    mock_server.expect_no_packet();
+}
+
+/// Tests that the fallback stops at the first failed delete, leaving the keys deleted before it
+/// deleted
+#[tokio::test]
+async fn del_prefix_fallback_partial_failure() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("del_prefix_partial_failure_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+
+    let (deleted, ()) = tokio::join!(
+        state_store_client.del_prefix(b"p/".to_vec(), Duration::from_secs(10)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_probes_and_respond(&mock_server, 1, &[b"DELPREFIX"]).await;
+            expect_request_and_respond(
+                &mock_server,
+                8,
+                b"*3\r\n$4\r\np/k1\r\n$4\r\np/k2\r\n$4\r\np/k3\r\n",
+            )
+            .await;
+            expect_request_and_respond(&mock_server, 9, b":1\r\n").await;
+            expect_request_and_respond(
+                &mock_server,
+                10,
+                b"-ERR a fencing token is required for this request\r\n",
+            )
+            .await;
+        }
+    );
+    assert!(matches!(
+        deleted.unwrap_err().kind(),
+        state_store::ErrorKind::ServiceError(state_store::ServiceError::MissingFencingToken)
+    ));
+    // No more keys are deleted after the failure
+    mock_server.expect_no_packet();
+}
+
+/// Tests that deleting by prefix fails without any request if neither `DELPREFIX` nor `KEYS` is
+/// supported
+#[tokio::test]
+async fn del_prefix_unsupported() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("del_prefix_unsupported_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    mock_server.expect_subscribe_and_accept().await;
+
+    let (deleted, ()) = tokio::join!(
+        state_store_client.del_prefix(b"p/".to_vec(), Duration::from_secs(10)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            expect_probes_and_respond(&mock_server, 1, &[b"DELPREFIX", b"KEYS"]).await;
+        }
+    );
+    assert!(matches!(
+        deleted.unwrap_err().kind(),
+        state_store::ErrorKind::ServiceError(state_store::ServiceError::UnknownCommand)
+    ));
+    mock_server.expect_no_packet();
+}
@@ -3,14 +3,23 @@
 
 #![cfg(feature = "state_store")]
 
-use std::{env, time::Duration};
+use std::{
+    env,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
 use env_logger::Builder;
 
 use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::control_packet::{PublishProperties, TopicName};
 use azure_iot_operations_mqtt::session::{Session, SessionExitHandle, SessionOptionsBuilder};
 use azure_iot_operations_protocol::application::ApplicationContextBuilder;
 use azure_iot_operations_protocol::common::hybrid_logical_clock::HybridLogicalClock;
+use azure_iot_operations_protocol::rpc_command;
 use azure_iot_operations_services::state_store::{self, SetCondition, SetOptions};
 
 // These tests test these scenarios - numbers are linked inline:
@@ -59,6 +68,13 @@ use azure_iot_operations_services::state_store::{self, SetCondition, SetOptions}
 //    36. TODO set with key expiry, recv delete notification once key expires
 // SHUTDOWN
 //    37. where key is being observed, then shutdown is called. Recv returns None.
+// EXECUTOR DISTRIBUTED DEDUP
+//    38. same request delivered multiple times to executors sharing a service group is only executed once
+// CONDITIONAL DELETE
+//    39. del_if_equal where key exists and value matches
+//    40. del_if_equal where key exists and value doesn't match, or key does not exist (expect false)
+//    41. del_if_version where the key is protected by that version's fencing token
+//    42. del_if_version where the key is protected by a newer fencing token (expect false)
 
 const VALUE1: &[u8] = b"value1";
 const VALUE2: &[u8] = b"value2";
@@ -1081,3 +1097,247 @@ async fn state_store_shutdown_right_away_network_tests() {
         .is_ok()
     );
 }
+
+#[tokio::test]
+async fn state_store_executor_distributed_dedup_network_tests() {
+    let Ok((session1, state_store_client1, exit_handle1)) =
+        setup_test("state_store_executor_distributed_dedup_network_tests-rust-1")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let Ok((session2, state_store_client2, exit_handle2)) =
+        setup_test("state_store_executor_distributed_dedup_network_tests-rust-2")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let request_topic = "test/state_store_executor_distributed_dedup/request";
+    let response_topic = "test/state_store_executor_distributed_dedup/response";
+    let executions = Arc::new(AtomicUsize::new(0));
+
+    // Two executor replicas in the same service group, each claiming requests with their own State Store Client
+    let mut executor_tasks = vec![];
+    for (session, state_store_client) in [
+        (&session1, state_store_client1),
+        (&session2, state_store_client2),
+    ] {
+        let executor_options = rpc_command::executor::OptionsBuilder::default()
+            .request_topic_pattern(request_topic)
+            .command_name("distributed_dedup")
+            .service_group_id("distributed_dedup_group")
+            .distributed_dedup(
+                rpc_command::executor::DistributedDedupOptionsBuilder::default()
+                    .store(state_store_client)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let mut executor: rpc_command::Executor<Vec<u8>, Vec<u8>> = rpc_command::Executor::new(
+            ApplicationContextBuilder::default().build().unwrap(),
+            session.create_managed_client(),
+            executor_options,
+        )
+        .unwrap();
+        let executions = executions.clone();
+        executor_tasks.push(tokio::task::spawn(async move {
+            while let Some(Ok(request)) = executor.recv().await {
+                executions.fetch_add(1, Ordering::SeqCst);
+                let response = rpc_command::executor::ResponseBuilder::default()
+                    .payload(Vec::new())
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                request.complete(response).await.unwrap();
+            }
+        }));
+    }
+
+    let session_monitor1 = session1.create_session_monitor();
+    let session_monitor2 = session2.create_session_monitor();
+    let managed_client = session1.create_managed_client();
+    let test_task = tokio::task::spawn({
+        async move {
+            session_monitor1.connected().await;
+            session_monitor2.connected().await;
+            // Wait for the executors to subscribe
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            // Deliver the same request (same correlation data) multiple times, as the broker
+            // would when redelivering to a different replica of the service group
+            let correlation_data = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+                .to_be_bytes();
+            for _ in 0..4 {
+                let publish_ct = managed_client
+                    .publish_qos1(
+                        TopicName::new(request_topic).unwrap(),
+                        false,
+                        Vec::new(),
+                        PublishProperties {
+                            correlation_data: Some(correlation_data.to_vec().into()),
+                            response_topic: Some(TopicName::new(response_topic).unwrap()),
+                            message_expiry_interval: Some(10),
+                            ..PublishProperties::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+                publish_ct.await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+
+            // The request was only executed once across both replicas
+            assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+            for executor_task in executor_tasks {
+                executor_task.abort();
+            }
+            exit_handle1.try_exit().unwrap();
+            exit_handle2.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the sessions to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| { e.to_string() }) },
+            async move { session1.run().await.map_err(|e| { e.to_string() }) },
+            async move { session2.run().await.map_err(|e| { e.to_string() }) },
+        )
+        .is_ok()
+    );
+}
+
+/// ~~~~~~~~ Key 9 ~~~~~~~~
+/// Tests conditional deletes
+#[tokio::test]
+async fn state_store_conditional_delete_network_tests() {
+    let log_identifier = "conditional_delete";
+    let Ok((session, state_store_client, exit_handle)) =
+        setup_test("state_store_conditional_delete_network_tests-rust")
+    else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+
+    let test_task = tokio::task::spawn({
+        async move {
+            let key9 = b"key9";
+
+            assert!(
+                state_store_client
+                    .set(
+                        key9.to_vec(),
+                        VALUE1.to_vec(),
+                        TIMEOUT,
+                        None,
+                        SetOptions::default(),
+                    )
+                    .await
+                    .unwrap()
+                    .response
+            );
+
+            // Tests 40 (where key exists and value doesn't match (expect false))
+            let del_if_not_equal_response = state_store_client
+                .del_if_equal(key9.to_vec(), VALUE2.to_vec(), TIMEOUT)
+                .await
+                .unwrap();
+            assert!(!del_if_not_equal_response.response);
+            log::info!(
+                "[{log_identifier}] del_if_not_equal_response: {del_if_not_equal_response:?}"
+            );
+
+            // Tests 39 (where key exists and value matches)
+            let del_if_equal_response = state_store_client
+                .del_if_equal(key9.to_vec(), VALUE1.to_vec(), TIMEOUT)
+                .await
+                .unwrap();
+            assert!(del_if_equal_response.response);
+            log::info!("[{log_identifier}] del_if_equal_response: {del_if_equal_response:?}");
+
+            // Tests 40 (where key does not exist (expect false))
+            let del_if_equal_no_key_response = state_store_client
+                .del_if_equal(key9.to_vec(), VALUE1.to_vec(), TIMEOUT)
+                .await
+                .unwrap();
+            assert!(!del_if_equal_no_key_response.response);
+            log::info!(
+                "[{log_identifier}] del_if_equal_no_key_response: {del_if_equal_no_key_response:?}"
+            );
+
+            // Set the key with a fencing token, then take it over with a newer fencing token
+            let old_version = state_store_client
+                .set(
+                    key9.to_vec(),
+                    VALUE1.to_vec(),
+                    TIMEOUT,
+                    Some(HybridLogicalClock::new()),
+                    SetOptions::default(),
+                )
+                .await
+                .unwrap()
+                .version
+                .unwrap();
+            let new_version = state_store_client
+                .set(
+                    key9.to_vec(),
+                    VALUE2.to_vec(),
+                    TIMEOUT,
+                    Some(HybridLogicalClock::new()),
+                    SetOptions::default(),
+                )
+                .await
+                .unwrap()
+                .version
+                .unwrap();
+
+            // Tests 42 (where the key is protected by a newer fencing token (expect false))
+            let del_if_old_version_response = state_store_client
+                .del_if_version(key9.to_vec(), old_version, TIMEOUT)
+                .await
+                .unwrap();
+            assert!(!del_if_old_version_response.response);
+            log::info!(
+                "[{log_identifier}] del_if_old_version_response: {del_if_old_version_response:?}"
+            );
+            // The key still holds the newer value
+            assert_eq!(
+                state_store_client
+                    .get(key9.to_vec(), TIMEOUT)
+                    .await
+                    .unwrap()
+                    .response,
+                Some(VALUE2.to_vec())
+            );
+
+            // Tests 41 (where the key is protected by that version's fencing token)
+            let del_if_version_response = state_store_client
+                .del_if_version(key9.to_vec(), new_version, TIMEOUT)
+                .await
+                .unwrap();
+            assert!(del_if_version_response.response);
+            log::info!("[{log_identifier}] del_if_version_response: {del_if_version_response:?}");
+
+            // Shutdown state store client and underlying resources
+            assert!(state_store_client.shutdown().await.is_ok());
+
+            exit_handle.try_exit().unwrap();
+        }
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| { e.to_string() }) },
+            async move { session.run().await.map_err(|e| { e.to_string() }) }
+        )
+        .is_ok()
+    );
+}
@@ -0,0 +1,241 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::hybrid_logical_clock::HybridLogicalClock,
+};
+use azure_iot_operations_services::state_store::{self, ErrorKind};
+
+fn setup_client_and_mock_server(client_id: &str) -> (state_store::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .refresh_server_capabilities(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (state_store_client, mock_server)
+}
+
+/// Expects a request publish from the State Store Client, acks it and responds to it
+async fn expect_request_and_respond(
+    mock_server: &MockServer,
+    response_packet_identifier: u16,
+    response: &[u8],
+) -> mqtt_proto::Publish<bytes::Bytes> {
+    let request = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        request.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.clone().unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(response_packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: response.to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data.clone(),
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+    mock_server.expect_puback().await;
+    request
+}
+
+/// Returns the fencing token sent with a request, if any
+fn fencing_token(request: &mqtt_proto::Publish<bytes::Bytes>) -> Option<String> {
+    request
+        .other_properties
+        .user_properties
+        .iter()
+        .find(|(name, _)| name.as_ref() == "__ft")
+        .map(|(_, value)| value.to_string())
+}
+
+/// Tests that a `Set` with `only_if_equal` deletes the key if it holds the expected value and then
+/// sets the new value only if the key does not exist, sending the fencing token with both requests
+#[tokio::test]
+async fn only_if_equal_applied() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("only_if_equal_applied_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+    let token = HybridLogicalClock::new();
+
+    let (result, (vdel_request, set_request)) = tokio::join!(
+        state_store_client.set(
+            b"key".to_vec(),
+            b"new".to_vec(),
+            Duration::from_secs(10),
+            Some(token.clone()),
+            state_store::SetOptions {
+                only_if_equal: Some(b"old".to_vec()),
+                expires: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+        ),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            let vdel_request = expect_request_and_respond(&mock_server, 1, b":1\r\n").await;
+            let set_request = expect_request_and_respond(&mock_server, 2, b"+OK\r\n").await;
+            (vdel_request, set_request)
+        }
+    );
+    assert!(result.unwrap().response);
+    assert_eq!(
+        vdel_request.payload.as_ref(),
+        b"*3\r\n$4\r\nVDEL\r\n$3\r\nkey\r\n$3\r\nold\r\n"
+    );
+    assert_eq!(
+        set_request.payload.as_ref(),
+        b"*6\r\n$3\r\nSET\r\n$3\r\nkey\r\n$3\r\nnew\r\n$2\r\nNX\r\n$2\r\nPX\r\n$2\r\n10\r\n"
+    );
+    assert_eq!(fencing_token(&vdel_request), Some(token.to_string()));
+    assert_eq!(fencing_token(&set_request), Some(token.to_string()));
+}
+
+/// Tests that a `Set` with `only_if_equal` is not applied, and no `SET` is sent, if the key does
+/// not hold the expected value
+#[tokio::test]
+async fn only_if_equal_not_applied_on_mismatch() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("only_if_equal_mismatch_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, _) = tokio::join!(
+        state_store_client.set(
+            b"key".to_vec(),
+            b"new".to_vec(),
+            Duration::from_secs(10),
+            None,
+            state_store::SetOptions {
+                only_if_equal: Some(b"old".to_vec()),
+                ..Default::default()
+            },
+        ),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            expect_request_and_respond(&mock_server, 1, b":-1\r\n").await
+        }
+    );
+    assert!(!result.unwrap().response);
+    mock_server.expect_no_packet();
+}
+
+/// Tests that a `Set` with `only_if_not_exists` is sent with the `NX` argument
+#[tokio::test]
+async fn only_if_not_exists_sent_as_nx() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("only_if_not_exists_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    let (result, request) = tokio::join!(
+        state_store_client.set(
+            b"key".to_vec(),
+            b"new".to_vec(),
+            Duration::from_secs(10),
+            None,
+            state_store::SetOptions {
+                only_if_not_exists: true,
+                ..Default::default()
+            },
+        ),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            expect_request_and_respond(&mock_server, 1, b":-1\r\n").await
+        }
+    );
+    assert!(!result.unwrap().response);
+    assert_eq!(
+        request.payload.as_ref(),
+        b"*4\r\n$3\r\nSET\r\n$3\r\nkey\r\n$3\r\nnew\r\n$2\r\nNX\r\n"
+    );
+}
+
+/// Tests that combining conditions is rejected without sending a request
+#[tokio::test]
+async fn conflicting_conditions_rejected() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("conflicting_conditions_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+
+    for options in [
+        state_store::SetOptions {
+            only_if_equal: Some(b"old".to_vec()),
+            only_if_not_exists: true,
+            ..Default::default()
+        },
+        state_store::SetOptions {
+            set_condition: state_store::SetCondition::OnlyIfEqualOrDoesNotExist,
+            only_if_equal: Some(b"old".to_vec()),
+            ..Default::default()
+        },
+        state_store::SetOptions {
+            set_condition: state_store::SetCondition::OnlyIfDoesNotExist,
+            only_if_not_exists: true,
+            ..Default::default()
+        },
+    ] {
+        let result = state_store_client
+            .set(
+                b"key".to_vec(),
+                b"new".to_vec(),
+                Duration::from_secs(10),
+                None,
+                options,
+            )
+            .await;
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            ErrorKind::InvalidArgument(_)
+        ));
+    }
+    mock_server.expect_no_packet();
+}
@@ -0,0 +1,267 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::{sync::Arc, time::Duration};
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{application::ApplicationContextBuilder, telemetry};
+use azure_iot_operations_services::state_store::{
+    self,
+    processed_tracker::{ProcessedTracker, ProcessedTrackerOptionsBuilder, TrackedReceiver},
+};
+use bytes::Bytes;
+
+const TRACKER_KEY: &[u8] = b"processed";
+const TELEMETRY_TOPIC: &str = "test/telemetry/processed";
+
+fn setup_client_and_mock_server(
+    client_id: &str,
+) -> (
+    Arc<state_store::Client>,
+    telemetry::Receiver<Vec<u8>>,
+    MockServer,
+) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .refresh_server_capabilities(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    let receiver = telemetry::Receiver::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        telemetry::receiver::OptionsBuilder::default()
+            .topic_pattern(TELEMETRY_TOPIC)
+            .auto_ack(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (Arc::new(state_store_client), receiver, mock_server)
+}
+
+/// Expects a request publish from the State Store Client and acks it
+async fn expect_request(mock_server: &MockServer) -> mqtt_proto::Publish<Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    publish
+}
+
+/// Sends the response to a State Store request publish with the given RESP3 payload
+fn send_response(
+    mock_server: &MockServer,
+    request: mqtt_proto::Publish<Bytes>,
+    packet_identifier: u16,
+    payload: &[u8],
+) {
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: payload.to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+}
+
+/// Creates a telemetry publish with a cloud event with the given id
+fn telemetry_publish(id: &str, packet_identifier: u16, dup: bool) -> mqtt_proto::Publish<Bytes> {
+    mqtt_proto::Publish {
+        topic_name: mqtt_proto::Topic::new(TELEMETRY_TOPIC.to_string())
+            .unwrap()
+            .into(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(packet_identifier).unwrap(),
+            dup,
+        ),
+        retain: false,
+        payload: id.as_bytes().to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__protVer".into(), "1.0".into()),
+                ("specversion".into(), "1.0".into()),
+                ("type".into(), "test-event".into()),
+                ("source".into(), "test".into()),
+                ("id".into(), id.into()),
+            ],
+            ..Default::default()
+        },
+    }
+}
+
+/// Creates a [`ProcessedTracker`], responding to the `Get` of its markers with `get_response`
+async fn create_tracker(
+    state_store_client: Arc<state_store::Client>,
+    mock_server: &MockServer,
+    get_response: &[u8],
+) -> ProcessedTracker {
+    let (tracker, ()) = tokio::join!(
+        ProcessedTracker::new(
+            state_store_client,
+            TRACKER_KEY.to_vec(),
+            ProcessedTrackerOptionsBuilder::default()
+                .flush_delay(Duration::ZERO)
+                .build()
+                .unwrap(),
+        ),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            let get_request = expect_request(mock_server).await;
+            assert!(
+                get_request
+                    .payload
+                    .ends_with(b"$3\r\nGET\r\n$9\r\nprocessed\r\n")
+            );
+            send_response(mock_server, get_request, 1, get_response);
+            mock_server.expect_puback().await;
+        }
+    );
+    tracker.unwrap()
+}
+
+/// Tests that a message processed before a crash, whose marker was written but whose
+/// acknowledgement wasn't delivered, is acknowledged and not processed again when it is
+/// redelivered after a restart
+#[tokio::test]
+async fn processed_message_not_reprocessed_after_restart() {
+    // First run: process a message, crashing before its acknowledgement is delivered
+    let (state_store_client, receiver, mock_server) =
+        setup_client_and_mock_server("processed_tracker_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+    let tracker = create_tracker(state_store_client, &mock_server, b"$-1\r\n").await;
+    let mut tracked_receiver = TrackedReceiver::new(receiver, tracker.clone());
+
+    let (recv_result, ()) = tokio::join!(tracked_receiver.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(telemetry_publish("1", 10, false));
+    });
+    let (message, token) = recv_result.unwrap().unwrap();
+    assert_eq!(message.payload, b"1".to_vec());
+    assert_eq!(token.marker(), Some("ce:test:1"));
+    assert!(!tracker.is_processed("ce:test:1"));
+
+    tracker.mark_processed(token);
+    assert!(tracker.is_processed("ce:test:1"));
+
+    // The marker is written before the message is acknowledged
+    let set_request = expect_request(&mock_server).await;
+    assert!(
+        set_request
+            .payload
+            .ends_with(b"$9\r\nprocessed\r\n$9\r\nce:test:1\r\n")
+    );
+    mock_server.expect_no_packet();
+
+    // Crash: the MQTT connection is lost before the acknowledgement is delivered
+    drop(tracked_receiver);
+    drop(tracker);
+    drop(mock_server);
+
+    // Second run: the broker redelivers the message, followed by a new one
+    let (state_store_client, receiver, mock_server) =
+        setup_client_and_mock_server("processed_tracker_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+    let tracker = create_tracker(state_store_client, &mock_server, b"$9\r\nce:test:1\r\n").await;
+    assert!(tracker.is_processed("ce:test:1"));
+    let mut tracked_receiver = TrackedReceiver::new(receiver, tracker);
+
+    let (recv_result, ()) = tokio::join!(tracked_receiver.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(telemetry_publish("1", 10, true));
+        mock_server.send_publish(telemetry_publish("2", 11, false));
+    });
+    let (message, token) = recv_result.unwrap().unwrap();
+    assert_eq!(message.payload, b"2".to_vec());
+    assert_eq!(token.marker(), Some("ce:test:2"));
+    assert_eq!(tracked_receiver.skipped_count(), 1);
+
+    // The already processed message is acknowledged without being delivered
+    let puback = mock_server.expect_puback().await;
+    assert_eq!(
+        puback.packet_identifier,
+        mqtt_proto::PacketIdentifier::new(10).unwrap()
+    );
+}
+
+/// Tests that a message is acknowledged once its marker has been written
+#[tokio::test]
+async fn message_acked_after_marker_written() {
+    let (state_store_client, receiver, mock_server) =
+        setup_client_and_mock_server("processed_tracker_ack_test_client");
+    mock_server.expect_connect_and_accept(true).await;
+    let tracker = create_tracker(state_store_client, &mock_server, b"$-1\r\n").await;
+    let mut tracked_receiver = TrackedReceiver::new(receiver, tracker.clone());
+
+    let (recv_result, ()) = tokio::join!(tracked_receiver.recv(), async {
+        mock_server.expect_subscribe_and_accept().await;
+        mock_server.send_publish(telemetry_publish("1", 10, false));
+    });
+    let (_, token) = recv_result.unwrap().unwrap();
+    tracker.mark_processed(token);
+
+    let set_request = expect_request(&mock_server).await;
+    mock_server.expect_no_packet();
+    send_response(&mock_server, set_request, 2, b"+OK\r\n");
+
+    // Acknowledgements of the State Store response and of the telemetry message
+    let mut acked = vec![
+        mock_server.expect_puback().await.packet_identifier,
+        mock_server.expect_puback().await.packet_identifier,
+    ];
+    acked.sort();
+    assert_eq!(
+        acked,
+        vec![
+            mqtt_proto::PacketIdentifier::new(2).unwrap(),
+            mqtt_proto::PacketIdentifier::new(10).unwrap()
+        ]
+    );
+}
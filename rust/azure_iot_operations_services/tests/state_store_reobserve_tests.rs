@@ -0,0 +1,233 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{
+        IncomingPacketsTx, InjectedPacketChannels, MockReconnectPolicy, MockServer,
+        OutgoingPacketsRx,
+    },
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::hybrid_logical_clock::HybridLogicalClock,
+};
+use azure_iot_operations_services::state_store;
+use data_encoding::HEXUPPER;
+use futures::StreamExt;
+
+const KEY: &[u8] = b"observed_key";
+const OK: &[u8] = b"+OK\r\n";
+const KEY_NOTIFY_REQUEST: &[u8] = b"*2\r\n$9\r\nKEYNOTIFY\r\n$12\r\nobserved_key\r\n";
+
+fn setup_client_and_mock_server(
+    client_id: &str,
+    reobserve_on_reconnect: bool,
+) -> (state_store::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let (mock_reconnect_policy, _) = MockReconnectPolicy::new();
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .reconnect_policy(Box::new(mock_reconnect_policy))
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .refresh_server_capabilities(false)
+            .reobserve_on_reconnect(reobserve_on_reconnect)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (state_store_client, mock_server)
+}
+
+/// Expects a request publish from the State Store Client, acks it and responds to it
+async fn expect_request_and_respond(
+    mock_server: &MockServer,
+    response_packet_identifier: u16,
+    response: &[u8],
+) -> bytes::Bytes {
+    let request = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        request.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: request.other_properties.response_topic.unwrap(),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(response_packet_identifier).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: response.to_vec().into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            correlation_data: request.other_properties.correlation_data,
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                ("__stat".into(), "200".into()),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+    mock_server.expect_puback().await;
+    request.payload
+}
+
+/// Observes [`KEY`], accepting the subscriptions made by the State Store Client
+async fn observe(
+    state_store_client: &state_store::Client,
+    mock_server: &MockServer,
+) -> state_store::KeyObservation {
+    let (observe_result, request) = tokio::join!(
+        state_store_client.observe(KEY.to_vec(), Duration::from_secs(10)),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            expect_request_and_respond(mock_server, 1, OK).await
+        }
+    );
+    assert_eq!(request.as_ref(), KEY_NOTIFY_REQUEST);
+    observe_result.unwrap().response
+}
+
+fn reconnect(mock_server: &MockServer) -> impl Future<Output = ()> + '_ {
+    mock_server.send_disconnect(mqtt_proto::Disconnect {
+        reason_code: mqtt_proto::DisconnectReasonCode::UnspecifiedError,
+        other_properties: mqtt_proto::DisconnectOtherProperties::default(),
+    });
+    async {
+        mock_server.expect_connect().await;
+        mock_server.send_connack(mqtt_proto::ConnAck {
+            reason_code: mqtt_proto::ConnectReasonCode::Success {
+                session_present: true,
+            },
+            other_properties: mqtt_proto::ConnAckOtherProperties::default(),
+        });
+    }
+}
+
+/// Sends a notification that [`KEY`] was set to `value` to the State Store Client
+fn send_set_notification(mock_server: &MockServer, client_id: &str, value: &[u8]) {
+    let payload = [
+        format!(
+            "*4\r\n$6\r\nNOTIFY\r\n$3\r\nSET\r\n$5\r\nVALUE\r\n${}\r\n",
+            value.len()
+        )
+        .as_bytes(),
+        value,
+        b"\r\n",
+    ]
+    .concat();
+    mock_server.send_publish(mqtt_proto::Publish {
+        topic_name: mqtt_proto::topic(format!(
+            "clients/statestore/v1/FA9AE35F-2F64-47CD-9BFF-08E2B32A0FE8/{}/command/notify/{}",
+            HEXUPPER.encode(client_id.as_bytes()),
+            HEXUPPER.encode(KEY)
+        )),
+        packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+            mqtt_proto::PacketIdentifier::new(10).unwrap(),
+            false,
+        ),
+        retain: false,
+        payload: payload.into(),
+        other_properties: mqtt_proto::PublishOtherProperties {
+            content_type: Some("application/octet-stream".into()),
+            user_properties: vec![
+                (
+                    "__ts".into(),
+                    HybridLogicalClock::new().to_string().as_str().into(),
+                ),
+                ("__protVer".into(), "1.0".into()),
+            ],
+            ..Default::default()
+        },
+    });
+}
+
+/// Tests that a key is observed again when the session reconnects, and that its observation keeps
+/// receiving notifications as a stream
+#[tokio::test]
+async fn key_observed_again_on_reconnect() {
+    let client_id = "reobserve_on_reconnect_test_client";
+    let (state_store_client, mock_server) = setup_client_and_mock_server(client_id, true);
+    mock_server.expect_connect_and_accept(true).await;
+    let mut observation = observe(&state_store_client, &mock_server).await;
+
+    reconnect(&mock_server).await;
+    let request = expect_request_and_respond(&mock_server, 2, OK).await;
+    assert_eq!(request.as_ref(), KEY_NOTIFY_REQUEST);
+
+    send_set_notification(&mock_server, client_id, b"value");
+    let (notification, _) = tokio::time::timeout(Duration::from_secs(5), observation.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(notification.key, KEY);
+    assert_eq!(
+        notification.operation,
+        state_store::Operation::Set(b"value".to_vec())
+    );
+}
+
+/// Tests that the observation of a key ends if it can't be observed again when the session
+/// reconnects
+#[tokio::test]
+async fn observation_ends_if_reobserve_fails() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("reobserve_failure_test_client", true);
+    mock_server.expect_connect_and_accept(true).await;
+    let mut observation = observe(&state_store_client, &mock_server).await;
+
+    reconnect(&mock_server).await;
+    expect_request_and_respond(&mock_server, 2, b"-ERR internal error\r\n").await;
+
+    let notification = tokio::time::timeout(Duration::from_secs(5), observation.next())
+        .await
+        .unwrap();
+    assert!(notification.is_none());
+}
+
+/// Tests that observations end when the session disconnects if keys aren't observed again on
+/// reconnect
+#[tokio::test]
+async fn observation_ends_on_disconnect_by_default() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("no_reobserve_test_client", false);
+    mock_server.expect_connect_and_accept(true).await;
+    let mut observation = observe(&state_store_client, &mock_server).await;
+
+    reconnect(&mock_server).await;
+    let notification =
+        tokio::time::timeout(Duration::from_secs(5), observation.recv_notification())
+            .await
+            .unwrap();
+    assert!(notification.is_none());
+    mock_server.expect_no_packet();
+}
@@ -0,0 +1,148 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+#![cfg(feature = "state_store")]
+
+use std::time::Duration;
+
+use azure_iot_operations_mqtt::{
+    aio::connection_settings::MqttConnectionSettingsBuilder,
+    azure_mqtt::mqtt_proto,
+    session::{Session, SessionOptionsBuilder},
+    test_utils::{IncomingPacketsTx, InjectedPacketChannels, MockServer, OutgoingPacketsRx},
+};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder, common::aio_protocol_error::AIOProtocolErrorKind,
+};
+use azure_iot_operations_services::state_store::{self, ErrorKind};
+use tokio::time::Instant;
+
+fn setup_client_and_mock_server(
+    client_id: &str,
+    attempt_timeout: Duration,
+) -> (state_store::Client, MockServer) {
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname("test-hostname")
+        .build()
+        .unwrap();
+    let incoming_packets_tx = IncomingPacketsTx::default();
+    let outgoing_packets_rx = OutgoingPacketsRx::default();
+    let mock_server = MockServer::new(incoming_packets_tx.clone(), outgoing_packets_rx.clone());
+    let options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .injected_packet_channels(Some(InjectedPacketChannels {
+            incoming_packets_tx,
+            outgoing_packets_rx,
+        }))
+        .build()
+        .unwrap();
+    let session = Session::new(options).unwrap();
+    let state_store_client = state_store::Client::new(
+        ApplicationContextBuilder::default().build().unwrap(),
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        state_store::ClientOptionsBuilder::default()
+            .attempt_timeout(attempt_timeout)
+            .refresh_server_capabilities(false)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    tokio::task::spawn(session.run());
+    (state_store_client, mock_server)
+}
+
+/// Expects a request publish from the State Store Client and acks it
+async fn expect_request(mock_server: &MockServer) -> mqtt_proto::Publish<bytes::Bytes> {
+    let publish = mock_server.expect_publish().await;
+    if let mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(packet_identifier, _) =
+        publish.packet_identifier_dup_qos
+    {
+        mock_server.send_puback(packet_identifier);
+    } else {
+        panic!("Expected QoS 1 request publish");
+    }
+    publish
+}
+
+/// Tests that an attempt that times out is retried with a new request within the timeout of the
+/// operation, and that the response to the retried request completes the operation
+#[tokio::test]
+async fn attempt_timeout_retried() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("retry_test_client", Duration::from_secs(1));
+    mock_server.expect_connect_and_accept(true).await;
+
+    let start = Instant::now();
+    let (result, ()) = tokio::join!(
+        state_store_client.get(b"testKey".to_vec(), Duration::from_secs(10)),
+        async {
+            // Key notification and response subscriptions
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+
+            // The first attempt is not responded to
+            let first_request = expect_request(&mock_server).await;
+            let retried_request = expect_request(&mock_server).await;
+            assert!(start.elapsed() >= Duration::from_secs(1));
+            assert_ne!(
+                first_request.other_properties.correlation_data,
+                retried_request.other_properties.correlation_data
+            );
+            assert_eq!(first_request.payload, retried_request.payload);
+
+            mock_server.send_publish(mqtt_proto::Publish {
+                topic_name: retried_request.other_properties.response_topic.unwrap(),
+                packet_identifier_dup_qos: mqtt_proto::PacketIdentifierDupQoS::AtLeastOnce(
+                    mqtt_proto::PacketIdentifier::new(1).unwrap(),
+                    false,
+                ),
+                retain: false,
+                payload: b"$5\r\nvalue\r\n".to_vec().into(),
+                other_properties: mqtt_proto::PublishOtherProperties {
+                    correlation_data: retried_request.other_properties.correlation_data,
+                    content_type: Some("application/octet-stream".into()),
+                    user_properties: vec![
+                        ("__stat".into(), "200".into()),
+                        ("__protVer".into(), "1.0".into()),
+                    ],
+                    ..Default::default()
+                },
+            });
+            mock_server.expect_puback().await;
+        }
+    );
+    assert_eq!(result.unwrap().response, Some(b"value".to_vec()));
+}
+
+/// Tests that attempts are retried until the timeout of the operation elapses, after which the
+/// timeout error of the last attempt is returned
+#[tokio::test]
+async fn attempt_timeout_retried_until_operation_timeout() {
+    let (state_store_client, mock_server) =
+        setup_client_and_mock_server("retry_exhausted_test_client", Duration::from_secs(1));
+    mock_server.expect_connect_and_accept(true).await;
+
+    let start = Instant::now();
+    let (result, ()) = tokio::join!(
+        state_store_client.get(b"testKey".to_vec(), Duration::from_secs(3)),
+        async {
+            mock_server.expect_subscribe_and_accept().await;
+            mock_server.expect_subscribe_and_accept().await;
+            for _ in 0..3 {
+                expect_request(&mock_server).await;
+            }
+        }
+    );
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(3));
+    assert!(elapsed < Duration::from_secs(4));
+    match result.unwrap_err().kind() {
+        ErrorKind::AIOProtocolError(e) => {
+            assert_eq!(e.kind, AIOProtocolErrorKind::Timeout);
+        }
+        e => panic!("Unexpected error: {e:?}"),
+    }
+    mock_server.expect_no_packet();
+}
@@ -0,0 +1,236 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Interop tests between dynamic envoys and the generated counter envoys.
+
+use std::{env, time::Duration};
+
+use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::session::{Session, SessionOptionsBuilder};
+use azure_iot_operations_protocol::{
+    application::ApplicationContextBuilder,
+    common::aio_protocol_error::AIOProtocolErrorKind,
+    dynamic::{DynamicCommand, DynamicTelemetry, JsonSchema},
+};
+use envoy::common_types::options::{CommandExecutorOptionsBuilder, TelemetrySenderOptionsBuilder};
+use envoy::counter::service::{
+    IncrementCommandExecutor, IncrementResponseBuilder, IncrementResponsePayload,
+    ReadCounterCommandExecutor, ReadCounterResponseBuilder, ReadCounterResponsePayload,
+    TelemetryCollectionBuilder, TelemetryMessageBuilder, TelemetrySender,
+};
+use envoy::counter::{REQUEST_TOPIC_PATTERN, TELEMETRY_TOPIC_PATTERN};
+use serde_json::json;
+
+const CLIENT_ID: &str = "dynamic_invoker_network_tests-rust";
+
+fn setup_test() -> Option<Session> {
+    let _ = env_logger::Builder::new()
+        .filter_level(log::LevelFilter::max())
+        .format_timestamp(None)
+        .filter_module("azure_mqtt", log::LevelFilter::Warn)
+        .try_init();
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        log::warn!("This test is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return None;
+    }
+
+    let connection_settings = MqttConnectionSettingsBuilder::default()
+        .client_id(CLIENT_ID)
+        .hostname("localhost")
+        .tcp_port(1883u16)
+        .keep_alive(Duration::from_secs(5))
+        .clean_start(true)
+        .use_tls(false)
+        .build()
+        .unwrap();
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()
+        .unwrap();
+    Some(Session::new(session_options).unwrap())
+}
+
+/// Dynamic invoker and receiver against the generated counter executors and sender
+#[tokio::test]
+async fn dynamic_invoker_generated_executor_network_tests() {
+    let Some(session) = setup_test() else {
+        // Network tests disabled, skipping tests
+        return;
+    };
+    let monitor = session.create_session_monitor();
+    let exit_handle = session.create_exit_handle();
+    let application_context = ApplicationContextBuilder::default().build().unwrap();
+
+    // Generated executors and sender, as in the counter server
+    let executor_options = CommandExecutorOptionsBuilder::default().build().unwrap();
+    let mut read_counter_executor = ReadCounterCommandExecutor::new(
+        application_context.clone(),
+        session.create_managed_client(),
+        &executor_options,
+    );
+    let mut increment_executor = IncrementCommandExecutor::new(
+        application_context.clone(),
+        session.create_managed_client(),
+        &executor_options,
+    );
+    let counter_sender = TelemetrySender::new(
+        application_context.clone(),
+        session.create_managed_client(),
+        &TelemetrySenderOptionsBuilder::default().build().unwrap(),
+    );
+
+    // Dynamic invokers and receiver defined at runtime
+    let counter_response_schema = JsonSchema::new(json!({
+        "type": "object",
+        "properties": { "CounterResponse": { "type": "integer" } },
+        "required": ["CounterResponse"]
+    }))
+    .unwrap();
+    let read_counter_invoker = DynamicCommand::new("readCounter", REQUEST_TOPIC_PATTERN)
+        .response_schema(counter_response_schema.clone())
+        .invoker(application_context.clone(), session.create_managed_client())
+        .unwrap();
+    let increment_invoker = DynamicCommand::new("increment", REQUEST_TOPIC_PATTERN)
+        .request_schema(
+            JsonSchema::new(json!({
+                "type": "object",
+                "properties": { "IncrementValue": { "type": "integer" } },
+                "required": ["IncrementValue"]
+            }))
+            .unwrap(),
+        )
+        .response_schema(counter_response_schema)
+        .invoker(application_context.clone(), session.create_managed_client())
+        .unwrap();
+    let mut counter_receiver = DynamicTelemetry::new(TELEMETRY_TOPIC_PATTERN)
+        .schema(
+            JsonSchema::new(json!({
+                "type": "object",
+                "properties": { "CounterValue": { "type": "integer" } }
+            }))
+            .unwrap(),
+        )
+        .receiver(application_context, session.create_managed_client(), true)
+        .unwrap();
+
+    let test_task = tokio::task::spawn(async move {
+        let executor_task = tokio::task::spawn(async move {
+            let mut counter = 0;
+            // Read, increment twice, read
+            let request = read_counter_executor.recv().await.unwrap().unwrap();
+            let response = ReadCounterResponseBuilder::default()
+                .payload(ReadCounterResponsePayload {
+                    counter_response: counter,
+                })
+                .unwrap()
+                .build()
+                .unwrap();
+            request.complete(response).await.unwrap();
+
+            for _ in 0..2 {
+                let request = increment_executor.recv().await.unwrap().unwrap();
+                counter += request.payload.increment_value;
+                let message = TelemetryMessageBuilder::default()
+                    .payload(
+                        TelemetryCollectionBuilder::default()
+                            .counter_value(Some(counter))
+                            .build()
+                            .unwrap(),
+                    )
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                counter_sender.send(message).await.unwrap();
+                let response = IncrementResponseBuilder::default()
+                    .payload(IncrementResponsePayload {
+                        counter_response: counter,
+                    })
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                request.complete(response).await.unwrap();
+            }
+
+            let request = read_counter_executor.recv().await.unwrap().unwrap();
+            let response = ReadCounterResponseBuilder::default()
+                .payload(ReadCounterResponsePayload {
+                    counter_response: counter,
+                })
+                .unwrap()
+                .build()
+                .unwrap();
+            request.complete(response).await.unwrap();
+
+            read_counter_executor.shutdown().await.unwrap();
+            increment_executor.shutdown().await.unwrap();
+        });
+
+        let receive_telemetry_task = tokio::task::spawn(async move {
+            for expected in 1..=2 {
+                let (message, _) = counter_receiver.recv().await.unwrap().unwrap();
+                assert_eq!(message.payload, json!({ "CounterValue": expected }));
+                assert_eq!(message.sender_id.unwrap(), CLIENT_ID);
+            }
+            counter_receiver.shutdown().await.unwrap();
+        });
+
+        // briefly wait after connection to let executors and receiver subscribe
+        monitor.connected().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let request = read_counter_invoker
+            .request_builder()
+            .timeout(Duration::from_secs(10))
+            .executor_id(CLIENT_ID)
+            .build()
+            .unwrap();
+        let response = read_counter_invoker.invoke(request).await.unwrap();
+        assert_eq!(response.payload, json!({ "CounterResponse": 0 }));
+
+        // Payloads violating the request schema are never sent
+        let error = increment_invoker
+            .request_builder()
+            .payload(json!({ "IncrementValue": "one" }))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, AIOProtocolErrorKind::PayloadInvalid);
+
+        for expected in 1..=2 {
+            let request = increment_invoker
+                .request_builder()
+                .timeout(Duration::from_secs(10))
+                .executor_id(CLIENT_ID)
+                .payload(json!({ "IncrementValue": 1 }))
+                .unwrap()
+                .build()
+                .unwrap();
+            let response = increment_invoker.invoke(request).await.unwrap();
+            assert_eq!(response.payload, json!({ "CounterResponse": expected }));
+        }
+
+        let request = read_counter_invoker
+            .request_builder()
+            .timeout(Duration::from_secs(10))
+            .executor_id(CLIENT_ID)
+            .build()
+            .unwrap();
+        let response = read_counter_invoker.invoke(request).await.unwrap();
+        assert_eq!(response.payload, json!({ "CounterResponse": 2 }));
+
+        assert!(executor_task.await.is_ok());
+        assert!(receive_telemetry_task.await.is_ok());
+        read_counter_invoker.shutdown().await.unwrap();
+        increment_invoker.shutdown().await.unwrap();
+        exit_handle.try_exit().unwrap();
+    });
+
+    // if an assert fails in the test task, propagate the panic to end the test,
+    // while still running the test task and the session to completion on the happy path
+    assert!(
+        tokio::try_join!(
+            async move { test_task.await.map_err(|e| e.to_string()) },
+            async move { session.run().await.map_err(|e| e.to_string()) }
+        )
+        .is_ok()
+    );
+}
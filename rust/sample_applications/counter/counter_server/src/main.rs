@@ -69,6 +69,8 @@ async fn read_counter_executor(
     let options = CommandExecutorOptionsBuilder::default().build().unwrap();
     let mut read_counter_executor =
         ReadCounterCommandExecutor::new(application_context, client, &options);
+    // Subscribe before receiving so that no early requests are missed
+    read_counter_executor.start().await.unwrap();
 
     // Respond to each read request with the current counter value
     loop {
@@ -96,6 +98,7 @@ async fn increment_counter_and_publish(
     let options = CommandExecutorOptionsBuilder::default().build().unwrap();
     let mut increment_executor =
         IncrementCommandExecutor::new(application_context.clone(), client.clone(), &options);
+    increment_executor.start().await.unwrap();
 
     // Create sender
     let counter_sender = TelemetrySender::new(
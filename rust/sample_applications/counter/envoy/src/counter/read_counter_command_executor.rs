@@ -105,6 +105,16 @@ impl ReadCounterCommandExecutor {
         )
     }
 
+    /// Start the [`ReadCounterCommandExecutor`]. Subscribes to the request topic and returns once the subscription
+    /// has been acknowledged. If not called, the subscribe happens on the first call to `recv`.
+    ///
+    /// Returns Ok(()) on success, otherwise returns [`AIOProtocolError`].
+    /// # Errors
+    /// [`AIOProtocolError`] of kind [`ClientError`](azure_iot_operations_protocol::common::aio_protocol_error::AIOProtocolErrorKind::ClientError) if the subscribe fails or if the suback reason code doesn't indicate success.
+    pub async fn start(&mut self) -> Result<(), AIOProtocolError> {
+        self.0.start().await
+    }
+
     /// Receive the next [`ReadCounterRequest`] or [`None`] if there will be no more requests
     ///
     /// # Errors
@@ -4,7 +4,7 @@
 //! This sample application demonstrates how to create an event-driven application that receives
 //! incoming sensor data and stores it in a state store.
 
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use azure_iot_operations_mqtt::{
     aio::connection_settings::MqttConnectionSettingsBuilder,
@@ -12,9 +12,7 @@ use azure_iot_operations_mqtt::{
 };
 use azure_iot_operations_protocol::{
     application::{ApplicationContext, ApplicationContextBuilder},
-    common::payload_serialize::{
-        DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
-    },
+    common::payload_serialize::Json,
     telemetry,
 };
 use azure_iot_operations_services::state_store::{self, SetOptions};
@@ -85,7 +83,7 @@ async fn receive_telemetry(
         .build()
         .expect("Telemetry receiver options should not fail");
 
-    let mut telemetry_receiver: telemetry::Receiver<SensorData> =
+    let mut telemetry_receiver: telemetry::Receiver<Json<SensorData>> =
         telemetry::Receiver::new(application_context, client, receiver_options)
             .expect("Telemetry receiver creation should not fail");
 
@@ -93,8 +91,14 @@ async fn receive_telemetry(
     while let Some(message) = telemetry_receiver.recv().await {
         match message {
             Ok((message, _ack_token)) => {
+                // Keep the cloud event of the sensor data, so that windows can reference it
+                let cloud_event = telemetry::receiver::cloud_event_from_telemetry(&message)
+                    .ok()
+                    .map(SensorCloudEvent::from);
+                let Json(mut sensor_data) = message.payload;
+                sensor_data.cloud_event = cloud_event;
                 sensor_data_processing_tx
-                    .send(message.payload)
+                    .send(sensor_data)
                     .expect("receiver end should not be dropped");
             }
             Err(e) => {
@@ -145,19 +149,26 @@ async fn process_sensor_data(
                     Some(serialized_data) => match serde_json::from_slice(&serialized_data) {
                         Ok(sensor_data) => sensor_data,
                         Err(e) => {
-                            // If we can't deserialize the data, delete the key
+                            // If we can't deserialize the data, delete the key, unless it has
+                            // since been overwritten by another writer
                             log::error!(
                                 "Unable to deserialize state store data, deleting the key: {e:?}"
                             );
                             match state_store_client
-                                .del(
+                                .del_if_equal(
                                     STATE_STORE_SENSOR_KEY.into(),
-                                    None,
+                                    serialized_data,
                                     DEFAULT_STATE_STORE_OPERATION_TIMEOUT,
                                 )
                                 .await
                             {
-                                Ok(_) => { /* Success */ }
+                                Ok(del_response) => {
+                                    if !del_response.response {
+                                        log::warn!(
+                                            "State store data changed since it was fetched, not deleting the key"
+                                        );
+                                    }
+                                }
                                 Err(e) => {
                                     log::error!("Failed to delete state store data: {e:?}");
                                 }
@@ -212,32 +223,30 @@ pub struct SensorData {
     pub pressure: f64,
     pub vibration: f64,
     pub msg_number: i64,
+    /// Attributes of the cloud event the sensor data was received with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_event: Option<SensorCloudEvent>,
 }
 
-impl PayloadSerialize for SensorData {
-    type Error = String;
-
-    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
-        unreachable!("This method should not be called");
-    }
+/// Cloud event attributes of received sensor data, kept to trace the lineage of windows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorCloudEvent {
+    pub id: String,
+    pub source: String,
+    pub event_type: String,
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+}
 
-    fn deserialize(
-        payload: &[u8],
-        content_type: Option<&String>,
-        _format_indicator: &FormatIndicator,
-    ) -> Result<Self, DeserializationError<Self::Error>> {
-        if let Some(content_type) = content_type
-            && content_type != "application/json"
-        {
-            return Err(DeserializationError::UnsupportedContentType(format!(
-                "Invalid content type: '{content_type:?}'. Must be 'application/json'"
-            )));
+impl From<telemetry::receiver::CloudEvent> for SensorCloudEvent {
+    fn from(cloud_event: telemetry::receiver::CloudEvent) -> Self {
+        SensorCloudEvent {
+            id: cloud_event.id,
+            source: cloud_event.source,
+            event_type: cloud_event.event_type,
+            time: cloud_event.time,
+            extensions: cloud_event.extensions,
         }
-
-        let payload = serde_json::from_slice(payload).map_err(|e| {
-            DeserializationError::InvalidPayload(format!("Failed to deserialize payload: {e}"))
-        })?;
-
-        Ok(payload)
     }
 }
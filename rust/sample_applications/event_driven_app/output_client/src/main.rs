@@ -3,18 +3,23 @@
 
 //! This sample application demonstrates how to create an event-driven application that gets sensor
 //! data from a state store, aggregates it into windows, and publishes the window data.
+//!
+//! Each window is published with a cloud event derived from the cloud events of the sensor data it
+//! aggregates, when the sensor data was received with cloud events.
+//!
+//! If `DATASET_DEVICE_NAME`, `DATASET_ASSET_NAME` and `DATASET_NAME` are set, the latest value of
+//! the dataset forwarded to the state store by a connector is also logged with each window.
 
-use std::time::Duration;
+use std::{collections::BTreeMap, env, time::Duration};
 
+use azure_iot_operations_connector::{DataOperationName, DataOperationRef, state_store_layout};
 use azure_iot_operations_mqtt::{
-    aio::connection_settings::MqttConnectionSettingsBuilder,
+    aio::{cloud_event::CloudEventBuilder, connection_settings::MqttConnectionSettingsBuilder},
     session::{Session, SessionManagedClient, SessionMonitor, SessionOptionsBuilder},
 };
 use azure_iot_operations_protocol::{
     application::{ApplicationContext, ApplicationContextBuilder},
-    common::payload_serialize::{
-        DeserializationError, FormatIndicator, PayloadSerialize, SerializedPayload,
-    },
+    common::payload_serialize::Json,
     telemetry,
 };
 use azure_iot_operations_services::state_store::{self};
@@ -77,6 +82,9 @@ async fn process_window(
         telemetry::Sender::new(application_context.clone(), client.clone(), sender_options)
             .expect("Telemetry sender creation should not fail");
 
+    // Get the connector dataset to log, if configured
+    let dataset_ref = connector_dataset_ref();
+
     // Create state store client
     let state_store_client = state_store::Client::new(
         application_context,
@@ -92,6 +100,25 @@ async fn process_window(
         // Wait before processing the next window
         tokio::time::sleep(PUBLISH_INTERVAL).await;
 
+        // Get the latest value of the connector dataset from the state store
+        if let Some(dataset_ref) = &dataset_ref {
+            match state_store_layout::read_dataset_value(
+                &state_store_client,
+                dataset_ref,
+                DEFAULT_STATE_STORE_OPERATION_TIMEOUT,
+            )
+            .await
+            {
+                Ok(Some(value)) => log::info!(
+                    "Latest connector dataset value ({}): {}",
+                    value.content_type,
+                    String::from_utf8_lossy(&value.payload)
+                ),
+                Ok(None) => log::info!("Connector dataset value not found in state store"),
+                Err(e) => log::error!("{e:?}"),
+            }
+        }
+
         // Get the past sensor data from the state store
         let get_result = state_store_client
             .get(
@@ -134,9 +161,16 @@ async fn process_window(
                                 .expect("output_window_data should contain all fields");
                             let output_data_clone = output_window_data.clone();
 
+                            // Derive the cloud event of the window from those of the sensor data
+                            let source_events: Vec<_> = sensor_data
+                                .iter()
+                                .filter_map(|d| d.cloud_event.as_ref()?.to_cloud_event())
+                                .collect();
+
                             let message = telemetry::sender::MessageBuilder::default()
-                                .payload(output_window_data)
+                                .payload(Json(output_window_data))
                                 .expect("output_window_data is a valid payload")
+                                .cloud_event(window_cloud_event(&source_events))
                                 .build()
                                 .expect("message should contain all fields");
 
@@ -169,6 +203,39 @@ async fn process_window(
     }
 }
 
+/// Cloud event for a window, derived from the cloud events of the sensor data in the window
+fn window_cloud_event(
+    source_events: &[telemetry::receiver::CloudEvent],
+) -> Option<telemetry::sender::CloudEvent> {
+    if source_events.is_empty() {
+        return None;
+    }
+    match telemetry::sender::CloudEventBuilder::default()
+        .derive_from_sources(source_events)
+        .build()
+    {
+        Ok(cloud_event) => Some(cloud_event),
+        Err(e) => {
+            // e.g. the sensor data does not share a source
+            log::warn!("Unable to derive cloud event for window: {e}");
+            None
+        }
+    }
+}
+
+/// Reference to the connector dataset to log, if configured in the environment
+fn connector_dataset_ref() -> Option<DataOperationRef> {
+    Some(DataOperationRef {
+        data_operation_name: DataOperationName::Dataset {
+            name: env::var("DATASET_NAME").ok()?,
+        },
+        asset_name: env::var("DATASET_ASSET_NAME").ok()?,
+        device_name: env::var("DATASET_DEVICE_NAME").ok()?,
+        // The inbound endpoint is not part of the state store key
+        inbound_endpoint_name: String::new(),
+    })
+}
+
 // Sensor Data
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SensorData {
@@ -178,33 +245,33 @@ pub struct SensorData {
     pub pressure: f64,
     pub vibration: f64,
     pub msg_number: i64,
+    /// Attributes of the cloud event the sensor data was received with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_event: Option<SensorCloudEvent>,
 }
 
-impl PayloadSerialize for SensorData {
-    type Error = String;
-
-    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
-        unreachable!("This method should not be called");
-    }
-
-    fn deserialize(
-        payload: &[u8],
-        content_type: Option<&String>,
-        _format_indicator: &FormatIndicator,
-    ) -> Result<Self, DeserializationError<Self::Error>> {
-        if let Some(content_type) = content_type
-            && content_type != "application/json"
-        {
-            return Err(DeserializationError::UnsupportedContentType(format!(
-                "Invalid content type: '{content_type:?}'. Must be 'application/json'"
-            )));
-        }
-
-        let payload = serde_json::from_slice(payload).map_err(|e| {
-            DeserializationError::InvalidPayload(format!("Failed to deserialize payload: {e}"))
-        })?;
+/// Cloud event attributes of received sensor data, kept to trace the lineage of windows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorCloudEvent {
+    pub id: String,
+    pub source: String,
+    pub event_type: String,
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+}
 
-        Ok(payload)
+impl SensorCloudEvent {
+    /// Rebuild the cloud event of the sensor data, if it is still valid
+    fn to_cloud_event(&self) -> Option<telemetry::receiver::CloudEvent> {
+        CloudEventBuilder::default()
+            .id(self.id.clone())
+            .source(self.source.clone())
+            .event_type(self.event_type.clone())
+            .time(self.time)
+            .extensions(self.extensions.clone())
+            .build()
+            .ok()
     }
 }
 
@@ -264,23 +331,3 @@ impl From<Vec<f64>> for WindowSensorData {
         }
     }
 }
-
-impl PayloadSerialize for WindowData {
-    type Error = String;
-
-    fn serialize(self) -> Result<SerializedPayload, Self::Error> {
-        Ok(SerializedPayload {
-            payload: serde_json::to_vec(&self).expect("A valid payload should serialize"),
-            content_type: "application/json".to_string(),
-            format_indicator: FormatIndicator::Utf8EncodedCharacterData,
-        })
-    }
-
-    fn deserialize(
-        _payload: &[u8],
-        _content_type: Option<&String>,
-        _format_indicator: &FormatIndicator,
-    ) -> Result<Self, DeserializationError<Self::Error>> {
-        unreachable!("This method should not be called");
-    }
-}
@@ -36,6 +36,14 @@
 //! - Use `report_health_event(RuntimeHealthEvent::Available)` when operations succeed.
 //! - Use `report_health_event(RuntimeHealthEvent::Unavailable { message, reason_code })` when operations fail.
 //! - Use `pause_and_refresh_health_version()` when configuration updates occur to avoid reporting stale health.
+//! - Dataset failures are categorized with `DatasetFailure`, so that each category is reported with its own reason code:
+//!   a **source** failure (couldn't read from the device) means the device should be checked, while a
+//!   **sink** failure (couldn't publish to the destination) means the broker or network should be checked.
+//!
+//! ### Warm Standby
+//! - A connector started passive with `start_passive(true)` creates all of its handlers, but doesn't sample until it is activated.
+//! - Activate or deactivate it at runtime with the `ActivationGate` from `base_connector.activation_gate()`, e.g. from a leader election.
+//! - The dataset handler only samples while the dataset, asset and device endpoint are ready AND the connector is active.
 //!
 //! ### When to Use Configuration Status vs Health Events
 //! A good rule for deciding whether to report something as configuration status vs health status:
@@ -58,10 +66,11 @@ use azure_iot_operations_connector::{
     AdrConfigError, Data,
     base_connector::{
         self, BaseConnector,
+        activation::ActivationGate,
         managed_azure_device_registry::{
             AssetClient, AssetComponentClient, AssetSpecification, ClientNotification,
-            DataOperationClient, DataOperationNotification, DeviceEndpointClient,
-            DeviceEndpointClientCreationObservation, ManagementActionClient,
+            DataOperationClient, DataOperationDefinition, DataOperationNotification,
+            DeviceEndpointClient, DeviceEndpointClientCreationObservation, ManagementActionClient,
             ManagementActionNotification, ModifyResult, RuntimeHealthEvent, SchemaModifyResult,
             UnsupportedComponentClient, UnsupportedComponentNotification,
         },
@@ -72,6 +81,7 @@ use azure_iot_operations_connector::{
         ManagementActionApplicationError, ManagementActionExecutor, ManagementActionRequest,
         ManagementActionResponseBuilder,
     },
+    message_schema::is_equivalent,
 };
 use azure_iot_operations_protocol::{
     application::ApplicationContextBuilder, common::hybrid_logical_clock::HybridLogicalClock,
@@ -80,6 +90,7 @@ use azure_iot_operations_services::azure_device_registry::models::ActionType;
 use tokio::sync::watch;
 
 const DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_millis(10000); // Default sampling interval in milliseconds
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(25); // Within the default Kubernetes termination grace period of 30 seconds
 
 /// Macro that generates closures for reporting status with one-way transitions.
 ///
@@ -127,6 +138,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let application_context = ApplicationContextBuilder::default().build()?;
 
     // Create options for the base connector, IMPLEMENT: Customize as needed
+    // NOTE: To run this instance as a warm standby, start it passive with `.start_passive(true)` and
+    // activate it once it should take over (see the activation gate below)
     let base_connector_options = base_connector::OptionsBuilder::default().build()?;
 
     // Create the Base Connector to handle device endpoints, assets, and datasets creation, update and deletion notifications plus status reporting.
@@ -138,11 +151,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create a device endpoint client creation observation
     let device_endpoint_client_creation_observation =
-        base_connector.create_device_endpoint_client_create_observation();
+        base_connector.create_device_endpoint_client_create_observation()?;
+
+    // The activation gate controls whether this instance samples and forwards data. It starts active
+    // unless the base connector was started passive.
+    // IMPLEMENT: If running as a warm standby, call `activation_gate.activate()` and `activation_gate.deactivate()`
+    // from whatever decides which instance is active (e.g. a leader election or an external signal).
+    let activation_gate = base_connector.activation_gate();
+
+    // Drain outgoing messages and end the session cleanly when the pod is stopped with SIGTERM,
+    // which ends `base_connector.run()`
+    let graceful_shutdown = base_connector.create_graceful_shutdown(SHUTDOWN_GRACE_PERIOD);
+    tokio::task::spawn(async move {
+        match graceful_shutdown.run().await {
+            Ok((signal, outcome)) => log::info!("Shut down on {signal:?}: {outcome:?}"),
+            Err(e) => log::error!("Could not handle shutdown signals: {e}"),
+        }
+    });
 
     // Run the session and the base connector concurrently, ending the application if either end (both should run forever unless there are fatal errors)
     tokio::select! {
-        () = receive_device_endpoints(device_endpoint_client_creation_observation) => {
+        () = receive_device_endpoints(device_endpoint_client_creation_observation, activation_gate) => {
             log::warn!("Connector Application tasks ended");
             Ok(())
         },
@@ -165,8 +194,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///
 /// # Arguments
 /// * `device_endpoint_client_creation_observation` - The device endpoint client creation observation.
+/// * `activation_gate` - The activation gate of the base connector.
 async fn receive_device_endpoints(
     mut device_endpoint_client_creation_observation: DeviceEndpointClientCreationObservation,
+    activation_gate: ActivationGate,
 ) {
     loop {
         let device_endpoint_client = device_endpoint_client_creation_observation
@@ -186,6 +217,7 @@ async fn receive_device_endpoints(
         tokio::task::spawn(device_handler(
             device_endpoint_log_identifier,
             device_endpoint_client,
+            activation_gate.clone(),
         ));
     }
 }
@@ -196,9 +228,11 @@ async fn receive_device_endpoints(
 /// # Arguments
 /// * `device_endpoint_log_identifier` - A string identifier for the device endpoint, used for logging.
 /// * `device_endpoint_client` - The device endpoint client.
+/// * `activation_gate` - The activation gate of the base connector.
 async fn device_handler(
     device_endpoint_log_identifier: String,
     mut device_endpoint_client: DeviceEndpointClient,
+    activation_gate: ActivationGate,
 ) {
     // Get the status reporter for the device endpoint
     let mut device_endpoint_status_reporter = device_endpoint_client.get_status_reporter();
@@ -369,6 +403,7 @@ async fn device_handler(
                     asset_log_identifier,
                     asset_client,
                     device_endpoint_ready_watcher_tx.subscribe(),
+                    activation_gate.clone(),
                 ));
             }
             ClientNotification::Deleted => {
@@ -378,6 +413,26 @@ async fn device_handler(
                 // The device endpoint ready state does not need to be updated here because all lower components will also get deleted
                 break;
             }
+            ClientNotification::OwnershipLost => {
+                // Only received if `exclusive_device_ownership` is enabled on the base connector.
+                // Another instance of the connector may now own this device endpoint, so stop sampling.
+                log::warn!(
+                    "{device_endpoint_log_identifier} Device endpoint ownership lost, pausing sampling"
+                );
+                device_endpoint_ready_watcher_tx.send_if_modified(send_if_modified_fn(false));
+            }
+            ClientNotification::OwnershipRegained => {
+                log::info!(
+                    "{device_endpoint_log_identifier} Device endpoint ownership regained, resuming sampling"
+                );
+                if device_endpoint_client
+                    .specification()
+                    .enabled
+                    .is_none_or(|enabled| enabled)
+                {
+                    device_endpoint_ready_watcher_tx.send_if_modified(send_if_modified_fn(true));
+                }
+            }
         }
     }
 }
@@ -388,25 +443,33 @@ async fn device_handler(
 /// * `asset_log_identifier` - A string identifier for the asset, used for logging.
 /// * `asset_client` - The asset client.
 /// * `device_endpoint_ready_watcher_rx` - A watcher for the device endpoint readiness state.
+/// * `activation_gate` - The activation gate of the base connector.
 async fn asset_handler(
     asset_log_identifier: String,
     mut asset_client: AssetClient,
     device_endpoint_ready_watcher_rx: watch::Receiver<bool>,
+    activation_gate: ActivationGate,
 ) {
     // Get the status reporter for the asset
     let asset_status_reporter = asset_client.get_status_reporter();
 
-    // IMPLEMENT: add any Asset validation here and report errors or Ok status
-    // If the asset specification has a default_dataset_configuration, then the asset status
-    // may not be able to be reported until the dataset level can validate this field.
-
-    // For this example, we will assume that the asset specification is OK. Modify this to report any errors
+    // IMPLEMENT: add any Asset validation to `validate_asset` and report errors or Ok status
+    let asset_status = validate_asset(
+        asset_client
+            .specification()
+            .default_datasets_configuration
+            .as_deref(),
+        asset_client.has_datasets(),
+    );
+    if let Err(e) = &asset_status {
+        log::error!("{asset_log_identifier} Asset has an invalid configuration: {e:?}");
+    }
     match asset_status_reporter
-        .report_status_if_modified(report_status_one_way!(Ok::<(), AdrConfigError>(())))
+        .report_status_if_modified(report_status_one_way!(asset_status.clone()))
         .await
     {
         Ok(ModifyResult::Reported) => {
-            log::info!("{asset_log_identifier} Asset status reported as OK");
+            log::info!("{asset_log_identifier} Asset status reported");
         }
         Ok(ModifyResult::NotModified) => {} // No change, do nothing
         Err(e) => {
@@ -420,15 +483,25 @@ async fn asset_handler(
             ClientNotification::Updated => {
                 log::info!("{asset_log_identifier} Asset update notification received");
 
-                // IMPLEMENT: Add custom asset update/validation logic here
-
-                // For this example, we will assume that the asset specification is OK. Modify this to report any errors
+                // IMPLEMENT: Add custom asset update logic here, and any Asset validation to `validate_asset`
+                // Datasets may have been added or removed, so whether the default dataset configuration
+                // is validated at the asset level may have changed too
+                let asset_status = validate_asset(
+                    asset_client
+                        .specification()
+                        .default_datasets_configuration
+                        .as_deref(),
+                    asset_client.has_datasets(),
+                );
+                if let Err(e) = &asset_status {
+                    log::error!("{asset_log_identifier} Asset has an invalid configuration: {e:?}");
+                }
                 match asset_status_reporter
-                    .report_status_if_modified(report_status_one_way!(Ok::<(), AdrConfigError>(())))
+                    .report_status_if_modified(report_status_one_way!(asset_status.clone()))
                     .await
                 {
                     Ok(ModifyResult::Reported) => {
-                        log::info!("{asset_log_identifier} Asset status reported as OK");
+                        log::info!("{asset_log_identifier} Asset status reported");
                     }
                     Ok(ModifyResult::NotModified) => {} // No change, do nothing
                     Err(e) => {
@@ -460,6 +533,7 @@ async fn asset_handler(
                             data_operation_client,
                             initial_data_operation_status,
                             device_endpoint_ready_watcher_rx.clone(),
+                            activation_gate.subscribe(),
                         ));
                     }
                     azure_iot_operations_connector::DataOperationKind::Event
@@ -521,6 +595,8 @@ async fn asset_handler(
                 // The asset ready state does not need to be updated here because all data operations will also get deleted
                 break;
             }
+            // Ownership notifications are only received on device endpoint clients
+            ClientNotification::OwnershipLost | ClientNotification::OwnershipRegained => {}
         }
     }
 }
@@ -532,11 +608,13 @@ async fn asset_handler(
 /// * `data_operation_client` - The data operation client we use for operations related to the dataset.
 /// * `initial_data_operation_status` - Whether the SDK detected an initial error with the dataset.
 /// * `device_endpoint_ready_watcher_rx` - A watcher for the device endpoint readiness state.
+/// * `active_watcher_rx` - A watcher for whether the connector is active.
 async fn handle_dataset(
     dataset_log_identifier: String,
     mut data_operation_client: DataOperationClient,
     initial_data_operation_status: Result<(), AdrConfigError>,
     mut device_endpoint_ready_watcher_rx: watch::Receiver<bool>,
+    mut active_watcher_rx: watch::Receiver<bool>,
 ) {
     // Get the status reporter for the data operation
     let mut data_operation_status_reporter = data_operation_client.get_status_reporter();
@@ -547,6 +625,8 @@ async fn handle_dataset(
         .enabled
         .is_none_or(|enabled| enabled);
     let mut is_device_endpoint_ready = *device_endpoint_ready_watcher_rx.borrow_and_update();
+    // Datasets are only sampled while the connector is active, so that a warm standby doesn't sample
+    let mut is_active = *active_watcher_rx.borrow_and_update();
     // This boolean tracks if the dataset is ready to be sampled.
     let mut is_dataset_ready;
     // This variable keeps track of the latest reported schema.
@@ -556,13 +636,15 @@ async fn handle_dataset(
 
     // Extract the dataset definition from the dataset client
     let mut _local_dataset_definition = data_operation_client.definition().clone();
-    // These variables keep track of the latest reported dataset status
-    let mut is_sdk_error_causing_invalid_state = initial_data_operation_status.is_err();
+    // This variable keeps track of the latest reported dataset status
     let mut last_reported_dataset_status = match initial_data_operation_status {
         Ok(()) => {
-            // IMPLEMENT: If the sdk didn't detect an initial error, verify whether the dataset definition is OK.
-            // For this example, we will assume that no additional validation is needed
-            Ok(())
+            // IMPLEMENT: If the sdk didn't detect an initial error, verify whether the dataset definition is OK
+            // by adding any validation to `validate_dataset`.
+            validate_dataset(
+                data_operation_client.definition(),
+                &data_operation_client.asset_specification(),
+            )
         }
         Err(e) => Err(e),
     };
@@ -609,6 +691,22 @@ async fn handle_dataset(
 
                 log::debug!("{dataset_log_identifier} Device endpoint ready state changed to {is_device_endpoint_ready}");
             },
+            // Monitor for the connector being activated or deactivated
+            res = active_watcher_rx.changed() => {
+                if res.is_err() {
+                    // The activation gate is held by the base connector, so this only happens when the connector is shutting down
+                    log::info!("{dataset_log_identifier} Connector shutting down, ending dataset handler");
+                    break;
+                }
+                is_active = *active_watcher_rx.borrow_and_update();
+                if !is_active {
+                    // While the connector is passive, any health event is reported as standby instead, so report
+                    // one to replace the last health event. Health is reported again after the next successful
+                    // sampling cycle once the connector is activated.
+                    data_operation_status_reporter.report_health_event(RuntimeHealthEvent::Available);
+                }
+                log::info!("{dataset_log_identifier} Connector active state changed to {is_active}");
+            },
             data_operation_notification = data_operation_client.recv_notification() => {
                 // Pause health reporting until we validate the new configuration and successfully
                 // complete a sampling cycle. This prevents reporting stale health status from
@@ -624,32 +722,43 @@ async fn handle_dataset(
                 match data_operation_notification {
                     DataOperationNotification::Updated(Ok(())) => {
                         // If we receive an `Ok(())` update from the SDK, then the SDK is not currently detecting any errors with the dataset definition.
-                        is_sdk_error_causing_invalid_state = false;
                         log::info!("{dataset_log_identifier} Dataset update notification received. Current Asset ready state is {is_asset_ready}.");
 
                         // Update the local dataset definition
                         _local_dataset_definition = data_operation_client.definition().clone();
 
-                        // IMPLEMENT: Verify the dataset specification is OK and send an error report if needed
-                        last_reported_dataset_status = Ok(());
+                        // IMPLEMENT: Verify the dataset specification is OK in `validate_dataset`
+                        last_reported_dataset_status = validate_dataset(
+                            data_operation_client.definition(),
+                            &data_operation_client.asset_specification(),
+                        );
                     },
                     DataOperationNotification::AssetUpdated(Ok(())) => {
                         log::info!("{dataset_log_identifier} Asset update notification received. Current Asset ready state is {is_asset_ready}.");
-                        if is_sdk_error_causing_invalid_state {
-                            // IMPLEMENT: If the data operation wasn't valid because of an error detected from the SDK, re-evaluate the definition to see if it's valid now
-                            last_reported_dataset_status = Ok(());
-                        }
                         // If we receive an `Ok(())` update from the SDK, then the SDK is not currently detecting any errors with the dataset definition.
-                        is_sdk_error_causing_invalid_state = false;
+                        // Re-evaluate the definition, since the asset's default dataset configuration may have changed, and
+                        // the dataset may now be valid if it wasn't because of an error detected from the SDK
+                        last_reported_dataset_status = validate_dataset(
+                            data_operation_client.definition(),
+                            &data_operation_client.asset_specification(),
+                        );
                     },
                     DataOperationNotification::Updated(Err(e)) | DataOperationNotification::AssetUpdated(Err(e))=> {
-                        is_sdk_error_causing_invalid_state = true;
                         log::error!("{dataset_log_identifier} Dataset update notification received with invalid configuration: {e}");
                         last_reported_dataset_status = Err(e);
                     },
                     DataOperationNotification::Deleted => {
                         // The dataset client has been deleted, we need to end the dataset handler
                         log::info!("{dataset_log_identifier} Dataset deleted notification received, ending dataset handler");
+                        // IMPLEMENT: Any buffered samples can still be forwarded with `forward_data` here, since the
+                        // destination remains usable for the deletion grace period configured on the base connector.
+
+                        // Send a tombstone so downstream consumers know this dataset no longer produces data.
+                        // This deletes the state store key or clears the retained MQTT message, depending on the destination.
+                        match data_operation_client.final_flush().await {
+                            Ok(()) => log::info!("{dataset_log_identifier} Dataset tombstone sent"),
+                            Err(e) => log::warn!("{dataset_log_identifier} Failed to send dataset tombstone: {e}"),
+                        }
                         break;
                     }
                 }
@@ -670,7 +779,7 @@ async fn handle_dataset(
                     }
                 }
             },
-            _ = timer.tick(), if is_dataset_ready && is_asset_ready && is_device_endpoint_ready => {
+            _ = timer.tick(), if is_dataset_ready && is_asset_ready && is_device_endpoint_ready && is_active => {
                 log::debug!("{dataset_log_identifier} Sampling!");
 
                 // IMPLEMENT: This should be replaced with the actual sampling logic.
@@ -679,10 +788,9 @@ async fn handle_dataset(
                     Err(e) => {
                         log::error!("{dataset_log_identifier} Sampling failed: {e}");
                         // Report Unavailable when sampling fails
-                        data_operation_status_reporter.report_health_event(RuntimeHealthEvent::Unavailable {
-                            message: Some(format!("Sampling failed: {e}")),
-                            reason_code: Some("SampleConnectorSamplingFailure".to_string()),
-                        });
+                        data_operation_status_reporter.report_health_event(
+                            DatasetFailure::Source(format!("Sampling failed: {e}")).health_event(),
+                        );
                         continue;
                     }
                 };
@@ -706,19 +814,19 @@ async fn handle_dataset(
                     // If we fail to create the message schema, we will not be able to report it or forward data.
                     // NOTE: Failing to create the message schema could be due to malformed data, so waiting for
                     // a dataset definition update on this failure is not desirable.
-                    data_operation_status_reporter.report_health_event(RuntimeHealthEvent::Unavailable {
-                        message: Some("Failed to create message schema. Response data may be malformed or in an unexpected format.".to_string()),
-                        reason_code: Some("SampleConnectorSchemaGenerationFailure".to_string()),
-                    });
+                    data_operation_status_reporter.report_health_event(
+                        DatasetFailure::Source("Failed to create message schema. Response data may be malformed or in an unexpected format.".to_string()).health_event(),
+                    );
                     continue;
                 };
 
                 // Report the message schema if needed
                 match data_operation_client.report_message_schema_if_modified(|schema_ref| {
-                    // Report unless we've already reported this exact schema with the same reference
+                    // Report unless we've already reported an equivalent schema with the same reference.
+                    // NOTE: Schemas that only differ in JSON key ordering or whitespace are equivalent
                     if let (Some(schema_ref), Some(last_reported_ref), Some(last_reported_schema)) = (schema_ref, &last_reported_schema_reference, last_reported_schema.as_ref()) {
-                        if schema_ref == last_reported_ref && message_schema == *last_reported_schema {
-                            // Already reported this exact schema
+                        if schema_ref == last_reported_ref && is_equivalent(&message_schema, last_reported_schema) {
+                            // Already reported this schema
                             None
                         } else {
                             Some(message_schema.clone())
@@ -736,6 +844,9 @@ async fn handle_dataset(
                     Err(e) => {
                         log::error!("{dataset_log_identifier} Failed to report message schema: {e}");
                         // If we fail to report the message schema, we will not be able to forward the data
+                        data_operation_status_reporter.report_health_event(
+                            DatasetFailure::Sink(format!("Failed to report message schema: {e}")).health_event(),
+                        );
                         continue;
                     }
                 }
@@ -745,13 +856,17 @@ async fn handle_dataset(
 
                 // IMPLEMENT: Handle errors forwarding the data.
                 match data_operation_client.forward_data(data).await {
-                    Ok(()) => {
+                    Ok(_) => {
                         // For this connector, report dataset healthy after successful data forwarding.
                         // This indicates the full sampling cycle completed successfully.
                         data_operation_status_reporter.report_health_event(RuntimeHealthEvent::Available);
                     }
                     Err(e) => {
                         log::error!("{dataset_log_identifier} Failed to forward data: {e}");
+                        // Report Unavailable when forwarding fails, distinctly from sampling failures
+                        data_operation_status_reporter.report_health_event(
+                            DatasetFailure::Sink(format!("Failed to forward data: {e}")).health_event(),
+                        );
                     }
                 }
             }
@@ -1199,6 +1314,74 @@ impl ActionState {
     }
 }
 
+/// Validates the asset level of an asset definition.
+///
+/// The asset's `default_datasets_configuration` applies to each dataset that doesn't specify its own
+/// `dataset_configuration`, so it is validated by those datasets in `validate_dataset` and any error
+/// is reported on the dataset that uses it. When the asset has no datasets, nothing would ever
+/// validate it, so it is validated here instead and any error is reported on the asset.
+///
+/// # Arguments
+/// * `default_datasets_configuration` - The asset's default dataset configuration, if any.
+/// * `has_datasets` - Whether the asset defines any datasets.
+fn validate_asset(
+    default_datasets_configuration: Option<&str>,
+    has_datasets: bool,
+) -> Result<(), AdrConfigError> {
+    // IMPLEMENT: Add any other asset level validation here
+    match default_datasets_configuration {
+        Some(configuration) if !has_datasets => validate_dataset_configuration(configuration)
+            .map_err(|mut e| {
+                e.message = e
+                    .message
+                    .map(|message| format!("Invalid default dataset configuration: {message}"));
+                e
+            }),
+        // Either there's nothing to validate, or the datasets validate it
+        _ => Ok(()),
+    }
+}
+
+/// Validates a dataset definition, including the asset's `default_datasets_configuration` if the
+/// dataset doesn't specify its own `dataset_configuration`.
+///
+/// # Arguments
+/// * `definition` - The definition of the data operation.
+/// * `asset_specification` - The specification of the asset the data operation belongs to.
+fn validate_dataset(
+    definition: &DataOperationDefinition,
+    asset_specification: &AssetSpecification,
+) -> Result<(), AdrConfigError> {
+    let DataOperationDefinition::Dataset(dataset) = definition else {
+        // Only datasets are handled by `handle_dataset`
+        return Ok(());
+    };
+    // IMPLEMENT: Add any other dataset level validation here
+    match dataset
+        .dataset_configuration
+        .as_deref()
+        .or(asset_specification
+            .default_datasets_configuration
+            .as_deref())
+    {
+        Some(configuration) => validate_dataset_configuration(configuration),
+        None => Ok(()),
+    }
+}
+
+/// Validates a dataset configuration, which is either a dataset's own `dataset_configuration` or
+/// its asset's `default_datasets_configuration`.
+fn validate_dataset_configuration(configuration: &str) -> Result<(), AdrConfigError> {
+    // IMPLEMENT: Validate the configuration fields used by this connector. For this example, the
+    // configuration only needs to be a JSON object.
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(configuration)
+        .map(|_| ())
+        .map_err(|e| AdrConfigError {
+            message: Some(format!("Dataset configuration is not a JSON object: {e}")),
+            ..Default::default()
+        })
+}
+
 fn mock_sample() -> Result<Vec<u8>, String> {
     // IMPLEMENT: This function is a mock for sampling data, it should be replaced with the actual sampling logic.
     // For now, it returns a simple JSON object as a byte vector.
@@ -1209,6 +1392,34 @@ fn mock_sample() -> Result<Vec<u8>, String> {
     .map_err(|e| e.to_string())
 }
 
+/// Category of a failure in the dataset sampling cycle, each reported with a distinct reason code
+/// so that operators can tell where to look when triaging.
+#[derive(Debug, Clone, PartialEq)]
+enum DatasetFailure {
+    /// Reading from the device failed (or it returned unusable data). The device should be checked.
+    Source(String),
+    /// Publishing to the destination failed. The broker or network should be checked.
+    Sink(String),
+}
+
+impl DatasetFailure {
+    // NOTE: reason_codes for unavailable health events should include the Connector Name as a prefix
+    const SOURCE_REASON_CODE: &str = "SampleConnectorSourceFailure";
+    const SINK_REASON_CODE: &str = "SampleConnectorSinkFailure";
+
+    /// Returns the Unavailable health event to report for this failure
+    fn health_event(self) -> RuntimeHealthEvent {
+        let (message, reason_code) = match self {
+            DatasetFailure::Source(message) => (message, Self::SOURCE_REASON_CODE),
+            DatasetFailure::Sink(message) => (message, Self::SINK_REASON_CODE),
+        };
+        RuntimeHealthEvent::Unavailable {
+            message: Some(message),
+            reason_code: Some(reason_code.to_string()),
+        }
+    }
+}
+
 /// Helper function to create a closure that sends an update if the desired state is different from the current state.
 fn send_if_modified_fn(desired_state: bool) -> impl FnOnce(&mut bool) -> bool {
     move |curr| {
@@ -1291,3 +1502,44 @@ async fn handle_unsupported_component<T: UnsupportedComponentClient>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_without_datasets_validates_default_dataset_configuration() {
+        assert!(validate_asset(Some(r#"{"samplingInterval":1000}"#), false).is_ok());
+        let error = validate_asset(Some("not a JSON object"), false).unwrap_err();
+        assert!(
+            error
+                .message
+                .unwrap()
+                .starts_with("Invalid default dataset configuration")
+        );
+    }
+
+    #[test]
+    fn asset_with_datasets_defers_default_dataset_configuration() {
+        // The datasets that use the default dataset configuration report its errors instead
+        assert!(validate_asset(Some("not a JSON object"), true).is_ok());
+    }
+
+    #[test]
+    fn asset_without_default_dataset_configuration() {
+        assert!(validate_asset(None, false).is_ok());
+        assert!(validate_asset(None, true).is_ok());
+    }
+
+    #[test]
+    fn dataset_failures_report_distinct_reason_codes() {
+        let reason_code = |failure: DatasetFailure| match failure.health_event() {
+            RuntimeHealthEvent::Unavailable { reason_code, .. } => reason_code.unwrap(),
+            RuntimeHealthEvent::Available => panic!("Failures must be reported as Unavailable"),
+        };
+        let source = reason_code(DatasetFailure::Source("device unreachable".to_string()));
+        let sink = reason_code(DatasetFailure::Sink("broker unreachable".to_string()));
+        assert_eq!(source, "SampleConnectorSourceFailure");
+        assert_eq!(sink, "SampleConnectorSinkFailure");
+    }
+}
@@ -8,6 +8,10 @@
 //! To run the stub service, set the `STUB_SERVICE_OUTPUT_DIR` environment variable to the desired
 //! output directory or disable the `enable-output` feature.
 //!
+//! Log and state files in the output directory are rotated so that long running sessions don't
+//! exhaust the disk. The rotation can be configured with the `STUB_SERVICE_MAX_FILE_SIZE` (in bytes)
+//! and `STUB_SERVICE_MAX_FILE_COUNT` environment variables, see [`RotationPolicy`].
+//!
 //! An example of running the stub service is as follows:
 //!
 //! ```bash
@@ -32,7 +36,9 @@ use log4rs::{
     append::rolling_file::{
         RollingFileAppender,
         policy::compound::{
-            CompoundPolicy, roll::delete::DeleteRoller, trigger::size::SizeTrigger,
+            CompoundPolicy,
+            roll::{Roll, delete::DeleteRoller, fixed_window::FixedWindowRoller},
+            trigger::size::SizeTrigger,
         },
     },
     encode::pattern::PatternEncoder,
@@ -45,6 +51,10 @@ pub mod schema_registry;
 const STUB_SERVICE_OUTPUT_DIR_NAME: &str = "stub_service";
 #[cfg(feature = "enable-output")]
 const STUB_SERVICE_ENVIRONMENT_VARIABLE: &str = "STUB_SERVICE_OUTPUT_DIR";
+const MAX_FILE_SIZE_ENVIRONMENT_VARIABLE: &str = "STUB_SERVICE_MAX_FILE_SIZE";
+const MAX_FILE_COUNT_ENVIRONMENT_VARIABLE: &str = "STUB_SERVICE_MAX_FILE_COUNT";
+const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024 * 10; // 10 MB
+const DEFAULT_MAX_FILE_COUNT: u32 = 5;
 
 /// Helper function to create a new service session with the given client ID.
 pub fn create_service_session(
@@ -67,9 +77,57 @@ pub fn create_service_session(
     Ok(Session::new(session_options)?)
 }
 
+/// Rotation policy for the log and state files written to the output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Size in bytes after which a log file is rolled over. State files larger than this are not
+    /// written.
+    pub max_file_size: u64,
+    /// Number of rolled over log files, and of previous snapshots of each state file, that are
+    /// kept. Older files are deleted. If `0`, no rolled over files are kept.
+    pub max_file_count: u32,
+}
+
+impl Default for RotationPolicy {
+    /// Rolls log files over every 10 MB, keeping the last 5 rolled over files.
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_file_count: DEFAULT_MAX_FILE_COUNT,
+        }
+    }
+}
+
+impl RotationPolicy {
+    /// Creates a new [`RotationPolicy`] from the `STUB_SERVICE_MAX_FILE_SIZE` and
+    /// `STUB_SERVICE_MAX_FILE_COUNT` environment variables, using the default for any that is not set.
+    ///
+    /// # Panics
+    /// If an environment variable is set to a value that is not a valid number.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_file_size: Self::read_env(MAX_FILE_SIZE_ENVIRONMENT_VARIABLE)
+                .unwrap_or(default.max_file_size),
+            max_file_count: Self::read_env(MAX_FILE_COUNT_ENVIRONMENT_VARIABLE)
+                .unwrap_or(default.max_file_count),
+        }
+    }
+
+    fn read_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+        std::env::var(name).ok().map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{name} must be a valid number, found: {value}"))
+        })
+    }
+}
+
 /// Helper struct to manage the output directory for the stub service.
 pub struct OutputDirectoryManager {
     pub output_stub_service_path: String,
+    pub rotation_policy: RotationPolicy,
 }
 
 impl Default for OutputDirectoryManager {
@@ -100,6 +158,7 @@ impl Default for OutputDirectoryManager {
                 .to_str()
                 .expect("Failed to convert path to string")
                 .to_string(),
+            rotation_policy: RotationPolicy::from_env(),
         }
     }
 
@@ -109,6 +168,7 @@ impl Default for OutputDirectoryManager {
         // If the feature is not enabled, return a dummy instance
         Self {
             output_stub_service_path: String::new(),
+            rotation_policy: RotationPolicy::default(),
         }
     }
 }
@@ -133,6 +193,7 @@ impl OutputDirectoryManager {
                 .to_str()
                 .expect("Created path is valid")
                 .to_string(),
+            self.rotation_policy.clone(),
         )
     }
 
@@ -140,18 +201,17 @@ impl OutputDirectoryManager {
     #[cfg(not(feature = "enable-output"))]
     fn create_new_service_output_manager(&self, _service_name: &str) -> ServiceStateOutputManager {
         // If the feature is not enabled, return a dummy instance
-        ServiceStateOutputManager::new(String::new())
+        ServiceStateOutputManager::new(String::new(), self.rotation_policy.clone())
     }
 
     /// Creates a new [`RollingFileAppender`] for the given service name and returns it.
     ///
-    /// The appender is configured to append logs to a file in the service's log directory with a
-    /// rolling policy based on size.
+    /// The appender is configured to append logs to a file in the service's log directory, rolled
+    /// over according to the [`RotationPolicy`] of the manager.
     #[cfg(feature = "enable-output")]
     pub fn create_new_service_log_appender(
         &self,
         service_name: &str,
-        size_limit: u64,
         logging_partner: &str,
     ) -> RollingFileAppender {
         let service_log_dir = Path::new(&self.output_stub_service_path)
@@ -161,10 +221,27 @@ impl OutputDirectoryManager {
         std::fs::create_dir_all(Path::new(&service_log_dir))
             .expect("Failed to create service directory");
 
-        // Create a policy for rolling the log file based on size
+        // Create a policy for rolling the log file based on size, keeping up to the max file count
+        // of rolled over files as log.1.log, log.2.log, ...
+        let roller: Box<dyn Roll> = if self.rotation_policy.max_file_count == 0 {
+            Box::new(DeleteRoller::new())
+        } else {
+            Box::new(
+                FixedWindowRoller::builder()
+                    .base(1)
+                    .build(
+                        service_log_dir
+                            .join("log.{}.log")
+                            .to_str()
+                            .expect("Created path is valid"),
+                        self.rotation_policy.max_file_count,
+                    )
+                    .expect("Creating log roller should not fail"),
+            )
+        };
         let compound_policy = CompoundPolicy::new(
-            Box::new(SizeTrigger::new(size_limit)),
-            Box::new(DeleteRoller::new()),
+            Box::new(SizeTrigger::new(self.rotation_policy.max_file_size)),
+            roller,
         );
 
         RollingFileAppender::builder()
@@ -185,30 +262,58 @@ impl OutputDirectoryManager {
 struct ServiceStateOutputManager {
     #[cfg(feature = "enable-output")]
     pub service_dir: String,
+    #[cfg(feature = "enable-output")]
+    pub rotation_policy: RotationPolicy,
 }
 
 impl ServiceStateOutputManager {
     /// Creates a new [`ServiceStateOutputManager`] instance for the given service state output directory.
     #[cfg(feature = "enable-output")]
-    pub fn new(service_dir: String) -> Self {
-        Self { service_dir }
+    pub fn new(service_dir: String, rotation_policy: RotationPolicy) -> Self {
+        Self {
+            service_dir,
+            rotation_policy,
+        }
     }
 
     /// Creates a new dummy [`ServiceStateOutputManager`] instance if the output feature is not enabled.
     #[cfg(not(feature = "enable-output"))]
-    pub fn new(_service_dir: String) -> Self {
+    pub fn new(_service_dir: String, _rotation_policy: RotationPolicy) -> Self {
         Self {}
     }
 
     /// Writes the state to a JSON file in the service state output directory.
+    ///
+    /// The previous state is kept as `<file_name>.1.json`, `<file_name>.2.json`, ... up to the max
+    /// file count of the [`RotationPolicy`]. State larger than the max file size is not written.
     #[cfg(feature = "enable-output")]
     pub fn write_state(&self, file_name: &str, state: String) {
-        // Append JSON extension to the file name
-        let file_name = format!("{file_name}.json");
+        let state_size = u64::try_from(state.len()).unwrap_or(u64::MAX);
+        if state_size > self.rotation_policy.max_file_size {
+            log::error!(
+                "State for {file_name} is {state_size} bytes, which exceeds the max file size of {} bytes, not writing it",
+                self.rotation_policy.max_file_size
+            );
+            return;
+        }
+
+        let service_dir = Path::new(&self.service_dir);
+        let file_path = service_dir.join(format!("{file_name}.json"));
 
-        let file_path = Path::new(&self.service_dir).join(file_name);
+        // Roll the previous states over, dropping the oldest one
+        let rolled_path = |index: u32| service_dir.join(format!("{file_name}.{index}.json"));
+        if self.rotation_policy.max_file_count > 0 && file_path.exists() {
+            for index in (1..self.rotation_policy.max_file_count).rev() {
+                let path = rolled_path(index);
+                if path.exists() {
+                    std::fs::rename(&path, rolled_path(index + 1))
+                        .expect("Rolling state file over should not fail");
+                }
+            }
+            std::fs::rename(&file_path, rolled_path(1))
+                .expect("Rolling state file over should not fail");
+        }
 
-        // Overwrite the file if it exists
         std::fs::write(&file_path, state).expect("Writing state to file should not fail");
     }
 
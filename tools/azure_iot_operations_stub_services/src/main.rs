@@ -17,19 +17,14 @@ use log4rs::{
     init_config,
 };
 
-#[cfg(feature = "enable-output")]
-const LOGGING_FILE_SIZE: u64 = 1024 * 1024 * 10; // 10 MB
 const LOGGING_PATTERN: &str = "[{h({l})} {M}] {m}{n}"; // Pattern for log messages, ex: [ERROR stub_service::schema_registry] message
 
 /// Helper function to initialize the logger for the stub service.
 #[cfg(feature = "enable-output")]
 fn initialize_logger(output_directory_manager: &OutputDirectoryManager) {
     // Create a file appender for the schema registry service
-    let sr_appender = output_directory_manager.create_new_service_log_appender(
-        schema_registry::SERVICE_NAME,
-        LOGGING_FILE_SIZE,
-        LOGGING_PATTERN,
-    );
+    let sr_appender = output_directory_manager
+        .create_new_service_log_appender(schema_registry::SERVICE_NAME, LOGGING_PATTERN);
 
     // Create config for logger
     let config = Config::builder()
@@ -17,6 +17,9 @@ use serde::{Deserialize, Serialize};
 
 pub const SERVICE_NAME: &str = "schema_registry";
 pub const CLIENT_ID: &str = "schema_registry_service_stub";
+/// Name of the state file the get lookups are written to. Schema names cannot start with an
+/// underscore, so it does not clash with the state file of a schema.
+const GET_LOOKUPS_STATE_FILE: &str = "_get_lookups";
 const NAMESPACE: &str = "aio-sr-ns-stub";
 
 // ~~~~~~~~~~~~~~~~~~~DTDL Equivalent Structs and Enums~~~~~~~
@@ -65,6 +68,17 @@ pub struct Schema {
     pub version: u32,
 }
 
+/// Lookup of a schema by a get request, recorded in the service state.
+#[derive(Debug, Clone, Serialize)]
+pub struct GetLookup {
+    /// Requested schema name.
+    pub name: String,
+    /// Requested version of the schema.
+    pub version: u32,
+    /// Whether the schema was retrieved, or missed because it does not exist.
+    pub found: bool,
+}
+
 /// Request to get a schema from the schema registry.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GetRequest {
@@ -19,7 +19,7 @@ use crate::{
 };
 use crate::{
     ServiceStateOutputManager,
-    schema_registry::{SERVICE_NAME, Schema, service_gen},
+    schema_registry::{GET_LOOKUPS_STATE_FILE, GetLookup, SERVICE_NAME, Schema, service_gen},
 };
 
 /// Schema Registry service implementation.
@@ -27,7 +27,7 @@ pub struct Service {
     schemas: Arc<Mutex<HashMap<String, BTreeSet<Schema>>>>,
     get_command_executor: service_gen::GetCommandExecutor,
     put_command_executor: service_gen::PutCommandExecutor,
-    service_output_manager: ServiceStateOutputManager,
+    service_output_manager: Arc<ServiceStateOutputManager>,
 }
 
 impl Service {
@@ -55,8 +55,9 @@ impl Service {
                     .build()
                     .expect("Default command executor options should be valid"),
             ),
-            service_output_manager: output_directory_manager
-                .create_new_service_output_manager(SERVICE_NAME),
+            service_output_manager: Arc::new(
+                output_directory_manager.create_new_service_output_manager(SERVICE_NAME),
+            ),
         }
     }
 
@@ -65,6 +66,7 @@ impl Service {
         let get_schema_runner_handle = tokio::spawn(Self::get_schema_runner(
             self.get_command_executor,
             self.schemas.clone(),
+            self.service_output_manager.clone(),
         ));
         let put_schema_runner_handle = tokio::spawn(Self::put_schema_runner(
             self.put_command_executor,
@@ -91,9 +93,14 @@ impl Service {
     }
 
     /// Processes a get request and returns a response that can be used with `get_request.complete()`.
+    ///
+    /// Requests for a valid schema version are recorded in `lookups`, which are written to the
+    /// service state whether the schema was retrieved or missed.
     fn process_get_request(
         payload: &service_gen::GetRequestSchema,
         schemas: &Arc<Mutex<HashMap<String, BTreeSet<Schema>>>>,
+        lookups: &mut Vec<GetLookup>,
+        service_state_manager: &ServiceStateOutputManager,
     ) -> rpc_command::executor::Response<service_gen::GetResponseSchema> {
         // Extract the schema name
         let schema_name = &payload.name;
@@ -215,6 +222,22 @@ impl Service {
             }
         };
 
+        // Output the lookups to the state file
+        lookups.push(GetLookup {
+            name: schema_name.clone(),
+            version: schema_version,
+            found: result.is_ok(),
+        });
+        match serde_json::to_string_pretty(lookups) {
+            Ok(serialized_lookups) => {
+                service_state_manager.write_state(GET_LOOKUPS_STATE_FILE, serialized_lookups);
+            }
+            Err(e) => {
+                // The lookups are only informational, so the get request is still completed
+                log::error!("Failed to serialize get lookups for state output: {e}");
+            }
+        }
+
         // Create the response
         match result {
             Ok(schema) => rpc_command::executor::ResponseBuilder::default()
@@ -416,7 +439,9 @@ impl Service {
     async fn get_schema_runner(
         mut get_command_executor: service_gen::GetCommandExecutor,
         schemas: Arc<Mutex<HashMap<String, BTreeSet<Schema>>>>,
+        service_state_manager: Arc<ServiceStateOutputManager>,
     ) -> Result<(), AIOProtocolError> {
+        let mut lookups = Vec::new();
         loop {
             // Wait for a new get request
             match get_command_executor.recv().await {
@@ -426,7 +451,12 @@ impl Service {
 
                         let schema_name = get_request.payload.name.clone();
                         let schema_version = get_request.payload.version.clone();
-                        let response = Self::process_get_request(&get_request.payload, &schemas);
+                        let response = Self::process_get_request(
+                            &get_request.payload,
+                            &schemas,
+                            &mut lookups,
+                            &service_state_manager,
+                        );
 
                         match get_request.complete(response).await {
                             Ok(_) => {
@@ -455,7 +485,7 @@ impl Service {
     async fn put_schema_runner(
         mut put_command_executor: service_gen::PutCommandExecutor,
         schemas: Arc<Mutex<HashMap<String, BTreeSet<Schema>>>>,
-        service_state_manager: ServiceStateOutputManager,
+        service_state_manager: Arc<ServiceStateOutputManager>,
     ) -> Result<(), AIOProtocolError> {
         loop {
             // Wait for a new put request
@@ -1,24 +1,38 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use core::str;
+mod output;
+
 use std::fs;
+use std::sync::Arc;
 use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 use env_logger::Builder;
 
-use azure_iot_operations_mqtt::aio::connection_settings::MqttConnectionSettingsBuilder;
+use azure_iot_operations_mqtt::aio::connection_settings::{
+    MqttConnectionSettings, MqttConnectionSettingsBuilder,
+};
 use azure_iot_operations_mqtt::session::{
     Session, SessionExitHandle, SessionManagedClient, SessionMonitor, SessionOptionsBuilder,
 };
 use azure_iot_operations_protocol::application::{ApplicationContext, ApplicationContextBuilder};
+use azure_iot_operations_protocol::common::aio_protocol_error::AIOProtocolErrorKind;
+use azure_iot_operations_services::leased_lock::{
+    self,
+    singleton::{SingletonGuard, WaitPolicy},
+};
 use azure_iot_operations_services::state_store::{self, SetOptions};
 
+use output::{CommandError, ExitCode, Output, OutputFormat};
+
 const TOOL_NAME: &str = "statestore-cli";
 const TOOL_VERSION: &str = "0.1.0";
 const TOOL_ABOUT_SHORT: &str = "Azure Device State Store CLI";
 const TOOL_ABOUT_LONG: &str = "Allows managing key/value pairs in the Azure State Store.";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const EXCLUSIVE_LEASE: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
 #[command(version = TOOL_VERSION, about = TOOL_ABOUT_SHORT, long_about = TOOL_ABOUT_LONG)]
@@ -54,6 +68,16 @@ struct Cli {
     /// Verbose logging (errors).
     #[arg(short = None, long, default_value_t = false, global = true)]
     verbose: bool,
+    /// Output format for results.
+    #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    output: OutputFormat,
+    /// Suppress non-data output, such as error messages.
+    #[arg(short = 'q', long, default_value_t = false, global = true)]
+    quiet: bool,
+    /// Fail if another invocation holds the exclusive lock with this name,
+    /// otherwise hold it while the command executes.
+    #[arg(short = None, long, default_value = None, global = true)]
+    exclusive: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -86,6 +110,52 @@ enum Commands {
         #[arg(short = 'k', long)]
         key: String,
     },
+    /// Diagnoses the connection to the MQ broker.
+    ///
+    /// Checks DNS resolution, TCP reachability, the TLS handshake, the client credentials and
+    /// MQTT connection in turn, and reports the first stage that fails.
+    Diagnose,
+}
+
+impl Commands {
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Get { .. } => "get",
+            Commands::Set { .. } => "set",
+            Commands::Delete { .. } => "delete",
+            Commands::Diagnose => "diagnose",
+        }
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            Commands::Get { key, .. } | Commands::Set { key, .. } | Commands::Delete { key } => key,
+            Commands::Diagnose => "",
+        }
+    }
+}
+
+/// State Store request to execute once connected.
+enum Request {
+    Get,
+    Set(Vec<u8>),
+    Delete,
+}
+
+/// Result of a successfully executed State Store [`Request`].
+enum Outcome {
+    Get {
+        value: Option<Vec<u8>>,
+        version: Option<String>,
+    },
+    Set {
+        success: bool,
+        version: Option<String>,
+    },
+    Delete {
+        deleted: i64,
+        version: Option<String>,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -103,208 +173,309 @@ async fn main() {
         .format_timestamp(None)
         .init();
 
-    // Create a session
-    let connection_settings = MqttConnectionSettingsBuilder::default()
-        .client_id(format!("{TOOL_NAME}-{TOOL_VERSION}"))
-        .hostname(args.hostname)
-        .tcp_port(args.port)
-        .keep_alive(Duration::from_secs(5))
-        .use_tls(!args.notls)
-        .ca_file(args.cafile)
-        .cert_file(args.certfile)
-        .key_file(args.keyfile)
-        .key_password_file(args.keypasswordfile)
-        .build()
-        .unwrap();
-    let session_options = SessionOptionsBuilder::default()
-        .connection_settings(connection_settings)
-        .build()
-        .unwrap();
-    let session = Session::new(session_options).unwrap();
-
-    let application_context = ApplicationContextBuilder::default().build().unwrap();
-
-    let exit_code: i32 = match args.cmd {
-        Commands::Get { key, valuefile } => {
-            let get_join_handle = tokio::task::spawn(state_store_get_value(
-                application_context.clone(),
-                session.create_managed_client(),
-                session.create_session_monitor(),
-                session.create_exit_handle(),
-                key,
-                valuefile,
-            ));
-
-            session.run().await.unwrap();
+    let output = Output::new(args.output, args.quiet);
+    let operation = args.cmd.name();
+    let key = args.cmd.key().to_string();
 
-            get_join_handle.await.unwrap()
+    let exit_code = match Box::pin(run(args, &output)).await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            output.error(operation, &key, &e);
+            e.exit_code
         }
+    };
+
+    std::process::exit(exit_code as i32);
+}
+
+async fn run(args: Cli, output: &Output) -> Result<ExitCode, CommandError> {
+    // Concurrent invocations holding an exclusive lock need distinct client ids to identify them
+    let client_id = if args.exclusive.is_some() {
+        format!("{TOOL_NAME}-{TOOL_VERSION}-{}", std::process::id())
+    } else {
+        format!("{TOOL_NAME}-{TOOL_VERSION}")
+    };
+    let connection_settings = connection_settings(client_id.clone(), &args)?;
+
+    let (key, request, valuefile) = match args.cmd {
+        Commands::Get { key, valuefile } => (key, Request::Get, valuefile),
         Commands::Set {
             key,
             value,
             valuefile,
         } => {
-            let actual_value = match value {
-                Some(option_value) => option_value,
-                None => fs::read_to_string(valuefile.unwrap()).expect("Could not open/read file"),
+            let value = match (value, valuefile) {
+                (Some(value), _) => value.into_bytes(),
+                (None, Some(valuefile)) => fs::read(&valuefile).map_err(|e| {
+                    CommandError::new(
+                        ExitCode::Failure,
+                        format!("could not read value from file '{valuefile}': {e}"),
+                    )
+                })?,
+                (None, None) => {
+                    return Err(CommandError::new(
+                        ExitCode::Usage,
+                        "either --value or --valuefile must be provided",
+                    ));
+                }
             };
-
-            let set_join_handle = tokio::task::spawn(state_store_set_value(
-                application_context.clone(),
-                session.create_managed_client(),
-                session.create_session_monitor(),
-                session.create_exit_handle(),
-                key,
-                actual_value,
-            ));
-
-            session.run().await.unwrap();
-
-            set_join_handle.await.unwrap()
+            (key, Request::Set(value), None)
         }
-        Commands::Delete { key } => {
-            let delete_join_handle = tokio::task::spawn(state_store_delete_key(
-                application_context.clone(),
-                session.create_managed_client(),
-                session.create_session_monitor(),
-                session.create_exit_handle(),
-                key,
-            ));
-
-            session.run().await.unwrap();
-
-            delete_join_handle.await.unwrap()
+        Commands::Delete { key } => (key, Request::Delete, None),
+        Commands::Diagnose => {
+            let report = connection_settings.diagnose().await;
+            output.diagnose_result(&report)?;
+            return Ok(if report.passed() {
+                ExitCode::Success
+            } else {
+                ExitCode::Connection
+            });
         }
     };
 
-    std::process::exit(exit_code);
-}
+    // Create a session
+    let session_options = SessionOptionsBuilder::default()
+        .connection_settings(connection_settings)
+        .build()
+        .map_err(|e| CommandError::new(ExitCode::Usage, format!("invalid session options: {e}")))?;
+    // Session creation loads the TLS configuration and client credentials
+    let session = Session::new(session_options).map_err(|e| {
+        CommandError::new(
+            ExitCode::Connection,
+            format!("could not create MQTT session: {e}"),
+        )
+    })?;
 
-async fn state_store_get_value(
-    context: ApplicationContext,
-    client: SessionManagedClient,
-    connection_monitor: SessionMonitor,
-    exit_handle: SessionExitHandle,
-    key: String,
-    valuefile: Option<String>,
-) -> i32 {
-    let state_store_key = key.as_bytes();
-    let timeout = Duration::from_secs(10);
-
-    let state_store_client = state_store::Client::new(
-        context,
-        client,
-        connection_monitor,
-        state_store::ClientOptionsBuilder::default()
-            .build()
-            .unwrap(),
-    )
-    .unwrap();
-
-    let get_response = state_store_client
-        .get(state_store_key.to_vec(), timeout)
+    let application_context = ApplicationContextBuilder::default()
+        .build()
+        .map_err(|e| CommandError::new(ExitCode::Failure, e))?;
+
+    let request_join_handle = tokio::task::spawn(state_store_request(
+        application_context,
+        session.create_managed_client(),
+        session.create_session_monitor(),
+        session.create_exit_handle(),
+        args.exclusive.map(|name| (name, client_id)),
+        key.as_bytes().to_vec(),
+        request,
+    ));
+
+    if let Err(e) = session.run().await {
+        // The session can end with an error after the request has completed, in which case
+        // the result of the request is still reported.
+        if !request_join_handle.is_finished() {
+            request_join_handle.abort();
+            return Err(CommandError::new(
+                ExitCode::Connection,
+                format!("MQTT session ended: {e}"),
+            ));
+        }
+    }
+
+    let outcome = request_join_handle
         .await
-        .unwrap();
+        .map_err(|e| CommandError::new(ExitCode::Failure, e))??;
 
-    let result = match get_response.response {
-        Some(response_body) => {
-            if let Some(vf) = valuefile {
-                fs::write(vf, response_body).expect("Could not open/write to file.");
+    match outcome {
+        Outcome::Get {
+            value: Some(value),
+            version,
+        } => {
+            output.get_result(&key, &value, version, valuefile.as_deref())?;
+            Ok(ExitCode::Success)
+        }
+        Outcome::Get { value: None, .. } => {
+            output.get_not_found(&key)?;
+            Ok(ExitCode::NotFound)
+        }
+        Outcome::Set { success, version } => {
+            output.set_result(&key, success, version)?;
+            Ok(if success {
+                ExitCode::Success
             } else {
-                println!("{}", String::from_utf8(response_body).unwrap());
-            }
-            0
+                ExitCode::Failure
+            })
+        }
+        Outcome::Delete { deleted, version } => {
+            output.delete_result(&key, deleted, version)?;
+            Ok(if deleted == 0 {
+                ExitCode::NotFound
+            } else {
+                ExitCode::Success
+            })
         }
-        None => 1,
-    };
-
-    match exit_handle.try_exit() {
-        Ok(_exit_result) => {}
-        Err(_exit_error) => {}
     }
+}
 
-    result
+/// Creates the connection settings for the MQ broker from the command line arguments.
+fn connection_settings(
+    client_id: String,
+    args: &Cli,
+) -> Result<MqttConnectionSettings, CommandError> {
+    MqttConnectionSettingsBuilder::default()
+        .client_id(client_id)
+        .hostname(args.hostname.clone())
+        .tcp_port(args.port)
+        .keep_alive(Duration::from_secs(5))
+        .connection_timeout(CONNECT_TIMEOUT)
+        .use_tls(!args.notls)
+        .ca_file(args.cafile.clone())
+        .cert_file(args.certfile.clone())
+        .key_file(args.keyfile.clone())
+        .key_password_file(args.keypasswordfile.clone())
+        .build()
+        .map_err(|e| {
+            CommandError::new(ExitCode::Usage, format!("invalid connection settings: {e}"))
+        })
 }
 
-async fn state_store_set_value(
+async fn state_store_request(
     context: ApplicationContext,
     client: SessionManagedClient,
     connection_monitor: SessionMonitor,
     exit_handle: SessionExitHandle,
-    key: String,
-    value: String,
-) -> i32 {
-    let state_store_key = key.as_bytes();
-    let state_store_value = value.as_bytes();
-    let timeout = Duration::from_secs(10);
-
-    let state_store_client = state_store::Client::new(
-        context,
-        client,
-        connection_monitor,
-        state_store::ClientOptionsBuilder::default()
-            .build()
-            .unwrap(),
-    )
-    .unwrap();
-
-    let set_response = state_store_client
-        .set(
-            state_store_key.to_vec(),
-            state_store_value.to_vec(),
-            timeout,
-            None,
-            SetOptions {
-                expires: None,
-                ..SetOptions::default()
-            },
-        )
-        .await
-        .unwrap();
-
-    // i32::from does false -> 0 and true -> 1, but we want to return 0 on success and 1 on failure, so we check if the response is false rather than true.
-    let result = i32::from(!set_response.response);
+    exclusive: Option<(String, String)>,
+    key: Vec<u8>,
+    request: Request,
+) -> Result<Outcome, CommandError> {
+    let result =
+        execute_request(context, client, connection_monitor, exclusive, key, request).await;
 
-    match exit_handle.try_exit() {
-        Ok(_exit_result) => {}
-        Err(_exit_error) => {}
-    }
+    // Disconnect gracefully if connected, otherwise stop any further connection attempts
+    exit_handle.force_exit();
 
     result
 }
 
-async fn state_store_delete_key(
+/// Executes the request, holding the exclusive lock named in `exclusive` (if any) as its holder.
+async fn execute_request(
     context: ApplicationContext,
     client: SessionManagedClient,
     connection_monitor: SessionMonitor,
-    exit_handle: SessionExitHandle,
-    key: String,
-) -> i32 {
-    let state_store_key = key.as_bytes();
-    let timeout = Duration::from_secs(10);
-
-    let state_store_client = state_store::Client::new(
-        context,
-        client,
-        connection_monitor,
-        state_store::ClientOptionsBuilder::default()
-            .build()
-            .unwrap(),
-    )
-    .unwrap();
-
-    let delete_response = state_store_client
-        .del(state_store_key.to_vec(), None, timeout)
+    exclusive: Option<(String, String)>,
+    key: Vec<u8>,
+    request: Request,
+) -> Result<Outcome, CommandError> {
+    if tokio::time::timeout(CONNECT_TIMEOUT, connection_monitor.connected())
         .await
-        .unwrap();
+        .is_err()
+    {
+        return Err(CommandError::new(
+            ExitCode::Connection,
+            format!(
+                "could not connect to the MQ broker within {} seconds",
+                CONNECT_TIMEOUT.as_secs()
+            ),
+        ));
+    }
+
+    let state_store_client = Arc::new(
+        state_store::Client::new(
+            context,
+            client,
+            connection_monitor,
+            state_store::ClientOptionsBuilder::default()
+                .build()
+                .map_err(|e| CommandError::new(ExitCode::Failure, e))?,
+        )
+        .map_err(|e| state_store_error(&e))?,
+    );
+
+    let guard = match exclusive {
+        Some((name, holder_name)) => Some(
+            SingletonGuard::acquire(
+                state_store_client.clone(),
+                name.clone().into_bytes(),
+                holder_name.into_bytes(),
+                EXCLUSIVE_LEASE,
+                WaitPolicy::FailFast,
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .map_err(|e| exclusive_error(&name, &e))?,
+        ),
+        None => None,
+    };
 
-    // i32::from does false -> 0 and true -> 1, but we want to return 0 on success and 1 on failure, so we are evaluating a boolean where the failure case evaluates to true.
-    let result = i32::from(delete_response.response != 1);
+    let outcome = match request {
+        Request::Get => state_store_client
+            .get(key, REQUEST_TIMEOUT)
+            .await
+            .map(|response| Outcome::Get {
+                value: response.response,
+                version: response.version.map(|v| v.to_string()),
+            }),
+        Request::Set(value) => state_store_client
+            .set(key, value, REQUEST_TIMEOUT, None, SetOptions::default())
+            .await
+            .map(|response| Outcome::Set {
+                success: response.response,
+                version: response.version.map(|v| v.to_string()),
+            }),
+        Request::Delete => state_store_client
+            .del(key, None, REQUEST_TIMEOUT)
+            .await
+            .map(|response| Outcome::Delete {
+                deleted: response.response,
+                version: response.version.map(|v| v.to_string()),
+            }),
+    };
 
-    match exit_handle.try_exit() {
-        Ok(_exit_result) => {}
-        Err(_exit_error) => {}
+    if let Some(guard) = guard {
+        if let Err(e) = guard.release().await {
+            // The lock expires on its own, so the outcome of the request is still reported
+            log::warn!("could not release exclusive lock: {e}");
+        }
     }
 
-    result
+    outcome.map_err(|e| state_store_error(&e))
+}
+
+/// Maps a State Store error to the [`ExitCode`] that best describes it.
+fn state_store_error(error: &state_store::Error) -> CommandError {
+    let exit_code = match error.kind() {
+        state_store::ErrorKind::AIOProtocolError(protocol_error) => match protocol_error.kind {
+            AIOProtocolErrorKind::Timeout => ExitCode::Timeout,
+            AIOProtocolErrorKind::ClientError => ExitCode::Connection,
+            _ => ExitCode::Failure,
+        },
+        state_store::ErrorKind::InvalidArgument(_) => ExitCode::Usage,
+        _ => ExitCode::Failure,
+    };
+    CommandError::new(exit_code, error)
+}
+
+/// Maps an error acquiring the `--exclusive` lock to the [`ExitCode`] that best describes it.
+fn exclusive_error(name: &str, error: &leased_lock::Error) -> CommandError {
+    match error.kind() {
+        leased_lock::ErrorKind::LeaseHeldBy(holder) => CommandError::new(
+            ExitCode::Busy,
+            format!(
+                "exclusive lock '{name}' is held by {}",
+                String::from_utf8_lossy(holder)
+            ),
+        ),
+        leased_lock::ErrorKind::LeaseAlreadyHeld => CommandError::new(
+            ExitCode::Busy,
+            format!("exclusive lock '{name}' is held by another invocation"),
+        ),
+        leased_lock::ErrorKind::AIOProtocolError(protocol_error) => {
+            let exit_code = match protocol_error.kind {
+                AIOProtocolErrorKind::Timeout => ExitCode::Timeout,
+                AIOProtocolErrorKind::ClientError => ExitCode::Connection,
+                _ => ExitCode::Failure,
+            };
+            CommandError::new(
+                exit_code,
+                format!("could not acquire exclusive lock: {error}"),
+            )
+        }
+        leased_lock::ErrorKind::InvalidArgument(_) => {
+            CommandError::new(ExitCode::Usage, format!("invalid exclusive lock: {error}"))
+        }
+        _ => CommandError::new(
+            ExitCode::Failure,
+            format!("could not acquire exclusive lock: {error}"),
+        ),
+    }
 }
@@ -0,0 +1,284 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Output formatting and exit codes for the `statestore-cli` tool.
+
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Write};
+
+use azure_iot_operations_mqtt::aio::diagnostics::{DiagnosisReport, StageOutcome};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+
+/// Exit codes returned by the `statestore-cli` tool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The operation completed successfully.
+    Success = 0,
+    /// The operation failed for a reason not covered by a more specific exit code.
+    Failure = 1,
+    /// The command line arguments were invalid.
+    Usage = 2,
+    /// The requested key does not exist.
+    NotFound = 3,
+    /// Could not connect or authenticate with the MQ broker.
+    Connection = 4,
+    /// The State Store did not respond within the timeout.
+    Timeout = 5,
+    /// The `--exclusive` lock is held by another invocation.
+    Busy = 6,
+}
+
+impl ExitCode {
+    /// Name used to identify the exit code in JSON output.
+    fn name(self) -> &'static str {
+        match self {
+            ExitCode::Success => "success",
+            ExitCode::Failure => "failure",
+            ExitCode::Usage => "usage",
+            ExitCode::NotFound => "notFound",
+            ExitCode::Connection => "connection",
+            ExitCode::Timeout => "timeout",
+            ExitCode::Busy => "busy",
+        }
+    }
+}
+
+/// Format used to write results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text. Values are printed as UTF-8 followed by a newline.
+    #[default]
+    Text,
+    /// Exact bytes of the value, without a trailing newline.
+    Raw,
+    /// JSON envelope describing the result. Binary values are base64 encoded.
+    Json,
+}
+
+/// An error that ends the execution of a command.
+#[derive(Debug)]
+pub struct CommandError {
+    /// Exit code to return for the error.
+    pub exit_code: ExitCode,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(exit_code: ExitCode, message: impl Display) -> Self {
+        Self {
+            exit_code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Writes command results in the requested [`OutputFormat`].
+pub struct Output {
+    format: OutputFormat,
+    quiet: bool,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, quiet: bool) -> Self {
+        Self { format, quiet }
+    }
+
+    /// Writes the result of a `get` that found a value, either to `valuefile` or to stdout.
+    pub fn get_result(
+        &self,
+        key: &str,
+        value: &[u8],
+        version: Option<String>,
+        valuefile: Option<&str>,
+    ) -> Result<(), CommandError> {
+        if let Some(valuefile) = valuefile {
+            fs::write(valuefile, value).map_err(|e| {
+                CommandError::new(
+                    ExitCode::Failure,
+                    format!("could not write value to file '{valuefile}': {e}"),
+                )
+            })?;
+        }
+
+        match self.format {
+            OutputFormat::Json => {
+                let mut envelope = envelope("get", key, version);
+                envelope.insert("found".to_string(), Value::Bool(true));
+                if let Some(valuefile) = valuefile {
+                    envelope.insert("valuefile".to_string(), Value::from(valuefile));
+                } else {
+                    let (encoding, encoded) = encode_value(value);
+                    envelope.insert("encoding".to_string(), Value::from(encoding));
+                    envelope.insert("value".to_string(), Value::from(encoded));
+                }
+                write_json(&Value::Object(envelope))
+            }
+            _ if valuefile.is_some() => Ok(()),
+            OutputFormat::Raw => write_stdout(value),
+            OutputFormat::Text => {
+                let mut text = String::from_utf8_lossy(value).into_owned();
+                text.push('\n');
+                write_stdout(text.as_bytes())
+            }
+        }
+    }
+
+    /// Writes the result of a `get` for a key that does not exist.
+    pub fn get_not_found(&self, key: &str) -> Result<(), CommandError> {
+        if self.format == OutputFormat::Json {
+            let mut envelope = envelope("get", key, None);
+            envelope.insert("found".to_string(), Value::Bool(false));
+            write_json(&Value::Object(envelope))?;
+        } else if !self.quiet {
+            eprintln!("key '{key}' not found");
+        }
+        Ok(())
+    }
+
+    /// Writes the result of a `set`.
+    pub fn set_result(
+        &self,
+        key: &str,
+        success: bool,
+        version: Option<String>,
+    ) -> Result<(), CommandError> {
+        if self.format == OutputFormat::Json {
+            let mut envelope = envelope("set", key, version);
+            envelope.insert("success".to_string(), Value::Bool(success));
+            write_json(&Value::Object(envelope))?;
+        } else if !success && !self.quiet {
+            eprintln!("key '{key}' was not set");
+        }
+        Ok(())
+    }
+
+    /// Writes the result of a `delete`.
+    pub fn delete_result(
+        &self,
+        key: &str,
+        deleted: i64,
+        version: Option<String>,
+    ) -> Result<(), CommandError> {
+        if self.format == OutputFormat::Json {
+            let mut envelope = envelope("delete", key, version);
+            envelope.insert("deleted".to_string(), Value::from(deleted));
+            write_json(&Value::Object(envelope))?;
+        } else if deleted == 0 && !self.quiet {
+            eprintln!("key '{key}' not found");
+        }
+        Ok(())
+    }
+
+    /// Writes the report of a `diagnose`.
+    pub fn diagnose_result(&self, report: &DiagnosisReport) -> Result<(), CommandError> {
+        if self.format != OutputFormat::Json {
+            return write_stdout(report.to_string().as_bytes());
+        }
+
+        let stages: Vec<Value> = report
+            .stages
+            .iter()
+            .map(|stage| {
+                let (outcome, reason) = match &stage.outcome {
+                    StageOutcome::Passed => ("passed", None),
+                    StageOutcome::Failed(reason) => ("failed", Some(reason)),
+                    StageOutcome::Skipped(reason) => ("skipped", Some(reason)),
+                };
+                json!({
+                    "stage": stage.stage.to_string(),
+                    "outcome": outcome,
+                    "reason": reason,
+                    "details": stage.details,
+                })
+            })
+            .collect();
+        let certificates: Vec<Value> = report
+            .server_certificate_chain
+            .iter()
+            .map(|certificate| {
+                json!({
+                    "subject": certificate.subject,
+                    "issuer": certificate.issuer,
+                    "notBefore": certificate.not_before.to_rfc3339(),
+                    "notAfter": certificate.not_after.to_rfc3339(),
+                })
+            })
+            .collect();
+        let envelope = json!({
+            "operation": "diagnose",
+            "passed": report.passed(),
+            "stages": stages,
+            "resolvedAddresses": report
+                .resolved_addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            "serverCertificates": certificates,
+            "satExpiry": report.sat_expiry.map(|expiry| expiry.to_rfc3339()),
+        });
+        write_json(&envelope)
+    }
+
+    /// Reports an error that ended the execution of `operation`.
+    ///
+    /// In JSON format the error is written to stdout as an envelope, since it is the result of
+    /// the command. Otherwise it is written to stderr, unless output is quiet.
+    pub fn error(&self, operation: &str, key: &str, error: &CommandError) {
+        if self.format == OutputFormat::Json {
+            let envelope = json!({
+                "operation": operation,
+                "key": key,
+                "error": {
+                    "code": error.exit_code as i32,
+                    "kind": error.exit_code.name(),
+                    "message": error.message,
+                },
+            });
+            // Nothing more can be reported if stdout itself cannot be written to.
+            let _ = write_json(&envelope);
+        } else if !self.quiet {
+            eprintln!("error: {}", error.message);
+        }
+    }
+}
+
+/// Creates the fields common to all JSON result envelopes.
+fn envelope(operation: &str, key: &str, version: Option<String>) -> Map<String, Value> {
+    let mut envelope = Map::new();
+    envelope.insert("operation".to_string(), Value::from(operation));
+    envelope.insert("key".to_string(), Value::from(key));
+    envelope.insert(
+        "version".to_string(),
+        version.map_or(Value::Null, Value::from),
+    );
+    envelope
+}
+
+/// Encodes a value for JSON output, as UTF-8 text when possible and as base64 otherwise.
+fn encode_value(value: &[u8]) -> (&'static str, String) {
+    match std::str::from_utf8(value) {
+        Ok(text) => ("utf8", text.to_string()),
+        Err(_) => ("base64", BASE64.encode(value)),
+    }
+}
+
+fn write_json(value: &Value) -> Result<(), CommandError> {
+    let mut text = value.to_string();
+    text.push('\n');
+    write_stdout(text.as_bytes())
+}
+
+fn write_stdout(bytes: &[u8]) -> Result<(), CommandError> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(bytes)
+        .and_then(|()| stdout.flush())
+        .map_err(|e| {
+            CommandError::new(ExitCode::Failure, format!("could not write to stdout: {e}"))
+        })
+}
@@ -38,16 +38,16 @@ call:assert_equals "07-get-console-anon-no-tls" 0 %ERRORLEVEL%
 call:assert_equals "08-delete-anon-no-tls" 0 %ERRORLEVEL%
 
 .\statestore-cli.exe delete -n %MQ_BROKER_HOSTNAME% -p 1883 -k "someKey3" --notls
-call:assert_equals "09-delete-repeated-anon-no-tls" 1 %ERRORLEVEL%
+call:assert_equals "09-delete-repeated-anon-no-tls" 3 %ERRORLEVEL%
 
 .\statestore-cli.exe get -n %MQ_BROKER_HOSTNAME% -p 1883 -k "someKey3" --notls
-call:assert_equals "10-get-already-deleted-anon-no-tls" 1 %ERRORLEVEL%
+call:assert_equals "10-get-already-deleted-anon-no-tls" 3 %ERRORLEVEL%
 
 .\statestore-cli.exe get -n "%MQ_BROKER_HOSTNAME%-invalid" -p 1883 -k "someKey3" --notls
-call:assert_not_equals "11-get-invalid-hostname-anon-no-tls" 0 %ERRORLEVEL%
+call:assert_equals "11-get-invalid-hostname-anon-no-tls" 4 %ERRORLEVEL%
 
 .\statestore-cli.exe get -n %MQ_BROKER_HOSTNAME% -p 1884 -k "someKey3" --notls
-call:assert_not_equals "12-get-invalid-port-anon-no-tls" 0 %ERRORLEVEL%
+call:assert_equals "12-get-invalid-port-anon-no-tls" 4 %ERRORLEVEL%
 
 popd
 
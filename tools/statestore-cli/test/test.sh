@@ -104,15 +104,15 @@ assert_equals "07-get-console-anon-no-tls" 0 $?
 assert_equals "08-delete-anon-no-tls" 0 $?
 
 ./statestore-cli delete -n $MQ_BROKER_HOSTNAME -p 1883 -k "someKey3" --notls
-assert_equals "09-delete-repeated-anon-no-tls" 1 $?
+assert_equals "09-delete-repeated-anon-no-tls" 3 $?
 
 ./statestore-cli get -n $MQ_BROKER_HOSTNAME -p 1883 -k "someKey3" --notls
-assert_equals "10-get-already-deleted-anon-no-tls" 1 $?
+assert_equals "10-get-already-deleted-anon-no-tls" 3 $?
 
 ./statestore-cli get -n "$MQ_BROKER_HOSTNAME-invalid" -p 1883 -k "someKey3" --notls
-assert_not_equals "11-get-invalid-hostname-anon-no-tls" 0 $?
+assert_equals "11-get-invalid-hostname-anon-no-tls" 4 $?
 
 ./statestore-cli get -n $MQ_BROKER_HOSTNAME -p 1884 -k "someKey3" --notls
-assert_not_equals "12-get-invalid-port-anon-no-tls" 0 $?
+assert_equals "12-get-invalid-port-anon-no-tls" 4 $?
 
 popd
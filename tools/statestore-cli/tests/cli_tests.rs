@@ -0,0 +1,372 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::env;
+use std::fs;
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+// Binary value containing embedded newlines and invalid UTF-8
+const BINARY_VALUE: &[u8] = b"line1\nline2\r\n\x00\xff\xfe\n";
+// Base64 encoding of BINARY_VALUE
+const BINARY_VALUE_BASE64: &str = "bGluZTEKbGluZTINCgD//go=";
+
+fn network_tests_enabled() -> bool {
+    if env::var("ENABLE_NETWORK_TESTS").is_err() {
+        eprintln!("This test is skipped. Set ENABLE_NETWORK_TESTS to run.");
+        return false;
+    }
+    true
+}
+
+/// Command connecting anonymously to the local broker without TLS.
+fn cli() -> Command {
+    let mut cmd = Command::cargo_bin("statestore-cli").unwrap();
+    cmd.args(["-n", "localhost", "-p", "1883", "--notls"]);
+    cmd
+}
+
+/// Key unique to the test and process, so tests can run concurrently against the same broker.
+fn test_key(name: &str) -> String {
+    format!("statestore-cli-test-{name}-{}", std::process::id())
+}
+
+fn set_from_file(key: &str, value: &[u8]) {
+    let valuefile = tempfile::NamedTempFile::new().unwrap();
+    fs::write(valuefile.path(), value).unwrap();
+    cli()
+        .args(["set", "-k", key, "-f"])
+        .arg(valuefile.path())
+        .assert()
+        .code(0);
+}
+
+#[test]
+fn usage_error_missing_key() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .arg("get")
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn usage_error_conflicting_values() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["set", "-k", "key", "--value", "value", "-f", "value.txt"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn usage_error_missing_value() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["set", "-k", "key", "--notls", "-q"])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn usage_error_invalid_output_format() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["get", "-k", "key", "--output", "yaml"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn set_value_file_not_found() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["set", "-k", "key", "--notls", "-f", "does-not-exist.txt"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("does-not-exist.txt"));
+}
+
+#[test]
+fn connection_failure() {
+    // Nothing listens on port 1, so the connection is refused
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["get", "-k", "key", "-n", "localhost", "-p", "1", "--notls"])
+        .assert()
+        .code(4)
+        .stdout("");
+}
+
+#[test]
+fn connection_failure_json() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args([
+            "delete",
+            "-k",
+            "key",
+            "-n",
+            "localhost",
+            "-p",
+            "1",
+            "--notls",
+        ])
+        .args(["--output", "json"])
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains(r#""kind":"connection""#));
+}
+
+#[test]
+fn connection_failure_quiet() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args([
+            "get",
+            "-k",
+            "key",
+            "-n",
+            "localhost",
+            "-p",
+            "1",
+            "--notls",
+            "-q",
+        ])
+        .assert()
+        .code(4)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn diagnose_connection_failure() {
+    Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["diagnose", "-n", "localhost", "-p", "1", "--notls"])
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("[PASS] DNS resolution"))
+        .stdout(predicate::str::contains("[FAIL] TCP connection"))
+        .stdout(predicate::str::contains("[SKIP] MQTT connection"));
+}
+
+#[test]
+fn diagnose_bad_ca_file_json() {
+    let output = Command::cargo_bin("statestore-cli")
+        .unwrap()
+        .args(["diagnose", "-n", "localhost", "-p", "1"])
+        .args(["-T", "does-not-exist.crt", "-o", "json"])
+        .assert()
+        .code(4)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["operation"], "diagnose");
+    assert_eq!(envelope["passed"], false);
+    let tls_stage = envelope["stages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|stage| stage["stage"] == "TLS handshake")
+        .unwrap();
+    assert_eq!(tls_stage["outcome"], "failed");
+}
+
+#[test]
+fn diagnose_network_tests() {
+    if !network_tests_enabled() {
+        return;
+    }
+    cli()
+        .arg("diagnose")
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains("[PASS] MQTT connection"));
+}
+
+#[test]
+fn set_get_delete_text_network_tests() {
+    if !network_tests_enabled() {
+        return;
+    }
+    let key = test_key("text");
+
+    cli()
+        .args(["set", "-k", &key, "--value", "hello"])
+        .assert()
+        .code(0)
+        .stdout("");
+    cli()
+        .args(["get", "-k", &key])
+        .assert()
+        .code(0)
+        .stdout("hello\n");
+    cli()
+        .args(["delete", "-k", &key])
+        .assert()
+        .code(0)
+        .stdout("");
+
+    // Key no longer exists
+    cli()
+        .args(["get", "-k", &key])
+        .assert()
+        .code(3)
+        .stdout("")
+        .stderr(predicate::str::contains("not found"));
+    cli()
+        .args(["delete", "-k", &key])
+        .assert()
+        .code(3)
+        .stdout("");
+    cli()
+        .args(["get", "-k", &key, "--quiet"])
+        .assert()
+        .code(3)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn get_raw_binary_network_tests() {
+    if !network_tests_enabled() {
+        return;
+    }
+    let key = test_key("raw");
+    set_from_file(&key, BINARY_VALUE);
+
+    // Exact bytes, with no trailing newline
+    cli()
+        .args(["get", "-k", &key, "--output", "raw"])
+        .assert()
+        .code(0)
+        .stdout(BINARY_VALUE);
+
+    // Exact bytes to the value file, with nothing written to stdout
+    let valuefile = tempfile::NamedTempFile::new().unwrap();
+    cli()
+        .args(["get", "-k", &key, "--output", "raw", "-f"])
+        .arg(valuefile.path())
+        .assert()
+        .code(0)
+        .stdout("");
+    assert_eq!(fs::read(valuefile.path()).unwrap(), BINARY_VALUE);
+
+    cli().args(["delete", "-k", &key]).assert().code(0);
+}
+
+#[test]
+fn get_json_network_tests() {
+    if !network_tests_enabled() {
+        return;
+    }
+    let key = test_key("json");
+
+    let output = cli()
+        .args(["set", "-k", &key, "--value", "multi\nline", "-o", "json"])
+        .assert()
+        .code(0)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["operation"], "set");
+    assert_eq!(envelope["key"], key.as_str());
+    assert_eq!(envelope["success"], true);
+    assert!(envelope["version"].is_string());
+
+    // UTF-8 values are included as text
+    let output = cli()
+        .args(["get", "-k", &key, "-o", "json"])
+        .assert()
+        .code(0)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["operation"], "get");
+    assert_eq!(envelope["found"], true);
+    assert_eq!(envelope["encoding"], "utf8");
+    assert_eq!(envelope["value"], "multi\nline");
+
+    // Binary values are base64 encoded
+    set_from_file(&key, BINARY_VALUE);
+    let output = cli()
+        .args(["get", "-k", &key, "-o", "json"])
+        .assert()
+        .code(0)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["encoding"], "base64");
+    assert_eq!(envelope["value"], BINARY_VALUE_BASE64);
+
+    let output = cli()
+        .args(["delete", "-k", &key, "-o", "json"])
+        .assert()
+        .code(0)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["operation"], "delete");
+    assert_eq!(envelope["deleted"], 1);
+
+    // Not found results are reported in the envelope
+    let output = cli()
+        .args(["get", "-k", &key, "-o", "json"])
+        .assert()
+        .code(3)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["found"], false);
+    let output = cli()
+        .args(["delete", "-k", &key, "-o", "json"])
+        .assert()
+        .code(3)
+        .get_output()
+        .stdout
+        .clone();
+    let envelope: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(envelope["deleted"], 0);
+}
+
+#[test]
+fn exclusive_lock_released_network_tests() {
+    if !network_tests_enabled() {
+        return;
+    }
+    let key = test_key("exclusive");
+    let lock = test_key("exclusive-lock");
+
+    cli()
+        .args(["set", "-k", &key, "--value", "hello", "--exclusive", &lock])
+        .assert()
+        .code(0)
+        .stdout("");
+    // The lock was released when the previous invocation completed
+    cli()
+        .args(["get", "-k", &key, "--exclusive", &lock])
+        .assert()
+        .code(0)
+        .stdout("hello\n");
+    cli()
+        .args(["delete", "-k", &key, "--exclusive", &lock])
+        .assert()
+        .code(0);
+
+    // The lock name must not be empty
+    cli()
+        .args(["get", "-k", &key, "--exclusive", ""])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("exclusive lock"));
+}
@@ -268,6 +268,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(");
@@ -311,6 +312,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }
@@ -369,6 +369,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else if let Some(");
             this.Write(this.ToStringHelper.ToStringWithCulture(this.propValueName.GetVariableName(TargetLanguage.Rust)));
@@ -395,6 +396,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Err(AIOProtocolError {
@@ -415,7 +417,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         command_name: Some(""");
             this.Write(this.ToStringHelper.ToStringWithCulture(this.readCommandName));
             this.Write("\".to_string()),\r\n                        protocol_version: None,\r\n               " +
-                    "         supported_protocol_major_versions: None,\r\n                    })\r\n     " +
+                    "         supported_protocol_major_versions: None,\r\n                        correlation_id: None,\r\n                    })\r\n     " +
                     "           }\r\n            }\r\n            Err(err) => Err(err),\r\n        }\r\n");
  } else { 
             this.Write("    ) -> Result<");
@@ -523,6 +525,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 } else {
                     Ok(Ok(");
@@ -535,6 +538,7 @@ namespace Azure.Iot.Operations.EnvoyGenerator
                         custom_user_data: response.custom_user_data,
                         timestamp: response.timestamp,
                         executor_id: response.executor_id,
+                        timing: response.timing,
                     }))
                 }
             }